@@ -4,9 +4,13 @@ fn main() {
             "src/schema/keys.proto",
             "src/schema/noise.proto",
             "src/schema/webrtc.proto",
+            "src/schema/relay.proto",
             "src/protocol/libp2p/schema/identify.proto",
             "src/protocol/libp2p/schema/kademlia.proto",
             "src/protocol/libp2p/schema/bitswap.proto",
+            "src/protocol/libp2p/schema/autonat.proto",
+            "src/protocol/libp2p/schema/dcutr.proto",
+            "src/protocol/libp2p/schema/pubsub.proto",
         ],
         &["src"],
     )
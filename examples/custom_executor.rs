@@ -29,13 +29,13 @@
 
 use litep2p::{
     config::ConfigBuilder,
-    executor::Executor,
-    protocol::libp2p::ping::{Config as PingConfig, PingEvent},
+    executor::{Executor, TaskHandle},
+    protocol::libp2p::ping::{Config as PingConfig, PingEvent, PingHandle},
     transport::tcp::config::Config as TcpConfig,
     Litep2p,
 };
 
-use futures::{future::BoxFuture, stream::FuturesUnordered, Stream, StreamExt};
+use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 use std::{future::Future, pin::Pin, sync::Arc};
@@ -78,20 +78,23 @@ struct TaskExecutorHandle {
 }
 
 impl Executor for TaskExecutorHandle {
-    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) -> TaskHandle {
+        let (future, handle) = TaskHandle::new(future);
         let _ = self.tx.try_send(future);
+
+        handle
     }
 
-    fn run_with_name(&self, _: &'static str, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
-        let _ = self.tx.try_send(future);
+    fn run_with_name(
+        &self,
+        _: &'static str,
+        future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ) -> TaskHandle {
+        self.run(future)
     }
 }
 
-fn make_litep2p() -> (
-    Litep2p,
-    TaskExecutor,
-    Box<dyn Stream<Item = PingEvent> + Send + Unpin>,
-) {
+fn make_litep2p() -> (Litep2p, TaskExecutor, PingHandle) {
     let (executor, sender) = TaskExecutor::new();
     let (ping_config, ping_event_stream) = PingConfig::default();
 
@@ -24,7 +24,7 @@
 use litep2p::{
     config::ConfigBuilder,
     protocol::{
-        libp2p::ping::{Config as PingConfig, PingEvent},
+        libp2p::ping::{Config as PingConfig, PingEvent, PingHandle},
         mdns::{Config as MdnsConfig, MdnsEvent},
     },
     Litep2p,
@@ -38,7 +38,7 @@ use std::time::Duration;
 /// establishes a connection to them and calculates the PING time
 async fn peer_event_loop(
     mut litep2p: Litep2p,
-    mut ping_event_stream: Box<dyn Stream<Item = PingEvent> + Send + Unpin>,
+    mut ping_event_stream: PingHandle,
     mut mdns_event_stream: Box<dyn Stream<Item = MdnsEvent> + Send + Unpin>,
 ) {
     loop {
@@ -48,6 +48,7 @@ async fn peer_event_loop(
                 PingEvent::Ping { peer, ping } => {
                     println!("ping received from {peer:?}: {ping:?}");
                 }
+                _ => {}
             },
             event = mdns_event_stream.next() => match event.unwrap() {
                 MdnsEvent::Discovered(addresses) => {
@@ -61,7 +62,7 @@ async fn peer_event_loop(
 /// helper function for creating `Litep2p` object
 fn make_litep2p() -> (
     Litep2p,
-    Box<dyn Stream<Item = PingEvent> + Send + Unpin>,
+    PingHandle,
     Box<dyn Stream<Item = MdnsEvent> + Send + Unpin>,
 ) {
     // initialize IPFS ping and mDNS
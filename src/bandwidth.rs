@@ -20,9 +20,19 @@
 
 //! Bandwidth sinks for metering inbound/outbound bytes.
 
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+use crate::{
+    transport::common::metered::{ByteCounters, ConnectionMeter, MeteredStream},
+    PeerId,
+};
+
+use parking_lot::RwLock;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 /// Inner bandwidth sink
@@ -33,6 +43,14 @@ struct InnerBandwidthSink {
 
     /// Number of outbound bytes.
     outbound: AtomicUsize,
+
+    /// Bytes transferred over all connections.
+    connections: Arc<ByteCounters>,
+
+    /// Bytes transferred over the connections of each peer.
+    ///
+    /// Only the peers whose counters are used by at least one connection are tracked.
+    peers: RwLock<HashMap<PeerId, Arc<ByteCounters>>>,
 }
 
 /// Bandwidth sink which provides metering for inbound/outbound byte usage.
@@ -49,6 +67,8 @@ impl BandwidthSink {
         Self(Arc::new(InnerBandwidthSink {
             inbound: AtomicUsize::new(0usize),
             outbound: AtomicUsize::new(0usize),
+            connections: Default::default(),
+            peers: Default::default(),
         }))
     }
 
@@ -71,6 +91,50 @@ impl BandwidthSink {
     pub fn outbound(&self) -> usize {
         self.0.outbound.load(Ordering::Relaxed)
     }
+
+    /// Meter the bytes transferred over the connection I/O `stream` of `peer`.
+    pub(crate) fn meter<S>(&self, peer: PeerId, stream: S) -> MeteredStream<S> {
+        MeteredStream::new(stream, self.connection_meter(peer))
+    }
+
+    /// Get meter for a connection of `peer` which doesn't have a single I/O stream to meter.
+    ///
+    /// The counters of `peer` are kept as long as the meter or any of its clones exist.
+    pub(crate) fn connection_meter(&self, peer: PeerId) -> ConnectionMeter {
+        let mut peers = self.0.peers.write();
+
+        // counters which are only referenced by the map belong to peers whose connections
+        // have all been closed
+        peers.retain(|_, counters| Arc::strong_count(counters) > 1);
+        let counters = Arc::clone(peers.entry(peer).or_default());
+
+        ConnectionMeter::new(counters, Arc::clone(&self.0.connections))
+    }
+
+    /// Stop tracking the counters of `peer` if none of its connections are open.
+    pub(crate) fn release_peer(&self, peer: &PeerId) {
+        let mut peers = self.0.peers.write();
+
+        if peers.get(peer).is_some_and(|counters| Arc::strong_count(counters) == 1) {
+            peers.remove(peer);
+        }
+    }
+
+    /// Get the number of bytes received from and sent to `peer` over its connections.
+    ///
+    /// Unlike [`BandwidthSink::inbound()`] and [`BandwidthSink::outbound()`], which count
+    /// the bytes of substreams, the connection byte counts include the multiplexer overhead.
+    /// WebRTC connections count the datagrams of the connection. QUIC multiplexes substreams
+    /// natively, so only the bytes of the substreams are counted for QUIC connections.
+    /// The counters of `peer` are reset once all of its connections have been closed.
+    pub fn peer_bandwidth(&self, peer: &PeerId) -> (u64, u64) {
+        self.0.peers.read().get(peer).map_or((0u64, 0u64), |counters| counters.get())
+    }
+
+    /// Get the number of bytes received and sent over all connections.
+    pub fn connection_bandwidth(&self) -> (u64, u64) {
+        self.0.connections.get()
+    }
 }
 
 #[cfg(test)]
@@ -87,4 +151,29 @@ mod tests {
         assert_eq!(sink.inbound(), 1337usize);
         assert_eq!(sink.outbound(), 1338usize);
     }
+
+    #[test]
+    fn closed_peers_are_pruned() {
+        let sink = BandwidthSink::new();
+        let peer1 = PeerId::random();
+        let peer2 = PeerId::random();
+
+        let stream1 = sink.meter(peer1, ());
+        let stream2 = sink.meter(peer2, ());
+        assert_eq!(sink.0.peers.read().len(), 2);
+
+        // the counters are kept while a connection of the peer is open
+        sink.release_peer(&peer1);
+        assert_eq!(sink.0.peers.read().len(), 2);
+
+        drop(stream1);
+        sink.release_peer(&peer1);
+        assert!(!sink.0.peers.read().contains_key(&peer1));
+
+        // counters of closed connections are also pruned when a new connection is metered
+        drop(stream2);
+        let _stream3 = sink.meter(peer1, ());
+        assert!(!sink.0.peers.read().contains_key(&peer2));
+        assert_eq!(sink.0.peers.read().len(), 1);
+    }
 }
@@ -50,7 +50,7 @@ impl Decoder for Identity {
     type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if src.is_empty() {
+        if src.len() < self.payload_len {
             return Ok(None);
         }
 
@@ -62,7 +62,7 @@ impl Encoder<Bytes> for Identity {
     type Error = Error;
 
     fn encode(&mut self, item: Bytes, dst: &mut bytes::BytesMut) -> Result<(), Self::Error> {
-        if item.len() > self.payload_len || item.is_empty() {
+        if item.len() != self.payload_len {
             return Err(Error::InvalidData);
         }
 
@@ -85,6 +85,16 @@ mod tests {
         assert_eq!(out_buf.freeze(), bytes);
     }
 
+    #[test]
+    fn encoding_wrong_size_fails() {
+        let mut codec = Identity::new(48);
+        let mut out_buf = BytesMut::with_capacity(64);
+
+        assert!(codec.encode(Bytes::from(vec![0u8; 47]), &mut out_buf).is_err());
+        assert!(codec.encode(Bytes::from(vec![0u8; 49]), &mut out_buf).is_err());
+        assert!(out_buf.is_empty());
+    }
+
     #[test]
     fn decoding_works() {
         let mut codec = Identity::new(64);
@@ -96,6 +106,26 @@ mod tests {
         assert_eq!(decoded, copy);
     }
 
+    #[test]
+    fn decoding_waits_for_full_frame() {
+        let mut codec = Identity::new(8);
+        let payload = (0u8..10).collect::<Vec<_>>();
+        let mut src = BytesMut::new();
+
+        for byte in &payload[..7] {
+            src.put_u8(*byte);
+            assert!(codec.decode(&mut src).unwrap().is_none());
+        }
+
+        src.put_u8(payload[7]);
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), &payload[..8]);
+        assert!(src.is_empty());
+
+        src.put_slice(&payload[8..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert_eq!(src.len(), 2);
+    }
+
     #[test]
     fn empty_encode() {
         let mut codec = Identity::new(32);
@@ -29,6 +29,9 @@ use unsigned_varint::codec::UviBytes;
 /// Unsigned varint codec.
 pub struct UnsignedVarint {
     codec: UviBytes<bytes::Bytes>,
+
+    /// Maximum size of a frame, if specified.
+    max_size: Option<usize>,
 }
 
 impl UnsignedVarint {
@@ -40,7 +43,7 @@ impl UnsignedVarint {
             codec.set_max_len(max_size);
         }
 
-        Self { codec }
+        Self { codec, max_size }
     }
 
     /// Set maximum size for encoded/decodes values.
@@ -48,7 +51,10 @@ impl UnsignedVarint {
         let mut codec = UviBytes::<Bytes>::default();
         codec.set_max_len(max_size);
 
-        Self { codec }
+        Self {
+            codec,
+            max_size: Some(max_size),
+        }
     }
 
     /// Encode `payload` using `unsigned-varint`.
@@ -75,6 +81,15 @@ impl Decoder for UnsignedVarint {
     type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // reject the frame based on the length prefix alone, before any space is reserved for it
+        if let (Some(max_size), Ok((size, _))) =
+            (self.max_size, unsigned_varint::decode::usize(&src[..]))
+        {
+            if size > max_size {
+                return Err(Error::FrameTooLarge(size));
+            }
+        }
+
         self.codec.decode(src).map_err(From::from)
     }
 }
@@ -89,7 +104,7 @@ impl Encoder<Bytes> for UnsignedVarint {
 
 #[cfg(test)]
 mod tests {
-    use super::{Bytes, BytesMut, UnsignedVarint};
+    use super::{Bytes, BytesMut, Error, UnsignedVarint};
 
     #[test]
     fn max_size_respected() {
@@ -138,4 +153,35 @@ mod tests {
 
         assert_eq!(decoded1, decoded2);
     }
+
+    #[test]
+    fn oversized_frame_rejected_before_allocation() {
+        use tokio_util::codec::Decoder;
+
+        let mut codec = UnsignedVarint::with_max_size(1024);
+        let mut buffer = [0u8; 10];
+        let mut src = BytesMut::from(unsigned_varint::encode::usize(1 << 40, &mut buffer));
+        let capacity = src.capacity();
+
+        match codec.decode(&mut src) {
+            Err(Error::FrameTooLarge(size)) => assert_eq!(size, 1 << 40),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert_eq!(src.capacity(), capacity);
+    }
+
+    #[test]
+    fn unbounded_codec_accepts_large_frames() {
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let mut codec = UnsignedVarint::new(None);
+        let bytes_to_encode: Bytes = vec![1u8; 1 << 20].into();
+        let mut out_bytes = BytesMut::new();
+
+        codec.encode(bytes_to_encode.clone(), &mut out_bytes).unwrap();
+        assert_eq!(
+            codec.decode(&mut out_bytes).unwrap().unwrap(),
+            bytes_to_encode
+        );
+    }
 }
@@ -24,22 +24,31 @@ use crate::{
     crypto::ed25519::Keypair,
     executor::{DefaultExecutor, Executor},
     protocol::{
-        libp2p::{bitswap, identify, kademlia, ping},
+        libp2p::{autonat, bitswap, dcutr, identify, kademlia, ping, pubsub, relay},
         mdns::Config as MdnsConfig,
         notification, request_response, UserProtocol,
     },
     transport::{
-        quic::config::Config as QuicConfig, tcp::config::Config as TcpConfig,
-        webrtc::config::Config as WebRtcConfig, websocket::config::Config as WebSocketConfig,
-        MAX_PARALLEL_DIALS,
+        dns::{CachingResolver, DnsResolver},
+        memory::config::Config as MemoryConfig,
+        quic::config::Config as QuicConfig,
+        relay::config::Config as RelayConfig,
+        tcp::config::Config as TcpConfig,
+        webrtc::config::Config as WebRtcConfig,
+        websocket::config::Config as WebSocketConfig,
+        DialBackoffConfig, DNS_CACHE_SIZE, EXTERNAL_ADDRESS_CONFIRMATIONS, MAX_PARALLEL_DIALS,
     },
     types::protocol::ProtocolName,
     PeerId,
 };
 
 use multiaddr::Multiaddr;
+use trust_dns_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    TokioAsyncResolver,
+};
 
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 /// Connection role.
 #[derive(Debug, Copy, Clone)]
@@ -74,21 +83,42 @@ pub struct ConfigBuilder {
     /// WebSocket transport config.
     websocket: Option<WebSocketConfig>,
 
+    /// Memory transport config.
+    memory: Option<MemoryConfig>,
+
+    /// Relay transport config.
+    relay: Option<RelayConfig>,
+
     /// Keypair.
     keypair: Option<Keypair>,
 
+    /// Seed used to derive the keypair if no keypair was specified.
+    keypair_seed: Option<[u8; 32]>,
+
     /// Ping protocol config.
     ping: Option<ping::Config>,
 
     /// Identify protocol config.
     identify: Option<identify::Config>,
 
+    /// AutoNAT protocol config.
+    autonat: Option<autonat::Config>,
+
+    /// DCUtR protocol config.
+    dcutr: Option<dcutr::Config>,
+
     /// Kademlia protocol config.
     kademlia: Option<kademlia::Config>,
 
+    /// Relay server config.
+    relay_server: Option<relay::Config>,
+
     /// Bitswap protocol config.
     bitswap: Option<bitswap::Config>,
 
+    /// Pubsub protocol config.
+    pubsub: Option<pubsub::Config>,
+
     /// Notification protocols.
     notification_protocols: HashMap<ProtocolName, notification::Config>,
 
@@ -109,6 +139,37 @@ pub struct ConfigBuilder {
 
     /// Maximum number of parallel dial attempts.
     max_parallel_dials: usize,
+
+    /// How many distinct peers must observe an address before it's considered external.
+    external_address_confirmations: usize,
+
+    /// Maximum number of established connections.
+    max_connections: Option<usize>,
+
+    /// How long a known address can go unused before it's removed.
+    address_expiry: Option<Duration>,
+
+    /// Score at or below which a failing address is removed.
+    min_address_score: Option<i32>,
+
+    /// How long a dial can take before it's aborted.
+    dial_timeout: Option<Duration>,
+
+    /// Backoff applied to peers after failed dials.
+    dial_backoff: Option<DialBackoffConfig>,
+
+    /// Event channel sizes of protocols.
+    protocol_channel_sizes: HashMap<ProtocolName, usize>,
+
+    /// Maximum number of cached DNS lookups.
+    dns_cache_size: usize,
+
+    /// DNS resolver.
+    dns_resolver: Option<Arc<dyn DnsResolver>>,
+
+    /// Prometheus registry for metrics.
+    #[cfg(feature = "metrics")]
+    metrics_registry: Option<prometheus::Registry>,
 }
 
 impl Default for ConfigBuilder {
@@ -125,14 +186,32 @@ impl ConfigBuilder {
             quic: None,
             webrtc: None,
             websocket: None,
+            memory: None,
+            relay: None,
             keypair: None,
+            keypair_seed: None,
             ping: None,
             identify: None,
+            autonat: None,
+            dcutr: None,
             kademlia: None,
+            relay_server: None,
             bitswap: None,
+            pubsub: None,
             mdns: None,
             executor: None,
             max_parallel_dials: MAX_PARALLEL_DIALS,
+            external_address_confirmations: EXTERNAL_ADDRESS_CONFIRMATIONS,
+            max_connections: None,
+            address_expiry: None,
+            min_address_score: None,
+            dial_timeout: None,
+            dial_backoff: None,
+            protocol_channel_sizes: HashMap::new(),
+            dns_cache_size: DNS_CACHE_SIZE,
+            dns_resolver: None,
+            #[cfg(feature = "metrics")]
+            metrics_registry: None,
             user_protocols: HashMap::new(),
             notification_protocols: HashMap::new(),
             request_response_protocols: HashMap::new(),
@@ -141,7 +220,16 @@ impl ConfigBuilder {
     }
 
     /// Add TCP transport configuration, enabling the transport.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config` doesn't offer any security protocol.
     pub fn with_tcp(mut self, config: TcpConfig) -> Self {
+        assert!(
+            !config.security_protocols.is_empty(),
+            "at least one security protocol must be offered"
+        );
+
         self.tcp = Some(config);
         self
     }
@@ -164,6 +252,24 @@ impl ConfigBuilder {
         self
     }
 
+    /// Add in-process memory transport configuration, enabling the transport.
+    ///
+    /// The memory transport can only connect nodes running in the same process.
+    pub fn with_memory(mut self, config: MemoryConfig) -> Self {
+        self.memory = Some(config);
+        self
+    }
+
+    /// Add circuit relay transport configuration, enabling the transport.
+    ///
+    /// The relay transport dials `/p2p-circuit` addresses through the relays and, if configured,
+    /// makes the node reachable through them. The connections to the relays are opened over the
+    /// other enabled transports.
+    pub fn with_relay(mut self, config: RelayConfig) -> Self {
+        self.relay = Some(config);
+        self
+    }
+
     /// Add keypair.
     ///
     /// If no keypair is specified, litep2p creates a new keypair.
@@ -172,6 +278,15 @@ impl ConfigBuilder {
         self
     }
 
+    /// Derive the keypair deterministically from `seed`.
+    ///
+    /// This allows pinning the `PeerId` of the node, e.g., in tests. Ignored if a keypair was
+    /// specified with [`ConfigBuilder::with_keypair()`].
+    pub fn with_keypair_seed(mut self, seed: [u8; 32]) -> Self {
+        self.keypair_seed = Some(seed);
+        self
+    }
+
     /// Enable notification protocol.
     pub fn with_notification_protocol(mut self, config: notification::Config) -> Self {
         self.notification_protocols.insert(config.protocol_name().clone(), config);
@@ -190,18 +305,50 @@ impl ConfigBuilder {
         self
     }
 
+    /// Enable IPFS AutoNAT protocol.
+    pub fn with_libp2p_autonat(mut self, config: autonat::Config) -> Self {
+        self.autonat = Some(config);
+        self
+    }
+
+    /// Enable IPFS DCUtR protocol.
+    ///
+    /// Peers connected over a relay are upgraded to a direct connection if hole punching succeeds.
+    /// Relayed connections are only opened if the relay transport is enabled, see
+    /// [`ConfigBuilder::with_relay()`].
+    pub fn with_libp2p_dcutr(mut self, config: dcutr::Config) -> Self {
+        self.dcutr = Some(config);
+        self
+    }
+
     /// Enable IPFS Kademlia protocol.
     pub fn with_libp2p_kademlia(mut self, config: kademlia::Config) -> Self {
         self.kademlia = Some(config);
         self
     }
 
+    /// Enable circuit relay v2 server.
+    ///
+    /// The node accepts reservations and relays circuits to the peers holding them within the
+    /// limits given in `config`. The relay server can't be enabled together with the relay
+    /// transport, see [`ConfigBuilder::with_relay()`].
+    pub fn with_libp2p_relay(mut self, config: relay::Config) -> Self {
+        self.relay_server = Some(config);
+        self
+    }
+
     /// Enable IPFS Bitswap protocol.
     pub fn with_libp2p_bitswap(mut self, config: bitswap::Config) -> Self {
         self.bitswap = Some(config);
         self
     }
 
+    /// Enable publish-subscribe protocol.
+    pub fn with_pubsub(mut self, config: pubsub::Config) -> Self {
+        self.pubsub = Some(config);
+        self
+    }
+
     /// Enable request-response protocol.
     pub fn with_request_response_protocol(mut self, config: request_response::Config) -> Self {
         self.request_response_protocols.insert(config.protocol_name().clone(), config);
@@ -243,11 +390,134 @@ impl ConfigBuilder {
         self
     }
 
+    /// How many distinct peers must report the same observed address before litep2p considers
+    /// it an external address of the local node.
+    ///
+    /// Observed addresses are learned through the identify protocol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `confirmations` is zero.
+    pub fn with_external_address_confirmations(mut self, confirmations: usize) -> Self {
+        assert!(confirmations != 0);
+
+        self.external_address_confirmations = confirmations;
+        self
+    }
+
+    /// Set the maximum number of established connections.
+    ///
+    /// When the limit is reached, new dials fail with
+    /// [`Error::ConnectionLimitReached`](crate::Error::ConnectionLimitReached) and inbound
+    /// connections are refused.
+    ///
+    /// By default the number of connections is not limited.
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Set how long a known address can go unused before it's removed.
+    ///
+    /// An address is used when a connection to it is established.
+    ///
+    /// By default addresses don't expire.
+    pub fn with_address_expiry(mut self, address_expiry: Duration) -> Self {
+        self.address_expiry = Some(address_expiry);
+        self
+    }
+
+    /// Set the score at or below which an address is removed after a failed connection attempt.
+    ///
+    /// Each failed connection attempt decreases the score of the address by `100` and each
+    /// established connection increases it by `100`.
+    ///
+    /// By default failing addresses are not removed.
+    pub fn with_min_address_score(mut self, min_address_score: i32) -> Self {
+        self.min_address_score = Some(min_address_score);
+        self
+    }
+
+    /// Set how long a dial can take before it's aborted.
+    ///
+    /// If a dialed connection hasn't been established within `dial_timeout`, the dial is
+    /// canceled and reported as a failure with [`Error::DialTimeout`](crate::Error::DialTimeout).
+    ///
+    /// By default dials are only limited by the timeouts of the transports.
+    pub fn with_dial_timeout(mut self, dial_timeout: Duration) -> Self {
+        self.dial_timeout = Some(dial_timeout);
+        self
+    }
+
+    /// Back off redialing peers after failed dials.
+    ///
+    /// After a failed dial, the peer can't be dialed again until its backoff has elapsed and
+    /// dial attempts fail with [`Error::DialBackoff`](crate::Error::DialBackoff). The backoff
+    /// grows exponentially with consecutive failures and is reset once a connection to the peer
+    /// is established.
+    ///
+    /// By default failed peers can be redialed immediately.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config` is invalid, see [`DialBackoffConfig`].
+    pub fn with_dial_backoff(mut self, config: DialBackoffConfig) -> Self {
+        config.assert_valid();
+        self.dial_backoff = Some(config);
+        self
+    }
+
+    /// Set the size of the channel used to deliver connection events to `protocol`.
+    ///
+    /// Once the channel is full, connections wait for the protocol to read events instead of
+    /// dropping them. Applies to all protocols, including the user protocols. Defaults to 256.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel_size` is zero.
+    pub fn with_protocol_channel_size(
+        mut self,
+        protocol: ProtocolName,
+        channel_size: usize,
+    ) -> Self {
+        assert!(channel_size > 0, "channel size must be non-zero");
+
+        self.protocol_channel_sizes.insert(protocol, channel_size);
+        self
+    }
+
+    /// Set the maximum number of hosts whose resolved addresses are cached.
+    ///
+    /// The cache is shared by all transports and consulted before the DNS resolver is queried.
+    /// Cached addresses expire according to the TTL of the DNS record. Defaults to 32.
+    pub fn with_dns_cache_size(mut self, dns_cache_size: usize) -> Self {
+        self.dns_cache_size = dns_cache_size;
+        self
+    }
+
+    /// Set DNS resolver used to resolve `/dns`, `/dns4` and `/dns6` addresses.
+    ///
+    /// If no resolver is specified, `litep2p` uses the `trust-dns` resolver with the default
+    /// configuration. Either way, the resolved addresses are cached according to
+    /// [`ConfigBuilder::with_dns_cache_size()`].
+    pub fn with_dns_resolver(mut self, dns_resolver: Arc<dyn DnsResolver>) -> Self {
+        self.dns_resolver = Some(dns_resolver);
+        self
+    }
+
+    /// Register Prometheus metrics of `litep2p` to `registry`.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics_registry(mut self, registry: prometheus::Registry) -> Self {
+        self.metrics_registry = Some(registry);
+        self
+    }
+
     /// Build [`Litep2pConfig`].
     pub fn build(mut self) -> Litep2pConfig {
-        let keypair = match self.keypair {
-            Some(keypair) => keypair,
-            None => Keypair::generate(),
+        let keypair = match (self.keypair, self.keypair_seed) {
+            (Some(keypair), _) => keypair,
+            (None, Some(seed)) => Keypair::from_seed(&seed),
+            (None, None) => Keypair::generate(),
         };
 
         Litep2pConfig {
@@ -257,16 +527,40 @@ impl ConfigBuilder {
             quic: self.quic.take(),
             webrtc: self.webrtc.take(),
             websocket: self.websocket.take(),
+            memory: self.memory.take(),
+            relay: self.relay.take(),
             ping: self.ping.take(),
             identify: self.identify.take(),
+            autonat: self.autonat.take(),
+            dcutr: self.dcutr.take(),
             kademlia: self.kademlia.take(),
+            relay_server: self.relay_server.take(),
             bitswap: self.bitswap.take(),
+            pubsub: self.pubsub.take(),
             max_parallel_dials: self.max_parallel_dials,
+            external_address_confirmations: self.external_address_confirmations,
+            max_connections: self.max_connections,
+            address_expiry: self.address_expiry,
+            min_address_score: self.min_address_score,
+            dial_timeout: self.dial_timeout,
+            dial_backoff: self.dial_backoff,
+            protocol_channel_sizes: self.protocol_channel_sizes,
+            dns_resolver: Arc::new(CachingResolver::new(
+                self.dns_resolver.unwrap_or_else(|| {
+                    Arc::new(TokioAsyncResolver::tokio(
+                        ResolverConfig::default(),
+                        ResolverOpts::default(),
+                    ))
+                }),
+                self.dns_cache_size,
+            )),
             executor: self.executor.map_or(Arc::new(DefaultExecutor {}), |executor| executor),
             user_protocols: self.user_protocols,
             notification_protocols: self.notification_protocols,
             request_response_protocols: self.request_response_protocols,
             known_addresses: self.known_addresses,
+            #[cfg(feature = "metrics")]
+            metrics_registry: self.metrics_registry,
         }
     }
 }
@@ -285,6 +579,12 @@ pub struct Litep2pConfig {
     /// WebSocket transport config.
     pub(crate) websocket: Option<WebSocketConfig>,
 
+    /// Memory transport config.
+    pub(crate) memory: Option<MemoryConfig>,
+
+    /// Relay transport config.
+    pub(crate) relay: Option<RelayConfig>,
+
     /// Keypair.
     pub(crate) keypair: Keypair,
 
@@ -294,12 +594,24 @@ pub struct Litep2pConfig {
     /// Identify protocol configuration, if enabled.
     pub(crate) identify: Option<identify::Config>,
 
+    /// AutoNAT protocol configuration, if enabled.
+    pub(crate) autonat: Option<autonat::Config>,
+
+    /// DCUtR protocol configuration, if enabled.
+    pub(crate) dcutr: Option<dcutr::Config>,
+
     /// Kademlia protocol configuration, if enabled.
     pub(crate) kademlia: Option<kademlia::Config>,
 
+    /// Relay server configuration, if enabled.
+    pub(crate) relay_server: Option<relay::Config>,
+
     /// Bitswap protocol configuration, if enabled.
     pub(crate) bitswap: Option<bitswap::Config>,
 
+    /// Pubsub protocol configuration, if enabled.
+    pub(crate) pubsub: Option<pubsub::Config>,
+
     /// Notification protocols.
     pub(crate) notification_protocols: HashMap<ProtocolName, notification::Config>,
 
@@ -318,6 +630,34 @@ pub struct Litep2pConfig {
     /// Maximum number of parallel dial attempts.
     pub(crate) max_parallel_dials: usize,
 
+    /// How many distinct peers must observe an address before it's considered external.
+    pub(crate) external_address_confirmations: usize,
+
+    /// Maximum number of established connections.
+    pub(crate) max_connections: Option<usize>,
+
+    /// How long a known address can go unused before it's removed.
+    pub(crate) address_expiry: Option<Duration>,
+
+    /// Score at or below which a failing address is removed.
+    pub(crate) min_address_score: Option<i32>,
+
+    /// How long a dial can take before it's aborted.
+    pub(crate) dial_timeout: Option<Duration>,
+
+    /// Backoff applied to peers after failed dials.
+    pub(crate) dial_backoff: Option<DialBackoffConfig>,
+
+    /// Event channel sizes of protocols.
+    pub(crate) protocol_channel_sizes: HashMap<ProtocolName, usize>,
+
+    /// DNS resolver.
+    pub(crate) dns_resolver: Arc<dyn DnsResolver>,
+
     /// Known addresses.
     pub(crate) known_addresses: Vec<(PeerId, Vec<Multiaddr>)>,
+
+    /// Prometheus registry for metrics.
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics_registry: Option<prometheus::Registry>,
 }
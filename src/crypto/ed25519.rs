@@ -38,6 +38,21 @@ impl Keypair {
         Keypair::from(SecretKey::generate())
     }
 
+    /// Derive an Ed25519 keypair deterministically from a 32-byte seed.
+    ///
+    /// The same seed always produces the same keypair and thus the same `PeerId`, which makes
+    /// this useful for reproducible test networks. Don't use it with low-entropy seeds outside
+    /// of tests.
+    pub fn from_seed(seed: &[u8; 32]) -> Keypair {
+        let mut bytes = *seed;
+
+        Keypair::from(
+            SecretKey::from_bytes(&mut bytes).expect(
+                "this returns `Err` only if the length is wrong; the length is correct; qed",
+            ),
+        )
+    }
+
     /// Encode the keypair into a byte array by concatenating the bytes
     /// of the secret scalar and the compressed public point,
     /// an informal standard for encoding Ed25519 keypairs.
@@ -257,6 +272,23 @@ mod tests {
         assert!(!pk.verify(invalid_msg, &sig));
     }
 
+    #[test]
+    fn keypair_from_seed_is_deterministic() {
+        let kp1 = Keypair::from_seed(&[1u8; 32]);
+        let kp2 = Keypair::from_seed(&[2u8; 32]);
+
+        assert!(eq_keypairs(&kp1, &Keypair::from_seed(&[1u8; 32])));
+        assert!(!eq_keypairs(&kp1, &kp2));
+        assert_eq!(
+            kp1.public().to_peer_id().to_string(),
+            "12D3KooWK99VoVxNE7XzyBwXEzW7xhK7Gpv85r9F3V3fyKSUKPH5",
+        );
+        assert_eq!(
+            kp2.public().to_peer_id().to_string(),
+            "12D3KooWJWoaqZhDaoEFshF7Rh1bpY9ohihFhzcW6d69Lr2NASuq",
+        );
+    }
+
     #[test]
     fn secret_key() {
         let _ = tracing_subscriber::fmt()
@@ -21,7 +21,16 @@
 
 //! Crypto-related code.
 
-use crate::{error::*, peer_id::*};
+use crate::{crypto::noise::NoiseSocket, error::*, peer_id::*};
+
+use futures::{AsyncRead, AsyncWrite};
+use futures_rustls::TlsStream;
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 pub mod ed25519;
 pub(crate) mod noise;
@@ -113,3 +122,72 @@ impl From<ed25519::PublicKey> for PublicKey {
         PublicKey::Ed25519(public_key)
     }
 }
+
+/// Security protocol used to authenticate and encrypt a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityProtocol {
+    /// Noise, see <https://github.com/libp2p/specs/tree/master/noise>.
+    Noise,
+
+    /// TLS 1.3, see <https://github.com/libp2p/specs/blob/master/tls/tls.md>.
+    Tls,
+}
+
+impl SecurityProtocol {
+    /// Get the `multistream-select` protocol name of the security protocol.
+    pub fn protocol_name(&self) -> &'static str {
+        match self {
+            Self::Noise => noise::PROTOCOL_NAME,
+            Self::Tls => tls::PROTOCOL_NAME,
+        }
+    }
+}
+
+/// Stream secured with one of the supported security protocols.
+pub(crate) enum SecureStream<S: AsyncRead + AsyncWrite + Unpin> {
+    /// Stream secured with Noise.
+    Noise(Box<NoiseSocket<S>>),
+
+    /// Stream secured with TLS.
+    Tls(Box<TlsStream<S>>),
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for SecureStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Noise(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for SecureStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Noise(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Noise(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Noise(stream) => Pin::new(stream).poll_close(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_close(cx),
+        }
+    }
+}
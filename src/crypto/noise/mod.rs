@@ -45,6 +45,9 @@ mod handshake_schema {
     include!(concat!(env!("OUT_DIR"), "/noise.rs"));
 }
 
+/// Noise protocol name.
+pub(crate) const PROTOCOL_NAME: &str = "/noise";
+
 /// Noise parameters.
 const NOISE_PARAMETERS: &str = "Noise_XX_25519_ChaChaPoly_SHA256";
 
@@ -98,18 +101,26 @@ impl fmt::Debug for NoiseContext {
 
 impl NoiseContext {
     /// Assemble Noise payload and return [`NoiseContext`].
+    ///
+    /// `protocols` are advertised to the remote in the extensions of the payload. They're
+    /// application protocols and not stream muxers, so `stream_muxers` is left empty and the
+    /// muxer is still negotiated over multistream-select.
     fn assemble(
         noise: snow::HandshakeState,
         keypair: snow::Keypair,
         id_keys: &Keypair,
         role: Role,
+        protocols: &[&str],
     ) -> crate::Result<Self> {
         let noise_payload = handshake_schema::NoiseHandshakePayload {
             identity_key: Some(PublicKey::Ed25519(id_keys.public()).to_protobuf_encoding()),
             identity_sig: Some(
                 id_keys.sign(&[STATIC_KEY_DOMAIN.as_bytes(), keypair.public.as_ref()].concat()),
             ),
-            ..Default::default()
+            extensions: (!protocols.is_empty()).then(|| handshake_schema::NoiseExtensions {
+                protocols: protocols.iter().map(|protocol| protocol.to_string()).collect(),
+                ..Default::default()
+            }),
         };
 
         let mut payload = Vec::with_capacity(noise_payload.encoded_len());
@@ -123,7 +134,7 @@ impl NoiseContext {
         })
     }
 
-    pub fn new(keypair: &Keypair, role: Role) -> crate::Result<Self> {
+    pub fn new(keypair: &Keypair, role: Role, protocols: &[&str]) -> crate::Result<Self> {
         tracing::trace!(target: LOG_TARGET, ?role, "create new noise configuration");
 
         let builder: Builder<'_> = Builder::with_resolver(
@@ -139,7 +150,7 @@ impl NoiseContext {
             Role::Listener => builder.local_private_key(static_key).build_responder()?,
         };
 
-        Self::assemble(noise, dh_keypair, keypair, role)
+        Self::assemble(noise, dh_keypair, keypair, role, protocols)
     }
 
     /// Create new [`NoiseContext`] with prologue.
@@ -156,7 +167,7 @@ impl NoiseContext {
             .prologue(&prologue)
             .build_initiator()?;
 
-        Self::assemble(noise, keypair, id_keys, Role::Dialer)
+        Self::assemble(noise, keypair, id_keys, Role::Dialer, &[])
     }
 
     /// Get remote public key from the received Noise payload.
@@ -263,12 +274,24 @@ impl NoiseContext {
             ));
         };
 
-        let nread = noise.read_message(&message, &mut out)?;
+        let nread = noise.read_message(&message, &mut out).map_err(|error| match error {
+            snow::Error::Decrypt =>
+                error::Error::HandshakeFailed(error::HandshakeError::DecryptionFailure),
+            error => error.into(),
+        })?;
         out.truncate(nread);
 
         Ok(out.freeze())
     }
 
+    /// Get the Noise static key of the remote, if it has been received.
+    fn remote_static_key(&self) -> Option<&[u8]> {
+        match self.noise {
+            NoiseState::Handshake(ref noise) => noise.get_remote_static(),
+            NoiseState::Transport(ref noise) => noise.get_remote_static(),
+        }
+    }
+
     fn read_message(&mut self, message: &[u8], out: &mut [u8]) -> Result<usize, snow::Error> {
         match self.noise {
             NoiseState::Handshake(ref mut noise) => noise.read_message(message, out),
@@ -661,31 +684,59 @@ impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for NoiseSocket<S> {
     }
 }
 
-/// Try to parse `PeerId` from received `NoiseHandshakePayload`
-fn parse_peer_id(buf: &[u8]) -> crate::Result<PeerId> {
-    match handshake_schema::NoiseHandshakePayload::decode(buf) {
-        Ok(payload) => {
-            let public_key = PublicKey::from_protobuf_encoding(&payload.identity_key.ok_or(
-                error::Error::NegotiationError(error::NegotiationError::PeerIdMissing),
-            )?)?;
-            Ok(PeerId::from_public_key(&public_key))
-        }
-        Err(err) => Err(From::from(err)),
+/// Parse received `NoiseHandshakePayload` and verify the identity signature over the Noise
+/// static key of the remote.
+///
+/// Returns the `PeerId` of the remote and the protocols it advertised in the payload.
+fn parse_payload(
+    buf: &[u8],
+    remote_static_key: Option<&[u8]>,
+) -> crate::Result<(PeerId, Vec<String>)> {
+    let payload = handshake_schema::NoiseHandshakePayload::decode(buf)?;
+    let public_key = PublicKey::from_protobuf_encoding(&payload.identity_key.ok_or(
+        error::Error::NegotiationError(error::NegotiationError::PeerIdMissing),
+    )?)?;
+
+    let (Some(signature), Some(remote_static_key)) = (payload.identity_sig, remote_static_key)
+    else {
+        return Err(error::Error::HandshakeFailed(
+            error::HandshakeError::SignatureMismatch,
+        ));
+    };
+
+    if !public_key.verify(
+        &[STATIC_KEY_DOMAIN.as_bytes(), remote_static_key].concat(),
+        &signature,
+    ) {
+        return Err(error::Error::HandshakeFailed(
+            error::HandshakeError::SignatureMismatch,
+        ));
     }
+
+    let protocols = payload.extensions.map(|extensions| extensions.protocols).unwrap_or_default();
+
+    Ok((PeerId::from_public_key(&public_key), protocols))
 }
 
 /// Perform Noise handshake.
+///
+/// If `dialed_peer` is set, the handshake fails unless the remote authenticates as that peer.
+///
+/// `protocols` are advertised to the remote in the handshake payload. Returns the socket, the
+/// verified `PeerId` of the remote and the protocols the remote advertised in its payload.
 pub async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
     mut io: S,
     keypair: &Keypair,
     role: Role,
+    dialed_peer: Option<PeerId>,
+    protocols: &[&str],
     max_read_ahead_factor: usize,
     max_write_buffer_size: usize,
-) -> crate::Result<(NoiseSocket<S>, PeerId)> {
+) -> crate::Result<(NoiseSocket<S>, PeerId, Vec<String>)> {
     tracing::debug!(target: LOG_TARGET, ?role, "start noise handshake");
 
-    let mut noise = NoiseContext::new(keypair, role)?;
-    let peer = match role {
+    let mut noise = NoiseContext::new(keypair, role, protocols)?;
+    let (peer, remote_protocols) = match role {
         Role::Dialer => {
             // write initial message
             let first_message = noise.first_message(Role::Dialer)?;
@@ -694,13 +745,22 @@ pub async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
 
             // read back response which contains the remote peer id
             let message = noise.read_handshake_message(&mut io).await?;
+            let (peer, remote_protocols) = parse_payload(&message, noise.remote_static_key())?;
+
+            if let Some(dialed_peer) = dialed_peer {
+                if dialed_peer != peer {
+                    return Err(error::Error::HandshakeFailed(
+                        error::HandshakeError::PeerIdMismatch(dialed_peer, peer),
+                    ));
+                }
+            }
 
             // send the final message which contains local peer id
             let second_message = noise.second_message()?;
             let _ = io.write(&second_message).await?;
             io.flush().await?;
 
-            parse_peer_id(&message)?
+            (peer, remote_protocols)
         }
         Role::Listener => {
             // read remote's first message
@@ -713,7 +773,7 @@ pub async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
 
             // read remote's second message which contains their peer id
             let message = noise.read_handshake_message(&mut io).await?;
-            parse_peer_id(&message)?
+            parse_payload(&message, noise.remote_static_key())?
         }
     };
 
@@ -725,6 +785,7 @@ pub async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
             max_write_buffer_size,
         ),
         peer,
+        remote_protocols,
     ))
 }
 
@@ -768,6 +829,8 @@ mod tests {
                 io1,
                 &keypair1,
                 Role::Dialer,
+                None,
+                &["/ipfs/ping/1.0.0"],
                 MAX_READ_AHEAD_FACTOR,
                 MAX_WRITE_BUFFER_SIZE
             ),
@@ -775,6 +838,8 @@ mod tests {
                 io2,
                 &keypair2,
                 Role::Listener,
+                None,
+                &["/ipfs/ping/1.0.0"],
                 MAX_READ_AHEAD_FACTOR,
                 MAX_WRITE_BUFFER_SIZE
             )
@@ -792,9 +857,182 @@ mod tests {
         assert_eq!(std::str::from_utf8(&buf[..sent]), Ok("hello, world"));
     }
 
+    #[tokio::test]
+    async fn handshake_yields_remote_protocols() {
+        let (io1, io2) = tokio::io::duplex(64 * 1024);
+        let keypair1 = Keypair::generate();
+        let keypair2 = Keypair::generate();
+
+        let (res1, res2) = tokio::join!(
+            handshake(
+                io1.compat(),
+                &keypair1,
+                Role::Dialer,
+                None,
+                &["/ipfs/ping/1.0.0"],
+                MAX_READ_AHEAD_FACTOR,
+                MAX_WRITE_BUFFER_SIZE
+            ),
+            handshake(
+                io2.compat(),
+                &keypair2,
+                Role::Listener,
+                None,
+                &["/ipfs/ping/1.0.0", "/ipfs/id/1.0.0"],
+                MAX_READ_AHEAD_FACTOR,
+                MAX_WRITE_BUFFER_SIZE
+            )
+        );
+        let ((_, peer2, protocols2), (_, peer1, protocols1)) = (res1.unwrap(), res2.unwrap());
+
+        assert_eq!(peer1, PeerId::from_public_key(&keypair1.public().into()));
+        assert_eq!(peer2, PeerId::from_public_key(&keypair2.public().into()));
+        assert_eq!(protocols1, vec!["/ipfs/ping/1.0.0".to_string()]);
+        assert_eq!(
+            protocols2,
+            vec!["/ipfs/ping/1.0.0".to_string(), "/ipfs/id/1.0.0".to_string()]
+        );
+    }
+
+    #[test]
+    fn protocols_not_advertised_as_stream_muxers() {
+        let context =
+            NoiseContext::new(&Keypair::generate(), Role::Dialer, &["/ipfs/ping/1.0.0"]).unwrap();
+        let payload =
+            handshake_schema::NoiseHandshakePayload::decode(context.payload.as_slice()).unwrap();
+        let extensions = payload.extensions.unwrap();
+
+        assert!(extensions.stream_muxers.is_empty());
+        assert_eq!(extensions.protocols, vec!["/ipfs/ping/1.0.0".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn invalid_identity_signature_rejected() {
+        let (io1, io2) = tokio::io::duplex(64 * 1024);
+        let (keypair1, keypair2) = (Keypair::generate(), Keypair::generate());
+
+        let listener = async move {
+            let mut io2 = io2.compat();
+            let mut noise = NoiseContext::new(&keypair2, Role::Listener, &[]).unwrap();
+
+            // sign some other key than the actual Noise static key
+            let payload = handshake_schema::NoiseHandshakePayload {
+                identity_key: Some(PublicKey::Ed25519(keypair2.public()).to_protobuf_encoding()),
+                identity_sig: Some(
+                    keypair2.sign(&[STATIC_KEY_DOMAIN.as_bytes(), &[0u8; 32]].concat()),
+                ),
+                ..Default::default()
+            };
+            noise.payload = payload.encode_to_vec();
+
+            let _ = noise.read_handshake_message(&mut io2).await.unwrap();
+            io2.write_all(&noise.second_message().unwrap()).await.unwrap();
+            io2.flush().await.unwrap();
+            io2
+        };
+
+        let (res, _io2) = tokio::join!(
+            handshake(
+                io1.compat(),
+                &keypair1,
+                Role::Dialer,
+                None,
+                &[],
+                MAX_READ_AHEAD_FACTOR,
+                MAX_WRITE_BUFFER_SIZE
+            ),
+            listener,
+        );
+
+        match res {
+            Err(crate::Error::HandshakeFailed(error::HandshakeError::SignatureMismatch)) => {}
+            Err(error) => panic!("invalid error: {error:?}"),
+            Ok(_) => panic!("handshake was supposed to fail"),
+        }
+    }
+
+    #[tokio::test]
+    async fn tampered_handshake_message_rejected() {
+        let (io1, io2) = tokio::io::duplex(64 * 1024);
+        let (keypair1, keypair2) = (Keypair::generate(), Keypair::generate());
+
+        let listener = async move {
+            let mut io2 = io2.compat();
+            let mut noise = NoiseContext::new(&keypair2, Role::Listener, &[]).unwrap();
+            let _ = noise.read_handshake_message(&mut io2).await.unwrap();
+
+            // flip a bit in the authentication tag of the encrypted payload
+            let mut message = noise.second_message().unwrap();
+            *message.last_mut().unwrap() ^= 1;
+
+            io2.write_all(&message).await.unwrap();
+            io2.flush().await.unwrap();
+            io2
+        };
+
+        let (res, _io2) = tokio::join!(
+            handshake(
+                io1.compat(),
+                &keypair1,
+                Role::Dialer,
+                None,
+                &[],
+                MAX_READ_AHEAD_FACTOR,
+                MAX_WRITE_BUFFER_SIZE
+            ),
+            listener,
+        );
+
+        match res {
+            Err(crate::Error::HandshakeFailed(error::HandshakeError::DecryptionFailure)) => {}
+            Err(error) => panic!("invalid error: {error:?}"),
+            Ok(_) => panic!("handshake was supposed to fail"),
+        }
+    }
+
+    #[tokio::test]
+    async fn dialed_peer_mismatch_rejected() {
+        let (io1, io2) = tokio::io::duplex(64 * 1024);
+        let (keypair1, keypair2) = (Keypair::generate(), Keypair::generate());
+        let dialed_peer = PeerId::random();
+
+        let (res, _) = tokio::join!(
+            handshake(
+                io1.compat(),
+                &keypair1,
+                Role::Dialer,
+                Some(dialed_peer),
+                &[],
+                MAX_READ_AHEAD_FACTOR,
+                MAX_WRITE_BUFFER_SIZE
+            ),
+            handshake(
+                io2.compat(),
+                &keypair2,
+                Role::Listener,
+                None,
+                &[],
+                MAX_READ_AHEAD_FACTOR,
+                MAX_WRITE_BUFFER_SIZE
+            ),
+        );
+
+        match res {
+            Err(crate::Error::HandshakeFailed(error::HandshakeError::PeerIdMismatch(
+                expected,
+                actual,
+            ))) => {
+                assert_eq!(expected, dialed_peer);
+                assert_eq!(actual, PeerId::from_public_key(&keypair2.public().into()));
+            }
+            Err(error) => panic!("invalid error: {error:?}"),
+            Ok(_) => panic!("handshake was supposed to fail"),
+        }
+    }
+
     #[test]
     fn invalid_peer_id_schema() {
-        match parse_peer_id(&vec![1, 2, 3, 4]).unwrap_err() {
+        match parse_payload(&vec![1, 2, 3, 4], None).unwrap_err() {
             crate::Error::ParseError(_) => {}
             _ => panic!("invalid error"),
         }
@@ -25,13 +25,22 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
-use crate::{crypto::ed25519::Keypair, PeerId};
+use crate::{config::Role, crypto::ed25519::Keypair, error::Error, PeerId};
+
+use futures::{AsyncRead, AsyncWrite};
+use futures_rustls::{TlsAcceptor, TlsConnector, TlsStream};
 
 use std::sync::Arc;
 
 pub mod certificate;
 mod verifier;
 
+/// TLS protocol name.
+pub(crate) const PROTOCOL_NAME: &str = "/tls/1.0.0";
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::crypto::tls";
+
 const P2P_ALPN: [u8; 6] = *b"libp2p";
 
 /// Create a TLS server configuration for litep2p.
@@ -74,3 +83,71 @@ pub fn make_client_config(
 
     Ok(crypto)
 }
+
+/// Perform TLS handshake over `io`.
+///
+/// The peer ID of the remote is extracted from the libp2p extension of the certificate the
+/// remote presented, after the certificate has been verified.
+pub async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    io: S,
+    keypair: &Keypair,
+    role: Role,
+) -> crate::Result<(TlsStream<S>, PeerId)> {
+    tracing::debug!(target: LOG_TARGET, ?role, "start tls handshake");
+
+    let stream = match role {
+        Role::Dialer => {
+            let config = make_client_config(keypair, None)?;
+            let server_name = rustls::ServerName::try_from("l").expect("valid server name");
+
+            TlsStream::Client(TlsConnector::from(Arc::new(config)).connect(server_name, io).await?)
+        }
+        Role::Listener => {
+            let config = make_server_config(keypair)?;
+
+            TlsStream::Server(TlsAcceptor::from(Arc::new(config)).accept(io).await?)
+        }
+    };
+
+    let certificate = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|certificates| certificates.first())
+        .ok_or(Error::InvalidCertificate)?;
+    let peer = certificate::parse(certificate)
+        .map_err(|_| Error::InvalidCertificate)?
+        .peer_id();
+
+    tracing::trace!(target: LOG_TARGET, ?peer, "tls handshake done");
+
+    Ok((stream, peer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::compat::TokioAsyncReadCompatExt;
+
+    #[tokio::test]
+    async fn handshake_verifies_peer_ids() {
+        let (dialer_io, listener_io) = tokio::io::duplex(64 * 1024);
+        let dialer_keypair = Keypair::generate();
+        let listener_keypair = Keypair::generate();
+
+        let (dialer, listener) = tokio::join!(
+            handshake(dialer_io.compat(), &dialer_keypair, Role::Dialer),
+            handshake(listener_io.compat(), &listener_keypair, Role::Listener),
+        );
+        let ((_, dialer_peer), (_, listener_peer)) = (dialer.unwrap(), listener.unwrap());
+
+        assert_eq!(
+            dialer_peer,
+            PeerId::from_public_key(&listener_keypair.public().into())
+        );
+        assert_eq!(
+            listener_peer,
+            PeerId::from_public_key(&dialer_keypair.public().into())
+        );
+    }
+}
@@ -35,7 +35,10 @@ use crate::{
 use multiaddr::Multiaddr;
 use multihash::{Multihash, MultihashGeneric};
 
-use std::io::{self, ErrorKind};
+use std::{
+    io::{self, ErrorKind},
+    time::Duration,
+};
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, thiserror::Error)]
@@ -86,6 +89,8 @@ pub enum Error {
     CertificateGeneration(#[from] crate::crypto::tls::certificate::GenError),
     #[error("Invalid data")]
     InvalidData,
+    #[error("Frame is too large: `{0}` bytes")]
+    FrameTooLarge(usize),
     #[error("Input rejected")]
     InputRejected,
     #[error("WebSocket error: `{0}`")]
@@ -118,6 +123,27 @@ pub enum Error {
     ChannelClogged,
     #[error("Connection doesn't exist: `{0:?}`")]
     ConnectionDoesntExist(ConnectionId),
+    #[error("Connection limit reached")]
+    ConnectionLimitReached,
+    #[error("Peer `{0}` is banned")]
+    PeerBanned(PeerId),
+    #[error("Dial aborted")]
+    DialAborted,
+    #[error("Dial timed out")]
+    DialTimeout,
+    #[error("Dial backoff in effect, retry after `{retry_after:?}`")]
+    DialBackoff { retry_after: Duration },
+    #[error("Protocol negotiation failed, attempted protocols: `{protocols:?}`")]
+    ProtocolNegotiationFailed { protocols: Vec<String> },
+    #[error("Handshake failed: `{0}`")]
+    HandshakeFailed(HandshakeError),
+    #[error("Relay error: `{0}`")]
+    RelayError(RelayError),
+    #[error("Dial error: `{0}`")]
+    Dial(DialError),
+    #[cfg(feature = "metrics")]
+    #[error("Prometheus error: `{0}`")]
+    Prometheus(#[from] prometheus::Error),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -126,8 +152,12 @@ pub enum AddressError {
     InvalidProtocol,
     #[error("`PeerId` missing from the address")]
     PeerIdMissing,
+    #[error("Invalid `PeerId` in the address")]
+    InvalidPeerId,
     #[error("Address not available")]
     AddressNotAvailable,
+    #[error("Invalid IP range: `{0}`")]
+    InvalidIpRange(String),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -150,6 +180,8 @@ pub enum SubstreamError {
     ReadFailure(Option<SubstreamId>),
     #[error("Failed to write to substream, substream id `{0:?}`")]
     WriteFailure(Option<SubstreamId>),
+    #[error("Substream operation timed out")]
+    Timeout,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -164,6 +196,34 @@ pub enum NegotiationError {
     PeerIdMissing,
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum HandshakeError {
+    #[error("Identity signature doesn't match the static key of the remote")]
+    SignatureMismatch,
+    #[error("Peer ID mismatch: expected `{0}`, got `{1}`")]
+    PeerIdMismatch(PeerId, PeerId),
+    #[error("Failed to decrypt handshake message")]
+    DecryptionFailure,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RelayError {
+    #[error("Relay refused the reservation")]
+    ReservationRefused,
+    #[error("Relay resource limit exceeded")]
+    ResourceLimitExceeded,
+    #[error("Permission denied by the relay")]
+    PermissionDenied,
+    #[error("Relay failed to connect to the destination")]
+    ConnectionFailed,
+    #[error("Destination has no reservation with the relay")]
+    NoReservation,
+    #[error("Malformed relay message")]
+    MalformedMessage,
+    #[error("Unexpected relay message")]
+    UnexpectedMessage,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum NotificationError {
     #[error("Peer already exists")]
@@ -184,6 +244,32 @@ pub enum DialError {
     AlreadyConnected,
     #[error("Peer doens't have any known addresses")]
     NoAddressAvailable(PeerId),
+    #[error("Connection refused")]
+    Refused,
+    #[error("Dial timed out")]
+    Timeout,
+    #[error("Security handshake failed: `{0}`")]
+    HandshakeFailed(HandshakeError),
+    #[error("Transport not supported: `{0}`")]
+    UnsupportedTransport(Multiaddr),
+    #[error("I/O error: `{0}`")]
+    IoError(ErrorKind),
+}
+
+impl Error {
+    /// Convert an error returned while dialing a remote peer into [`Error::Dial`].
+    ///
+    /// Errors which don't map to any [`DialError`] category are returned as-is.
+    pub(crate) fn into_dial_error(self) -> Error {
+        match self {
+            Error::IoError(kind) => Error::Dial(kind.into()),
+            Error::Timeout => Error::Dial(DialError::Timeout),
+            Error::HandshakeFailed(error) => Error::Dial(DialError::HandshakeFailed(error)),
+            Error::TransportNotSupported(address) =>
+                Error::Dial(DialError::UnsupportedTransport(address)),
+            error => error,
+        }
+    }
 }
 
 impl From<MultihashGeneric<64>> for Error {
@@ -198,6 +284,22 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<ErrorKind> for DialError {
+    fn from(kind: ErrorKind) -> DialError {
+        match kind {
+            ErrorKind::ConnectionRefused => DialError::Refused,
+            ErrorKind::TimedOut => DialError::Timeout,
+            kind => DialError::IoError(kind),
+        }
+    }
+}
+
+impl From<DialError> for Error {
+    fn from(error: DialError) -> Error {
+        Error::Dial(error)
+    }
+}
+
 impl From<crate::multistream_select::NegotiationError> for Error {
     fn from(error: crate::multistream_select::NegotiationError) -> Error {
         Error::NegotiationError(NegotiationError::MultistreamSelectError(error))
@@ -274,4 +376,23 @@ mod tests {
             _ => panic!("invalid error"),
         }
     }
+
+    #[test]
+    fn connection_refused_maps_to_dial_error() {
+        let error = Error::from(io::Error::from(ErrorKind::ConnectionRefused));
+
+        match error.into_dial_error() {
+            Error::Dial(DialError::Refused) => {}
+            error => panic!("invalid error: {error:?}"),
+        }
+
+        assert!(std::matches!(
+            DialError::from(ErrorKind::TimedOut),
+            DialError::Timeout
+        ));
+        assert!(std::matches!(
+            DialError::from(ErrorKind::AddrNotAvailable),
+            DialError::IoError(ErrorKind::AddrNotAvailable)
+        ));
+    }
 }
@@ -20,27 +20,178 @@
 
 //! Behavior defining how futures running in the background should be executed.
 
-use std::{future::Future, pin::Pin};
+use futures::future::{AbortHandle, Abortable};
+use tokio::{sync::oneshot, task::JoinHandle};
+use tracing::Instrument;
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::executor";
 
 /// Trait which defines the interface the executor must implement.
 pub trait Executor: Send + Sync {
     /// Start executing a future in the background.
-    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>);
+    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) -> TaskHandle;
 
     /// Start executing a future in the background and give the future a name;
-    fn run_with_name(&self, name: &'static str, future: Pin<Box<dyn Future<Output = ()> + Send>>);
+    fn run_with_name(
+        &self,
+        name: &'static str,
+        future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ) -> TaskHandle;
+}
+
+/// Handle to a future started by an [`Executor`].
+///
+/// The handle resolves once the future has exited, either by completing or by being aborted.
+/// Dropping the handle doesn't stop the future.
+pub struct TaskHandle {
+    inner: TaskHandleInner,
+}
+
+enum TaskHandleInner {
+    /// Future spawned with `tokio::spawn()`.
+    Tokio(JoinHandle<()>),
+
+    /// Future wrapped with [`TaskHandle::new()`].
+    Abortable {
+        /// Abort handle of the future.
+        abort_handle: AbortHandle,
+
+        /// Receiver which is notified when the future exits.
+        exit_rx: oneshot::Receiver<()>,
+    },
+}
+
+impl TaskHandle {
+    /// Wrap `future` so that it can be awaited and aborted through the returned [`TaskHandle`].
+    ///
+    /// Allows custom executors to return a [`TaskHandle`] by running the returned future
+    /// instead of the original one.
+    pub fn new(
+        future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ) -> (Pin<Box<dyn Future<Output = ()> + Send>>, Self) {
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let future = Abortable::new(future, abort_registration);
+
+        (
+            Box::pin(async move {
+                let _ = future.await;
+                let _ = exit_tx.send(());
+            }),
+            Self {
+                inner: TaskHandleInner::Abortable {
+                    abort_handle,
+                    exit_rx,
+                },
+            },
+        )
+    }
+
+    /// Abort the future.
+    pub fn abort(&self) {
+        match &self.inner {
+            TaskHandleInner::Tokio(handle) => handle.abort(),
+            TaskHandleInner::Abortable { abort_handle, .. } => abort_handle.abort(),
+        }
+    }
+}
+
+impl From<JoinHandle<()>> for TaskHandle {
+    fn from(handle: JoinHandle<()>) -> Self {
+        Self {
+            inner: TaskHandleInner::Tokio(handle),
+        }
+    }
+}
+
+impl Future for TaskHandle {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.inner {
+            TaskHandleInner::Tokio(handle) => Pin::new(handle).poll(cx).map(|_| ()),
+            TaskHandleInner::Abortable { exit_rx, .. } => Pin::new(exit_rx).poll(cx).map(|_| ()),
+        }
+    }
 }
 
 /// Default executor, defaults to calling `tokio::spawn()`.
+///
+/// Futures started with [`Executor::run_with_name()`] are instrumented with a tracing span
+/// carrying the name. If `tokio_unstable` is enabled, the name is also given to the tokio task
+/// which requires enabling the `tracing` feature of `tokio`.
 pub(crate) struct DefaultExecutor;
 
 impl Executor for DefaultExecutor {
-    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
-        tokio::spawn(future);
+    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) -> TaskHandle {
+        tokio::spawn(future).into()
     }
 
-    fn run_with_name(&self, _: &'static str, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
-        tokio::spawn(future);
+    fn run_with_name(
+        &self,
+        name: &'static str,
+        future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ) -> TaskHandle {
+        let future = future.instrument(tracing::info_span!(target: LOG_TARGET, "task", name));
+
+        #[cfg(tokio_unstable)]
+        return tokio::task::Builder::new()
+            .name(name)
+            .spawn(future)
+            .expect("runtime to be running")
+            .into();
+
+        #[cfg(not(tokio_unstable))]
+        tokio::spawn(future).into()
+    }
+}
+
+/// Executor which runs each future on its own current-thread runtime inside
+/// `tokio::task::spawn_blocking()`.
+///
+/// Suitable for futures doing CPU-heavy work which would otherwise starve the main runtime.
+/// The future doesn't run on the runtime that called [`Executor::run()`] so it must not rely
+/// on I/O resources registered with that runtime.
+pub struct BlockingExecutor;
+
+impl BlockingExecutor {
+    /// Run `future` to completion on a new current-thread runtime.
+    fn block_on(future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime.block_on(future),
+            Err(error) => tracing::error!(
+                target: LOG_TARGET,
+                ?error,
+                "failed to create runtime for blocking task",
+            ),
+        }
+    }
+}
+
+impl Executor for BlockingExecutor {
+    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) -> TaskHandle {
+        // aborting a `spawn_blocking()` task has no effect once it has started
+        // so the future itself is made abortable
+        let (future, handle) = TaskHandle::new(future);
+        tokio::task::spawn_blocking(move || Self::block_on(future));
+
+        handle
+    }
+
+    fn run_with_name(
+        &self,
+        name: &'static str,
+        future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ) -> TaskHandle {
+        let future = future.instrument(tracing::info_span!(target: LOG_TARGET, "task", name));
+        self.run(Box::pin(future))
     }
 }
 
@@ -69,4 +220,96 @@ mod tests {
         assert_eq!(rx.recv().await.unwrap(), 1337usize);
         assert_eq!(rx.recv().await.unwrap(), 1337usize);
     }
+
+    #[tokio::test]
+    async fn blocking_executor_runs_futures() {
+        let executor = BlockingExecutor;
+        let (tx, mut rx) = channel(1);
+
+        let sender = tx.clone();
+        executor.run(Box::pin(async move {
+            sender.send(1337usize).await.unwrap();
+        }));
+
+        executor.run_with_name(
+            "test",
+            Box::pin(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                tx.send(1338usize).await.unwrap();
+            }),
+        );
+
+        assert_eq!(rx.recv().await.unwrap(), 1337usize);
+        assert_eq!(rx.recv().await.unwrap(), 1338usize);
+    }
+
+    #[tokio::test]
+    async fn abort_task() {
+        for executor in [
+            Box::new(DefaultExecutor) as Box<dyn Executor>,
+            Box::new(BlockingExecutor),
+        ] {
+            let (tx, mut rx) = channel::<()>(1);
+
+            let handle = executor.run(Box::pin(async move {
+                let _tx = tx;
+                futures::future::pending::<()>().await;
+            }));
+
+            handle.abort();
+            tokio::time::timeout(std::time::Duration::from_secs(5), handle)
+                .await
+                .expect("task to exit");
+
+            // the future has been dropped so the channel is closed
+            assert!(rx.recv().await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn await_task() {
+        let (future, handle) = TaskHandle::new(Box::pin(async move {}));
+
+        tokio::spawn(future);
+        tokio::time::timeout(std::time::Duration::from_secs(5), handle)
+            .await
+            .expect("task to exit");
+    }
+
+    #[tokio::test]
+    async fn named_task_logs_carry_name() {
+        #[derive(Clone, Default)]
+        struct Buffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        DefaultExecutor
+            .run_with_name(
+                "named-task",
+                Box::pin(async move {
+                    tracing::info!(target: LOG_TARGET, "hello from task");
+                }),
+            )
+            .await;
+
+        let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("task{name=\"named-task\"}"), "{logs}");
+        assert!(logs.contains("hello from task"), "{logs}");
+    }
 }
@@ -29,20 +29,30 @@
 #![allow(clippy::match_like_matches_macro)]
 
 use crate::{
+    codec::ProtocolCodec,
     config::Litep2pConfig,
+    executor::TaskHandle,
     protocol::{
-        libp2p::{bitswap::Bitswap, identify::Identify, kademlia::Kademlia, ping::Ping},
+        libp2p::{
+            autonat::AutoNat, bitswap::Bitswap, dcutr::Dcutr, identify::Identify,
+            kademlia::Kademlia, ping::Ping, pubsub::Pubsub, relay::Relay,
+        },
         mdns::Mdns,
         notification::NotificationProtocol,
         request_response::RequestResponseProtocol,
     },
     transport::{
         manager::{SupportedTransport, TransportManager},
+        memory::MemoryTransport,
         quic::QuicTransport,
+        relay::{
+            protocol::{HOP_PROTOCOL, STOP_PROTOCOL},
+            RelayTransport,
+        },
         tcp::TcpTransport,
         webrtc::WebRtcTransport,
         websocket::WebSocketTransport,
-        TransportBuilder, TransportEvent,
+        Metrics, TransportBuilder, TransportEvent,
     },
 };
 
@@ -51,7 +61,11 @@ use multihash::Multihash;
 use transport::Endpoint;
 use types::ConnectionId;
 
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 pub use bandwidth::BandwidthSink;
 pub use error::Error;
@@ -84,6 +98,9 @@ const LOG_TARGET: &str = "litep2p";
 /// Default channel size.
 const DEFAULT_CHANNEL_SIZE: usize = 4096usize;
 
+/// How long [`Litep2p::shutdown()`] waits for the protocol event loops to exit.
+const PROTOCOL_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Litep2p events.
 #[derive(Debug)]
 pub enum Litep2pEvent {
@@ -94,6 +111,9 @@ pub enum Litep2pEvent {
 
         /// Endpoint.
         endpoint: Endpoint,
+
+        /// Transport used to establish the connection.
+        transport: SupportedTransport,
     },
 
     /// Connection closed to remote peer.
@@ -128,6 +148,12 @@ pub struct Litep2p {
 
     /// Bandwidth sink.
     bandwidth_sink: BandwidthSink,
+
+    /// Event loops of the installed protocols.
+    protocol_tasks: Vec<TaskHandle>,
+
+    /// mDNS event loop, if enabled.
+    mdns_task: Option<TaskHandle>,
 }
 
 impl Litep2p {
@@ -136,14 +162,33 @@ impl Litep2p {
         let local_peer_id = PeerId::from_public_key(&litep2p_config.keypair.public().into());
         let bandwidth_sink = BandwidthSink::new();
         let mut listen_addresses = vec![];
+        let mut protocol_tasks = vec![];
+        let mut mdns_task = None;
 
         let supported_transports = Self::supported_transports(&litep2p_config);
-        let (mut transport_manager, transport_handle) = TransportManager::new(
+        let (mut transport_manager, transport_handle) = TransportManager::with_resolver(
             litep2p_config.keypair.clone(),
             supported_transports,
             bandwidth_sink.clone(),
             litep2p_config.max_parallel_dials,
+            Arc::clone(&litep2p_config.dns_resolver),
         );
+        transport_manager
+            .set_external_address_confirmations(litep2p_config.external_address_confirmations);
+        transport_manager.set_max_connections(litep2p_config.max_connections);
+        transport_manager.set_address_expiry(litep2p_config.address_expiry);
+        transport_manager.set_min_address_score(litep2p_config.min_address_score);
+        transport_manager.set_dial_timeout(litep2p_config.dial_timeout);
+        transport_manager.set_dial_backoff(litep2p_config.dial_backoff);
+
+        for (protocol, channel_size) in litep2p_config.protocol_channel_sizes {
+            transport_manager.set_protocol_channel_size(protocol, channel_size);
+        }
+
+        #[cfg(feature = "metrics")]
+        if let Some(registry) = &litep2p_config.metrics_registry {
+            transport_manager.register_metrics(registry)?;
+        }
 
         // add known addresses to `TransportManager`, if any exist
         if !litep2p_config.known_addresses.is_empty() {
@@ -166,9 +211,9 @@ impl Litep2p {
                 config.codec,
             );
             let executor = Arc::clone(&litep2p_config.executor);
-            litep2p_config.executor.run(Box::pin(async move {
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
                 NotificationProtocol::new(service, config, executor).run().await
-            }));
+            })));
         }
 
         // start request-response protocol event loops
@@ -184,9 +229,9 @@ impl Litep2p {
                 config.fallback_names.clone(),
                 config.codec,
             );
-            litep2p_config.executor.run(Box::pin(async move {
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
                 RequestResponseProtocol::new(service, config).run().await
-            }));
+            })));
         }
 
         // start user protocol event loops
@@ -195,9 +240,9 @@ impl Litep2p {
 
             let service =
                 transport_manager.register_protocol(protocol_name, Vec::new(), protocol.codec());
-            litep2p_config.executor.run(Box::pin(async move {
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
                 let _ = protocol.run(service).await;
-            }));
+            })));
         }
 
         // start ping protocol event loop if enabled
@@ -213,9 +258,9 @@ impl Litep2p {
                 Vec::new(),
                 ping_config.codec,
             );
-            litep2p_config.executor.run(Box::pin(async move {
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
                 Ping::new(service, ping_config).run().await
-            }));
+            })));
         }
 
         // start kademlia protocol event loop if enabled
@@ -235,9 +280,9 @@ impl Litep2p {
                 fallback_names,
                 kademlia_config.codec,
             );
-            litep2p_config.executor.run(Box::pin(async move {
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
                 let _ = Kademlia::new(service, kademlia_config).run().await;
-            }));
+            })));
         }
 
         // start identify protocol event loop if enabled
@@ -261,6 +306,93 @@ impl Litep2p {
             }
         };
 
+        // start autonat protocol event loop if enabled
+        let mut autonat_info = match litep2p_config.autonat.take() {
+            None => None,
+            Some(autonat_config) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    protocol = ?autonat_config.protocol,
+                    "enable ipfs autonat protocol",
+                );
+
+                let service = transport_manager.register_protocol(
+                    autonat_config.protocol.clone(),
+                    Vec::new(),
+                    autonat_config.codec,
+                );
+
+                Some((service, autonat_config))
+            }
+        };
+
+        // start dcutr protocol event loop if enabled
+        let mut dcutr_info = match litep2p_config.dcutr.take() {
+            None => None,
+            Some(dcutr_config) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    protocol = ?dcutr_config.protocol,
+                    "enable ipfs dcutr protocol",
+                );
+
+                let service = transport_manager.register_protocol(
+                    dcutr_config.protocol.clone(),
+                    Vec::new(),
+                    dcutr_config.codec,
+                );
+
+                Some((service, dcutr_config))
+            }
+        };
+
+        // start relay server event loop if enabled
+        //
+        // the relay server and the relay transport both use the `hop` and `stop` protocols
+        if let Some(relay_config) = litep2p_config.relay_server.take() {
+            if litep2p_config.relay.is_some() {
+                let protocol = ProtocolName::from(HOP_PROTOCOL);
+                return Err(Error::ProtocolAlreadyExists(protocol));
+            }
+
+            tracing::debug!(target: LOG_TARGET, config = ?relay_config, "enable relay server");
+
+            let hop = transport_manager.register_protocol(
+                ProtocolName::from(HOP_PROTOCOL),
+                Vec::new(),
+                ProtocolCodec::Unspecified,
+            );
+            let stop = transport_manager.register_protocol(
+                ProtocolName::from(STOP_PROTOCOL),
+                Vec::new(),
+                ProtocolCodec::Unspecified,
+            );
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
+                Relay::new(hop, stop, relay_config).run().await
+            })));
+        }
+
+        // register the circuit relay protocols if the relay transport is enabled
+        //
+        // the protocols must be registered before the transports are initialized so the
+        // connections opened by the transports can carry relay substreams
+        let mut relay_info = litep2p_config.relay.take().map(|config| {
+            tracing::debug!(target: LOG_TARGET, relays = ?config.relays, "enable relay transport");
+
+            let hop = transport_manager.register_protocol(
+                ProtocolName::from(HOP_PROTOCOL),
+                Vec::new(),
+                ProtocolCodec::Unspecified,
+            );
+            let stop = transport_manager.register_protocol(
+                ProtocolName::from(STOP_PROTOCOL),
+                Vec::new(),
+                ProtocolCodec::Unspecified,
+            );
+
+            (config, hop, stop)
+        });
+
         // start bitswap protocol event loop if enabled
         if let Some(bitswap_config) = litep2p_config.bitswap.take() {
             tracing::debug!(
@@ -274,9 +406,28 @@ impl Litep2p {
                 Vec::new(),
                 bitswap_config.codec,
             );
-            litep2p_config.executor.run(Box::pin(async move {
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
                 Bitswap::new(service, bitswap_config).run().await
-            }));
+            })));
+        }
+
+        // start pubsub protocol event loop if enabled
+        if let Some(mut pubsub_config) = litep2p_config.pubsub.take() {
+            tracing::debug!(
+                target: LOG_TARGET,
+                protocol = ?pubsub_config.protocol,
+                "enable pubsub protocol",
+            );
+
+            let service = transport_manager.register_protocol(
+                pubsub_config.protocol.clone(),
+                Vec::new(),
+                pubsub_config.codec,
+            );
+            pubsub_config.keypair = Some(litep2p_config.keypair.clone());
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
+                Pubsub::new(service, pubsub_config).run().await
+            })));
         }
 
         // enable tcp transport if the config exists
@@ -287,9 +438,7 @@ impl Litep2p {
 
             for address in transport_listen_addresses {
                 transport_manager.register_listen_address(address.clone());
-                listen_addresses.push(address.with(Protocol::P2p(
-                    Multihash::from_bytes(&local_peer_id.to_bytes()).unwrap(),
-                )));
+                listen_addresses.push(address.with(Protocol::P2p(local_peer_id.to_multihash())));
             }
 
             transport_manager.register_transport(SupportedTransport::Tcp, Box::new(transport));
@@ -303,9 +452,7 @@ impl Litep2p {
 
             for address in transport_listen_addresses {
                 transport_manager.register_listen_address(address.clone());
-                listen_addresses.push(address.with(Protocol::P2p(
-                    Multihash::from_bytes(&local_peer_id.to_bytes()).unwrap(),
-                )));
+                listen_addresses.push(address.with(Protocol::P2p(local_peer_id.to_multihash())));
             }
 
             transport_manager.register_transport(SupportedTransport::Quic, Box::new(transport));
@@ -319,9 +466,7 @@ impl Litep2p {
 
             for address in transport_listen_addresses {
                 transport_manager.register_listen_address(address.clone());
-                listen_addresses.push(address.with(Protocol::P2p(
-                    Multihash::from_bytes(&local_peer_id.to_bytes()).unwrap(),
-                )));
+                listen_addresses.push(address.with(Protocol::P2p(local_peer_id.to_multihash())));
             }
 
             transport_manager.register_transport(SupportedTransport::WebRtc, Box::new(transport));
@@ -335,22 +480,48 @@ impl Litep2p {
 
             for address in transport_listen_addresses {
                 transport_manager.register_listen_address(address.clone());
-                listen_addresses.push(address.with(Protocol::P2p(
-                    Multihash::from_bytes(&local_peer_id.to_bytes()).unwrap(),
-                )));
+                listen_addresses.push(address.with(Protocol::P2p(local_peer_id.to_multihash())));
             }
 
             transport_manager
                 .register_transport(SupportedTransport::WebSocket, Box::new(transport));
         }
 
+        // enable memory transport if the config exists
+        if let Some(config) = litep2p_config.memory.take() {
+            let handle = transport_manager.transport_handle(Arc::clone(&litep2p_config.executor));
+            let (transport, transport_listen_addresses) =
+                <MemoryTransport as TransportBuilder>::new(handle, config)?;
+
+            for address in transport_listen_addresses {
+                transport_manager.register_listen_address(address.clone());
+                listen_addresses.push(address.with(Protocol::P2p(local_peer_id.to_multihash())));
+            }
+
+            transport_manager.register_transport(SupportedTransport::Memory, Box::new(transport));
+        }
+
+        // enable relay transport if the config exists
+        if let Some((config, hop, stop)) = relay_info.take() {
+            let handle = transport_manager.transport_handle(Arc::clone(&litep2p_config.executor));
+            let (transport, transport_listen_addresses) =
+                RelayTransport::new(handle, config, hop, stop)?;
+
+            for address in transport_listen_addresses {
+                transport_manager.register_listen_address(address.clone());
+                listen_addresses.push(address.with(Protocol::P2p(local_peer_id.to_multihash())));
+            }
+
+            transport_manager.register_transport(SupportedTransport::Relay, Box::new(transport));
+        }
+
         // enable mdns if the config exists
         if let Some(config) = litep2p_config.mdns.take() {
             let mdns = Mdns::new(transport_handle, config, listen_addresses.clone())?;
 
-            litep2p_config.executor.run(Box::pin(async move {
+            mdns_task = Some(litep2p_config.executor.run(Box::pin(async move {
                 let _ = mdns.start().await;
-            }));
+            })));
         }
 
         // if identify was enabled, give it the enabled protocols and listen addresses and start it
@@ -358,9 +529,27 @@ impl Litep2p {
             identify_config.protocols = transport_manager.protocols().cloned().collect();
             let identify = Identify::new(service, identify_config, listen_addresses.clone());
 
-            litep2p_config.executor.run(Box::pin(async move {
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
                 let _ = identify.run().await;
-            }));
+            })));
+        }
+
+        // if autonat was enabled, give it the listen addresses and start it
+        if let Some((service, autonat_config)) = autonat_info.take() {
+            let autonat = AutoNat::new(service, autonat_config, listen_addresses.clone());
+
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
+                autonat.run().await;
+            })));
+        }
+
+        // if dcutr was enabled, give it the listen addresses and start it
+        if let Some((service, dcutr_config)) = dcutr_info.take() {
+            let dcutr = Dcutr::new(service, dcutr_config, listen_addresses.clone());
+
+            protocol_tasks.push(litep2p_config.executor.run(Box::pin(async move {
+                dcutr.run().await;
+            })));
         }
 
         if transport_manager.installed_transports().count() == 0 {
@@ -380,6 +569,8 @@ impl Litep2p {
             bandwidth_sink,
             listen_addresses,
             transport_manager,
+            protocol_tasks,
+            mdns_task,
         })
     }
 
@@ -407,6 +598,14 @@ impl Litep2p {
             .webrtc
             .is_some()
             .then(|| supported_transports.insert(SupportedTransport::WebRtc));
+        config
+            .memory
+            .is_some()
+            .then(|| supported_transports.insert(SupportedTransport::Memory));
+        config
+            .relay
+            .is_some()
+            .then(|| supported_transports.insert(SupportedTransport::Relay));
 
         supported_transports
     }
@@ -421,21 +620,94 @@ impl Litep2p {
         self.listen_addresses.iter()
     }
 
+    /// Get external addresses of litep2p.
+    ///
+    /// External addresses are either added with [`Litep2p::add_external_address()`] or
+    /// observed by enough distinct remote peers. Listen addresses are not included.
+    pub fn external_addresses(&self) -> Vec<Multiaddr> {
+        self.transport_manager.external_addresses()
+    }
+
+    /// Add external address which is advertised to remote peers.
+    ///
+    /// Returns `true` if the address was added and `false` if it had already been added or
+    /// if it's a listen address.
+    pub fn add_external_address(&mut self, address: Multiaddr) -> bool {
+        self.transport_manager.add_external_address(address)
+    }
+
+    /// Remove external address added with [`Litep2p::add_external_address()`].
+    ///
+    /// Returns `true` if the address was removed.
+    pub fn remove_external_address(&mut self, address: &Multiaddr) -> bool {
+        self.transport_manager.remove_external_address(address)
+    }
+
+    /// Get addresses litep2p advertises to remote peers.
+    ///
+    /// The addresses are the listen addresses followed by the external addresses.
+    pub fn advertised_addresses(&self) -> Vec<Multiaddr> {
+        self.transport_manager
+            .advertised_addresses()
+            .into_iter()
+            .map(|address| address.with(Protocol::P2p(Multihash::from(self.local_peer_id))))
+            .collect()
+    }
+
+    /// Get snapshot of the connection state.
+    pub fn metrics(&self) -> Metrics {
+        self.transport_manager.metrics()
+    }
+
     /// Get handle to bandwidth sink.
     pub fn bandwidth_sink(&self) -> BandwidthSink {
         self.bandwidth_sink.clone()
     }
 
+    /// Get the number of bytes received from and sent to `peer` over its connections.
+    pub fn bandwidth_for(&self, peer: &PeerId) -> (u64, u64) {
+        self.transport_manager.bandwidth_for(peer)
+    }
+
+    /// Get the number of bytes received and sent over all connections.
+    pub fn total_bandwidth(&self) -> (u64, u64) {
+        self.transport_manager.total_bandwidth()
+    }
+
     /// Dial peer.
     pub async fn dial(&mut self, peer: &PeerId) -> crate::Result<()> {
         self.transport_manager.dial(*peer).await
     }
 
     /// Dial address.
-    pub async fn dial_address(&mut self, address: Multiaddr) -> crate::Result<()> {
+    ///
+    /// Returns the ID of the dialed connection which can be passed to [`Litep2p::cancel_dial()`].
+    /// If the peer is already connected or being dialed, the ID of that connection or dial is
+    /// returned.
+    pub async fn dial_address(&mut self, address: Multiaddr) -> crate::Result<ConnectionId> {
         self.transport_manager.dial_address(address).await
     }
 
+    /// Abort an in-flight dial started with [`Litep2p::dial_address()`].
+    ///
+    /// The aborted dial is reported as [`Litep2pEvent::DialFailure`] with
+    /// [`Error::DialAborted`].
+    pub fn cancel_dial(&mut self, connection_id: ConnectionId) -> crate::Result<()> {
+        self.transport_manager.cancel_dial(connection_id)
+    }
+
+    /// Ban peer, optionally until `until`.
+    ///
+    /// Banned peers cannot be dialed and inbound connections from them are rejected.
+    pub fn ban_peer(&mut self, peer: PeerId, until: Option<Instant>) {
+        self.transport_manager.ban_peer(peer, until)
+    }
+
+    /// Lift the ban of peer.
+    pub fn unban_peer(&mut self, peer: PeerId) {
+        self.transport_manager.unban_peer(peer)
+    }
+
     /// Add one ore more known addresses for peer.
     ///
     /// Return value denotes how many addresses were added for the peer.
@@ -448,14 +720,67 @@ impl Litep2p {
         self.transport_manager.add_known_address(peer, address)
     }
 
+    /// Remove known `address` of `peer`.
+    ///
+    /// Returns `true` if the address was removed.
+    pub fn remove_known_address(&mut self, peer: PeerId, address: &Multiaddr) -> bool {
+        self.transport_manager.remove_known_address(peer, address)
+    }
+
+    /// Shut down litep2p.
+    ///
+    /// Listeners of all transports are closed, pending dials are canceled and open connections
+    /// are closed, after which the event loops of the installed protocols are given
+    /// [`PROTOCOL_SHUTDOWN_TIMEOUT`] to exit. Event loops still running after that are aborted.
+    ///
+    /// Protocols are notified of the shutdown by their [`protocol::TransportService`]
+    /// terminating, so user protocols should exit once the service returns `None`.
+    pub async fn shutdown(mut self) {
+        tracing::info!(target: LOG_TARGET, "shut down litep2p");
+
+        if let Some(mdns) = self.mdns_task.take() {
+            mdns.abort();
+            let _ = mdns.await;
+        }
+
+        self.transport_manager.shutdown().await;
+
+        let mut tasks = std::mem::take(&mut self.protocol_tasks);
+        let exited = tokio::time::timeout(
+            PROTOCOL_SHUTDOWN_TIMEOUT,
+            futures::future::join_all(tasks.iter_mut()),
+        )
+        .await;
+
+        if exited.is_err() {
+            tracing::warn!(
+                target: LOG_TARGET,
+                "protocols did not exit in time, aborting their event loops",
+            );
+
+            for task in &tasks {
+                task.abort();
+            }
+            futures::future::join_all(tasks).await;
+        }
+    }
+
     /// Poll next event.
     ///
     /// This function must be called in order for litep2p to make progress.
     pub async fn next_event(&mut self) -> Option<Litep2pEvent> {
         loop {
             match self.transport_manager.next().await? {
-                TransportEvent::ConnectionEstablished { peer, endpoint, .. } =>
-                    return Some(Litep2pEvent::ConnectionEstablished { peer, endpoint }),
+                TransportEvent::ConnectionEstablished {
+                    peer,
+                    endpoint,
+                    transport,
+                } =>
+                    return Some(Litep2pEvent::ConnectionEstablished {
+                        peer,
+                        endpoint,
+                        transport,
+                    }),
                 TransportEvent::ConnectionClosed {
                     peer,
                     connection_id,
@@ -600,9 +925,7 @@ mod tests {
         let address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(255, 254, 253, 252)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
 
         let mut litep2p = Litep2p::new(config).unwrap();
         litep2p.dial_address(address.clone()).await.unwrap();
@@ -619,4 +942,29 @@ mod tests {
             _ => panic!("invalid event received"),
         }
     }
+
+    #[tokio::test]
+    async fn manual_external_address_advertised() {
+        let mut litep2p =
+            Litep2p::new(ConfigBuilder::new().with_memory(Default::default()).build()).unwrap();
+        let local_peer_id = *litep2p.local_peer_id();
+        let listen_address = litep2p.listen_addresses().next().unwrap().clone();
+        let address: Multiaddr = "/ip4/1.2.3.4/tcp/8888".parse().unwrap();
+
+        assert!(litep2p.external_addresses().is_empty());
+        assert!(!litep2p.add_external_address(listen_address.clone()));
+
+        assert!(litep2p.add_external_address(address.clone()));
+        assert_eq!(litep2p.external_addresses(), vec![address.clone()]);
+        assert_eq!(
+            litep2p.advertised_addresses(),
+            vec![
+                listen_address.clone(),
+                address.clone().with(Protocol::P2p(Multihash::from(local_peer_id))),
+            ]
+        );
+
+        assert!(litep2p.remove_external_address(&address));
+        assert_eq!(litep2p.advertised_addresses(), vec![listen_address]);
+    }
 }
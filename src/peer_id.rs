@@ -119,6 +119,11 @@ impl PeerId {
         self.multihash.to_bytes()
     }
 
+    /// Returns the multihash of this `PeerId`.
+    pub fn to_multihash(&self) -> Multihash {
+        self.multihash
+    }
+
     /// Returns a base-58 encoded string of this `PeerId`.
     pub fn to_base58(&self) -> String {
         bs58::encode(self.to_bytes()).into_string()
@@ -326,6 +331,15 @@ mod tests {
         assert_eq!(PeerId::try_from(multihash).unwrap(), peer);
     }
 
+    #[test]
+    fn peer_id_to_multihash_then_back() {
+        let peer = Keypair::generate().public().to_peer_id();
+        let multihash = peer.to_multihash();
+
+        assert_eq!(multihash.to_bytes(), peer.to_bytes());
+        assert_eq!(PeerId::from_multihash(multihash).unwrap(), peer);
+    }
+
     #[test]
     fn serialize_deserialize() {
         let peer = PeerId::random();
@@ -94,6 +94,15 @@ impl ConnectionHandle {
         }
     }
 
+    /// Mark connection as active again, if it's still open.
+    pub fn activate(&mut self) {
+        if let ConnectionType::Inactive(connection) = &self.connection {
+            if let Some(connection) = connection.upgrade() {
+                self.connection = ConnectionType::Active(connection);
+            }
+        }
+    }
+
     /// Attempt to acquire permit which will keep the connection open for indefinite time.
     pub fn try_get_permit(&self) -> Option<Permit> {
         match &self.connection {
@@ -0,0 +1,198 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{
+    codec::ProtocolCodec, protocol::libp2p::autonat::AutoNatEvent, types::protocol::ProtocolName,
+    DEFAULT_CHANNEL_SIZE,
+};
+
+use futures::Stream;
+use tokio::sync::mpsc::{channel, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+
+use std::time::Duration;
+
+/// AutoNAT protocol name as a string.
+pub const PROTOCOL_NAME: &str = "/libp2p/autonat/1.0.0";
+
+/// Maximum size of an AutoNAT message.
+const AUTONAT_PAYLOAD_SIZE: usize = 4096;
+
+/// Interval between reachability probes.
+const PROBE_INTERVAL: Duration = Duration::from_secs(90);
+
+/// Number of consecutive probes which must agree before the reachability is changed.
+const CONFIDENCE: usize = 3;
+
+/// Minimum interval between two probes sent to the same peer.
+const THROTTLE_SERVER_INTERVAL: Duration = Duration::from_secs(90);
+
+/// Minimum interval between two dial-backs performed for the same peer.
+const THROTTLE_CLIENT_INTERVAL: Duration = Duration::from_secs(90);
+
+/// Maximum number of concurrent dial-backs.
+const MAX_DIAL_BACKS: usize = 30;
+
+/// Timeout for a dial-back.
+const DIAL_BACK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// AutoNAT configuration.
+pub struct Config {
+    /// Protocol name.
+    pub(crate) protocol: ProtocolName,
+
+    /// Codec used by the protocol.
+    pub(crate) codec: ProtocolCodec,
+
+    /// Interval between reachability probes.
+    pub(crate) probe_interval: Duration,
+
+    /// Number of consecutive probes which must agree before the reachability is changed.
+    pub(crate) confidence: usize,
+
+    /// Minimum interval between two probes sent to the same peer.
+    pub(crate) throttle_server_interval: Duration,
+
+    /// Minimum interval between two dial-backs performed for the same peer.
+    pub(crate) throttle_client_interval: Duration,
+
+    /// Maximum number of concurrent dial-backs.
+    pub(crate) max_dial_backs: usize,
+
+    /// Timeout for a dial-back.
+    pub(crate) dial_back_timeout: Duration,
+
+    /// TX channel for sending events to the user protocol.
+    pub(crate) tx_event: Sender<AutoNatEvent>,
+}
+
+impl Config {
+    /// Create new [`Config`] with default values.
+    ///
+    /// Returns a config that is given to `Litep2pConfig` and an event stream for
+    /// [`AutoNatEvent`]s.
+    pub fn default() -> (Self, Box<dyn Stream<Item = AutoNatEvent> + Send + Unpin>) {
+        ConfigBuilder::new().build()
+    }
+}
+
+/// AutoNAT configuration builder.
+pub struct ConfigBuilder {
+    /// Interval between reachability probes.
+    probe_interval: Duration,
+
+    /// Number of consecutive probes which must agree before the reachability is changed.
+    confidence: usize,
+
+    /// Minimum interval between two probes sent to the same peer.
+    throttle_server_interval: Duration,
+
+    /// Minimum interval between two dial-backs performed for the same peer.
+    throttle_client_interval: Duration,
+
+    /// Maximum number of concurrent dial-backs.
+    max_dial_backs: usize,
+
+    /// Timeout for a dial-back.
+    dial_back_timeout: Duration,
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigBuilder {
+    /// Create new default [`Config`] which can be modified by the user.
+    pub fn new() -> Self {
+        Self {
+            probe_interval: PROBE_INTERVAL,
+            confidence: CONFIDENCE,
+            throttle_server_interval: THROTTLE_SERVER_INTERVAL,
+            throttle_client_interval: THROTTLE_CLIENT_INTERVAL,
+            max_dial_backs: MAX_DIAL_BACKS,
+            dial_back_timeout: DIAL_BACK_TIMEOUT,
+        }
+    }
+
+    /// Set interval between reachability probes.
+    pub fn with_probe_interval(mut self, probe_interval: Duration) -> Self {
+        self.probe_interval = probe_interval;
+        self
+    }
+
+    /// Set number of consecutive probes which must agree before the reachability is changed.
+    pub fn with_confidence(mut self, confidence: usize) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// Set minimum interval between two probes sent to the same peer.
+    pub fn with_throttle_server_interval(mut self, throttle_server_interval: Duration) -> Self {
+        self.throttle_server_interval = throttle_server_interval;
+        self
+    }
+
+    /// Set minimum interval between two dial-backs performed for the same peer.
+    ///
+    /// Requests received from the peer before the interval has elapsed are refused.
+    pub fn with_throttle_client_interval(mut self, throttle_client_interval: Duration) -> Self {
+        self.throttle_client_interval = throttle_client_interval;
+        self
+    }
+
+    /// Set maximum number of concurrent dial-backs.
+    ///
+    /// Requests received while the limit is reached are refused.
+    pub fn with_max_dial_backs(mut self, max_dial_backs: usize) -> Self {
+        self.max_dial_backs = max_dial_backs;
+        self
+    }
+
+    /// Set timeout for a dial-back.
+    pub fn with_dial_back_timeout(mut self, dial_back_timeout: Duration) -> Self {
+        self.dial_back_timeout = dial_back_timeout;
+        self
+    }
+
+    /// Build [`Config`].
+    ///
+    /// Returns a config that is given to `Litep2pConfig` and an event stream for
+    /// [`AutoNatEvent`]s.
+    pub fn build(self) -> (Config, Box<dyn Stream<Item = AutoNatEvent> + Send + Unpin>) {
+        let (tx_event, rx_event) = channel(DEFAULT_CHANNEL_SIZE);
+
+        (
+            Config {
+                tx_event,
+                probe_interval: self.probe_interval,
+                confidence: self.confidence,
+                throttle_server_interval: self.throttle_server_interval,
+                throttle_client_interval: self.throttle_client_interval,
+                max_dial_backs: self.max_dial_backs,
+                dial_back_timeout: self.dial_back_timeout,
+                protocol: ProtocolName::from(PROTOCOL_NAME),
+                codec: ProtocolCodec::UnsignedVarint(Some(AUTONAT_PAYLOAD_SIZE)),
+            },
+            Box::new(ReceiverStream::new(rx_event)),
+        )
+    }
+}
@@ -0,0 +1,731 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! [`/libp2p/autonat/1.0.0`](https://github.com/libp2p/specs/blob/master/autonat/autonat-v1.md) implementation.
+//!
+//! The local node periodically asks one of its connected peers to dial it back on its listen and
+//! external addresses. Each response is a vote for the node being publicly reachable or not and
+//! once enough consecutive votes agree, the reachability status is updated and reported to the
+//! user as [`AutoNatEvent::ReachabilityChanged`].
+//!
+//! The local node also serves dial-back requests of other peers. Dial-backs are performed over a
+//! new connection, see [`TransportService::dial_secondary()`], and they're rate-limited per peer
+//! as well as globally.
+
+use crate::{
+    error::{Error, SubstreamError},
+    protocol::{Direction, TransportEvent, TransportService},
+    substream::Substream,
+    transport::Endpoint,
+    types::SubstreamId,
+    PeerId,
+};
+
+use bytes::BytesMut;
+use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
+use multiaddr::{Multiaddr, Protocol};
+use prost::Message;
+use tokio::{
+    sync::mpsc::Sender,
+    time::{Interval, MissedTickBehavior},
+};
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+pub use config::{Config, ConfigBuilder};
+
+mod config;
+
+mod schema {
+    include!(concat!(env!("OUT_DIR"), "/autonat.rs"));
+}
+
+use schema::message::{Dial, DialResponse, MessageType, PeerInfo, ResponseStatus};
+
+/// Log target for the file.
+const LOG_TARGET: &str = "litep2p::ipfs::autonat";
+
+/// Timeout for reading and writing AutoNAT messages.
+const SUBSTREAM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reachability of the local node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reachability {
+    /// Local node is reachable over at least one of its addresses.
+    Public,
+
+    /// Local node is not reachable over any of its addresses.
+    Private,
+
+    /// Reachability of the local node has not been determined yet.
+    Unknown,
+}
+
+/// Events emitted by the AutoNAT protocol.
+#[derive(Debug)]
+pub enum AutoNatEvent {
+    /// Reachability of the local node changed.
+    ReachabilityChanged {
+        /// New reachability status.
+        reachability: Reachability,
+    },
+}
+
+/// Dial-back performed on behalf of a remote peer.
+struct DialBack {
+    /// Substream the response is sent over.
+    substream: Substream,
+
+    /// Dialed address.
+    address: Multiaddr,
+
+    /// Instant after which the dial-back is considered failed.
+    deadline: Instant,
+}
+
+/// AutoNAT protocol.
+pub(crate) struct AutoNat {
+    // Connection service.
+    service: TransportService,
+
+    /// TX channel for sending events to the user protocol.
+    tx: Sender<AutoNatEvent>,
+
+    /// Listen addresses of the local node.
+    listen_addresses: Vec<Multiaddr>,
+
+    /// Connected peers and the endpoints of their connections.
+    peers: HashMap<PeerId, Endpoint>,
+
+    /// Current reachability of the local node.
+    reachability: Reachability,
+
+    /// Outcome of the most recent probes and the number of consecutive probes which agreed.
+    streak: (Reachability, usize),
+
+    /// Number of consecutive probes which must agree before the reachability is changed.
+    confidence: usize,
+
+    /// Probe timer.
+    timer: Interval,
+
+    /// When each peer was last probed.
+    probed: HashMap<PeerId, Instant>,
+
+    /// Minimum interval between two probes sent to the same peer.
+    throttle_server_interval: Duration,
+
+    /// Pending outbound substreams.
+    pending_opens: HashMap<SubstreamId, PeerId>,
+
+    /// Pending probes.
+    pending_outbound: FuturesUnordered<BoxFuture<'static, (PeerId, crate::Result<ResponseStatus>)>>,
+
+    /// When a dial-back was last performed for each peer.
+    served: HashMap<PeerId, Instant>,
+
+    /// Minimum interval between two dial-backs performed for the same peer.
+    throttle_client_interval: Duration,
+
+    /// Maximum number of concurrent dial-backs.
+    max_dial_backs: usize,
+
+    /// Timeout for a dial-back.
+    dial_back_timeout: Duration,
+
+    /// Pending inbound requests.
+    pending_inbound:
+        FuturesUnordered<BoxFuture<'static, (PeerId, Substream, crate::Result<BytesMut>)>>,
+
+    /// Active dial-backs.
+    dial_backs: HashMap<PeerId, DialBack>,
+
+    /// Pending dial-back timeouts.
+    dial_back_timeouts: FuturesUnordered<BoxFuture<'static, (PeerId, Instant)>>,
+
+    /// Pending responses.
+    pending_responses: FuturesUnordered<BoxFuture<'static, ()>>,
+}
+
+impl AutoNat {
+    /// Create new [`AutoNat`] protocol.
+    pub(crate) fn new(
+        service: TransportService,
+        config: Config,
+        listen_addresses: Vec<Multiaddr>,
+    ) -> Self {
+        let mut timer = tokio::time::interval(config.probe_interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Self {
+            service,
+            timer,
+            tx: config.tx_event,
+            listen_addresses: listen_addresses
+                .into_iter()
+                .filter(|address| !address.iter().any(|protocol| protocol == Protocol::P2pCircuit))
+                .collect(),
+            peers: HashMap::new(),
+            reachability: Reachability::Unknown,
+            streak: (Reachability::Unknown, 0usize),
+            confidence: config.confidence,
+            probed: HashMap::new(),
+            throttle_server_interval: config.throttle_server_interval,
+            pending_opens: HashMap::new(),
+            pending_outbound: FuturesUnordered::new(),
+            served: HashMap::new(),
+            throttle_client_interval: config.throttle_client_interval,
+            max_dial_backs: config.max_dial_backs,
+            dial_back_timeout: config.dial_back_timeout,
+            pending_inbound: FuturesUnordered::new(),
+            dial_backs: HashMap::new(),
+            dial_back_timeouts: FuturesUnordered::new(),
+            pending_responses: FuturesUnordered::new(),
+        }
+    }
+
+    /// Connection established to remote peer.
+    fn on_connection_established(&mut self, peer: PeerId, endpoint: Endpoint) {
+        tracing::trace!(target: LOG_TARGET, ?peer, ?endpoint, "connection established");
+
+        self.peers.insert(peer, endpoint);
+    }
+
+    /// Connection closed to remote peer.
+    fn on_connection_closed(&mut self, peer: PeerId) {
+        tracing::trace!(target: LOG_TARGET, ?peer, "connection closed");
+
+        self.peers.remove(&peer);
+        self.dial_backs.remove(&peer);
+    }
+
+    /// Candidate addresses of the local node.
+    fn candidate_addresses(&self) -> Vec<Multiaddr> {
+        let mut addresses = self.listen_addresses.clone();
+
+        for address in self.service.external_addresses() {
+            if !addresses.contains(&address)
+                && !address.iter().any(|protocol| protocol == Protocol::P2pCircuit)
+            {
+                addresses.push(address);
+            }
+        }
+
+        addresses
+    }
+
+    /// Probe interval has elapsed, ask one of the connected peers to dial the local node back.
+    fn on_probe_interval(&mut self) {
+        if !self.pending_opens.is_empty() || !self.pending_outbound.is_empty() {
+            return;
+        }
+
+        let now = Instant::now();
+        let Some(peer) = self
+            .peers
+            .keys()
+            .find(|peer| {
+                self.probed.get(peer).is_none_or(|probed| {
+                    now.duration_since(*probed) >= self.throttle_server_interval
+                })
+            })
+            .copied()
+        else {
+            tracing::trace!(target: LOG_TARGET, "no peer available for probing");
+            return;
+        };
+
+        tracing::trace!(target: LOG_TARGET, ?peer, "probe reachability");
+
+        match self.service.open_substream(peer) {
+            Ok(substream_id) => {
+                self.probed.insert(peer, now);
+                self.pending_opens.insert(substream_id, peer);
+            }
+            Err(error) => {
+                tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to open substream");
+            }
+        }
+    }
+
+    /// Outbound substream opened, send dial request to remote peer.
+    fn on_outbound_substream(
+        &mut self,
+        peer: PeerId,
+        substream_id: SubstreamId,
+        mut substream: Substream,
+    ) {
+        tracing::trace!(target: LOG_TARGET, ?peer, ?substream_id, "outbound substream opened");
+
+        let mut request = schema::Message {
+            dial: Some(Dial {
+                peer: Some(PeerInfo {
+                    id: Some(self.service.local_peer_id.to_bytes()),
+                    addrs: self
+                        .candidate_addresses()
+                        .into_iter()
+                        .map(|address| address.to_vec())
+                        .collect(),
+                }),
+            }),
+            ..Default::default()
+        };
+        request.set_type(MessageType::Dial);
+
+        self.pending_outbound.push(Box::pin(async move {
+            let future = async move {
+                substream.send_framed(request.encode_to_vec().into()).await?;
+
+                let payload = substream.next().await.ok_or(Error::SubstreamError(
+                    SubstreamError::ReadFailure(Some(substream_id)),
+                ))??;
+                let response = schema::Message::decode(payload.freeze())?;
+
+                match (response.r#type(), response.dial_response) {
+                    (MessageType::DialResponse, Some(response)) => Ok(response.status()),
+                    _ => Err(Error::InvalidData),
+                }
+            };
+
+            match tokio::time::timeout(SUBSTREAM_TIMEOUT, future).await {
+                Err(_) => (peer, Err(Error::Timeout)),
+                Ok(result) => (peer, result),
+            }
+        }));
+    }
+
+    /// Handle response to a probe.
+    async fn on_probe_result(&mut self, peer: PeerId, result: crate::Result<ResponseStatus>) {
+        let outcome = match result {
+            Ok(ResponseStatus::Ok) => Reachability::Public,
+            Ok(ResponseStatus::EDialError) => Reachability::Private,
+            Ok(status) => {
+                tracing::debug!(target: LOG_TARGET, ?peer, ?status, "peer didn't dial back");
+                return;
+            }
+            Err(error) => {
+                tracing::debug!(target: LOG_TARGET, ?peer, ?error, "probe failed");
+                return;
+            }
+        };
+
+        tracing::trace!(target: LOG_TARGET, ?peer, ?outcome, "probe finished");
+
+        if self.streak.0 == outcome {
+            self.streak.1 += 1;
+        } else {
+            self.streak = (outcome, 1usize);
+        }
+
+        if self.streak.1 >= self.confidence && self.reachability != outcome {
+            tracing::debug!(
+                target: LOG_TARGET,
+                old = ?self.reachability,
+                new = ?outcome,
+                "reachability changed",
+            );
+
+            self.reachability = outcome;
+            let _ = self
+                .tx
+                .send(AutoNatEvent::ReachabilityChanged {
+                    reachability: outcome,
+                })
+                .await;
+        }
+    }
+
+    /// Inbound substream opened, read dial request.
+    fn on_inbound_substream(&mut self, peer: PeerId, mut substream: Substream) {
+        tracing::trace!(target: LOG_TARGET, ?peer, "inbound substream opened");
+
+        self.pending_inbound.push(Box::pin(async move {
+            let result = match tokio::time::timeout(SUBSTREAM_TIMEOUT, substream.next()).await {
+                Err(_) => Err(Error::Timeout),
+                Ok(None) => Err(Error::SubstreamError(SubstreamError::ReadFailure(None))),
+                Ok(Some(result)) => result,
+            };
+
+            (peer, substream, result)
+        }));
+    }
+
+    /// Select the address to dial `peer` back on.
+    ///
+    /// Only addresses which have the same IP address as the connection of the peer are dialed so
+    /// the local node can't be used to dial arbitrary hosts. If the connection of the peer has no
+    /// IP address, e.g., it's a relayed connection, the request is refused.
+    fn dial_back_address(&self, peer: PeerId, request: &[u8]) -> Result<Multiaddr, ResponseStatus> {
+        let request = schema::Message::decode(request).map_err(|_| ResponseStatus::EBadRequest)?;
+
+        let info = match (request.r#type(), request.dial.and_then(|dial| dial.peer)) {
+            (MessageType::Dial, Some(info)) => info,
+            _ => return Err(ResponseStatus::EBadRequest),
+        };
+
+        if info.id.and_then(|id| PeerId::from_bytes(&id).ok()) != Some(peer) {
+            return Err(ResponseStatus::EBadRequest);
+        }
+
+        let Some(observed_ip) =
+            self.peers.get(&peer).and_then(|endpoint| ip_address(endpoint.address()))
+        else {
+            return Err(ResponseStatus::EDialRefused);
+        };
+
+        info.addrs
+            .into_iter()
+            .filter_map(|address| Multiaddr::try_from(address).ok())
+            .filter(|address| {
+                !address.iter().any(|protocol| protocol == Protocol::P2pCircuit)
+                    && ip_address(address) == Some(observed_ip)
+            })
+            .find(|address| match address.iter().last() {
+                Some(Protocol::P2p(multihash)) => {
+                    PeerId::from_multihash(multihash).ok() == Some(peer)
+                }
+                _ => true,
+            })
+            .map(|address| {
+                if std::matches!(address.iter().last(), Some(Protocol::P2p(_))) {
+                    return address;
+                }
+
+                address.with(Protocol::P2p(peer.to_multihash()))
+            })
+            .ok_or(ResponseStatus::EDialRefused)
+    }
+
+    /// Handle dial request of `peer`.
+    fn on_dial_request(&mut self, peer: PeerId, substream: Substream, request: BytesMut) {
+        let throttled = self
+            .served
+            .get(&peer)
+            .is_some_and(|served| served.elapsed() < self.throttle_client_interval);
+
+        if throttled
+            || self.dial_backs.contains_key(&peer)
+            || self.dial_backs.len() >= self.max_dial_backs
+        {
+            tracing::debug!(target: LOG_TARGET, ?peer, "dial-back throttled");
+            return self.send_response(peer, substream, ResponseStatus::EDialRefused, None);
+        }
+
+        let address = match self.dial_back_address(peer, &request) {
+            Ok(address) => address,
+            Err(status) => {
+                tracing::debug!(target: LOG_TARGET, ?peer, ?status, "dial request rejected");
+                return self.send_response(peer, substream, status, None);
+            }
+        };
+
+        tracing::trace!(target: LOG_TARGET, ?peer, ?address, "dial back peer");
+
+        if let Err(error) = self.service.dial_secondary(address.clone()) {
+            tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to dial back peer");
+            return self.send_response(peer, substream, ResponseStatus::EInternalError, None);
+        }
+
+        let deadline = Instant::now() + self.dial_back_timeout;
+        self.served.insert(peer, Instant::now());
+        self.dial_backs.insert(
+            peer,
+            DialBack {
+                substream,
+                address,
+                deadline,
+            },
+        );
+        self.dial_back_timeouts.push(Box::pin(async move {
+            tokio::time::sleep_until(deadline.into()).await;
+            (peer, deadline)
+        }));
+    }
+
+    /// Dial-back to `peer` succeeded.
+    fn on_dial_back_success(&mut self, peer: PeerId) {
+        if let Some(DialBack {
+            substream, address, ..
+        }) = self.dial_backs.remove(&peer)
+        {
+            tracing::trace!(target: LOG_TARGET, ?peer, ?address, "dial-back succeeded");
+
+            self.send_response(peer, substream, ResponseStatus::Ok, Some(address));
+        }
+    }
+
+    /// Dial to `peer` failed.
+    fn on_dial_failure(&mut self, peer: PeerId, address: Multiaddr) {
+        if self.dial_backs.get(&peer).is_some_and(|dial_back| dial_back.address == address) {
+            let DialBack { substream, .. } =
+                self.dial_backs.remove(&peer).expect("dial-back to exist");
+
+            tracing::trace!(target: LOG_TARGET, ?peer, ?address, "dial-back failed");
+
+            self.send_response(peer, substream, ResponseStatus::EDialError, None);
+        }
+    }
+
+    /// Dial-back to `peer` timed out.
+    fn on_dial_back_timeout(&mut self, peer: PeerId, deadline: Instant) {
+        if self
+            .dial_backs
+            .get(&peer)
+            .is_some_and(|dial_back| dial_back.deadline == deadline)
+        {
+            let DialBack { substream, .. } =
+                self.dial_backs.remove(&peer).expect("dial-back to exist");
+
+            tracing::trace!(target: LOG_TARGET, ?peer, "dial-back timed out");
+
+            self.send_response(peer, substream, ResponseStatus::EDialError, None);
+        }
+    }
+
+    /// Send dial response to `peer`.
+    fn send_response(
+        &mut self,
+        peer: PeerId,
+        mut substream: Substream,
+        status: ResponseStatus,
+        address: Option<Multiaddr>,
+    ) {
+        let mut response = schema::Message {
+            dial_response: Some(DialResponse {
+                addr: address.map(|address| address.to_vec()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        response.set_type(MessageType::DialResponse);
+        if let Some(dial_response) = response.dial_response.as_mut() {
+            dial_response.set_status(status);
+        }
+
+        self.pending_responses.push(Box::pin(async move {
+            let future = substream.send_framed(response.encode_to_vec().into());
+
+            match tokio::time::timeout(SUBSTREAM_TIMEOUT, future).await {
+                Err(_) => {
+                    tracing::debug!(target: LOG_TARGET, ?peer, "timed out sending response");
+                }
+                Ok(Err(error)) => {
+                    tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to send response");
+                }
+                Ok(Ok(())) => {
+                    let _ = substream.close_gracefully(SUBSTREAM_TIMEOUT).await;
+                }
+            }
+        }));
+    }
+
+    /// Start [`AutoNat`] event loop.
+    pub async fn run(mut self) {
+        tracing::debug!(target: LOG_TARGET, "starting autonat event loop");
+
+        loop {
+            tokio::select! {
+                event = self.service.next() => match event {
+                    Some(TransportEvent::ConnectionEstablished { peer, endpoint }) => {
+                        self.on_connection_established(peer, endpoint);
+                    }
+                    Some(TransportEvent::SecondaryConnectionEstablished { peer, .. }) => {
+                        self.on_dial_back_success(peer);
+                    }
+                    Some(TransportEvent::ConnectionClosed { peer }) => {
+                        self.on_connection_closed(peer);
+                    }
+                    Some(TransportEvent::DialFailure { peer, address }) => {
+                        self.on_dial_failure(peer, address);
+                    }
+                    Some(TransportEvent::SubstreamOpened {
+                        peer,
+                        substream,
+                        direction,
+                        ..
+                    }) => match direction {
+                        Direction::Inbound => self.on_inbound_substream(peer, substream),
+                        Direction::Outbound(substream_id) => {
+                            match self.pending_opens.remove(&substream_id) {
+                                Some(_) => self.on_outbound_substream(peer, substream_id, substream),
+                                None => tracing::debug!(
+                                    target: LOG_TARGET,
+                                    ?peer,
+                                    ?substream_id,
+                                    "outbound substream doesn't exist",
+                                ),
+                            }
+                        }
+                    },
+                    Some(TransportEvent::SubstreamOpenFailure { substream, error }) => {
+                        if let Some(peer) = self.pending_opens.remove(&substream) {
+                            tracing::debug!(
+                                target: LOG_TARGET,
+                                ?peer,
+                                ?error,
+                                "failed to open substream",
+                            );
+                        }
+                    }
+                    None => return,
+                },
+                _ = self.timer.tick() => self.on_probe_interval(),
+                event = self.pending_outbound.next(), if !self.pending_outbound.is_empty() => {
+                    if let Some((peer, result)) = event {
+                        self.on_probe_result(peer, result).await;
+                    }
+                }
+                event = self.pending_inbound.next(), if !self.pending_inbound.is_empty() => {
+                    match event {
+                        Some((peer, substream, Ok(request))) => {
+                            self.on_dial_request(peer, substream, request);
+                        }
+                        Some((peer, _, Err(error))) => {
+                            tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to read request");
+                        }
+                        None => {}
+                    }
+                }
+                event = self.dial_back_timeouts.next(), if !self.dial_back_timeouts.is_empty() => {
+                    if let Some((peer, deadline)) = event {
+                        self.on_dial_back_timeout(peer, deadline);
+                    }
+                }
+                _ = self.pending_responses.next(), if !self.pending_responses.is_empty() => {}
+            }
+        }
+    }
+}
+
+/// Get the IP address of `address`, if it has one.
+fn ip_address(address: &Multiaddr) -> Option<IpAddr> {
+    match address.iter().next() {
+        Some(Protocol::Ip4(address)) => Some(IpAddr::V4(address)),
+        Some(Protocol::Ip6(address)) => Some(IpAddr::V6(address)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::ed25519::Keypair,
+        transport::manager::TransportManager,
+        types::{protocol::ProtocolName, ConnectionId},
+        BandwidthSink,
+    };
+    use std::collections::HashSet;
+
+    /// Create new [`AutoNat`] which serves dial requests of `peer` connected over `address`.
+    fn make_autonat(peer: PeerId, address: Multiaddr) -> (AutoNat, TransportManager) {
+        let (manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let (service, _tx) = TransportService::new(
+            PeerId::random(),
+            ProtocolName::from(config::PROTOCOL_NAME),
+            Vec::new(),
+            Default::default(),
+            handle,
+        );
+        let (config, _event_stream) = ConfigBuilder::new().build();
+
+        let mut autonat = AutoNat::new(service, config, Vec::new());
+        autonat.peers.insert(
+            peer,
+            Endpoint::listener(address, ConnectionId::from(0usize)),
+        );
+
+        (autonat, manager)
+    }
+
+    /// Create dial request of `peer` which lists `addresses`.
+    fn dial_request(peer: PeerId, addresses: Vec<Multiaddr>) -> Vec<u8> {
+        let mut request = schema::Message {
+            dial: Some(Dial {
+                peer: Some(PeerInfo {
+                    id: Some(peer.to_bytes()),
+                    addrs: addresses.into_iter().map(|address| address.to_vec()).collect(),
+                }),
+            }),
+            ..Default::default()
+        };
+        request.set_type(MessageType::Dial);
+
+        request.encode_to_vec()
+    }
+
+    #[tokio::test]
+    async fn dial_back_to_observed_ip() {
+        let peer = PeerId::random();
+        let (autonat, _manager) = make_autonat(peer, "/ip4/1.2.3.4/tcp/5555".parse().unwrap());
+
+        let request = dial_request(
+            peer,
+            vec![
+                "/dns4/victim.example/tcp/443".parse().unwrap(),
+                "/ip4/5.6.7.8/tcp/8888".parse().unwrap(),
+                "/ip4/1.2.3.4/tcp/8888".parse().unwrap(),
+            ],
+        );
+
+        assert_eq!(
+            autonat.dial_back_address(peer, &request),
+            Ok("/ip4/1.2.3.4/tcp/8888"
+                .parse::<Multiaddr>()
+                .unwrap()
+                .with(Protocol::P2p(peer.to_multihash())))
+        );
+    }
+
+    #[tokio::test]
+    async fn dns_address_refused() {
+        let peer = PeerId::random();
+        let (autonat, _manager) = make_autonat(peer, "/ip4/1.2.3.4/tcp/5555".parse().unwrap());
+
+        let request = dial_request(peer, vec!["/dns4/victim.example/tcp/443".parse().unwrap()]);
+
+        assert_eq!(
+            autonat.dial_back_address(peer, &request),
+            Err(ResponseStatus::EDialRefused)
+        );
+    }
+
+    #[tokio::test]
+    async fn peer_without_observed_ip_refused() {
+        let peer = PeerId::random();
+        let (autonat, _manager) = make_autonat(peer, "/dns4/example.com/tcp/5555".parse().unwrap());
+
+        let request = dial_request(peer, vec!["/dns4/victim.example/tcp/443".parse().unwrap()]);
+
+        assert_eq!(
+            autonat.dial_back_address(peer, &request),
+            Err(ResponseStatus::EDialRefused)
+        );
+    }
+}
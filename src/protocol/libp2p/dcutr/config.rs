@@ -0,0 +1,109 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{
+    codec::ProtocolCodec, protocol::libp2p::dcutr::DcutrEvent, types::protocol::ProtocolName,
+    DEFAULT_CHANNEL_SIZE,
+};
+
+use futures::Stream;
+use tokio::sync::mpsc::{channel, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+
+/// DCUtR protocol name as a string.
+pub const PROTOCOL_NAME: &str = "/libp2p/dcutr";
+
+/// Maximum size of a DCUtR message.
+const DCUTR_PAYLOAD_SIZE: usize = 4096;
+
+/// Maximum number of hole punch attempts.
+const MAX_ATTEMPTS: usize = 3;
+
+/// DCUtR configuration.
+pub struct Config {
+    /// Protocol name.
+    pub(crate) protocol: ProtocolName,
+
+    /// Codec used by the protocol.
+    pub(crate) codec: ProtocolCodec,
+
+    /// Maximum number of hole punch attempts.
+    pub(crate) max_attempts: usize,
+
+    /// TX channel for sending events to the user protocol.
+    pub(crate) tx_event: Sender<DcutrEvent>,
+}
+
+impl Config {
+    /// Create new [`Config`] with default values.
+    ///
+    /// Returns a config that is given to `Litep2pConfig` and an event stream for
+    /// [`DcutrEvent`]s.
+    pub fn default() -> (Self, Box<dyn Stream<Item = DcutrEvent> + Send + Unpin>) {
+        ConfigBuilder::new().build()
+    }
+}
+
+/// DCUtR configuration builder.
+pub struct ConfigBuilder {
+    /// Maximum number of hole punch attempts.
+    max_attempts: usize,
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigBuilder {
+    /// Create new default [`Config`] which can be modified by the user.
+    pub fn new() -> Self {
+        Self {
+            max_attempts: MAX_ATTEMPTS,
+        }
+    }
+
+    /// Set maximum number of hole punch attempts.
+    ///
+    /// A failed attempt is retried over the relayed connection until the limit is reached.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Build [`Config`].
+    ///
+    /// Returns a config that is given to `Litep2pConfig` and an event stream for
+    /// [`DcutrEvent`]s.
+    pub fn build(self) -> (Config, Box<dyn Stream<Item = DcutrEvent> + Send + Unpin>) {
+        let (tx_event, rx_event) = channel(DEFAULT_CHANNEL_SIZE);
+
+        (
+            Config {
+                tx_event,
+                max_attempts: self.max_attempts,
+                protocol: ProtocolName::from(PROTOCOL_NAME),
+                codec: ProtocolCodec::UnsignedVarint(Some(DCUTR_PAYLOAD_SIZE)),
+            },
+            Box::new(ReceiverStream::new(rx_event)),
+        )
+    }
+}
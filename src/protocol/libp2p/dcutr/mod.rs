@@ -0,0 +1,658 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! [`/libp2p/dcutr`](https://github.com/libp2p/specs/blob/master/relay/DCUtR.md) implementation.
+//!
+//! When a peer connects to the local node over a relayed connection, the local node initiates a
+//! hole punch: both nodes exchange the addresses they've been observed at over the relayed
+//! connection, measuring the round-trip time in the process, and then dial each other at the same
+//! time so that the packets sent by the other node are let through by the NATs.
+//!
+//! The direct connection is opened as a secondary connection to the peer and it replaces the
+//! relayed connection once the relayed connection is closed.
+
+use crate::{
+    error::{Error, SubstreamError},
+    protocol::{Direction, TransportEvent, TransportService},
+    substream::Substream,
+    transport::Endpoint,
+    types::SubstreamId,
+    PeerId,
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
+use multiaddr::{Multiaddr, Protocol};
+use prost::Message;
+use tokio::sync::mpsc::Sender;
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+pub use config::{Config, ConfigBuilder};
+
+mod config;
+
+mod schema {
+    include!(concat!(env!("OUT_DIR"), "/dcutr.rs"));
+}
+
+use schema::hole_punch::Type as MessageType;
+
+/// Log target for the file.
+const LOG_TARGET: &str = "litep2p::ipfs::dcutr";
+
+/// Timeout for exchanging the addresses over the relayed connection.
+const NEGOTIATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events emitted by the DCUtR protocol.
+#[derive(Debug)]
+pub enum DcutrEvent {
+    /// Relayed connection to `peer` was upgraded to a direct connection.
+    DirectConnectionEstablished {
+        /// Peer ID.
+        peer: PeerId,
+
+        /// Address of the direct connection.
+        address: Multiaddr,
+    },
+
+    /// Relayed connection to `peer` couldn't be upgraded to a direct connection.
+    ///
+    /// If the local node initiated the hole punch, this is reported once all attempts have failed.
+    DirectConnectionFailed {
+        /// Peer ID.
+        peer: PeerId,
+
+        /// Error.
+        error: Error,
+    },
+}
+
+/// State of a hole punch.
+enum HolePunchState {
+    /// Addresses are being exchanged over the relayed connection.
+    Negotiating,
+
+    /// Addresses of the remote peer are being dialed.
+    Dialing {
+        /// Addresses which are still being dialed.
+        addresses: HashSet<Multiaddr>,
+    },
+}
+
+/// Hole punch to a peer connected over a relayed connection.
+struct HolePunch {
+    /// Whether the hole punch was initiated by the local node.
+    initiator: bool,
+
+    /// Number of attempts made so far.
+    attempts: usize,
+
+    /// State of the hole punch.
+    state: HolePunchState,
+}
+
+/// DCUtR protocol.
+pub(crate) struct Dcutr {
+    // Connection service.
+    service: TransportService,
+
+    /// TX channel for sending events to the user protocol.
+    tx: Sender<DcutrEvent>,
+
+    /// Listen addresses of the local node.
+    listen_addresses: Vec<Multiaddr>,
+
+    /// Maximum number of hole punch attempts.
+    max_attempts: usize,
+
+    /// Active hole punches.
+    hole_punches: HashMap<PeerId, HolePunch>,
+
+    /// Pending outbound substreams.
+    pending_opens: HashMap<SubstreamId, PeerId>,
+
+    /// Pending address exchanges.
+    pending_negotiations:
+        FuturesUnordered<BoxFuture<'static, (PeerId, crate::Result<Vec<Multiaddr>>)>>,
+}
+
+impl Dcutr {
+    /// Create new [`Dcutr`] protocol.
+    pub(crate) fn new(
+        service: TransportService,
+        config: Config,
+        listen_addresses: Vec<Multiaddr>,
+    ) -> Self {
+        Self {
+            service,
+            tx: config.tx_event,
+            listen_addresses: listen_addresses
+                .into_iter()
+                .filter(|address| !is_relayed(address))
+                .collect(),
+            max_attempts: config.max_attempts,
+            hole_punches: HashMap::new(),
+            pending_opens: HashMap::new(),
+            pending_negotiations: FuturesUnordered::new(),
+        }
+    }
+
+    /// Connection established to remote peer.
+    ///
+    /// If the remote peer connected to the local node over a relay, initiate a hole punch.
+    async fn on_connection_established(&mut self, peer: PeerId, endpoint: Endpoint) {
+        tracing::trace!(target: LOG_TARGET, ?peer, ?endpoint, "connection established");
+
+        if endpoint.is_listener() && is_relayed(endpoint.address()) {
+            self.initiate_hole_punch(peer, 1usize).await;
+        }
+    }
+
+    /// Secondary connection established to remote peer.
+    async fn on_secondary_connection_established(&mut self, peer: PeerId, endpoint: Endpoint) {
+        if is_relayed(endpoint.address()) || self.hole_punches.remove(&peer).is_none() {
+            return;
+        }
+
+        tracing::debug!(
+            target: LOG_TARGET,
+            ?peer,
+            ?endpoint,
+            "relayed connection upgraded to direct connection",
+        );
+
+        let _ = self
+            .tx
+            .send(DcutrEvent::DirectConnectionEstablished {
+                peer,
+                address: endpoint.address().clone(),
+            })
+            .await;
+    }
+
+    /// Connection closed to remote peer.
+    fn on_connection_closed(&mut self, peer: PeerId) {
+        tracing::trace!(target: LOG_TARGET, ?peer, "connection closed");
+
+        self.hole_punches.remove(&peer);
+    }
+
+    /// Candidate addresses of the local node.
+    ///
+    /// The addresses the local node has been observed at by other peers are preferred.
+    fn candidate_addresses(&self) -> Vec<Multiaddr> {
+        let mut addresses = self
+            .service
+            .external_addresses()
+            .into_iter()
+            .filter(|address| !is_relayed(address))
+            .collect::<Vec<_>>();
+
+        for address in &self.listen_addresses {
+            if !addresses.contains(address) {
+                addresses.push(address.clone());
+            }
+        }
+
+        addresses
+    }
+
+    /// Initiate hole punch to `peer` by opening a substream over the relayed connection.
+    async fn initiate_hole_punch(&mut self, peer: PeerId, attempts: usize) {
+        tracing::trace!(target: LOG_TARGET, ?peer, ?attempts, "initiate hole punch");
+
+        match self.service.open_substream(peer) {
+            Ok(substream_id) => {
+                self.pending_opens.insert(substream_id, peer);
+                self.hole_punches.insert(
+                    peer,
+                    HolePunch {
+                        initiator: true,
+                        attempts,
+                        state: HolePunchState::Negotiating,
+                    },
+                );
+            }
+            Err(error) => {
+                tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to open substream");
+
+                self.hole_punches.remove(&peer);
+                let _ = self.tx.send(DcutrEvent::DirectConnectionFailed { peer, error }).await;
+            }
+        }
+    }
+
+    /// Outbound substream opened, send local addresses to remote peer.
+    fn on_outbound_substream(&mut self, peer: PeerId, substream: Substream) {
+        tracing::trace!(target: LOG_TARGET, ?peer, "outbound substream opened");
+
+        let addresses = self.candidate_addresses();
+
+        self.pending_negotiations.push(Box::pin(async move {
+            match tokio::time::timeout(NEGOTIATION_TIMEOUT, initiate(substream, addresses)).await {
+                Err(_) => (peer, Err(Error::Timeout)),
+                Ok(result) => (peer, result),
+            }
+        }));
+    }
+
+    /// Inbound substream opened, remote peer initiated a hole punch.
+    ///
+    /// If a hole punch to the peer is already in progress, the substream is dropped.
+    fn on_inbound_substream(&mut self, peer: PeerId, substream: Substream) {
+        tracing::trace!(target: LOG_TARGET, ?peer, "inbound substream opened");
+
+        if self.hole_punches.contains_key(&peer) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?peer,
+                "hole punch already in progress, ignoring inbound substream",
+            );
+            return;
+        }
+
+        let addresses = self.candidate_addresses();
+
+        self.hole_punches.insert(
+            peer,
+            HolePunch {
+                initiator: false,
+                attempts: 1usize,
+                state: HolePunchState::Negotiating,
+            },
+        );
+        self.pending_negotiations.push(Box::pin(async move {
+            match tokio::time::timeout(NEGOTIATION_TIMEOUT, respond(substream, addresses)).await {
+                Err(_) => (peer, Err(Error::Timeout)),
+                Ok(result) => (peer, result),
+            }
+        }));
+    }
+
+    /// Addresses exchanged with `peer`, dial all of them at the same time.
+    async fn on_negotiation_finished(
+        &mut self,
+        peer: PeerId,
+        result: crate::Result<Vec<Multiaddr>>,
+    ) {
+        let Some(HolePunch {
+            state: state @ HolePunchState::Negotiating,
+            ..
+        }) = self.hole_punches.get_mut(&peer)
+        else {
+            tracing::trace!(target: LOG_TARGET, ?peer, "hole punch doesn't exist");
+            return;
+        };
+
+        let addresses = match result {
+            Ok(addresses) => addresses,
+            Err(error) => return self.on_hole_punch_failure(peer, error).await,
+        };
+
+        let addresses = addresses
+            .into_iter()
+            .filter(|address| match address.iter().last() {
+                Some(Protocol::P2p(multihash)) => {
+                    PeerId::from_multihash(multihash).ok() == Some(peer)
+                }
+                _ => true,
+            })
+            .filter(|address| !is_relayed(address))
+            .map(|address| {
+                if std::matches!(address.iter().last(), Some(Protocol::P2p(_))) {
+                    return address;
+                }
+
+                address.with(Protocol::P2p(peer.to_multihash()))
+            })
+            .collect::<HashSet<_>>();
+
+        tracing::trace!(target: LOG_TARGET, ?peer, ?addresses, "dial remote peer");
+
+        let mut last_error = Error::NoAddressAvailable(peer);
+        let mut dialed = HashSet::new();
+
+        for address in addresses {
+            match self.service.dial_secondary(address.clone()) {
+                Ok(()) => {
+                    dialed.insert(address);
+                }
+                Err(error) => {
+                    tracing::debug!(target: LOG_TARGET, ?peer, ?address, ?error, "failed to dial");
+                    last_error = error;
+                }
+            }
+        }
+
+        if !dialed.is_empty() {
+            *state = HolePunchState::Dialing { addresses: dialed };
+            return;
+        }
+
+        self.on_hole_punch_failure(peer, last_error).await
+    }
+
+    /// Dial to `peer` failed.
+    ///
+    /// The hole punch fails once dials to all addresses of the peer have failed.
+    async fn on_dial_failure(&mut self, peer: PeerId, address: Multiaddr) {
+        let Some(HolePunch {
+            state: HolePunchState::Dialing { addresses },
+            ..
+        }) = self.hole_punches.get_mut(&peer)
+        else {
+            return;
+        };
+
+        if !addresses.remove(&address) {
+            return;
+        }
+
+        tracing::trace!(target: LOG_TARGET, ?peer, ?address, "failed to dial remote peer");
+
+        if addresses.is_empty() {
+            self.on_hole_punch_failure(peer, Error::NoAddressAvailable(peer)).await
+        }
+    }
+
+    /// Hole punch to `peer` failed.
+    ///
+    /// If the local node initiated the hole punch, it's retried until the attempts run out.
+    async fn on_hole_punch_failure(&mut self, peer: PeerId, error: Error) {
+        let Some(HolePunch {
+            initiator,
+            attempts,
+            ..
+        }) = self.hole_punches.remove(&peer)
+        else {
+            return;
+        };
+
+        tracing::debug!(target: LOG_TARGET, ?peer, ?attempts, ?error, "hole punch failed");
+
+        if initiator && attempts < self.max_attempts {
+            return self.initiate_hole_punch(peer, attempts + 1).await;
+        }
+
+        let _ = self.tx.send(DcutrEvent::DirectConnectionFailed { peer, error }).await;
+    }
+
+    /// Start [`Dcutr`] event loop.
+    pub async fn run(mut self) {
+        tracing::debug!(target: LOG_TARGET, "starting dcutr event loop");
+
+        loop {
+            tokio::select! {
+                event = self.service.next() => match event {
+                    Some(TransportEvent::ConnectionEstablished { peer, endpoint }) => {
+                        self.on_connection_established(peer, endpoint).await;
+                    }
+                    Some(TransportEvent::SecondaryConnectionEstablished { peer, endpoint }) => {
+                        self.on_secondary_connection_established(peer, endpoint).await;
+                    }
+                    Some(TransportEvent::ConnectionClosed { peer }) => {
+                        self.on_connection_closed(peer);
+                    }
+                    Some(TransportEvent::DialFailure { peer, address }) => {
+                        self.on_dial_failure(peer, address).await;
+                    }
+                    Some(TransportEvent::SubstreamOpened {
+                        peer,
+                        substream,
+                        direction,
+                        ..
+                    }) => match direction {
+                        Direction::Inbound => self.on_inbound_substream(peer, substream),
+                        Direction::Outbound(substream_id) => {
+                            match self.pending_opens.remove(&substream_id) {
+                                Some(_) => self.on_outbound_substream(peer, substream),
+                                None => tracing::debug!(
+                                    target: LOG_TARGET,
+                                    ?peer,
+                                    ?substream_id,
+                                    "outbound substream doesn't exist",
+                                ),
+                            }
+                        }
+                    },
+                    Some(TransportEvent::SubstreamOpenFailure { substream, error }) => {
+                        if let Some(peer) = self.pending_opens.remove(&substream) {
+                            tracing::debug!(
+                                target: LOG_TARGET,
+                                ?peer,
+                                ?error,
+                                "failed to open substream",
+                            );
+
+                            // the remote peer doesn't support the protocol so there's no point in
+                            // retrying the hole punch
+                            self.hole_punches.remove(&peer);
+                            let _ = self
+                                .tx
+                                .send(DcutrEvent::DirectConnectionFailed { peer, error })
+                                .await;
+                        }
+                    }
+                    None => return,
+                },
+                event = self.pending_negotiations.next(), if !self.pending_negotiations.is_empty() => {
+                    if let Some((peer, result)) = event {
+                        self.on_negotiation_finished(peer, result).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Check if `address` is a relayed address.
+fn is_relayed(address: &Multiaddr) -> bool {
+    address.iter().any(|protocol| std::matches!(protocol, Protocol::P2pCircuit))
+}
+
+/// Encode hole punch message.
+fn encode_message(r#type: MessageType, addresses: Vec<Multiaddr>) -> Bytes {
+    let mut message = schema::HolePunch {
+        obs_addrs: addresses.into_iter().map(|address| address.to_vec()).collect(),
+        ..Default::default()
+    };
+    message.set_type(r#type);
+
+    message.encode_to_vec().into()
+}
+
+/// Read hole punch message of type `r#type` from `substream` and return its addresses.
+async fn read_message(
+    substream: &mut Substream,
+    r#type: MessageType,
+) -> crate::Result<Vec<Multiaddr>> {
+    let payload = substream
+        .next()
+        .await
+        .ok_or(Error::SubstreamError(SubstreamError::ReadFailure(None)))??;
+    let message = schema::HolePunch::decode(payload.freeze())?;
+
+    if message.r#type() != r#type {
+        return Err(Error::InvalidData);
+    }
+
+    Ok(message
+        .obs_addrs
+        .into_iter()
+        .filter_map(|address| Multiaddr::try_from(address).ok())
+        .collect())
+}
+
+/// Initiate hole punch over `substream`.
+///
+/// Sends `CONNECT` with the local addresses, waits for the remote addresses and sends `SYNC`. The
+/// remote peer starts dialing once it receives `SYNC` so the call returns after half of the
+/// measured round-trip time, at which point the local node should start dialing as well.
+async fn initiate(
+    mut substream: Substream,
+    addresses: Vec<Multiaddr>,
+) -> crate::Result<Vec<Multiaddr>> {
+    let started = Instant::now();
+
+    substream.send_framed(encode_message(MessageType::Connect, addresses)).await?;
+    let addresses = read_message(&mut substream, MessageType::Connect).await?;
+    let rtt = started.elapsed();
+
+    substream.send_framed(encode_message(MessageType::Sync, Vec::new())).await?;
+    substream.close().await;
+
+    tokio::time::sleep(rtt / 2).await;
+
+    Ok(addresses)
+}
+
+/// Respond to a hole punch initiated over `substream`.
+///
+/// Waits for `CONNECT` from the remote peer, answers it with the local addresses and returns the
+/// remote addresses once `SYNC` has been received, at which point the local node should start
+/// dialing.
+async fn respond(
+    mut substream: Substream,
+    addresses: Vec<Multiaddr>,
+) -> crate::Result<Vec<Multiaddr>> {
+    let remote_addresses = read_message(&mut substream, MessageType::Connect).await?;
+
+    substream.send_framed(encode_message(MessageType::Connect, addresses)).await?;
+    read_message(&mut substream, MessageType::Sync).await?;
+    substream.close().await;
+
+    Ok(remote_addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use futures::{
+        channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        Sink, Stream,
+    };
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    /// One end of an in-memory message channel.
+    #[derive(Debug)]
+    struct Channel {
+        tx: UnboundedSender<Bytes>,
+        rx: UnboundedReceiver<Bytes>,
+    }
+
+    impl Sink<Bytes> for Channel {
+        type Error = Error;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), Error> {
+            self.tx.unbounded_send(item).map_err(|_| Error::Unknown)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            self.tx.close_channel();
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl Stream for Channel {
+        type Item = crate::Result<BytesMut>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.rx
+                .poll_next_unpin(cx)
+                .map(|bytes| bytes.map(|bytes| Ok(BytesMut::from(&bytes[..]))))
+        }
+    }
+
+    /// Create two substreams which are connected to each other.
+    fn substreams() -> (Substream, Substream) {
+        let (tx1, rx1) = unbounded();
+        let (tx2, rx2) = unbounded();
+
+        (
+            Substream::new_mock(
+                PeerId::random(),
+                SubstreamId::from(0usize),
+                Box::new(Channel { tx: tx1, rx: rx2 }),
+            ),
+            Substream::new_mock(
+                PeerId::random(),
+                SubstreamId::from(0usize),
+                Box::new(Channel { tx: tx2, rx: rx1 }),
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn addresses_exchanged() {
+        let (substream1, substream2) = substreams();
+        let addresses1: Vec<Multiaddr> = vec!["/ip4/1.1.1.1/tcp/8888".parse().unwrap()];
+        let addresses2: Vec<Multiaddr> = vec![
+            "/ip4/2.2.2.2/tcp/8888".parse().unwrap(),
+            "/ip6/::2/udp/8888/quic-v1".parse().unwrap(),
+        ];
+
+        let (result1, result2) = tokio::join!(
+            initiate(substream1, addresses1.clone()),
+            respond(substream2, addresses2.clone()),
+        );
+
+        assert_eq!(result1.unwrap(), addresses2);
+        assert_eq!(result2.unwrap(), addresses1);
+    }
+
+    #[tokio::test]
+    async fn sync_before_connect_rejected() {
+        let (mut substream1, substream2) = substreams();
+
+        substream1
+            .send_framed(encode_message(MessageType::Sync, Vec::new()))
+            .await
+            .unwrap();
+
+        assert!(std::matches!(
+            respond(substream2, Vec::new()).await,
+            Err(Error::InvalidData)
+        ));
+    }
+
+    #[tokio::test]
+    async fn closed_substream_fails_hole_punch() {
+        let (substream1, substream2) = substreams();
+        drop(substream2);
+
+        assert!(initiate(substream1, Vec::new()).await.is_err());
+    }
+}
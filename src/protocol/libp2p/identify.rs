@@ -272,6 +272,13 @@ impl Identify {
             listen_addrs: self
                 .listen_addresses
                 .iter()
+                .cloned()
+                .chain(
+                    self.service
+                        .external_addresses()
+                        .into_iter()
+                        .filter(|address| !self.listen_addresses.contains(address)),
+                )
                 .map(|address| address.to_vec())
                 .collect::<Vec<_>>(),
             observed_addr,
@@ -395,6 +402,10 @@ impl Identify {
                 _ = self.pending_inbound.next(), if !self.pending_inbound.is_empty() => {}
                 event = self.pending_outbound.next(), if !self.pending_outbound.is_empty() => match event {
                     Some(Ok(response)) => {
+                        if let Some(address) = &response.observed_address {
+                            self.service.add_external_address(response.peer, address.clone());
+                        }
+
                         let _ = self.tx
                             .send(IdentifyEvent::PeerIdentified {
                                 peer: response.peer,
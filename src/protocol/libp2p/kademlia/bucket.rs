@@ -55,6 +55,8 @@ impl<'a> KBucketEntry<'a> {
 }
 
 /// Kademlia k-bucket.
+///
+/// Nodes are kept in the order they were inserted, the least recently inserted node first.
 pub struct KBucket {
     // TODO: store peers in a btreemap with increasing distance from local key?
     nodes: Vec<KademliaPeer>,
@@ -87,16 +89,30 @@ impl KBucket {
             return KBucketEntry::Vacant(&mut self.nodes[len]);
         }
 
-        for i in 0..self.nodes.len() {
-            match self.nodes[i].connection {
-                ConnectionType::NotConnected | ConnectionType::CannotConnect => {
-                    return KBucketEntry::Vacant(&mut self.nodes[i]);
-                }
-                _ => continue,
+        // evict the least recently inserted node that is not connected and move its slot
+        // to the back of the bucket so the new node becomes the most recently inserted one
+        let evictable = self.nodes.iter().position(|node| {
+            std::matches!(
+                node.connection,
+                ConnectionType::NotConnected | ConnectionType::CannotConnect
+            )
+        });
+
+        match evictable {
+            Some(index) => {
+                let node = self.nodes.remove(index);
+                self.nodes.push(node);
+
+                let len = self.nodes.len() - 1;
+                KBucketEntry::Vacant(&mut self.nodes[len])
             }
+            None => KBucketEntry::NoSlot,
         }
+    }
 
-        KBucketEntry::NoSlot
+    /// Get the number of peers in the k-bucket.
+    pub fn num_entries(&self) -> usize {
+        self.nodes.iter().filter(|node| !node.addresses.is_empty()).count()
     }
 
     /// Get iterator over the k-bucket, sorting the k-bucket entries in increasing order
@@ -31,7 +31,11 @@ use crate::{
 use multiaddr::Multiaddr;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicUsize, Arc},
+    time::Duration,
+};
 
 /// Default TTL for the records.
 const DEFAULT_TTL: u64 = 36 * 60 * 60;
@@ -65,6 +69,12 @@ pub struct Config {
     /// Incoming records validation mode.
     pub(super) validation_mode: IncomingRecordValidationMode,
 
+    /// Routing table refresh interval, if enabled.
+    pub(super) refresh_interval: Option<Duration>,
+
+    /// Next query ID, shared with `KademliaHandle`.
+    pub(super) next_query_id: Arc<AtomicUsize>,
+
     /// TX channel for sending events to `KademliaHandle`.
     pub(super) event_tx: Sender<KademliaEvent>,
 
@@ -80,9 +90,11 @@ impl Config {
         update_mode: RoutingTableUpdateMode,
         validation_mode: IncomingRecordValidationMode,
         record_ttl: Duration,
+        refresh_interval: Option<Duration>,
     ) -> (Self, KademliaHandle) {
         let (cmd_tx, cmd_rx) = channel(DEFAULT_CHANNEL_SIZE);
         let (event_tx, event_rx) = channel(DEFAULT_CHANNEL_SIZE);
+        let next_query_id = Arc::new(AtomicUsize::new(0usize));
 
         // if no protocol names were provided, use the default protocol
         if protocol_names.is_empty() {
@@ -97,10 +109,12 @@ impl Config {
                 codec: ProtocolCodec::UnsignedVarint(None),
                 replication_factor,
                 known_peers,
+                refresh_interval,
+                next_query_id: Arc::clone(&next_query_id),
                 cmd_rx,
                 event_tx,
             },
-            KademliaHandle::new(cmd_tx, event_rx, record_ttl),
+            KademliaHandle::new(cmd_tx, event_rx, record_ttl, next_query_id),
         )
     }
 
@@ -113,6 +127,7 @@ impl Config {
             RoutingTableUpdateMode::Automatic,
             IncomingRecordValidationMode::Automatic,
             Duration::from_secs(DEFAULT_TTL),
+            None,
         )
     }
 }
@@ -137,6 +152,9 @@ pub struct ConfigBuilder {
 
     /// Default TTL for the records.
     pub(super) record_ttl: Duration,
+
+    /// Routing table refresh interval, if enabled.
+    pub(super) refresh_interval: Option<Duration>,
 }
 
 impl Default for ConfigBuilder {
@@ -155,6 +173,7 @@ impl ConfigBuilder {
             update_mode: RoutingTableUpdateMode::Automatic,
             validation_mode: IncomingRecordValidationMode::Automatic,
             record_ttl: Duration::from_secs(DEFAULT_TTL),
+            refresh_interval: None,
         }
     }
 
@@ -207,6 +226,17 @@ impl ConfigBuilder {
         self
     }
 
+    /// Periodically refresh the routing table.
+    ///
+    /// Every `refresh_interval`, a `FIND_NODE` query is started for a random key in the range of
+    /// each k-bucket that isn't full, starting from the closest non-empty k-bucket.
+    ///
+    /// If unspecified, the routing table is not refreshed.
+    pub fn with_routing_table_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = Some(refresh_interval);
+        self
+    }
+
     /// Build Kademlia [`Config`].
     pub fn build(self) -> (Config, KademliaHandle) {
         Config::new(
@@ -216,6 +246,7 @@ impl ConfigBuilder {
             self.update_mode,
             self.validation_mode,
             self.record_ttl,
+            self.refresh_interval,
         )
     }
 }
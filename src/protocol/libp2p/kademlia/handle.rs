@@ -30,6 +30,10 @@ use tokio::sync::mpsc::{Receiver, Sender};
 use std::{
     num::NonZeroUsize,
     pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
     time::{Duration, Instant},
 };
@@ -166,6 +170,21 @@ pub enum KademliaEvent {
         peers: Vec<PeerId>,
     },
 
+    /// K-bucket membership changed.
+    ///
+    /// `peer` was either inserted into the k-bucket or evicted from it to make room for a new
+    /// peer when the k-bucket was full.
+    KBucketUpdate {
+        /// Peer ID.
+        peer: PeerId,
+
+        /// Index of the k-bucket.
+        bucket_index: usize,
+
+        /// `true` if the peer was inserted, `false` if it was evicted.
+        added: bool,
+    },
+
     /// `GET_VALUE` query succeeded.
     GetRecordSuccess {
         /// Query ID.
@@ -221,8 +240,8 @@ pub struct KademliaHandle {
     /// RX channel for receiving events from `Kademlia`.
     event_rx: Receiver<KademliaEvent>,
 
-    /// Next query ID.
-    next_query_id: usize,
+    /// Next query ID, shared with `Kademlia`.
+    next_query_id: Arc<AtomicUsize>,
 
     /// Default TTL for the records.
     record_ttl: Duration,
@@ -234,21 +253,19 @@ impl KademliaHandle {
         cmd_tx: Sender<KademliaCommand>,
         event_rx: Receiver<KademliaEvent>,
         record_ttl: Duration,
+        next_query_id: Arc<AtomicUsize>,
     ) -> Self {
         Self {
             cmd_tx,
             event_rx,
-            next_query_id: 0usize,
+            next_query_id,
             record_ttl,
         }
     }
 
     /// Allocate next query ID.
     fn next_query_id(&mut self) -> QueryId {
-        let query_id = self.next_query_id;
-        self.next_query_id += 1;
-
-        QueryId(query_id)
+        QueryId(self.next_query_id.fetch_add(1usize, Ordering::Relaxed))
     }
 
     /// Add known peer.
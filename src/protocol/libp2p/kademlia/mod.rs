@@ -43,9 +43,18 @@ use crate::{
 use bytes::{Bytes, BytesMut};
 use futures::StreamExt;
 use multiaddr::Multiaddr;
-use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::{
+    sync::mpsc::{Receiver, Sender},
+    time::Interval,
+};
 
-use std::collections::{hash_map::Entry, HashMap};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 pub use self::handle::RecordsType;
 pub use config::{Config, ConfigBuilder};
@@ -152,6 +161,15 @@ pub(crate) struct Kademlia {
 
     /// Query executor.
     executor: QueryExecutor,
+
+    /// Routing table refresh timer, if enabled.
+    refresh_timer: Option<Interval>,
+
+    /// Pending routing table refresh queries.
+    refresh_queries: HashSet<QueryId>,
+
+    /// Next query ID, shared with `KademliaHandle`.
+    next_query_id: Arc<AtomicUsize>,
 }
 
 impl Kademlia {
@@ -183,6 +201,45 @@ impl Kademlia {
             validation_mode: config.validation_mode,
             replication_factor: config.replication_factor,
             engine: QueryEngine::new(local_peer_id, config.replication_factor, PARALLELISM_FACTOR),
+            refresh_timer: config.refresh_interval.map(|interval| {
+                tokio::time::interval_at(tokio::time::Instant::now() + interval, interval)
+            }),
+            refresh_queries: HashSet::new(),
+            next_query_id: config.next_query_id,
+        }
+    }
+
+    /// Allocate next query ID.
+    fn next_query_id(&mut self) -> QueryId {
+        QueryId(self.next_query_id.fetch_add(1usize, Ordering::Relaxed))
+    }
+
+    /// Wait until the routing table should be refreshed.
+    ///
+    /// Never resolves if the routing table refresh is disabled.
+    async fn next_refresh(refresh_timer: &mut Option<Interval>) {
+        match refresh_timer {
+            Some(timer) => {
+                timer.tick().await;
+            }
+            None => futures::future::pending().await,
+        }
+    }
+
+    /// Refresh routing table by starting a `FIND_NODE` query for a random key in the range of
+    /// each k-bucket that isn't full.
+    fn refresh_routing_table(&mut self) {
+        for target in self.routing_table.refresh_targets() {
+            let query_id = self.next_query_id();
+
+            tracing::trace!(target: LOG_TARGET, ?query_id, ?target, "refresh k-bucket");
+
+            self.refresh_queries.insert(query_id);
+            self.engine.start_find_node(
+                query_id,
+                target,
+                self.routing_table.closest(Key::from(target), self.replication_factor).into(),
+            );
         }
     }
 
@@ -360,17 +417,30 @@ impl Kademlia {
             self.service.add_known_address(&info.peer, info.addresses.iter().cloned());
 
             if std::matches!(self.update_mode, RoutingTableUpdateMode::Automatic) {
-                self.routing_table.add_known_peer(
-                    info.peer,
-                    info.addresses.clone(),
-                    self.peers
-                        .get(&info.peer)
-                        .map_or(ConnectionType::NotConnected, |_| ConnectionType::Connected),
-                );
+                self.add_known_peer(info.peer, info.addresses.clone()).await;
             }
         }
     }
 
+    /// Add `peer` to the routing table and inform user about k-bucket membership changes.
+    async fn add_known_peer(&mut self, peer: PeerId, addresses: Vec<Multiaddr>) {
+        let connection = self
+            .peers
+            .get(&peer)
+            .map_or(ConnectionType::NotConnected, |_| ConnectionType::Connected);
+
+        for update in self.routing_table.add_known_peer(peer, addresses, connection) {
+            let _ = self
+                .event_tx
+                .send(KademliaEvent::KBucketUpdate {
+                    peer: update.peer,
+                    bucket_index: update.bucket_index,
+                    added: update.added,
+                })
+                .await;
+        }
+    }
+
     /// Handle received message.
     async fn on_message_received(
         &mut self,
@@ -591,6 +661,11 @@ impl Kademlia {
                     "`FIND_NODE` succeeded",
                 );
 
+                // found peers have already been added to the routing table
+                if self.refresh_queries.remove(&query) {
+                    return Ok(());
+                }
+
                 let _ = self
                     .event_tx
                     .send(KademliaEvent::FindNodeSuccess {
@@ -685,6 +760,10 @@ impl Kademlia {
             QueryAction::QueryFailed { query } => {
                 tracing::debug!(target: LOG_TARGET, ?query, "query failed");
 
+                if self.refresh_queries.remove(&query) {
+                    return Ok(());
+                }
+
                 let _ = self.event_tx.send(KademliaEvent::QueryFailed { query_id: query }).await;
                 Ok(())
             }
@@ -734,8 +813,10 @@ impl Kademlia {
                         self.on_substream_open_failure(substream, error).await;
                     }
                     Some(TransportEvent::DialFailure { peer, address }) => self.on_dial_failure(peer, address),
+                    Some(TransportEvent::SecondaryConnectionEstablished { .. }) => {}
                     None => return Err(Error::EssentialTaskClosed),
                 },
+                _ = Self::next_refresh(&mut self.refresh_timer) => self.refresh_routing_table(),
                 context = self.executor.next() => {
                     let QueryContext { peer, query_id, result } = context.unwrap();
 
@@ -844,13 +925,7 @@ impl Kademlia {
                                 "add known peer",
                             );
 
-                            self.routing_table.add_known_peer(
-                                peer,
-                                addresses.clone(),
-                                self.peers
-                                    .get(&peer)
-                                    .map_or(ConnectionType::NotConnected, |_| ConnectionType::Connected),
-                            );
+                            self.add_known_peer(peer, addresses.clone()).await;
                             self.service.add_known_address(&peer, addresses.into_iter());
 
                         }
@@ -880,8 +955,11 @@ mod tests {
         codec::ProtocolCodec, crypto::ed25519::Keypair, transport::manager::TransportManager,
         types::protocol::ProtocolName, BandwidthSink,
     };
+    use futures::FutureExt;
     use tokio::sync::mpsc::channel;
 
+    use std::time::Duration;
+
     #[allow(unused)]
     struct Context {
         _cmd_tx: Sender<KademliaCommand>,
@@ -889,6 +967,12 @@ mod tests {
     }
 
     fn make_kademlia() -> (Kademlia, Context, TransportManager) {
+        make_kademlia_with_refresh_interval(None)
+    }
+
+    fn make_kademlia_with_refresh_interval(
+        refresh_interval: Option<Duration>,
+    ) -> (Kademlia, Context, TransportManager) {
         let (manager, handle) = TransportManager::new(
             Keypair::generate(),
             HashSet::new(),
@@ -914,6 +998,8 @@ mod tests {
             replication_factor: 20usize,
             update_mode: RoutingTableUpdateMode::Automatic,
             validation_mode: IncomingRecordValidationMode::Automatic,
+            refresh_interval,
+            next_query_id: Default::default(),
             event_tx,
             cmd_rx,
         };
@@ -996,4 +1082,40 @@ mod tests {
         let record = kademlia.store.get(&key).unwrap();
         assert_eq!(record.value, vec![0x2]);
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn routing_table_refreshed_periodically() {
+        let (mut kademlia, _context, _manager) =
+            make_kademlia_with_refresh_interval(Some(Duration::from_secs(60)));
+
+        for _ in 0..5 {
+            kademlia.routing_table.add_known_peer(
+                PeerId::random(),
+                vec!["/ip6/::1/tcp/8888".parse().unwrap()],
+                ConnectionType::NotConnected,
+            );
+        }
+
+        // refresh is not due yet
+        assert!(Kademlia::next_refresh(&mut kademlia.refresh_timer).now_or_never().is_none());
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert!(Kademlia::next_refresh(&mut kademlia.refresh_timer).now_or_never().is_some());
+
+        kademlia.refresh_routing_table();
+        assert!(!kademlia.refresh_queries.is_empty());
+
+        // and the next refresh is scheduled one interval later
+        assert!(Kademlia::next_refresh(&mut kademlia.refresh_timer).now_or_never().is_none());
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(Kademlia::next_refresh(&mut kademlia.refresh_timer).now_or_never().is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn routing_table_refresh_disabled_by_default() {
+        let (mut kademlia, _context, _manager) = make_kademlia();
+
+        tokio::time::advance(Duration::from_secs(24 * 60 * 60)).await;
+        assert!(Kademlia::next_refresh(&mut kademlia.refresh_timer).now_or_never().is_none());
+    }
 }
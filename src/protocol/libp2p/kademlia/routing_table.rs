@@ -30,7 +30,8 @@ use crate::{
 };
 
 use multiaddr::{Multiaddr, Protocol};
-use multihash::Multihash;
+
+use std::collections::HashMap;
 
 /// Number of k-buckets.
 const NUM_BUCKETS: usize = 256;
@@ -38,6 +39,25 @@ const NUM_BUCKETS: usize = 256;
 /// Logging target for the file.
 const LOG_TARGET: &str = "litep2p::ipfs::kademlia::routing_table";
 
+/// Size of a k-bucket.
+const K_VALUE: usize = 20usize;
+
+/// How many random peer IDs are generated at most when looking for refresh targets.
+const MAX_REFRESH_TARGET_ATTEMPTS: usize = 4096usize;
+
+/// K-bucket membership change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BucketUpdate {
+    /// Peer which was added to or removed from the k-bucket.
+    pub peer: PeerId,
+
+    /// Index of the k-bucket.
+    pub bucket_index: usize,
+
+    /// `true` if the peer was added, `false` if it was evicted.
+    pub added: bool,
+}
+
 pub struct RoutingTable {
     /// Local key.
     local_key: Key<PeerId>,
@@ -124,12 +144,15 @@ impl RoutingTable {
     ///
     /// In order to bootstrap the lookup process, the routing table must be aware of at least one
     /// node and of its addresses. The insert operation is ignored
+    ///
+    /// Returns the changes made to k-bucket membership, if any. If the k-bucket was full, the least
+    /// recently inserted peer which is not connected is evicted to make room for `peer`.
     pub fn add_known_peer(
         &mut self,
         peer: PeerId,
         addresses: Vec<Multiaddr>,
         connection: ConnectionType,
-    ) {
+    ) -> Vec<BucketUpdate> {
         tracing::trace!(
             target: LOG_TARGET,
             ?peer,
@@ -141,22 +164,48 @@ impl RoutingTable {
         // TODO: this has to be moved elsewhere at some point
         let addresses: Vec<Multiaddr> = addresses
             .into_iter()
-            .filter_map(|address| {
+            .map(|address| {
                 let last = address.iter().last();
                 if std::matches!(last, Some(Protocol::P2p(_))) {
-                    Some(address)
+                    address
                 } else {
-                    Some(address.with(Protocol::P2p(Multihash::from_bytes(&peer.to_bytes()).ok()?)))
+                    address.with(Protocol::P2p(peer.to_multihash()))
                 }
             })
             .collect();
 
-        match (self.entry(Key::from(peer)), addresses.is_empty()) {
+        let key = Key::from(peer);
+        let bucket_index =
+            BucketIndex::new(&self.local_key.distance(&key)).map(|index| index.get());
+
+        match (self.entry(key), addresses.is_empty()) {
             (KBucketEntry::Occupied(entry), false) => {
                 entry.addresses = addresses;
             }
             (mut entry @ KBucketEntry::Vacant(_), false) => {
+                let bucket_index = bucket_index.expect("local node to have `LocalNode` entry");
+
+                // vacant entry is either a fresh slot or holds a disconnected peer
+                // which is evicted, fresh slots never have any addresses
+                let evicted = match &entry {
+                    KBucketEntry::Vacant(old) if !old.addresses.is_empty() => Some(old.peer),
+                    _ => None,
+                };
                 entry.insert(KademliaPeer::new(peer, addresses, connection));
+
+                return evicted
+                    .map(|peer| BucketUpdate {
+                        peer,
+                        bucket_index,
+                        added: false,
+                    })
+                    .into_iter()
+                    .chain(std::iter::once(BucketUpdate {
+                        peer,
+                        bucket_index,
+                        added: true,
+                    }))
+                    .collect();
             }
             (KBucketEntry::LocalNode, _) => tracing::warn!(
                 target: LOG_TARGET,
@@ -174,6 +223,44 @@ impl RoutingTable {
                 "tried to add zero addresses to the routing table",
             ),
         }
+
+        Vec::new()
+    }
+
+    /// Get targets for refreshing k-buckets.
+    ///
+    /// Returns a random peer ID for each k-bucket that isn't full, starting from the closest
+    /// non-empty k-bucket. Since keys are hashes of peer IDs, random peer IDs are generated until
+    /// each k-bucket has a target or [`MAX_REFRESH_TARGET_ATTEMPTS`] is reached, so k-buckets
+    /// that are very close to the local key may not get a target.
+    pub fn refresh_targets(&self) -> Vec<PeerId> {
+        let Some(closest) = self.buckets.iter().position(|bucket| bucket.num_entries() != 0) else {
+            return Vec::new();
+        };
+
+        let mut targets = (closest..NUM_BUCKETS)
+            .filter(|index| self.buckets[*index].num_entries() < K_VALUE)
+            .map(|index| (index, None))
+            .collect::<HashMap<usize, Option<PeerId>>>();
+        let mut remaining = targets.len();
+
+        for _ in 0..MAX_REFRESH_TARGET_ATTEMPTS {
+            if remaining == 0 {
+                break;
+            }
+
+            let peer = PeerId::random();
+            let Some(index) = BucketIndex::new(&self.local_key.distance(&Key::from(peer))) else {
+                continue;
+            };
+
+            if let Some(target @ None) = targets.get_mut(&index.get()) {
+                *target = Some(peer);
+                remaining -= 1;
+            }
+        }
+
+        targets.into_values().flatten().collect()
     }
 
     /// Get `limit` closest peers to `target` from the k-buckets.
@@ -492,6 +579,114 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn least_recently_inserted_disconnected_peer_evicted() {
+        let own_peer_id = PeerId::random();
+        let own_key = Key::from(own_peer_id);
+        let mut table = RoutingTable::new(own_key.clone());
+        let address: Multiaddr = "/ip6/::1/tcp/8888".parse().unwrap();
+
+        // generate peers which fall into the same k-bucket as their key matches the peer ID
+        let mut peers = std::iter::repeat_with(PeerId::random)
+            .filter(|peer| {
+                BucketIndex::new(&own_key.distance(&Key::from(*peer))) == Some(BucketIndex(255))
+            })
+            .take(23);
+
+        // fill the k-bucket
+        let inserted = (0..20)
+            .map(|_| {
+                let peer = peers.next().unwrap();
+                let updates =
+                    table.add_known_peer(peer, vec![address.clone()], ConnectionType::NotConnected);
+                assert_eq!(
+                    updates,
+                    vec![BucketUpdate {
+                        peer,
+                        bucket_index: 255,
+                        added: true
+                    }]
+                );
+
+                peer
+            })
+            .collect::<Vec<_>>();
+
+        // the oldest peer is evicted when the k-bucket is full
+        let peer = peers.next().unwrap();
+        assert_eq!(
+            table.add_known_peer(peer, vec![address.clone()], ConnectionType::NotConnected),
+            vec![
+                BucketUpdate {
+                    peer: inserted[0],
+                    bucket_index: 255,
+                    added: false
+                },
+                BucketUpdate {
+                    peer,
+                    bucket_index: 255,
+                    added: true
+                },
+            ]
+        );
+
+        // the oldest peer is connected so the next oldest disconnected peer is evicted
+        match table.entry(Key::from(inserted[1])) {
+            KBucketEntry::Occupied(entry) => entry.connection = ConnectionType::Connected,
+            state => panic!("invalid state for `KBucketEntry`: {state:?}"),
+        }
+
+        let peer = peers.next().unwrap();
+        assert_eq!(
+            table.add_known_peer(peer, vec![address.clone()], ConnectionType::NotConnected),
+            vec![
+                BucketUpdate {
+                    peer: inserted[2],
+                    bucket_index: 255,
+                    added: false
+                },
+                BucketUpdate {
+                    peer,
+                    bucket_index: 255,
+                    added: true
+                },
+            ]
+        );
+
+        // updating addresses of a known peer doesn't change k-bucket membership
+        assert!(table
+            .add_known_peer(peer, vec![address.clone()], ConnectionType::NotConnected)
+            .is_empty());
+    }
+
+    #[test]
+    fn refresh_targets_fall_into_non_full_buckets() {
+        let own_key = Key::from(PeerId::random());
+        let mut table = RoutingTable::new(own_key.clone());
+        assert!(table.refresh_targets().is_empty());
+
+        // add a peer into k-bucket 253
+        let peer = std::iter::repeat_with(PeerId::random)
+            .find(|peer| {
+                BucketIndex::new(&own_key.distance(&Key::from(*peer))) == Some(BucketIndex(253))
+            })
+            .unwrap();
+        table.add_known_peer(
+            peer,
+            vec!["/ip6/::1/tcp/8888".parse().unwrap()],
+            ConnectionType::NotConnected,
+        );
+
+        let mut buckets = table
+            .refresh_targets()
+            .into_iter()
+            .map(|target| BucketIndex::new(&own_key.distance(&Key::from(target))).unwrap().get())
+            .collect::<Vec<_>>();
+        buckets.sort();
+
+        assert_eq!(buckets, vec![253, 254, 255]);
+    }
+
     #[test]
     fn closest_buckets_iterator_set_lsb() {
         // Test zooming-in & zooming-out of the iterator using a toy example with set LSB.
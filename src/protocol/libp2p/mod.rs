@@ -20,7 +20,11 @@
 
 //! Supported [`libp2p`](https://libp2p.io/) protocols.
 
+pub mod autonat;
 pub mod bitswap;
+pub mod dcutr;
 pub mod identify;
 pub mod kademlia;
 pub mod ping;
+pub mod pubsub;
+pub mod relay;
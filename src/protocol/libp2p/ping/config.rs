@@ -19,13 +19,16 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::{
-    codec::ProtocolCodec, protocol::libp2p::ping::PingEvent, types::protocol::ProtocolName,
-    DEFAULT_CHANNEL_SIZE,
+    codec::ProtocolCodec,
+    protocol::libp2p::ping::{PingEvent, PingHandle, RttWindow},
+    types::protocol::ProtocolName,
+    PeerId, DEFAULT_CHANNEL_SIZE,
 };
 
-use futures::Stream;
+use parking_lot::RwLock;
 use tokio::sync::mpsc::{channel, Sender};
-use tokio_stream::wrappers::ReceiverStream;
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 /// IPFS Ping protocol name as a string.
 pub const PROTOCOL_NAME: &str = "/ipfs/ping/1.0.0";
@@ -36,6 +39,15 @@ const PING_PAYLOAD_SIZE: usize = 32;
 /// Maximum PING failures.
 const MAX_FAILURES: usize = 3;
 
+/// Interval between outbound pings.
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Timeout for a single ping.
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of round-trip time measurements used to compute RTT statistics.
+const RTT_WINDOW_SIZE: usize = 10;
+
 /// Ping configuration.
 pub struct Config {
     /// Protocol name.
@@ -47,25 +59,43 @@ pub struct Config {
     /// Maximum failures before the peer is considered unreachable.
     pub(crate) max_failures: usize,
 
+    /// Interval between outbound pings.
+    pub(crate) interval: Duration,
+
+    /// Timeout for a single ping.
+    pub(crate) timeout: Duration,
+
+    /// Number of round-trip time measurements used to compute RTT statistics.
+    pub(crate) rtt_window_size: usize,
+
     /// TX channel for sending events to the user protocol.
     pub(crate) tx_event: Sender<PingEvent>,
+
+    /// Recent round-trip times of connected peers, shared with [`PingHandle`].
+    pub(super) rtts: Arc<RwLock<HashMap<PeerId, RttWindow>>>,
 }
 
 impl Config {
     /// Create new [`Config`] with default values.
     ///
-    /// Returns a config that is given to `Litep2pConfig` and an event stream for [`PingEvent`]s.
-    pub fn default() -> (Self, Box<dyn Stream<Item = PingEvent> + Send + Unpin>) {
+    /// Returns a config that is given to `Litep2pConfig` and a [`PingHandle`] which emits
+    /// [`PingEvent`]s.
+    pub fn default() -> (Self, PingHandle) {
         let (tx_event, rx_event) = channel(DEFAULT_CHANNEL_SIZE);
+        let rtts = Arc::new(RwLock::new(HashMap::new()));
 
         (
             Self {
                 tx_event,
+                rtts: Arc::clone(&rtts),
                 max_failures: MAX_FAILURES,
+                interval: PING_INTERVAL,
+                timeout: PING_TIMEOUT,
+                rtt_window_size: RTT_WINDOW_SIZE,
                 protocol: ProtocolName::from(PROTOCOL_NAME),
                 codec: ProtocolCodec::Identity(PING_PAYLOAD_SIZE),
             },
-            Box::new(ReceiverStream::new(rx_event)),
+            PingHandle::new(rx_event, rtts),
         )
     }
 }
@@ -80,6 +110,15 @@ pub struct ConfigBuilder {
 
     /// Maximum failures before the peer is considered unreachable.
     max_failures: usize,
+
+    /// Interval between outbound pings.
+    interval: Duration,
+
+    /// Timeout for a single ping.
+    timeout: Duration,
+
+    /// Number of round-trip time measurements used to compute RTT statistics.
+    rtt_window_size: usize,
 }
 
 impl Default for ConfigBuilder {
@@ -93,29 +132,87 @@ impl ConfigBuilder {
     pub fn new() -> Self {
         Self {
             max_failures: MAX_FAILURES,
+            interval: PING_INTERVAL,
+            timeout: PING_TIMEOUT,
+            rtt_window_size: RTT_WINDOW_SIZE,
             protocol: ProtocolName::from(PROTOCOL_NAME),
             codec: ProtocolCodec::Identity(PING_PAYLOAD_SIZE),
         }
     }
 
     /// Set maximum failures the protocol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_failures` is zero.
     pub fn with_max_failure(mut self, max_failures: usize) -> Self {
+        assert!(max_failures != 0);
+
         self.max_failures = max_failures;
         self
     }
 
+    /// Set the interval between outbound pings.
+    ///
+    /// Defaults to 15 seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        assert!(!interval.is_zero());
+
+        self.interval = interval;
+        self
+    }
+
+    /// Set the timeout for a single ping.
+    ///
+    /// Pings that don't complete within the timeout count as failures. Defaults to 10 seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout` is zero.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        assert!(!timeout.is_zero());
+
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set how many of the most recent round-trip time measurements are used to compute
+    /// [`RttStats`](crate::protocol::libp2p::ping::RttStats), which are queried with
+    /// [`PingHandle::rtt_stats()`].
+    ///
+    /// Defaults to 10.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rtt_window_size` is zero.
+    pub fn with_rtt_window_size(mut self, rtt_window_size: usize) -> Self {
+        assert!(rtt_window_size != 0);
+
+        self.rtt_window_size = rtt_window_size;
+        self
+    }
+
     /// Build [`Config`].
-    pub fn build(self) -> (Config, Box<dyn Stream<Item = PingEvent> + Send + Unpin>) {
+    pub fn build(self) -> (Config, PingHandle) {
         let (tx_event, rx_event) = channel(DEFAULT_CHANNEL_SIZE);
+        let rtts = Arc::new(RwLock::new(HashMap::new()));
 
         (
             Config {
                 tx_event,
+                rtts: Arc::clone(&rtts),
                 max_failures: self.max_failures,
+                interval: self.interval,
+                timeout: self.timeout,
+                rtt_window_size: self.rtt_window_size,
                 protocol: self.protocol,
                 codec: self.codec,
             },
-            Box::new(ReceiverStream::new(rx_event)),
+            PingHandle::new(rx_event, rtts),
         )
     }
 }
@@ -28,11 +28,19 @@ use crate::{
     PeerId,
 };
 
-use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
-use tokio::sync::mpsc::Sender;
+use futures::{future::BoxFuture, stream::FuturesUnordered, Stream, StreamExt};
+use parking_lot::RwLock;
+use tokio::{
+    sync::mpsc::{Receiver, Sender},
+    time::MissedTickBehavior,
+};
+use tokio_stream::{wrappers::IntervalStream, StreamMap};
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, VecDeque},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
     time::{Duration, Instant},
 };
 
@@ -40,12 +48,11 @@ pub use config::{Config, ConfigBuilder};
 
 mod config;
 
-// TODO: handle max failures
-
 /// Log target for the file.
 const LOG_TARGET: &str = "litep2p::ipfs::ping";
 
 /// Events emitted by the ping protocol.
+#[non_exhaustive]
 #[derive(Debug)]
 pub enum PingEvent {
     /// Ping time with remote peer.
@@ -56,12 +63,120 @@ pub enum PingEvent {
         /// Measured ping time with the peer.
         ping: Duration,
     },
+
+    /// Peer failed to respond to `max_failures` consecutive pings and is considered unreachable.
+    Unreachable {
+        /// Peer ID.
+        peer: PeerId,
+    },
+}
+
+/// Handle for communicating with the ping protocol.
+///
+/// Emits [`PingEvent`]s and allows querying the round-trip time statistics of connected peers.
+pub struct PingHandle {
+    /// RX channel for receiving ping events.
+    event_rx: Receiver<PingEvent>,
+
+    /// Recent round-trip times of connected peers, shared with `Ping`.
+    rtts: Arc<RwLock<HashMap<PeerId, RttWindow>>>,
+}
+
+impl PingHandle {
+    /// Create new [`PingHandle`].
+    fn new(event_rx: Receiver<PingEvent>, rtts: Arc<RwLock<HashMap<PeerId, RttWindow>>>) -> Self {
+        Self { event_rx, rtts }
+    }
+
+    /// Get round-trip time statistics of `peer`, computed over the most recent pings.
+    ///
+    /// Returns `None` if `peer` is not connected or hasn't responded to a ping yet.
+    pub fn rtt_stats(&self, peer: &PeerId) -> Option<RttStats> {
+        self.rtts.read().get(peer).and_then(RttWindow::stats)
+    }
+}
+
+impl Stream for PingHandle {
+    type Item = PingEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.event_rx).poll_recv(cx)
+    }
+}
+
+/// Round-trip time statistics of a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RttStats {
+    /// Smallest measured round-trip time.
+    pub min: Duration,
+
+    /// Largest measured round-trip time.
+    pub max: Duration,
+
+    /// Mean of the measured round-trip times.
+    pub mean: Duration,
+
+    /// Most recently measured round-trip time.
+    pub last: Duration,
+}
+
+/// Rolling window of the most recent round-trip time measurements.
+#[derive(Debug)]
+struct RttWindow {
+    /// Maximum number of measurements stored.
+    size: usize,
+
+    /// Measurements, oldest first.
+    samples: VecDeque<Duration>,
+}
+
+impl RttWindow {
+    /// Create new [`RttWindow`].
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            samples: VecDeque::with_capacity(size),
+        }
+    }
+
+    /// Add new measurement to the window, evicting the oldest measurement if the window is full.
+    fn push(&mut self, rtt: Duration) {
+        if self.samples.len() == self.size {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(rtt);
+    }
+
+    /// Compute statistics over the stored measurements.
+    fn stats(&self) -> Option<RttStats> {
+        let last = *self.samples.back()?;
+        let min = *self.samples.iter().min()?;
+        let max = *self.samples.iter().max()?;
+        let mean = self.samples.iter().sum::<Duration>() / self.samples.len() as u32;
+
+        Some(RttStats {
+            min,
+            max,
+            mean,
+            last,
+        })
+    }
 }
 
 /// Ping protocol.
 pub(crate) struct Ping {
     /// Maximum failures before the peer is considered unreachable.
-    _max_failures: usize,
+    max_failures: usize,
+
+    /// Interval between outbound pings.
+    interval: Duration,
+
+    /// Timeout for a single ping.
+    timeout: Duration,
+
+    /// Number of round-trip time measurements used to compute RTT statistics.
+    rtt_window_size: usize,
 
     // Connection service.
     service: TransportService,
@@ -69,17 +184,27 @@ pub(crate) struct Ping {
     /// TX channel for sending events to the user protocol.
     tx: Sender<PingEvent>,
 
-    /// Connected peers.
-    peers: HashSet<PeerId>,
-
     /// Pending outbound substreams.
     pending_opens: HashMap<SubstreamId, PeerId>,
 
     /// Pending outbound substreams.
-    pending_outbound: FuturesUnordered<BoxFuture<'static, crate::Result<(PeerId, Duration)>>>,
+    pending_outbound:
+        FuturesUnordered<BoxFuture<'static, (PeerId, crate::Result<(Duration, Substream)>)>>,
+
+    /// Outbound substreams which are being closed after a successful ping.
+    pending_closes: FuturesUnordered<BoxFuture<'static, ()>>,
 
     /// Pending inbound substreams.
     pending_inbound: FuturesUnordered<BoxFuture<'static, crate::Result<()>>>,
+
+    /// Consecutive outbound ping failures for each peer.
+    failures: HashMap<PeerId, usize>,
+
+    /// Ping timers of connected peers.
+    timers: StreamMap<PeerId, IntervalStream>,
+
+    /// Recent round-trip times of connected peers, shared with [`PingHandle`].
+    rtts: Arc<RwLock<HashMap<PeerId, RttWindow>>>,
 }
 
 impl Ping {
@@ -88,11 +213,17 @@ impl Ping {
         Self {
             service,
             tx: config.tx_event,
-            peers: HashSet::new(),
             pending_opens: HashMap::new(),
             pending_outbound: FuturesUnordered::new(),
+            pending_closes: FuturesUnordered::new(),
             pending_inbound: FuturesUnordered::new(),
-            _max_failures: config.max_failures,
+            failures: HashMap::new(),
+            timers: StreamMap::new(),
+            rtts: config.rtts,
+            max_failures: config.max_failures,
+            interval: config.interval,
+            timeout: config.timeout,
+            rtt_window_size: config.rtt_window_size,
         }
     }
 
@@ -100,9 +231,14 @@ impl Ping {
     fn on_connection_established(&mut self, peer: PeerId) -> crate::Result<()> {
         tracing::trace!(target: LOG_TARGET, ?peer, "connection established");
 
+        // start the timer first so the peer is pinged again even if the first ping can't be sent
+        let mut interval =
+            tokio::time::interval_at(tokio::time::Instant::now() + self.interval, self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        self.timers.insert(peer, IntervalStream::new(interval));
+
         let substream_id = self.service.open_substream(peer)?;
         self.pending_opens.insert(substream_id, peer);
-        self.peers.insert(peer);
 
         Ok(())
     }
@@ -111,7 +247,30 @@ impl Ping {
     fn on_connection_closed(&mut self, peer: PeerId) {
         tracing::trace!(target: LOG_TARGET, ?peer, "connection closed");
 
-        self.peers.remove(&peer);
+        self.failures.remove(&peer);
+        self.timers.remove(&peer);
+        self.rtts.write().remove(&peer);
+    }
+
+    /// Ping interval for `peer` has elapsed, open a new substream.
+    async fn on_ping_interval(&mut self, peer: PeerId) {
+        tracing::trace!(target: LOG_TARGET, ?peer, "ping interval elapsed");
+
+        match self.service.open_substream(peer) {
+            Ok(substream_id) => {
+                self.pending_opens.insert(substream_id, peer);
+            }
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?peer,
+                    ?error,
+                    "failed to open ping substream",
+                );
+
+                self.on_outbound_ping_failure(peer).await;
+            }
+        }
     }
 
     /// Handle outbound substream.
@@ -123,6 +282,7 @@ impl Ping {
     ) {
         tracing::trace!(target: LOG_TARGET, ?peer, "handle outbound substream");
 
+        let timeout = self.timeout;
         self.pending_outbound.push(Box::pin(async move {
             let future = async move {
                 // TODO: generate random payload and verify it
@@ -131,23 +291,89 @@ impl Ping {
                 let _ = substream.next().await.ok_or(Error::SubstreamError(
                     SubstreamError::ReadFailure(Some(substream_id)),
                 ))?;
-                let _ = substream.close().await;
 
-                Ok(now.elapsed())
+                Ok((now.elapsed(), substream))
             };
 
-            match tokio::time::timeout(Duration::from_secs(10), future).await {
-                Err(_) => Err(Error::Timeout),
-                Ok(Err(error)) => Err(error),
-                Ok(Ok(elapsed)) => Ok((peer, elapsed)),
+            match tokio::time::timeout(timeout, future).await {
+                Err(_) => (peer, Err(Error::Timeout)),
+                Ok(result) => (peer, result),
             }
         }));
     }
 
+    /// Close the substream of a successful outbound ping.
+    ///
+    /// The substream is closed outside of the ping deadline so a slow close doesn't turn the ping
+    /// into a timeout or delay reporting it.
+    fn close_outbound_substream(&mut self, peer: PeerId, substream: Substream) {
+        let timeout = self.timeout;
+        self.pending_closes.push(Box::pin(async move {
+            if let Err(error) = substream.close_gracefully(timeout).await {
+                tracing::trace!(target: LOG_TARGET, ?peer, ?error, "failed to close substream");
+            }
+        }));
+    }
+
+    /// Handle the result of an outbound ping.
+    ///
+    /// Successful pings reset the failure counter of the peer and failed pings increment it.
+    /// When the counter reaches `max_failures`, [`PingEvent::Unreachable`] is emitted once.
+    async fn on_outbound_ping_result(&mut self, peer: PeerId, result: crate::Result<Duration>) {
+        match result {
+            Ok(elapsed) => {
+                self.failures.remove(&peer);
+
+                let window_size = self.rtt_window_size;
+                self.rtts
+                    .write()
+                    .entry(peer)
+                    .or_insert_with(|| RttWindow::new(window_size))
+                    .push(elapsed);
+
+                let _ = self
+                    .tx
+                    .send(PingEvent::Ping {
+                        peer,
+                        ping: elapsed,
+                    })
+                    .await;
+            }
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?peer,
+                    ?error,
+                    "failed to handle ping for an outbound peer",
+                );
+
+                self.on_outbound_ping_failure(peer).await;
+            }
+        }
+    }
+
+    /// Record a failed outbound ping for `peer`.
+    async fn on_outbound_ping_failure(&mut self, peer: PeerId) {
+        let failures = self.failures.entry(peer).or_default();
+        *failures += 1;
+
+        if *failures == self.max_failures {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?peer,
+                max_failures = ?self.max_failures,
+                "peer is unreachable",
+            );
+
+            let _ = self.tx.send(PingEvent::Unreachable { peer }).await;
+        }
+    }
+
     /// Substream opened to remote peer.
     fn on_inbound_substream(&mut self, peer: PeerId, mut substream: Substream) {
         tracing::trace!(target: LOG_TARGET, ?peer, "handle inbound substream");
 
+        let timeout = self.timeout;
         self.pending_inbound.push(Box::pin(async move {
             let future = async move {
                 let payload = substream
@@ -155,15 +381,14 @@ impl Ping {
                     .await
                     .ok_or(Error::SubstreamError(SubstreamError::ReadFailure(None)))??;
                 substream.send_framed(payload.freeze()).await?;
-                let _ = substream.next().await.map(|_| ());
 
-                Ok(())
+                Ok(substream)
             };
 
-            match tokio::time::timeout(Duration::from_secs(10), future).await {
+            match tokio::time::timeout(timeout, future).await {
                 Err(_) => Err(Error::Timeout),
                 Ok(Err(error)) => Err(error),
-                Ok(Ok(())) => Ok(()),
+                Ok(Ok(substream)) => substream.close_gracefully(timeout).await,
             }
         }));
     }
@@ -197,34 +422,323 @@ impl Ping {
                                     self.on_outbound_substream(peer, substream_id, substream);
                                 }
                                 None => {
-                                    tracing::warn!(
+                                    tracing::debug!(
                                         target: LOG_TARGET,
+                                        ?peer,
                                         ?substream_id,
-                                        "outbound ping substream ID does not exist",
+                                        "outbound ping substream ID does not exist, dropping substream",
                                     );
+                                    drop(substream);
                                 }
                             }
                         }
                     },
+                    Some(TransportEvent::SubstreamOpenFailure { substream, error }) => {
+                        if let Some(peer) = self.pending_opens.remove(&substream) {
+                            tracing::debug!(
+                                target: LOG_TARGET,
+                                ?peer,
+                                ?substream,
+                                ?error,
+                                "failed to open ping substream",
+                            );
+
+                            self.on_outbound_ping_failure(peer).await;
+                        }
+                    }
                     Some(_) => {}
                     None => return,
                 },
                 _event = self.pending_inbound.next(), if !self.pending_inbound.is_empty() => {}
+                _ = self.pending_closes.next(), if !self.pending_closes.is_empty() => {}
+                event = self.timers.next(), if !self.timers.is_empty() => {
+                    if let Some((peer, _)) = event {
+                        self.on_ping_interval(peer).await;
+                    }
+                }
                 event = self.pending_outbound.next(), if !self.pending_outbound.is_empty() => {
-                    match event {
-                        Some(Ok((peer, elapsed))) => {
-                            let _ = self
-                                .tx
-                                .send(PingEvent::Ping {
-                                    peer,
-                                    ping: elapsed,
-                                })
-                                .await;
-                        }
-                        event => tracing::debug!(target: LOG_TARGET, "failed to handle ping for an outbound peer: {event:?}"),
+                    if let Some((peer, result)) = event {
+                        let result = result.map(|(elapsed, substream)| {
+                            self.close_outbound_substream(peer, substream);
+                            elapsed
+                        });
+                        self.on_outbound_ping_result(peer, result).await;
                     }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::ed25519::Keypair,
+        mock::substream::MockSubstream,
+        protocol::{connection::ConnectionHandle, InnerTransportEvent, ProtocolCommand},
+        transport::{manager::TransportManager, Endpoint},
+        types::ConnectionId,
+        BandwidthSink, ProtocolName,
+    };
+    use bytes::BytesMut;
+    use multiaddr::Multiaddr;
+    use tokio::sync::mpsc::channel;
+
+    use std::collections::HashSet;
+
+    // create new ping protocol for testing
+    fn ping(max_failures: usize) -> (Ping, PingHandle, TransportManager) {
+        let (manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        let (transport_service, _tx) = TransportService::new(
+            PeerId::random(),
+            ProtocolName::from("/ipfs/ping/1.0.0"),
+            Vec::new(),
+            std::sync::Arc::new(Default::default()),
+            handle,
+        );
+        let (config, ping_handle) = ConfigBuilder::new().with_max_failure(max_failures).build();
+
+        (Ping::new(transport_service, config), ping_handle, manager)
+    }
+
+    #[tokio::test]
+    async fn unreachable_emitted_once_at_threshold() {
+        let (mut ping, mut handle, _manager) = ping(3usize);
+        let peer = PeerId::random();
+
+        for _ in 0..5 {
+            ping.pending_outbound.push(Box::pin(async move { (peer, Err(Error::Timeout)) }));
+        }
+
+        while let Some((peer, result)) = ping.pending_outbound.next().await {
+            ping.on_outbound_ping_result(peer, result.map(|(elapsed, _)| elapsed)).await;
+        }
+
+        match handle.event_rx.try_recv() {
+            Ok(PingEvent::Unreachable { peer: unreachable }) => assert_eq!(unreachable, peer),
+            event => panic!("invalid event received: {event:?}"),
+        }
+        assert!(handle.event_rx.try_recv().is_err());
+        assert_eq!(ping.failures.get(&peer), Some(&5usize));
+    }
+
+    #[tokio::test]
+    async fn successful_ping_resets_failures() {
+        let (mut ping, mut handle, _manager) = ping(3usize);
+        let peer = PeerId::random();
+
+        ping.on_outbound_ping_result(peer, Err(Error::Timeout)).await;
+        ping.on_outbound_ping_result(peer, Err(Error::Timeout)).await;
+        ping.on_outbound_ping_result(peer, Ok(Duration::from_millis(10))).await;
+        ping.on_outbound_ping_result(peer, Err(Error::Timeout)).await;
+
+        assert!(std::matches!(
+            handle.event_rx.try_recv(),
+            Ok(PingEvent::Ping { .. })
+        ));
+        assert!(handle.event_rx.try_recv().is_err());
+        assert_eq!(ping.failures.get(&peer), Some(&1usize));
+    }
+
+    #[tokio::test]
+    async fn failures_cleared_on_connection_closed() {
+        let (mut ping, _handle, _manager) = ping(3usize);
+        let peer = PeerId::random();
+
+        ping.on_outbound_ping_result(peer, Err(Error::Timeout)).await;
+        assert_eq!(ping.failures.get(&peer), Some(&1usize));
+
+        ping.on_connection_closed(peer);
+        assert!(ping.failures.get(&peer).is_none());
+    }
+
+    #[tokio::test]
+    async fn timer_started_even_if_first_ping_fails() {
+        let (mut ping, _handle, _manager) = ping(3usize);
+        let peer = PeerId::random();
+
+        // the peer has no connection in the transport service so opening the substream fails
+        assert!(ping.on_connection_established(peer).is_err());
+        assert!(ping.timers.contains_key(&peer));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn peer_pinged_periodically() {
+        let (_manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let (service, tx) = TransportService::new(
+            PeerId::random(),
+            ProtocolName::from(config::PROTOCOL_NAME),
+            Vec::new(),
+            std::sync::Arc::new(Default::default()),
+            handle,
+        );
+        let (config, mut event_stream) =
+            ConfigBuilder::new().with_interval(Duration::from_secs(5)).build();
+        tokio::spawn(Ping::new(service, config).run());
+
+        // keep a sender alive so the connection isn't considered closed after keep-alive timeout
+        let peer = PeerId::random();
+        let (cmd_tx, mut cmd_rx) = channel(64);
+        tx.send(InnerTransportEvent::ConnectionEstablished {
+            peer,
+            connection: ConnectionId::from(0usize),
+            endpoint: Endpoint::dialer(Multiaddr::empty(), ConnectionId::from(0usize)),
+            sender: ConnectionHandle::new(ConnectionId::from(0usize), cmd_tx.clone()),
+        })
+        .await
+        .unwrap();
+
+        for _ in 0..3 {
+            let substream_id = match cmd_rx.recv().await.unwrap() {
+                ProtocolCommand::OpenSubstream { substream_id, .. } => substream_id,
+                _ => panic!("invalid command received"),
+            };
+
+            let mut substream = MockSubstream::new();
+            substream.expect_poll_ready().returning(|_| Poll::Ready(Ok(())));
+            substream.expect_start_send().returning(|_| Ok(()));
+            substream.expect_poll_flush().returning(|_| Poll::Ready(Ok(())));
+            substream.expect_poll_close().returning(|_| Poll::Ready(Ok(())));
+            substream
+                .expect_poll_next()
+                .times(1)
+                .return_once(|_| Poll::Ready(Some(Ok(BytesMut::from(&[0u8; 32][..])))));
+            substream.expect_poll_next().returning(|_| Poll::Ready(None));
+
+            tx.send(InnerTransportEvent::SubstreamOpened {
+                peer,
+                protocol: ProtocolName::from(config::PROTOCOL_NAME),
+                fallback: None,
+                direction: Direction::Outbound(substream_id),
+                substream: Substream::new_mock(peer, substream_id, Box::new(substream)),
+            })
+            .await
+            .unwrap();
+
+            match event_stream.next().await {
+                Some(PingEvent::Ping { peer: pinged, .. }) => assert_eq!(pinged, peer),
+                event => panic!("invalid event received: {event:?}"),
+            }
+        }
+
+        drop(cmd_tx);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unresponsive_peer_times_out() {
+        let (mut ping, _handle, _manager) = ping(3usize);
+        let peer = PeerId::random();
+        let substream_id = SubstreamId::from(0usize);
+
+        // substream accepts the ping payload but never responds to it
+        let mut substream = MockSubstream::new();
+        substream.expect_poll_ready().returning(|_| Poll::Ready(Ok(())));
+        substream.expect_start_send().returning(|_| Ok(()));
+        substream.expect_poll_flush().returning(|_| Poll::Ready(Ok(())));
+        substream.expect_poll_next().returning(|_| Poll::Pending);
+
+        let started = tokio::time::Instant::now();
+        ping.on_outbound_substream(
+            peer,
+            substream_id,
+            Substream::new_mock(peer, substream_id, Box::new(substream)),
+        );
+
+        match ping.pending_outbound.next().await {
+            Some((event_peer, Err(Error::Timeout))) => assert_eq!(event_peer, peer),
+            event => panic!("invalid event: {event:?}"),
+        }
+        assert!(started.elapsed() >= ping.timeout);
+        assert!(started.elapsed() < ping.timeout + Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn unknown_outbound_substream_dropped() {
+        let (_manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let (service, tx) = TransportService::new(
+            PeerId::random(),
+            ProtocolName::from(config::PROTOCOL_NAME),
+            Vec::new(),
+            std::sync::Arc::new(Default::default()),
+            handle,
+        );
+        let (config, _event_stream) = ConfigBuilder::new().build();
+        tokio::spawn(Ping::new(service, config).run());
+
+        let peer = PeerId::random();
+        let substream_id = SubstreamId::from(1337usize);
+        tx.send(InnerTransportEvent::SubstreamOpened {
+            peer,
+            protocol: ProtocolName::from(config::PROTOCOL_NAME),
+            fallback: None,
+            direction: Direction::Outbound(substream_id),
+            substream: Substream::new_mock(peer, substream_id, Box::new(MockSubstream::new())),
+        })
+        .await
+        .unwrap();
+
+        // verify the event loop is still running by establishing a connection
+        let (cmd_tx, mut cmd_rx) = channel(64);
+        tx.send(InnerTransportEvent::ConnectionEstablished {
+            peer,
+            connection: ConnectionId::from(0usize),
+            endpoint: Endpoint::dialer(Multiaddr::empty(), ConnectionId::from(0usize)),
+            sender: ConnectionHandle::new(ConnectionId::from(0usize), cmd_tx),
+        })
+        .await
+        .unwrap();
+
+        match cmd_rx.recv().await {
+            Some(ProtocolCommand::OpenSubstream { .. }) => {}
+            _ => panic!("expected ping substream to be opened"),
+        }
+    }
+
+    #[tokio::test]
+    async fn rtt_statistics() {
+        let (mut ping, handle, _manager) = ping(3usize);
+        ping.rtt_window_size = 3usize;
+        let peer = PeerId::random();
+
+        for millis in [40u64, 10, 20, 30] {
+            ping.on_outbound_ping_result(peer, Ok(Duration::from_millis(millis))).await;
+        }
+
+        // the first measurement has been evicted from the window
+        assert_eq!(
+            handle.rtt_stats(&peer),
+            Some(RttStats {
+                min: Duration::from_millis(10),
+                max: Duration::from_millis(30),
+                mean: Duration::from_millis(20),
+                last: Duration::from_millis(30),
+            })
+        );
+
+        // statistics are discarded once the peer disconnects
+        ping.on_connection_closed(peer);
+        assert!(handle.rtt_stats(&peer).is_none());
+    }
+
+    #[test]
+    fn rtt_window_empty() {
+        assert!(RttWindow::new(10usize).stats().is_none());
+    }
+}
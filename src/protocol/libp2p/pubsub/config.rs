@@ -0,0 +1,102 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{
+    codec::ProtocolCodec,
+    crypto::ed25519::Keypair,
+    protocol::libp2p::pubsub::{PubsubCommand, PubsubEvent, PubsubHandle},
+    types::protocol::ProtocolName,
+    DEFAULT_CHANNEL_SIZE,
+};
+
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use std::sync::{atomic::AtomicUsize, Arc};
+
+/// Floodsub protocol name as a string.
+pub const PROTOCOL_NAME: &str = "/floodsub/1.0.0";
+
+/// Maximum size of a pubsub RPC.
+const MAX_PAYLOAD_SIZE: usize = 1024 * 1024;
+
+/// Authenticity of the published messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageAuthenticity {
+    /// Messages are signed with the keypair of the local node.
+    ///
+    /// Inbound messages must carry a valid signature of their author.
+    Signed,
+
+    /// Messages are published without an author or a signature.
+    ///
+    /// A random sequence number is attached to each message so that messages with the same
+    /// payload are not dropped as duplicates. Inbound messages carrying an author or a signature
+    /// are rejected.
+    Anonymous,
+}
+
+/// Pubsub configuration.
+#[derive(Debug)]
+pub struct Config {
+    /// Protocol name.
+    pub(crate) protocol: ProtocolName,
+
+    /// Protocol codec.
+    pub(crate) codec: ProtocolCodec,
+
+    /// Authenticity of the published messages.
+    pub(super) authenticity: MessageAuthenticity,
+
+    /// Keypair used to sign messages.
+    ///
+    /// Set by `Litep2p` when the protocol is started.
+    pub(crate) keypair: Option<Keypair>,
+
+    /// Number of rejected inbound messages.
+    pub(super) invalid_messages: Arc<AtomicUsize>,
+
+    /// TX channel for sending events to the user protocol.
+    pub(super) event_tx: Sender<PubsubEvent>,
+
+    /// RX channel for receiving commands from the user.
+    pub(super) cmd_rx: Receiver<PubsubCommand>,
+}
+
+impl Config {
+    /// Create new [`Config`].
+    pub fn new(authenticity: MessageAuthenticity) -> (Self, PubsubHandle) {
+        let (event_tx, event_rx) = channel(DEFAULT_CHANNEL_SIZE);
+        let (cmd_tx, cmd_rx) = channel(DEFAULT_CHANNEL_SIZE);
+        let invalid_messages = Arc::new(AtomicUsize::new(0usize));
+
+        (
+            Self {
+                cmd_rx,
+                event_tx,
+                protocol: ProtocolName::from(PROTOCOL_NAME),
+                codec: ProtocolCodec::UnsignedVarint(Some(MAX_PAYLOAD_SIZE)),
+                authenticity,
+                keypair: None,
+                invalid_messages: Arc::clone(&invalid_messages),
+            },
+            PubsubHandle::new(event_rx, cmd_tx, invalid_messages),
+        )
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Pubsub handle for communicating with the pubsub protocol implementation.
+
+use crate::PeerId;
+
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+/// Events emitted by the pubsub protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PubsubEvent {
+    /// Message received to a subscribed topic.
+    Message {
+        /// Topic.
+        topic: String,
+
+        /// Author of the message.
+        ///
+        /// `None` if the message was published anonymously.
+        from: Option<PeerId>,
+
+        /// Message data.
+        data: Vec<u8>,
+    },
+
+    /// Peer subscribed to a topic.
+    Subscribed {
+        /// Peer ID.
+        peer: PeerId,
+
+        /// Topic.
+        topic: String,
+    },
+
+    /// Peer unsubscribed from a topic.
+    Unsubscribed {
+        /// Peer ID.
+        peer: PeerId,
+
+        /// Topic.
+        topic: String,
+    },
+}
+
+/// Commands sent from the user to `Pubsub`.
+#[derive(Debug)]
+pub(super) enum PubsubCommand {
+    /// Subscribe to topic.
+    Subscribe {
+        /// Topic.
+        topic: String,
+    },
+
+    /// Unsubscribe from topic.
+    Unsubscribe {
+        /// Topic.
+        topic: String,
+    },
+
+    /// Publish message to topic.
+    Publish {
+        /// Topic.
+        topic: String,
+
+        /// Message data.
+        data: Vec<u8>,
+    },
+}
+
+/// Handle for communicating with the pubsub protocol.
+pub struct PubsubHandle {
+    /// RX channel for receiving pubsub events.
+    event_rx: Receiver<PubsubEvent>,
+
+    /// TX channel for sending commands to `Pubsub`.
+    cmd_tx: Sender<PubsubCommand>,
+
+    /// Number of rejected inbound messages.
+    invalid_messages: Arc<AtomicUsize>,
+}
+
+impl PubsubHandle {
+    /// Create new [`PubsubHandle`].
+    pub(super) fn new(
+        event_rx: Receiver<PubsubEvent>,
+        cmd_tx: Sender<PubsubCommand>,
+        invalid_messages: Arc<AtomicUsize>,
+    ) -> Self {
+        Self {
+            event_rx,
+            cmd_tx,
+            invalid_messages,
+        }
+    }
+
+    /// Get the number of inbound messages which have been rejected.
+    ///
+    /// A message is rejected if its authenticity doesn't match the configured
+    /// [`MessageAuthenticity`](super::MessageAuthenticity), e.g., if its signature doesn't match
+    /// the claimed author.
+    pub fn invalid_messages(&self) -> usize {
+        self.invalid_messages.load(Ordering::Relaxed)
+    }
+
+    /// Subscribe to `topic`.
+    ///
+    /// Messages published to `topic` are reported as [`PubsubEvent::Message`].
+    pub async fn subscribe(&self, topic: impl Into<String>) {
+        let _ = self
+            .cmd_tx
+            .send(PubsubCommand::Subscribe {
+                topic: topic.into(),
+            })
+            .await;
+    }
+
+    /// Unsubscribe from `topic`.
+    pub async fn unsubscribe(&self, topic: impl Into<String>) {
+        let _ = self
+            .cmd_tx
+            .send(PubsubCommand::Unsubscribe {
+                topic: topic.into(),
+            })
+            .await;
+    }
+
+    /// Publish `data` to `topic`.
+    ///
+    /// The message is sent to all connected peers subscribed to `topic`. The local node doesn't
+    /// need to be subscribed to `topic` to publish to it and it doesn't receive its own messages.
+    pub async fn publish(&self, topic: impl Into<String>, data: Vec<u8>) {
+        let _ = self
+            .cmd_tx
+            .send(PubsubCommand::Publish {
+                topic: topic.into(),
+                data,
+            })
+            .await;
+    }
+}
+
+impl futures::Stream for PubsubHandle {
+    type Item = PubsubEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.event_rx).poll_recv(cx)
+    }
+}
@@ -0,0 +1,850 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Publish-subscribe protocol.
+//!
+//! The current implementation is [`/floodsub/1.0.0`](https://github.com/libp2p/specs/tree/master/pubsub):
+//! each message is forwarded to all connected peers subscribed to its topic and messages which
+//! have already been seen are dropped to prevent loops.
+//!
+//! RPCs sent to a peer are written over a single long-lived outbound substream which is reopened
+//! only if it fails.
+//!
+//! Depending on the configured [`MessageAuthenticity`], published messages are either signed
+//! with the keypair of the local node or published anonymously. Inbound messages which don't
+//! match the configured authenticity are dropped.
+
+use crate::{
+    crypto::{ed25519::Keypair, PublicKey},
+    error::Error,
+    protocol::{Direction, TransportEvent, TransportService},
+    substream::Substream,
+    types::SubstreamId,
+    PeerId,
+};
+
+use bytes::BytesMut;
+use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
+use multihash::Code;
+use prost::Message;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc::{channel, error::TrySendError, Receiver, Sender};
+
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+pub use config::{Config, MessageAuthenticity};
+pub use handle::{PubsubEvent, PubsubHandle};
+
+use handle::PubsubCommand;
+
+mod config;
+mod handle;
+
+mod schema {
+    pub(super) mod pubsub {
+        include!(concat!(env!("OUT_DIR"), "/pubsub.rs"));
+    }
+}
+
+/// Log target for the file.
+const LOG_TARGET: &str = "litep2p::pubsub";
+
+/// Number of message IDs remembered for deduplication.
+const SEEN_CACHE_SIZE: usize = 4096;
+
+/// Maximum number of topics a peer can be subscribed to.
+const MAX_PEER_SUBSCRIPTIONS: usize = 1024;
+
+/// Maximum number of concurrent inbound substreams of a peer.
+const MAX_INBOUND_SUBSTREAMS: usize = 2;
+
+/// Maximum number of RPCs queued for the outbound substream of a peer.
+const OUTBOUND_QUEUE_SIZE: usize = 64;
+
+/// Prefix of the signed data of a message.
+const SIGNING_PREFIX: &[u8] = b"libp2p-pubsub:";
+
+/// Pubsub protocol.
+pub(crate) struct Pubsub {
+    // Connection service.
+    service: TransportService,
+
+    /// TX channel for sending events to the user protocol.
+    event_tx: Sender<PubsubEvent>,
+
+    /// RX channel for receiving commands from `PubsubHandle`.
+    cmd_rx: Receiver<PubsubCommand>,
+
+    /// Authenticity of the published messages.
+    authenticity: MessageAuthenticity,
+
+    /// Keypair used to sign messages.
+    keypair: Keypair,
+
+    /// Number of rejected inbound messages.
+    invalid_messages: Arc<AtomicUsize>,
+
+    /// Topics the local node is subscribed to.
+    subscriptions: HashSet<String>,
+
+    /// Connected peers and the topics they're subscribed to.
+    peers: HashMap<PeerId, HashSet<String>>,
+
+    /// IDs of the messages which have been seen.
+    seen: HashSet<Vec<u8>>,
+
+    /// IDs of the messages which have been seen, in the order they were seen.
+    seen_order: VecDeque<Vec<u8>>,
+
+    /// RPC queues of the outbound substreams, opened or pending.
+    outbound: HashMap<PeerId, Sender<schema::pubsub::Rpc>>,
+
+    /// Number of open inbound substreams of each peer.
+    inbound: HashMap<PeerId, usize>,
+
+    /// Pending outbound substreams and the RPCs queued for them.
+    pending_outbound: HashMap<SubstreamId, (PeerId, Receiver<schema::pubsub::Rpc>)>,
+
+    /// Outbound substreams writing queued RPCs.
+    pending_sends: FuturesUnordered<BoxFuture<'static, ()>>,
+
+    /// Pending inbound RPCs.
+    pending_inbound:
+        FuturesUnordered<BoxFuture<'static, (PeerId, Substream, Option<crate::Result<BytesMut>>)>>,
+}
+
+impl Pubsub {
+    /// Create new [`Pubsub`] protocol.
+    pub(crate) fn new(service: TransportService, config: Config) -> Self {
+        Self {
+            service,
+            cmd_rx: config.cmd_rx,
+            event_tx: config.event_tx,
+            authenticity: config.authenticity,
+            keypair: config.keypair.expect("keypair to be supplied"),
+            invalid_messages: config.invalid_messages,
+            subscriptions: HashSet::new(),
+            peers: HashMap::new(),
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+            outbound: HashMap::new(),
+            inbound: HashMap::new(),
+            pending_outbound: HashMap::new(),
+            pending_sends: FuturesUnordered::new(),
+            pending_inbound: FuturesUnordered::new(),
+        }
+    }
+
+    /// Connection established to remote peer, send the local subscriptions to it.
+    fn on_connection_established(&mut self, peer: PeerId) {
+        tracing::trace!(target: LOG_TARGET, ?peer, "connection established");
+
+        self.peers.insert(peer, HashSet::new());
+
+        if !self.subscriptions.is_empty() {
+            let rpc = subscriptions_rpc(self.subscriptions.iter().cloned(), true);
+            self.send_rpc(peer, rpc);
+        }
+    }
+
+    /// Connection closed to remote peer.
+    fn on_connection_closed(&mut self, peer: PeerId) {
+        tracing::trace!(target: LOG_TARGET, ?peer, "connection closed");
+
+        self.peers.remove(&peer);
+
+        // dropping the queue closes the outbound substream
+        self.outbound.remove(&peer);
+    }
+
+    /// Send `rpc` to `peer` over its outbound substream, opening the substream if needed.
+    fn send_rpc(&mut self, peer: PeerId, rpc: schema::pubsub::Rpc) {
+        let rpc = match self.outbound.get(&peer) {
+            None => rpc,
+            Some(tx) => match tx.try_send(rpc) {
+                Ok(()) => return,
+                Err(TrySendError::Full(_)) => {
+                    tracing::debug!(target: LOG_TARGET, ?peer, "outbound queue full, dropping rpc");
+                    return;
+                }
+                // the previous substream failed, open a new one
+                Err(TrySendError::Closed(rpc)) => {
+                    self.outbound.remove(&peer);
+                    rpc
+                }
+            },
+        };
+
+        match self.service.open_substream(peer) {
+            Ok(substream_id) => {
+                let (tx, rx) = channel(OUTBOUND_QUEUE_SIZE);
+                let _ = tx.try_send(rpc);
+
+                self.outbound.insert(peer, tx);
+                self.pending_outbound.insert(substream_id, (peer, rx));
+            }
+            Err(error) => {
+                tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to open substream");
+            }
+        }
+    }
+
+    /// Outbound substream opened, send the queued RPCs over it until the queue is closed.
+    fn on_outbound_substream(
+        &mut self,
+        peer: PeerId,
+        substream_id: SubstreamId,
+        mut substream: Substream,
+    ) {
+        let Some((_, mut rx)) = self.pending_outbound.remove(&substream_id) else {
+            tracing::warn!(target: LOG_TARGET, ?peer, ?substream_id, "pending outbound entry doesn't exist");
+            return;
+        };
+
+        self.pending_sends.push(Box::pin(async move {
+            while let Some(rpc) = rx.recv().await {
+                if let Err(error) = substream.send_framed(rpc.encode_to_vec().into()).await {
+                    tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to send rpc");
+                    return;
+                }
+            }
+
+            substream.close().await
+        }));
+    }
+
+    /// Inbound substream opened, read RPCs from it until it's closed.
+    fn on_inbound_substream(&mut self, peer: PeerId, substream: Substream) {
+        let substreams = self.inbound.entry(peer).or_default();
+
+        if *substreams >= MAX_INBOUND_SUBSTREAMS {
+            tracing::debug!(target: LOG_TARGET, ?peer, "too many inbound substreams");
+            return;
+        }
+
+        tracing::trace!(target: LOG_TARGET, ?peer, "inbound substream opened");
+
+        *substreams += 1;
+        self.read_rpc(peer, substream);
+    }
+
+    /// Inbound substream of `peer` closed.
+    fn on_inbound_substream_closed(&mut self, peer: PeerId) {
+        if let Entry::Occupied(mut entry) = self.inbound.entry(peer) {
+            *entry.get_mut() -= 1;
+
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Read next RPC from `substream`.
+    fn read_rpc(&mut self, peer: PeerId, mut substream: Substream) {
+        self.pending_inbound.push(Box::pin(async move {
+            let result = substream.next().await;
+            (peer, substream, result)
+        }));
+    }
+
+    /// Remember `message_id` and return `true` if it hadn't been seen before.
+    fn insert_seen(&mut self, message_id: Vec<u8>) -> bool {
+        if !self.seen.insert(message_id.clone()) {
+            return false;
+        }
+
+        self.seen_order.push_back(message_id);
+        if self.seen_order.len() > SEEN_CACHE_SIZE {
+            if let Some(message_id) = self.seen_order.pop_front() {
+                self.seen.remove(&message_id);
+            }
+        }
+
+        true
+    }
+
+    /// Forward `message` to all peers subscribed to its topics except `source` and its author.
+    fn forward(
+        &mut self,
+        message: schema::pubsub::Message,
+        source: Option<PeerId>,
+        author: Option<PeerId>,
+    ) {
+        let peers = self
+            .peers
+            .iter()
+            .filter(|(peer, topics)| {
+                Some(**peer) != source
+                    && Some(**peer) != author
+                    && message.topic_ids.iter().any(|topic| topics.contains(topic))
+            })
+            .map(|(peer, _)| *peer)
+            .collect::<Vec<_>>();
+
+        for peer in peers {
+            tracing::trace!(target: LOG_TARGET, ?peer, topics = ?message.topic_ids, "forward message");
+
+            self.send_rpc(
+                peer,
+                schema::pubsub::Rpc {
+                    publish: vec![message.clone()],
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// Subscribe to `topic` and announce the subscription to connected peers.
+    fn on_subscribe(&mut self, topic: String) {
+        if !self.subscriptions.insert(topic.clone()) {
+            return;
+        }
+
+        tracing::debug!(target: LOG_TARGET, ?topic, "subscribe to topic");
+
+        let peers = self.peers.keys().copied().collect::<Vec<_>>();
+        for peer in peers {
+            self.send_rpc(
+                peer,
+                subscriptions_rpc(std::iter::once(topic.clone()), true),
+            );
+        }
+    }
+
+    /// Unsubscribe from `topic` and announce it to connected peers.
+    fn on_unsubscribe(&mut self, topic: String) {
+        if !self.subscriptions.remove(&topic) {
+            return;
+        }
+
+        tracing::debug!(target: LOG_TARGET, ?topic, "unsubscribe from topic");
+
+        let peers = self.peers.keys().copied().collect::<Vec<_>>();
+        for peer in peers {
+            self.send_rpc(
+                peer,
+                subscriptions_rpc(std::iter::once(topic.clone()), false),
+            );
+        }
+    }
+
+    /// Publish `data` to `topic`.
+    fn on_publish(&mut self, topic: String, data: Vec<u8>) {
+        let message = match self.authenticity {
+            MessageAuthenticity::Signed => {
+                let mut message = schema::pubsub::Message {
+                    from: Some(self.service.local_peer_id.to_bytes()),
+                    data: Some(data),
+                    seqno: Some(rand::random::<u64>().to_be_bytes().to_vec()),
+                    topic_ids: vec![topic],
+                    ..Default::default()
+                };
+                message.signature = Some(self.keypair.sign(&signed_data(&message)));
+
+                message
+            }
+            MessageAuthenticity::Anonymous => schema::pubsub::Message {
+                data: Some(data),
+                seqno: Some(rand::random::<u64>().to_be_bytes().to_vec()),
+                topic_ids: vec![topic],
+                ..Default::default()
+            },
+        };
+        let author = message.from.as_ref().map(|_| self.service.local_peer_id);
+
+        tracing::trace!(target: LOG_TARGET, topics = ?message.topic_ids, "publish message");
+
+        self.insert_seen(message_id(&message));
+        self.forward(message, None, author);
+    }
+
+    /// Handle RPC received from `peer`.
+    async fn on_rpc(&mut self, peer: PeerId, rpc: schema::pubsub::Rpc) {
+        for subscription in rpc.subscriptions {
+            let Some(topic) = subscription.topic_id else {
+                continue;
+            };
+            let Some(topics) = self.peers.get_mut(&peer) else {
+                tracing::trace!(target: LOG_TARGET, ?peer, ?topic, "peer not connected");
+                continue;
+            };
+
+            let event = match subscription.subscribe.unwrap_or_default() {
+                true if topics.len() >= MAX_PEER_SUBSCRIPTIONS && !topics.contains(&topic) => {
+                    tracing::debug!(target: LOG_TARGET, ?peer, ?topic, "too many subscriptions");
+                    continue;
+                }
+                true if topics.insert(topic.clone()) => PubsubEvent::Subscribed { peer, topic },
+                false if topics.remove(&topic) => PubsubEvent::Unsubscribed { peer, topic },
+                _ => continue,
+            };
+
+            tracing::trace!(target: LOG_TARGET, ?event, "subscriptions updated");
+            let _ = self.event_tx.send(event).await;
+        }
+
+        for message in rpc.publish {
+            self.on_message(peer, message).await;
+        }
+    }
+
+    /// Handle `message` received from `peer`.
+    async fn on_message(&mut self, peer: PeerId, message: schema::pubsub::Message) {
+        let Ok(author) = validate_message(self.authenticity, &message) else {
+            tracing::debug!(target: LOG_TARGET, ?peer, "invalid message");
+
+            self.invalid_messages.fetch_add(1usize, Ordering::Relaxed);
+            return;
+        };
+
+        if !self.insert_seen(message_id(&message)) {
+            tracing::trace!(target: LOG_TARGET, ?peer, ?author, "duplicate message");
+            return;
+        }
+
+        let data = message.data.clone().unwrap_or_default();
+        for topic in message.topic_ids.iter().filter(|topic| self.subscriptions.contains(*topic)) {
+            let _ = self
+                .event_tx
+                .send(PubsubEvent::Message {
+                    topic: topic.clone(),
+                    from: author,
+                    data: data.clone(),
+                })
+                .await;
+        }
+
+        self.forward(message, Some(peer), author);
+    }
+
+    /// Start [`Pubsub`] event loop.
+    pub async fn run(mut self) {
+        tracing::debug!(target: LOG_TARGET, "starting pubsub event loop");
+
+        loop {
+            tokio::select! {
+                event = self.service.next() => match event {
+                    Some(TransportEvent::ConnectionEstablished { peer, .. }) => {
+                        self.on_connection_established(peer);
+                    }
+                    Some(TransportEvent::ConnectionClosed { peer }) => {
+                        self.on_connection_closed(peer);
+                    }
+                    Some(TransportEvent::SubstreamOpened {
+                        peer,
+                        substream,
+                        direction,
+                        ..
+                    }) => match direction {
+                        Direction::Inbound => self.on_inbound_substream(peer, substream),
+                        Direction::Outbound(substream_id) =>
+                            self.on_outbound_substream(peer, substream_id, substream),
+                    },
+                    Some(TransportEvent::SubstreamOpenFailure { substream, error }) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?substream,
+                            ?error,
+                            "failed to open substream",
+                        );
+                        if let Some((peer, _)) = self.pending_outbound.remove(&substream) {
+                            self.outbound.remove(&peer);
+                        }
+                    }
+                    None => return,
+                    event => tracing::trace!(target: LOG_TARGET, ?event, "unhandled event"),
+                },
+                command = self.cmd_rx.recv() => match command {
+                    Some(PubsubCommand::Subscribe { topic }) => self.on_subscribe(topic),
+                    Some(PubsubCommand::Unsubscribe { topic }) => self.on_unsubscribe(topic),
+                    Some(PubsubCommand::Publish { topic, data }) => self.on_publish(topic, data),
+                    None => return,
+                },
+                event = self.pending_inbound.next(), if !self.pending_inbound.is_empty() => {
+                    match event {
+                        Some((peer, substream, Some(Ok(payload)))) => {
+                            match schema::pubsub::Rpc::decode(payload.freeze()) {
+                                Ok(rpc) => self.on_rpc(peer, rpc).await,
+                                Err(error) => {
+                                    tracing::debug!(
+                                        target: LOG_TARGET,
+                                        ?peer,
+                                        ?error,
+                                        "failed to decode rpc",
+                                    );
+                                }
+                            }
+
+                            self.read_rpc(peer, substream);
+                        }
+                        Some((peer, _, Some(Err(error)))) => {
+                            tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to read rpc");
+                            self.on_inbound_substream_closed(peer);
+                        }
+                        Some((peer, _, None)) => self.on_inbound_substream_closed(peer),
+                        None => {}
+                    }
+                }
+                _ = self.pending_sends.next(), if !self.pending_sends.is_empty() => {}
+            }
+        }
+    }
+}
+
+/// Create RPC which subscribes to or unsubscribes from `topics`.
+fn subscriptions_rpc(topics: impl Iterator<Item = String>, subscribe: bool) -> schema::pubsub::Rpc {
+    schema::pubsub::Rpc {
+        subscriptions: topics
+            .map(|topic| schema::pubsub::rpc::SubOpts {
+                subscribe: Some(subscribe),
+                topic_id: Some(topic),
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Get the ID of `message`.
+///
+/// The ID is the concatenation of the author and the sequence number of the message or, for
+/// anonymous messages, the hash of the message contents including its random sequence number.
+fn message_id(message: &schema::pubsub::Message) -> Vec<u8> {
+    match (&message.from, &message.seqno) {
+        (Some(from), Some(seqno)) => [from.as_slice(), seqno.as_slice()].concat(),
+        _ => Sha256::digest(message.encode_to_vec()).to_vec(),
+    }
+}
+
+/// Get the data of `message` covered by its signature.
+fn signed_data(message: &schema::pubsub::Message) -> Vec<u8> {
+    let message = schema::pubsub::Message {
+        signature: None,
+        key: None,
+        ..message.clone()
+    };
+
+    [SIGNING_PREFIX, message.encode_to_vec().as_slice()].concat()
+}
+
+/// Get the public key of `author`.
+///
+/// The key is either carried in the `key` field of the message or inlined into the peer ID.
+fn public_key(author: &PeerId, key: Option<&[u8]>) -> Option<PublicKey> {
+    let key = match key {
+        Some(key) => PublicKey::from_protobuf_encoding(key).ok()?,
+        None => {
+            let multihash = author.to_multihash();
+
+            if multihash.code() != u64::from(Code::Identity) {
+                return None;
+            }
+
+            PublicKey::from_protobuf_encoding(multihash.digest()).ok()?
+        }
+    };
+
+    (key.to_peer_id() == *author).then_some(key)
+}
+
+/// Validate the authenticity of `message` and return its author.
+fn validate_message(
+    authenticity: MessageAuthenticity,
+    message: &schema::pubsub::Message,
+) -> crate::Result<Option<PeerId>> {
+    match authenticity {
+        MessageAuthenticity::Signed => {
+            let (Some(from), Some(_), Some(signature)) =
+                (&message.from, &message.seqno, &message.signature)
+            else {
+                return Err(Error::InvalidData);
+            };
+            let author = PeerId::from_bytes(from).map_err(|_| Error::InvalidData)?;
+            let key = public_key(&author, message.key.as_deref()).ok_or(Error::InvalidData)?;
+
+            match key.verify(&signed_data(message), signature) {
+                true => Ok(Some(author)),
+                false => Err(Error::InvalidData),
+            }
+        }
+        MessageAuthenticity::Anonymous => {
+            match message.from.is_none() && message.signature.is_none() && message.key.is_none() {
+                true => Ok(None),
+                false => Err(Error::InvalidData),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::substream::DummySubstream, transport::manager::TransportManager,
+        types::protocol::ProtocolName, BandwidthSink,
+    };
+    use futures::FutureExt;
+
+    /// Create new [`Pubsub`] which is subscribed to `topic`.
+    fn make_pubsub(authenticity: MessageAuthenticity) -> (Pubsub, PubsubHandle, TransportManager) {
+        let (manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let (service, _tx) = TransportService::new(
+            PeerId::random(),
+            ProtocolName::from(config::PROTOCOL_NAME),
+            Vec::new(),
+            Default::default(),
+            handle,
+        );
+        let (mut config, handle) = Config::new(authenticity);
+        config.keypair = Some(Keypair::generate());
+
+        let mut pubsub = Pubsub::new(service, config);
+        pubsub.subscriptions.insert(String::from("topic"));
+
+        (pubsub, handle, manager)
+    }
+
+    /// Create message to `topic` which is signed with `keypair`.
+    fn signed_message(keypair: &Keypair, data: Vec<u8>) -> schema::pubsub::Message {
+        let mut message = schema::pubsub::Message {
+            from: Some(PeerId::from_public_key(&keypair.public().into()).to_bytes()),
+            data: Some(data),
+            seqno: Some(1u64.to_be_bytes().to_vec()),
+            topic_ids: vec![String::from("topic")],
+            ..Default::default()
+        };
+        message.signature = Some(keypair.sign(&signed_data(&message)));
+
+        message
+    }
+
+    #[tokio::test]
+    async fn signed_message_accepted() {
+        let (mut pubsub, mut handle, _manager) = make_pubsub(MessageAuthenticity::Signed);
+        let keypair = Keypair::generate();
+        let author = PeerId::from_public_key(&keypair.public().into());
+
+        let message = signed_message(&keypair, vec![1, 2, 3]);
+        pubsub.on_message(PeerId::random(), message).await;
+
+        assert_eq!(
+            handle.next().now_or_never(),
+            Some(Some(PubsubEvent::Message {
+                topic: String::from("topic"),
+                from: Some(author),
+                data: vec![1, 2, 3],
+            }))
+        );
+        assert_eq!(handle.invalid_messages(), 0usize);
+    }
+
+    #[tokio::test]
+    async fn tampered_message_rejected() {
+        let (mut pubsub, mut handle, _manager) = make_pubsub(MessageAuthenticity::Signed);
+
+        let mut message = signed_message(&Keypair::generate(), vec![1, 2, 3]);
+        message.data = Some(vec![1, 3, 3, 7]);
+
+        pubsub.on_message(PeerId::random(), message).await;
+
+        assert!(handle.next().now_or_never().is_none());
+        assert_eq!(handle.invalid_messages(), 1usize);
+    }
+
+    #[tokio::test]
+    async fn spoofed_author_rejected() {
+        let (mut pubsub, mut handle, _manager) = make_pubsub(MessageAuthenticity::Signed);
+
+        let mut message = signed_message(&Keypair::generate(), vec![1, 2, 3]);
+        message.from =
+            Some(PeerId::from_public_key(&Keypair::generate().public().into()).to_bytes());
+
+        pubsub.on_message(PeerId::random(), message).await;
+
+        assert!(handle.next().now_or_never().is_none());
+        assert_eq!(handle.invalid_messages(), 1usize);
+    }
+
+    #[tokio::test]
+    async fn unsigned_message_rejected() {
+        let (mut pubsub, mut handle, _manager) = make_pubsub(MessageAuthenticity::Signed);
+
+        let mut message = signed_message(&Keypair::generate(), vec![1, 2, 3]);
+        message.signature = None;
+
+        pubsub.on_message(PeerId::random(), message).await;
+
+        assert!(handle.next().now_or_never().is_none());
+        assert_eq!(handle.invalid_messages(), 1usize);
+    }
+
+    #[tokio::test]
+    async fn anonymous_mode() {
+        let (mut pubsub, mut handle, _manager) = make_pubsub(MessageAuthenticity::Anonymous);
+
+        // signed messages are rejected
+        let message = signed_message(&Keypair::generate(), vec![1]);
+        pubsub.on_message(PeerId::random(), message).await;
+        assert!(handle.next().now_or_never().is_none());
+        assert_eq!(handle.invalid_messages(), 1usize);
+
+        // anonymous messages are accepted
+        let message = schema::pubsub::Message {
+            data: Some(vec![2]),
+            topic_ids: vec![String::from("topic")],
+            ..Default::default()
+        };
+        pubsub.on_message(PeerId::random(), message).await;
+
+        assert_eq!(
+            handle.next().now_or_never(),
+            Some(Some(PubsubEvent::Message {
+                topic: String::from("topic"),
+                from: None,
+                data: vec![2],
+            }))
+        );
+        assert_eq!(handle.invalid_messages(), 1usize);
+    }
+
+    #[tokio::test]
+    async fn anonymous_messages_with_same_payload_published() {
+        let (mut pubsub, _handle, _manager) = make_pubsub(MessageAuthenticity::Anonymous);
+
+        pubsub.on_publish(String::from("topic"), vec![1, 2, 3]);
+        pubsub.on_publish(String::from("topic"), vec![1, 2, 3]);
+
+        assert_eq!(pubsub.seen.len(), 2usize);
+    }
+
+    #[tokio::test]
+    async fn peer_subscriptions_capped() {
+        let (mut pubsub, mut handle, _manager) = make_pubsub(MessageAuthenticity::Signed);
+        let peer = PeerId::random();
+
+        pubsub.peers.insert(
+            peer,
+            (0..MAX_PEER_SUBSCRIPTIONS).map(|topic| topic.to_string()).collect(),
+        );
+        pubsub
+            .on_rpc(
+                peer,
+                subscriptions_rpc(std::iter::once(String::from("topic")), true),
+            )
+            .await;
+
+        assert!(handle.next().now_or_never().is_none());
+        assert_eq!(
+            pubsub.peers.get(&peer).unwrap().len(),
+            MAX_PEER_SUBSCRIPTIONS
+        );
+        assert!(!pubsub.peers.get(&peer).unwrap().contains("topic"));
+    }
+
+    #[tokio::test]
+    async fn subscriptions_of_disconnected_peer_ignored() {
+        let (mut pubsub, mut handle, _manager) = make_pubsub(MessageAuthenticity::Signed);
+        let peer = PeerId::random();
+
+        pubsub.on_connection_established(peer);
+        pubsub.on_connection_closed(peer);
+        pubsub
+            .on_rpc(
+                peer,
+                subscriptions_rpc(std::iter::once(String::from("topic")), true),
+            )
+            .await;
+
+        assert!(handle.next().now_or_never().is_none());
+        assert!(!pubsub.peers.contains_key(&peer));
+    }
+
+    #[tokio::test]
+    async fn outbound_substream_reused() {
+        let (mut pubsub, _handle, _manager) = make_pubsub(MessageAuthenticity::Signed);
+        let peer = PeerId::random();
+        let (tx, mut rx) = channel(OUTBOUND_QUEUE_SIZE);
+        pubsub.outbound.insert(peer, tx);
+
+        pubsub.send_rpc(
+            peer,
+            subscriptions_rpc(std::iter::once(String::from("a")), true),
+        );
+        pubsub.send_rpc(
+            peer,
+            subscriptions_rpc(std::iter::once(String::from("b")), true),
+        );
+
+        // both rpcs are queued for the existing substream
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_ok());
+        assert!(pubsub.pending_outbound.is_empty());
+
+        // failed substream is replaced, which fails since the peer isn't connected
+        drop(rx);
+        pubsub.send_rpc(
+            peer,
+            subscriptions_rpc(std::iter::once(String::from("c")), true),
+        );
+
+        assert!(!pubsub.outbound.contains_key(&peer));
+        assert!(pubsub.pending_outbound.is_empty());
+    }
+
+    #[tokio::test]
+    async fn inbound_substreams_capped() {
+        let (mut pubsub, _handle, _manager) = make_pubsub(MessageAuthenticity::Signed);
+        let peer = PeerId::random();
+
+        for i in 0..=MAX_INBOUND_SUBSTREAMS {
+            pubsub.on_inbound_substream(
+                peer,
+                Substream::new_mock(peer, SubstreamId::from(i), Box::new(DummySubstream::new())),
+            );
+        }
+
+        assert_eq!(pubsub.pending_inbound.len(), MAX_INBOUND_SUBSTREAMS);
+        assert_eq!(pubsub.inbound.get(&peer), Some(&MAX_INBOUND_SUBSTREAMS));
+
+        // closed substream makes room for a new one
+        pubsub.on_inbound_substream_closed(peer);
+        pubsub.on_inbound_substream(
+            peer,
+            Substream::new_mock(
+                peer,
+                SubstreamId::from(0usize),
+                Box::new(DummySubstream::new()),
+            ),
+        );
+
+        assert_eq!(pubsub.pending_inbound.len(), MAX_INBOUND_SUBSTREAMS + 1);
+        assert_eq!(pubsub.inbound.get(&peer), Some(&MAX_INBOUND_SUBSTREAMS));
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::time::Duration;
+
+/// Maximum number of reservations.
+const MAX_RESERVATIONS: usize = 128;
+
+/// Duration of a reservation.
+const RESERVATION_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum number of simultaneously relayed circuits.
+const MAX_CIRCUITS: usize = 16;
+
+/// Maximum duration of a relayed circuit.
+const MAX_CIRCUIT_DURATION: Duration = Duration::from_secs(2 * 60);
+
+/// Maximum number of bytes relayed over a circuit in each direction.
+const MAX_CIRCUIT_BYTES: u64 = 1 << 17;
+
+/// Relay server configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Maximum number of reservations.
+    pub(crate) max_reservations: usize,
+
+    /// Duration of a reservation.
+    pub(crate) reservation_duration: Duration,
+
+    /// Maximum number of simultaneously relayed circuits.
+    pub(crate) max_circuits: usize,
+
+    /// Maximum duration of a relayed circuit.
+    pub(crate) max_circuit_duration: Duration,
+
+    /// Maximum number of bytes relayed over a circuit in each direction.
+    pub(crate) max_circuit_bytes: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_reservations: MAX_RESERVATIONS,
+            reservation_duration: RESERVATION_DURATION,
+            max_circuits: MAX_CIRCUITS,
+            max_circuit_duration: MAX_CIRCUIT_DURATION,
+            max_circuit_bytes: MAX_CIRCUIT_BYTES,
+        }
+    }
+}
+
+/// Relay server configuration builder.
+pub struct ConfigBuilder {
+    /// Maximum number of reservations.
+    max_reservations: usize,
+
+    /// Duration of a reservation.
+    reservation_duration: Duration,
+
+    /// Maximum number of simultaneously relayed circuits.
+    max_circuits: usize,
+
+    /// Maximum duration of a relayed circuit.
+    max_circuit_duration: Duration,
+
+    /// Maximum number of bytes relayed over a circuit in each direction.
+    max_circuit_bytes: u64,
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigBuilder {
+    /// Create new default [`Config`] which can be modified by the user.
+    pub fn new() -> Self {
+        Self {
+            max_reservations: MAX_RESERVATIONS,
+            reservation_duration: RESERVATION_DURATION,
+            max_circuits: MAX_CIRCUITS,
+            max_circuit_duration: MAX_CIRCUIT_DURATION,
+            max_circuit_bytes: MAX_CIRCUIT_BYTES,
+        }
+    }
+
+    /// Set maximum number of reservations.
+    ///
+    /// Once the limit is reached, new reservations are refused until existing ones expire or the
+    /// peers holding them disconnect.
+    pub fn with_max_reservations(mut self, max_reservations: usize) -> Self {
+        self.max_reservations = max_reservations;
+        self
+    }
+
+    /// Set duration of a reservation.
+    pub fn with_reservation_duration(mut self, reservation_duration: Duration) -> Self {
+        self.reservation_duration = reservation_duration;
+        self
+    }
+
+    /// Set maximum number of simultaneously relayed circuits.
+    pub fn with_max_circuits(mut self, max_circuits: usize) -> Self {
+        self.max_circuits = max_circuits;
+        self
+    }
+
+    /// Set maximum duration of a relayed circuit.
+    pub fn with_max_circuit_duration(mut self, max_circuit_duration: Duration) -> Self {
+        self.max_circuit_duration = max_circuit_duration;
+        self
+    }
+
+    /// Set maximum number of bytes relayed over a circuit in each direction.
+    ///
+    /// The circuit is closed once either direction reaches the limit.
+    pub fn with_max_circuit_bytes(mut self, max_circuit_bytes: u64) -> Self {
+        self.max_circuit_bytes = max_circuit_bytes;
+        self
+    }
+
+    /// Build relay server configuration.
+    pub fn build(self) -> Config {
+        Config {
+            max_reservations: self.max_reservations,
+            reservation_duration: self.reservation_duration,
+            max_circuits: self.max_circuits,
+            max_circuit_duration: self.max_circuit_duration,
+            max_circuit_bytes: self.max_circuit_bytes,
+        }
+    }
+}
@@ -0,0 +1,634 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! [Circuit relay v2](https://github.com/libp2p/specs/blob/master/relay/circuit-v2.md) server.
+//!
+//! Peers reserve a slot with the relay over the `hop` protocol, after which other peers can ask
+//! the relay to connect them to the peer holding the reservation. The relay opens a `stop`
+//! substream to the destination and, once it has accepted the circuit, proxies bytes between
+//! the two substreams until either side closes the circuit or one of the limits specified in
+//! [`Config`] is reached.
+
+use crate::{
+    error::{Error, RelayError},
+    protocol::{Direction, KeepAlive, TransportEvent, TransportService},
+    substream::Substream,
+    transport::relay::protocol::{
+        read_message,
+        schema::relay::{
+            hop_message, stop_message, HopMessage, Limit, Peer, Reservation, Status, StopMessage,
+        },
+        status_to_result, write_message,
+    },
+    types::SubstreamId,
+    PeerId,
+};
+
+use futures::{
+    future::{BoxFuture, Either},
+    stream::FuturesUnordered,
+    StreamExt,
+};
+use prost::Message;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time::Instant,
+};
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub use config::{Config, ConfigBuilder};
+
+mod config;
+
+/// Log target for the file.
+const LOG_TARGET: &str = "litep2p::ipfs::relay";
+
+/// Timeout for reading a request and for the `stop` handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often expired reservations are pruned.
+const RESERVATION_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Events produced by the pending futures of [`Relay`].
+enum RelayEvent {
+    /// `hop` request was read from an inbound substream.
+    Request {
+        /// Peer who sent the request.
+        peer: PeerId,
+
+        /// Substream the request was read from.
+        substream: Box<Substream>,
+
+        /// Encoded request.
+        result: crate::Result<Vec<u8>>,
+    },
+
+    /// Response was sent to a `hop` request.
+    ResponseSent,
+
+    /// Circuit between `source` and `destination` was closed.
+    CircuitClosed {
+        /// Peer who requested the circuit.
+        source: PeerId,
+
+        /// Peer holding the reservation.
+        destination: PeerId,
+    },
+}
+
+/// Circuit relay v2 server.
+pub(crate) struct Relay {
+    /// `hop` protocol service.
+    hop: TransportService,
+
+    /// `stop` protocol service.
+    stop: TransportService,
+
+    /// Relay configuration.
+    config: Config,
+
+    /// Active reservations and their expiration times.
+    reservations: HashMap<PeerId, Instant>,
+
+    /// Number of active circuits.
+    circuits: usize,
+
+    /// Pending `stop` substreams, mapped to the requesting peer and its `hop` substream.
+    pending_stops: HashMap<SubstreamId, (PeerId, Substream)>,
+
+    /// Pending requests, responses and circuits.
+    pending_events: FuturesUnordered<BoxFuture<'static, RelayEvent>>,
+}
+
+impl Relay {
+    /// Create new [`Relay`].
+    pub(crate) fn new(hop: TransportService, stop: TransportService, config: Config) -> Self {
+        Self {
+            hop,
+            stop,
+            config,
+            reservations: HashMap::new(),
+            circuits: 0usize,
+            pending_stops: HashMap::new(),
+            pending_events: FuturesUnordered::new(),
+        }
+    }
+
+    /// Limits of a relayed circuit.
+    fn limit(&self) -> Limit {
+        Limit {
+            duration: Some(
+                u32::try_from(self.config.max_circuit_duration.as_secs()).unwrap_or(u32::MAX),
+            ),
+            data: Some(self.config.max_circuit_bytes),
+        }
+    }
+
+    /// Remove expired reservations.
+    fn prune_reservations(&mut self) {
+        let now = Instant::now();
+        let expired = self
+            .reservations
+            .iter()
+            .filter_map(|(peer, expire)| (*expire <= now).then_some(*peer))
+            .collect::<Vec<_>>();
+
+        for peer in expired {
+            tracing::trace!(target: LOG_TARGET, ?peer, "reservation expired");
+
+            self.reservations.remove(&peer);
+            let _ = self.hop.set_keep_alive(peer, KeepAlive::No);
+        }
+    }
+
+    /// Send `status` over `substream` and close it.
+    fn send_status(&mut self, mut substream: Substream, status: Status) {
+        self.pending_events.push(Box::pin(async move {
+            let mut response = HopMessage::default();
+            response.set_type(hop_message::Type::Status);
+            response.set_status(status);
+
+            let _ = write_message(&mut substream, response).await;
+            let _ = substream.shutdown().await;
+
+            RelayEvent::ResponseSent
+        }));
+    }
+
+    /// Inbound `hop` substream opened.
+    fn on_hop_substream(&mut self, peer: PeerId, mut substream: Substream) {
+        tracing::trace!(target: LOG_TARGET, ?peer, "handle inbound hop substream");
+
+        self.pending_events.push(Box::pin(async move {
+            let future = read_message(&mut substream);
+            let result = match tokio::time::timeout(HANDSHAKE_TIMEOUT, future).await {
+                Err(_) => Err(Error::Timeout),
+                Ok(result) => result,
+            };
+
+            RelayEvent::Request {
+                peer,
+                substream: Box::new(substream),
+                result,
+            }
+        }));
+    }
+
+    /// Handle `hop` request read from `peer`.
+    fn on_request(&mut self, peer: PeerId, substream: Substream, request: Vec<u8>) {
+        let request = match HopMessage::decode(request.as_slice()) {
+            Ok(request) => request,
+            Err(error) => {
+                tracing::debug!(target: LOG_TARGET, ?peer, ?error, "malformed hop request");
+                return self.send_status(substream, Status::MalformedMessage);
+            }
+        };
+
+        self.prune_reservations();
+
+        match request.r#type() {
+            hop_message::Type::Reserve => self.on_reserve(peer, substream),
+            hop_message::Type::Connect => {
+                match request.peer.and_then(|peer| PeerId::from_bytes(&peer.id).ok()) {
+                    Some(destination) => self.on_connect(peer, destination, substream),
+                    None => self.send_status(substream, Status::MalformedMessage),
+                }
+            }
+            hop_message::Type::Status => self.send_status(substream, Status::UnexpectedMessage),
+        }
+    }
+
+    /// Handle reservation request from `peer`.
+    fn on_reserve(&mut self, peer: PeerId, mut substream: Substream) {
+        if !self.reservations.contains_key(&peer)
+            && self.reservations.len() >= self.config.max_reservations
+        {
+            tracing::debug!(target: LOG_TARGET, ?peer, "reservation limit reached");
+            return self.send_status(substream, Status::ResourceLimitExceeded);
+        }
+
+        if let Err(error) = self.hop.set_keep_alive(peer, KeepAlive::Yes) {
+            tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to keep connection open");
+            return self.send_status(substream, Status::ReservationRefused);
+        }
+
+        tracing::trace!(target: LOG_TARGET, ?peer, "reservation accepted");
+
+        let duration = self.config.reservation_duration;
+        self.reservations.insert(peer, Instant::now() + duration);
+
+        let expire = (SystemTime::now() + duration)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut response = HopMessage {
+            reservation: Some(Reservation {
+                expire,
+                addrs: Vec::new(),
+                voucher: None,
+            }),
+            limit: Some(self.limit()),
+            ..Default::default()
+        };
+        response.set_type(hop_message::Type::Status);
+        response.set_status(Status::Ok);
+
+        self.pending_events.push(Box::pin(async move {
+            if let Err(error) = write_message(&mut substream, response).await {
+                tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to send reservation");
+            }
+            let _ = substream.shutdown().await;
+
+            RelayEvent::ResponseSent
+        }));
+    }
+
+    /// Handle request from `source` to connect to `destination`.
+    fn on_connect(&mut self, source: PeerId, destination: PeerId, substream: Substream) {
+        tracing::trace!(target: LOG_TARGET, ?source, ?destination, "circuit requested");
+
+        if !self.reservations.contains_key(&destination) {
+            return self.send_status(substream, Status::NoReservation);
+        }
+
+        if self.circuits >= self.config.max_circuits {
+            tracing::debug!(target: LOG_TARGET, ?source, ?destination, "circuit limit reached");
+            return self.send_status(substream, Status::ResourceLimitExceeded);
+        }
+
+        match self.stop.open_substream(destination) {
+            Ok(substream_id) => {
+                self.circuits += 1;
+                self.pending_stops.insert(substream_id, (source, substream));
+            }
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?source,
+                    ?destination,
+                    ?error,
+                    "failed to open stop substream",
+                );
+                self.send_status(substream, Status::ConnectionFailed);
+            }
+        }
+    }
+
+    /// Outbound `stop` substream opened to `destination`.
+    fn on_stop_substream(
+        &mut self,
+        destination: PeerId,
+        substream_id: SubstreamId,
+        mut stop: Substream,
+    ) {
+        let Some((source, mut hop)) = self.pending_stops.remove(&substream_id) else {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?destination,
+                ?substream_id,
+                "stop substream doesn't exist",
+            );
+            return;
+        };
+        let limit = self.limit();
+
+        self.pending_events.push(Box::pin(async move {
+            let mut response = HopMessage::default();
+            response.set_type(hop_message::Type::Status);
+
+            let future = connect_destination(&mut stop, source, limit.clone());
+            let result = tokio::time::timeout(HANDSHAKE_TIMEOUT, future).await;
+
+            match result {
+                Ok(Ok(())) => {
+                    response.limit = Some(limit.clone());
+                    response.set_status(Status::Ok);
+
+                    if write_message(&mut hop, response).await.is_ok() {
+                        tracing::trace!(target: LOG_TARGET, ?source, ?destination, "circuit opened");
+                        relay_circuit(hop, stop, limit).await;
+                    }
+                }
+                result => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?source,
+                        ?destination,
+                        ?result,
+                        "destination didn't accept the circuit",
+                    );
+
+                    response.set_status(Status::ConnectionFailed);
+                    let _ = write_message(&mut hop, response).await;
+                }
+            }
+
+            RelayEvent::CircuitClosed {
+                source,
+                destination,
+            }
+        }));
+    }
+
+    /// Failed to open `stop` substream.
+    fn on_stop_substream_failure(&mut self, substream_id: SubstreamId) {
+        if let Some((source, substream)) = self.pending_stops.remove(&substream_id) {
+            tracing::debug!(target: LOG_TARGET, ?source, "failed to open stop substream");
+
+            self.circuits -= 1;
+            self.send_status(substream, Status::ConnectionFailed);
+        }
+    }
+
+    /// Handle event produced by one of the pending futures.
+    fn on_event(&mut self, event: RelayEvent) {
+        match event {
+            RelayEvent::Request {
+                peer,
+                substream,
+                result,
+            } => match result {
+                Ok(request) => self.on_request(peer, *substream, request),
+                Err(error) => {
+                    tracing::debug!(target: LOG_TARGET, ?peer, ?error, "failed to read hop request");
+                }
+            },
+            RelayEvent::CircuitClosed {
+                source,
+                destination,
+            } => {
+                tracing::trace!(target: LOG_TARGET, ?source, ?destination, "circuit closed");
+                self.circuits -= 1;
+            }
+            RelayEvent::ResponseSent => {}
+        }
+    }
+
+    /// Start [`Relay`] event loop.
+    pub(crate) async fn run(mut self) {
+        tracing::debug!(target: LOG_TARGET, "starting relay event loop");
+
+        let mut prune_timer = tokio::time::interval_at(
+            Instant::now() + RESERVATION_PRUNE_INTERVAL,
+            RESERVATION_PRUNE_INTERVAL,
+        );
+
+        loop {
+            tokio::select! {
+                event = self.hop.next() => match event {
+                    Some(TransportEvent::SubstreamOpened {
+                        peer,
+                        substream,
+                        direction: Direction::Inbound,
+                        ..
+                    }) => self.on_hop_substream(peer, substream),
+                    Some(TransportEvent::ConnectionClosed { peer }) => {
+                        self.reservations.remove(&peer);
+                    }
+                    Some(_) => {}
+                    None => return,
+                },
+                event = self.stop.next() => match event {
+                    Some(TransportEvent::SubstreamOpened {
+                        peer,
+                        substream,
+                        direction: Direction::Outbound(substream_id),
+                        ..
+                    }) => self.on_stop_substream(peer, substream_id, substream),
+                    Some(TransportEvent::SubstreamOpenFailure { substream, .. }) => {
+                        self.on_stop_substream_failure(substream);
+                    }
+                    Some(_) => {}
+                    None => return,
+                },
+                event = self.pending_events.next(), if !self.pending_events.is_empty() => {
+                    if let Some(event) = event {
+                        self.on_event(event);
+                    }
+                }
+                _ = prune_timer.tick() => self.prune_reservations(),
+            }
+        }
+    }
+}
+
+/// Ask the destination to accept a circuit from `source` over a `stop` substream.
+async fn connect_destination(
+    stop: &mut Substream,
+    source: PeerId,
+    limit: Limit,
+) -> crate::Result<()> {
+    let mut request = StopMessage {
+        peer: Some(Peer {
+            id: source.to_bytes(),
+            addrs: Vec::new(),
+        }),
+        limit: Some(limit),
+        ..Default::default()
+    };
+    request.set_type(stop_message::Type::Connect);
+    write_message(stop, request).await?;
+
+    let response = StopMessage::decode(read_message(stop).await?.as_slice())?;
+
+    if response.r#type() != stop_message::Type::Status {
+        return Err(Error::RelayError(RelayError::UnexpectedMessage));
+    }
+
+    status_to_result(response.status())
+}
+
+/// Proxy bytes between `source` and `destination` until either side closes the circuit or one of
+/// the circuit limits is reached.
+async fn relay_circuit<S1, S2>(source: S1, destination: S2, limit: Limit)
+where
+    S1: tokio::io::AsyncRead + tokio::io::AsyncWrite,
+    S2: tokio::io::AsyncRead + tokio::io::AsyncWrite,
+{
+    let (mut source_read, mut source_write) = tokio::io::split(source);
+    let (mut destination_read, mut destination_write) = tokio::io::split(destination);
+    let data = limit.data.unwrap_or(u64::MAX);
+
+    let forward = async {
+        let copied =
+            tokio::io::copy(&mut (&mut source_read).take(data), &mut destination_write).await;
+        let _ = destination_write.shutdown().await;
+        copied
+    };
+    let backward = async {
+        let copied =
+            tokio::io::copy(&mut (&mut destination_read).take(data), &mut source_write).await;
+        let _ = source_write.shutdown().await;
+        copied
+    };
+    futures::pin_mut!(forward, backward);
+
+    // one side closing its write half doesn't close the circuit but reaching the data limit in
+    // either direction does
+    let circuit = async {
+        match futures::future::select(forward, backward).await {
+            Either::Left((Ok(copied), other)) if copied < data => {
+                let _ = other.await;
+            }
+            Either::Right((Ok(copied), other)) if copied < data => {
+                let _ = other.await;
+            }
+            _ => {}
+        }
+    };
+
+    match limit.duration {
+        Some(duration) => {
+            let _ = tokio::time::timeout(Duration::from_secs(duration as u64), circuit).await;
+        }
+        None => circuit.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::ProtocolCodec,
+        crypto::ed25519::Keypair,
+        transport::{
+            manager::TransportManager,
+            relay::protocol::{HOP_PROTOCOL, STOP_PROTOCOL},
+        },
+        types::protocol::ProtocolName,
+        BandwidthSink,
+    };
+    use std::collections::HashSet;
+
+    /// Create new [`Relay`] with `config`.
+    fn make_relay(config: Config) -> (Relay, TransportManager) {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let hop = manager.register_protocol(
+            ProtocolName::from(HOP_PROTOCOL),
+            Vec::new(),
+            ProtocolCodec::Unspecified,
+        );
+        let stop = manager.register_protocol(
+            ProtocolName::from(STOP_PROTOCOL),
+            Vec::new(),
+            ProtocolCodec::Unspecified,
+        );
+
+        (Relay::new(hop, stop, config), manager)
+    }
+
+    #[test]
+    fn circuit_duration_limit_saturates() {
+        let config = ConfigBuilder::new()
+            .with_max_circuit_duration(Duration::from_secs(u64::MAX))
+            .build();
+        let (relay, _manager) = make_relay(config);
+
+        assert_eq!(relay.limit().duration, Some(u32::MAX));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_reservations_pruned() {
+        let (mut relay, _manager) = make_relay(ConfigBuilder::new().build());
+        let expired = PeerId::random();
+        let active = PeerId::random();
+
+        relay.reservations.insert(expired, Instant::now() + Duration::from_secs(10));
+        relay.reservations.insert(active, Instant::now() + Duration::from_secs(60));
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+        relay.prune_reservations();
+
+        assert!(!relay.reservations.contains_key(&expired));
+        assert!(relay.reservations.contains_key(&active));
+    }
+
+    #[tokio::test]
+    async fn circuit_relays_bytes() {
+        let (mut source, source_relay) = tokio::io::duplex(1024);
+        let (mut destination, destination_relay) = tokio::io::duplex(1024);
+        let limit = Limit {
+            duration: Some(60),
+            data: Some(1024),
+        };
+
+        tokio::spawn(relay_circuit(source_relay, destination_relay, limit));
+
+        source.write_all(b"hello, world").await.unwrap();
+        let mut buffer = [0u8; 12];
+        destination.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"hello, world");
+
+        destination.write_all(b"hello, back").await.unwrap();
+        let mut buffer = [0u8; 11];
+        source.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"hello, back");
+    }
+
+    #[tokio::test]
+    async fn circuit_closed_over_data_limit() {
+        let (mut source, source_relay) = tokio::io::duplex(1024);
+        let (mut destination, destination_relay) = tokio::io::duplex(1024);
+        let limit = Limit {
+            duration: None,
+            data: Some(16),
+        };
+
+        tokio::spawn(relay_circuit(source_relay, destination_relay, limit));
+        source.write_all(&[1u8; 32]).await.unwrap();
+
+        // only the allowed bytes are relayed after which both sides of the circuit are closed
+        let mut buffer = Vec::new();
+        destination.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, vec![1u8; 16]);
+
+        let mut buffer = Vec::new();
+        source.read_to_end(&mut buffer).await.unwrap();
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn circuit_closed_after_duration() {
+        let (mut source, source_relay) = tokio::io::duplex(1024);
+        let (mut destination, destination_relay) = tokio::io::duplex(1024);
+        let limit = Limit {
+            duration: Some(60),
+            data: None,
+        };
+
+        let circuit = tokio::spawn(relay_circuit(source_relay, destination_relay, limit));
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        circuit.await.unwrap();
+
+        let mut buffer = Vec::new();
+        destination.read_to_end(&mut buffer).await.unwrap();
+        assert!(buffer.is_empty());
+        assert!(source.write_all(&[1u8; 32]).await.is_err());
+    }
+}
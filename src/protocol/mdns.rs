@@ -21,7 +21,9 @@
 
 //! [Multicast DNS](https://en.wikipedia.org/wiki/Multicast_DNS) implementation.
 
-use crate::{error::Error, transport::manager::TransportManagerHandle, DEFAULT_CHANNEL_SIZE};
+use crate::{
+    error::Error, transport::manager::TransportManagerHandle, PeerId, DEFAULT_CHANNEL_SIZE,
+};
 
 use futures::Stream;
 use multiaddr::Multiaddr;
@@ -39,6 +41,7 @@ use tokio_stream::wrappers::ReceiverStream;
 
 use std::{
     collections::HashSet,
+    io::ErrorKind,
     net,
     net::{IpAddr, Ipv4Addr, SocketAddr},
     sync::Arc,
@@ -57,6 +60,9 @@ const IPV4_MULTICAST_PORT: u16 = 5353;
 /// Service name.
 const SERVICE_NAME: &str = "_p2p._udp.local";
 
+/// Default interval between the first two queries.
+const INITIAL_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Events emitted by mDNS.
 // #[derive(Debug, Clone)]
 pub enum MdnsEvent {
@@ -67,9 +73,12 @@ pub enum MdnsEvent {
 /// mDNS configuration.
 // #[derive(Debug)]
 pub struct Config {
-    /// How often the network should be queried for new peers.
+    /// Maximum interval between queries.
     query_interval: Duration,
 
+    /// Interval between the first two queries.
+    initial_query_interval: Duration,
+
     /// TX channel for sending mDNS events to user.
     tx: Sender<MdnsEvent>,
 }
@@ -77,16 +86,129 @@ pub struct Config {
 impl Config {
     /// Create new [`Config`].
     ///
+    /// The network is first queried after [`INITIAL_QUERY_INTERVAL`] and the interval is
+    /// doubled after each query until it reaches `query_interval`.
+    ///
     /// Return the configuration and an event stream for receiving [`MdnsEvent`]s.
     pub fn new(
         query_interval: Duration,
     ) -> (Self, Box<dyn Stream<Item = MdnsEvent> + Send + Unpin>) {
         let (tx, rx) = channel(DEFAULT_CHANNEL_SIZE);
         (
-            Self { query_interval, tx },
+            Self {
+                query_interval,
+                initial_query_interval: INITIAL_QUERY_INTERVAL.min(query_interval),
+                tx,
+            },
             Box::new(ReceiverStream::new(rx)),
         )
     }
+
+    /// Set the interval between the first two queries.
+    ///
+    /// The interval is capped to the maximum query interval given to [`Config::new()`].
+    pub fn with_initial_query_interval(mut self, initial_query_interval: Duration) -> Self {
+        self.initial_query_interval = initial_query_interval.min(self.query_interval);
+        self
+    }
+}
+
+/// Exponential backoff for the interval between mDNS queries.
+///
+/// Queries start at the initial interval which is doubled after each query until it reaches
+/// the maximum interval. Up to 10% of random jitter is added to each interval so that nodes
+/// started at the same time don't query the network in lockstep.
+struct QueryBackoff {
+    /// Interval before the next query, without jitter.
+    next: Duration,
+
+    /// Maximum interval between queries.
+    max: Duration,
+}
+
+impl QueryBackoff {
+    /// Create new [`QueryBackoff`].
+    fn new(initial: Duration, max: Duration) -> Self {
+        Self { next: initial, max }
+    }
+
+    /// Get the interval before the next query and advance the backoff.
+    fn next_interval(&mut self) -> Duration {
+        let interval = self.next;
+        self.next = std::cmp::min(interval.saturating_mul(2), self.max);
+
+        interval + interval.mul_f64(rand::thread_rng().gen_range(0.0..0.1))
+    }
+}
+
+/// Change in the IPv4 addresses of the network interfaces.
+#[cfg_attr(not(feature = "if-watch"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InterfaceEvent {
+    /// Address was assigned to an interface.
+    Up(Ipv4Addr),
+
+    /// Address was removed from an interface.
+    Down(Ipv4Addr),
+}
+
+/// Watcher for network interface changes.
+///
+/// Without the `if-watch` feature, the watcher never reports any changes.
+struct InterfaceWatcher {
+    /// Network interface watcher, if it could be created.
+    #[cfg(feature = "if-watch")]
+    watcher: Option<if_watch::tokio::IfWatcher>,
+}
+
+impl InterfaceWatcher {
+    /// Create new [`InterfaceWatcher`].
+    #[cfg(feature = "if-watch")]
+    fn new() -> Self {
+        match if_watch::tokio::IfWatcher::new() {
+            Ok(watcher) => Self {
+                watcher: Some(watcher),
+            },
+            Err(error) => {
+                tracing::warn!(target: LOG_TARGET, ?error, "failed to watch network interfaces");
+                Self { watcher: None }
+            }
+        }
+    }
+
+    /// Create new [`InterfaceWatcher`].
+    #[cfg(not(feature = "if-watch"))]
+    fn new() -> Self {
+        Self {}
+    }
+
+    /// Get the next change in the IPv4 addresses of the network interfaces.
+    #[cfg(feature = "if-watch")]
+    async fn next(&mut self) -> InterfaceEvent {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return futures::future::pending().await;
+        };
+
+        loop {
+            match futures::future::poll_fn(|cx| watcher.poll_if_event(cx)).await {
+                Ok(if_watch::IfEvent::Up(if_watch::IpNet::V4(network))) =>
+                    return InterfaceEvent::Up(network.addr()),
+                Ok(if_watch::IfEvent::Down(if_watch::IpNet::V4(network))) =>
+                    return InterfaceEvent::Down(network.addr()),
+                // mDNS is only run over IPv4
+                Ok(_) => {}
+                Err(error) => {
+                    tracing::debug!(target: LOG_TARGET, ?error, "network interface watcher error");
+                }
+            }
+        }
+    }
+
+    /// Get the next change in the IPv4 addresses of the network interfaces.
+    #[cfg(not(feature = "if-watch"))]
+    async fn next(&mut self) -> InterfaceEvent {
+        futures::future::pending().await
+    }
 }
 
 /// Main mDNS object.
@@ -94,14 +216,17 @@ pub(crate) struct Mdns {
     /// UDP socket for multicast requests/responses.
     socket: UdpSocket,
 
-    /// Query interval.
+    /// Maximum query interval.
     query_interval: Duration,
 
+    /// Interval between the first two queries.
+    initial_query_interval: Duration,
+
     /// TX channel for sending events to user.
     event_tx: Sender<MdnsEvent>,
 
     /// Handle to `TransportManager`.
-    _transport_handle: TransportManagerHandle,
+    transport_handle: TransportManagerHandle,
 
     // Username.
     username: String,
@@ -117,12 +242,15 @@ pub(crate) struct Mdns {
 
     /// Discovered addresses.
     discovered: HashSet<Multiaddr>,
+
+    /// Interfaces on which the multicast group has been joined.
+    interfaces: HashSet<Ipv4Addr>,
 }
 
 impl Mdns {
     /// Create new [`Mdns`].
     pub(crate) fn new(
-        _transport_handle: TransportManagerHandle,
+        transport_handle: TransportManagerHandle,
         config: Config,
         listen_addresses: Vec<Multiaddr>,
     ) -> crate::Result<Self> {
@@ -138,12 +266,24 @@ impl Mdns {
         socket.join_multicast_v4(&IPV4_MULTICAST_ADDRESS, &Ipv4Addr::UNSPECIFIED)?;
         socket.set_nonblocking(true)?;
 
+        // advertised addresses must point to the local node
+        let local_peer_id = *transport_handle.local_peer_id();
+        let listen_addresses = listen_addresses
+            .into_iter()
+            .map(|address| match address.iter().last() {
+                Some(multiaddr::Protocol::P2p(_)) => address,
+                _ => address.with(multiaddr::Protocol::P2p(local_peer_id.into())),
+            })
+            .collect::<Vec<_>>();
+
         Ok(Self {
-            _transport_handle,
+            transport_handle,
             event_tx: config.tx,
             next_query_id: 1337u16,
             discovered: HashSet::new(),
+            interfaces: HashSet::new(),
             query_interval: config.query_interval,
+            initial_query_interval: config.initial_query_interval,
             receive_buffer: vec![0u8; 4096],
             username: rand::thread_rng()
                 .sample_iter(&Alphanumeric)
@@ -193,7 +333,12 @@ impl Mdns {
     fn on_inbound_request(&self, packet: Packet) -> Option<Vec<u8>> {
         tracing::debug!(target: LOG_TARGET, ?packet, "handle inbound request");
 
-        let mut packet = Packet::new_reply(packet.id());
+        Some(self.response(packet.id()))
+    }
+
+    /// Build response which advertises the listen addresses of the local node.
+    fn response(&self, id: u16) -> Vec<u8> {
+        let mut packet = Packet::new_reply(id);
         let srv_name = Name::new_unchecked(SERVICE_NAME);
 
         packet.answers.push(ResourceRecord::new(
@@ -216,10 +361,13 @@ impl Mdns {
             });
         }
 
-        Some(packet.build_bytes_vec().expect("valid packet"))
+        packet.build_bytes_vec().expect("valid packet")
     }
 
     /// Handle inbound response.
+    ///
+    /// Returns the addresses advertised in the response, excluding the addresses of the local
+    /// node.
     fn on_inbound_response(&self, packet: Packet) -> Vec<Multiaddr> {
         tracing::debug!(target: LOG_TARGET, "handle inbound response");
 
@@ -272,9 +420,81 @@ impl Mdns {
                     _ => vec![],
                 }
             })
+            .filter(|address: &Multiaddr| {
+                PeerId::try_from_multiaddr(address).as_ref()
+                    != Some(self.transport_handle.local_peer_id())
+            })
             .collect()
     }
 
+    /// Handle discovered addresses.
+    ///
+    /// Addresses which contain a `PeerId` are registered to `TransportManager` so the peers
+    /// can be dialed. Returns the addresses that haven't been discovered before.
+    fn on_discovered(&mut self, addresses: Vec<Multiaddr>) -> Vec<Multiaddr> {
+        let mut discovered = Vec::new();
+
+        for address in addresses {
+            if !self.discovered.insert(address.clone()) {
+                continue;
+            }
+
+            if let Some(peer) = PeerId::try_from_multiaddr(&address) {
+                self.transport_handle.add_known_address(&peer, std::iter::once(address.clone()));
+            }
+
+            discovered.push(address);
+        }
+
+        discovered
+    }
+
+    /// Handle network interface change.
+    ///
+    /// When an address is assigned to an interface, the multicast group is joined on that
+    /// interface, the network is queried and the listen addresses of the local node are
+    /// advertised.
+    async fn on_interface_event(&mut self, event: InterfaceEvent) {
+        tracing::debug!(target: LOG_TARGET, ?event, "network interface changed");
+
+        match event {
+            InterfaceEvent::Up(address) => {
+                match self.socket.join_multicast_v4(IPV4_MULTICAST_ADDRESS, address) {
+                    Ok(()) => {}
+                    // the group has already been joined on the interface
+                    Err(error) if error.kind() == ErrorKind::AddrInUse => {}
+                    Err(error) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?address,
+                            ?error,
+                            "failed to join multicast group",
+                        );
+                        return;
+                    }
+                }
+                self.interfaces.insert(address);
+
+                if let Err(error) = self.on_outbound_request().await {
+                    tracing::debug!(target: LOG_TARGET, ?error, "failed to send mdns query");
+                }
+
+                let response = self.response(0u16);
+                if let Err(error) = self
+                    .socket
+                    .send_to(&response, (IPV4_MULTICAST_ADDRESS, IPV4_MULTICAST_PORT))
+                    .await
+                {
+                    tracing::debug!(target: LOG_TARGET, ?error, "failed to advertise addresses");
+                }
+            }
+            InterfaceEvent::Down(address) =>
+                if self.interfaces.remove(&address) {
+                    let _ = self.socket.leave_multicast_v4(IPV4_MULTICAST_ADDRESS, address);
+                },
+        }
+    }
+
     /// Event loop for [`Mdns`].
     pub(crate) async fn start(mut self) -> crate::Result<()> {
         tracing::debug!(target: LOG_TARGET, "starting mdns event loop");
@@ -284,24 +504,29 @@ impl Mdns {
         // bail early if the socket is not working
         self.on_outbound_request().await?;
 
+        let mut backoff = QueryBackoff::new(self.initial_query_interval, self.query_interval);
+        let next_query = tokio::time::sleep(backoff.next_interval());
+        tokio::pin!(next_query);
+        let mut interface_watcher = InterfaceWatcher::new();
+
         loop {
             tokio::select! {
-                _ = tokio::time::sleep(self.query_interval) => {
+                _ = &mut next_query => {
                     tracing::trace!(target: LOG_TARGET, "timeout expired");
+                    next_query.as_mut().reset(tokio::time::Instant::now() + backoff.next_interval());
 
                     if let Err(error) = self.on_outbound_request().await {
                         tracing::error!(target: LOG_TARGET, ?error, "failed to send mdns query");
                         return Err(error);
                     }
                 }
+                event = interface_watcher.next() => self.on_interface_event(event).await,
                 result = self.socket.recv_from(&mut self.receive_buffer) => match result {
                     Ok((nread, address)) => match Packet::parse(&self.receive_buffer[..nread]) {
                         Ok(packet) => match packet.has_flags(PacketFlag::RESPONSE) {
                             true => {
-                                let to_forward = self.on_inbound_response(packet).into_iter().filter_map(|address| {
-                                    self.discovered.insert(address.clone()).then_some(address)
-                                })
-                                .collect::<Vec<_>>();
+                                let addresses = self.on_inbound_response(packet);
+                                let to_forward = self.on_discovered(addresses);
 
                                 if !to_forward.is_empty() {
                                     let _ = self.event_tx.send(MdnsEvent::Discovered(to_forward)).await;
@@ -334,7 +559,11 @@ impl Mdns {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{crypto::ed25519::Keypair, transport::manager::TransportManager, BandwidthSink};
+    use crate::{
+        crypto::ed25519::Keypair,
+        transport::manager::{SupportedTransport, TransportManager},
+        BandwidthSink,
+    };
     use futures::StreamExt;
     use multiaddr::Protocol;
 
@@ -443,4 +672,143 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn discovered_addresses_parsed_from_response() {
+        let keypair = Keypair::generate();
+        let local_peer_id = PeerId::from_public_key(&keypair.public().into());
+        let (config, _stream) = Config::new(Duration::from_secs(5));
+        let (_manager, handle) = TransportManager::new(
+            keypair,
+            HashSet::from_iter([SupportedTransport::Tcp]),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let mut mdns = Mdns::new(handle.clone(), config, Vec::new()).unwrap();
+
+        let peer = PeerId::random();
+        let address1: Multiaddr = format!("/ip4/192.168.1.2/tcp/8888/p2p/{peer}").parse().unwrap();
+        let address2: Multiaddr = format!("/ip6/::1/tcp/8888/p2p/{peer}").parse().unwrap();
+        let local_address: Multiaddr =
+            format!("/ip4/192.168.1.3/tcp/8888/p2p/{local_peer_id}").parse().unwrap();
+
+        let name = Name::new_unchecked("remote");
+        let mut packet = Packet::new_reply(1);
+        packet.answers.push(ResourceRecord::new(
+            Name::new_unchecked(SERVICE_NAME),
+            CLASS::IN,
+            360,
+            RData::PTR(PTR(name.clone())),
+        ));
+
+        let entries = [&address1, &address2, &local_address]
+            .into_iter()
+            .map(|address| format!("dnsaddr={address}"))
+            .collect::<Vec<_>>();
+
+        for entry in &entries {
+            let mut record = TXT::new();
+            record.add_string(entry).unwrap();
+
+            packet.additional_records.push(ResourceRecord {
+                name: name.clone(),
+                class: CLASS::IN,
+                ttl: 360,
+                rdata: RData::TXT(record),
+                cache_flush: false,
+            });
+        }
+
+        let addresses = mdns.on_inbound_response(packet);
+        assert_eq!(addresses, vec![address1.clone(), address2.clone()]);
+
+        // discovered addresses are registered to `TransportManager` only once
+        assert_eq!(mdns.on_discovered(addresses.clone()), addresses);
+        assert!(mdns.on_discovered(addresses).is_empty());
+        assert!(handle.dial(&peer).is_ok());
+    }
+
+    #[tokio::test]
+    async fn response_advertises_listen_addresses() {
+        let keypair = Keypair::generate();
+        let local_peer_id = PeerId::from_public_key(&keypair.public().into());
+        let (config, _stream) = Config::new(Duration::from_secs(5));
+        let (_manager, handle) =
+            TransportManager::new(keypair, HashSet::new(), BandwidthSink::new(), 8usize);
+
+        let address1: Multiaddr = "/ip4/192.168.1.2/tcp/8888".parse().unwrap();
+        let address2: Multiaddr = "/ip6/::1/tcp/9999".parse().unwrap();
+        let mdns = Mdns::new(handle, config, vec![address1.clone(), address2.clone()]).unwrap();
+
+        let response = mdns.on_inbound_request(Packet::new_query(1)).unwrap();
+        let response = Packet::parse(&response).unwrap();
+
+        let advertised = response
+            .additional_records
+            .iter()
+            .flat_map(|record| match &record.rdata {
+                RData::TXT(text) => text
+                    .attributes()
+                    .into_iter()
+                    .filter_map(|(key, value)| (key == "dnsaddr").then_some(value?))
+                    .collect::<Vec<_>>(),
+                _ => Vec::new(),
+            })
+            .collect::<HashSet<_>>();
+
+        assert_eq!(
+            advertised,
+            HashSet::from_iter([
+                format!("{address1}/p2p/{local_peer_id}"),
+                format!("{address2}/p2p/{local_peer_id}"),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn multicast_group_rejoined_on_interface_up() {
+        let (config, _stream) = Config::new(Duration::from_secs(5));
+        let (_manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let mut mdns = Mdns::new(handle, config, Vec::new()).unwrap();
+
+        mdns.on_interface_event(InterfaceEvent::Up(Ipv4Addr::LOCALHOST)).await;
+        assert!(mdns.interfaces.contains(&Ipv4Addr::LOCALHOST));
+
+        mdns.on_interface_event(InterfaceEvent::Down(Ipv4Addr::LOCALHOST)).await;
+        assert!(mdns.interfaces.is_empty());
+
+        // interface coming back up joins the group again
+        mdns.on_interface_event(InterfaceEvent::Up(Ipv4Addr::LOCALHOST)).await;
+        assert!(mdns.interfaces.contains(&Ipv4Addr::LOCALHOST));
+    }
+
+    #[test]
+    fn query_interval_doubles_up_to_maximum() {
+        let mut backoff = QueryBackoff::new(Duration::from_secs(1), Duration::from_secs(10));
+
+        for expected in [1, 2, 4, 8, 10, 10] {
+            let expected = Duration::from_secs(expected);
+            let interval = backoff.next_interval();
+
+            assert!(interval >= expected);
+            assert!(interval <= expected.mul_f64(1.1));
+        }
+    }
+
+    #[test]
+    fn initial_query_interval_capped_to_maximum() {
+        let (config, _stream) = Config::new(Duration::from_millis(500));
+        assert_eq!(config.initial_query_interval, Duration::from_millis(500));
+
+        let config = config.with_initial_query_interval(Duration::from_secs(2));
+        assert_eq!(config.initial_query_interval, Duration::from_millis(500));
+
+        let (config, _stream) = Config::new(Duration::from_secs(30));
+        assert_eq!(config.initial_query_interval, INITIAL_QUERY_INTERVAL);
+    }
 }
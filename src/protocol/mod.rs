@@ -36,7 +36,7 @@ use std::fmt::Debug;
 pub(crate) use connection::Permit;
 pub(crate) use protocol_set::{InnerTransportEvent, ProtocolCommand, ProtocolSet};
 
-pub use transport_service::TransportService;
+pub use transport_service::{KeepAlive, TransportService};
 
 pub mod libp2p;
 pub mod mdns;
@@ -58,6 +58,7 @@ pub enum Direction {
 }
 
 /// Events emitted by one of the installed transports to protocol(s).
+#[non_exhaustive]
 #[derive(Debug)]
 pub enum TransportEvent {
     /// Connection established to `peer`.
@@ -69,6 +70,18 @@ pub enum TransportEvent {
         endpoint: Endpoint,
     },
 
+    /// Secondary connection established to `peer`.
+    ///
+    /// This is reported only to the protocol which dialed the connection with
+    /// [`TransportService::dial_secondary()`].
+    SecondaryConnectionEstablished {
+        /// Peer ID.
+        peer: PeerId,
+
+        /// Endpoint.
+        endpoint: Endpoint,
+    },
+
     /// Connection closed to peer.
     ConnectionClosed {
         /// Peer ID.
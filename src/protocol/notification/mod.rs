@@ -1600,7 +1600,9 @@ impl NotificationProtocol {
     }
 
     /// Handle next notification event.
-    async fn next_event(&mut self) {
+    ///
+    /// Returns `false` if the transport service has exited and the event loop must be stopped.
+    async fn next_event(&mut self) -> bool {
         // biased select is used because the substream events must be prioritized above other events
         // that is because a closed substream is detected by either `substreams` or `negotiation`
         // and if that event is not handled with priority but, e.g., inbound substream is
@@ -1764,7 +1766,8 @@ impl NotificationProtocol {
                     self.on_substream_open_failure(substream, error).await;
                 }
                 Some(TransportEvent::DialFailure { peer, address }) => self.on_dial_failure(peer, address).await,
-                None => (),
+                Some(TransportEvent::SecondaryConnectionEstablished { .. }) => {}
+                None => return false,
             },
             result = self.pending_validations.select_next_some(), if !self.pending_validations.is_empty() => {
                 if let Err(error) = self.on_validation_result(result.0, result.1).await {
@@ -1805,14 +1808,16 @@ impl NotificationProtocol {
                 }
             },
         }
+
+        true
     }
 
     /// Start [`NotificationProtocol`] event loop.
     pub(crate) async fn run(mut self) {
         tracing::debug!(target: LOG_TARGET, "starting notification event loop");
 
-        loop {
-            self.next_event().await;
-        }
+        while self.next_event().await {}
+
+        tracing::debug!(target: LOG_TARGET, "transport service has exited, exiting");
     }
 }
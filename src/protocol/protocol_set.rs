@@ -34,9 +34,10 @@ use crate::{
     PeerId,
 };
 
-use futures::{stream::FuturesUnordered, Stream, StreamExt};
+use futures::{future::BoxFuture, stream::FuturesUnordered, FutureExt, Stream, StreamExt};
 use multiaddr::Multiaddr;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio_util::sync::CancellationToken;
 
 use std::{
     collections::HashMap,
@@ -79,6 +80,19 @@ pub enum InnerTransportEvent {
         connection: ConnectionId,
     },
 
+    /// Connection is redundant after `peer` and the local node connected to each other
+    /// simultaneously.
+    ///
+    /// The connection must not be used for opening new substreams and it's closed once the
+    /// substreams open over it have finished.
+    ConnectionRedundant {
+        /// Peer ID.
+        peer: PeerId,
+
+        /// Connection ID.
+        connection: ConnectionId,
+    },
+
     /// Failed to dial peer.
     ///
     /// This is reported to that protocol which initiated the connection.
@@ -215,6 +229,11 @@ pub struct ProtocolSet {
     rx: Receiver<ProtocolCommand>,
     next_substream_id: Arc<AtomicUsize>,
     fallback_names: HashMap<ProtocolName, ProtocolName>,
+
+    /// Resolves when litep2p is shutting down and the connection must be closed.
+    ///
+    /// Set to `None` after the shutdown has been reported to the connection.
+    shutdown: Option<BoxFuture<'static, ()>>,
 }
 
 impl ProtocolSet {
@@ -223,6 +242,7 @@ impl ProtocolSet {
         mgr_tx: Sender<TransportManagerEvent>,
         next_substream_id: Arc<AtomicUsize>,
         protocols: HashMap<ProtocolName, ProtocolContext>,
+        shutdown: CancellationToken,
     ) -> Self {
         let (tx, rx) = channel(256);
 
@@ -244,6 +264,7 @@ impl ProtocolSet {
             next_substream_id,
             fallback_names,
             connection: ConnectionHandle::new(connection_id, tx),
+            shutdown: Some(Box::pin(shutdown.cancelled_owned())),
         }
     }
 
@@ -368,6 +389,9 @@ impl ProtocolSet {
     }
 
     /// Report to protocols that a connection was closed.
+    ///
+    /// Protocols which have exited are skipped so that the rest of the protocols and
+    /// the transport manager are still informed about the closed connection.
     pub(crate) async fn report_connection_closed(
         &mut self,
         peer: PeerId,
@@ -375,8 +399,8 @@ impl ProtocolSet {
     ) -> crate::Result<()> {
         let mut futures = self
             .protocols
-            .values()
-            .map(|sender| async move {
+            .iter()
+            .map(|(protocol, sender)| async move {
                 sender
                     .tx
                     .send(InnerTransportEvent::ConnectionClosed {
@@ -384,12 +408,19 @@ impl ProtocolSet {
                         connection: connection_id,
                     })
                     .await
+                    .map_err(|_| protocol)
             })
             .collect::<FuturesUnordered<_>>();
 
-        while !futures.is_empty() {
-            if let Some(Err(error)) = futures.next().await {
-                return Err(error.into());
+        while let Some(result) = futures.next().await {
+            if let Err(protocol) = result {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %protocol,
+                    ?peer,
+                    ?connection_id,
+                    "protocol exited, cannot report closed connection",
+                );
             }
         }
 
@@ -407,6 +438,13 @@ impl Stream for ProtocolSet {
     type Item = ProtocolCommand;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(shutdown) = self.shutdown.as_mut() {
+            if shutdown.poll_unpin(cx).is_ready() {
+                self.shutdown = None;
+                return Poll::Ready(Some(ProtocolCommand::ForceClose));
+            }
+        }
+
         self.rx.poll_recv(cx)
     }
 }
@@ -414,8 +452,12 @@ impl Stream for ProtocolSet {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::mock::substream::MockSubstream;
+    use crate::{
+        mock::substream::MockSubstream,
+        multistream_select::{dialer_select_proto, listener_select_proto, Version},
+    };
     use std::collections::HashSet;
+    use tokio_util::compat::TokioAsyncReadCompatExt;
 
     #[tokio::test]
     async fn fallback_is_provided() {
@@ -437,6 +479,7 @@ mod tests {
                     ],
                 },
             )]),
+            Default::default(),
         );
 
         let expected_protocols = HashSet::from([
@@ -484,6 +527,7 @@ mod tests {
                     ],
                 },
             )]),
+            Default::default(),
         );
 
         protocol_set
@@ -531,6 +575,7 @@ mod tests {
                     ],
                 },
             )]),
+            Default::default(),
         );
 
         protocol_set
@@ -557,4 +602,248 @@ mod tests {
             _ => panic!("invalid event received"),
         }
     }
+
+    #[tokio::test]
+    async fn inbound_substreams_dispatched_to_negotiated_protocol() {
+        let (tx, _rx) = channel(64);
+        let (tx1, mut rx1) = channel(64);
+        let (tx2, mut rx2) = channel(64);
+
+        let mut protocol_set = ProtocolSet::new(
+            ConnectionId::from(0usize),
+            tx,
+            Default::default(),
+            HashMap::from_iter([
+                (
+                    ProtocolName::from("/notif/1"),
+                    ProtocolContext {
+                        tx: tx1,
+                        codec: ProtocolCodec::Identity(32),
+                        fallback_names: Vec::new(),
+                    },
+                ),
+                (
+                    ProtocolName::from("/req/1"),
+                    ProtocolContext {
+                        tx: tx2,
+                        codec: ProtocolCodec::UnsignedVarint(None),
+                        fallback_names: vec![ProtocolName::from("/req/1/fallback")],
+                    },
+                ),
+            ]),
+            Default::default(),
+        );
+        let protocols = protocol_set.protocols();
+
+        for (substream_id, dialed) in ["/notif/1", "/req/1/fallback"].into_iter().enumerate() {
+            let (dialer, listener) = tokio::io::duplex(1024);
+
+            let (dialer_result, listener_result) = tokio::join!(
+                dialer_select_proto(dialer.compat(), vec![dialed], Version::V1),
+                listener_select_proto(
+                    listener.compat(),
+                    protocols.iter().map(|protocol| &**protocol).collect::<Vec<&str>>(),
+                ),
+            );
+            assert!(dialer_result.is_ok());
+            let (protocol, _io) = listener_result.unwrap();
+            assert_eq!(protocol, dialed);
+
+            protocol_set
+                .report_substream_open(
+                    PeerId::random(),
+                    ProtocolName::from(protocol.to_string()),
+                    Direction::Inbound,
+                    Substream::new_mock(
+                        PeerId::random(),
+                        SubstreamId::from(substream_id),
+                        Box::new(MockSubstream::new()),
+                    ),
+                )
+                .await
+                .unwrap();
+        }
+
+        match rx1.try_recv().unwrap() {
+            InnerTransportEvent::SubstreamOpened {
+                protocol, fallback, ..
+            } => {
+                assert!(fallback.is_none());
+                assert_eq!(protocol, ProtocolName::from("/notif/1"));
+            }
+            _ => panic!("invalid event received"),
+        }
+        match rx2.try_recv().unwrap() {
+            InnerTransportEvent::SubstreamOpened {
+                protocol, fallback, ..
+            } => {
+                assert_eq!(fallback, Some(ProtocolName::from("/req/1/fallback")));
+                assert_eq!(protocol, ProtocolName::from("/req/1"));
+            }
+            _ => panic!("invalid event received"),
+        }
+        assert!(rx1.try_recv().is_err());
+        assert!(rx2.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn slow_protocol_applies_backpressure() {
+        let (tx, _rx) = channel(64);
+        let (tx1, mut rx1) = channel(1);
+
+        let mut protocol_set = ProtocolSet::new(
+            ConnectionId::from(0usize),
+            tx,
+            Default::default(),
+            HashMap::from_iter([(
+                ProtocolName::from("/notif/1"),
+                ProtocolContext {
+                    tx: tx1,
+                    codec: ProtocolCodec::Identity(32),
+                    fallback_names: Vec::new(),
+                },
+            )]),
+            Default::default(),
+        );
+
+        protocol_set
+            .report_substream_open_failure(
+                ProtocolName::from("/notif/1"),
+                SubstreamId::from(0usize),
+                Error::Timeout,
+            )
+            .await
+            .unwrap();
+
+        // the channel is full so the second event must wait for the protocol
+        let mut future = Box::pin(protocol_set.report_substream_open_failure(
+            ProtocolName::from("/notif/1"),
+            SubstreamId::from(1usize),
+            Error::Timeout,
+        ));
+        assert!(
+            futures::future::poll_fn(|cx| Poll::Ready(future.poll_unpin(cx).is_pending())).await
+        );
+
+        // once the protocol reads the first event, the second event is delivered
+        assert!(std::matches!(
+            rx1.recv().await,
+            Some(InnerTransportEvent::SubstreamOpenFailure { substream, .. })
+                if substream == SubstreamId::from(0usize)
+        ));
+        future.await.unwrap();
+        assert!(std::matches!(
+            rx1.recv().await,
+            Some(InnerTransportEvent::SubstreamOpenFailure { substream, .. })
+                if substream == SubstreamId::from(1usize)
+        ));
+    }
+
+    #[tokio::test]
+    async fn exited_protocol_reported_as_error() {
+        let (tx, _rx) = channel(64);
+        let (tx1, rx1) = channel(64);
+
+        let mut protocol_set = ProtocolSet::new(
+            ConnectionId::from(0usize),
+            tx,
+            Default::default(),
+            HashMap::from_iter([(
+                ProtocolName::from("/notif/1"),
+                ProtocolContext {
+                    tx: tx1,
+                    codec: ProtocolCodec::Identity(32),
+                    fallback_names: Vec::new(),
+                },
+            )]),
+            Default::default(),
+        );
+        drop(rx1);
+
+        assert!(std::matches!(
+            protocol_set
+                .report_substream_open_failure(
+                    ProtocolName::from("/notif/1"),
+                    SubstreamId::from(0usize),
+                    Error::Timeout,
+                )
+                .await,
+            Err(Error::EssentialTaskClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn connection_closed_reported_even_if_protocol_exited() {
+        let (tx, mut rx) = channel(64);
+        let (tx1, rx1) = channel(64);
+        let (tx2, mut rx2) = channel(64);
+        let peer = PeerId::random();
+
+        let mut protocol_set = ProtocolSet::new(
+            ConnectionId::from(0usize),
+            tx,
+            Default::default(),
+            HashMap::from_iter([
+                (
+                    ProtocolName::from("/notif/1"),
+                    ProtocolContext {
+                        tx: tx1,
+                        codec: ProtocolCodec::Identity(32),
+                        fallback_names: Vec::new(),
+                    },
+                ),
+                (
+                    ProtocolName::from("/notif/2"),
+                    ProtocolContext {
+                        tx: tx2,
+                        codec: ProtocolCodec::Identity(32),
+                        fallback_names: Vec::new(),
+                    },
+                ),
+            ]),
+            Default::default(),
+        );
+        drop(rx1);
+
+        protocol_set
+            .report_connection_closed(peer, ConnectionId::from(0usize))
+            .await
+            .unwrap();
+
+        assert!(std::matches!(
+            rx2.recv().await,
+            Some(InnerTransportEvent::ConnectionClosed { peer: closed, .. }) if closed == peer
+        ));
+        assert!(std::matches!(
+            rx.recv().await,
+            Some(TransportManagerEvent::ConnectionClosed { peer: closed, .. }) if closed == peer
+        ));
+    }
+
+    #[tokio::test]
+    async fn connection_force_closed_on_shutdown() {
+        let (tx, _rx) = channel(64);
+        let shutdown = CancellationToken::new();
+
+        let mut protocol_set = ProtocolSet::new(
+            ConnectionId::from(0usize),
+            tx,
+            Default::default(),
+            HashMap::new(),
+            shutdown.clone(),
+        );
+
+        futures::future::poll_fn(|cx| match protocol_set.poll_next_unpin(cx) {
+            Poll::Pending => Poll::Ready(()),
+            _ => panic!("unexpected command"),
+        })
+        .await;
+
+        shutdown.cancel();
+
+        assert!(std::matches!(
+            protocol_set.next().await,
+            Some(ProtocolCommand::ForceClose)
+        ));
+    }
 }
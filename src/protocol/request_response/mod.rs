@@ -662,7 +662,9 @@ impl RequestResponseProtocol {
                 error: match error {
                     Error::NegotiationError(NegotiationError::MultistreamSelectError(
                         MultistreamFailed,
-                    )) => RequestResponseError::UnsupportedProtocol,
+                    ))
+                    | Error::ProtocolNegotiationFailed { .. } =>
+                        RequestResponseError::UnsupportedProtocol,
                     _ => RequestResponseError::Rejected,
                 },
             })
@@ -915,6 +917,7 @@ impl RequestResponseProtocol {
                         }
                     }
                     Some(TransportEvent::DialFailure { peer, .. }) => self.on_dial_failure(peer).await,
+                    Some(TransportEvent::SecondaryConnectionEstablished { .. }) => {}
                     None => return,
                 },
                 event = self.pending_inbound.select_next_some(), if !self.pending_inbound.is_empty() => {
@@ -28,11 +28,10 @@ use crate::{
 
 use futures::{future::BoxFuture, stream::FuturesUnordered, Stream, StreamExt};
 use multiaddr::{Multiaddr, Protocol};
-use multihash::Multihash;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{hash_map::Entry, HashMap, HashSet},
     fmt::Debug,
     pin::Pin,
     sync::{
@@ -40,12 +39,36 @@ use std::{
         Arc,
     },
     task::{Context, Poll},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 /// Logging target for the file.
 const LOG_TARGET: &str = "litep2p::transport-service";
 
+/// How long a redundant connection is kept open so the remote peer can finish opening substreams
+/// over it.
+///
+/// Longer than the keep-alive timeout of established connections so the connection replacing
+/// the redundant connection goes idle first.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Protocol's preference on whether connections to a peer should be kept open.
+///
+/// Connections are closed once all protocols have given up on them and there are no substreams
+/// open over them. If a protocol doesn't express a preference, the connections are kept open for
+/// a grace period after they've been established.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepAlive {
+    /// Keep the connections open until the preference is changed.
+    Yes,
+
+    /// Keep the connections open until the specified instant.
+    Until(Instant),
+
+    /// The protocol doesn't need the connections anymore.
+    No,
+}
+
 /// Connection context for the peer.
 ///
 /// Each peer is allowed to have at most two connections open. The first open connection is the
@@ -61,6 +84,9 @@ struct ConnectionContext {
 
     /// Secondary connection, if it exists.
     secondary: Option<ConnectionHandle>,
+
+    /// Keep-alive preference of the protocol, if set.
+    keep_alive: Option<KeepAlive>,
 }
 
 impl ConnectionContext {
@@ -69,6 +95,34 @@ impl ConnectionContext {
         Self {
             primary,
             secondary: None,
+            keep_alive: None,
+        }
+    }
+
+    /// Check if the protocol wants the connections to be kept open.
+    fn keep_open(&self) -> bool {
+        match self.keep_alive {
+            Some(KeepAlive::Yes) => true,
+            Some(KeepAlive::Until(until)) => until > Instant::now(),
+            Some(KeepAlive::No) | None => false,
+        }
+    }
+
+    /// Mark all connections as active.
+    fn activate(&mut self) {
+        self.primary.activate();
+
+        if let Some(handle) = &mut self.secondary {
+            handle.activate();
+        }
+    }
+
+    /// Downgrade all connections to non-active.
+    fn downgrade_all(&mut self) {
+        self.primary.close();
+
+        if let Some(handle) = &mut self.secondary {
+            handle.close();
         }
     }
 
@@ -124,6 +178,19 @@ pub struct TransportService {
 
     /// Pending keep-alive timeouts.
     keep_alive_timeouts: FuturesUnordered<BoxFuture<'static, (PeerId, ConnectionId)>>,
+
+    /// Number of secondary connections each peer is being dialed over by the protocol.
+    pending_secondary: HashMap<PeerId, usize>,
+
+    /// Redundant connections of simultaneous connects, tracked until they're closed.
+    ///
+    /// A redundant connection is drained once it's been established or, if it's the primary
+    /// connection, once the connection replacing it has been established.
+    redundant: HashSet<ConnectionId>,
+
+    /// Permits which keep the drained connections open for a grace period so that the remote
+    /// peer can finish opening substreams over them.
+    draining: FuturesUnordered<BoxFuture<'static, ()>>,
 }
 
 impl TransportService {
@@ -135,7 +202,27 @@ impl TransportService {
         next_substream_id: Arc<AtomicUsize>,
         transport_handle: TransportManagerHandle,
     ) -> (Self, Sender<InnerTransportEvent>) {
-        let (tx, rx) = channel(DEFAULT_CHANNEL_SIZE);
+        Self::with_channel_size(
+            local_peer_id,
+            protocol,
+            fallback_names,
+            next_substream_id,
+            transport_handle,
+            DEFAULT_CHANNEL_SIZE,
+        )
+    }
+
+    /// Create new [`TransportService`] which buffers at most `channel_size` events
+    /// from the connections before the connections are made to wait.
+    pub(crate) fn with_channel_size(
+        local_peer_id: PeerId,
+        protocol: ProtocolName,
+        fallback_names: Vec<ProtocolName>,
+        next_substream_id: Arc<AtomicUsize>,
+        transport_handle: TransportManagerHandle,
+        channel_size: usize,
+    ) -> (Self, Sender<InnerTransportEvent>) {
+        let (tx, rx) = channel(channel_size);
 
         (
             Self {
@@ -147,6 +234,9 @@ impl TransportService {
                 next_substream_id,
                 connections: HashMap::new(),
                 keep_alive_timeouts: FuturesUnordered::new(),
+                pending_secondary: HashMap::new(),
+                redundant: HashSet::new(),
+                draining: FuturesUnordered::new(),
             },
             tx,
         )
@@ -169,8 +259,40 @@ impl TransportService {
             "connection established",
         );
 
+        if self.redundant.contains(&connection_id) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?peer,
+                ?connection_id,
+                ?endpoint,
+                "drain redundant connection",
+            );
+
+            self.drain(handle);
+            return None;
+        }
+
         match self.connections.get_mut(&peer) {
             Some(context) => match context.secondary {
+                None if self.redundant.contains(context.primary.connection_id()) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?peer,
+                        ?connection_id,
+                        ?endpoint,
+                        redundant = ?context.primary.connection_id(),
+                        "replace redundant primary connection",
+                    );
+
+                    self.keep_alive_timeouts.push(Box::pin(async move {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        (peer, connection_id)
+                    }));
+                    let redundant = std::mem::replace(&mut context.primary, handle);
+                    self.drain(redundant);
+
+                    None
+                }
                 Some(_) => {
                     tracing::debug!(
                         target: LOG_TARGET,
@@ -188,10 +310,13 @@ impl TransportService {
                     }));
                     context.secondary = Some(handle);
 
-                    None
+                    self.pending_secondary
+                        .remove(&peer)
+                        .map(|_| TransportEvent::SecondaryConnectionEstablished { peer, endpoint })
                 }
             },
             None => {
+                self.pending_secondary.remove(&peer);
                 self.connections.insert(peer, ConnectionContext::new(handle));
                 self.keep_alive_timeouts.push(Box::pin(async move {
                     tokio::time::sleep(Duration::from_secs(5)).await;
@@ -203,12 +328,78 @@ impl TransportService {
         }
     }
 
+    /// Handle redundant connection event.
+    ///
+    /// If the connection hasn't been reported yet or it's the primary connection and the
+    /// connection replacing it hasn't been reported yet, the connection is drained later.
+    fn on_connection_redundant(&mut self, peer: PeerId, connection_id: ConnectionId) {
+        tracing::debug!(
+            target: LOG_TARGET,
+            ?peer,
+            protocol = %self.protocol,
+            ?connection_id,
+            "connection redundant",
+        );
+
+        self.redundant.insert(connection_id);
+
+        let Some(context) = self.connections.get_mut(&peer) else {
+            return;
+        };
+
+        let handle = if context.primary.connection_id() == &connection_id {
+            match context.secondary.take() {
+                Some(handle) => std::mem::replace(&mut context.primary, handle),
+                None => return,
+            }
+        } else {
+            match context.secondary.take() {
+                Some(handle) if handle.connection_id() == &connection_id => handle,
+                handle => {
+                    context.secondary = handle;
+                    return;
+                }
+            }
+        };
+
+        self.drain(handle);
+    }
+
+    /// Stop using the connection of `handle` and keep it open only for a grace period, after
+    /// which it's closed once the substreams open over it have finished.
+    fn drain(&mut self, handle: ConnectionHandle) {
+        if let Some(permit) = handle.try_get_permit() {
+            self.draining.push(Box::pin(async move {
+                tokio::time::sleep(DRAIN_TIMEOUT).await;
+                drop(permit);
+            }));
+        }
+    }
+
     /// Handle connection closed event.
     fn on_connection_closed(
         &mut self,
         peer: PeerId,
         connection_id: ConnectionId,
     ) -> Option<TransportEvent> {
+        // drained connections are not tracked, unless the redundant connection is the primary
+        // connection which hasn't been replaced yet
+        if self.redundant.remove(&connection_id)
+            && self
+                .connections
+                .get(&peer)
+                .is_none_or(|context| context.primary.connection_id() != &connection_id)
+        {
+            tracing::trace!(
+                target: LOG_TARGET,
+                ?peer,
+                ?connection_id,
+                "redundant connection closed",
+            );
+
+            return None;
+        }
+
         let Some(context) = self.connections.get_mut(&peer) else {
             tracing::warn!(
                 target: LOG_TARGET,
@@ -289,16 +480,40 @@ impl TransportService {
         self.transport_handle.dial_address(address)
     }
 
+    /// Dial peer using a `Multiaddr` over a new connection, even if the peer is already connected.
+    ///
+    /// If the peer is connected, the dialed connection becomes its secondary connection and
+    /// [`TransportEvent::SecondaryConnectionEstablished`] is emitted once it's been established.
+    /// The call is useful for verifying that the peer is reachable over `address`. If the peer is
+    /// not connected, the call is equivalent to [`TransportService::dial_address()`].
+    ///
+    /// Multiple addresses of the peer can be dialed concurrently and the secondary connection is
+    /// reported once, even if it was opened by the remote peer while the dials were in progress.
+    ///
+    /// Dial failures are reported as [`TransportEvent::DialFailure`].
+    pub fn dial_secondary(&mut self, address: Multiaddr) -> crate::Result<()> {
+        self.transport_handle.dial_secondary(address.clone())?;
+        *self
+            .pending_secondary
+            .entry(
+                PeerId::try_from_multiaddr(&address)
+                    .expect("`PeerId` to be validated by the handle"),
+            )
+            .or_default() += 1;
+
+        Ok(())
+    }
+
     /// Add one or more addresses for `peer`.
     ///
     /// The list is filtered for duplicates and unsupported transports.
     pub fn add_known_address(&mut self, peer: &PeerId, addresses: impl Iterator<Item = Multiaddr>) {
         let addresses: HashSet<Multiaddr> = addresses
-            .filter_map(|address| {
+            .map(|address| {
                 if !std::matches!(address.iter().last(), Some(Protocol::P2p(_))) {
-                    Some(address.with(Protocol::P2p(Multihash::from_bytes(&peer.to_bytes()).ok()?)))
+                    address.with(Protocol::P2p(peer.to_multihash()))
                 } else {
-                    Some(address)
+                    address
                 }
             })
             .collect();
@@ -306,6 +521,31 @@ impl TransportService {
         self.transport_handle.add_known_address(peer, addresses.into_iter());
     }
 
+    /// Add local address as observed by `peer`.
+    ///
+    /// The address is considered external once enough distinct peers have reported it.
+    pub(crate) fn add_external_address(&mut self, peer: PeerId, address: Multiaddr) -> bool {
+        self.transport_handle.add_external_address(peer, address)
+    }
+
+    /// Get external addresses of the local node, both manually added and confirmed.
+    pub(crate) fn external_addresses(&self) -> Vec<Multiaddr> {
+        self.transport_handle.external_addresses()
+    }
+
+    /// Get listen addresses of the local node, both with and without the local `PeerId`.
+    pub fn listen_addresses(&self) -> Vec<Multiaddr> {
+        self.transport_handle.listen_addresses()
+    }
+
+    /// Get a snapshot of the peers which are currently connected.
+    ///
+    /// The snapshot is taken from the peer states shared by all protocols, so protocols don't
+    /// need to track connected peers themselves.
+    pub fn connected_peers(&self) -> Vec<PeerId> {
+        self.transport_handle.connected_peers()
+    }
+
     /// Open substream to `peer`.
     ///
     /// Call fails if there is no connection open to `peer` or the channel towards
@@ -337,6 +577,43 @@ impl TransportService {
             .map(|_| substream_id)
     }
 
+    /// Set keep-alive preference for connections to `peer`.
+    ///
+    /// The preference stays in effect for as long as the peer is connected.
+    pub fn set_keep_alive(&mut self, peer: PeerId, keep_alive: KeepAlive) -> crate::Result<()> {
+        let context = self.connections.get_mut(&peer).ok_or(Error::PeerDoesntExist(peer))?;
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?peer,
+            protocol = %self.protocol,
+            ?keep_alive,
+            "set keep-alive",
+        );
+
+        context.keep_alive = Some(keep_alive);
+
+        match keep_alive {
+            KeepAlive::Yes => context.activate(),
+            KeepAlive::Until(until) => {
+                context.activate();
+
+                let connection_ids = std::iter::once(*context.primary.connection_id())
+                    .chain(context.secondary.as_ref().map(|handle| *handle.connection_id()));
+
+                for connection_id in connection_ids {
+                    self.keep_alive_timeouts.push(Box::pin(async move {
+                        tokio::time::sleep_until(until.into()).await;
+                        (peer, connection_id)
+                    }));
+                }
+            }
+            KeepAlive::No => context.downgrade_all(),
+        }
+
+        Ok(())
+    }
+
     /// Forcibly close the connection, even if other protocols have substreams open over it.
     pub fn force_close(&mut self, peer: PeerId) -> crate::Result<()> {
         let connection =
@@ -382,14 +659,33 @@ impl Stream for TransportService {
                         return Poll::Ready(Some(event));
                     }
                 }
+                Some(InnerTransportEvent::ConnectionRedundant { peer, connection }) => {
+                    self.on_connection_redundant(peer, connection);
+                }
+                Some(InnerTransportEvent::DialFailure { peer, address }) => {
+                    if let Entry::Occupied(mut entry) = self.pending_secondary.entry(peer) {
+                        *entry.get_mut() -= 1;
+
+                        if *entry.get() == 0 {
+                            entry.remove();
+                        }
+                    }
+                    return Poll::Ready(Some(TransportEvent::DialFailure { peer, address }));
+                }
                 Some(event) => return Poll::Ready(Some(event.into())),
             }
         }
 
+        while let Poll::Ready(Some(())) = self.draining.poll_next_unpin(cx) {}
+
         while let Poll::Ready(Some((peer, connection_id))) =
             self.keep_alive_timeouts.poll_next_unpin(cx)
         {
             if let Some(context) = self.connections.get_mut(&peer) {
+                if context.keep_open() {
+                    continue;
+                }
+
                 tracing::trace!(
                     target: LOG_TARGET,
                     ?peer,
@@ -431,6 +727,7 @@ mod tests {
             cmd_tx,
             HashSet::new(),
             Default::default(),
+            Default::default(),
         );
 
         let (service, sender) = TransportService::new(
@@ -498,6 +795,86 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn secondary_connection_reported_after_concurrent_dials() {
+        let (mut service, sender, _cmd_rx) = transport_service();
+        let peer = PeerId::random();
+
+        // register first connection
+        let (cmd_tx1, _cmd_rx1) = channel(64);
+        sender
+            .send(InnerTransportEvent::ConnectionEstablished {
+                peer,
+                connection: ConnectionId::from(0usize),
+                endpoint: Endpoint::listener(Multiaddr::empty(), ConnectionId::from(0usize)),
+                sender: ConnectionHandle::new(ConnectionId::from(0usize), cmd_tx1),
+            })
+            .await
+            .unwrap();
+
+        match service.next().await {
+            Some(TransportEvent::ConnectionEstablished { .. }) => {}
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        // dial two addresses of the peer at the same time
+        let address1 = Multiaddr::empty()
+            .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(peer.to_multihash()));
+        let address2 = Multiaddr::empty()
+            .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(9999))
+            .with(Protocol::P2p(peer.to_multihash()));
+
+        service.dial_secondary(address1.clone()).unwrap();
+        service.dial_secondary(address2).unwrap();
+
+        // first dial fails
+        sender
+            .send(InnerTransportEvent::DialFailure {
+                peer,
+                address: address1.clone(),
+            })
+            .await
+            .unwrap();
+
+        match service.next().await {
+            Some(TransportEvent::DialFailure {
+                peer: failed_peer,
+                address,
+            }) => {
+                assert_eq!(failed_peer, peer);
+                assert_eq!(address, address1);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        // remote peer opens the secondary connection while the second dial is in progress
+        let (cmd_tx2, _cmd_rx2) = channel(64);
+        sender
+            .send(InnerTransportEvent::ConnectionEstablished {
+                peer,
+                connection: ConnectionId::from(1usize),
+                endpoint: Endpoint::listener(Multiaddr::empty(), ConnectionId::from(1usize)),
+                sender: ConnectionHandle::new(ConnectionId::from(1usize), cmd_tx2),
+            })
+            .await
+            .unwrap();
+
+        match service.next().await {
+            Some(TransportEvent::SecondaryConnectionEstablished {
+                peer: connected_peer,
+                endpoint,
+            }) => {
+                assert_eq!(connected_peer, peer);
+                assert_eq!(endpoint.connection_id(), ConnectionId::from(1usize));
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+        assert!(service.pending_secondary.is_empty());
+    }
+
     #[tokio::test]
     async fn tertiary_connection_ignored() {
         let (mut service, sender, _) = transport_service();
@@ -860,4 +1237,244 @@ mod tests {
             Err(_) => {}
         }
     }
+
+    #[tokio::test]
+    async fn connection_closed_when_protocol_gives_up_on_it() {
+        let (mut service, sender, _) = transport_service();
+        let peer = PeerId::random();
+
+        let (cmd_tx, mut cmd_rx) = channel(64);
+        sender
+            .send(InnerTransportEvent::ConnectionEstablished {
+                peer,
+                connection: ConnectionId::from(1337usize),
+                endpoint: Endpoint::dialer(Multiaddr::empty(), ConnectionId::from(1337usize)),
+                sender: ConnectionHandle::new(ConnectionId::from(1337usize), cmd_tx),
+            })
+            .await
+            .unwrap();
+
+        assert!(std::matches!(
+            service.next().await,
+            Some(TransportEvent::ConnectionEstablished { .. })
+        ));
+
+        // the connection is kept open during the grace period
+        assert!(std::matches!(
+            cmd_rx.try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Empty)
+        ));
+
+        // protocol gives up on the connection and since it's the only user, the connection closes
+        service.set_keep_alive(peer, KeepAlive::No).unwrap();
+        assert!(std::matches!(
+            cmd_rx.try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Disconnected)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connection_kept_open_after_grace_period() {
+        let (mut service, sender, _) = transport_service();
+        let peer = PeerId::random();
+
+        let (cmd_tx, mut cmd_rx) = channel(64);
+        sender
+            .send(InnerTransportEvent::ConnectionEstablished {
+                peer,
+                connection: ConnectionId::from(1337usize),
+                endpoint: Endpoint::dialer(Multiaddr::empty(), ConnectionId::from(1337usize)),
+                sender: ConnectionHandle::new(ConnectionId::from(1337usize), cmd_tx),
+            })
+            .await
+            .unwrap();
+
+        assert!(std::matches!(
+            service.next().await,
+            Some(TransportEvent::ConnectionEstablished { .. })
+        ));
+        service.set_keep_alive(peer, KeepAlive::Yes).unwrap();
+
+        // poll the service past the grace period and verify the connection is still active
+        match tokio::time::timeout(Duration::from_secs(10), service.next()).await {
+            Ok(event) => panic!("didn't expect an event: {event:?}"),
+            Err(_) => {}
+        }
+        assert_eq!(service.keep_alive_timeouts.len(), 0);
+        assert!(std::matches!(
+            cmd_rx.try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Empty)
+        ));
+
+        // after protocol stops voting for the connection, it's closed
+        service.set_keep_alive(peer, KeepAlive::No).unwrap();
+        assert!(std::matches!(
+            cmd_rx.try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Disconnected)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn redundant_primary_connection_drained() {
+        let (mut service, sender, _) = transport_service();
+        let peer = PeerId::random();
+
+        let (cmd_tx1, mut cmd_rx1) = channel(64);
+        sender
+            .send(InnerTransportEvent::ConnectionEstablished {
+                peer,
+                connection: ConnectionId::from(0usize),
+                endpoint: Endpoint::dialer(Multiaddr::empty(), ConnectionId::from(0usize)),
+                sender: ConnectionHandle::new(ConnectionId::from(0usize), cmd_tx1),
+            })
+            .await
+            .unwrap();
+
+        assert!(std::matches!(
+            service.next().await,
+            Some(TransportEvent::ConnectionEstablished { .. })
+        ));
+        service.set_keep_alive(peer, KeepAlive::Yes).unwrap();
+
+        // primary connection is found redundant before the connection replacing it is reported
+        let (cmd_tx2, mut cmd_rx2) = channel(64);
+        sender
+            .send(InnerTransportEvent::ConnectionRedundant {
+                peer,
+                connection: ConnectionId::from(0usize),
+            })
+            .await
+            .unwrap();
+        sender
+            .send(InnerTransportEvent::ConnectionEstablished {
+                peer,
+                connection: ConnectionId::from(1usize),
+                endpoint: Endpoint::listener(Multiaddr::empty(), ConnectionId::from(1usize)),
+                sender: ConnectionHandle::new(ConnectionId::from(1usize), cmd_tx2),
+            })
+            .await
+            .unwrap();
+
+        match tokio::time::timeout(Duration::from_secs(1), service.next()).await {
+            Ok(event) => panic!("didn't expect an event: {event:?}"),
+            Err(_) => {}
+        }
+
+        let context = service.connections.get(&peer).unwrap();
+        assert_eq!(context.primary.connection_id(), &ConnectionId::from(1usize));
+        assert!(context.secondary.is_none());
+
+        // the redundant connection is kept open for the drain period, despite the keep-alive
+        assert!(std::matches!(
+            cmd_rx1.try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Empty)
+        ));
+
+        match tokio::time::timeout(DRAIN_TIMEOUT, service.next()).await {
+            Ok(event) => panic!("didn't expect an event: {event:?}"),
+            Err(_) => {}
+        }
+        assert!(std::matches!(
+            cmd_rx1.try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Disconnected)
+        ));
+        assert!(std::matches!(
+            cmd_rx2.try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Empty)
+        ));
+
+        // closing the redundant connection doesn't affect the peer
+        sender
+            .send(InnerTransportEvent::ConnectionClosed {
+                peer,
+                connection: ConnectionId::from(0usize),
+            })
+            .await
+            .unwrap();
+
+        match tokio::time::timeout(Duration::from_secs(1), service.next()).await {
+            Ok(event) => panic!("didn't expect an event: {event:?}"),
+            Err(_) => {}
+        }
+        assert_eq!(
+            service.connections.get(&peer).unwrap().primary.connection_id(),
+            &ConnectionId::from(1usize)
+        );
+        assert!(service.redundant.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn redundant_connection_drained_when_established() {
+        let (mut service, sender, _) = transport_service();
+        let peer = PeerId::random();
+
+        let (cmd_tx1, mut cmd_rx1) = channel(64);
+        sender
+            .send(InnerTransportEvent::ConnectionEstablished {
+                peer,
+                connection: ConnectionId::from(0usize),
+                endpoint: Endpoint::dialer(Multiaddr::empty(), ConnectionId::from(0usize)),
+                sender: ConnectionHandle::new(ConnectionId::from(0usize), cmd_tx1),
+            })
+            .await
+            .unwrap();
+
+        assert!(std::matches!(
+            service.next().await,
+            Some(TransportEvent::ConnectionEstablished { .. })
+        ));
+        service.set_keep_alive(peer, KeepAlive::Yes).unwrap();
+
+        // connection is found redundant before it's reported
+        let (cmd_tx2, mut cmd_rx2) = channel(64);
+        sender
+            .send(InnerTransportEvent::ConnectionRedundant {
+                peer,
+                connection: ConnectionId::from(1usize),
+            })
+            .await
+            .unwrap();
+        sender
+            .send(InnerTransportEvent::ConnectionEstablished {
+                peer,
+                connection: ConnectionId::from(1usize),
+                endpoint: Endpoint::listener(Multiaddr::empty(), ConnectionId::from(1usize)),
+                sender: ConnectionHandle::new(ConnectionId::from(1usize), cmd_tx2),
+            })
+            .await
+            .unwrap();
+
+        match tokio::time::timeout(DRAIN_TIMEOUT, service.next()).await {
+            Ok(event) => panic!("didn't expect an event: {event:?}"),
+            Err(_) => {}
+        }
+
+        // the redundant connection isn't used as the secondary connection and it's been closed
+        let context = service.connections.get(&peer).unwrap();
+        assert_eq!(context.primary.connection_id(), &ConnectionId::from(0usize));
+        assert!(context.secondary.is_none());
+        assert!(std::matches!(
+            cmd_rx1.try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Empty)
+        ));
+        assert!(std::matches!(
+            cmd_rx2.try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Disconnected)
+        ));
+
+        sender
+            .send(InnerTransportEvent::ConnectionClosed {
+                peer,
+                connection: ConnectionId::from(1usize),
+            })
+            .await
+            .unwrap();
+
+        match tokio::time::timeout(Duration::from_secs(1), service.next()).await {
+            Ok(event) => panic!("didn't expect an event: {event:?}"),
+            Err(_) => {}
+        }
+        assert!(service.connections.contains_key(&peer));
+        assert!(service.redundant.is_empty());
+    }
 }
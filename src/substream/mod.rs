@@ -31,16 +31,18 @@ use crate::{
 
 use bytes::{Buf, Bytes, BytesMut};
 use futures::{Sink, Stream};
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use unsigned_varint::{decode, encode};
 
 use std::{
     collections::{hash_map::Entry, HashMap, VecDeque},
     fmt,
+    future::Future,
     hash::Hash,
     io::ErrorKind,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 /// Logging target for the file.
@@ -137,6 +139,15 @@ macro_rules! delegate_poll_flush {
     }};
 }
 
+macro_rules! delegate_poll_close {
+    ($substream:expr, $cx:ident) => {{
+        #[cfg(test)]
+        if let SubstreamType::Mock(inner) = $substream {
+            return Pin::new(inner).poll_close($cx);
+        }
+    }};
+}
+
 macro_rules! check_size {
     ($max_size:expr, $size:expr) => {{
         if let Some(max_size) = $max_size {
@@ -326,6 +337,83 @@ impl Substream {
         };
     }
 
+    /// Close the substream gracefully.
+    ///
+    /// Flushes pending writes, closes the write side of the substream and then reads from the
+    /// substream until the remote peer closes its side, discarding any data it sends.
+    ///
+    /// Returns [`SubstreamError::Timeout`] if the substream is not closed within `timeout`.
+    pub async fn close_gracefully(mut self, timeout: Duration) -> crate::Result<()> {
+        match tokio::time::timeout(timeout, self.flush_and_drain()).await {
+            Err(_) => Err(Error::SubstreamError(SubstreamError::Timeout)),
+            Ok(result) => result,
+        }
+    }
+
+    /// Flush pending writes, close the write side and read until EOF.
+    async fn flush_and_drain(&mut self) -> crate::Result<()> {
+        futures::SinkExt::flush(self).await?;
+
+        #[cfg(test)]
+        if let SubstreamType::Mock(ref mut substream) = self.substream {
+            futures::SinkExt::close(substream).await?;
+            while futures::StreamExt::next(substream).await.is_some() {}
+
+            return Ok(());
+        }
+
+        self.shutdown().await?;
+
+        let mut buffer = [0u8; 1024];
+        while self.read(&mut buffer).await? != 0 {}
+
+        Ok(())
+    }
+
+    /// Wrap the substream into a [`TimeoutSubstream`] which fails reads and writes that don't
+    /// complete within `read_timeout` and `write_timeout`, respectively.
+    pub fn with_timeouts(
+        self,
+        read_timeout: Duration,
+        write_timeout: Duration,
+    ) -> TimeoutSubstream<Self> {
+        TimeoutSubstream::new(self, read_timeout, write_timeout)
+    }
+
+    /// Poll the substream for the next frame.
+    ///
+    /// Returns `Poll::Ready(None)` once the substream has been closed.
+    ///
+    /// Equivalent to polling the [`futures::Stream`] implementation of [`Substream`] but
+    /// doesn't require the substream to be pinned, allowing protocols to drive any number of
+    /// substreams from their own `poll()` without boxing a future for each of them.
+    pub fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Option<crate::Result<BytesMut>>> {
+        Stream::poll_next(Pin::new(self), cx)
+    }
+
+    /// Poll the substream to write `frame`.
+    ///
+    /// Returns `Poll::Ready(Ok(()))` once `frame` has been accepted for sending. If the substream
+    /// can't accept more data, `Poll::Pending` is returned and the caller must pass the same
+    /// frame to the next call. Flushing of the accepted frames is started right away and
+    /// continued by subsequent calls to [`Substream::poll_write()`] and
+    /// [`Substream::poll_close()`].
+    pub fn poll_write(&mut self, cx: &mut Context<'_>, frame: Bytes) -> Poll<crate::Result<()>> {
+        futures::ready!(Sink::poll_ready(Pin::new(&mut *self), cx))?;
+        Sink::start_send(Pin::new(&mut *self), frame)?;
+
+        match Sink::poll_flush(Pin::new(self), cx) {
+            Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+            Poll::Ready(Ok(())) | Poll::Pending => Poll::Ready(Ok(())),
+        }
+    }
+
+    /// Poll the substream to flush all pending frames and close it.
+    pub fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<crate::Result<()>> {
+        futures::ready!(Sink::poll_flush(Pin::new(&mut *self), cx))?;
+        Sink::poll_close(Pin::new(self), cx)
+    }
+
     /// Send identity payload to remote peer.
     async fn send_identity_payload<T: AsyncWrite + Unpin>(
         io: &mut T,
@@ -626,7 +714,7 @@ impl Stream for Substream {
                                                 if let Some(max_size) = max_size {
                                                     if size > max_size {
                                                         return Poll::Ready(Some(Err(
-                                                            Error::InvalidData,
+                                                            Error::FrameTooLarge(size),
                                                         )));
                                                     }
                                                 }
@@ -736,10 +824,155 @@ impl Sink<Bytes> for Substream {
     }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // `MockSubstream` implements `Sink` so calls to `poll_close()` must be delegated
+        delegate_poll_close!(&mut self.substream, cx);
+
         poll_shutdown!(&mut self.substream, cx).map_err(From::from)
     }
 }
 
+/// Substream wrapper which enforces per-operation read and write timeouts.
+///
+/// The read timer is started when [`Stream::poll_next()`] is first polled and reset once a frame
+/// is received. The write timer is started when [`Sink::poll_ready()`], [`Sink::poll_flush()`] or
+/// [`Sink::poll_close()`] returns [`Poll::Pending`] and reset once the operation completes.
+/// If a timer expires, the operation fails with [`SubstreamError::Timeout`].
+pub struct TimeoutSubstream<S = Substream> {
+    /// Inner substream.
+    substream: S,
+
+    /// Read timeout.
+    read_timeout: Duration,
+
+    /// Write timeout.
+    write_timeout: Duration,
+
+    /// Timer for the pending read, if any.
+    read_timer: Option<Pin<Box<tokio::time::Sleep>>>,
+
+    /// Timer for the pending write, if any.
+    write_timer: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl<S: fmt::Debug> fmt::Debug for TimeoutSubstream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimeoutSubstream")
+            .field("substream", &self.substream)
+            .field("read_timeout", &self.read_timeout)
+            .field("write_timeout", &self.write_timeout)
+            .finish()
+    }
+}
+
+impl<S> TimeoutSubstream<S> {
+    /// Create new [`TimeoutSubstream`].
+    pub fn new(substream: S, read_timeout: Duration, write_timeout: Duration) -> Self {
+        Self {
+            substream,
+            read_timeout,
+            write_timeout,
+            read_timer: None,
+            write_timer: None,
+        }
+    }
+
+    /// Get mutable reference to the inner substream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.substream
+    }
+
+    /// Consume the wrapper and return the inner substream.
+    pub fn into_inner(self) -> S {
+        self.substream
+    }
+
+    /// Poll the write timer after the inner substream returned [`Poll::Pending`].
+    fn poll_write_timer(&mut self, cx: &mut Context<'_>) -> Poll<crate::Result<()>> {
+        let write_timeout = self.write_timeout;
+        let timer = self
+            .write_timer
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(write_timeout)));
+
+        match timer.as_mut().poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(()) => {
+                self.write_timer = None;
+                Poll::Ready(Err(Error::SubstreamError(SubstreamError::Timeout)))
+            }
+        }
+    }
+
+    /// Poll the result of a write operation, enforcing the write timeout.
+    fn poll_write_op(
+        &mut self,
+        cx: &mut Context<'_>,
+        result: Poll<crate::Result<()>>,
+    ) -> Poll<crate::Result<()>> {
+        match result {
+            Poll::Ready(result) => {
+                self.write_timer = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending => self.poll_write_timer(cx),
+        }
+    }
+}
+
+impl<S> Stream for TimeoutSubstream<S>
+where
+    S: Stream<Item = crate::Result<BytesMut>> + Unpin,
+{
+    type Item = crate::Result<BytesMut>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        if let Poll::Ready(event) = Pin::new(&mut this.substream).poll_next(cx) {
+            this.read_timer = None;
+            return Poll::Ready(event);
+        }
+
+        let read_timeout = this.read_timeout;
+        let timer = this
+            .read_timer
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(read_timeout)));
+
+        match timer.as_mut().poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(()) => {
+                this.read_timer = None;
+                Poll::Ready(Some(Err(Error::SubstreamError(SubstreamError::Timeout))))
+            }
+        }
+    }
+}
+
+impl<S> Sink<Bytes> for TimeoutSubstream<S>
+where
+    S: Sink<Bytes, Error = Error> + Unpin,
+{
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let result = Pin::new(&mut self.substream).poll_ready(cx);
+        self.poll_write_op(cx, result)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        Pin::new(&mut self.substream).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let result = Pin::new(&mut self.substream).poll_flush(cx);
+        self.poll_write_op(cx, result)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let result = Pin::new(&mut self.substream).poll_close(cx);
+        self.poll_write_op(cx, result)
+    }
+}
+
 /// Substream set key.
 pub trait SubstreamSetKey: Hash + Unpin + fmt::Debug + PartialEq + Eq + Copy {}
 
@@ -1057,4 +1290,96 @@ mod tests {
             assert!(futures::poll!(set.next()).is_pending());
         }
     }
+
+    #[tokio::test]
+    async fn read_times_out() {
+        let mut substream = MockSubstream::new();
+        substream.expect_poll_next().returning(|_| Poll::Pending);
+
+        let mut substream = TimeoutSubstream::new(
+            substream,
+            Duration::from_millis(100),
+            Duration::from_millis(100),
+        );
+        let started = std::time::Instant::now();
+
+        match substream.next().await {
+            Some(Err(Error::SubstreamError(SubstreamError::Timeout))) => {}
+            event => panic!("invalid event received: {event:?}"),
+        }
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn write_times_out() {
+        let mut substream = MockSubstream::new();
+        substream.expect_poll_ready().returning(|_| Poll::Pending);
+
+        let mut substream = TimeoutSubstream::new(
+            substream,
+            Duration::from_millis(100),
+            Duration::from_millis(100),
+        );
+
+        match substream.send(Bytes::from(vec![0u8; 8])).await {
+            Err(Error::SubstreamError(SubstreamError::Timeout)) => {}
+            event => panic!("invalid event received: {event:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn buffered_writes_flushed_before_close() {
+        let mut sequence = mockall::Sequence::new();
+        let mut substream = MockSubstream::new();
+        substream
+            .expect_poll_ready()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .return_once(|_| Poll::Ready(Ok(())));
+        substream
+            .expect_start_send()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .return_once(|_| Ok(()));
+        substream
+            .expect_poll_flush()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .return_once(|_| Poll::Ready(Ok(())));
+        substream
+            .expect_poll_close()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .return_once(|_| Poll::Ready(Ok(())));
+        substream
+            .expect_poll_next()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .return_once(|_| Poll::Ready(None));
+
+        let peer = PeerId::random();
+        let mut substream =
+            Substream::new_mock(peer, SubstreamId::from(0usize), Box::new(substream));
+
+        // buffer the write without flushing it
+        substream.feed(Bytes::from(vec![1u8; 8])).await.unwrap();
+
+        substream.close_gracefully(Duration::from_secs(5)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn close_gracefully_times_out() {
+        let mut substream = MockSubstream::new();
+        substream.expect_poll_flush().returning(|_| Poll::Ready(Ok(())));
+        substream.expect_poll_close().returning(|_| Poll::Ready(Ok(())));
+        substream.expect_poll_next().returning(|_| Poll::Pending);
+
+        let peer = PeerId::random();
+        let substream = Substream::new_mock(peer, SubstreamId::from(0usize), Box::new(substream));
+
+        match substream.close_gracefully(Duration::from_millis(100)).await {
+            Err(Error::SubstreamError(SubstreamError::Timeout)) => {}
+            event => panic!("invalid event received: {event:?}"),
+        }
+    }
 }
@@ -0,0 +1,591 @@
+// Copyright 2024 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Event loop of a `yamux` connection.
+//!
+//! Shared by the transports which secure a byte stream and multiplex it with `yamux`. The
+//! transports only differ in how the stream is negotiated; once [`NegotiatedConnection`] has
+//! been created, the connection is driven by [`Connection`].
+
+use crate::{
+    config::Role,
+    error::{Error, NegotiationError},
+    multistream_select::{
+        dialer_select_proto, listener_select_proto, Negotiated,
+        NegotiationError as MultistreamNegotiationError, ProtocolError, Version,
+    },
+    protocol::{Direction, Permit, ProtocolCommand, ProtocolSet},
+    substream,
+    transport::{common::metered::MeteredStream, tcp::Substream, Endpoint},
+    types::{protocol::ProtocolName, ConnectionId, SubstreamId},
+    BandwidthSink, PeerId,
+};
+
+use futures::{
+    future::BoxFuture,
+    stream::{FuturesUnordered, StreamExt},
+    AsyncRead, AsyncWrite,
+};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::transport::connection";
+
+/// Negotiated substream.
+#[derive(Debug)]
+struct NegotiatedSubstream {
+    /// Substream direction.
+    direction: Direction,
+
+    /// Substream ID.
+    substream_id: SubstreamId,
+
+    /// Protocol name.
+    protocol: ProtocolName,
+
+    /// Yamux substream.
+    io: crate::yamux::Stream,
+
+    /// Permit.
+    permit: Permit,
+
+    /// Inbound substream slot.
+    ///
+    /// `None` for outbound substreams as they don't count towards the inbound substream limit.
+    slot: Option<Arc<()>>,
+}
+
+/// Substream error.
+#[derive(Debug)]
+struct SubstreamError {
+    /// Protocol, if the substream was opened locally.
+    protocol: Option<ProtocolName>,
+
+    /// Substream ID, if the substream was opened locally.
+    substream_id: Option<SubstreamId>,
+
+    /// Error.
+    error: Error,
+}
+
+/// Negotiate protocol.
+///
+/// If `version` is [`Version::V1Lazy`] and only one protocol is proposed, the dialer settles
+/// on the protocol without waiting for the confirmation from the remote. Otherwise the
+/// negotiation falls back to [`Version::V1`]. `version` is ignored by the listener.
+pub async fn negotiate_protocol<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    role: &Role,
+    protocols: Vec<&str>,
+    version: Version,
+    substream_open_timeout: Duration,
+) -> crate::Result<(Negotiated<S>, ProtocolName)> {
+    tracing::trace!(target: LOG_TARGET, ?protocols, ?version, "negotiating protocols");
+
+    let attempted = protocols.iter().map(|protocol| protocol.to_string()).collect::<Vec<_>>();
+    let version = match protocols.len() {
+        1 => version,
+        _ => Version::V1,
+    };
+
+    match tokio::time::timeout(substream_open_timeout, async move {
+        match role {
+            Role::Dialer => dialer_select_proto(stream, protocols, version).await,
+            Role::Listener => listener_select_proto(stream, protocols).await,
+        }
+    })
+    .await
+    {
+        Err(_) => Err(Error::Timeout),
+        Ok(Err(MultistreamNegotiationError::Failed)) => {
+            tracing::debug!(
+                target: LOG_TARGET,
+                protocols = ?attempted,
+                "remote doesn't support any of the protocols",
+            );
+
+            Err(Error::ProtocolNegotiationFailed {
+                protocols: attempted,
+            })
+        }
+        Ok(Err(MultistreamNegotiationError::ProtocolError(ProtocolError::IoError(error)))) =>
+            Err(Error::IoError(error.kind())),
+        Ok(Err(error)) => Err(Error::NegotiationError(
+            NegotiationError::MultistreamSelectError(error),
+        )),
+        Ok(Ok((protocol, socket))) => {
+            tracing::trace!(target: LOG_TARGET, ?protocol, "protocol negotiated");
+
+            Ok((socket, ProtocolName::from(protocol.to_string())))
+        }
+    }
+}
+
+/// Connection context for an opened connection that hasn't yet started its event loop.
+pub struct NegotiatedConnection<S> {
+    /// Yamux connection.
+    connection: crate::yamux::ControlledConnection<MeteredStream<S>>,
+
+    /// Yamux control.
+    control: crate::yamux::Control,
+
+    /// Remote peer ID.
+    peer: PeerId,
+
+    /// Endpoint.
+    endpoint: Endpoint,
+
+    /// Substream open timeout.
+    substream_open_timeout: Duration,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> NegotiatedConnection<S> {
+    /// Create new [`NegotiatedConnection`] by multiplexing the secured `stream` with `yamux`.
+    pub fn new(
+        stream: S,
+        peer: PeerId,
+        role: Role,
+        endpoint: Endpoint,
+        bandwidth_sink: &BandwidthSink,
+        yamux_config: crate::yamux::Config,
+        substream_open_timeout: Duration,
+    ) -> Self {
+        let connection = crate::yamux::Connection::new(
+            bandwidth_sink.meter(peer, stream),
+            yamux_config,
+            role.into(),
+        );
+        let (control, connection) = crate::yamux::Control::new(connection);
+
+        Self {
+            connection,
+            control,
+            peer,
+            endpoint,
+            substream_open_timeout,
+        }
+    }
+}
+
+impl<S> NegotiatedConnection<S> {
+    /// Get `ConnectionId` of the negotiated connection.
+    pub fn connection_id(&self) -> ConnectionId {
+        self.endpoint.connection_id()
+    }
+
+    /// Get `PeerId` of the negotiated connection.
+    pub fn peer(&self) -> PeerId {
+        self.peer
+    }
+
+    /// Get `Endpoint` of the negotiated connection.
+    pub fn endpoint(&self) -> Endpoint {
+        self.endpoint.clone()
+    }
+}
+
+/// Yamux connection.
+pub struct Connection<S> {
+    /// Protocol context.
+    protocol_set: ProtocolSet,
+
+    /// Yamux connection.
+    connection: crate::yamux::ControlledConnection<MeteredStream<S>>,
+
+    /// Yamux control.
+    control: crate::yamux::Control,
+
+    /// Remote peer ID.
+    peer: PeerId,
+
+    /// Endpoint.
+    endpoint: Endpoint,
+
+    /// Substream open timeout.
+    substream_open_timeout: Duration,
+
+    /// Next substream ID.
+    next_substream_id: Arc<AtomicUsize>,
+
+    // Bandwidth sink.
+    bandwidth_sink: BandwidthSink,
+
+    /// Pending substreams.
+    pending_substreams:
+        FuturesUnordered<BoxFuture<'static, Result<NegotiatedSubstream, SubstreamError>>>,
+
+    /// Maximum number of open inbound substreams.
+    max_inbound_substreams: usize,
+
+    /// Inbound substream slots.
+    ///
+    /// Each open inbound substream holds a clone of the `Arc` which makes the number of open
+    /// inbound substreams equal to its strong count minus one.
+    inbound_slots: Arc<()>,
+}
+
+impl<S> fmt::Debug for Connection<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("peer", &self.peer)
+            .field("next_substream_id", &self.next_substream_id)
+            .finish()
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> Connection<S> {
+    /// Create new [`Connection`] from [`NegotiatedConnection`].
+    pub fn new(
+        context: NegotiatedConnection<S>,
+        protocol_set: ProtocolSet,
+        bandwidth_sink: BandwidthSink,
+        next_substream_id: Arc<AtomicUsize>,
+        max_inbound_substreams: usize,
+    ) -> Self {
+        let NegotiatedConnection {
+            connection,
+            control,
+            peer,
+            endpoint,
+            substream_open_timeout,
+        } = context;
+
+        Self {
+            protocol_set,
+            connection,
+            control,
+            peer,
+            endpoint,
+            bandwidth_sink,
+            next_substream_id,
+            pending_substreams: FuturesUnordered::new(),
+            substream_open_timeout,
+            max_inbound_substreams,
+            inbound_slots: Arc::new(()),
+        }
+    }
+
+    /// Open substream for `protocol`.
+    async fn open_substream(
+        mut control: crate::yamux::Control,
+        substream_id: SubstreamId,
+        permit: Permit,
+        protocol: ProtocolName,
+        fallback_names: Vec<ProtocolName>,
+        open_timeout: Duration,
+    ) -> crate::Result<NegotiatedSubstream> {
+        tracing::debug!(target: LOG_TARGET, ?protocol, ?substream_id, "open substream");
+
+        let stream = match control.open_stream().await {
+            Ok(stream) => {
+                tracing::trace!(target: LOG_TARGET, ?substream_id, "substream opened");
+                stream
+            }
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?substream_id,
+                    ?error,
+                    "failed to open substream"
+                );
+                return Err(Error::YamuxError(Direction::Outbound(substream_id), error));
+            }
+        };
+
+        // TODO: protocols don't change after they've been initialized so this should be done only
+        // once
+        let protocols = std::iter::once(&*protocol)
+            .chain(fallback_names.iter().map(|protocol| &**protocol))
+            .collect();
+
+        // the substream is unwrapped from `Negotiated` right away which requires the negotiation
+        // to be completed so `V1Lazy` cannot be used for substreams
+        let (io, protocol) =
+            negotiate_protocol(stream, &Role::Dialer, protocols, Version::V1, open_timeout).await?;
+
+        Ok(NegotiatedSubstream {
+            io: io.inner(),
+            substream_id,
+            direction: Direction::Outbound(substream_id),
+            protocol,
+            permit,
+            slot: None,
+        })
+    }
+
+    /// Accept substream.
+    async fn accept_substream(
+        stream: crate::yamux::Stream,
+        permit: Permit,
+        slot: Arc<()>,
+        substream_id: SubstreamId,
+        protocols: Vec<ProtocolName>,
+        open_timeout: Duration,
+    ) -> crate::Result<NegotiatedSubstream> {
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?substream_id,
+            "accept inbound substream",
+        );
+
+        let protocols = protocols.iter().map(|protocol| &**protocol).collect::<Vec<&str>>();
+        let (io, protocol) =
+            negotiate_protocol(stream, &Role::Listener, protocols, Version::V1, open_timeout)
+                .await?;
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?substream_id,
+            "substream accepted and negotiated",
+        );
+
+        Ok(NegotiatedSubstream {
+            io: io.inner(),
+            substream_id,
+            direction: Direction::Inbound,
+            protocol,
+            permit,
+            slot: Some(slot),
+        })
+    }
+
+    /// Handle negotiated or failed substream.
+    async fn on_substream(
+        &mut self,
+        substream: Result<NegotiatedSubstream, SubstreamError>,
+    ) -> crate::Result<()> {
+        match substream {
+            Err(SubstreamError {
+                protocol,
+                substream_id,
+                error,
+            }) => {
+                tracing::debug!(target: LOG_TARGET, ?error, "failed to accept/open substream");
+
+                // failures of inbound substreams are not reported to protocols
+                if let (Some(protocol), Some(substream_id)) = (protocol, substream_id) {
+                    return self
+                        .protocol_set
+                        .report_substream_open_failure(protocol, substream_id, error)
+                        .await;
+                }
+
+                Ok(())
+            }
+            Ok(substream) => {
+                let protocol = substream.protocol.clone();
+                let direction = substream.direction;
+                let substream_id = substream.substream_id;
+                let socket = FuturesAsyncReadCompatExt::compat(substream.io);
+                let bandwidth_sink = self.bandwidth_sink.clone();
+
+                let substream = substream::Substream::new_tcp(
+                    self.peer,
+                    substream_id,
+                    Substream::new(socket, bandwidth_sink, substream.permit, substream.slot),
+                    self.protocol_set.protocol_codec(&protocol),
+                );
+
+                self.protocol_set
+                    .report_substream_open(self.peer, protocol, direction, substream)
+                    .await
+            }
+        }
+    }
+
+    /// Start connection event loop.
+    pub async fn start(mut self) -> crate::Result<()> {
+        self.protocol_set
+            .report_connection_established(self.peer, self.endpoint.clone())
+            .await?;
+
+        loop {
+            tokio::select! {
+                substream = self.connection.next() => match substream {
+                    Some(Ok(stream)) => {
+                        let open_inbound = Arc::strong_count(&self.inbound_slots) - 1;
+
+                        if open_inbound >= self.max_inbound_substreams {
+                            tracing::debug!(
+                                target: LOG_TARGET,
+                                peer = ?self.peer,
+                                max_inbound_substreams = ?self.max_inbound_substreams,
+                                "too many inbound substreams, reset substream",
+                            );
+
+                            // dropping the stream before it's negotiated resets it
+                            drop(stream);
+                            continue;
+                        }
+
+                        let slot = Arc::clone(&self.inbound_slots);
+                        let substream_id = SubstreamId::from(
+                            self.next_substream_id.fetch_add(1usize, Ordering::Relaxed),
+                        );
+                        let protocols = self.protocol_set.protocols();
+                        let permit =
+                            self.protocol_set.try_get_permit().ok_or(Error::ConnectionClosed)?;
+                        let open_timeout = self.substream_open_timeout;
+
+                        self.pending_substreams.push(Box::pin(async move {
+                            match tokio::time::timeout(
+                                open_timeout,
+                                Self::accept_substream(
+                                    stream,
+                                    permit,
+                                    slot,
+                                    substream_id,
+                                    protocols,
+                                    open_timeout,
+                                ),
+                            )
+                            .await
+                            {
+                                Ok(Ok(substream)) => Ok(substream),
+                                Ok(Err(error)) => Err(SubstreamError {
+                                    protocol: None,
+                                    substream_id: None,
+                                    error,
+                                }),
+                                Err(_) => Err(SubstreamError {
+                                    protocol: None,
+                                    substream_id: None,
+                                    error: Error::Timeout,
+                                }),
+                            }
+                        }));
+                    }
+                    Some(Err(error)) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            peer = ?self.peer,
+                            ?error,
+                            "connection closed with error",
+                        );
+                        return self
+                            .protocol_set
+                            .report_connection_closed(self.peer, self.endpoint.connection_id())
+                            .await;
+                    }
+                    None => {
+                        tracing::debug!(target: LOG_TARGET, peer = ?self.peer, "connection closed");
+                        return self
+                            .protocol_set
+                            .report_connection_closed(self.peer, self.endpoint.connection_id())
+                            .await;
+                    }
+                },
+                substream = self.pending_substreams.select_next_some(),
+                    if !self.pending_substreams.is_empty() =>
+                {
+                    if let Err(error) = self.on_substream(substream).await {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            peer = ?self.peer,
+                            ?error,
+                            "failed to report substream to protocol, closing connection",
+                        );
+                        return self
+                            .protocol_set
+                            .report_connection_closed(self.peer, self.endpoint.connection_id())
+                            .await;
+                    }
+                }
+                protocol = self.protocol_set.next() => match protocol {
+                    Some(ProtocolCommand::OpenSubstream {
+                        protocol,
+                        fallback_names,
+                        substream_id,
+                        permit,
+                    }) => {
+                        let control = self.control.clone();
+                        let open_timeout = self.substream_open_timeout;
+
+                        tracing::trace!(
+                            target: LOG_TARGET,
+                            ?protocol,
+                            ?substream_id,
+                            "open substream",
+                        );
+
+                        self.pending_substreams.push(Box::pin(async move {
+                            match tokio::time::timeout(
+                                open_timeout,
+                                Self::open_substream(
+                                    control,
+                                    substream_id,
+                                    permit,
+                                    protocol.clone(),
+                                    fallback_names,
+                                    open_timeout,
+                                ),
+                            )
+                            .await
+                            {
+                                Ok(Ok(substream)) => Ok(substream),
+                                Ok(Err(error)) => Err(SubstreamError {
+                                    protocol: Some(protocol),
+                                    substream_id: Some(substream_id),
+                                    error,
+                                }),
+                                Err(_) => Err(SubstreamError {
+                                    protocol: Some(protocol),
+                                    substream_id: Some(substream_id),
+                                    error: Error::Timeout,
+                                }),
+                            }
+                        }));
+                    }
+                    Some(ProtocolCommand::ForceClose) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            peer = ?self.peer,
+                            connection_id = ?self.endpoint.connection_id(),
+                            "force closing connection",
+                        );
+                        return self
+                            .protocol_set
+                            .report_connection_closed(self.peer, self.endpoint.connection_id())
+                            .await;
+                    }
+                    None => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            "protocols have disconnected, closing connection",
+                        );
+                        return self
+                            .protocol_set
+                            .report_connection_closed(self.peer, self.endpoint.connection_id())
+                            .await;
+                    }
+                },
+            }
+        }
+    }
+}
@@ -20,17 +20,21 @@
 
 //! Shared socket listener between TCP and WebSocket.
 
-use crate::{error::AddressError, Error, PeerId};
+use crate::{
+    error::AddressError,
+    transport::{
+        common::multiaddr::{parse_multiaddr, ParsedAddress},
+        dns::DnsResolver,
+        manager::SupportedTransport,
+    },
+    Error, PeerId,
+};
 
 use futures::Stream;
 use multiaddr::{Multiaddr, Protocol};
 use network_interface::{Addr, NetworkInterface, NetworkInterfaceConfig};
 use socket2::{Domain, Socket, Type};
 use tokio::net::{TcpListener as TokioTcpListener, TcpStream};
-use trust_dns_resolver::{
-    config::{ResolverConfig, ResolverOpts},
-    TokioAsyncResolver,
-};
 
 use std::{
     io,
@@ -55,6 +59,9 @@ pub enum AddressType {
         port: u16,
         dns_type: DnsType,
     },
+
+    /// In-process memory address.
+    Memory(u64),
 }
 
 /// The DNS type of the address.
@@ -69,11 +76,15 @@ pub enum DnsType {
 }
 
 impl AddressType {
-    /// Resolve the address to a concrete IP.
-    pub async fn lookup_ip(self) -> crate::Result<SocketAddr> {
+    /// Resolve the address to a concrete IP using `resolver`.
+    pub async fn lookup_ip(self, resolver: &dyn DnsResolver) -> crate::Result<SocketAddr> {
         let (url, port, dns_type) = match self {
             // We already have the IP address.
             AddressType::Socket(address) => return Ok(address),
+            AddressType::Memory(port) => {
+                tracing::debug!(target: LOG_TARGET, ?port, "memory address cannot be resolved");
+                return Err(Error::AddressError(AddressError::InvalidProtocol));
+            }
             AddressType::Dns {
                 address,
                 port,
@@ -81,25 +92,21 @@ impl AddressType {
             } => (address, port, dns_type),
         };
 
-        let lookup =
-            match TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
-                .lookup_ip(url.clone())
-                .await
-            {
-                Ok(lookup) => lookup,
-                Err(error) => {
-                    tracing::debug!(
-                        target: LOG_TARGET,
-                        ?error,
-                        "failed to resolve DNS address `{}`",
-                        url
-                    );
-
-                    return Err(Error::Other(format!("Failed to resolve DNS address {url}")));
-                }
-            };
+        let lookup = match resolver.lookup_ip(&url).await {
+            Ok(lookup) => lookup,
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "failed to resolve DNS address `{}`",
+                    url
+                );
+
+                return Err(Error::Other(format!("Failed to resolve DNS address {url}")));
+            }
+        };
 
-        let Some(ip) = lookup.iter().find(|ip| match dns_type {
+        let Some(ip) = lookup.into_iter().find(|ip| match dns_type {
             DnsType::Dns => true,
             DnsType::Dns4 => ip.is_ipv4(),
             DnsType::Dns6 => ip.is_ipv6(),
@@ -247,6 +254,15 @@ impl SocketListener {
 
                         return None;
                     }
+                    AddressType::Memory(port) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?port,
+                            "memory address not supported as bind address"
+                        );
+
+                        return None;
+                    }
                     AddressType::Socket(address) => address,
                 };
 
@@ -353,96 +369,25 @@ fn multiaddr_to_socket_address(
     address: &Multiaddr,
     ty: SocketListenerType,
 ) -> crate::Result<(AddressType, Option<PeerId>)> {
-    tracing::trace!(target: LOG_TARGET, ?address, "parse multi address");
-
-    let mut iter = address.iter();
-    // Small helper to handle DNS types.
-    let handle_dns_type =
-        |address: String, dns_type: DnsType, protocol: Option<Protocol>| match protocol {
-            Some(Protocol::Tcp(port)) => Ok(AddressType::Dns {
-                address,
-                port,
-                dns_type,
-            }),
-            protocol => {
-                tracing::error!(
-                    target: LOG_TARGET,
-                    ?protocol,
-                    "invalid transport protocol, expected `Tcp`",
-                );
-                return Err(Error::AddressError(AddressError::InvalidProtocol));
-            }
-        };
-
-    let socket_address = match iter.next() {
-        Some(Protocol::Ip6(address)) => match iter.next() {
-            Some(Protocol::Tcp(port)) =>
-                AddressType::Socket(SocketAddr::new(IpAddr::V6(address), port)),
-            protocol => {
-                tracing::error!(
-                    target: LOG_TARGET,
-                    ?protocol,
-                    "invalid transport protocol, expected `Tcp`",
-                );
-                return Err(Error::AddressError(AddressError::InvalidProtocol));
-            }
-        },
-        Some(Protocol::Ip4(address)) => match iter.next() {
-            Some(Protocol::Tcp(port)) =>
-                AddressType::Socket(SocketAddr::new(IpAddr::V4(address), port)),
-            protocol => {
-                tracing::error!(
-                    target: LOG_TARGET,
-                    ?protocol,
-                    "invalid transport protocol, expected `Tcp`",
-                );
-                return Err(Error::AddressError(AddressError::InvalidProtocol));
-            }
-        },
-        Some(Protocol::Dns(address)) => handle_dns_type(address.into(), DnsType::Dns, iter.next())?,
-        Some(Protocol::Dns4(address)) =>
-            handle_dns_type(address.into(), DnsType::Dns4, iter.next())?,
-        Some(Protocol::Dns6(address)) =>
-            handle_dns_type(address.into(), DnsType::Dns6, iter.next())?,
-        protocol => {
-            tracing::error!(target: LOG_TARGET, ?protocol, "invalid transport protocol");
-            return Err(Error::AddressError(AddressError::InvalidProtocol));
-        }
-    };
-
-    match ty {
-        SocketListenerType::Tcp => (),
-        SocketListenerType::WebSocket => {
-            // verify that `/ws`/`/wss` is part of the multi address
-            match iter.next() {
-                Some(Protocol::Ws(_address)) => {}
-                Some(Protocol::Wss(_address)) => {}
-                protocol => {
-                    tracing::error!(
-                        target: LOG_TARGET,
-                        ?protocol,
-                        "invalid protocol, expected `Ws` or `Wss`"
-                    );
-                    return Err(Error::AddressError(AddressError::InvalidProtocol));
-                }
-            };
-        }
-    }
-
-    let maybe_peer = match iter.next() {
-        Some(Protocol::P2p(multihash)) => Some(PeerId::from_multihash(multihash)?),
-        None => None,
-        protocol => {
+    let ParsedAddress {
+        transport,
+        socket,
+        peer,
+    } = parse_multiaddr(address)?;
+
+    match (ty, transport) {
+        (SocketListenerType::Tcp, SupportedTransport::Tcp)
+        | (SocketListenerType::WebSocket, SupportedTransport::WebSocket) => Ok((socket, peer)),
+        (_, transport) => {
             tracing::error!(
                 target: LOG_TARGET,
-                ?protocol,
-                "invalid protocol, expected `P2p` or `None`"
+                ?address,
+                ?transport,
+                "invalid transport for socket listener",
             );
-            return Err(Error::AddressError(AddressError::InvalidProtocol));
+            Err(Error::AddressError(AddressError::InvalidProtocol))
         }
-    };
-
-    Ok((socket_address, maybe_peer))
+    }
 }
 
 impl Stream for SocketListener {
@@ -751,4 +696,51 @@ mod tests {
             ))),
         );
     }
+
+    /// DNS resolver which resolves every host to the same addresses.
+    struct MockResolver(Vec<IpAddr>);
+
+    #[async_trait::async_trait]
+    impl DnsResolver for MockResolver {
+        async fn lookup_ip(&self, _host: &str) -> crate::Result<Vec<IpAddr>> {
+            Ok(self.0.clone())
+        }
+
+        async fn lookup_txt(&self, _name: &str) -> crate::Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn dns_address_resolved_with_custom_resolver() {
+        let resolver = MockResolver(vec![
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+            IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)),
+        ]);
+
+        let (address, _) = TcpAddress::multiaddr_to_socket_address(
+            &"/dns4/example.com/tcp/8888".parse().expect("valid multiaddress"),
+        )
+        .unwrap();
+        assert_eq!(
+            address.lookup_ip(&resolver).await.unwrap(),
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 8888),
+        );
+
+        let (address, _) = TcpAddress::multiaddr_to_socket_address(
+            &"/dns6/example.com/tcp/8888".parse().expect("valid multiaddress"),
+        )
+        .unwrap();
+        assert_eq!(
+            address.lookup_ip(&resolver).await.unwrap(),
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 8888),
+        );
+
+        let resolver = MockResolver(vec![IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4))]);
+        let (address, _) = TcpAddress::multiaddr_to_socket_address(
+            &"/dns6/example.com/tcp/8888".parse().expect("valid multiaddress"),
+        )
+        .unwrap();
+        assert!(address.lookup_ip(&resolver).await.is_err());
+    }
 }
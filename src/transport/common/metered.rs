@@ -0,0 +1,180 @@
+// Copyright 2024 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Metering adapter for connection I/O.
+
+use futures::io::{AsyncRead, AsyncWrite};
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+/// Inbound/outbound byte counters.
+#[derive(Debug, Default)]
+pub struct ByteCounters {
+    /// Number of inbound bytes.
+    inbound: AtomicU64,
+
+    /// Number of outbound bytes.
+    outbound: AtomicU64,
+}
+
+impl ByteCounters {
+    /// Increase the amount of inbound bytes.
+    fn increase_inbound(&self, bytes: usize) {
+        let _ = self.inbound.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Increase the amount of outbound bytes.
+    fn increase_outbound(&self, bytes: usize) {
+        let _ = self.outbound.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Get the number of inbound and outbound bytes.
+    pub fn get(&self) -> (u64, u64) {
+        (
+            self.inbound.load(Ordering::Relaxed),
+            self.outbound.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Meter of a connection.
+///
+/// The bytes are accounted both to the counters of the remote peer and to the counters
+/// shared by all connections.
+#[derive(Debug, Clone)]
+pub struct ConnectionMeter {
+    /// Counters of the remote peer.
+    peer: Arc<ByteCounters>,
+
+    /// Counters of all connections.
+    total: Arc<ByteCounters>,
+}
+
+impl ConnectionMeter {
+    /// Create new [`ConnectionMeter`].
+    pub fn new(peer: Arc<ByteCounters>, total: Arc<ByteCounters>) -> Self {
+        Self { peer, total }
+    }
+
+    /// Account `bytes` received from the remote peer.
+    pub fn on_read(&self, bytes: usize) {
+        self.peer.increase_inbound(bytes);
+        self.total.increase_inbound(bytes);
+    }
+
+    /// Account `bytes` sent to the remote peer.
+    pub fn on_write(&self, bytes: usize) {
+        self.peer.increase_outbound(bytes);
+        self.total.increase_outbound(bytes);
+    }
+}
+
+/// Stream which meters the bytes read from and written to the inner stream.
+#[derive(Debug)]
+pub struct MeteredStream<S> {
+    /// Inner stream.
+    inner: S,
+
+    /// Meter of the connection.
+    meter: ConnectionMeter,
+}
+
+impl<S> MeteredStream<S> {
+    /// Create new [`MeteredStream`].
+    pub fn new(inner: S, meter: ConnectionMeter) -> Self {
+        Self { inner, meter }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for MeteredStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let nread = futures::ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.meter.on_read(nread);
+
+        Poll::Ready(Ok(nread))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for MeteredStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let nwritten = futures::ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.meter.on_write(nwritten);
+
+        Poll::Ready(Ok(nwritten))
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let nwritten = futures::ready!(Pin::new(&mut self.inner).poll_write_vectored(cx, bufs))?;
+        self.meter.on_write(nwritten);
+
+        Poll::Ready(Ok(nwritten))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn bytes_are_metered() {
+        let peer = Arc::new(ByteCounters::default());
+        let total = Arc::new(ByteCounters::default());
+        let mut stream = MeteredStream::new(
+            futures::io::Cursor::new(vec![0u8; 64]),
+            ConnectionMeter::new(Arc::clone(&peer), Arc::clone(&total)),
+        );
+
+        let mut buf = vec![0u8; 16];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.write_all(&[1u8; 8]).await.unwrap();
+
+        assert_eq!(peer.get(), (16u64, 8u64));
+        assert_eq!(total.get(), (16u64, 8u64));
+    }
+}
@@ -20,4 +20,7 @@
 
 //! Shared transport protocol implementation
 
+pub mod connection;
 pub mod listener;
+pub mod metered;
+pub mod multiaddr;
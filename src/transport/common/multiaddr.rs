@@ -0,0 +1,346 @@
+// Copyright 2024 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Multiaddress parsing shared between the transport manager and the transports.
+
+use crate::{
+    error::AddressError,
+    transport::{
+        common::listener::{AddressType, DnsType},
+        manager::SupportedTransport,
+    },
+    Error, PeerId,
+};
+
+use multiaddr::{Multiaddr, Protocol};
+
+use std::net::{IpAddr, SocketAddr};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::transport::multiaddr";
+
+/// Parsed multiaddress.
+#[derive(Debug)]
+pub struct ParsedAddress {
+    /// Transport the address is dialed or listened on with.
+    pub transport: SupportedTransport,
+
+    /// Socket address, DNS name or memory port of the remote.
+    ///
+    /// For relayed addresses, this is the socket of the relay.
+    pub socket: AddressType,
+
+    /// `PeerId`, if the address ended with `/p2p`.
+    pub peer: Option<PeerId>,
+}
+
+/// Parse `address` into a [`ParsedAddress`].
+///
+/// The accepted protocol stacks are:
+///  - `/ip4|ip6|dns|dns4|dns6/.../tcp/<port>[/p2p/<peer>]`
+///  - `/ip4|ip6|dns|dns4|dns6/.../tcp/<port>/ws|wss[/p2p/<peer>]`
+///  - `/ip4|ip6|dns|dns4|dns6/.../udp/<port>/quic-v1[/p2p/<peer>]`
+///  - `/memory/<port>[/p2p/<peer>]`
+///  - `<any of the above>/p2p/<relay>/p2p-circuit[/p2p/<peer>]`
+///
+/// Any other protocol stack is rejected with [`AddressError::InvalidProtocol`].
+pub fn parse_multiaddr(address: &Multiaddr) -> crate::Result<ParsedAddress> {
+    tracing::trace!(target: LOG_TARGET, ?address, "parse multi address");
+
+    if let Some(position) = address
+        .iter()
+        .position(|protocol| std::matches!(protocol, Protocol::P2pCircuit))
+    {
+        return parse_circuit(address, position);
+    }
+
+    let mut iter = address.iter();
+
+    let host = match iter.next() {
+        Some(Protocol::Memory(port)) => {
+            let peer = parse_peer(iter.next())?;
+
+            if let Some(protocol) = iter.next() {
+                tracing::debug!(target: LOG_TARGET, ?protocol, "trailing protocol after `p2p`");
+                return Err(Error::AddressError(AddressError::InvalidProtocol));
+            }
+
+            return Ok(ParsedAddress {
+                transport: SupportedTransport::Memory,
+                socket: AddressType::Memory(port),
+                peer,
+            });
+        }
+        Some(Protocol::Ip4(address)) => Host::Ip(IpAddr::V4(address)),
+        Some(Protocol::Ip6(address)) => Host::Ip(IpAddr::V6(address)),
+        Some(Protocol::Dns(address)) => Host::Dns(address.into(), DnsType::Dns),
+        Some(Protocol::Dns4(address)) => Host::Dns(address.into(), DnsType::Dns4),
+        Some(Protocol::Dns6(address)) => Host::Dns(address.into(), DnsType::Dns6),
+        protocol => {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?protocol,
+                "invalid protocol, expected `ip4`/`ip6`/`dns`/`dns4`/`dns6`",
+            );
+            return Err(Error::AddressError(AddressError::InvalidProtocol));
+        }
+    };
+
+    let (transport, port, next) = match iter.next() {
+        Some(Protocol::Tcp(port)) => match iter.next() {
+            Some(Protocol::Ws(_)) | Some(Protocol::Wss(_)) =>
+                (SupportedTransport::WebSocket, port, iter.next()),
+            protocol => (SupportedTransport::Tcp, port, protocol),
+        },
+        Some(Protocol::Udp(port)) => match iter.next() {
+            Some(Protocol::QuicV1) => (SupportedTransport::Quic, port, iter.next()),
+            protocol => {
+                tracing::debug!(target: LOG_TARGET, ?protocol, "invalid protocol, expected `quic-v1`");
+                return Err(Error::AddressError(AddressError::InvalidProtocol));
+            }
+        },
+        protocol => {
+            tracing::debug!(target: LOG_TARGET, ?protocol, "invalid protocol, expected `tcp`/`udp`");
+            return Err(Error::AddressError(AddressError::InvalidProtocol));
+        }
+    };
+
+    let peer = parse_peer(next)?;
+
+    if let Some(protocol) = iter.next() {
+        tracing::debug!(target: LOG_TARGET, ?protocol, "trailing protocol after `p2p`");
+        return Err(Error::AddressError(AddressError::InvalidProtocol));
+    }
+
+    let socket = match host {
+        Host::Ip(address) => AddressType::Socket(SocketAddr::new(address, port)),
+        Host::Dns(address, dns_type) => AddressType::Dns {
+            address,
+            port,
+            dns_type,
+        },
+    };
+
+    Ok(ParsedAddress {
+        transport,
+        socket,
+        peer,
+    })
+}
+
+/// Parse relayed `address` which has `/p2p-circuit` at `position`.
+fn parse_circuit(address: &Multiaddr, position: usize) -> crate::Result<ParsedAddress> {
+    let relay: Multiaddr = address.iter().take(position).collect();
+
+    let socket = match parse_multiaddr(&relay)? {
+        ParsedAddress {
+            socket,
+            peer: Some(_),
+            ..
+        } => socket,
+        ParsedAddress { peer: None, .. } => {
+            tracing::debug!(target: LOG_TARGET, ?address, "`PeerId` of the relay missing");
+            return Err(Error::AddressError(AddressError::PeerIdMissing));
+        }
+    };
+
+    let mut iter = address.iter().skip(position + 1);
+    let peer = parse_peer(iter.next())?;
+
+    if let Some(protocol) = iter.next() {
+        tracing::debug!(target: LOG_TARGET, ?protocol, "trailing protocol after `p2p`");
+        return Err(Error::AddressError(AddressError::InvalidProtocol));
+    }
+
+    Ok(ParsedAddress {
+        transport: SupportedTransport::Relay,
+        socket,
+        peer,
+    })
+}
+
+/// Parse the optional `/p2p` protocol ending the address.
+fn parse_peer(protocol: Option<Protocol<'_>>) -> crate::Result<Option<PeerId>> {
+    match protocol {
+        Some(Protocol::P2p(multihash)) => Ok(Some(PeerId::from_multihash(multihash)?)),
+        None => Ok(None),
+        protocol => {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?protocol,
+                "invalid protocol, expected `p2p` or `None`",
+            );
+            Err(Error::AddressError(AddressError::InvalidProtocol))
+        }
+    }
+}
+
+/// Host part of the address.
+enum Host {
+    /// IP address.
+    Ip(IpAddr),
+
+    /// DNS name.
+    Dns(String, DnsType),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(address: &str) -> crate::Result<ParsedAddress> {
+        parse_multiaddr(&address.parse().expect("valid multiaddress"))
+    }
+
+    #[test]
+    fn parse_tcp() {
+        let parsed = parse("/ip4/127.0.0.1/tcp/8888").unwrap();
+        assert_eq!(parsed.transport, SupportedTransport::Tcp);
+        assert!(std::matches!(
+            parsed.socket,
+            AddressType::Socket(address) if address == "127.0.0.1:8888".parse().unwrap()
+        ));
+        assert!(parsed.peer.is_none());
+
+        let peer = PeerId::random();
+        let parsed = parse(&format!("/ip6/::1/tcp/8888/p2p/{peer}")).unwrap();
+        assert_eq!(parsed.transport, SupportedTransport::Tcp);
+        assert!(std::matches!(
+            parsed.socket,
+            AddressType::Socket(address) if address == "[::1]:8888".parse().unwrap()
+        ));
+        assert_eq!(parsed.peer, Some(peer));
+    }
+
+    #[test]
+    fn parse_websocket() {
+        let parsed = parse("/ip4/127.0.0.1/tcp/8888/ws").unwrap();
+        assert_eq!(parsed.transport, SupportedTransport::WebSocket);
+        assert!(parsed.peer.is_none());
+
+        let peer = PeerId::random();
+        let parsed = parse(&format!("/ip6/::1/tcp/8888/wss/p2p/{peer}")).unwrap();
+        assert_eq!(parsed.transport, SupportedTransport::WebSocket);
+        assert_eq!(parsed.peer, Some(peer));
+    }
+
+    #[test]
+    fn parse_quic() {
+        let peer = PeerId::random();
+        let parsed = parse(&format!("/ip4/127.0.0.1/udp/8888/quic-v1/p2p/{peer}")).unwrap();
+        assert_eq!(parsed.transport, SupportedTransport::Quic);
+        assert!(std::matches!(
+            parsed.socket,
+            AddressType::Socket(address) if address == "127.0.0.1:8888".parse().unwrap()
+        ));
+        assert_eq!(parsed.peer, Some(peer));
+    }
+
+    #[test]
+    fn parse_dns() {
+        for (address, expected) in [
+            ("/dns/hello.world/tcp/8888", DnsType::Dns),
+            ("/dns4/hello.world/tcp/8888/ws", DnsType::Dns4),
+            ("/dns6/hello.world/udp/8888/quic-v1", DnsType::Dns6),
+        ] {
+            match parse(address).unwrap().socket {
+                AddressType::Dns {
+                    address,
+                    port,
+                    dns_type,
+                } => {
+                    assert_eq!(address, "hello.world");
+                    assert_eq!(port, 8888);
+                    assert_eq!(dns_type, expected);
+                }
+                socket => panic!("invalid socket: {socket:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_memory() {
+        let parsed = parse("/memory/1337").unwrap();
+        assert_eq!(parsed.transport, SupportedTransport::Memory);
+        assert!(std::matches!(parsed.socket, AddressType::Memory(1337)));
+        assert!(parsed.peer.is_none());
+
+        let peer = PeerId::random();
+        let parsed = parse(&format!("/memory/1337/p2p/{peer}")).unwrap();
+        assert_eq!(parsed.transport, SupportedTransport::Memory);
+        assert_eq!(parsed.peer, Some(peer));
+    }
+
+    #[test]
+    fn parse_relay() {
+        let relay = PeerId::random();
+        let peer = PeerId::random();
+
+        let parsed = parse(&format!("/ip4/127.0.0.1/tcp/8888/p2p/{relay}/p2p-circuit")).unwrap();
+        assert_eq!(parsed.transport, SupportedTransport::Relay);
+        assert!(std::matches!(
+            parsed.socket,
+            AddressType::Socket(address) if address == "127.0.0.1:8888".parse().unwrap()
+        ));
+        assert!(parsed.peer.is_none());
+
+        let parsed = parse(&format!("/memory/1337/p2p/{relay}/p2p-circuit/p2p/{peer}")).unwrap();
+        assert_eq!(parsed.transport, SupportedTransport::Relay);
+        assert!(std::matches!(parsed.socket, AddressType::Memory(1337)));
+        assert_eq!(parsed.peer, Some(peer));
+
+        assert!(std::matches!(
+            parse(&format!("/ip4/127.0.0.1/tcp/8888/p2p-circuit/p2p/{peer}")),
+            Err(Error::AddressError(AddressError::PeerIdMissing))
+        ));
+        assert!(std::matches!(
+            parse(&format!(
+                "/memory/1/p2p/{relay}/p2p-circuit/p2p/{peer}/p2p-circuit"
+            )),
+            Err(Error::AddressError(AddressError::InvalidProtocol))
+        ));
+    }
+
+    #[test]
+    fn parse_malformed() {
+        let peer = PeerId::random();
+
+        for address in [
+            format!("/p2p/{peer}"),
+            format!("/ip4/127.0.0.1/udp/8888/p2p/{peer}"),
+            format!("/ip4/127.0.0.1/udp/8888/utp/p2p/{peer}"),
+            format!("/ip4/127.0.0.1/sctp/8888/p2p/{peer}"),
+            format!("/ip4/127.0.0.1/tcp/8888/utp/p2p/{peer}"),
+            format!("/ip4/127.0.0.1/tcp/8888/ws/utp"),
+            format!("/ip4/127.0.0.1/tcp/8888/p2p/{peer}/ws"),
+            format!("/ip4/127.0.0.1"),
+            format!("/memory/1337/tcp/8888"),
+            format!("/memory/1337/p2p/{peer}/ws"),
+        ] {
+            assert!(
+                std::matches!(
+                    parse(&address),
+                    Err(Error::AddressError(AddressError::InvalidProtocol))
+                ),
+                "{address} should be rejected",
+            );
+        }
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! DNS resolution for `/dns`, `/dns4`, `/dns6` and `/dnsaddr` addresses.
+
+use crate::error::Error;
+
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use trust_dns_resolver::TokioAsyncResolver;
+
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::transport::dns";
+
+/// Trait which defines the interface a DNS resolver must implement.
+#[async_trait::async_trait]
+pub trait DnsResolver: Send + Sync {
+    /// Resolve `host` into IP addresses.
+    async fn lookup_ip(&self, host: &str) -> crate::Result<Vec<IpAddr>>;
+
+    /// Resolve `host` into IP addresses and get how long the addresses may be cached for.
+    ///
+    /// By default the addresses are not cached.
+    async fn lookup_ip_with_ttl(&self, host: &str) -> crate::Result<(Vec<IpAddr>, Duration)> {
+        Ok((self.lookup_ip(host).await?, Duration::ZERO))
+    }
+
+    /// Look up TXT records of `name`.
+    async fn lookup_txt(&self, name: &str) -> crate::Result<Vec<String>>;
+}
+
+#[async_trait::async_trait]
+impl DnsResolver for TokioAsyncResolver {
+    async fn lookup_ip(&self, host: &str) -> crate::Result<Vec<IpAddr>> {
+        self.lookup_ip_with_ttl(host).await.map(|(addresses, _)| addresses)
+    }
+
+    async fn lookup_ip_with_ttl(&self, host: &str) -> crate::Result<(Vec<IpAddr>, Duration)> {
+        match TokioAsyncResolver::lookup_ip(self, host).await {
+            Ok(lookup) => Ok((
+                lookup.iter().collect(),
+                lookup.valid_until().saturating_duration_since(Instant::now()),
+            )),
+            Err(error) => {
+                tracing::debug!(target: LOG_TARGET, ?error, ?host, "failed to resolve host");
+                Err(Error::DnsAddressResolutionFailed)
+            }
+        }
+    }
+
+    async fn lookup_txt(&self, name: &str) -> crate::Result<Vec<String>> {
+        match TokioAsyncResolver::txt_lookup(self, name).await {
+            Ok(lookup) => Ok(lookup
+                .iter()
+                .map(|record| {
+                    record
+                        .iter()
+                        .map(|data| String::from_utf8_lossy(data).into_owned())
+                        .collect::<String>()
+                })
+                .collect()),
+            Err(error) => {
+                tracing::debug!(target: LOG_TARGET, ?error, ?name, "failed to look up txt records");
+                Err(Error::DnsAddressResolutionFailed)
+            }
+        }
+    }
+}
+
+/// Cached IP addresses of a host.
+#[derive(Debug)]
+struct CachedLookup {
+    /// Resolved addresses.
+    addresses: Vec<IpAddr>,
+
+    /// When the addresses expire.
+    expires: Instant,
+}
+
+/// DNS resolver which caches the IP addresses resolved by another resolver.
+///
+/// The addresses are cached for as long as the resolver allows, at most `cache_size` hosts at a
+/// time. When the cache is full, the least recently used host is evicted. TXT records are not
+/// cached.
+pub(crate) struct CachingResolver {
+    /// Resolver used for lookups that miss the cache.
+    resolver: Arc<dyn DnsResolver>,
+
+    /// Maximum number of cached hosts.
+    cache_size: usize,
+
+    /// Cached lookups, least recently used first.
+    cache: Mutex<IndexMap<String, CachedLookup>>,
+}
+
+impl CachingResolver {
+    /// Create new [`CachingResolver`].
+    pub(crate) fn new(resolver: Arc<dyn DnsResolver>, cache_size: usize) -> Self {
+        Self {
+            resolver,
+            cache_size,
+            cache: Mutex::new(IndexMap::new()),
+        }
+    }
+
+    /// Get the cached addresses of `host` and their remaining TTL, if they haven't expired.
+    fn cached(&self, host: &str) -> Option<(Vec<IpAddr>, Duration)> {
+        let mut cache = self.cache.lock();
+        let lookup = cache.shift_remove(host)?;
+        let ttl = lookup.expires.saturating_duration_since(Instant::now());
+
+        if ttl.is_zero() {
+            return None;
+        }
+
+        let addresses = lookup.addresses.clone();
+        cache.insert(host.to_string(), lookup);
+
+        Some((addresses, ttl))
+    }
+
+    /// Cache `addresses` of `host` for `ttl`.
+    fn cache(&self, host: &str, addresses: Vec<IpAddr>, ttl: Duration) {
+        if self.cache_size == 0 || ttl.is_zero() {
+            return;
+        }
+
+        let mut cache = self.cache.lock();
+        cache.shift_remove(host);
+
+        if cache.len() >= self.cache_size {
+            cache.shift_remove_index(0);
+        }
+
+        cache.insert(
+            host.to_string(),
+            CachedLookup {
+                addresses,
+                expires: Instant::now() + ttl,
+            },
+        );
+    }
+}
+
+#[async_trait::async_trait]
+impl DnsResolver for CachingResolver {
+    async fn lookup_ip(&self, host: &str) -> crate::Result<Vec<IpAddr>> {
+        self.lookup_ip_with_ttl(host).await.map(|(addresses, _)| addresses)
+    }
+
+    async fn lookup_ip_with_ttl(&self, host: &str) -> crate::Result<(Vec<IpAddr>, Duration)> {
+        if let Some(lookup) = self.cached(host) {
+            tracing::trace!(target: LOG_TARGET, ?host, "use cached lookup");
+            return Ok(lookup);
+        }
+
+        let (addresses, ttl) = self.resolver.lookup_ip_with_ttl(host).await?;
+        self.cache(host, addresses.clone(), ttl);
+
+        Ok((addresses, ttl))
+    }
+
+    async fn lookup_txt(&self, name: &str) -> crate::Result<Vec<String>> {
+        self.resolver.lookup_txt(name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        net::Ipv4Addr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// DNS resolver which counts its lookups and resolves every host to the same address.
+    struct CountingResolver {
+        lookups: Arc<AtomicUsize>,
+        ttl: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl DnsResolver for CountingResolver {
+        async fn lookup_ip(&self, host: &str) -> crate::Result<Vec<IpAddr>> {
+            self.lookup_ip_with_ttl(host).await.map(|(addresses, _)| addresses)
+        }
+
+        async fn lookup_ip_with_ttl(&self, _: &str) -> crate::Result<(Vec<IpAddr>, Duration)> {
+            self.lookups.fetch_add(1usize, Ordering::Relaxed);
+            Ok((vec![IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4))], self.ttl))
+        }
+
+        async fn lookup_txt(&self, _: &str) -> crate::Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn caching_resolver(ttl: Duration, cache_size: usize) -> (CachingResolver, Arc<AtomicUsize>) {
+        let lookups = Arc::new(AtomicUsize::new(0usize));
+        let resolver = CountingResolver {
+            lookups: Arc::clone(&lookups),
+            ttl,
+        };
+
+        (
+            CachingResolver::new(Arc::new(resolver), cache_size),
+            lookups,
+        )
+    }
+
+    #[tokio::test]
+    async fn lookup_cached_within_ttl() {
+        let (resolver, lookups) = caching_resolver(Duration::from_secs(60), 32usize);
+
+        let first = resolver.lookup_ip("example.com").await.unwrap();
+        let second = resolver.lookup_ip("example.com").await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(lookups.load(Ordering::Relaxed), 1usize);
+    }
+
+    #[tokio::test]
+    async fn expired_lookup_not_cached() {
+        let (resolver, lookups) = caching_resolver(Duration::ZERO, 32usize);
+
+        resolver.lookup_ip("example.com").await.unwrap();
+        resolver.lookup_ip("example.com").await.unwrap();
+
+        assert_eq!(lookups.load(Ordering::Relaxed), 2usize);
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_host_evicted() {
+        let (resolver, lookups) = caching_resolver(Duration::from_secs(60), 2usize);
+
+        resolver.lookup_ip("first.com").await.unwrap();
+        resolver.lookup_ip("second.com").await.unwrap();
+        resolver.lookup_ip("first.com").await.unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 2usize);
+
+        // `second.com` is the least recently used host
+        resolver.lookup_ip("third.com").await.unwrap();
+        resolver.lookup_ip("first.com").await.unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 3usize);
+
+        resolver.lookup_ip("second.com").await.unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 4usize);
+    }
+}
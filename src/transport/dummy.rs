@@ -96,7 +96,10 @@ impl Transport for DummyTransport {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{transport::Endpoint, Error, PeerId};
+    use crate::{
+        transport::{Endpoint, SupportedTransport},
+        Error, PeerId,
+    };
     use futures::StreamExt;
 
     #[tokio::test]
@@ -115,6 +118,7 @@ mod tests {
         transport.inject_event(TransportEvent::ConnectionEstablished {
             peer,
             endpoint: endpoint.clone(),
+            transport: SupportedTransport::Tcp,
         });
 
         match transport.next().await.unwrap() {
@@ -133,6 +137,7 @@ mod tests {
             TransportEvent::ConnectionEstablished {
                 peer: event_peer,
                 endpoint: event_endpoint,
+                ..
             } => {
                 assert_eq!(peer, event_peer);
                 assert_eq!(endpoint, event_endpoint);
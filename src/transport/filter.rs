@@ -0,0 +1,214 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Inbound connection filtering.
+
+use crate::error::{AddressError, Error};
+
+use std::{
+    fmt::Debug,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+/// Filter for inbound connections.
+///
+/// The filter is consulted by the transport after the connection has been accepted from the
+/// socket but before any protocol negotiation takes place. If the filter rejects the connection,
+/// it is closed immediately.
+pub trait ConnectionFilter: Debug + Send + Sync {
+    /// Check whether an inbound connection from `address` should be accepted.
+    fn accept(&self, address: &SocketAddr) -> bool;
+}
+
+/// IP address range in CIDR notation, e.g., `10.0.0.0/8` or `fe80::/10`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    /// Network address.
+    network: IpAddr,
+
+    /// Prefix length.
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Create new [`IpRange`].
+    ///
+    /// Returns an error if `prefix_len` is longer than the address.
+    pub fn new(network: IpAddr, prefix_len: u8) -> crate::Result<Self> {
+        let max_len = match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        if prefix_len > max_len {
+            return Err(Error::AddressError(AddressError::InvalidIpRange(format!(
+                "{network}/{prefix_len}"
+            ))));
+        }
+
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+
+    /// Check if `address` belongs to the range.
+    ///
+    /// IPv4 and IPv6 addresses never match each other.
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        match (self.network, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(*address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(*address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = Error;
+
+    /// Parse range from `<address>/<prefix length>`.
+    ///
+    /// If the prefix length is omitted, the range matches only the given address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::AddressError(AddressError::InvalidIpRange(s.to_string()));
+
+        let (network, prefix_len) = match s.split_once('/') {
+            Some((network, prefix_len)) => (
+                network.parse::<IpAddr>().map_err(|_| invalid())?,
+                Some(prefix_len.parse::<u8>().map_err(|_| invalid())?),
+            ),
+            None => (s.parse::<IpAddr>().map_err(|_| invalid())?, None),
+        };
+
+        let prefix_len = prefix_len.unwrap_or(match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        });
+
+        IpRange::new(network, prefix_len)
+    }
+}
+
+/// Connection filter based on IP address allow and deny lists.
+///
+/// Denied ranges take precedence over allowed ranges. If no allowed ranges are configured,
+/// all addresses not explicitly denied are accepted.
+#[derive(Debug, Default, Clone)]
+pub struct IpFilter {
+    /// Allowed ranges.
+    allow: Vec<IpRange>,
+
+    /// Denied ranges.
+    deny: Vec<IpRange>,
+}
+
+impl IpFilter {
+    /// Create new [`IpFilter`] which accepts all connections.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `range` to the allowlist.
+    pub fn with_allowed(mut self, range: IpRange) -> Self {
+        self.allow.push(range);
+        self
+    }
+
+    /// Add `range` to the denylist.
+    pub fn with_denied(mut self, range: IpRange) -> Self {
+        self.deny.push(range);
+        self
+    }
+}
+
+impl ConnectionFilter for IpFilter {
+    fn accept(&self, address: &SocketAddr) -> bool {
+        let ip = address.ip();
+
+        if self.deny.iter().any(|range| range.contains(&ip)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|range| range.contains(&ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ip_range() {
+        assert_eq!(
+            "10.0.0.0/8".parse::<IpRange>().unwrap(),
+            IpRange::new("10.0.0.0".parse().unwrap(), 8).unwrap(),
+        );
+        assert_eq!(
+            "::1".parse::<IpRange>().unwrap(),
+            IpRange::new("::1".parse().unwrap(), 128).unwrap(),
+        );
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("10.0.0/8".parse::<IpRange>().is_err());
+        assert!("10.0.0.0/".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn ip_range_contains() {
+        let range: IpRange = "192.168.0.0/16".parse().unwrap();
+        assert!(range.contains(&"192.168.1.1".parse().unwrap()));
+        assert!(!range.contains(&"192.169.0.1".parse().unwrap()));
+        assert!(!range.contains(&"::ffff:192.168.1.1".parse().unwrap()));
+
+        let range: IpRange = "0.0.0.0/0".parse().unwrap();
+        assert!(range.contains(&"1.2.3.4".parse().unwrap()));
+
+        let range: IpRange = "fe80::/10".parse().unwrap();
+        assert!(range.contains(&"fe80::1".parse().unwrap()));
+        assert!(!range.contains(&"fec0::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn denylist_takes_precedence() {
+        let filter = IpFilter::new()
+            .with_allowed("10.0.0.0/8".parse().unwrap())
+            .with_denied("10.1.0.0/16".parse().unwrap());
+
+        assert!(filter.accept(&"10.2.0.1:8888".parse().unwrap()));
+        assert!(!filter.accept(&"10.1.0.1:8888".parse().unwrap()));
+        assert!(!filter.accept(&"11.0.0.1:8888".parse().unwrap()));
+    }
+
+    #[test]
+    fn empty_allowlist_accepts_all() {
+        let filter = IpFilter::new().with_denied("127.0.0.1".parse().unwrap());
+
+        assert!(filter.accept(&"127.0.0.2:8888".parse().unwrap()));
+        assert!(filter.accept(&"[::1]:8888".parse().unwrap()));
+        assert!(!filter.accept(&"127.0.0.1:8888".parse().unwrap()));
+    }
+}
@@ -18,12 +18,44 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::{types::ConnectionId, PeerId};
+use crate::{
+    transport::{manager::LOG_TARGET, EXTERNAL_ADDRESS_CONFIRMATIONS},
+    types::ConnectionId,
+    PeerId,
+};
 
+use indexmap::IndexMap;
 use multiaddr::{Multiaddr, Protocol};
-use multihash::Multihash;
 
-use std::collections::{BinaryHeap, HashSet};
+use std::{
+    borrow::Cow,
+    collections::{BinaryHeap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Canonicalize `address` so that equivalent addresses compare equal.
+///
+/// DNS names are case-insensitive so they're converted to lowercase.
+fn canonicalize(address: Multiaddr) -> Multiaddr {
+    fn lowercase(name: Cow<'_, str>) -> Cow<'_, str> {
+        match name.chars().any(|c| c.is_ascii_uppercase()) {
+            true => Cow::Owned(name.to_ascii_lowercase()),
+            false => name,
+        }
+    }
+
+    address
+        .iter()
+        .map(|protocol| match protocol {
+            Protocol::Dns(name) => Protocol::Dns(lowercase(name)),
+            Protocol::Dns4(name) => Protocol::Dns4(lowercase(name)),
+            Protocol::Dns6(name) => Protocol::Dns6(lowercase(name)),
+            Protocol::Dnsaddr(name) => Protocol::Dnsaddr(lowercase(name)),
+            protocol => protocol,
+        })
+        .collect()
+}
 
 #[allow(clippy::derived_hash_with_manual_eq)]
 #[derive(Debug, Clone, Hash)]
@@ -32,10 +64,15 @@ pub struct AddressRecord {
     score: i32,
 
     /// Address.
-    address: Multiaddr,
+    ///
+    /// Shared between the clones of the record and the address store index.
+    address: Arc<Multiaddr>,
 
     /// Connection ID, if specifed.
     connection_id: Option<ConnectionId>,
+
+    /// When the address was added or last connected to successfully.
+    last_used: Instant,
 }
 
 impl AsRef<Multiaddr> for AddressRecord {
@@ -47,24 +84,26 @@ impl AsRef<Multiaddr> for AddressRecord {
 impl AddressRecord {
     /// Create new `AddressRecord` and if `address` doesn't contain `P2p`,
     /// append the provided `PeerId` to the address.
+    ///
+    /// DNS names of the address are converted to lowercase.
     pub fn new(
         peer: &PeerId,
         address: Multiaddr,
         score: i32,
         connection_id: Option<ConnectionId>,
     ) -> Self {
+        let address = canonicalize(address);
         let address = if !std::matches!(address.iter().last(), Some(Protocol::P2p(_))) {
-            address.with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).expect("valid peer id"),
-            ))
+            address.with(Protocol::P2p(peer.to_multihash()))
         } else {
             address
         };
 
         Self {
-            address,
+            address: Arc::new(address),
             score,
             connection_id,
+            last_used: Instant::now(),
         }
     }
 
@@ -72,20 +111,22 @@ impl AddressRecord {
     ///
     /// If `address` doesn't contain `PeerId`, return `None` to indicate that this
     /// an invalid `Multiaddr` from the perspective of the `TransportManager`.
+    ///
+    /// DNS names of the address are converted to lowercase.
     pub fn from_multiaddr(address: Multiaddr) -> Option<AddressRecord> {
         if !std::matches!(address.iter().last(), Some(Protocol::P2p(_))) {
             return None;
         }
 
         Some(AddressRecord {
-            address,
+            address: Arc::new(canonicalize(address)),
             score: 0i32,
             connection_id: None,
+            last_used: Instant::now(),
         })
     }
 
     /// Get address score.
-    #[cfg(test)]
     pub fn score(&self) -> i32 {
         self.score
     }
@@ -101,8 +142,14 @@ impl AddressRecord {
     }
 
     /// Update score of an address.
+    ///
+    /// A positive score update marks the address as used.
     pub fn update_score(&mut self, score: i32) {
         self.score += score;
+
+        if score > 0 {
+            self.last_used = Instant::now();
+        }
     }
 
     /// Set `ConnectionId` for the [`AddressRecord`].
@@ -138,7 +185,7 @@ pub struct AddressStore {
     pub by_score: BinaryHeap<AddressRecord>,
 
     /// Addresses queryable by hashing them for faster lookup.
-    pub by_address: HashSet<Multiaddr>,
+    pub by_address: HashSet<Arc<Multiaddr>>,
 }
 
 impl FromIterator<Multiaddr> for AddressStore {
@@ -158,7 +205,7 @@ impl FromIterator<AddressRecord> for AddressStore {
     fn from_iter<T: IntoIterator<Item = AddressRecord>>(iter: T) -> Self {
         let mut store = AddressStore::new();
         for record in iter {
-            store.by_address.insert(record.address.clone());
+            store.by_address.insert(Arc::clone(&record.address));
             store.by_score.push(record);
         }
 
@@ -198,7 +245,7 @@ impl AddressStore {
 
     /// Check if address is already in the a
     pub fn contains(&self, address: &Multiaddr) -> bool {
-        self.by_address.contains(address)
+        self.by_address.contains(&canonicalize(address.clone()))
     }
 
     /// Insert new address record into [`AddressStore`] with default address score.
@@ -208,10 +255,56 @@ impl AddressStore {
         }
 
         record.connection_id = None;
-        self.by_address.insert(record.address.clone());
+        self.by_address.insert(Arc::clone(&record.address));
         self.by_score.push(record);
     }
 
+    /// Insert address record of a failed connection attempt into [`AddressStore`].
+    ///
+    /// If `min_score` is set and the score of the address has dropped to or below it, the
+    /// address is discarded instead.
+    pub fn insert_failed(&mut self, record: AddressRecord, min_score: Option<i32>) {
+        if min_score.is_some_and(|min_score| record.score <= min_score) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                address = ?record.address,
+                score = ?record.score,
+                "discarding failing address",
+            );
+            return;
+        }
+
+        self.insert(record);
+    }
+
+    /// Remove `address` from [`AddressStore`].
+    ///
+    /// Returns `true` if the address was removed.
+    pub fn remove(&mut self, address: &Multiaddr) -> bool {
+        let address = canonicalize(address.clone());
+
+        if !self.by_address.remove(&address) {
+            return false;
+        }
+
+        self.by_score.retain(|record| record.address() != &address);
+        true
+    }
+
+    /// Remove addresses which haven't been used for `expiry`.
+    pub fn remove_expired(&mut self, expiry: Duration) {
+        let by_address = &mut self.by_address;
+
+        self.by_score.retain(|record| {
+            let expired = record.last_used.elapsed() >= expiry;
+            if expired {
+                by_address.remove(&record.address);
+            }
+
+            !expired
+        });
+    }
+
     /// Pop address with the highest score from [`AddressStore`].
     pub fn pop(&mut self) -> Option<AddressRecord> {
         self.by_score.pop().map(|record| {
@@ -235,6 +328,127 @@ impl AddressStore {
     }
 }
 
+/// Maximum number of observed addresses tracked by [`ExternalAddresses`].
+const MAX_OBSERVED_ADDRESSES: usize = 64;
+
+/// Local addresses as observed by remote peers.
+///
+/// An observed address is considered external once enough distinct peers have reported it.
+///
+/// At most [`MAX_OBSERVED_ADDRESSES`] addresses are tracked. Once the limit is reached, the least
+/// recently reported address which hasn't been confirmed is evicted to make room for a new one.
+/// Confirmed addresses are never evicted and if all tracked addresses are confirmed, new
+/// observations are ignored.
+#[derive(Debug)]
+pub struct ExternalAddresses {
+    /// How many distinct peers must report an address before it's considered external.
+    confirmations: usize,
+
+    /// Observed addresses and the peers who reported them, least recently reported first.
+    ///
+    /// At most `confirmations` peers are tracked for each address.
+    observed: IndexMap<Multiaddr, HashSet<PeerId>>,
+
+    /// Manually added external addresses.
+    manual: HashSet<Multiaddr>,
+}
+
+impl Default for ExternalAddresses {
+    fn default() -> Self {
+        Self::new(EXTERNAL_ADDRESS_CONFIRMATIONS)
+    }
+}
+
+impl ExternalAddresses {
+    /// Create new [`ExternalAddresses`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `confirmations` is zero.
+    pub fn new(confirmations: usize) -> Self {
+        assert!(confirmations != 0);
+
+        Self {
+            confirmations,
+            observed: IndexMap::new(),
+            manual: HashSet::new(),
+        }
+    }
+
+    /// Set how many distinct peers must report an address before it's considered external.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `confirmations` is zero.
+    pub fn set_confirmations(&mut self, confirmations: usize) {
+        assert!(confirmations != 0);
+
+        self.confirmations = confirmations;
+    }
+
+    /// Record that `peer` observed the local node at `address`.
+    ///
+    /// Returns `true` if `address` became confirmed as a result of this report.
+    pub fn add(&mut self, peer: PeerId, address: Multiaddr) -> bool {
+        // reinsert the address so it becomes the most recently reported one
+        let mut peers = self.observed.shift_remove(&address).unwrap_or_default();
+
+        if self.observed.len() >= MAX_OBSERVED_ADDRESSES {
+            let Some(index) =
+                self.observed.values().position(|peers| peers.len() < self.confirmations)
+            else {
+                // all tracked addresses are confirmed, ignore the new observation
+                return false;
+            };
+            self.observed.shift_remove_index(index);
+        }
+
+        // peers reporting an already-confirmed address aren't tracked
+        let confirmed = peers.len() < self.confirmations
+            && peers.insert(peer)
+            && peers.len() == self.confirmations;
+        self.observed.insert(address, peers);
+
+        confirmed
+    }
+
+    /// Check if `address` has been confirmed as an external address.
+    #[cfg(test)]
+    pub fn is_confirmed(&self, address: &Multiaddr) -> bool {
+        self.observed
+            .get(address)
+            .is_some_and(|peers| peers.len() >= self.confirmations)
+    }
+
+    /// Get iterator over confirmed external addresses.
+    pub fn confirmed(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.observed
+            .iter()
+            .filter_map(|(address, peers)| (peers.len() >= self.confirmations).then_some(address))
+    }
+
+    /// Add `address` as an external address without requiring it to be confirmed.
+    ///
+    /// Returns `true` if the address wasn't added before.
+    pub fn add_manual(&mut self, address: Multiaddr) -> bool {
+        self.manual.insert(address)
+    }
+
+    /// Remove manually added external `address`.
+    ///
+    /// Returns `true` if the address had been added.
+    pub fn remove_manual(&mut self, address: &Multiaddr) -> bool {
+        self.manual.remove(address)
+    }
+
+    /// Get iterator over external addresses, both manually added and confirmed.
+    pub fn addresses(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.manual
+            .iter()
+            .chain(self.confirmed().filter(|address| !self.manual.contains(*address)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -243,6 +457,7 @@ mod tests {
     };
 
     use super::*;
+    use multihash::Multihash;
     use rand::{rngs::ThreadRng, Rng};
 
     fn tcp_address_record(rng: &mut ThreadRng) -> AddressRecord {
@@ -440,4 +655,137 @@ mod tests {
             assert_eq!(stored.address(), record.address());
         }
     }
+
+    #[test]
+    fn stored_address_is_shared() {
+        let mut rng = rand::thread_rng();
+        let mut store = AddressStore::new();
+        let record = tcp_address_record(&mut rng);
+        let cloned = record.clone();
+
+        assert!(Arc::ptr_eq(&record.address, &cloned.address));
+
+        store.insert(cloned);
+        assert!(store.contains(record.address()));
+        assert!(Arc::ptr_eq(
+            store.by_address.get(record.address()).unwrap(),
+            &record.address
+        ));
+
+        let popped = store.pop().unwrap();
+        assert!(Arc::ptr_eq(&popped.address, &record.address));
+        assert_eq!(popped.address(), record.address());
+        assert!(!store.contains(record.address()));
+    }
+
+    #[test]
+    fn equivalent_addresses_stored_once() {
+        let peer = PeerId::random();
+        let mut store = AddressStore::new();
+
+        store.insert(AddressRecord::new(
+            &peer,
+            "/dns4/Example.COM/tcp/8888".parse().unwrap(),
+            0i32,
+            None,
+        ));
+        store.insert(AddressRecord::new(
+            &peer,
+            "/dns4/example.com/tcp/8888"
+                .parse::<Multiaddr>()
+                .unwrap()
+                .with(Protocol::P2p(Multihash::from(peer))),
+            0i32,
+            None,
+        ));
+
+        assert_eq!(store.by_address.len(), 1usize);
+        assert_eq!(store.by_score.len(), 1usize);
+        assert!(store.contains(
+            &"/dns4/EXAMPLE.com/tcp/8888"
+                .parse::<Multiaddr>()
+                .unwrap()
+                .with(Protocol::P2p(Multihash::from(peer)))
+        ));
+    }
+
+    #[test]
+    fn external_address_confirmed_at_threshold() {
+        let mut addresses = ExternalAddresses::new(3usize);
+        let address: Multiaddr = "/ip4/1.2.3.4/tcp/8888".parse().unwrap();
+        let peer = PeerId::random();
+
+        assert!(!addresses.add(peer, address.clone()));
+        assert!(!addresses.is_confirmed(&address));
+
+        // the same peer reporting the address again doesn't count as a confirmation
+        assert!(!addresses.add(peer, address.clone()));
+        assert!(!addresses.add(PeerId::random(), address.clone()));
+        assert!(!addresses.is_confirmed(&address));
+
+        assert!(addresses.add(PeerId::random(), address.clone()));
+        assert!(addresses.is_confirmed(&address));
+        assert_eq!(addresses.confirmed().collect::<Vec<_>>(), vec![&address]);
+
+        // further reports don't confirm the address again
+        assert!(!addresses.add(PeerId::random(), address.clone()));
+        assert_eq!(addresses.observed.get(&address).unwrap().len(), 3usize);
+    }
+
+    #[test]
+    fn observed_addresses_bounded() {
+        let mut addresses = ExternalAddresses::new(2usize);
+        let confirmed: Multiaddr = "/ip4/1.2.3.4/tcp/8888".parse().unwrap();
+
+        assert!(!addresses.add(PeerId::random(), confirmed.clone()));
+        assert!(addresses.add(PeerId::random(), confirmed.clone()));
+
+        let candidates = (0..MAX_OBSERVED_ADDRESSES)
+            .map(|port| {
+                let address: Multiaddr = format!("/ip4/5.6.7.8/tcp/{port}").parse().unwrap();
+                assert!(!addresses.add(PeerId::random(), address.clone()));
+                address
+            })
+            .collect::<Vec<_>>();
+
+        // the oldest candidate is evicted, the confirmed address is kept
+        assert_eq!(addresses.observed.len(), MAX_OBSERVED_ADDRESSES);
+        assert!(addresses.is_confirmed(&confirmed));
+        assert!(!addresses.observed.contains_key(&candidates[0]));
+        assert!(addresses.observed.contains_key(&candidates[1]));
+
+        // reporting an address again protects it from eviction
+        assert!(addresses.add(PeerId::random(), candidates[1].clone()));
+        let address: Multiaddr = "/ip4/9.9.9.9/tcp/8888".parse().unwrap();
+        assert!(!addresses.add(PeerId::random(), address.clone()));
+
+        assert!(addresses.observed.contains_key(&candidates[1]));
+        assert!(!addresses.observed.contains_key(&candidates[2]));
+        assert!(addresses.observed.contains_key(&address));
+    }
+
+    #[test]
+    fn confirmed_addresses_not_evicted() {
+        let mut addresses = ExternalAddresses::new(1usize);
+
+        let confirmed = (0..MAX_OBSERVED_ADDRESSES)
+            .map(|port| {
+                let address: Multiaddr = format!("/ip4/1.2.3.4/tcp/{port}").parse().unwrap();
+                assert!(addresses.add(PeerId::random(), address.clone()));
+                address
+            })
+            .collect::<Vec<_>>();
+
+        // the table is full of confirmed addresses so the new observation is ignored
+        let address: Multiaddr = "/ip4/5.6.7.8/tcp/8888".parse().unwrap();
+        assert!(!addresses.add(PeerId::random(), address.clone()));
+
+        assert_eq!(addresses.observed.len(), MAX_OBSERVED_ADDRESSES);
+        assert!(!addresses.observed.contains_key(&address));
+        assert!(confirmed.iter().all(|address| addresses.is_confirmed(address)));
+
+        // confirmed addresses can still be reported again
+        assert!(!addresses.add(PeerId::random(), confirmed[0].clone()));
+        assert!(addresses.is_confirmed(&confirmed[0]));
+    }
 }
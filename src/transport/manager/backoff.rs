@@ -0,0 +1,286 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Backoff for redialing peers after failed dials.
+
+use crate::PeerId;
+
+use rand::Rng;
+use tokio::time::Instant;
+
+use std::{collections::HashMap, time::Duration};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::transport-manager::backoff";
+
+/// Dial backoff configuration.
+///
+/// After a failed dial, the peer can't be dialed again until the backoff has elapsed. The backoff
+/// starts at `base_delay` and is multiplied by `multiplier` after each consecutive failure, up to
+/// `max_delay`. A successful connection resets the backoff of the peer, as does not failing a dial
+/// for `max_delay` after the backoff has elapsed.
+#[derive(Debug, Clone)]
+pub struct DialBackoffConfig {
+    /// Backoff after the first failed dial.
+    ///
+    /// Defaults to 1 second.
+    pub base_delay: Duration,
+
+    /// Factor by which the backoff grows after each consecutive failed dial.
+    ///
+    /// Must be finite and at least `1.0`. Defaults to `2.0`.
+    pub multiplier: f64,
+
+    /// Maximum fraction of the backoff added to it as random jitter.
+    ///
+    /// The jitter keeps nodes which lost the same peer from redialing it in lockstep.
+    ///
+    /// Must be finite and non-negative. Defaults to `0.1`.
+    pub jitter: f64,
+
+    /// Maximum backoff.
+    ///
+    /// Must not be less than `base_delay`. Defaults to 60 seconds.
+    pub max_delay: Duration,
+}
+
+impl DialBackoffConfig {
+    /// Assert that the configuration is valid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `multiplier` is not finite or is less than `1.0`, if `jitter` is not finite or
+    /// is negative or if `max_delay` is less than `base_delay`.
+    pub(crate) fn assert_valid(&self) {
+        assert!(self.multiplier.is_finite() && self.multiplier >= 1.0);
+        assert!(self.jitter.is_finite() && self.jitter >= 0.0);
+        assert!(self.max_delay >= self.base_delay);
+    }
+}
+
+impl Default for DialBackoffConfig {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_secs(1),
+            multiplier: 2.0,
+            jitter: 0.1,
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Backoff state of a peer.
+#[derive(Debug)]
+struct PeerBackoff {
+    /// Number of consecutive failed dials.
+    failures: u32,
+
+    /// When the peer can be dialed again.
+    retry_at: Instant,
+}
+
+/// Dial backoffs of peers.
+#[derive(Debug)]
+pub(super) struct DialBackoff {
+    /// Backoff configuration.
+    config: DialBackoffConfig,
+
+    /// Peers with failed dials.
+    peers: HashMap<PeerId, PeerBackoff>,
+}
+
+impl DialBackoff {
+    /// Create new [`DialBackoff`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config` is invalid, see [`DialBackoffConfig::assert_valid()`].
+    pub(super) fn new(config: DialBackoffConfig) -> Self {
+        config.assert_valid();
+
+        Self {
+            config,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Get the backoff after `failures` consecutive failed dials, without jitter.
+    fn delay(&self, failures: u32) -> Duration {
+        let exponent = i32::try_from(failures.saturating_sub(1)).unwrap_or(i32::MAX);
+        let delay = self.config.base_delay.as_secs_f64() * self.config.multiplier.powi(exponent);
+
+        Duration::try_from_secs_f64(delay).map_or(self.config.max_delay, |delay| {
+            std::cmp::min(delay, self.config.max_delay)
+        })
+    }
+
+    /// Forget peers which haven't failed a dial for `max_delay` after their backoff elapsed.
+    fn prune(&mut self) {
+        let now = Instant::now();
+        let max_delay = self.config.max_delay;
+
+        self.peers.retain(|_, backoff| match backoff.retry_at.checked_add(max_delay) {
+            Some(expires_at) => expires_at > now,
+            None => true,
+        });
+    }
+
+    /// Register failed dial to `peer` and return how long the peer is backed off for.
+    pub(super) fn on_dial_failure(&mut self, peer: PeerId) -> Duration {
+        self.prune();
+
+        let failures =
+            self.peers.get(&peer).map_or(1, |backoff| backoff.failures.saturating_add(1));
+        let delay = self.delay(failures);
+        let jitter = delay.mul_f64(rand::thread_rng().gen_range(0.0..=self.config.jitter));
+        let delay = std::cmp::min(delay + jitter, self.config.max_delay);
+
+        tracing::trace!(target: LOG_TARGET, ?peer, ?failures, ?delay, "back off peer");
+
+        self.peers.insert(
+            peer,
+            PeerBackoff {
+                failures,
+                retry_at: Instant::now() + delay,
+            },
+        );
+
+        delay
+    }
+
+    /// Reset the backoff of `peer` after a connection has been established.
+    pub(super) fn on_connection_established(&mut self, peer: &PeerId) {
+        self.peers.remove(peer);
+    }
+
+    /// Get how long `peer` is still backed off for, if at all.
+    pub(super) fn retry_after(&self, peer: &PeerId) -> Option<Duration> {
+        let backoff = self.peers.get(peer)?;
+
+        backoff
+            .retry_at
+            .checked_duration_since(Instant::now())
+            .filter(|delay| !delay.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> DialBackoffConfig {
+        DialBackoffConfig {
+            base_delay: Duration::from_secs(1),
+            multiplier: 2.0,
+            jitter: 0.0,
+            max_delay: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_cap() {
+        let mut backoff = DialBackoff::new(config());
+        let peer = PeerId::random();
+
+        assert!(backoff.retry_after(&peer).is_none());
+        assert_eq!(backoff.on_dial_failure(peer), Duration::from_secs(1));
+        assert_eq!(backoff.on_dial_failure(peer), Duration::from_secs(2));
+        assert_eq!(backoff.on_dial_failure(peer), Duration::from_secs(4));
+        assert_eq!(backoff.on_dial_failure(peer), Duration::from_secs(5));
+        assert_eq!(backoff.on_dial_failure(peer), Duration::from_secs(5));
+
+        let retry_after = backoff.retry_after(&peer).unwrap();
+        assert!(retry_after > Duration::from_secs(4) && retry_after <= Duration::from_secs(5));
+    }
+
+    #[test]
+    fn jitter_is_bounded() {
+        let mut backoff = DialBackoff::new(DialBackoffConfig {
+            jitter: 0.5,
+            max_delay: Duration::from_secs(60),
+            ..config()
+        });
+
+        for _ in 0..100 {
+            let peer = PeerId::random();
+            let delay = backoff.on_dial_failure(peer);
+
+            assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_millis(1500));
+        }
+    }
+
+    #[test]
+    fn connection_resets_backoff() {
+        let mut backoff = DialBackoff::new(config());
+        let peer = PeerId::random();
+
+        backoff.on_dial_failure(peer);
+        backoff.on_dial_failure(peer);
+        backoff.on_connection_established(&peer);
+
+        assert!(backoff.retry_after(&peer).is_none());
+        assert_eq!(backoff.on_dial_failure(peer), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stale_peers_pruned() {
+        let mut backoff = DialBackoff::new(config());
+        let peer = PeerId::random();
+
+        assert_eq!(backoff.on_dial_failure(peer), Duration::from_secs(1));
+
+        // failure within `max_delay` of the backoff elapsing grows the backoff
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert_eq!(backoff.on_dial_failure(peer), Duration::from_secs(2));
+
+        // the peer and other stale peers are forgotten once `max_delay` has passed
+        tokio::time::advance(Duration::from_secs(8)).await;
+        backoff.on_dial_failure(PeerId::random());
+        assert!(!backoff.peers.contains_key(&peer));
+        assert_eq!(backoff.on_dial_failure(peer), Duration::from_secs(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn nan_jitter_rejected() {
+        DialBackoff::new(DialBackoffConfig {
+            jitter: f64::NAN,
+            ..config()
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn shrinking_multiplier_rejected() {
+        DialBackoff::new(DialBackoffConfig {
+            multiplier: 0.5,
+            ..config()
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn max_delay_below_base_delay_rejected() {
+        DialBackoff::new(DialBackoffConfig {
+            max_delay: Duration::from_millis(500),
+            ..config()
+        });
+    }
+}
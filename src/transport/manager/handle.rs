@@ -23,10 +23,13 @@ use crate::{
     error::{AddressError, Error},
     executor::Executor,
     protocol::ProtocolSet,
-    transport::manager::{
-        address::{AddressRecord, AddressStore},
-        types::{PeerContext, PeerState, SupportedTransport},
-        ProtocolContext, TransportManagerEvent, LOG_TARGET,
+    transport::{
+        dns::DnsResolver,
+        manager::{
+            address::{AddressRecord, AddressStore, ExternalAddresses},
+            types::{PeerContext, PeerState, SupportedTransport},
+            ProtocolContext, TransportManagerEvent, LOG_TARGET,
+        },
     },
     types::{protocol::ProtocolName, ConnectionId},
     BandwidthSink, PeerId,
@@ -35,6 +38,7 @@ use crate::{
 use multiaddr::{Multiaddr, Protocol};
 use parking_lot::RwLock;
 use tokio::sync::mpsc::{error::TrySendError, Sender};
+use tokio_util::sync::CancellationToken;
 
 use std::{
     collections::{HashMap, HashSet},
@@ -42,6 +46,7 @@ use std::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::Instant,
 };
 
 /// Inner commands sent from [`TransportManagerHandle`] to
@@ -58,6 +63,12 @@ pub enum InnerTransportManagerCommand {
         /// Remote address.
         address: Multiaddr,
     },
+
+    /// Dial address over a new connection even if the peer is already connected.
+    SecondaryAddress {
+        /// Remote address.
+        address: Multiaddr,
+    },
 }
 
 /// Handle for communicating with [`crate::transport::manager::TransportManager`].
@@ -77,6 +88,9 @@ pub struct TransportManagerHandle {
 
     /// Local listen addresess.
     listen_addresses: Arc<RwLock<HashSet<Multiaddr>>>,
+
+    /// Local addresses observed by remote peers.
+    external_addresses: Arc<RwLock<ExternalAddresses>>,
 }
 
 impl TransportManagerHandle {
@@ -87,16 +101,23 @@ impl TransportManagerHandle {
         cmd_tx: Sender<InnerTransportManagerCommand>,
         supported_transport: HashSet<SupportedTransport>,
         listen_addresses: Arc<RwLock<HashSet<Multiaddr>>>,
+        external_addresses: Arc<RwLock<ExternalAddresses>>,
     ) -> Self {
         Self {
             peers,
             cmd_tx,
             local_peer_id,
             listen_addresses,
+            external_addresses,
             supported_transport,
         }
     }
 
+    /// Get local peer ID.
+    pub(crate) fn local_peer_id(&self) -> &PeerId {
+        &self.local_peer_id
+    }
+
     /// Register new transport to [`TransportManagerHandle`].
     pub(crate) fn register_transport(&mut self, transport: SupportedTransport) {
         self.supported_transport.insert(transport);
@@ -104,6 +125,10 @@ impl TransportManagerHandle {
 
     /// Check if `address` is supported by one of the enabled transports.
     pub fn supported_transport(&self, address: &Multiaddr) -> bool {
+        if address.iter().any(|protocol| std::matches!(protocol, Protocol::P2pCircuit)) {
+            return self.supported_transport.contains(&SupportedTransport::Relay);
+        }
+
         let mut iter = address.iter();
 
         match iter.next() {
@@ -116,6 +141,8 @@ impl TransportManagerHandle {
                     return false;
                 },
             Some(Protocol::Dns(_)) | Some(Protocol::Dns4(_)) | Some(Protocol::Dns6(_)) => {}
+            Some(Protocol::Memory(_)) =>
+                return self.supported_transport.contains(&SupportedTransport::Memory),
             _ => return false,
         }
 
@@ -203,6 +230,7 @@ impl TransportManagerHandle {
                         state: PeerState::Disconnected { dial_record: None },
                         addresses: AddressStore::from_iter(addresses),
                         secondary_connection: None,
+                        pending_ban: None,
                     },
                 );
             }
@@ -211,6 +239,250 @@ impl TransportManagerHandle {
         num_added
     }
 
+    /// Remove known `address` of `peer`.
+    ///
+    /// Returns `true` if the address was removed.
+    pub fn remove_known_address(&mut self, peer: &PeerId, address: &Multiaddr) -> bool {
+        let record = AddressRecord::new(peer, address.clone(), 0i32, None);
+
+        self.peers
+            .write()
+            .get_mut(peer)
+            .is_some_and(|context| context.addresses.remove(record.address()))
+    }
+
+    /// Add local address observed by `peer`.
+    ///
+    /// The address is considered external once enough distinct peers have reported it.
+    ///
+    /// Returns `true` if the address became confirmed as a result of this report.
+    pub fn add_external_address(&mut self, peer: PeerId, address: Multiaddr) -> bool {
+        if address.is_empty() {
+            return false;
+        }
+
+        let address = Self::strip_peer_id(address);
+        let confirmed = self.external_addresses.write().add(peer, address.clone());
+
+        if confirmed {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?address,
+                "external address confirmed",
+            );
+        }
+
+        confirmed
+    }
+
+    /// Add external address of the local node which is advertised without being confirmed
+    /// by remote peers.
+    ///
+    /// Listen addresses are advertised already and are not added.
+    ///
+    /// Returns `true` if the address was added.
+    pub fn add_manual_external_address(&mut self, address: Multiaddr) -> bool {
+        if address.is_empty() {
+            return false;
+        }
+
+        let address = Self::strip_peer_id(address);
+
+        if self.listen_addresses.read().contains(&address) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?address,
+                "external address is a listen address, ignoring",
+            );
+            return false;
+        }
+
+        tracing::debug!(target: LOG_TARGET, ?address, "add external address");
+
+        self.external_addresses.write().add_manual(address)
+    }
+
+    /// Remove manually added external address of the local node.
+    ///
+    /// Returns `true` if the address was removed.
+    pub fn remove_manual_external_address(&mut self, address: &Multiaddr) -> bool {
+        let address = Self::strip_peer_id(address.clone());
+
+        self.external_addresses.write().remove_manual(&address)
+    }
+
+    /// Get local listen addresses, both with and without the local `PeerId`.
+    pub fn listen_addresses(&self) -> Vec<Multiaddr> {
+        self.listen_addresses.read().iter().cloned().collect()
+    }
+
+    /// Get external addresses of the local node, both manually added and confirmed.
+    ///
+    /// Addresses which are also listen addresses are not returned.
+    pub fn external_addresses(&self) -> Vec<Multiaddr> {
+        let listen_addresses = self.listen_addresses.read();
+
+        self.external_addresses
+            .read()
+            .addresses()
+            .filter(|address| !listen_addresses.contains(*address))
+            .cloned()
+            .collect()
+    }
+
+    /// Get addresses the local node advertises to remote peers.
+    ///
+    /// The addresses are the listen addresses followed by the external addresses, without
+    /// the local `PeerId`.
+    pub fn advertised_addresses(&self) -> Vec<Multiaddr> {
+        let mut addresses: Vec<Multiaddr> = self
+            .listen_addresses
+            .read()
+            .iter()
+            .filter(|address| !std::matches!(address.iter().last(), Some(Protocol::P2p(_))))
+            .cloned()
+            .collect();
+        addresses.extend(self.external_addresses());
+
+        addresses
+    }
+
+    /// Get peers which the local node is currently connected to.
+    pub fn connected_peers(&self) -> Vec<PeerId> {
+        self.peers
+            .read()
+            .iter()
+            .filter(|(_, context)| std::matches!(context.state, PeerState::Connected { .. }))
+            .map(|(peer, _)| *peer)
+            .collect()
+    }
+
+    /// Strip the `/p2p` suffix from local `address`.
+    ///
+    /// The address is the local address so any `/p2p` suffix would point to the local node.
+    fn strip_peer_id(address: Multiaddr) -> Multiaddr {
+        address
+            .iter()
+            .take_while(|protocol| !std::matches!(protocol, Protocol::P2p(_)))
+            .collect()
+    }
+
+    /// Ban `peer`.
+    ///
+    /// Banned peers cannot be dialed and inbound connections from them are rejected. If `until`
+    /// is set, the ban is lifted after that instant. Existing connections are not closed and if
+    /// the peer is connected or being dialed, it's moved to [`PeerState::Banned`] once it has
+    /// disconnected.
+    ///
+    /// Unknown peers are tracked only for the duration of the ban so peers whose ban has expired
+    /// are pruned when a new unknown peer is banned.
+    pub fn ban_peer(&mut self, peer: PeerId, until: Option<Instant>) {
+        tracing::debug!(target: LOG_TARGET, ?peer, ?until, "ban peer");
+
+        let mut peers = self.peers.write();
+
+        match peers.get_mut(&peer) {
+            None => {
+                let now = Instant::now();
+                peers.retain(|_, context| {
+                    !(Self::is_ban_only(context)
+                        && std::matches!(
+                            context.state,
+                            PeerState::Banned { until: Some(until) } if until <= now
+                        ))
+                });
+
+                peers.insert(
+                    peer,
+                    PeerContext {
+                        state: PeerState::Banned { until },
+                        addresses: AddressStore::new(),
+                        secondary_connection: None,
+                        pending_ban: None,
+                    },
+                );
+            }
+            Some(context) => match context.state {
+                PeerState::Disconnected { dial_record: None } | PeerState::Banned { .. } => {
+                    context.state = PeerState::Banned { until };
+                    context.pending_ban = None;
+                }
+                _ => {
+                    context.pending_ban = Some(until);
+                }
+            },
+        }
+    }
+
+    /// Lift the ban of `peer`, if it exists.
+    pub fn unban_peer(&mut self, peer: PeerId) {
+        tracing::debug!(target: LOG_TARGET, ?peer, "unban peer");
+
+        if let Some(context) = self.peers.write().get_mut(&peer) {
+            context.pending_ban = None;
+
+            if let PeerState::Banned { .. } = context.state {
+                context.state = PeerState::Disconnected { dial_record: None };
+            }
+        }
+    }
+
+    /// Check if peer is known only because it has been banned.
+    fn is_ban_only(context: &PeerContext) -> bool {
+        std::matches!(context.state, PeerState::Banned { .. })
+            && context.addresses.is_empty()
+            && context.secondary_connection.is_none()
+    }
+
+    /// Check if `peer` is banned.
+    ///
+    /// Pending bans of peers that have since disconnected are moved to the peer's state and
+    /// peers whose temporary ban has expired are moved back to [`PeerState::Disconnected`], or
+    /// removed if they were only known because of the ban.
+    ///
+    /// The check is done under the write lock so the state of the peer can't change between
+    /// checking and updating it.
+    pub(crate) fn is_banned(&self, peer: &PeerId) -> bool {
+        let mut peers = self.peers.write();
+        let Some(context) = peers.get_mut(peer) else {
+            return false;
+        };
+
+        if let PeerState::Disconnected { dial_record: None } = context.state {
+            if let Some(until) = context.pending_ban.take() {
+                context.state = PeerState::Banned { until };
+            }
+        }
+
+        let until = match context.state {
+            PeerState::Banned { until } => until,
+            _ => match context.pending_ban {
+                None => return false,
+                Some(until) => until,
+            },
+        };
+
+        match until {
+            None => true,
+            Some(until) if until > Instant::now() => true,
+            Some(_) => {
+                tracing::trace!(target: LOG_TARGET, ?peer, "ban expired");
+
+                if Self::is_ban_only(context) {
+                    peers.remove(peer);
+                    return false;
+                }
+
+                if let PeerState::Banned { .. } = context.state {
+                    context.state = PeerState::Disconnected { dial_record: None };
+                }
+                context.pending_ban = None;
+
+                false
+            }
+        }
+    }
+
     /// Dial peer using `PeerId`.
     ///
     /// Returns an error if the peer is unknown or the peer is already connected.
@@ -219,6 +491,10 @@ impl TransportManagerHandle {
             return Err(Error::TriedToDialSelf);
         }
 
+        if self.is_banned(peer) {
+            return Err(Error::PeerBanned(*peer));
+        }
+
         {
             match self.peers.read().get(peer) {
                 Some(PeerContext {
@@ -249,6 +525,10 @@ impl TransportManagerHandle {
                     state: PeerState::Dialing { .. } | PeerState::Opening { .. },
                     ..
                 }) => return Ok(()),
+                Some(PeerContext {
+                    state: PeerState::Banned { .. },
+                    ..
+                }) => return Err(Error::PeerBanned(*peer)),
                 None => return Err(Error::PeerDoesntExist(*peer)),
             }
         }
@@ -269,6 +549,13 @@ impl TransportManagerHandle {
             return Err(Error::AddressError(AddressError::PeerIdMissing));
         }
 
+        let peer = PeerId::try_from_multiaddr(&address)
+            .ok_or(Error::AddressError(AddressError::InvalidPeerId))?;
+
+        if self.is_banned(&peer) {
+            return Err(Error::PeerBanned(peer));
+        }
+
         self.cmd_tx
             .try_send(InnerTransportManagerCommand::DialAddress { address })
             .map_err(|error| match error {
@@ -276,6 +563,29 @@ impl TransportManagerHandle {
                 TrySendError::Closed(_) => Error::EssentialTaskClosed,
             })
     }
+
+    /// Dial peer using `Multiaddr` over a new connection, even if the peer is already connected.
+    ///
+    /// Returns an error if address it not valid.
+    pub fn dial_secondary(&self, address: Multiaddr) -> crate::Result<()> {
+        if !address.iter().any(|protocol| std::matches!(protocol, Protocol::P2p(_))) {
+            return Err(Error::AddressError(AddressError::PeerIdMissing));
+        }
+
+        let peer = PeerId::try_from_multiaddr(&address)
+            .ok_or(Error::AddressError(AddressError::InvalidPeerId))?;
+
+        if self.is_banned(&peer) {
+            return Err(Error::PeerBanned(peer));
+        }
+
+        self.cmd_tx
+            .try_send(InnerTransportManagerCommand::SecondaryAddress { address })
+            .map_err(|error| match error {
+                TrySendError::Full(_) => Error::ChannelClogged,
+                TrySendError::Closed(_) => Error::EssentialTaskClosed,
+            })
+    }
 }
 
 // TODO: add getters for these
@@ -288,6 +598,8 @@ pub struct TransportHandle {
     pub protocol_names: Vec<ProtocolName>,
     pub bandwidth_sink: BandwidthSink,
     pub executor: Arc<dyn Executor>,
+    pub resolver: Arc<dyn DnsResolver>,
+    pub shutdown: CancellationToken,
 }
 
 impl TransportHandle {
@@ -297,6 +609,7 @@ impl TransportHandle {
             self.tx.clone(),
             self.next_substream_id.clone(),
             self.protocols.clone(),
+            self.shutdown.clone(),
         )
     }
 
@@ -327,6 +640,7 @@ mod tests {
                 peers: Default::default(),
                 supported_transport: HashSet::new(),
                 listen_addresses: Default::default(),
+                external_addresses: Default::default(),
             },
             cmd_rx,
         )
@@ -345,6 +659,24 @@ mod tests {
         assert!(handle.supported_transport(&address));
     }
 
+    #[test]
+    fn relay_supported() {
+        let (mut handle, _rx) = make_transport_manager_handle();
+        handle.supported_transport.insert(SupportedTransport::Tcp);
+
+        let address: Multiaddr = format!(
+            "/ip4/127.0.0.1/tcp/8888/p2p/{}/p2p-circuit/p2p/{}",
+            PeerId::random(),
+            PeerId::random(),
+        )
+        .parse()
+        .unwrap();
+        assert!(!handle.supported_transport(&address));
+
+        handle.supported_transport.insert(SupportedTransport::Relay);
+        assert!(handle.supported_transport(&address));
+    }
+
     #[test]
     fn transport_not_supported() {
         let (handle, _rx) = make_transport_manager_handle();
@@ -424,6 +756,7 @@ mod tests {
                         dial_record: None,
                     },
                     secondary_connection: None,
+                    pending_ban: None,
                     addresses: AddressStore::from_iter(
                         vec![Multiaddr::empty()
                             .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
@@ -466,6 +799,7 @@ mod tests {
                         .unwrap(),
                     },
                     secondary_connection: None,
+                    pending_ban: None,
                     addresses: AddressStore::from_iter(
                         vec![Multiaddr::empty()
                             .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
@@ -500,6 +834,7 @@ mod tests {
                 PeerContext {
                     state: PeerState::Disconnected { dial_record: None },
                     secondary_connection: None,
+                    pending_ban: None,
                     addresses: AddressStore::new(),
                 },
             );
@@ -540,6 +875,7 @@ mod tests {
                         ),
                     },
                     secondary_connection: None,
+                    pending_ban: None,
                     addresses: AddressStore::from_iter(
                         vec![Multiaddr::empty()
                             .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
@@ -592,6 +928,7 @@ mod tests {
                     .parse()
                     .expect("valid multiaddress"),
             ]))),
+            external_addresses: Default::default(),
         };
 
         // local addresses
@@ -630,4 +967,54 @@ mod tests {
         assert!(!handle
             .is_local_address(&"/ip4/127.0.0.1/tcp/7777".parse().expect("valid multiaddress")));
     }
+
+    #[test]
+    fn external_address_confirmed_by_distinct_peers() {
+        let (mut handle, _rx) = make_transport_manager_handle();
+        handle.external_addresses.write().set_confirmations(3usize);
+        let address: Multiaddr = "/ip4/1.2.3.4/tcp/8888".parse().unwrap();
+
+        assert!(!handle.add_external_address(PeerId::random(), address.clone()));
+        assert!(!handle.add_external_address(PeerId::random(), address.clone()));
+        assert!(handle.external_addresses().is_empty());
+
+        assert!(handle.add_external_address(PeerId::random(), address.clone()));
+        assert_eq!(handle.external_addresses(), vec![address]);
+    }
+
+    #[test]
+    fn manual_external_address_advertised() {
+        let (mut handle, _rx) = make_transport_manager_handle();
+        let listen_address: Multiaddr = "/ip4/127.0.0.1/tcp/8888".parse().unwrap();
+        let address: Multiaddr = "/ip4/1.2.3.4/tcp/8888".parse().unwrap();
+        handle.listen_addresses.write().insert(listen_address.clone());
+
+        // listen addresses are advertised already
+        assert!(!handle.add_manual_external_address(listen_address.clone()));
+        assert!(handle.external_addresses().is_empty());
+
+        // manual addresses don't require confirmations and `/p2p` suffix is ignored
+        assert!(handle.add_manual_external_address(
+            address.clone().with(Protocol::P2p(Multihash::from(PeerId::random())))
+        ));
+        assert!(!handle.add_manual_external_address(address.clone()));
+        assert_eq!(handle.external_addresses(), vec![address.clone()]);
+        assert_eq!(
+            handle.advertised_addresses(),
+            vec![listen_address.clone(), address.clone()]
+        );
+
+        assert!(handle.remove_manual_external_address(&address));
+        assert!(!handle.remove_manual_external_address(&address));
+        assert_eq!(handle.advertised_addresses(), vec![listen_address]);
+    }
+
+    #[test]
+    fn empty_external_address_ignored() {
+        let (mut handle, _rx) = make_transport_manager_handle();
+        handle.external_addresses.write().set_confirmations(1usize);
+
+        assert!(!handle.add_external_address(PeerId::random(), Multiaddr::empty()));
+        assert!(handle.external_addresses().is_empty());
+    }
 }
@@ -0,0 +1,107 @@
+// Copyright 2024 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Prometheus metrics of [`crate::transport::manager::TransportManager`].
+
+use prometheus::{IntCounter, Registry};
+
+/// Prometheus counters updated by [`crate::transport::manager::TransportManager`].
+#[derive(Debug, Clone)]
+pub(crate) struct TransportManagerMetrics {
+    /// Number of dials started.
+    pub(crate) dials_attempted: IntCounter,
+
+    /// Number of dials which resulted in an established connection.
+    pub(crate) dials_succeeded: IntCounter,
+
+    /// Number of dials which failed.
+    pub(crate) dials_failed: IntCounter,
+
+    /// Number of dials which failed because the security handshake failed.
+    pub(crate) handshake_failures: IntCounter,
+
+    /// Number of established connections.
+    pub(crate) connections_established: IntCounter,
+
+    /// Number of closed connections.
+    pub(crate) connections_closed: IntCounter,
+
+    /// Number of completed `/dnsaddr` resolutions.
+    pub(crate) dns_resolutions: IntCounter,
+}
+
+impl TransportManagerMetrics {
+    /// Create new [`TransportManagerMetrics`] and register the counters to `registry`.
+    pub(crate) fn register(registry: &Registry) -> crate::Result<Self> {
+        let counter = |name: &str, help: &str| -> crate::Result<IntCounter> {
+            let counter = IntCounter::new(name, help)?;
+            registry.register(Box::new(counter.clone()))?;
+
+            Ok(counter)
+        };
+
+        Ok(Self {
+            dials_attempted: counter("litep2p_dials_attempted_total", "Number of dials started")?,
+            dials_succeeded: counter(
+                "litep2p_dials_succeeded_total",
+                "Number of dials which resulted in an established connection",
+            )?,
+            dials_failed: counter("litep2p_dials_failed_total", "Number of failed dials")?,
+            handshake_failures: counter(
+                "litep2p_handshake_failures_total",
+                "Number of dials which failed because the security handshake failed",
+            )?,
+            connections_established: counter(
+                "litep2p_connections_established_total",
+                "Number of established connections",
+            )?,
+            connections_closed: counter(
+                "litep2p_connections_closed_total",
+                "Number of closed connections",
+            )?,
+            dns_resolutions: counter(
+                "litep2p_dns_resolutions_total",
+                "Number of completed `/dnsaddr` resolutions",
+            )?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_registered() {
+        let registry = Registry::new();
+        let metrics = TransportManagerMetrics::register(&registry).unwrap();
+        metrics.dials_attempted.inc();
+
+        let families = registry.gather();
+        assert_eq!(families.len(), 7usize);
+        assert!(families.iter().any(|family| {
+            family.get_name() == "litep2p_dials_attempted_total"
+                && family.get_metric()[0].get_counter().get_value() == 1f64
+        }));
+
+        // registering the same counters twice is an error
+        assert!(TransportManagerMetrics::register(&registry).is_err());
+    }
+}
@@ -25,43 +25,59 @@ use crate::{
     executor::Executor,
     protocol::{InnerTransportEvent, TransportService},
     transport::{
+        common::multiaddr::{parse_multiaddr, ParsedAddress},
+        dns::DnsResolver,
         manager::{
-            address::{AddressRecord, AddressStore},
+            address::{AddressRecord, AddressStore, ExternalAddresses},
+            backoff::DialBackoff,
             handle::InnerTransportManagerCommand,
             types::{PeerContext, PeerState},
         },
         Endpoint, Transport, TransportEvent,
     },
     types::{protocol::ProtocolName, ConnectionId},
-    BandwidthSink, PeerId,
+    BandwidthSink, PeerId, DEFAULT_CHANNEL_SIZE,
 };
 
-use futures::{Stream, StreamExt};
+use futures::{
+    future::{AbortHandle, Abortable, BoxFuture},
+    stream::FuturesUnordered,
+    Stream, StreamExt,
+};
 use indexmap::IndexMap;
 use multiaddr::{Multiaddr, Protocol};
 use multihash::Multihash;
 use parking_lot::RwLock;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio_util::sync::CancellationToken;
+use trust_dns_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    TokioAsyncResolver,
+};
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     pin::Pin,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
+pub use backoff::DialBackoffConfig;
 pub use handle::{TransportHandle, TransportManagerHandle};
-pub use types::SupportedTransport;
+pub use types::{Metrics, PeerStateCounts, SupportedTransport};
 
 mod address;
+mod backoff;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod types;
 
 pub(crate) mod handle;
 
-// TODO: store `Multiaddr` in `Arc`
 // TODO: limit number of peers and addresses
 // TODO: rename constants
 // TODO: add lots of documentation
@@ -72,14 +88,26 @@ const LOG_TARGET: &str = "litep2p::transport-manager";
 /// Score for a working address.
 const SCORE_CONNECT_SUCCESS: i32 = 100i32;
 
+/// Prefix of `/dnsaddr` TXT records.
+const DNSADDR_PREFIX: &str = "dnsaddr=";
+
 /// Score for a non-working address.
 const SCORE_CONNECT_FAILURE: i32 = -100i32;
 
+/// How long [`TransportManager::shutdown()`] waits for open connections to close.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// TODO:
 enum ConnectionEstablishedResult {
     /// Accept connection and inform `Litep2p` about the connection.
     Accept,
 
+    /// Accept connection and drain the redundant connection of a simultaneous connect.
+    ///
+    /// The redundant connection is either the accepted connection itself or the connection the
+    /// accepted connection replaces.
+    Drain(ConnectionId),
+
     /// Reject connection.
     Reject,
 }
@@ -163,6 +191,12 @@ impl TransportContext {
     ) {
         assert!(self.transports.insert(name, transport).is_none());
     }
+
+    /// Remove all registered transports.
+    pub fn clear(&mut self) {
+        self.index = 0usize;
+        self.transports.clear();
+    }
 }
 
 impl Stream for TransportContext {
@@ -193,6 +227,21 @@ impl Stream for TransportContext {
     }
 }
 
+/// Result of a `/dnsaddr` resolution.
+struct DnsaddrResolution {
+    /// Connection ID allocated for the dial.
+    connection_id: ConnectionId,
+
+    /// Peer ID.
+    peer: PeerId,
+
+    /// Dialed `/dnsaddr` address.
+    address: Multiaddr,
+
+    /// Resolved TXT records.
+    result: crate::Result<Vec<String>>,
+}
+
 /// Litep2p connection manager.
 pub struct TransportManager {
     /// Local peer ID.
@@ -207,6 +256,36 @@ pub struct TransportManager {
     /// Maximum parallel dial attempts per peer.
     max_parallel_dials: usize,
 
+    /// Maximum number of established connections, if limited.
+    max_connections: Option<usize>,
+
+    /// Established connections and their endpoints.
+    ///
+    /// Only connections accepted by the transport manager are tracked so connections which are
+    /// closed without having been accepted don't affect the connection limit.
+    connections: HashMap<ConnectionId, Endpoint>,
+
+    /// Redundant connections of simultaneous connects which are being drained.
+    redundant_connections: HashSet<ConnectionId>,
+
+    /// How long an address can go unused before it's removed, if addresses expire.
+    address_expiry: Option<Duration>,
+
+    /// Score at or below which a failing address is removed, if failing addresses are removed.
+    min_address_score: Option<i32>,
+
+    /// How long a dial can take before it's aborted, if dials are time-limited.
+    dial_timeout: Option<Duration>,
+
+    /// Backoffs of peers with failed dials, if failed peers are backed off.
+    dial_backoff: Option<DialBackoff>,
+
+    /// Event channel sizes of protocols which don't use [`DEFAULT_CHANNEL_SIZE`].
+    protocol_channel_sizes: HashMap<ProtocolName, usize>,
+
+    /// DNS resolver shared by all transports.
+    resolver: Arc<dyn DnsResolver>,
+
     /// Installed protocols.
     protocols: HashMap<ProtocolName, ProtocolContext>,
 
@@ -216,6 +295,9 @@ pub struct TransportManager {
     /// Listen addresses.
     listen_addresses: Arc<RwLock<HashSet<Multiaddr>>>,
 
+    /// Local addresses observed by remote peers.
+    external_addresses: Arc<RwLock<ExternalAddresses>>,
+
     /// Next connection ID.
     next_connection_id: Arc<AtomicUsize>,
 
@@ -242,6 +324,33 @@ pub struct TransportManager {
 
     /// Pending connections.
     pending_connections: HashMap<ConnectionId, PeerId>,
+
+    /// Secondary connections being dialed to connected peers.
+    pending_secondary_dials: HashMap<ConnectionId, AddressRecord>,
+
+    /// Pending `/dnsaddr` resolutions.
+    pending_dns_resolves: FuturesUnordered<BoxFuture<'static, DnsaddrResolution>>,
+
+    /// Deadlines of the pending connections, if dials are time-limited.
+    ///
+    /// Deadlines of dials which have resolved are aborted and yield `None`.
+    pending_dial_timeouts: FuturesUnordered<BoxFuture<'static, Option<ConnectionId>>>,
+
+    /// Abort handles of the pending dial deadlines.
+    dial_timeout_aborts: HashMap<ConnectionId, AbortHandle>,
+
+    /// Events which are returned before new events are polled.
+    pending_events: VecDeque<TransportEvent>,
+
+    /// Timed out dials which the transport may still report.
+    timed_out_dials: HashSet<ConnectionId>,
+
+    /// Shutdown signal shared with the connections of all installed transports.
+    shutdown: CancellationToken,
+
+    /// Prometheus metrics, if registered.
+    #[cfg(feature = "metrics")]
+    metrics: Option<metrics::TransportManagerMetrics>,
 }
 
 impl TransportManager {
@@ -252,18 +361,41 @@ impl TransportManager {
         supported_transports: HashSet<SupportedTransport>,
         bandwidth_sink: BandwidthSink,
         max_parallel_dials: usize,
+    ) -> (Self, TransportManagerHandle) {
+        Self::with_resolver(
+            keypair,
+            supported_transports,
+            bandwidth_sink,
+            max_parallel_dials,
+            Arc::new(TokioAsyncResolver::tokio(
+                ResolverConfig::default(),
+                ResolverOpts::default(),
+            )),
+        )
+    }
+
+    /// Create new [`crate::transport::manager::TransportManager`] which uses `resolver`
+    /// to resolve DNS addresses.
+    pub fn with_resolver(
+        keypair: Keypair,
+        supported_transports: HashSet<SupportedTransport>,
+        bandwidth_sink: BandwidthSink,
+        max_parallel_dials: usize,
+        resolver: Arc<dyn DnsResolver>,
     ) -> (Self, TransportManagerHandle) {
         let local_peer_id = PeerId::from_public_key(&keypair.public().into());
         let peers = Arc::new(RwLock::new(HashMap::new()));
         let (cmd_tx, cmd_rx) = channel(256);
         let (event_tx, event_rx) = channel(256);
         let listen_addresses = Arc::new(RwLock::new(HashSet::new()));
+        let external_addresses = Arc::new(RwLock::new(ExternalAddresses::default()));
         let handle = TransportManagerHandle::new(
             local_peer_id,
             peers.clone(),
             cmd_tx,
             supported_transports,
             Arc::clone(&listen_addresses),
+            Arc::clone(&external_addresses),
         );
 
         (
@@ -276,14 +408,33 @@ impl TransportManager {
                 local_peer_id,
                 bandwidth_sink,
                 listen_addresses,
+                external_addresses,
                 max_parallel_dials,
+                max_connections: None,
+                address_expiry: None,
+                min_address_score: None,
+                dial_timeout: None,
+                dial_backoff: None,
+                protocol_channel_sizes: HashMap::new(),
+                connections: HashMap::new(),
+                redundant_connections: HashSet::new(),
+                resolver,
                 protocols: HashMap::new(),
                 transports: TransportContext::new(),
                 protocol_names: HashSet::new(),
                 transport_manager_handle: handle.clone(),
                 pending_connections: HashMap::new(),
+                pending_secondary_dials: HashMap::new(),
+                pending_dns_resolves: FuturesUnordered::new(),
+                pending_dial_timeouts: FuturesUnordered::new(),
+                dial_timeout_aborts: HashMap::new(),
+                pending_events: VecDeque::new(),
+                timed_out_dials: HashSet::new(),
+                shutdown: CancellationToken::new(),
                 next_substream_id: Arc::new(AtomicUsize::new(0usize)),
                 next_connection_id: Arc::new(AtomicUsize::new(0usize)),
+                #[cfg(feature = "metrics")]
+                metrics: None,
             },
             handle,
         )
@@ -310,12 +461,38 @@ impl TransportManager {
     ///
     /// This allocates new context for the protocol and returns a handle
     /// which the protocol can use the interact with the transport subsystem.
+    ///
+    /// The event channel of the protocol is sized according to
+    /// [`TransportManager::set_protocol_channel_size()`], if it was set for `protocol`.
     pub fn register_protocol(
         &mut self,
         protocol: ProtocolName,
         fallback_names: Vec<ProtocolName>,
         codec: ProtocolCodec,
     ) -> TransportService {
+        let channel_size = self
+            .protocol_channel_sizes
+            .get(&protocol)
+            .copied()
+            .unwrap_or(DEFAULT_CHANNEL_SIZE);
+
+        self.register_protocol_with_channel_size(protocol, fallback_names, codec, channel_size)
+    }
+
+    /// Register protocol to the [`crate::transport::manager::TransportManager`] with a custom
+    /// event channel size.
+    ///
+    /// `channel_size` is the number of events the connections can queue for the protocol.
+    /// Once the channel is full, the connections wait until the protocol has read events from
+    /// the channel, instead of dropping them.
+    pub fn register_protocol_with_channel_size(
+        &mut self,
+        protocol: ProtocolName,
+        fallback_names: Vec<ProtocolName>,
+        codec: ProtocolCodec,
+        channel_size: usize,
+    ) -> TransportService {
+        assert!(channel_size > 0, "channel size must be non-zero");
         assert!(!self.protocol_names.contains(&protocol));
 
         for fallback in &fallback_names {
@@ -324,12 +501,13 @@ impl TransportManager {
             }
         }
 
-        let (service, sender) = TransportService::new(
+        let (service, sender) = TransportService::with_channel_size(
             self.local_peer_id,
             protocol.clone(),
             fallback_names.clone(),
             self.next_substream_id.clone(),
             self.transport_manager_handle.clone(),
+            channel_size,
         );
 
         self.protocols.insert(
@@ -353,6 +531,8 @@ impl TransportManager {
             protocol_names: self.protocol_names.iter().cloned().collect(),
             next_substream_id: self.next_substream_id.clone(),
             next_connection_id: self.next_connection_id.clone(),
+            resolver: Arc::clone(&self.resolver),
+            shutdown: self.shutdown.clone(),
         }
     }
 
@@ -369,15 +549,161 @@ impl TransportManager {
     }
 
     /// Register local listen address.
+    ///
+    /// Relayed listen addresses contain the `PeerId` of the relay but `address` must not end
+    /// with a `PeerId`.
     pub fn register_listen_address(&mut self, address: Multiaddr) {
-        assert!(!address.iter().any(|protocol| std::matches!(protocol, Protocol::P2p(_))));
+        assert!(!std::matches!(address.iter().last(), Some(Protocol::P2p(_))));
 
         let mut listen_addresses = self.listen_addresses.write();
 
         listen_addresses.insert(address.clone());
-        listen_addresses.insert(address.with(Protocol::P2p(
-            Multihash::from_bytes(&self.local_peer_id.to_bytes()).unwrap(),
-        )));
+        listen_addresses.insert(address.with(Protocol::P2p(self.local_peer_id.to_multihash())));
+    }
+
+    /// Set how many distinct peers must observe an address before it's considered external.
+    pub fn set_external_address_confirmations(&mut self, confirmations: usize) {
+        self.external_addresses.write().set_confirmations(confirmations);
+    }
+
+    /// Get external addresses of the local node, both manually added and confirmed.
+    pub fn external_addresses(&self) -> Vec<Multiaddr> {
+        self.transport_manager_handle.external_addresses()
+    }
+
+    /// Add external address of the local node which is advertised without being confirmed.
+    ///
+    /// Returns `true` if the address was added.
+    pub fn add_external_address(&mut self, address: Multiaddr) -> bool {
+        self.transport_manager_handle.add_manual_external_address(address)
+    }
+
+    /// Remove manually added external address of the local node.
+    ///
+    /// Returns `true` if the address was removed.
+    pub fn remove_external_address(&mut self, address: &Multiaddr) -> bool {
+        self.transport_manager_handle.remove_manual_external_address(address)
+    }
+
+    /// Get addresses the local node advertises to remote peers.
+    pub fn advertised_addresses(&self) -> Vec<Multiaddr> {
+        self.transport_manager_handle.advertised_addresses()
+    }
+
+    /// Set the maximum number of established connections.
+    ///
+    /// Pending dials count towards the limit. When the limit is reached, outbound dials are
+    /// rejected with [`Error::ConnectionLimitReached`] and inbound connections are refused.
+    pub fn set_max_connections(&mut self, max_connections: Option<usize>) {
+        self.max_connections = max_connections;
+    }
+
+    /// Set how long a known address can go unused before it's removed.
+    ///
+    /// An address is used when a connection to it is established. Expired addresses are
+    /// removed when the peer is dialed.
+    pub fn set_address_expiry(&mut self, address_expiry: Option<Duration>) {
+        self.address_expiry = address_expiry;
+    }
+
+    /// Set the score at or below which an address is removed after a failed connection attempt.
+    ///
+    /// Each failed connection attempt decreases the score of the address by `100`.
+    pub fn set_min_address_score(&mut self, min_address_score: Option<i32>) {
+        self.min_address_score = min_address_score;
+    }
+
+    /// Set how long a dial can take before it's aborted.
+    ///
+    /// If the connection hasn't been established within `dial_timeout`, the dial is canceled and
+    /// reported as [`TransportEvent::DialFailure`] with [`Error::DialTimeout`].
+    pub fn set_dial_timeout(&mut self, dial_timeout: Option<Duration>) {
+        self.dial_timeout = dial_timeout;
+    }
+
+    /// Set the backoff applied to peers after failed dials.
+    ///
+    /// While `peer` is backed off, [`TransportManager::dial()`] and
+    /// [`TransportManager::dial_address()`] fail with [`Error::DialBackoff`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config` is invalid, see [`DialBackoffConfig`].
+    pub fn set_dial_backoff(&mut self, config: Option<DialBackoffConfig>) {
+        self.dial_backoff = config.map(DialBackoff::new);
+    }
+
+    /// Set the size of the event channel of `protocol`.
+    ///
+    /// Must be called before `protocol` is registered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel_size` is zero.
+    pub fn set_protocol_channel_size(&mut self, protocol: ProtocolName, channel_size: usize) {
+        assert!(channel_size > 0, "channel size must be non-zero");
+
+        self.protocol_channel_sizes.insert(protocol, channel_size);
+    }
+
+    /// Return an error if `peer` is backed off after a failed dial.
+    fn check_dial_backoff(&self, peer: &PeerId) -> crate::Result<()> {
+        match self.dial_backoff.as_ref().and_then(|backoff| backoff.retry_after(peer)) {
+            Some(retry_after) => Err(Error::DialBackoff { retry_after }),
+            None => Ok(()),
+        }
+    }
+
+    /// Start the connection establishment deadline for `connection_id`, if dials are
+    /// time-limited.
+    fn start_dial_timeout(&mut self, connection_id: ConnectionId) {
+        if let Some(dial_timeout) = self.dial_timeout {
+            let (abort_handle, abort_registration) = AbortHandle::new_pair();
+            self.dial_timeout_aborts.insert(connection_id, abort_handle);
+
+            self.pending_dial_timeouts.push(Box::pin(async move {
+                Abortable::new(tokio::time::sleep(dial_timeout), abort_registration)
+                    .await
+                    .ok()
+                    .map(|()| connection_id)
+            }));
+        }
+    }
+
+    /// Abort the deadline of a dial which has resolved.
+    fn stop_dial_timeout(&mut self, connection_id: ConnectionId) {
+        if let Some(abort_handle) = self.dial_timeout_aborts.remove(&connection_id) {
+            abort_handle.abort();
+        }
+    }
+
+    /// Register Prometheus metrics of the transport manager to `registry`.
+    #[cfg(feature = "metrics")]
+    pub fn register_metrics(&mut self, registry: &prometheus::Registry) -> crate::Result<()> {
+        self.metrics = Some(metrics::TransportManagerMetrics::register(registry)?);
+        Ok(())
+    }
+
+    /// Check if the number of established connections and pending dials has reached the
+    /// configured limit.
+    ///
+    /// Pending dials are counted so that concurrent dials can't push the number of established
+    /// connections past the limit once they succeed.
+    fn connection_limit_reached(&self) -> bool {
+        self.max_connections
+            .is_some_and(|limit| self.connections.len() + self.pending_connections.len() >= limit)
+    }
+
+    /// Ban `peer`, optionally until `until`.
+    ///
+    /// Banned peers cannot be dialed and inbound connections from them are rejected.
+    pub fn ban_peer(&mut self, peer: PeerId, until: Option<Instant>) {
+        self.transport_manager_handle.ban_peer(peer, until);
+    }
+
+    /// Lift the ban of `peer`, if it exists.
+    pub fn unban_peer(&mut self, peer: PeerId) {
+        self.transport_manager_handle.unban_peer(peer);
     }
 
     /// Add one or more known addresses for `peer`.
@@ -389,13 +715,106 @@ impl TransportManager {
         self.transport_manager_handle.add_known_address(&peer, address)
     }
 
+    /// Remove known `address` of `peer`.
+    ///
+    /// Returns `true` if the address was removed.
+    pub fn remove_known_address(&mut self, peer: PeerId, address: &Multiaddr) -> bool {
+        self.transport_manager_handle.remove_known_address(&peer, address)
+    }
+
+    /// Get snapshot of the connection state.
+    pub fn metrics(&self) -> Metrics {
+        let mut metrics = Metrics {
+            pending_connections: self.pending_connections.len(),
+            pending_dns_resolves: self.pending_dns_resolves.len(),
+            ..Default::default()
+        };
+
+        for context in self.peers.read().values() {
+            match &context.state {
+                PeerState::Connected { record, .. } => {
+                    metrics.peers.connected += 1;
+
+                    for record in std::iter::once(record).chain(&context.secondary_connection) {
+                        metrics.established_connections += 1;
+                        *metrics
+                            .connections_per_transport
+                            .entry(Self::connection_transport(record.address()))
+                            .or_default() += 1;
+                    }
+                }
+                PeerState::Opening { .. } => metrics.peers.opening += 1,
+                PeerState::Dialing { .. } => metrics.peers.dialing += 1,
+                PeerState::Disconnected { .. } => metrics.peers.disconnected += 1,
+                PeerState::Banned { .. } => metrics.peers.banned += 1,
+            }
+        }
+
+        metrics
+    }
+
+    /// Get the number of bytes received from and sent to `peer` over its connections.
+    pub fn bandwidth_for(&self, peer: &PeerId) -> (u64, u64) {
+        self.bandwidth_sink.peer_bandwidth(peer)
+    }
+
+    /// Get the number of bytes received and sent over all connections.
+    pub fn total_bandwidth(&self) -> (u64, u64) {
+        self.bandwidth_sink.connection_bandwidth()
+    }
+
+    /// Get the transport of an established connection with `address`.
+    fn connection_transport(address: &Multiaddr) -> SupportedTransport {
+        match address.iter().any(|protocol| std::matches!(protocol, Protocol::WebRTC)) {
+            true => SupportedTransport::WebRtc,
+            false => Self::dial_transport(address),
+        }
+    }
+
+    /// Get the transport used to dial `address` of a known peer.
+    fn dial_transport(address: &Multiaddr) -> SupportedTransport {
+        if address.iter().any(|protocol| std::matches!(protocol, Protocol::P2pCircuit)) {
+            return SupportedTransport::Relay;
+        }
+
+        if address.iter().any(|protocol| std::matches!(protocol, Protocol::QuicV1)) {
+            return SupportedTransport::Quic;
+        }
+
+        if address.iter().any(|protocol| std::matches!(protocol, Protocol::Memory(_))) {
+            return SupportedTransport::Memory;
+        }
+
+        match address
+            .iter()
+            .any(|protocol| std::matches!(protocol, Protocol::Ws(_) | Protocol::Wss(_)))
+        {
+            true => SupportedTransport::WebSocket,
+            false => SupportedTransport::Tcp,
+        }
+    }
+
     /// Dial peer using `PeerId`.
     ///
     /// Returns an error if the peer is unknown or the peer is already connected.
     pub async fn dial(&mut self, peer: PeerId) -> crate::Result<()> {
+        if self.shutdown.is_cancelled() {
+            return Err(Error::EssentialTaskClosed);
+        }
+
         if peer == self.local_peer_id {
             return Err(Error::TriedToDialSelf);
         }
+
+        if self.transport_manager_handle.is_banned(&peer) {
+            return Err(Error::PeerBanned(peer));
+        }
+
+        self.check_dial_backoff(&peer)?;
+
+        if self.connection_limit_reached() {
+            return Err(Error::ConnectionLimitReached);
+        }
         let mut peers = self.peers.write();
 
         // if the peer is disconnected, return its context
@@ -405,6 +824,7 @@ impl TransportManager {
             state,
             secondary_connection,
             mut addresses,
+            pending_ban,
         } = match peers.remove(&peer) {
             None => return Err(Error::PeerDoesntExist(peer)),
             Some(
@@ -425,6 +845,15 @@ impl TransportManager {
                 peers.insert(peer, context);
                 return Ok(());
             }
+            Some(
+                context @ PeerContext {
+                    state: PeerState::Banned { .. },
+                    ..
+                },
+            ) => {
+                peers.insert(peer, context);
+                return Err(Error::PeerBanned(peer));
+            }
             Some(context) => context,
         };
 
@@ -444,34 +873,62 @@ impl TransportManager {
                     state,
                     secondary_connection,
                     addresses,
+                    pending_ban,
                 },
             );
 
             return Ok(());
         }
 
+        if let Some(address_expiry) = self.address_expiry {
+            addresses.remove_expired(address_expiry);
+        }
+
         let mut records: HashMap<_, _> = addresses
             .take(self.max_parallel_dials)
             .into_iter()
             .map(|record| (record.address().clone(), record))
             .collect();
 
+        // the peer context must be put back before returning so its state isn't lost
         if records.is_empty() {
+            peers.insert(
+                peer,
+                PeerContext {
+                    state,
+                    secondary_connection,
+                    addresses,
+                    pending_ban,
+                },
+            );
+
             return Err(Error::NoAddressAvailable(peer));
         }
 
-        for record in records.values() {
-            if self.listen_addresses.read().contains(record.as_ref()) {
-                tracing::warn!(
-                    target: LOG_TARGET,
-                    ?peer,
-                    ?record,
-                    "tried to dial self",
-                );
+        if let Some(record) = records
+            .values()
+            .find(|record| self.listen_addresses.read().contains(record.as_ref()))
+        {
+            tracing::warn!(
+                target: LOG_TARGET,
+                ?peer,
+                ?record,
+                "tried to dial self",
+            );
 
-                debug_assert!(false);
-                return Err(Error::TriedToDialSelf);
-            }
+            addresses.extend(records.values());
+            peers.insert(
+                peer,
+                PeerContext {
+                    state,
+                    secondary_connection,
+                    addresses,
+                    pending_ban,
+                },
+            );
+
+            debug_assert!(false);
+            return Err(Error::TriedToDialSelf);
         }
 
         // set connection id for the address record and put peer into `Opening` state
@@ -488,31 +945,22 @@ impl TransportManager {
         let mut transports = HashSet::new();
         let mut websocket = Vec::new();
         let mut quic = Vec::new();
+        let mut memory = Vec::new();
+        let mut relay = Vec::new();
         let mut tcp = Vec::new();
 
         for (address, record) in &mut records {
             record.set_connection_id(connection_id);
 
-            let mut iter = address.iter();
-            match iter.find(|protocol| std::matches!(protocol, Protocol::QuicV1)) {
-                Some(_) => {
-                    quic.push(address.clone());
-                    transports.insert(SupportedTransport::Quic);
-                }
-                _ => match address
-                    .iter()
-                    .find(|protocol| std::matches!(protocol, Protocol::Ws(_) | Protocol::Wss(_)))
-                {
-                    Some(_) => {
-                        websocket.push(address.clone());
-                        transports.insert(SupportedTransport::WebSocket);
-                    }
-                    None => {
-                        tcp.push(address.clone());
-                        transports.insert(SupportedTransport::Tcp);
-                    }
-                },
+            let transport = Self::dial_transport(address);
+            match transport {
+                SupportedTransport::Quic => quic.push(address.clone()),
+                SupportedTransport::WebSocket => websocket.push(address.clone()),
+                SupportedTransport::Memory => memory.push(address.clone()),
+                SupportedTransport::Relay => relay.push(address.clone()),
+                _ => tcp.push(address.clone()),
             }
+            transports.insert(transport);
         }
 
         peers.insert(
@@ -522,102 +970,171 @@ impl TransportManager {
                     records,
                     connection_id,
                     transports,
+                    errors: Vec::new(),
                 },
                 secondary_connection,
                 addresses,
+                pending_ban,
             },
         );
+        drop(peers);
 
-        if !tcp.is_empty() {
-            self.transports
-                .get_mut(&SupportedTransport::Tcp)
-                .expect("transport to be supported")
-                .open(connection_id, tcp)?;
-        }
+        let mut opened = Vec::new();
+
+        for (transport, addresses) in [
+            (SupportedTransport::Tcp, tcp),
+            (SupportedTransport::Quic, quic),
+            (SupportedTransport::WebSocket, websocket),
+            (SupportedTransport::Memory, memory),
+            (SupportedTransport::Relay, relay),
+        ] {
+            if addresses.is_empty() {
+                continue;
+            }
 
-        if !quic.is_empty() {
-            self.transports
-                .get_mut(&SupportedTransport::Quic)
+            if let Err(error) = self
+                .transports
+                .get_mut(&transport)
                 .expect("transport to be supported")
-                .open(connection_id, quic)?;
-        }
+                .open(connection_id, addresses)
+            {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?peer,
+                    ?connection_id,
+                    ?transport,
+                    ?error,
+                    "failed to open connection",
+                );
 
-        if !websocket.is_empty() {
-            self.transports
-                .get_mut(&SupportedTransport::WebSocket)
-                .expect("transport to be supported")
-                .open(connection_id, websocket)?;
+                // cancel the opens that were already started and return the dialed addresses
+                // back to the peer so it can be dialed again
+                for transport in opened {
+                    self.transports
+                        .get_mut(&transport)
+                        .expect("transport to be supported")
+                        .cancel(connection_id);
+                }
+
+                if let Some(context) = self.peers.write().get_mut(&peer) {
+                    if let PeerState::Opening { records, .. } = std::mem::replace(
+                        &mut context.state,
+                        PeerState::Disconnected { dial_record: None },
+                    ) {
+                        context.addresses.extend(records.into_values());
+                    }
+                }
+
+                return Err(error);
+            }
+
+            opened.push(transport);
         }
 
         self.pending_connections.insert(connection_id, peer);
+        self.start_dial_timeout(connection_id);
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.dials_attempted.inc();
+        }
 
         Ok(())
     }
 
     /// Dial peer using `Multiaddr`.
     ///
-    /// Returns an error if address it not valid.
-    pub async fn dial_address(&mut self, address: Multiaddr) -> crate::Result<()> {
+    /// Returns an error if address it not valid. On success, returns the ID of the connection
+    /// which can be used to abort the dial with [`TransportManager::cancel_dial()`]. If the peer
+    /// is already connected or being dialed, the ID of that connection or dial is returned.
+    pub async fn dial_address(&mut self, address: Multiaddr) -> crate::Result<ConnectionId> {
+        if self.shutdown.is_cancelled() {
+            return Err(Error::EssentialTaskClosed);
+        }
+
         let mut record = AddressRecord::from_multiaddr(address)
             .ok_or(Error::AddressError(AddressError::PeerIdMissing))?;
 
+        // validate the `PeerId` before resolving or dialing the address so a malformed
+        // `PeerId` is rejected early and the dial always targets the peer in the address
+        let remote_peer_id = PeerId::try_from_multiaddr(record.address())
+            .ok_or(Error::AddressError(AddressError::InvalidPeerId))?;
+
+        if self.transport_manager_handle.is_banned(&remote_peer_id) {
+            return Err(Error::PeerBanned(remote_peer_id));
+        }
+
+        self.check_dial_backoff(&remote_peer_id)?;
+
         if self.listen_addresses.read().contains(record.as_ref()) {
             return Err(Error::TriedToDialSelf);
         }
 
+        if self.connection_limit_reached() {
+            return Err(Error::ConnectionLimitReached);
+        }
+
         tracing::debug!(target: LOG_TARGET, address = ?record.address(), "dial remote peer over address");
 
-        let mut protocol_stack = record.as_ref().iter();
-        match protocol_stack
-            .next()
-            .ok_or_else(|| Error::TransportNotSupported(record.address().clone()))?
-        {
-            Protocol::Ip4(_) | Protocol::Ip6(_) => {}
-            Protocol::Dns(_) | Protocol::Dns4(_) | Protocol::Dns6(_) => {}
-            transport => {
-                tracing::error!(
-                    target: LOG_TARGET,
-                    ?transport,
-                    "invalid transport, expected `ip4`/`ip6`"
-                );
-                return Err(Error::TransportNotSupported(record.address().clone()));
-            }
-        };
+        // `/dnsaddr` is resolved into the actual addresses of the peer which are then dialed
+        if let Some(Protocol::Dnsaddr(host)) = record.address().iter().next() {
+            let peer = remote_peer_id;
+            let name = format!("_dnsaddr.{host}");
+            let address = record.address().clone();
+            let resolver = Arc::clone(&self.resolver);
+            let connection_id = self.next_connection_id();
+            record.set_connection_id(connection_id);
 
-        let supported_transport = match protocol_stack
-            .next()
-            .ok_or_else(|| Error::TransportNotSupported(record.address().clone()))?
-        {
-            Protocol::Tcp(_) => match protocol_stack.next() {
-                Some(Protocol::Ws(_)) | Some(Protocol::Wss(_)) => SupportedTransport::WebSocket,
-                Some(Protocol::P2p(_)) => SupportedTransport::Tcp,
-                _ => return Err(Error::TransportNotSupported(record.address().clone())),
-            },
-            Protocol::Udp(_) => match protocol_stack
-                .next()
-                .ok_or_else(|| Error::TransportNotSupported(record.address().clone()))?
+            // keep the peer in `Dialing` state while the TXT records are being looked up so
+            // concurrent dials to the peer don't start another lookup or a parallel dial
             {
-                Protocol::QuicV1 => SupportedTransport::Quic,
-                _ => {
-                    tracing::debug!(target: LOG_TARGET, address = ?record.address(), "expected `quic-v1`");
-                    return Err(Error::TransportNotSupported(record.address().clone()));
+                let mut peers = self.peers.write();
+                let context = peers.entry(peer).or_insert_with(|| PeerContext {
+                    state: PeerState::Disconnected { dial_record: None },
+                    addresses: AddressStore::new(),
+                    secondary_connection: None,
+                    pending_ban: None,
+                });
+
+                match context.state {
+                    PeerState::Dialing { .. }
+                    | PeerState::Connected { .. }
+                    | PeerState::Opening { .. } =>
+                        return context.state.connection_id().ok_or(Error::InvalidState),
+                    PeerState::Banned { .. } => return Err(Error::PeerBanned(peer)),
+                    PeerState::Disconnected { .. } => {
+                        context.state = PeerState::Dialing { record };
+                    }
                 }
-            },
-            protocol => {
-                tracing::error!(
+            }
+
+            self.pending_dns_resolves.push(Box::pin(async move {
+                DnsaddrResolution {
+                    connection_id,
+                    peer,
+                    address,
+                    result: resolver.lookup_txt(&name).await,
+                }
+            }));
+            self.pending_connections.insert(connection_id, peer);
+            self.start_dial_timeout(connection_id);
+
+            return Ok(connection_id);
+        }
+
+        let supported_transport = match parse_multiaddr(record.address()) {
+            Ok(ParsedAddress { transport, .. }) => transport,
+            Err(error) => {
+                tracing::debug!(
                     target: LOG_TARGET,
-                    ?protocol,
-                    "invalid protocol, expected `tcp`"
+                    address = ?record.address(),
+                    ?error,
+                    "invalid address, transport not supported",
                 );
-
                 return Err(Error::TransportNotSupported(record.address().clone()));
             }
         };
 
-        // when constructing `AddressRecord`, `PeerId` was verified to be part of the address
-        let remote_peer_id =
-            PeerId::try_from_multiaddr(record.address()).expect("`PeerId` to exist");
-
         // set connection id for the address record and put peer into `Dialing` state
         let connection_id = self.next_connection_id();
         record.set_connection_id(connection_id);
@@ -636,16 +1153,21 @@ impl TransportManager {
                             },
                             addresses: AddressStore::new(),
                             secondary_connection: None,
+                            pending_ban: None,
                         },
                     );
                 }
                 Some(PeerContext {
                     state:
-                        PeerState::Dialing { .. }
+                        ref state @ (PeerState::Dialing { .. }
                         | PeerState::Connected { .. }
-                        | PeerState::Opening { .. },
+                        | PeerState::Opening { .. }),
+                    ..
+                }) => return state.connection_id().ok_or(Error::InvalidState),
+                Some(PeerContext {
+                    state: PeerState::Banned { .. },
                     ..
-                }) => return Ok(()),
+                }) => return Err(Error::PeerBanned(remote_peer_id)),
                 Some(PeerContext { ref mut state, .. }) => {
                     // TODO: verify that the address is not in `addresses` already
                     // addresses.insert(address.clone());
@@ -661,11 +1183,390 @@ impl TransportManager {
             .ok_or(Error::TransportNotSupported(record.address().clone()))?
             .dial(connection_id, record.address().clone())?;
         self.pending_connections.insert(connection_id, remote_peer_id);
+        self.start_dial_timeout(connection_id);
 
-        Ok(())
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.dials_attempted.inc();
+        }
+
+        Ok(connection_id)
     }
 
-    /// Handle dial failure.
+    /// Dial peer using `Multiaddr` over a new connection, even if the peer is already connected.
+    ///
+    /// If the peer is connected, the dialed connection becomes its secondary connection. A peer
+    /// can only have one secondary connection, so the call fails if the peer already has one or
+    /// if `address` is already being dialed. Other addresses of the peer can be dialed
+    /// concurrently, the first connection that is established becomes the secondary connection
+    /// and the rest are rejected. If the peer is not connected, the call is equivalent to
+    /// [`TransportManager::dial_address()`].
+    pub async fn dial_secondary(&mut self, address: Multiaddr) -> crate::Result<()> {
+        let mut record = AddressRecord::from_multiaddr(address.clone())
+            .ok_or(Error::AddressError(AddressError::PeerIdMissing))?;
+        let remote_peer_id = PeerId::try_from_multiaddr(record.address())
+            .ok_or(Error::AddressError(AddressError::InvalidPeerId))?;
+
+        let can_dial = match self.peers.read().get(&remote_peer_id) {
+            Some(PeerContext {
+                state: PeerState::Connected { dial_record, .. },
+                secondary_connection,
+                ..
+            }) => Some(dial_record.is_none() && secondary_connection.is_none()),
+            _ => None,
+        };
+
+        match can_dial {
+            None => return self.dial_address(address).await.map(|_| ()),
+            Some(false) => return Err(Error::AlreadyConnected),
+            Some(true) => {}
+        }
+
+        if self
+            .pending_secondary_dials
+            .values()
+            .any(|pending| pending.address() == record.address())
+        {
+            return Err(Error::AlreadyConnected);
+        }
+
+        if self.shutdown.is_cancelled() {
+            return Err(Error::EssentialTaskClosed);
+        }
+
+        if self.transport_manager_handle.is_banned(&remote_peer_id) {
+            return Err(Error::PeerBanned(remote_peer_id));
+        }
+
+        if self.connection_limit_reached() {
+            return Err(Error::ConnectionLimitReached);
+        }
+
+        let supported_transport = match parse_multiaddr(record.address()) {
+            Ok(ParsedAddress { transport, .. }) => transport,
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    address = ?record.address(),
+                    ?error,
+                    "invalid address, transport not supported",
+                );
+                return Err(Error::TransportNotSupported(record.address().clone()));
+            }
+        };
+
+        tracing::debug!(
+            target: LOG_TARGET,
+            peer = ?remote_peer_id,
+            address = ?record.address(),
+            "dial secondary connection",
+        );
+
+        let connection_id = self.next_connection_id();
+        record.set_connection_id(connection_id);
+
+        self.transports
+            .get_mut(&supported_transport)
+            .ok_or(Error::TransportNotSupported(record.address().clone()))?
+            .dial(connection_id, record.address().clone())?;
+        self.pending_connections.insert(connection_id, remote_peer_id);
+        self.pending_secondary_dials.insert(connection_id, record);
+        self.start_dial_timeout(connection_id);
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.dials_attempted.inc();
+        }
+
+        Ok(())
+    }
+
+    /// Abort an in-flight dial started with [`TransportManager::dial_address()`].
+    ///
+    /// The transport aborts the dial and it's reported as [`TransportEvent::DialFailure`] with
+    /// [`Error::DialAborted`]. Dials started with [`TransportManager::dial()`] are canceled
+    /// automatically if the peer connects while it's being dialed and cannot be aborted.
+    pub fn cancel_dial(&mut self, connection_id: ConnectionId) -> crate::Result<()> {
+        let peer = *self
+            .pending_connections
+            .get(&connection_id)
+            .ok_or(Error::ConnectionDoesntExist(connection_id))?;
+
+        let transport = {
+            let peers = self.peers.read();
+            let record = match peers.get(&peer).map(|context| &context.state) {
+                _ if self.pending_secondary_dials.contains_key(&connection_id) =>
+                    &self.pending_secondary_dials[&connection_id],
+                Some(PeerState::Dialing { record })
+                | Some(PeerState::Connected {
+                    dial_record: Some(record),
+                    ..
+                })
+                | Some(PeerState::Disconnected {
+                    dial_record: Some(record),
+                }) if record.connection_id() == &Some(connection_id) => record,
+                state => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?peer,
+                        ?connection_id,
+                        ?state,
+                        "cannot abort dial, connection is not being dialed",
+                    );
+                    return Err(Error::InvalidState);
+                }
+            };
+
+            Self::dial_transport(record.address())
+        };
+
+        tracing::debug!(target: LOG_TARGET, ?peer, ?connection_id, ?transport, "abort dial");
+
+        self.transports
+            .get_mut(&transport)
+            .ok_or(Error::InvalidState)?
+            .cancel(connection_id);
+
+        Ok(())
+    }
+
+    /// Handle expired dial deadline.
+    ///
+    /// If the connection is still pending, the dial is canceled, the failure is recorded for
+    /// the dialed address(es) and the dialed peer is returned together with the dialed addresses.
+    fn on_dial_timeout(&mut self, connection_id: ConnectionId) -> Option<(PeerId, Vec<Multiaddr>)> {
+        self.dial_timeout_aborts.remove(&connection_id);
+        let peer = *self.pending_connections.get(&connection_id)?;
+
+        let mut peers = self.peers.write();
+        let context = peers.get_mut(&peer)?;
+
+        let address = match std::mem::replace(
+            &mut context.state,
+            PeerState::Disconnected { dial_record: None },
+        ) {
+            // secondary dials are tracked separately from the state of the peer
+            state if self.pending_secondary_dials.contains_key(&connection_id) => {
+                context.state = state;
+                self.pending_secondary_dials[&connection_id].address().clone()
+            }
+            PeerState::Opening {
+                records,
+                transports,
+                ..
+            } => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?peer,
+                    ?connection_id,
+                    ?transports,
+                    "open timed out",
+                );
+
+                for transport in &transports {
+                    self.transports
+                        .get_mut(transport)
+                        .expect("transport to exist")
+                        .cancel(connection_id);
+                }
+
+                let addresses = records.keys().cloned().collect();
+                for (_, mut record) in records {
+                    record.update_score(SCORE_CONNECT_FAILURE);
+                    context.addresses.insert_failed(record, self.min_address_score);
+                }
+                self.pending_connections.remove(&connection_id);
+
+                if let Some(backoff) = self.dial_backoff.as_mut() {
+                    backoff.on_dial_failure(peer);
+                }
+
+                return Some((peer, addresses));
+            }
+            state => {
+                context.state = state;
+
+                match &context.state {
+                    PeerState::Dialing { record }
+                    | PeerState::Connected {
+                        dial_record: Some(record),
+                        ..
+                    }
+                    | PeerState::Disconnected {
+                        dial_record: Some(record),
+                    } if record.connection_id() == &Some(connection_id) => record.address().clone(),
+                    state => {
+                        tracing::warn!(
+                            target: LOG_TARGET,
+                            ?peer,
+                            ?connection_id,
+                            ?state,
+                            "dial timed out but connection is not being dialed",
+                        );
+
+                        debug_assert!(false);
+                        return None;
+                    }
+                }
+            }
+        };
+        drop(peers);
+
+        tracing::debug!(target: LOG_TARGET, ?peer, ?connection_id, ?address, "dial timed out");
+
+        // `/dnsaddr` addresses haven't been passed to a transport while they're being resolved
+        // and the result of the lookup is ignored once the dial is no longer pending
+        if !std::matches!(address.iter().next(), Some(Protocol::Dnsaddr(_))) {
+            // the transport may still report the dial before it has processed the cancellation
+            self.timed_out_dials.insert(connection_id);
+            self.transports
+                .get_mut(&Self::dial_transport(&address))
+                .expect("transport to exist")
+                .cancel(connection_id);
+        }
+        self.on_dial_failure(connection_id).ok()?;
+
+        Some((peer, vec![address]))
+    }
+
+    /// Handle resolved `/dnsaddr` address.
+    ///
+    /// Addresses found in the TXT records of `address` are added to the known addresses of `peer`
+    /// and the peer is dialed.
+    ///
+    /// If the address couldn't be resolved or none of the resolved addresses could be dialed,
+    /// installed protocols are notified and [`TransportEvent::DialFailure`] is returned.
+    async fn on_dnsaddr_resolved(
+        &mut self,
+        resolution: DnsaddrResolution,
+    ) -> Option<TransportEvent> {
+        let DnsaddrResolution {
+            connection_id,
+            peer,
+            address,
+            result,
+        } = resolution;
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.dns_resolutions.inc();
+        }
+
+        // the dial is no longer pending if it timed out while the address was being resolved
+        if self.pending_connections.remove(&connection_id).is_none() {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?peer,
+                ?address,
+                "`/dnsaddr` resolved but the dial is no longer pending",
+            );
+            return None;
+        }
+        self.stop_dial_timeout(connection_id);
+
+        // the lookup is no longer pending, clear it from the peer state so the resolved
+        // addresses can be dialed
+        {
+            let mut peers = self.peers.write();
+
+            if let Some(context) = peers.get_mut(&peer) {
+                match &mut context.state {
+                    PeerState::Dialing { record }
+                        if record.connection_id() == &Some(connection_id) =>
+                    {
+                        context.state = PeerState::Disconnected { dial_record: None };
+                    }
+                    PeerState::Connected { dial_record, .. }
+                    | PeerState::Disconnected { dial_record }
+                        if dial_record.as_ref().is_some_and(|record| {
+                            record.connection_id() == &Some(connection_id)
+                        }) =>
+                    {
+                        *dial_record = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let records = match result {
+            Ok(records) => records,
+            Err(error) => {
+                tracing::debug!(target: LOG_TARGET, ?address, ?error, "failed to resolve `/dnsaddr`");
+
+                self.report_dial_failure(peer, address.clone()).await;
+                return Some(TransportEvent::DialFailure {
+                    connection_id,
+                    address,
+                    error,
+                });
+            }
+        };
+
+        let addresses = records
+            .iter()
+            .filter_map(|record| {
+                let resolved = record.strip_prefix(DNSADDR_PREFIX)?.parse::<Multiaddr>().ok()?;
+
+                match resolved.iter().last() {
+                    Some(Protocol::P2p(multihash)) =>
+                        (PeerId::from_multihash(multihash).ok()? == peer).then_some(resolved),
+                    _ => Some(resolved.with(Protocol::P2p(Multihash::from(peer)))),
+                }
+            })
+            .filter(|resolved| {
+                // nested `/dnsaddr` addresses are not resolved
+                !std::matches!(resolved.iter().next(), Some(Protocol::Dnsaddr(_)))
+            })
+            .collect::<Vec<_>>();
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?peer,
+            ?address,
+            ?addresses,
+            "`/dnsaddr` resolved",
+        );
+
+        self.add_known_address(peer, addresses.into_iter());
+
+        match self.dial(peer).await {
+            Ok(()) | Err(Error::AlreadyConnected) => None,
+            Err(error) => {
+                tracing::debug!(target: LOG_TARGET, ?peer, ?address, ?error, "failed to dial resolved `/dnsaddr`");
+
+                self.report_dial_failure(peer, address.clone()).await;
+                Some(TransportEvent::DialFailure {
+                    connection_id,
+                    address,
+                    error,
+                })
+            }
+        }
+    }
+
+    /// Notify installed protocols that dialing `peer` over `address` failed.
+    async fn report_dial_failure(&mut self, peer: PeerId, address: Multiaddr) {
+        for context in self.protocols.values() {
+            match context.tx.try_send(InnerTransportEvent::DialFailure {
+                peer,
+                address: address.clone(),
+            }) {
+                Ok(()) => {}
+                Err(_) => {
+                    let _ = context
+                        .tx
+                        .send(InnerTransportEvent::DialFailure {
+                            peer,
+                            address: address.clone(),
+                        })
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// Handle dial failure.
     fn on_dial_failure(&mut self, connection_id: ConnectionId) -> crate::Result<()> {
         let peer = self.pending_connections.remove(&connection_id).ok_or_else(|| {
             tracing::error!(
@@ -677,6 +1578,24 @@ impl TransportManager {
 
             Error::InvalidState
         })?;
+        self.stop_dial_timeout(connection_id);
+
+        if let Some(mut record) = self.pending_secondary_dials.remove(&connection_id) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?peer,
+                ?connection_id,
+                ?record,
+                "failed to dial secondary connection",
+            );
+
+            if let Some(context) = self.peers.write().get_mut(&peer) {
+                record.update_score(SCORE_CONNECT_FAILURE);
+                context.addresses.insert_failed(record, self.min_address_score);
+            }
+
+            return Ok(());
+        }
 
         let mut peers = self.peers.write();
         let context = peers.get_mut(&peer).ok_or_else(|| {
@@ -699,20 +1618,53 @@ impl TransportManager {
                 debug_assert_eq!(record.connection_id(), &Some(connection_id));
 
                 record.update_score(SCORE_CONNECT_FAILURE);
-                context.addresses.insert(record.clone());
+                context.addresses.insert_failed(record.clone(), self.min_address_score);
+
+                if let Some(backoff) = self.dial_backoff.as_mut() {
+                    backoff.on_dial_failure(peer);
+                }
 
                 context.state = PeerState::Disconnected { dial_record: None };
                 Ok(())
             }
-            PeerState::Opening { .. } => {
-                todo!();
+            PeerState::Opening {
+                records,
+                transports,
+                ..
+            } => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?peer,
+                    ?connection_id,
+                    ?transports,
+                    "dial failed while opening connection",
+                );
+
+                // the connection attempt as a whole failed, cancel the dials that may still
+                // be in progress over the other transports
+                for transport in &transports {
+                    if let Some(transport) = self.transports.get_mut(transport) {
+                        transport.cancel(connection_id);
+                    }
+                }
+
+                for (_, mut record) in records {
+                    record.update_score(SCORE_CONNECT_FAILURE);
+                    context.addresses.insert_failed(record, self.min_address_score);
+                }
+
+                if let Some(backoff) = self.dial_backoff.as_mut() {
+                    backoff.on_dial_failure(peer);
+                }
+
+                Ok(())
             }
             PeerState::Connected {
                 record,
                 dial_record: Some(mut dial_record),
             } => {
                 dial_record.update_score(SCORE_CONNECT_FAILURE);
-                context.addresses.insert(dial_record);
+                context.addresses.insert_failed(dial_record, self.min_address_score);
 
                 context.state = PeerState::Connected {
                     record,
@@ -731,7 +1683,11 @@ impl TransportManager {
                 );
 
                 dial_record.update_score(SCORE_CONNECT_FAILURE);
-                context.addresses.insert(dial_record);
+                context.addresses.insert_failed(dial_record, self.min_address_score);
+
+                if let Some(backoff) = self.dial_backoff.as_mut() {
+                    backoff.on_dial_failure(peer);
+                }
 
                 Ok(())
             }
@@ -759,6 +1715,21 @@ impl TransportManager {
         peer: PeerId,
         connection_id: ConnectionId,
     ) -> crate::Result<Option<TransportEvent>> {
+        // the redundant connection is not tracked in the peer state
+        if self.redundant_connections.remove(&connection_id) {
+            tracing::trace!(
+                target: LOG_TARGET,
+                ?peer,
+                ?connection_id,
+                "redundant connection closed",
+            );
+
+            return Ok(Some(TransportEvent::ConnectionClosed {
+                peer,
+                connection_id,
+            }));
+        }
+
         let mut peers = self.peers.write();
         let Some(context) = peers.get_mut(&peer) else {
             tracing::warn!(
@@ -797,6 +1768,7 @@ impl TransportManager {
                         context.state = PeerState::Disconnected {
                             dial_record: actual_dial_record,
                         };
+                        self.bandwidth_sink.release_peer(&peer);
 
                         Ok(Some(TransportEvent::ConnectionClosed {
                             peer,
@@ -877,6 +1849,7 @@ impl TransportManager {
                 }
                 None => {
                     context.state = PeerState::Disconnected { dial_record };
+                    self.bandwidth_sink.release_peer(&peer);
 
                     Ok(Some(TransportEvent::ConnectionClosed {
                         peer,
@@ -892,101 +1865,206 @@ impl TransportManager {
         }
     }
 
+    /// Check whether the connection of `endpoint` is kept when `peer` and the local node have
+    /// connected to each other simultaneously.
+    ///
+    /// Both peers must keep the same connection so the one dialed by the peer with the
+    /// lexicographically smaller `PeerId` is kept.
+    fn keeps_connection(&self, peer: &PeerId, endpoint: &Endpoint) -> bool {
+        endpoint.is_listener() != (self.local_peer_id.to_bytes() < peer.to_bytes())
+    }
+
+    /// Notify installed protocols that `connection_id` of `peer` is redundant.
+    ///
+    /// Protocols stop opening substreams over the connection and it's closed once the substreams
+    /// already open over it have finished. [`TransportEvent::ConnectionClosed`] is emitted once
+    /// the transport reports the connection as closed.
+    async fn drain_redundant_connection(&mut self, peer: PeerId, connection_id: ConnectionId) {
+        for context in self.protocols.values() {
+            match context.tx.try_send(InnerTransportEvent::ConnectionRedundant {
+                peer,
+                connection: connection_id,
+            }) {
+                Ok(()) => {}
+                Err(_) => {
+                    let _ = context
+                        .tx
+                        .send(InnerTransportEvent::ConnectionRedundant {
+                            peer,
+                            connection: connection_id,
+                        })
+                        .await;
+                }
+            }
+        }
+    }
+
     fn on_connection_established(
         &mut self,
         peer: PeerId,
         endpoint: &Endpoint,
     ) -> crate::Result<ConnectionEstablishedResult> {
-        if let Some(dialed_peer) = self.pending_connections.remove(&endpoint.connection_id()) {
+        if let Some(dialed_peer) = self.pending_connections.get(&endpoint.connection_id()).copied()
+        {
             if dialed_peer != peer {
                 tracing::warn!(
                     target: LOG_TARGET,
                     ?dialed_peer,
                     ?peer,
                     ?endpoint,
-                    "peer ids do not match but transport was supposed to reject connection"
+                    "peer ids do not match, treating connection as a dial failure",
                 );
-                debug_assert!(false);
-                return Err(Error::InvalidState);
+
+                self.on_dial_failure(endpoint.connection_id())?;
+                return Err(Error::PeerIdMismatch(dialed_peer, peer));
             }
+
+            self.pending_connections.remove(&endpoint.connection_id());
+            self.stop_dial_timeout(endpoint.connection_id());
         };
 
+        // if the connection was dialed as a secondary connection, it becomes the secondary
+        // connection of the peer unless the peer already has one
+        let secondary_dial =
+            self.pending_secondary_dials.remove(&endpoint.connection_id()).is_some();
+
         let mut peers = self.peers.write();
         match peers.get_mut(&peer) {
             Some(context) => match context.state {
                 PeerState::Connected {
+                    ref mut record,
                     ref mut dial_record,
-                    ..
-                } => match context.secondary_connection {
-                    Some(_) => {
+                } => {
+                    // both peers dialed each other and the connections are of opposite directions,
+                    // keep only one of them
+                    let primary = record.connection_id().and_then(|id| self.connections.get(&id));
+                    let simultaneous_connect = !secondary_dial
+                        && context.secondary_connection.is_none()
+                        && primary
+                            .is_some_and(|primary| primary.is_listener() != endpoint.is_listener());
+
+                    if simultaneous_connect {
+                        let dialed = dial_record.as_ref().is_some_and(|dial_record| {
+                            dial_record.connection_id() == &Some(endpoint.connection_id())
+                        });
+                        let dial_record = match dialed {
+                            true => dial_record.take(),
+                            false => None,
+                        };
+
+                        if !self.keeps_connection(&peer, endpoint) {
+                            tracing::debug!(
+                                target: LOG_TARGET,
+                                ?peer,
+                                connection_id = ?endpoint.connection_id(),
+                                ?endpoint,
+                                "simultaneous connect, drain redundant connection",
+                            );
+
+                            context.addresses.extend(dial_record);
+                            self.redundant_connections.insert(endpoint.connection_id());
+
+                            return Ok(ConnectionEstablishedResult::Drain(
+                                endpoint.connection_id(),
+                            ));
+                        }
+
+                        let redundant = record.connection_id().expect("connection to exist");
                         tracing::debug!(
                             target: LOG_TARGET,
                             ?peer,
                             connection_id = ?endpoint.connection_id(),
                             ?endpoint,
-                            "secondary connection already exists, ignoring connection",
+                            ?redundant,
+                            "simultaneous connect, replace redundant connection",
                         );
 
-                        // insert address into the store only if we're the dialer
-                        //
-                        // if we're the listener, remote might have dialed with an ephemeral port
-                        // which it might not be listening, making this address useless
-                        if endpoint.is_listener() {
-                            context.addresses.insert(AddressRecord::new(
+                        let replaced = std::mem::replace(
+                            record,
+                            AddressRecord::new(
                                 &peer,
                                 endpoint.address().clone(),
                                 SCORE_CONNECT_SUCCESS,
-                                None,
-                            ))
-                        }
+                                Some(endpoint.connection_id()),
+                            ),
+                        );
+                        context.addresses.insert(replaced);
+                        self.redundant_connections.insert(redundant);
 
-                        return Ok(ConnectionEstablishedResult::Reject);
+                        return Ok(ConnectionEstablishedResult::Drain(redundant));
                     }
-                    None => match dial_record.take() {
-                        Some(record)
-                            if record.connection_id() == &Some(endpoint.connection_id()) =>
-                        {
+
+                    match context.secondary_connection {
+                        Some(_) => {
                             tracing::debug!(
                                 target: LOG_TARGET,
                                 ?peer,
                                 connection_id = ?endpoint.connection_id(),
-                                address = ?endpoint.address(),
-                                "dialed connection opened as secondary connection",
+                                ?endpoint,
+                                "secondary connection already exists, ignoring connection",
                             );
 
-                            context.secondary_connection = Some(AddressRecord::new(
-                                &peer,
-                                endpoint.address().clone(),
-                                SCORE_CONNECT_SUCCESS,
-                                Some(endpoint.connection_id()),
-                            ));
-                        }
-                        None => {
-                            tracing::debug!(
+                            // insert address into the store only if we're the dialer
+                            //
+                            // if we're the listener, remote might have dialed with an ephemeral
+                            // port which it might not be listening, making this address useless
+                            if endpoint.is_listener() {
+                                context.addresses.insert(AddressRecord::new(
+                                    &peer,
+                                    endpoint.address().clone(),
+                                    SCORE_CONNECT_SUCCESS,
+                                    None,
+                                ))
+                            }
+
+                            return Ok(ConnectionEstablishedResult::Reject);
+                        }
+                        None => match dial_record.take() {
+                            Some(record)
+                                if record.connection_id() == &Some(endpoint.connection_id()) =>
+                            {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    ?peer,
+                                    connection_id = ?endpoint.connection_id(),
+                                    address = ?endpoint.address(),
+                                    "dialed connection opened as secondary connection",
+                                );
+
+                                context.secondary_connection = Some(AddressRecord::new(
+                                    &peer,
+                                    endpoint.address().clone(),
+                                    SCORE_CONNECT_SUCCESS,
+                                    Some(endpoint.connection_id()),
+                                ));
+                            }
+                            None => {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    ?peer,
+                                    connection_id = ?endpoint.connection_id(),
+                                    address = ?endpoint.address(),
+                                    "secondary connection",
+                                );
+
+                                context.secondary_connection = Some(AddressRecord::new(
+                                    &peer,
+                                    endpoint.address().clone(),
+                                    SCORE_CONNECT_SUCCESS,
+                                    Some(endpoint.connection_id()),
+                                ));
+                            }
+                            Some(record) => tracing::warn!(
                                 target: LOG_TARGET,
                                 ?peer,
                                 connection_id = ?endpoint.connection_id(),
                                 address = ?endpoint.address(),
-                                "secondary connection",
-                            );
-
-                            context.secondary_connection = Some(AddressRecord::new(
-                                &peer,
-                                endpoint.address().clone(),
-                                SCORE_CONNECT_SUCCESS,
-                                Some(endpoint.connection_id()),
-                            ));
-                        }
-                        Some(record) => tracing::warn!(
-                            target: LOG_TARGET,
-                            ?peer,
-                            connection_id = ?endpoint.connection_id(),
-                            address = ?endpoint.address(),
-                            dial_record = ?record,
-                            "unknown connection opened as secondary connection, discarding",
-                        ),
-                    },
-                },
+                                dial_record = ?record,
+                                "unknown connection opened as secondary connection, discarding",
+                            ),
+                        },
+                    }
+                }
                 PeerState::Dialing { ref record, .. } => {
                     match record.connection_id() == &Some(endpoint.connection_id()) {
                         true => {
@@ -1029,6 +2107,7 @@ impl TransportManager {
                     ref mut records,
                     connection_id,
                     ref transports,
+                    ..
                 } => {
                     debug_assert!(std::matches!(endpoint, &Endpoint::Listener { .. }));
 
@@ -1064,6 +2143,9 @@ impl TransportManager {
                             .connection_id()
                             .expect("`ConnectionId` to exist"),
                     );
+                    if let Some(abort_handle) = self.dial_timeout_aborts.remove(&connection_id) {
+                        abort_handle.abort();
+                    }
 
                     let record = match records.remove(endpoint.address()) {
                         Some(mut record) => {
@@ -1129,6 +2211,16 @@ impl TransportManager {
                         dial_record,
                     };
                 }
+                PeerState::Banned { .. } => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?peer,
+                        ?endpoint,
+                        "connection established to a banned peer, reject",
+                    );
+
+                    return Ok(ConnectionEstablishedResult::Reject);
+                }
             },
             None => {
                 peers.insert(
@@ -1145,6 +2237,7 @@ impl TransportManager {
                         },
                         addresses: AddressStore::new(),
                         secondary_connection: None,
+                        pending_ban: None,
                     },
                 );
             }
@@ -1193,6 +2286,7 @@ impl TransportManager {
                 mut records,
                 connection_id,
                 transports,
+                ..
             } => {
                 tracing::trace!(
                     target: LOG_TARGET,
@@ -1204,9 +2298,12 @@ impl TransportManager {
                 );
 
                 // cancel open attempts for other transports as connection already exists
-                for transport in transports.iter() {
+                //
+                // the transport which opened the connection is not canceled as it would otherwise
+                // keep the canceled `ConnectionId` around since it has no open attempt to discard
+                for other in transports.iter().filter(|other| **other != transport) {
                     self.transports
-                        .get_mut(transport)
+                        .get_mut(other)
                         .expect("transport to exist")
                         .cancel(connection_id);
                 }
@@ -1280,11 +2377,16 @@ impl TransportManager {
     }
 
     /// Handle open failure for dialing attempt for `transport`
+    ///
+    /// `errors` contains the addresses dialed over `transport` and the errors they failed with.
+    /// Once all transports have failed, the peer is returned together with the errors of every
+    /// dialed address.
     fn on_open_failure(
         &mut self,
         transport: SupportedTransport,
         connection_id: ConnectionId,
-    ) -> crate::Result<Option<PeerId>> {
+        errors: Vec<(Multiaddr, Error)>,
+    ) -> crate::Result<Option<(PeerId, Vec<(Multiaddr, Error)>)>> {
         let Some(peer) = self.pending_connections.remove(&connection_id) else {
             tracing::warn!(
                 target: LOG_TARGET,
@@ -1317,20 +2419,27 @@ impl TransportManager {
                 records,
                 connection_id,
                 mut transports,
+                errors: mut all_errors,
             } => {
                 tracing::trace!(
                     target: LOG_TARGET,
                     ?peer,
                     ?connection_id,
                     ?transport,
+                    ?errors,
                     "open failure for peer",
                 );
                 transports.remove(&transport);
+                all_errors.extend(errors);
 
                 if transports.is_empty() {
+                    if let Some(abort_handle) = self.dial_timeout_aborts.remove(&connection_id) {
+                        abort_handle.abort();
+                    }
+
                     for (_, mut record) in records {
                         record.update_score(SCORE_CONNECT_FAILURE);
-                        context.addresses.insert(record);
+                        context.addresses.insert_failed(record, self.min_address_score);
                     }
 
                     tracing::trace!(
@@ -1340,7 +2449,11 @@ impl TransportManager {
                         "open failure for last transport",
                     );
 
-                    return Ok(Some(peer));
+                    if let Some(backoff) = self.dial_backoff.as_mut() {
+                        backoff.on_dial_failure(peer);
+                    }
+
+                    return Ok(Some((peer, all_errors)));
                 }
 
                 self.pending_connections.insert(connection_id, peer);
@@ -1348,6 +2461,7 @@ impl TransportManager {
                     records,
                     connection_id,
                     transports,
+                    errors: all_errors,
                 };
 
                 Ok(None)
@@ -1368,24 +2482,139 @@ impl TransportManager {
         }
     }
 
+    /// Shut down [`crate::transport::manager::TransportManager`].
+    ///
+    /// All installed transports are removed which closes their listeners and cancels pending
+    /// dials and negotiations. Open connections are signaled to close and the call returns once
+    /// they have closed or [`SHUTDOWN_TIMEOUT`] has elapsed.
+    ///
+    /// Once the connections have closed, the protocols no longer receive events and their
+    /// [`TransportService`]s are terminated. After the shutdown, dials are rejected and
+    /// [`TransportManager::next()`] returns `None`.
+    pub async fn shutdown(&mut self) {
+        tracing::debug!(
+            target: LOG_TARGET,
+            num_connections = ?self.connections.len(),
+            "shut down transport manager",
+        );
+
+        self.shutdown.cancel();
+        self.transports.clear();
+        self.pending_connections.clear();
+        self.pending_secondary_dials.clear();
+        self.pending_dns_resolves.clear();
+        self.pending_dial_timeouts.clear();
+        self.dial_timeout_aborts.clear();
+        self.pending_events.clear();
+        self.timed_out_dials.clear();
+
+        let drain = async {
+            while !self.connections.is_empty() {
+                match self.event_rx.recv().await {
+                    Some(TransportManagerEvent::ConnectionClosed {
+                        peer,
+                        connection: connection_id,
+                    }) => {
+                        #[cfg(feature = "metrics")]
+                        if let Some(metrics) = &self.metrics {
+                            if self.connections.contains_key(&connection_id) {
+                                metrics.connections_closed.inc();
+                            }
+                        }
+                        self.connections.remove(&connection_id);
+
+                        if let Err(error) = self.on_connection_closed(peer, connection_id) {
+                            tracing::debug!(
+                                target: LOG_TARGET,
+                                ?peer,
+                                ?connection_id,
+                                ?error,
+                                "failed to handle closed connection",
+                            );
+                        }
+                    }
+                    None => return,
+                }
+            }
+        };
+
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, drain).await.is_err() {
+            tracing::warn!(
+                target: LOG_TARGET,
+                num_connections = ?self.connections.len(),
+                "connections did not close in time",
+            );
+        }
+
+        // drop the event senders of the installed protocols so that they're notified
+        // of the shutdown once the closed connections have released their senders
+        self.protocols.clear();
+    }
+
     /// Poll next event from [`crate::transport::manager::TransportManager`].
     pub async fn next(&mut self) -> Option<TransportEvent> {
         loop {
+            if let Some(event) = self.pending_events.pop_front() {
+                return Some(event);
+            }
+
             tokio::select! {
                 event = self.event_rx.recv() => match event? {
                     TransportManagerEvent::ConnectionClosed {
                         peer,
                         connection: connection_id,
-                    } => match self.on_connection_closed(peer, connection_id) {
-                        Ok(None) => {}
-                        Ok(Some(event)) => return Some(event),
-                        Err(error) => tracing::error!(
-                            target: LOG_TARGET,
-                            ?error,
-                            "failed to handle closed connection",
-                        ),
+                    } => {
+                        #[cfg(feature = "metrics")]
+                        if let Some(metrics) = &self.metrics {
+                            if self.connections.contains_key(&connection_id) {
+                                metrics.connections_closed.inc();
+                            }
+                        }
+                        self.connections.remove(&connection_id);
+
+                        match self.on_connection_closed(peer, connection_id) {
+                            Ok(None) => {}
+                            Ok(Some(event)) => return Some(event),
+                            Err(error) => tracing::error!(
+                                target: LOG_TARGET,
+                                ?error,
+                                "failed to handle closed connection",
+                            ),
+                        }
                     }
                 },
+                event = self.pending_dns_resolves.next(), if !self.pending_dns_resolves.is_empty() => {
+                    let resolution = event.expect("future to exist");
+
+                    if let Some(event) = self.on_dnsaddr_resolved(resolution).await {
+                        return Some(event);
+                    }
+                }
+                event = self.pending_dial_timeouts.next(), if !self.pending_dial_timeouts.is_empty() => {
+                    let Some(connection_id) = event.expect("future to exist") else {
+                        continue;
+                    };
+
+                    if let Some((peer, addresses)) = self.on_dial_timeout(connection_id) {
+                        for address in &addresses {
+                            self.report_dial_failure(peer, address.clone()).await;
+                        }
+
+                        #[cfg(feature = "metrics")]
+                        if let Some(metrics) = &self.metrics {
+                            metrics.dials_failed.inc();
+                        }
+
+                        // each dialed address is reported separately
+                        self.pending_events.extend(addresses.into_iter().map(|address| {
+                            TransportEvent::DialFailure {
+                                connection_id,
+                                address,
+                                error: Error::DialTimeout,
+                            }
+                        }));
+                    }
+                }
                 command = self.cmd_rx.recv() => match command? {
                     InnerTransportManagerCommand::DialPeer { peer } => {
                         if let Err(error) = self.dial(peer).await {
@@ -1397,12 +2626,27 @@ impl TransportManager {
                             tracing::debug!(target: LOG_TARGET, ?error, "failed to dial peer")
                         }
                     }
+                    InnerTransportManagerCommand::SecondaryAddress { address } => {
+                        if let Err(error) = self.dial_secondary(address).await {
+                            tracing::debug!(target: LOG_TARGET, ?error, "failed to dial peer")
+                        }
+                    }
                 },
                 event = self.transports.next() => {
                     let (transport, event) = event?;
 
                     match event {
                         TransportEvent::DialFailure { connection_id, address, error } => {
+                            if self.timed_out_dials.remove(&connection_id) {
+                                tracing::trace!(
+                                    target: LOG_TARGET,
+                                    ?connection_id,
+                                    ?error,
+                                    "timed out dial canceled",
+                                );
+                                continue;
+                            }
+
                             tracing::debug!(
                                 target: LOG_TARGET,
                                 ?connection_id,
@@ -1483,6 +2727,21 @@ impl TransportManager {
                                     }
                                 }
 
+                                #[cfg(feature = "metrics")]
+                                if let Some(metrics) = &self.metrics {
+                                    metrics.dials_failed.inc();
+
+                                    if std::matches!(
+                                        error,
+                                        Error::HandshakeFailed(_)
+                                            | Error::Dial(
+                                                crate::error::DialError::HandshakeFailed(_)
+                                            )
+                                    ) {
+                                        metrics.handshake_failures.inc();
+                                    }
+                                }
+
                                 return Some(TransportEvent::DialFailure {
                                     connection_id,
                                     address,
@@ -1490,8 +2749,84 @@ impl TransportManager {
                                 })
                             }
                         }
-                        TransportEvent::ConnectionEstablished { peer, endpoint } => {
+                        TransportEvent::ConnectionEstablished {
+                            peer,
+                            endpoint,
+                            transport: supported_transport,
+                        } => {
+                            if self.timed_out_dials.remove(&endpoint.connection_id()) {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    ?peer,
+                                    ?endpoint,
+                                    "dial timed out, reject connection",
+                                );
+
+                                let _ = self
+                                    .transports
+                                    .get_mut(&transport)
+                                    .expect("transport to exist")
+                                    .reject(endpoint.connection_id());
+                                continue;
+                            }
+
+                            if endpoint.is_listener() && self.connection_limit_reached() {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    ?peer,
+                                    ?endpoint,
+                                    "connection limit reached, reject inbound connection",
+                                );
+
+                                let _ = self
+                                    .transports
+                                    .get_mut(&transport)
+                                    .expect("transport to exist")
+                                    .reject(endpoint.connection_id());
+                                continue;
+                            }
+
+                            if endpoint.is_listener() && self.transport_manager_handle.is_banned(&peer) {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    ?peer,
+                                    ?endpoint,
+                                    "peer is banned, reject inbound connection",
+                                );
+
+                                let _ = self
+                                    .transports
+                                    .get_mut(&transport)
+                                    .expect("transport to exist")
+                                    .reject(endpoint.connection_id());
+                                continue;
+                            }
+
                             match self.on_connection_established(peer, &endpoint) {
+                                Err(Error::PeerIdMismatch(dialed_peer, actual_peer)) => {
+                                    let _ = self
+                                        .transports
+                                        .get_mut(&transport)
+                                        .expect("transport to exist")
+                                        .reject(endpoint.connection_id());
+
+                                    let address = endpoint
+                                        .address()
+                                        .clone()
+                                        .with(Protocol::P2p(Multihash::from(dialed_peer)));
+                                    self.report_dial_failure(dialed_peer, address.clone()).await;
+
+                                    #[cfg(feature = "metrics")]
+                                    if let Some(metrics) = &self.metrics {
+                                        metrics.dials_failed.inc();
+                                    }
+
+                                    return Some(TransportEvent::DialFailure {
+                                        connection_id: endpoint.connection_id(),
+                                        address,
+                                        error: Error::PeerIdMismatch(dialed_peer, actual_peer),
+                                    });
+                                }
                                 Err(error) => {
                                     tracing::debug!(
                                         target: LOG_TARGET,
@@ -1507,7 +2842,10 @@ impl TransportManager {
                                         .expect("transport to exist")
                                         .reject(endpoint.connection_id());
                                 }
-                                Ok(ConnectionEstablishedResult::Accept) => {
+                                Ok(
+                                    result @ (ConnectionEstablishedResult::Accept
+                                    | ConnectionEstablishedResult::Drain(_)),
+                                ) => {
                                     tracing::trace!(
                                         target: LOG_TARGET,
                                         ?peer,
@@ -1520,10 +2858,30 @@ impl TransportManager {
                                         .get_mut(&transport)
                                         .expect("transport to exist")
                                         .accept(endpoint.connection_id());
+                                    self.connections
+                                        .insert(endpoint.connection_id(), endpoint.clone());
+
+                                    if let ConnectionEstablishedResult::Drain(redundant) = result {
+                                        self.drain_redundant_connection(peer, redundant).await;
+                                    }
+
+                                    if let Some(backoff) = self.dial_backoff.as_mut() {
+                                        backoff.on_connection_established(&peer);
+                                    }
+
+                                    #[cfg(feature = "metrics")]
+                                    if let Some(metrics) = &self.metrics {
+                                        metrics.connections_established.inc();
+
+                                        if !endpoint.is_listener() {
+                                            metrics.dials_succeeded.inc();
+                                        }
+                                    }
 
                                     return Some(TransportEvent::ConnectionEstablished {
                                         peer,
                                         endpoint,
+                                        transport: supported_transport,
                                     });
                                 }
                                 Ok(ConnectionEstablishedResult::Reject) => {
@@ -1552,15 +2910,15 @@ impl TransportManager {
                                 );
                             }
                         }
-                        TransportEvent::OpenFailure { connection_id } => {
-                            match self.on_open_failure(transport, connection_id) {
+                        TransportEvent::OpenFailure { connection_id, errors } => {
+                            match self.on_open_failure(transport, connection_id, errors) {
                                 Err(error) => tracing::debug!(
                                     target: LOG_TARGET,
                                     ?connection_id,
                                     ?error,
                                     "failed to handle opened connection",
                                 ),
-                                Ok(Some(peer)) => {
+                                Ok(Some((peer, errors))) => {
                                     tracing::trace!(
                                         target: LOG_TARGET,
                                         ?peer,
@@ -1569,39 +2927,23 @@ impl TransportManager {
                                         "inform protocols about open failure",
                                     );
 
-                                    for (protocol, context) in &self.protocols {
-                                        let _ = match context
-                                            .tx
-                                            .try_send(InnerTransportEvent::DialFailure {
-                                                peer,
-                                                address: Multiaddr::empty(),
-                                            }) {
-                                            Ok(_) => Ok(()),
-                                            Err(_) => {
-                                                tracing::trace!(
-                                                    target: LOG_TARGET,
-                                                    ?peer,
-                                                    %protocol,
-                                                    ?connection_id,
-                                                    "call to protocol would, block try sending in a blocking way",
-                                                );
+                                    for (address, _) in &errors {
+                                        self.report_dial_failure(peer, address.clone()).await;
+                                    }
 
-                                                context
-                                                    .tx
-                                                    .send(InnerTransportEvent::DialFailure {
-                                                        peer,
-                                                        address: Multiaddr::empty(),
-                                                    })
-                                                    .await
-                                            }
-                                        };
+                                    #[cfg(feature = "metrics")]
+                                    if let Some(metrics) = &self.metrics {
+                                        metrics.dials_failed.inc();
                                     }
 
-                                    return Some(TransportEvent::DialFailure {
-                                        connection_id,
-                                        address: Multiaddr::empty(),
-                                        error: Error::Unknown,
-                                    })
+                                    // each dialed address is reported separately
+                                    self.pending_events.extend(errors.into_iter().map(
+                                        |(address, error)| TransportEvent::DialFailure {
+                                            connection_id,
+                                            address,
+                                            error,
+                                        },
+                                    ));
                                 }
                                 Ok(None) => {}
                             }
@@ -1618,10 +2960,18 @@ impl TransportManager {
 mod tests {
     use super::*;
     use crate::{
-        crypto::ed25519::Keypair, executor::DefaultExecutor, transport::dummy::DummyTransport,
+        crypto::ed25519::Keypair,
+        error::DialError,
+        executor::DefaultExecutor,
+        transport::{
+            dns::CachingResolver,
+            dummy::DummyTransport,
+            tcp::{config::Config as TcpConfig, TcpTransport},
+            TransportBuilder, DNS_CACHE_SIZE,
+        },
     };
     use std::{
-        net::{Ipv4Addr, Ipv6Addr},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
         sync::Arc,
     };
 
@@ -1694,6 +3044,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn configured_protocol_channel_size() {
+        let sink = BandwidthSink::new();
+        let (mut manager, _handle) =
+            TransportManager::new(Keypair::generate(), HashSet::new(), sink, 8usize);
+        manager.set_protocol_channel_size(ProtocolName::from("/notif/1"), 4usize);
+
+        manager.register_protocol(
+            ProtocolName::from("/notif/1"),
+            Vec::new(),
+            ProtocolCodec::UnsignedVarint(None),
+        );
+        manager.register_protocol(
+            ProtocolName::from("/notif/2"),
+            Vec::new(),
+            ProtocolCodec::UnsignedVarint(None),
+        );
+
+        let capacity = |protocol: &'static str| {
+            manager.protocols[&ProtocolName::from(protocol)].tx.max_capacity()
+        };
+        assert_eq!(capacity("/notif/1"), 4usize);
+        assert_eq!(capacity("/notif/2"), DEFAULT_CHANNEL_SIZE);
+    }
+
     #[test]
     #[should_panic]
     #[cfg(debug_assertions)]
@@ -1731,9 +3106,7 @@ mod tests {
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Udp(8888))
             .with(Protocol::QuicV1)
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&PeerId::random().to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(PeerId::random().to_multihash()));
 
         assert!(std::matches!(
             manager.dial_address(address).await,
@@ -1757,15 +3130,14 @@ mod tests {
         let dial_address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
 
         let transport = Box::new({
             let mut transport = DummyTransport::new();
             transport.inject_event(TransportEvent::ConnectionEstablished {
                 peer,
                 endpoint: Endpoint::dialer(dial_address.clone(), ConnectionId::from(0usize)),
+                transport: SupportedTransport::Tcp,
             });
             transport
         });
@@ -1821,9 +3193,7 @@ mod tests {
         let dial_address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
 
         assert!(manager.dial_address(dial_address.clone()).await.is_ok());
         assert_eq!(manager.pending_connections.len(), 1);
@@ -1854,9 +3224,7 @@ mod tests {
                 Multiaddr::empty()
                     .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
                     .with(Protocol::Tcp(8888))
-                    .with(Protocol::P2p(
-                        Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-                    ))
+                    .with(Protocol::P2p(peer.to_multihash()))
             )
             .await
             .is_ok());
@@ -1867,9 +3235,7 @@ mod tests {
                 Multiaddr::empty()
                     .with(Protocol::Ip6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)))
                     .with(Protocol::Tcp(8888))
-                    .with(Protocol::P2p(
-                        Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-                    ))
+                    .with(Protocol::P2p(peer.to_multihash()))
             )
             .await
             .is_ok());
@@ -1916,6 +3282,7 @@ mod tests {
                 state: PeerState::Disconnected { dial_record: None },
                 addresses: AddressStore::new(),
                 secondary_connection: None,
+                pending_ban: None,
             },
         );
 
@@ -1939,18 +3306,14 @@ mod tests {
         let address = Multiaddr::empty()
             .with(Protocol::Ip6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&PeerId::random().to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(PeerId::random().to_multihash()));
         assert!(handle.supported_transport(&address));
 
         // ipv4
         let address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&PeerId::random().to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(PeerId::random().to_multihash()));
         assert!(handle.supported_transport(&address));
 
         // quic
@@ -1958,9 +3321,7 @@ mod tests {
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Udp(8888))
             .with(Protocol::QuicV1)
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&PeerId::random().to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(PeerId::random().to_multihash()));
         assert!(handle.supported_transport(&address));
 
         // websocket
@@ -1999,15 +3360,11 @@ mod tests {
         let dial_address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         let connect_address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         assert!(manager.dial_address(dial_address.clone()).await.is_ok());
         assert_eq!(manager.pending_connections.len(), 1);
 
@@ -2041,13 +3398,8 @@ mod tests {
         }
     }
 
-    // local node tried to dial a node and it failed but in the mean
-    // time the remote node dialed local node and that succeeded.
-    //
-    // while the dial was still in progresss, the remote node disconnected after which
-    // the dial failure was reported.
     #[tokio::test]
-    async fn on_dial_failure_already_connected_and_disconnected() {
+    async fn dial_secondary_to_connected_peer() {
         let _ = tracing_subscriber::fmt()
             .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
             .try_init();
@@ -2062,57 +3414,206 @@ mod tests {
         manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
 
         let peer = PeerId::random();
-        let dial_address = Multiaddr::empty()
-            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
-            .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
         let connect_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
+            .with(Protocol::Tcp(55555))
+            .with(Protocol::P2p(peer.to_multihash()));
+        let dial_address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
-        assert!(manager.dial_address(dial_address.clone()).await.is_ok());
-        assert_eq!(manager.pending_connections.len(), 1);
-
-        match &manager.peers.read().get(&peer).unwrap().state {
-            PeerState::Dialing { record } => {
-                assert_eq!(record.address(), &dial_address);
-            }
-            state => panic!("invalid state for peer: {state:?}"),
-        }
+            .with(Protocol::P2p(peer.to_multihash()));
 
-        // remote peer connected to local node from a different address that was dialed
+        // remote peer connected to local node
         manager
             .on_connection_established(
                 peer,
-                &Endpoint::listener(connect_address, ConnectionId::from(1usize)),
+                &Endpoint::listener(connect_address, ConnectionId::from(0usize)),
             )
             .unwrap();
 
-        // connection to remote was closed while the dial was still in progress
-        manager.on_connection_closed(peer, ConnectionId::from(1usize)).unwrap();
+        // dialing a connected peer with `dial_address()` is a no-op
+        assert!(manager.dial_address(dial_address.clone()).await.is_ok());
+        assert!(manager.pending_connections.is_empty());
 
-        // verify that the peer state is `Disconnected`
-        {
-            let peers = manager.peers.read();
-            let peer = peers.get(&peer).unwrap();
+        // but `dial_secondary()` opens a new connection
+        assert!(manager.dial_secondary(dial_address.clone()).await.is_ok());
+        assert_eq!(manager.pending_connections.len(), 1);
+        assert!(std::matches!(
+            manager.dial_secondary(dial_address.clone()).await,
+            Err(Error::AlreadyConnected)
+        ));
 
-            match &peer.state {
-                PeerState::Disconnected {
-                    dial_record: Some(dial_record),
-                    ..
-                } => {
-                    assert_eq!(dial_record.address(), &dial_address);
-                }
-                state => panic!("invalid state: {state:?}"),
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+        manager
+            .on_connection_established(peer, &Endpoint::dialer(dial_address.clone(), connection_id))
+            .unwrap();
+        assert!(manager.pending_connections.is_empty());
+
+        match manager.peers.read().get(&peer).unwrap() {
+            PeerContext {
+                state: PeerState::Connected { dial_record, .. },
+                secondary_connection: Some(secondary_connection),
+                ..
+            } => {
+                assert!(dial_record.is_none());
+                assert_eq!(secondary_connection.address(), &dial_address);
             }
+            context => panic!("invalid state for peer: {:?}", context.state),
         }
 
-        // dialing the peer failed
-        manager.on_dial_failure(ConnectionId::from(0usize)).unwrap();
+        // only one secondary connection is allowed
+        assert!(std::matches!(
+            manager.dial_secondary(dial_address).await,
+            Err(Error::AlreadyConnected)
+        ));
+    }
+
+    #[tokio::test]
+    async fn concurrent_secondary_dials() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let _handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let connect_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
+            .with(Protocol::Tcp(55555))
+            .with(Protocol::P2p(peer.to_multihash()));
+        let first_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(peer.to_multihash()));
+        let second_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 174)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(peer.to_multihash()));
+
+        manager
+            .on_connection_established(
+                peer,
+                &Endpoint::listener(connect_address, ConnectionId::from(0usize)),
+            )
+            .unwrap();
+
+        // both addresses are dialed at the same time
+        assert!(manager.dial_secondary(first_address.clone()).await.is_ok());
+        assert!(manager.dial_secondary(second_address.clone()).await.is_ok());
+        assert_eq!(manager.pending_connections.len(), 2);
+
+        let connection_id = |address: &Multiaddr| {
+            *manager
+                .pending_secondary_dials
+                .iter()
+                .find(|(_, record)| record.address() == address)
+                .unwrap()
+                .0
+        };
+        let first_connection = connection_id(&first_address);
+        let second_connection = connection_id(&second_address);
+
+        // failure of one dial doesn't affect the other dial
+        assert!(manager.on_dial_failure(first_connection).is_ok());
+        assert_eq!(manager.pending_connections.len(), 1);
+
+        manager
+            .on_connection_established(
+                peer,
+                &Endpoint::dialer(second_address.clone(), second_connection),
+            )
+            .unwrap();
+        assert!(manager.pending_connections.is_empty());
+        assert!(manager.pending_secondary_dials.is_empty());
+
+        match manager.peers.read().get(&peer).unwrap() {
+            PeerContext {
+                state: PeerState::Connected { dial_record, .. },
+                secondary_connection: Some(secondary_connection),
+                ..
+            } => {
+                assert!(dial_record.is_none());
+                assert_eq!(secondary_connection.address(), &second_address);
+            }
+            context => panic!("invalid state for peer: {:?}", context.state),
+        };
+    }
+
+    // local node tried to dial a node and it failed but in the mean
+    // time the remote node dialed local node and that succeeded.
+    //
+    // while the dial was still in progresss, the remote node disconnected after which
+    // the dial failure was reported.
+    #[tokio::test]
+    async fn on_dial_failure_already_connected_and_disconnected() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let _handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let dial_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(peer.to_multihash()));
+        let connect_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(peer.to_multihash()));
+        assert!(manager.dial_address(dial_address.clone()).await.is_ok());
+        assert_eq!(manager.pending_connections.len(), 1);
+
+        match &manager.peers.read().get(&peer).unwrap().state {
+            PeerState::Dialing { record } => {
+                assert_eq!(record.address(), &dial_address);
+            }
+            state => panic!("invalid state for peer: {state:?}"),
+        }
+
+        // remote peer connected to local node from a different address that was dialed
+        manager
+            .on_connection_established(
+                peer,
+                &Endpoint::listener(connect_address, ConnectionId::from(1usize)),
+            )
+            .unwrap();
+
+        // connection to remote was closed while the dial was still in progress
+        manager.on_connection_closed(peer, ConnectionId::from(1usize)).unwrap();
+
+        // verify that the peer state is `Disconnected`
+        {
+            let peers = manager.peers.read();
+            let peer = peers.get(&peer).unwrap();
+
+            match &peer.state {
+                PeerState::Disconnected {
+                    dial_record: Some(dial_record),
+                    ..
+                } => {
+                    assert_eq!(dial_record.address(), &dial_address);
+                }
+                state => panic!("invalid state: {state:?}"),
+            }
+        }
+
+        // dialing the peer failed
+        manager.on_dial_failure(ConnectionId::from(0usize)).unwrap();
 
         let peers = manager.peers.read();
         let peer = peers.get(&peer).unwrap();
@@ -2151,15 +3652,11 @@ mod tests {
         let dial_address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         let connect_address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         assert!(manager.dial_address(dial_address.clone()).await.is_ok());
         assert_eq!(manager.pending_connections.len(), 1);
 
@@ -2234,21 +3731,15 @@ mod tests {
         let address1 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         let address2 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         let address3 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 10, 64)))
             .with(Protocol::Tcp(9999))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
 
         // remote peer connected to local node
         manager
@@ -2338,15 +3829,11 @@ mod tests {
         let address1 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         let address2 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
 
         // remote peer connected to local node
         let emit_event = manager
@@ -2440,15 +3927,11 @@ mod tests {
         let address1 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         let address2 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
 
         // remote peer connected to local node
         let emit_event = manager
@@ -2546,21 +4029,15 @@ mod tests {
         let address1 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         let address2 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         let address3 = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 173)))
             .with(Protocol::Tcp(9999))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
 
         // remote peer connected to local node
         let emit_event = manager
@@ -2692,6 +4169,40 @@ mod tests {
         manager.on_dial_failure(connection_id).unwrap();
     }
 
+    #[tokio::test]
+    async fn dial_failure_while_opening() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let address1: Multiaddr = format!("/ip4/1.1.1.1/tcp/8888/p2p/{peer}").parse().unwrap();
+        let address2: Multiaddr = format!("/ip4/2.2.2.2/tcp/8888/p2p/{peer}").parse().unwrap();
+        manager.add_known_address(peer, vec![address1.clone(), address2.clone()].into_iter());
+        manager.dial(peer).await.unwrap();
+
+        let connection_id = match &manager.peers.read().get(&peer).unwrap().state {
+            PeerState::Opening { connection_id, .. } => *connection_id,
+            state => panic!("invalid state: {state:?}"),
+        };
+        manager.on_dial_failure(connection_id).unwrap();
+
+        let peers = manager.peers.read();
+        let context = peers.get(&peer).unwrap();
+
+        match &context.state {
+            PeerState::Disconnected { dial_record: None } => {}
+            state => panic!("invalid state: {state:?}"),
+        }
+        assert!(context.addresses.contains(&address1));
+        assert!(context.addresses.contains(&address2));
+        assert!(!manager.pending_connections.contains_key(&connection_id));
+    }
+
     #[tokio::test]
     #[cfg(debug_assertions)]
     #[should_panic]
@@ -2797,7 +4308,7 @@ mod tests {
         );
 
         manager
-            .on_open_failure(SupportedTransport::Tcp, ConnectionId::random())
+            .on_open_failure(SupportedTransport::Tcp, ConnectionId::random(), Vec::new())
             .unwrap();
     }
 
@@ -2819,451 +4330,2589 @@ mod tests {
         let peer = PeerId::random();
 
         manager.pending_connections.insert(connection_id, peer);
-        manager.on_open_failure(SupportedTransport::Tcp, connection_id).unwrap();
+        manager.on_open_failure(SupportedTransport::Tcp, connection_id, Vec::new()).unwrap();
     }
 
-    #[tokio::test]
-    async fn no_transports() {
-        let _ = tracing_subscriber::fmt()
-            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-            .try_init();
+    /// Transport which records the connections it was asked to cancel.
+    struct CancelTrackingTransport {
+        canceled: Arc<parking_lot::Mutex<HashSet<ConnectionId>>>,
+    }
 
-        let (mut manager, _handle) = TransportManager::new(
-            Keypair::generate(),
-            HashSet::new(),
-            BandwidthSink::new(),
-            8usize,
-        );
+    impl Stream for CancelTrackingTransport {
+        type Item = TransportEvent;
 
-        assert!(manager.next().await.is_none());
+        fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Pending
+        }
     }
 
-    #[tokio::test]
-    async fn dial_already_connected_peer() {
-        let (mut manager, _handle) = TransportManager::new(
-            Keypair::generate(),
-            HashSet::new(),
-            BandwidthSink::new(),
-            8usize,
-        );
+    impl Transport for CancelTrackingTransport {
+        fn dial(&mut self, _: ConnectionId, _: Multiaddr) -> crate::Result<()> {
+            Ok(())
+        }
 
-        let peer = {
-            let peer = PeerId::random();
-            let mut peers = manager.peers.write();
+        fn accept(&mut self, _: ConnectionId) -> crate::Result<()> {
+            Ok(())
+        }
 
-            peers.insert(
-                peer,
-                PeerContext {
-                    state: PeerState::Connected {
-                        record: AddressRecord::from_multiaddr(
-                            Multiaddr::empty()
-                                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                                .with(Protocol::Tcp(8888))
-                                .with(Protocol::P2p(Multihash::from(peer))),
-                        )
-                        .unwrap(),
-                        dial_record: None,
-                    },
-                    secondary_connection: None,
-                    addresses: AddressStore::from_iter(
-                        vec![Multiaddr::empty()
-                            .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                            .with(Protocol::Tcp(8888))
-                            .with(Protocol::P2p(Multihash::from(peer)))]
-                        .into_iter(),
-                    ),
-                },
-            );
-            drop(peers);
+        fn reject(&mut self, _: ConnectionId) -> crate::Result<()> {
+            Ok(())
+        }
 
-            peer
-        };
+        fn open(&mut self, _: ConnectionId, _: Vec<Multiaddr>) -> crate::Result<()> {
+            Ok(())
+        }
 
-        match manager.dial(peer).await {
-            Err(Error::AlreadyConnected) => {}
-            _ => panic!("invalid return value"),
+        fn negotiate(&mut self, _: ConnectionId) -> crate::Result<()> {
+            Ok(())
+        }
+
+        fn cancel(&mut self, connection_id: ConnectionId) {
+            self.canceled.lock().insert(connection_id);
         }
     }
 
     #[tokio::test]
-    async fn peer_already_being_dialed() {
+    async fn other_transports_canceled_when_connection_opened() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
         let (mut manager, _handle) = TransportManager::new(
             Keypair::generate(),
-            HashSet::new(),
+            HashSet::from_iter([SupportedTransport::Tcp, SupportedTransport::Quic]),
             BandwidthSink::new(),
             8usize,
         );
 
-        let peer = {
-            let peer = PeerId::random();
-            let mut peers = manager.peers.write();
-
-            peers.insert(
-                peer,
-                PeerContext {
-                    state: PeerState::Dialing {
-                        record: AddressRecord::from_multiaddr(
-                            Multiaddr::empty()
-                                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                                .with(Protocol::Tcp(8888))
-                                .with(Protocol::P2p(Multihash::from(peer))),
-                        )
-                        .unwrap(),
-                    },
-                    secondary_connection: None,
-                    addresses: AddressStore::from_iter(
-                        vec![Multiaddr::empty()
-                            .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                            .with(Protocol::Tcp(8888))
-                            .with(Protocol::P2p(Multihash::from(peer)))]
-                        .into_iter(),
-                    ),
-                },
-            );
-            drop(peers);
+        let tcp_canceled = Arc::new(parking_lot::Mutex::new(HashSet::new()));
+        let quic_canceled = Arc::new(parking_lot::Mutex::new(HashSet::new()));
+        manager.register_transport(
+            SupportedTransport::Tcp,
+            Box::new(CancelTrackingTransport {
+                canceled: Arc::clone(&tcp_canceled),
+            }),
+        );
+        manager.register_transport(
+            SupportedTransport::Quic,
+            Box::new(CancelTrackingTransport {
+                canceled: Arc::clone(&quic_canceled),
+            }),
+        );
 
-            peer
-        };
+        let peer = PeerId::random();
+        let tcp_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 5)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        let quic_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 5)))
+            .with(Protocol::Udp(8888))
+            .with(Protocol::QuicV1)
+            .with(Protocol::P2p(Multihash::from(peer)));
 
+        assert_eq!(
+            manager.add_known_address(peer, vec![tcp_address, quic_address.clone()].into_iter()),
+            2usize
+        );
         manager.dial(peer).await.unwrap();
-    }
 
-    #[tokio::test]
-    async fn pending_connection_for_disconnected_peer() {
-        let (mut manager, _handle) = TransportManager::new(
-            Keypair::generate(),
-            HashSet::new(),
-            BandwidthSink::new(),
-            8usize,
-        );
+        let connection_id = match &manager.peers.read().get(&peer).unwrap().state {
+            PeerState::Opening {
+                connection_id,
+                transports,
+                ..
+            } => {
+                assert_eq!(
+                    transports,
+                    &HashSet::from_iter([SupportedTransport::Tcp, SupportedTransport::Quic])
+                );
+                *connection_id
+            }
+            state => panic!("invalid state: {state:?}"),
+        };
 
-        let peer = {
-            let peer = PeerId::random();
-            let mut peers = manager.peers.write();
+        // dialing the peer again doesn't start another dial
+        manager.dial(peer).await.unwrap();
+        assert_eq!(manager.pending_connections.len(), 1usize);
 
-            peers.insert(
-                peer,
-                PeerContext {
-                    state: PeerState::Disconnected {
-                        dial_record: Some(
-                            AddressRecord::from_multiaddr(
-                                Multiaddr::empty()
-                                    .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                                    .with(Protocol::Tcp(8888))
-                                    .with(Protocol::P2p(Multihash::from(peer))),
-                            )
-                            .unwrap(),
-                        ),
-                    },
-                    secondary_connection: None,
-                    addresses: AddressStore::new(),
-                },
-            );
-            drop(peers);
+        // quic wins the race and the tcp dial is canceled
+        manager
+            .on_connection_opened(
+                SupportedTransport::Quic,
+                connection_id,
+                quic_address.clone(),
+            )
+            .unwrap();
 
-            peer
-        };
+        assert_eq!(*tcp_canceled.lock(), HashSet::from_iter([connection_id]));
+        assert!(quic_canceled.lock().is_empty());
 
-        manager.dial(peer).await.unwrap();
+        match &manager.peers.read().get(&peer).unwrap().state {
+            PeerState::Dialing { record } => {
+                assert_eq!(record.address(), &quic_address);
+                assert_eq!(record.connection_id(), &Some(connection_id));
+            }
+            state => panic!("invalid state: {state:?}"),
+        };
     }
 
-    #[tokio::test]
-    async fn dial_address_invalid_transport() {
-        let _ = tracing_subscriber::fmt()
-            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-            .try_init();
+    /// Transport which fails to open every connection.
+    struct OpenFailingTransport;
 
-        let (mut manager, _handle) = TransportManager::new(
-            Keypair::generate(),
-            HashSet::new(),
-            BandwidthSink::new(),
-            8usize,
-        );
+    impl Stream for OpenFailingTransport {
+        type Item = TransportEvent;
 
-        // transport doesn't start with ip/dns
-        {
-            let address = Multiaddr::empty().with(Protocol::P2p(Multihash::from(PeerId::random())));
-            match manager.dial_address(address.clone()).await {
-                Err(Error::TransportNotSupported(dial_address)) => {
-                    assert_eq!(dial_address, address);
-                }
-                _ => panic!("invalid return value"),
-            }
+        fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Pending
         }
+    }
 
-        {
-            // upd-based protocol but not quic
-            let address = Multiaddr::empty()
-                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                .with(Protocol::Udp(8888))
-                .with(Protocol::Utp)
-                .with(Protocol::P2p(Multihash::from(PeerId::random())));
-            match manager.dial_address(address.clone()).await {
-                Err(Error::TransportNotSupported(dial_address)) => {
-                    assert_eq!(dial_address, address);
-                }
-                res => panic!("invalid return value: {res:?}"),
-            }
+    impl Transport for OpenFailingTransport {
+        fn dial(&mut self, _: ConnectionId, _: Multiaddr) -> crate::Result<()> {
+            Ok(())
         }
 
-        // not tcp nor udp
-        {
-            let address = Multiaddr::empty()
-                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                .with(Protocol::Sctp(8888))
-                .with(Protocol::P2p(Multihash::from(PeerId::random())));
-            match manager.dial_address(address.clone()).await {
-                Err(Error::TransportNotSupported(dial_address)) => {
-                    assert_eq!(dial_address, address);
-                }
-                _ => panic!("invalid return value"),
-            }
+        fn accept(&mut self, _: ConnectionId) -> crate::Result<()> {
+            Ok(())
         }
 
-        // random protocol after tcp
-        {
-            let address = Multiaddr::empty()
-                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                .with(Protocol::Tcp(8888))
-                .with(Protocol::Utp)
-                .with(Protocol::P2p(Multihash::from(PeerId::random())));
-            match manager.dial_address(address.clone()).await {
-                Err(Error::TransportNotSupported(dial_address)) => {
-                    assert_eq!(dial_address, address);
-                }
-                _ => panic!("invalid return value"),
-            }
+        fn reject(&mut self, _: ConnectionId) -> crate::Result<()> {
+            Ok(())
         }
+
+        fn open(&mut self, _: ConnectionId, _: Vec<Multiaddr>) -> crate::Result<()> {
+            Err(Error::Unknown)
+        }
+
+        fn negotiate(&mut self, _: ConnectionId) -> crate::Result<()> {
+            Ok(())
+        }
+
+        fn cancel(&mut self, _: ConnectionId) {}
     }
 
     #[tokio::test]
-    async fn dial_address_peer_id_missing() {
+    async fn failed_open_restores_peer_state() {
         let (mut manager, _handle) = TransportManager::new(
             Keypair::generate(),
-            HashSet::new(),
+            HashSet::from_iter([SupportedTransport::Tcp, SupportedTransport::Quic]),
             BandwidthSink::new(),
             8usize,
         );
 
-        async fn call_manager(manager: &mut TransportManager, address: Multiaddr) {
-            match manager.dial_address(address).await {
-                Err(Error::AddressError(AddressError::PeerIdMissing)) => {}
-                _ => panic!("invalid return value"),
-            }
-        }
+        let tcp_canceled = Arc::new(parking_lot::Mutex::new(HashSet::new()));
+        manager.register_transport(
+            SupportedTransport::Tcp,
+            Box::new(CancelTrackingTransport {
+                canceled: Arc::clone(&tcp_canceled),
+            }),
+        );
+        manager.register_transport(SupportedTransport::Quic, Box::new(OpenFailingTransport));
 
-        {
-            call_manager(
-                &mut manager,
-                Multiaddr::empty()
-                    .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                    .with(Protocol::Tcp(8888)),
-            )
-            .await;
-        }
+        let peer = PeerId::random();
+        let tcp_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 5)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        let quic_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 5)))
+            .with(Protocol::Udp(8888))
+            .with(Protocol::QuicV1)
+            .with(Protocol::P2p(Multihash::from(peer)));
+        manager.add_known_address(
+            peer,
+            vec![tcp_address.clone(), quic_address.clone()].into_iter(),
+        );
 
-        {
-            call_manager(
-                &mut manager,
-                Multiaddr::empty()
-                    .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                    .with(Protocol::Tcp(8888))
-                    .with(Protocol::Wss(std::borrow::Cow::Owned("".to_string()))),
-            )
-            .await;
-        }
+        // tcp is opened before quic fails and its open is canceled
+        assert!(std::matches!(manager.dial(peer).await, Err(Error::Unknown)));
+        assert_eq!(tcp_canceled.lock().len(), 1usize);
+        assert!(manager.pending_connections.is_empty());
 
         {
-            call_manager(
-                &mut manager,
-                Multiaddr::empty()
-                    .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
-                    .with(Protocol::Udp(8888))
-                    .with(Protocol::QuicV1),
-            )
-            .await;
+            let peers = manager.peers.read();
+            let context = peers.get(&peer).unwrap();
+
+            assert!(std::matches!(
+                context.state,
+                PeerState::Disconnected { dial_record: None }
+            ));
+            assert!(context.addresses.contains(&tcp_address));
+            assert!(context.addresses.contains(&quic_address));
         }
+
+        // the peer isn't stuck in `Opening` and is dialed again
+        assert!(std::matches!(manager.dial(peer).await, Err(Error::Unknown)));
+        assert_eq!(tcp_canceled.lock().len(), 2usize);
     }
 
     #[tokio::test]
-    async fn inbound_connection_while_dialing() {
+    async fn cancel_dial_aborts_dial() {
         let _ = tracing_subscriber::fmt()
             .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
             .try_init();
 
         let (mut manager, _handle) = TransportManager::new(
             Keypair::generate(),
-            HashSet::new(),
+            HashSet::from_iter([SupportedTransport::Tcp]),
             BandwidthSink::new(),
             8usize,
         );
+        let handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        let (transport, _) = <TcpTransport as TransportBuilder>::new(
+            handle,
+            TcpConfig {
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        manager.register_transport(SupportedTransport::Tcp, Box::new(transport));
+
+        // listener which never responds to the connection negotiation
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
         let peer = PeerId::random();
-        let dial_address = Multiaddr::empty()
+        let address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
-            .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
-
-        let connection_id = ConnectionId::random();
-        let transport = Box::new({
-            let mut transport = DummyTransport::new();
-            transport.inject_event(TransportEvent::ConnectionEstablished {
-                peer,
-                endpoint: Endpoint::listener(dial_address.clone(), connection_id),
-            });
-            transport
-        });
-        manager.register_transport(SupportedTransport::Tcp, transport);
-        manager.add_known_address(
-            peer,
-            vec![Multiaddr::empty()
-                .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 5)))
-                .with(Protocol::Tcp(8888))
-                .with(Protocol::P2p(Multihash::from(peer)))]
-            .into_iter(),
-        );
-
-        assert!(manager.dial(peer).await.is_ok());
-        assert!(!manager.pending_connections.is_empty());
-
-        {
-            let peers = manager.peers.read();
+            .with(Protocol::Tcp(listener.local_addr().unwrap().port()))
+            .with(Protocol::P2p(Multihash::from(peer)));
 
-            match peers.get(&peer) {
-                Some(PeerContext {
-                    state: PeerState::Opening { .. },
-                    ..
-                }) => {}
-                state => panic!("invalid state for peer: {state:?}"),
-            }
-        }
+        manager.dial_address(address.clone()).await.unwrap();
 
-        match manager.next().await.unwrap() {
-            TransportEvent::ConnectionEstablished {
-                peer: event_peer,
-                endpoint: event_endpoint,
-                ..
-            } => {
-                assert_eq!(peer, event_peer);
-                assert_eq!(
-                    event_endpoint,
-                    Endpoint::listener(dial_address.clone(), connection_id),
-                );
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+        assert!(std::matches!(
+            manager.cancel_dial(ConnectionId::from(1337usize)),
+            Err(Error::ConnectionDoesntExist(_))
+        ));
+        manager.cancel_dial(connection_id).unwrap();
+
+        match tokio::time::timeout(std::time::Duration::from_secs(5), manager.next()).await {
+            Ok(Some(TransportEvent::DialFailure {
+                connection_id: failed_connection_id,
+                address: failed_address,
+                error: Error::DialAborted,
+            })) => {
+                assert_eq!(failed_connection_id, connection_id);
+                assert_eq!(failed_address, address);
             }
             event => panic!("invalid event: {event:?}"),
         }
-        assert!(manager.pending_connections.is_empty());
 
-        let peers = manager.peers.read();
-        match peers.get(&peer).unwrap() {
-            PeerContext {
-                state:
-                    PeerState::Connected {
-                        record,
-                        dial_record,
-                    },
-                secondary_connection,
-                addresses,
-            } => {
-                assert!(!addresses.contains(record.address()));
-                assert!(dial_record.is_none());
-                assert!(secondary_connection.is_none());
-                assert_eq!(record.address(), &dial_address);
-                assert_eq!(record.connection_id(), &Some(connection_id));
-            }
-            state => panic!("invalid peer state: {state:?}"),
-        }
+        assert!(manager.pending_connections.is_empty());
+        assert!(std::matches!(
+            manager.peers.read().get(&peer).unwrap().state,
+            PeerState::Disconnected { dial_record: None }
+        ));
     }
 
     #[tokio::test]
-    async fn inbound_connection_for_same_address_while_dialing() {
+    async fn dial_timeout_aborts_dial() {
         let _ = tracing_subscriber::fmt()
             .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
             .try_init();
 
         let (mut manager, _handle) = TransportManager::new(
             Keypair::generate(),
-            HashSet::new(),
+            HashSet::from_iter([SupportedTransport::Tcp]),
             BandwidthSink::new(),
             8usize,
         );
+        manager.set_dial_timeout(Some(std::time::Duration::from_millis(500)));
+        let handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        let (transport, _) = <TcpTransport as TransportBuilder>::new(
+            handle,
+            TcpConfig {
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        manager.register_transport(SupportedTransport::Tcp, Box::new(transport));
+
+        // black hole which accepts the connection but never responds to the negotiation
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
         let peer = PeerId::random();
-        let dial_address = Multiaddr::empty()
+        let address = Multiaddr::empty()
             .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
-            .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
-
+            .with(Protocol::Tcp(listener.local_addr().unwrap().port()))
+            .with(Protocol::P2p(Multihash::from(peer)));
+
+        manager.dial_address(address.clone()).await.unwrap();
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+
+        match tokio::time::timeout(std::time::Duration::from_secs(5), manager.next()).await {
+            Ok(Some(TransportEvent::DialFailure {
+                connection_id: failed_connection_id,
+                address: failed_address,
+                error: Error::DialTimeout,
+            })) => {
+                assert_eq!(failed_connection_id, connection_id);
+                assert_eq!(failed_address, address);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        assert!(manager.pending_connections.is_empty());
+        assert!(std::matches!(
+            manager.peers.read().get(&peer).unwrap().state,
+            PeerState::Disconnected { dial_record: None }
+        ));
+
+        // the aborted dial reported by the transport is not reported again
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_secs(1), manager.next())
+                .await
+                .is_err()
+        );
+        assert!(manager.timed_out_dials.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dial_timeout_dropped_once_dial_resolves() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.set_dial_timeout(Some(std::time::Duration::from_millis(500)));
+        let _handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 5)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+
+        manager.dial_address(address).await.unwrap();
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+        assert_eq!(manager.dial_timeout_aborts.len(), 1usize);
+
+        manager.on_dial_failure(connection_id).unwrap();
+        assert!(manager.dial_timeout_aborts.is_empty());
+
+        // the deadline resolves immediately without reporting a timeout
+        assert_eq!(manager.pending_dial_timeouts.next().await, Some(None));
+    }
+
+    #[tokio::test]
+    async fn dial_timeout_cancels_open_attempts() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::from_iter([SupportedTransport::Tcp, SupportedTransport::Quic]),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.set_dial_timeout(Some(std::time::Duration::from_millis(500)));
+
+        let tcp_canceled = Arc::new(parking_lot::Mutex::new(HashSet::new()));
+        let quic_canceled = Arc::new(parking_lot::Mutex::new(HashSet::new()));
+        manager.register_transport(
+            SupportedTransport::Tcp,
+            Box::new(CancelTrackingTransport {
+                canceled: Arc::clone(&tcp_canceled),
+            }),
+        );
+        manager.register_transport(
+            SupportedTransport::Quic,
+            Box::new(CancelTrackingTransport {
+                canceled: Arc::clone(&quic_canceled),
+            }),
+        );
+
+        let peer = PeerId::random();
+        let tcp_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 5)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        let quic_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 5)))
+            .with(Protocol::Udp(8888))
+            .with(Protocol::QuicV1)
+            .with(Protocol::P2p(Multihash::from(peer)));
+
+        assert_eq!(
+            manager.add_known_address(
+                peer,
+                vec![tcp_address.clone(), quic_address.clone()].into_iter()
+            ),
+            2usize
+        );
+        manager.dial(peer).await.unwrap();
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+
+        // neither transport reports anything and the open attempts time out
+        //
+        // the timeout is reported for each dialed address
+        let mut failed_addresses = HashSet::new();
+        for _ in 0..2 {
+            match tokio::time::timeout(std::time::Duration::from_secs(5), manager.next()).await {
+                Ok(Some(TransportEvent::DialFailure {
+                    connection_id: failed_connection_id,
+                    address,
+                    error: Error::DialTimeout,
+                })) => {
+                    assert_eq!(failed_connection_id, connection_id);
+                    failed_addresses.insert(address);
+                }
+                event => panic!("invalid event: {event:?}"),
+            }
+        }
+        assert_eq!(
+            failed_addresses,
+            HashSet::from_iter([tcp_address, quic_address])
+        );
+        assert!(manager.dial_timeout_aborts.is_empty());
+
+        assert_eq!(*tcp_canceled.lock(), HashSet::from_iter([connection_id]));
+        assert_eq!(*quic_canceled.lock(), HashSet::from_iter([connection_id]));
+        assert!(manager.pending_connections.is_empty());
+
+        let peers = manager.peers.read();
+        let context = peers.get(&peer).unwrap();
+        assert!(std::matches!(
+            context.state,
+            PeerState::Disconnected { dial_record: None }
+        ));
+        assert_eq!(context.addresses.by_address.len(), 2usize);
+    }
+
+    #[tokio::test]
+    async fn no_transports() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        assert!(manager.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn dial_already_connected_peer() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        let peer = {
+            let peer = PeerId::random();
+            let mut peers = manager.peers.write();
+
+            peers.insert(
+                peer,
+                PeerContext {
+                    state: PeerState::Connected {
+                        record: AddressRecord::from_multiaddr(
+                            Multiaddr::empty()
+                                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                                .with(Protocol::Tcp(8888))
+                                .with(Protocol::P2p(Multihash::from(peer))),
+                        )
+                        .unwrap(),
+                        dial_record: None,
+                    },
+                    secondary_connection: None,
+                    pending_ban: None,
+                    addresses: AddressStore::from_iter(
+                        vec![Multiaddr::empty()
+                            .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                            .with(Protocol::Tcp(8888))
+                            .with(Protocol::P2p(Multihash::from(peer)))]
+                        .into_iter(),
+                    ),
+                },
+            );
+            drop(peers);
+
+            peer
+        };
+
+        match manager.dial(peer).await {
+            Err(Error::AlreadyConnected) => {}
+            _ => panic!("invalid return value"),
+        }
+    }
+
+    #[tokio::test]
+    async fn peer_already_being_dialed() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        let peer = {
+            let peer = PeerId::random();
+            let mut peers = manager.peers.write();
+
+            peers.insert(
+                peer,
+                PeerContext {
+                    state: PeerState::Dialing {
+                        record: AddressRecord::from_multiaddr(
+                            Multiaddr::empty()
+                                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                                .with(Protocol::Tcp(8888))
+                                .with(Protocol::P2p(Multihash::from(peer))),
+                        )
+                        .unwrap(),
+                    },
+                    secondary_connection: None,
+                    pending_ban: None,
+                    addresses: AddressStore::from_iter(
+                        vec![Multiaddr::empty()
+                            .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                            .with(Protocol::Tcp(8888))
+                            .with(Protocol::P2p(Multihash::from(peer)))]
+                        .into_iter(),
+                    ),
+                },
+            );
+            drop(peers);
+
+            peer
+        };
+
+        manager.dial(peer).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pending_connection_for_disconnected_peer() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        let peer = {
+            let peer = PeerId::random();
+            let mut peers = manager.peers.write();
+
+            peers.insert(
+                peer,
+                PeerContext {
+                    state: PeerState::Disconnected {
+                        dial_record: Some(
+                            AddressRecord::from_multiaddr(
+                                Multiaddr::empty()
+                                    .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                                    .with(Protocol::Tcp(8888))
+                                    .with(Protocol::P2p(Multihash::from(peer))),
+                            )
+                            .unwrap(),
+                        ),
+                    },
+                    secondary_connection: None,
+                    pending_ban: None,
+                    addresses: AddressStore::new(),
+                },
+            );
+            drop(peers);
+
+            peer
+        };
+
+        manager.dial(peer).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn dial_address_invalid_transport() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        // transport doesn't start with ip/dns
+        {
+            let address = Multiaddr::empty().with(Protocol::P2p(Multihash::from(PeerId::random())));
+            match manager.dial_address(address.clone()).await {
+                Err(Error::TransportNotSupported(dial_address)) => {
+                    assert_eq!(dial_address, address);
+                }
+                _ => panic!("invalid return value"),
+            }
+        }
+
+        {
+            // upd-based protocol but not quic
+            let address = Multiaddr::empty()
+                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                .with(Protocol::Udp(8888))
+                .with(Protocol::Utp)
+                .with(Protocol::P2p(Multihash::from(PeerId::random())));
+            match manager.dial_address(address.clone()).await {
+                Err(Error::TransportNotSupported(dial_address)) => {
+                    assert_eq!(dial_address, address);
+                }
+                res => panic!("invalid return value: {res:?}"),
+            }
+        }
+
+        // not tcp nor udp
+        {
+            let address = Multiaddr::empty()
+                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                .with(Protocol::Sctp(8888))
+                .with(Protocol::P2p(Multihash::from(PeerId::random())));
+            match manager.dial_address(address.clone()).await {
+                Err(Error::TransportNotSupported(dial_address)) => {
+                    assert_eq!(dial_address, address);
+                }
+                _ => panic!("invalid return value"),
+            }
+        }
+
+        // random protocol after tcp
+        {
+            let address = Multiaddr::empty()
+                .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                .with(Protocol::Tcp(8888))
+                .with(Protocol::Utp)
+                .with(Protocol::P2p(Multihash::from(PeerId::random())));
+            match manager.dial_address(address.clone()).await {
+                Err(Error::TransportNotSupported(dial_address)) => {
+                    assert_eq!(dial_address, address);
+                }
+                _ => panic!("invalid return value"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn dial_address_peer_id_missing() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        async fn call_manager(manager: &mut TransportManager, address: Multiaddr) {
+            match manager.dial_address(address).await {
+                Err(Error::AddressError(AddressError::PeerIdMissing)) => {}
+                _ => panic!("invalid return value"),
+            }
+        }
+
+        {
+            call_manager(
+                &mut manager,
+                Multiaddr::empty()
+                    .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                    .with(Protocol::Tcp(8888)),
+            )
+            .await;
+        }
+
+        {
+            call_manager(
+                &mut manager,
+                Multiaddr::empty()
+                    .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                    .with(Protocol::Tcp(8888))
+                    .with(Protocol::Wss(std::borrow::Cow::Owned("".to_string()))),
+            )
+            .await;
+        }
+
+        {
+            call_manager(
+                &mut manager,
+                Multiaddr::empty()
+                    .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+                    .with(Protocol::Udp(8888))
+                    .with(Protocol::QuicV1),
+            )
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn inbound_connection_while_dialing() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let peer = PeerId::random();
+        let dial_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(peer.to_multihash()));
+
+        let connection_id = ConnectionId::random();
+        let transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer,
+                endpoint: Endpoint::listener(dial_address.clone(), connection_id),
+                transport: SupportedTransport::Tcp,
+            });
+            transport
+        });
+        manager.register_transport(SupportedTransport::Tcp, transport);
+        manager.add_known_address(
+            peer,
+            vec![Multiaddr::empty()
+                .with(Protocol::Ip4(Ipv4Addr::new(192, 168, 1, 5)))
+                .with(Protocol::Tcp(8888))
+                .with(Protocol::P2p(Multihash::from(peer)))]
+            .into_iter(),
+        );
+
+        assert!(manager.dial(peer).await.is_ok());
+        assert!(!manager.pending_connections.is_empty());
+
+        {
+            let peers = manager.peers.read();
+
+            match peers.get(&peer) {
+                Some(PeerContext {
+                    state: PeerState::Opening { .. },
+                    ..
+                }) => {}
+                state => panic!("invalid state for peer: {state:?}"),
+            }
+        }
+
+        match manager.next().await.unwrap() {
+            TransportEvent::ConnectionEstablished {
+                peer: event_peer,
+                endpoint: event_endpoint,
+                ..
+            } => {
+                assert_eq!(peer, event_peer);
+                assert_eq!(
+                    event_endpoint,
+                    Endpoint::listener(dial_address.clone(), connection_id),
+                );
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+        assert!(manager.pending_connections.is_empty());
+
+        let peers = manager.peers.read();
+        match peers.get(&peer).unwrap() {
+            PeerContext {
+                state:
+                    PeerState::Connected {
+                        record,
+                        dial_record,
+                    },
+                secondary_connection,
+                addresses,
+                ..
+            } => {
+                assert!(!addresses.contains(record.address()));
+                assert!(dial_record.is_none());
+                assert!(secondary_connection.is_none());
+                assert_eq!(record.address(), &dial_address);
+                assert_eq!(record.connection_id(), &Some(connection_id));
+            }
+            state => panic!("invalid peer state: {state:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn inbound_connection_for_same_address_while_dialing() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let peer = PeerId::random();
+        let dial_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(peer.to_multihash()));
+
         let connection_id = ConnectionId::random();
         let transport = Box::new({
             let mut transport = DummyTransport::new();
             transport.inject_event(TransportEvent::ConnectionEstablished {
                 peer,
-                endpoint: Endpoint::listener(dial_address.clone(), connection_id),
+                endpoint: Endpoint::listener(dial_address.clone(), connection_id),
+                transport: SupportedTransport::Tcp,
+            });
+            transport
+        });
+        manager.register_transport(SupportedTransport::Tcp, transport);
+        manager.add_known_address(
+            peer,
+            vec![Multiaddr::empty()
+                .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+                .with(Protocol::Tcp(8888))
+                .with(Protocol::P2p(Multihash::from(peer)))]
+            .into_iter(),
+        );
+
+        assert!(manager.dial(peer).await.is_ok());
+        assert!(!manager.pending_connections.is_empty());
+
+        {
+            let peers = manager.peers.read();
+
+            match peers.get(&peer) {
+                Some(PeerContext {
+                    state: PeerState::Opening { .. },
+                    ..
+                }) => {}
+                state => panic!("invalid state for peer: {state:?}"),
+            }
+        }
+
+        match manager.next().await.unwrap() {
+            TransportEvent::ConnectionEstablished {
+                peer: event_peer,
+                endpoint: event_endpoint,
+                ..
+            } => {
+                assert_eq!(peer, event_peer);
+                assert_eq!(
+                    event_endpoint,
+                    Endpoint::listener(dial_address.clone(), connection_id),
+                );
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+        assert!(manager.pending_connections.is_empty());
+
+        let peers = manager.peers.read();
+        match peers.get(&peer).unwrap() {
+            PeerContext {
+                state:
+                    PeerState::Connected {
+                        record,
+                        dial_record,
+                    },
+                secondary_connection,
+                addresses,
+                ..
+            } => {
+                assert!(addresses.is_empty());
+                assert!(dial_record.is_none());
+                assert!(secondary_connection.is_none());
+                assert_eq!(record.address(), &dial_address);
+                assert_eq!(record.connection_id(), &Some(connection_id));
+            }
+            state => panic!("invalid peer state: {state:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn dial_rejected_when_connection_limit_reached() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.set_max_connections(Some(1usize));
+
+        let peer = PeerId::random();
+        let dial_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        let connection_id = ConnectionId::from(0usize);
+
+        let transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer,
+                endpoint: Endpoint::dialer(dial_address.clone(), connection_id),
+                transport: SupportedTransport::Tcp,
+            });
+            transport
+        });
+        manager.register_transport(SupportedTransport::Tcp, transport);
+
+        assert!(manager.dial_address(dial_address).await.is_ok());
+        assert!(std::matches!(
+            manager.next().await,
+            Some(TransportEvent::ConnectionEstablished { .. })
+        ));
+
+        let other_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8889))
+            .with(Protocol::P2p(Multihash::from(PeerId::random())));
+
+        assert!(std::matches!(
+            manager.dial_address(other_address.clone()).await,
+            Err(Error::ConnectionLimitReached)
+        ));
+
+        // closing the connection frees up a slot
+        manager
+            .event_tx
+            .send(TransportManagerEvent::ConnectionClosed {
+                peer,
+                connection: connection_id,
+            })
+            .await
+            .unwrap();
+        assert!(std::matches!(
+            manager.next().await,
+            Some(TransportEvent::ConnectionClosed { .. })
+        ));
+
+        assert!(manager.dial_address(other_address).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn pending_dials_count_towards_connection_limit() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.set_max_connections(Some(1usize));
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let first_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(PeerId::random())));
+        let second_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8889))
+            .with(Protocol::P2p(Multihash::from(PeerId::random())));
+
+        // the first dial hasn't concluded yet but it still takes up the only slot
+        assert!(manager.dial_address(first_address).await.is_ok());
+        assert!(std::matches!(
+            manager.dial_address(second_address).await,
+            Err(Error::ConnectionLimitReached)
+        ));
+    }
+
+    #[tokio::test]
+    async fn inbound_connection_rejected_when_connection_limit_reached() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.set_max_connections(Some(1usize));
+
+        let transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: PeerId::random(),
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8888".parse().unwrap(),
+                    ConnectionId::from(0usize),
+                ),
+                transport: SupportedTransport::Tcp,
+            });
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: PeerId::random(),
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8889".parse().unwrap(),
+                    ConnectionId::from(1usize),
+                ),
+                transport: SupportedTransport::Tcp,
+            });
+            transport
+        });
+        manager.register_transport(SupportedTransport::Tcp, transport);
+
+        assert!(std::matches!(
+            manager.next().await,
+            Some(TransportEvent::ConnectionEstablished { .. })
+        ));
+
+        // second inbound connection is rejected and not reported
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_secs(1), manager.next())
+                .await
+                .is_err()
+        );
+        assert_eq!(manager.connections.len(), 1usize);
+        assert_eq!(manager.peers.read().len(), 1usize);
+    }
+
+    /// DNS resolver which counts its lookups and resolves every host to the same address.
+    struct CountingResolver(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl DnsResolver for CountingResolver {
+        async fn lookup_ip(&self, host: &str) -> crate::Result<Vec<IpAddr>> {
+            self.lookup_ip_with_ttl(host).await.map(|(addresses, _)| addresses)
+        }
+
+        async fn lookup_ip_with_ttl(&self, _host: &str) -> crate::Result<(Vec<IpAddr>, Duration)> {
+            self.0.fetch_add(1usize, Ordering::Relaxed);
+
+            Ok((
+                vec![IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4))],
+                Duration::from_secs(60),
+            ))
+        }
+
+        async fn lookup_txt(&self, _name: &str) -> crate::Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn transports_share_dns_resolver() {
+        let lookups = Arc::new(AtomicUsize::new(0usize));
+        let resolver = CachingResolver::new(
+            Arc::new(CountingResolver(Arc::clone(&lookups))),
+            DNS_CACHE_SIZE,
+        );
+        let (manager, _handle) = TransportManager::with_resolver(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+            Arc::new(resolver),
+        );
+        let handle1 = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        let handle2 = manager.transport_handle(Arc::new(DefaultExecutor {}));
+
+        assert!(Arc::ptr_eq(&handle1.resolver, &handle2.resolver));
+        assert!(Arc::ptr_eq(&handle1.resolver, &manager.resolver));
+
+        // the second transport resolves the host within the TTL and hits the cache
+        let first = handle1.resolver.lookup_ip("example.com").await.unwrap();
+        let second = handle2.resolver.lookup_ip("example.com").await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(lookups.load(Ordering::Relaxed), 1usize);
+    }
+
+    /// DNS resolver which returns the same TXT records for every name.
+    struct TxtResolver(Vec<String>);
+
+    #[async_trait::async_trait]
+    impl DnsResolver for TxtResolver {
+        async fn lookup_ip(&self, _host: &str) -> crate::Result<Vec<std::net::IpAddr>> {
+            Err(Error::DnsAddressResolutionFailed)
+        }
+
+        async fn lookup_txt(&self, _name: &str) -> crate::Result<Vec<String>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn dnsaddr_resolved_and_dialed() {
+        let peer = PeerId::random();
+        let other = PeerId::random();
+        let resolver = TxtResolver(vec![
+            format!("dnsaddr=/ip4/1.1.1.1/tcp/8888/p2p/{peer}"),
+            format!("dnsaddr=/ip4/2.2.2.2/tcp/8888/p2p/{other}"),
+            "dnsaddr=/ip4/3.3.3.3/tcp/8888".to_string(),
+            format!("dnsaddr=/dnsaddr/nested.example.com/p2p/{peer}"),
+            "unrelated record".to_string(),
+        ]);
+
+        let (mut manager, _handle) = TransportManager::with_resolver(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+            Arc::new(resolver),
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let address: Multiaddr =
+            format!("/dnsaddr/bootstrap.example.com/p2p/{peer}").parse().unwrap();
+        assert!(manager.dial_address(address).await.is_ok());
+
+        let resolution = manager.pending_dns_resolves.next().await.unwrap();
+        assert_eq!(resolution.peer, peer);
+        assert!(manager.on_dnsaddr_resolved(resolution).await.is_none());
+
+        let peers = manager.peers.read();
+        match peers.get(&peer) {
+            Some(PeerContext {
+                state: PeerState::Opening { records, .. },
+                ..
+            }) => {
+                let dialed = records.keys().cloned().collect::<HashSet<_>>();
+                let expected = HashSet::from_iter([
+                    format!("/ip4/1.1.1.1/tcp/8888/p2p/{peer}").parse().unwrap(),
+                    format!("/ip4/3.3.3.3/tcp/8888/p2p/{peer}").parse().unwrap(),
+                ]);
+
+                assert_eq!(dialed, expected);
+            }
+            state => panic!("invalid state for peer: {state:?}"),
+        }
+        assert!(peers.get(&other).is_none());
+    }
+
+    #[tokio::test]
+    async fn dnsaddr_dial_times_out_while_resolving() {
+        /// DNS resolver which never resolves TXT records.
+        struct PendingResolver;
+
+        #[async_trait::async_trait]
+        impl DnsResolver for PendingResolver {
+            async fn lookup_ip(&self, _host: &str) -> crate::Result<Vec<std::net::IpAddr>> {
+                Err(Error::DnsAddressResolutionFailed)
+            }
+
+            async fn lookup_txt(&self, _name: &str) -> crate::Result<Vec<String>> {
+                std::future::pending().await
+            }
+        }
+
+        let (mut manager, _handle) = TransportManager::with_resolver(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+            Arc::new(PendingResolver),
+        );
+        manager.set_dial_timeout(Some(std::time::Duration::from_millis(500)));
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let address: Multiaddr =
+            format!("/dnsaddr/bootstrap.example.com/p2p/{peer}").parse().unwrap();
+        manager.dial_address(address.clone()).await.unwrap();
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+
+        match tokio::time::timeout(std::time::Duration::from_secs(5), manager.next()).await {
+            Ok(Some(TransportEvent::DialFailure {
+                connection_id: failed_connection_id,
+                address: failed_address,
+                error: Error::DialTimeout,
+            })) => {
+                assert_eq!(failed_connection_id, connection_id);
+                assert_eq!(failed_address, address);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        assert!(manager.pending_connections.is_empty());
+        assert!(std::matches!(
+            manager.peers.read().get(&peer).unwrap().state,
+            PeerState::Disconnected { dial_record: None }
+        ));
+    }
+
+    #[tokio::test]
+    async fn dnsaddr_peer_dialing_while_resolving() {
+        let peer = PeerId::random();
+        let resolver = TxtResolver(vec![format!("dnsaddr=/ip4/1.1.1.1/tcp/8888/p2p/{peer}")]);
+
+        let (mut manager, _handle) = TransportManager::with_resolver(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+            Arc::new(resolver),
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let address: Multiaddr =
+            format!("/dnsaddr/bootstrap.example.com/p2p/{peer}").parse().unwrap();
+        assert!(manager.dial_address(address.clone()).await.is_ok());
+
+        // the peer is dialing while the lookup is pending and further dials are no-ops
+        match &manager.peers.read().get(&peer).unwrap().state {
+            PeerState::Dialing { record } => assert_eq!(record.address(), &address),
+            state => panic!("invalid state for peer: {state:?}"),
+        }
+        assert!(manager.dial_address(address).await.is_ok());
+        assert!(manager.dial(peer).await.is_ok());
+        assert_eq!(manager.pending_dns_resolves.len(), 1);
+        assert_eq!(manager.pending_connections.len(), 1);
+
+        // once resolved, the lookup is cleared and the resolved address is dialed
+        let resolution = manager.pending_dns_resolves.next().await.unwrap();
+        assert!(manager.pending_connections.contains_key(&resolution.connection_id));
+        assert!(manager.on_dnsaddr_resolved(resolution).await.is_none());
+
+        match &manager.peers.read().get(&peer).unwrap().state {
+            PeerState::Opening { records, .. } => assert!(records.contains_key(
+                &format!("/ip4/1.1.1.1/tcp/8888/p2p/{peer}").parse::<Multiaddr>().unwrap()
+            )),
+            state => panic!("invalid state for peer: {state:?}"),
+        }
+        assert_eq!(manager.pending_connections.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dnsaddr_with_unsupported_transport_reports_dial_failure() {
+        let peer = PeerId::random();
+        let resolver = TxtResolver(vec![format!(
+            "dnsaddr=/ip4/1.1.1.1/udp/8888/quic-v1/p2p/{peer}"
+        )]);
+
+        let (mut manager, _handle) = TransportManager::with_resolver(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+            Arc::new(resolver),
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let mut service = manager.register_protocol(
+            ProtocolName::from("/notif/1"),
+            Vec::new(),
+            ProtocolCodec::UnsignedVarint(None),
+        );
+
+        let address: Multiaddr =
+            format!("/dnsaddr/bootstrap.example.com/p2p/{peer}").parse().unwrap();
+        assert!(manager.dial_address(address.clone()).await.is_ok());
+
+        match manager.next().await {
+            Some(TransportEvent::DialFailure {
+                address: failed_address,
+                ..
+            }) => assert_eq!(failed_address, address),
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        match service.next().await {
+            Some(crate::protocol::TransportEvent::DialFailure {
+                peer: failed_peer,
+                address: failed_address,
+            }) => {
+                assert_eq!(failed_peer, peer);
+                assert_eq!(failed_address, address);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn peer_id_preserved_for_dns_address() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let address: Multiaddr = format!("/dns4/example.com/tcp/8888/p2p/{peer}").parse().unwrap();
+        assert!(manager.dial_address(address.clone()).await.is_ok());
+
+        let peers = manager.peers.read();
+        match peers.get(&peer) {
+            Some(PeerContext {
+                state: PeerState::Dialing { record },
+                ..
+            }) => assert_eq!(record.address(), &address),
+            state => panic!("invalid state for peer: {state:?}"),
+        }
+        assert_eq!(
+            manager.pending_connections.values().collect::<Vec<_>>(),
+            vec![&peer]
+        );
+    }
+
+    #[tokio::test]
+    async fn dial_address_invalid_peer_id() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        // sha2-512 multihash is not a valid `PeerId`
+        let address = Multiaddr::empty()
+            .with(Protocol::Dns4(std::borrow::Cow::Borrowed("example.com")))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::wrap(0x13, &[0u8; 64]).unwrap()));
+
+        assert!(std::matches!(
+            manager.dial_address(address).await,
+            Err(Error::AddressError(AddressError::InvalidPeerId))
+        ));
+        assert!(manager.peers.read().is_empty());
+        assert!(manager.pending_connections.is_empty());
+    }
+
+    #[tokio::test]
+    async fn peer_id_mismatch_reported_as_dial_failure() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        let dialed_peer = PeerId::random();
+        let actual_peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888));
+        let connection_id = ConnectionId::from(0usize);
+
+        let transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: actual_peer,
+                endpoint: Endpoint::dialer(address.clone(), connection_id),
+                transport: SupportedTransport::Tcp,
+            });
+            transport
+        });
+        manager.register_transport(SupportedTransport::Tcp, transport);
+
+        let dial_address = address.clone().with(Protocol::P2p(Multihash::from(dialed_peer)));
+        assert!(manager.dial_address(dial_address.clone()).await.is_ok());
+
+        match manager.next().await {
+            Some(TransportEvent::DialFailure {
+                connection_id: failed_connection_id,
+                address,
+                error: Error::PeerIdMismatch(dialed, actual),
+            }) => {
+                assert_eq!(failed_connection_id, connection_id);
+                assert_eq!(address, dial_address);
+                assert_eq!(dialed, dialed_peer);
+                assert_eq!(actual, actual_peer);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        assert!(manager.pending_connections.is_empty());
+        assert!(manager.connections.is_empty());
+
+        let peers = manager.peers.read();
+        assert!(!peers.contains_key(&actual_peer));
+        match peers.get(&dialed_peer) {
+            Some(PeerContext {
+                state: PeerState::Disconnected { dial_record: None },
+                addresses,
+                ..
+            }) => {
+                assert!(addresses.contains(&dial_address));
+            }
+            state => panic!("invalid state for peer: {state:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn dial_banned_peer() {
+        let (mut manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        manager.add_known_address(peer, std::iter::once(address.clone()));
+        manager.ban_peer(peer, None);
+
+        assert!(std::matches!(
+            manager.dial(peer).await,
+            Err(Error::PeerBanned(banned)) if banned == peer
+        ));
+        assert!(std::matches!(
+            manager.dial_address(address.clone()).await,
+            Err(Error::PeerBanned(banned)) if banned == peer
+        ));
+        assert!(std::matches!(handle.dial(&peer), Err(Error::PeerBanned(_))));
+        assert!(std::matches!(
+            handle.dial_address(address.clone()),
+            Err(Error::PeerBanned(_))
+        ));
+        assert!(manager.pending_connections.is_empty());
+
+        manager.unban_peer(peer);
+        assert!(manager.dial_address(address).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn temporary_ban_expires() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        manager.ban_peer(
+            peer,
+            Some(Instant::now() + std::time::Duration::from_millis(100)),
+        );
+
+        assert!(std::matches!(
+            manager.dial_address(address.clone()).await,
+            Err(Error::PeerBanned(_))
+        ));
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        assert!(manager.dial_address(address).await.is_ok());
+        assert!(manager.peers.read().get(&peer).unwrap().pending_ban.is_none());
+    }
+
+    #[tokio::test]
+    async fn expired_ban_of_unknown_peer_removed() {
+        let (mut manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        let peer = PeerId::random();
+        manager.ban_peer(
+            peer,
+            Some(Instant::now() + std::time::Duration::from_millis(100)),
+        );
+        assert!(handle.is_banned(&peer));
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        assert!(!handle.is_banned(&peer));
+        assert!(manager.peers.read().get(&peer).is_none());
+    }
+
+    #[tokio::test]
+    async fn banning_unknown_peer_prunes_expired_bans() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        // known peer is kept after its ban expires
+        let known_peer = PeerId::random();
+        manager.add_known_address(
+            known_peer,
+            std::iter::once(
+                Multiaddr::empty()
+                    .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+                    .with(Protocol::Tcp(8888))
+                    .with(Protocol::P2p(Multihash::from(known_peer))),
+            ),
+        );
+
+        let expired = Some(Instant::now() + std::time::Duration::from_millis(50));
+        let unknown_peer = PeerId::random();
+        manager.ban_peer(known_peer, expired);
+        manager.ban_peer(unknown_peer, expired);
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let banned_peer = PeerId::random();
+        manager.ban_peer(banned_peer, None);
+
+        let peers = manager.peers.read();
+        assert!(peers.get(&unknown_peer).is_none());
+        assert!(peers.get(&known_peer).is_some());
+        assert!(std::matches!(
+            peers.get(&banned_peer),
+            Some(PeerContext {
+                state: PeerState::Banned { until: None },
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn inbound_connection_from_banned_peer_rejected() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        let banned_peer = PeerId::random();
+        let peer = PeerId::random();
+        manager.ban_peer(banned_peer, None);
+
+        let transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: banned_peer,
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8888".parse().unwrap(),
+                    ConnectionId::from(0usize),
+                ),
+                transport: SupportedTransport::Tcp,
+            });
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer,
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8889".parse().unwrap(),
+                    ConnectionId::from(1usize),
+                ),
+                transport: SupportedTransport::Tcp,
+            });
+            transport
+        });
+        manager.register_transport(SupportedTransport::Tcp, transport);
+
+        // connection from the banned peer is rejected and not reported
+        match manager.next().await {
+            Some(TransportEvent::ConnectionEstablished {
+                peer: connected, ..
+            }) => assert_eq!(connected, peer),
+            event => panic!("invalid event: {event:?}"),
+        }
+        assert!(std::matches!(
+            manager.peers.read().get(&banned_peer),
+            Some(PeerContext {
+                state: PeerState::Banned { until: None },
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn banned_peer_moved_to_disconnected_after_ban_expires() {
+        let (mut manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        manager.add_known_address(peer, std::iter::once(address.clone()));
+        manager.ban_peer(
+            peer,
+            Some(Instant::now() + std::time::Duration::from_millis(100)),
+        );
+
+        assert!(std::matches!(
+            manager.peers.read().get(&peer),
+            Some(PeerContext {
+                state: PeerState::Banned { until: Some(_) },
+                ..
+            })
+        ));
+        assert_eq!(manager.metrics().peers.banned, 1usize);
+        assert!(std::matches!(
+            manager.dial(peer).await,
+            Err(Error::PeerBanned(banned)) if banned == peer
+        ));
+        assert!(std::matches!(handle.dial(&peer), Err(Error::PeerBanned(_))));
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        assert!(!handle.is_banned(&peer));
+        assert!(std::matches!(
+            manager.peers.read().get(&peer),
+            Some(PeerContext {
+                state: PeerState::Disconnected { dial_record: None },
+                ..
+            })
+        ));
+        assert!(manager.dial(peer).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn ban_applied_after_connected_peer_disconnects() {
+        let (mut manager, handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let connection_id = ConnectionId::from(0usize);
+        manager
+            .on_connection_established(
+                peer,
+                &Endpoint::listener("/ip4/127.0.0.1/tcp/8888".parse().unwrap(), connection_id),
+            )
+            .unwrap();
+        manager.ban_peer(peer, None);
+
+        // the peer stays connected until the connection is closed
+        assert!(handle.is_banned(&peer));
+        assert!(std::matches!(
+            manager.peers.read().get(&peer),
+            Some(PeerContext {
+                state: PeerState::Connected { .. },
+                pending_ban: Some(None),
+                ..
+            })
+        ));
+
+        manager.on_connection_closed(peer, connection_id).unwrap();
+
+        assert!(handle.is_banned(&peer));
+        assert!(std::matches!(
+            manager.peers.read().get(&peer),
+            Some(PeerContext {
+                state: PeerState::Banned { until: None },
+                pending_ban: None,
+                ..
+            })
+        ));
+
+        manager.unban_peer(peer);
+        assert!(!handle.is_banned(&peer));
+        assert!(std::matches!(
+            manager.peers.read().get(&peer),
+            Some(PeerContext {
+                state: PeerState::Disconnected { dial_record: None },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn listen_addresses_returned() {
+        let keypair = Keypair::generate();
+        let local_peer_id = PeerId::from_public_key(&keypair.public().into());
+        let (mut manager, handle) =
+            TransportManager::new(keypair, HashSet::new(), BandwidthSink::new(), 8usize);
+
+        // each registered address is returned both as is and with the local `PeerId` appended
+        let address1: Multiaddr = "/ip4/127.0.0.1/tcp/8888".parse().unwrap();
+        let address2: Multiaddr = "/ip6/::1/udp/8888/quic-v1".parse().unwrap();
+        manager.register_listen_address(address1.clone());
+        manager.register_listen_address(address2.clone());
+
+        let expected = HashSet::from_iter([
+            address1.clone(),
+            address1.with(Protocol::P2p(Multihash::from(local_peer_id))),
+            address2.clone(),
+            address2.with(Protocol::P2p(Multihash::from(local_peer_id))),
+        ]);
+
+        assert_eq!(handle.listen_addresses().len(), 4usize);
+        assert_eq!(
+            HashSet::<Multiaddr>::from_iter(handle.listen_addresses()),
+            expected
+        );
+    }
+
+    /// Transport which fails every dial and open.
+    #[derive(Default)]
+    struct FailingTransport {
+        events: std::collections::VecDeque<TransportEvent>,
+    }
+
+    impl Stream for FailingTransport {
+        type Item = TransportEvent;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            match self.events.pop_front() {
+                Some(event) => Poll::Ready(Some(event)),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    impl Transport for FailingTransport {
+        fn dial(&mut self, connection_id: ConnectionId, address: Multiaddr) -> crate::Result<()> {
+            self.events.push_back(TransportEvent::DialFailure {
+                connection_id,
+                address,
+                error: Error::Unknown,
+            });
+
+            Ok(())
+        }
+
+        fn accept(&mut self, _: ConnectionId) -> crate::Result<()> {
+            Ok(())
+        }
+
+        fn reject(&mut self, _: ConnectionId) -> crate::Result<()> {
+            Ok(())
+        }
+
+        fn open(
+            &mut self,
+            connection_id: ConnectionId,
+            addresses: Vec<Multiaddr>,
+        ) -> crate::Result<()> {
+            self.events.push_back(TransportEvent::OpenFailure {
+                connection_id,
+                errors: addresses
+                    .into_iter()
+                    .map(|address| (address, Error::Dial(DialError::Refused)))
+                    .collect(),
+            });
+
+            Ok(())
+        }
+
+        fn negotiate(&mut self, _: ConnectionId) -> crate::Result<()> {
+            Ok(())
+        }
+
+        fn cancel(&mut self, _: ConnectionId) {}
+    }
+
+    #[tokio::test]
+    async fn dialing_protocol_notified_about_dial_failure() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(
+            SupportedTransport::Tcp,
+            Box::new(FailingTransport::default()),
+        );
+
+        let mut service = manager.register_protocol(
+            ProtocolName::from("/notif/1"),
+            Vec::new(),
+            ProtocolCodec::UnsignedVarint(None),
+        );
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        service.dial_address(address.clone()).unwrap();
+
+        match manager.next().await {
+            Some(TransportEvent::DialFailure {
+                address: failed_address,
+                ..
+            }) => assert_eq!(failed_address, address),
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        match service.next().await {
+            Some(crate::protocol::TransportEvent::DialFailure {
+                peer: failed_peer,
+                address: failed_address,
+            }) => {
+                assert_eq!(failed_peer, peer);
+                assert_eq!(failed_address, address);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn open_failure_reported_for_each_dialed_address() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(
+            SupportedTransport::Tcp,
+            Box::new(FailingTransport::default()),
+        );
+
+        let mut service = manager.register_protocol(
+            ProtocolName::from("/notif/1"),
+            Vec::new(),
+            ProtocolCodec::UnsignedVarint(None),
+        );
+
+        let peer = PeerId::random();
+        let addresses = HashSet::<Multiaddr>::from_iter([8888, 8889].into_iter().map(|port| {
+            Multiaddr::empty()
+                .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+                .with(Protocol::Tcp(port))
+                .with(Protocol::P2p(Multihash::from(peer)))
+        }));
+        manager.add_known_address(peer, addresses.clone().into_iter());
+        manager.dial(peer).await.unwrap();
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+
+        let mut failed = HashSet::new();
+        for _ in 0..2 {
+            match manager.next().await {
+                Some(TransportEvent::DialFailure {
+                    connection_id: failed_connection_id,
+                    address,
+                    error: Error::Dial(DialError::Refused),
+                }) => {
+                    assert_eq!(failed_connection_id, connection_id);
+                    failed.insert(address);
+                }
+                event => panic!("invalid event: {event:?}"),
+            }
+        }
+        assert_eq!(failed, addresses);
+
+        let mut reported = HashSet::new();
+        for _ in 0..2 {
+            match service.next().await {
+                Some(crate::protocol::TransportEvent::DialFailure {
+                    peer: failed_peer,
+                    address,
+                }) => {
+                    assert_eq!(failed_peer, peer);
+                    reported.insert(address);
+                }
+                event => panic!("invalid event: {event:?}"),
+            }
+        }
+        assert_eq!(reported, addresses);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_peer_is_backed_off() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(
+            SupportedTransport::Tcp,
+            Box::new(FailingTransport::default()),
+        );
+        manager.set_dial_backoff(Some(DialBackoffConfig {
+            base_delay: Duration::from_millis(200),
+            multiplier: 2.0,
+            jitter: 0.1,
+            max_delay: Duration::from_secs(10),
+        }));
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+
+        manager.dial_address(address.clone()).await.unwrap();
+        assert!(matches!(
+            manager.next().await,
+            Some(TransportEvent::DialFailure { .. })
+        ));
+
+        let retry_after = match manager.dial_address(address.clone()).await {
+            Err(Error::DialBackoff { retry_after }) => retry_after,
+            result => panic!("invalid result: {result:?}"),
+        };
+        assert!(retry_after >= Duration::from_millis(200));
+        assert!(retry_after <= Duration::from_millis(220));
+        assert!(matches!(
+            manager.dial(peer).await,
+            Err(Error::DialBackoff { .. })
+        ));
+
+        // redial once the backoff has elapsed and fail again
+        tokio::time::advance(retry_after).await;
+        manager.dial_address(address.clone()).await.unwrap();
+        assert!(matches!(
+            manager.next().await,
+            Some(TransportEvent::DialFailure { .. })
+        ));
+
+        // the second failure doubles the backoff
+        let retry_after = match manager.dial_address(address).await {
+            Err(Error::DialBackoff { retry_after }) => retry_after,
+            result => panic!("invalid result: {result:?}"),
+        };
+        assert!(retry_after >= Duration::from_millis(400));
+        assert!(retry_after <= Duration::from_millis(440));
+    }
+
+    #[tokio::test]
+    async fn connected_peers_reflect_established_connections() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::from_iter([SupportedTransport::Tcp]),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let service = manager.register_protocol(
+            ProtocolName::from("/notif/1"),
+            Vec::new(),
+            ProtocolCodec::UnsignedVarint(None),
+        );
+        assert!(service.connected_peers().is_empty());
+
+        let peer1 = PeerId::random();
+        let peer2 = PeerId::random();
+        let transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: peer1,
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8888".parse().unwrap(),
+                    ConnectionId::from(0usize),
+                ),
+                transport: SupportedTransport::Tcp,
+            });
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: peer2,
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8889".parse().unwrap(),
+                    ConnectionId::from(1usize),
+                ),
+                transport: SupportedTransport::Tcp,
+            });
+            transport
+        });
+        manager.register_transport(SupportedTransport::Tcp, transport);
+
+        // peer with a known address but no connection
+        let peer = PeerId::random();
+        manager.add_known_address(
+            peer,
+            std::iter::once(
+                Multiaddr::empty()
+                    .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+                    .with(Protocol::Tcp(9999))
+                    .with(Protocol::P2p(Multihash::from(peer))),
+            ),
+        );
+
+        for _ in 0..2 {
+            assert!(std::matches!(
+                manager.next().await,
+                Some(TransportEvent::ConnectionEstablished { .. })
+            ));
+        }
+        assert_eq!(
+            service.connected_peers().into_iter().collect::<HashSet<_>>(),
+            HashSet::from_iter([peer1, peer2]),
+        );
+
+        manager
+            .event_tx
+            .send(TransportManagerEvent::ConnectionClosed {
+                peer: peer1,
+                connection: ConnectionId::from(0usize),
+            })
+            .await
+            .unwrap();
+        assert!(std::matches!(
+            manager.next().await,
+            Some(TransportEvent::ConnectionClosed { .. })
+        ));
+        assert_eq!(service.connected_peers(), vec![peer2]);
+    }
+
+    #[tokio::test]
+    async fn metrics_reflect_established_connections() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::from_iter([SupportedTransport::Tcp, SupportedTransport::WebSocket]),
+            BandwidthSink::new(),
+            8usize,
+        );
+        assert_eq!(manager.metrics(), Metrics::default());
+
+        let tcp_transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: PeerId::random(),
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8888".parse().unwrap(),
+                    ConnectionId::from(0usize),
+                ),
+                transport: SupportedTransport::Tcp,
+            });
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: PeerId::random(),
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8889".parse().unwrap(),
+                    ConnectionId::from(1usize),
+                ),
+                transport: SupportedTransport::Tcp,
+            });
+            transport
+        });
+        let websocket_transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: PeerId::random(),
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8890/ws".parse().unwrap(),
+                    ConnectionId::from(2usize),
+                ),
+                transport: SupportedTransport::WebSocket,
+            });
+            transport
+        });
+        manager.register_transport(SupportedTransport::Tcp, tcp_transport);
+        manager.register_transport(SupportedTransport::WebSocket, websocket_transport);
+
+        for _ in 0..3 {
+            assert!(std::matches!(
+                manager.next().await,
+                Some(TransportEvent::ConnectionEstablished { .. })
+            ));
+        }
+
+        // peer with a known address but no connection
+        let peer = PeerId::random();
+        manager.add_known_address(
+            peer,
+            std::iter::once(
+                Multiaddr::empty()
+                    .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+                    .with(Protocol::Tcp(9999))
+                    .with(Protocol::P2p(Multihash::from(peer))),
+            ),
+        );
+
+        assert_eq!(
+            manager.metrics(),
+            Metrics {
+                established_connections: 3usize,
+                pending_connections: 0usize,
+                pending_dns_resolves: 0usize,
+                peers: PeerStateCounts {
+                    connected: 3usize,
+                    disconnected: 1usize,
+                    ..Default::default()
+                },
+                connections_per_transport: HashMap::from_iter([
+                    (SupportedTransport::Tcp, 2usize),
+                    (SupportedTransport::WebSocket, 1usize),
+                ]),
+            }
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "metrics")]
+    async fn prometheus_counters_advance() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let registry = prometheus::Registry::new();
+        manager.register_metrics(&registry).unwrap();
+
+        let connected_peer = PeerId::random();
+        let connected_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888));
+        let failed_peer = PeerId::random();
+        let failed_address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8889))
+            .with(Protocol::P2p(Multihash::from(failed_peer)));
+
+        let transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer: connected_peer,
+                endpoint: Endpoint::dialer(connected_address.clone(), ConnectionId::from(0usize)),
+                transport: SupportedTransport::Tcp,
+            });
+            transport.inject_event(TransportEvent::DialFailure {
+                connection_id: ConnectionId::from(1usize),
+                address: failed_address.clone(),
+                error: Error::Unknown,
+            });
+            transport
+        });
+        manager.register_transport(SupportedTransport::Tcp, transport);
+
+        manager
+            .dial_address(connected_address.with(Protocol::P2p(Multihash::from(connected_peer))))
+            .await
+            .unwrap();
+        manager.dial_address(failed_address).await.unwrap();
+
+        assert!(std::matches!(
+            manager.next().await,
+            Some(TransportEvent::ConnectionEstablished { .. })
+        ));
+        assert!(std::matches!(
+            manager.next().await,
+            Some(TransportEvent::DialFailure { .. })
+        ));
+
+        manager
+            .event_tx
+            .send(TransportManagerEvent::ConnectionClosed {
+                peer: connected_peer,
+                connection: ConnectionId::from(0usize),
+            })
+            .await
+            .unwrap();
+        assert!(std::matches!(
+            manager.next().await,
+            Some(TransportEvent::ConnectionClosed { .. })
+        ));
+
+        let metrics = manager.metrics.as_ref().unwrap();
+        assert_eq!(metrics.dials_attempted.get(), 2);
+        assert_eq!(metrics.dials_succeeded.get(), 1);
+        assert_eq!(metrics.dials_failed.get(), 1);
+        assert_eq!(metrics.connections_established.get(), 1);
+        assert_eq!(metrics.connections_closed.get(), 1);
+        assert_eq!(metrics.dns_resolutions.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn failing_address_removed() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+        manager.set_min_address_score(Some(2 * SCORE_CONNECT_FAILURE));
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        assert_eq!(
+            manager.add_known_address(peer, std::iter::once(address.clone())),
+            1usize
+        );
+
+        // first failure lowers the score of the address but keeps it
+        manager.dial(peer).await.unwrap();
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+        assert_eq!(
+            manager
+                .on_open_failure(SupportedTransport::Tcp, connection_id, Vec::new())
+                .unwrap()
+                .map(|(peer, _)| peer),
+            Some(peer)
+        );
+        assert!(manager.peers.read().get(&peer).unwrap().addresses.contains(&address));
+
+        // second failure drops the score to the minimum and the address is removed
+        manager.dial(peer).await.unwrap();
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+        assert_eq!(
+            manager
+                .on_open_failure(SupportedTransport::Tcp, connection_id, Vec::new())
+                .unwrap()
+                .map(|(peer, _)| peer),
+            Some(peer)
+        );
+        assert!(!manager.peers.read().get(&peer).unwrap().addresses.contains(&address));
+
+        assert!(std::matches!(
+            manager.dial(peer).await,
+            Err(Error::NoAddressAvailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unused_address_expires() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+        manager.set_address_expiry(Some(Duration::from_millis(100)));
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        manager.add_known_address(peer, std::iter::once(address));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(std::matches!(
+            manager.dial(peer).await,
+            Err(Error::NoAddressAvailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn peer_tracked_after_addresses_expire() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+        manager.set_address_expiry(Some(Duration::from_millis(100)));
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        manager.add_known_address(peer, std::iter::once(address.clone()));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(std::matches!(
+            manager.dial(peer).await,
+            Err(Error::NoAddressAvailable(_))
+        ));
+
+        // the peer is still tracked after the failed dial
+        match manager.peers.read().get(&peer) {
+            Some(PeerContext {
+                state: PeerState::Disconnected { dial_record: None },
+                addresses,
+                pending_ban: None,
+                ..
+            }) => assert!(addresses.is_empty()),
+            context => panic!("invalid context for peer: {context:?}"),
+        }
+
+        // ban of the connected peer is kept in its context and applies once it disconnects
+        manager
+            .on_connection_established(
+                peer,
+                &Endpoint::listener(address, ConnectionId::from(0usize)),
+            )
+            .unwrap();
+        manager.ban_peer(peer, None);
+        assert_eq!(
+            manager.peers.read().get(&peer).unwrap().pending_ban,
+            Some(None)
+        );
+
+        manager.on_connection_closed(peer, ConnectionId::from(0usize)).unwrap();
+        assert!(std::matches!(
+            manager.dial(peer).await,
+            Err(Error::PeerBanned(_))
+        ));
+    }
+
+    #[test]
+    fn known_address_removed() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::from_iter([SupportedTransport::Tcp]),
+            BandwidthSink::new(),
+            8usize,
+        );
+
+        let peer = PeerId::random();
+        let address = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888));
+        manager.add_known_address(
+            peer,
+            std::iter::once(address.clone().with(Protocol::P2p(Multihash::from(peer)))),
+        );
+
+        // address without `PeerId` is matched against the stored address
+        assert!(manager.remove_known_address(peer, &address));
+        assert!(!manager.remove_known_address(peer, &address));
+        assert!(!manager.remove_known_address(PeerId::random(), &address));
+        assert!(manager.peers.read().get(&peer).unwrap().addresses.is_empty());
+    }
+
+    #[tokio::test]
+    async fn higher_scored_address_dialed_first() {
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            1usize,
+        );
+        manager.register_transport(SupportedTransport::Tcp, Box::new(DummyTransport::new()));
+
+        let peer = PeerId::random();
+        let address1 = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8888))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        let address2 = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(8889))
+            .with(Protocol::P2p(Multihash::from(peer)));
+        manager.add_known_address(peer, vec![address1, address2].into_iter());
+
+        let dialed_address = |manager: &TransportManager| -> Multiaddr {
+            match &manager.peers.read().get(&peer).unwrap().state {
+                PeerState::Opening { records, .. } => {
+                    assert_eq!(records.len(), 1usize);
+                    records.keys().next().unwrap().clone()
+                }
+                state => panic!("invalid state for peer: {state:?}"),
+            }
+        };
+
+        // first dial fails which lowers the score of the dialed address
+        manager.dial(peer).await.unwrap();
+        let failed_address = dialed_address(&manager);
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+        manager.on_open_failure(SupportedTransport::Tcp, connection_id, Vec::new()).unwrap();
+
+        // second dial picks the other address and succeeds which raises its score
+        manager.dial(peer).await.unwrap();
+        let working_address = dialed_address(&manager);
+        assert_ne!(working_address, failed_address);
+
+        let connection_id = *manager.pending_connections.keys().next().unwrap();
+        manager
+            .on_connection_opened(
+                SupportedTransport::Tcp,
+                connection_id,
+                working_address.clone(),
+            )
+            .unwrap();
+        manager
+            .on_connection_established(
+                peer,
+                &Endpoint::dialer(working_address.clone(), connection_id),
+            )
+            .unwrap();
+        manager.on_connection_closed(peer, connection_id).unwrap();
+
+        // the address which succeeded is dialed first
+        manager.dial(peer).await.unwrap();
+        assert_eq!(dialed_address(&manager), working_address);
+    }
+
+    /// Generate a `PeerId` which is ordered before or after `peer`.
+    ///
+    /// [`PeerId::random()`] isn't used because its encoding differs from that of a public key.
+    fn ordered_peer(peer: &PeerId, smaller: bool) -> PeerId {
+        loop {
+            let other = PeerId::from_public_key(&Keypair::generate().public().into());
+
+            if (other.to_bytes() < peer.to_bytes()) == smaller {
+                return other;
+            }
+        }
+    }
+
+    /// Register a transport which reports an inbound connection followed by an outbound
+    /// connection to `peer`, as happens when both nodes dial each other simultaneously.
+    fn simultaneous_connect(manager: &mut TransportManager, peer: PeerId) {
+        let transport = Box::new({
+            let mut transport = DummyTransport::new();
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer,
+                endpoint: Endpoint::listener(
+                    "/ip4/127.0.0.1/tcp/8888".parse().unwrap(),
+                    ConnectionId::from(0usize),
+                ),
+                transport: SupportedTransport::Tcp,
+            });
+            transport.inject_event(TransportEvent::ConnectionEstablished {
+                peer,
+                endpoint: Endpoint::dialer(
+                    "/ip4/127.0.0.1/tcp/9999".parse().unwrap(),
+                    ConnectionId::from(1usize),
+                ),
+                transport: SupportedTransport::Tcp,
             });
             transport
         });
         manager.register_transport(SupportedTransport::Tcp, transport);
-        manager.add_known_address(
-            peer,
-            vec![Multiaddr::empty()
-                .with(Protocol::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
-                .with(Protocol::Tcp(8888))
-                .with(Protocol::P2p(Multihash::from(peer)))]
-            .into_iter(),
-        );
+    }
 
-        assert!(manager.dial(peer).await.is_ok());
-        assert!(!manager.pending_connections.is_empty());
+    /// Register a protocol to `manager` which receives the events sent to `rx`.
+    fn register_protocol(manager: &mut TransportManager) -> Receiver<InnerTransportEvent> {
+        let (tx, rx) = channel(64);
+        manager.protocols.insert(
+            ProtocolName::from("/notif/1"),
+            ProtocolContext::new(ProtocolCodec::UnsignedVarint(None), tx, Vec::new()),
+        );
 
-        {
-            let peers = manager.peers.read();
+        rx
+    }
 
-            match peers.get(&peer) {
-                Some(PeerContext {
-                    state: PeerState::Opening { .. },
-                    ..
-                }) => {}
-                state => panic!("invalid state for peer: {state:?}"),
+    /// Verify that both connections of a simultaneous connect are established, `redundant` is
+    /// drained and exactly `kept` survives once `redundant` has been closed.
+    async fn verify_simultaneous_connect(
+        manager: &mut TransportManager,
+        rx: &mut Receiver<InnerTransportEvent>,
+        peer: PeerId,
+        kept: ConnectionId,
+        redundant: ConnectionId,
+    ) {
+        for connection_id in [ConnectionId::from(0usize), ConnectionId::from(1usize)] {
+            match manager.next().await {
+                Some(TransportEvent::ConnectionEstablished { endpoint, .. }) => {
+                    assert_eq!(endpoint.connection_id(), connection_id);
+                }
+                _ => panic!("invalid event"),
             }
         }
 
-        match manager.next().await.unwrap() {
-            TransportEvent::ConnectionEstablished {
-                peer: event_peer,
-                endpoint: event_endpoint,
+        // protocols are told to stop using the redundant connection instead of it being closed
+        match rx.try_recv() {
+            Ok(InnerTransportEvent::ConnectionRedundant {
+                peer: redundant_peer,
+                connection,
+            }) => {
+                assert_eq!(redundant_peer, peer);
+                assert_eq!(connection, redundant);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+        match manager.peers.read().get(&peer).unwrap() {
+            PeerContext {
+                state: PeerState::Connected { record, .. },
+                secondary_connection: None,
                 ..
             } => {
-                assert_eq!(peer, event_peer);
-                assert_eq!(
-                    event_endpoint,
-                    Endpoint::listener(dial_address.clone(), connection_id),
-                );
+                assert_eq!(record.connection_id(), &Some(kept));
             }
-            event => panic!("invalid event: {event:?}"),
+            state => panic!("invalid state: {state:?}"),
+        };
+
+        // the redundant connection is closed once it's been drained
+        manager
+            .event_tx
+            .send(TransportManagerEvent::ConnectionClosed {
+                peer,
+                connection: redundant,
+            })
+            .await
+            .unwrap();
+
+        match manager.next().await {
+            Some(TransportEvent::ConnectionClosed {
+                peer: closed_peer,
+                connection_id,
+            }) => {
+                assert_eq!(closed_peer, peer);
+                assert_eq!(connection_id, redundant);
+            }
+            _ => panic!("invalid event"),
         }
-        assert!(manager.pending_connections.is_empty());
 
-        let peers = manager.peers.read();
-        match peers.get(&peer).unwrap() {
+        // exactly one connection survives
+        assert_eq!(manager.connections.keys().collect::<Vec<_>>(), vec![&kept]);
+        assert!(manager.redundant_connections.is_empty());
+        match manager.peers.read().get(&peer).unwrap() {
             PeerContext {
-                state:
-                    PeerState::Connected {
-                        record,
-                        dial_record,
-                    },
-                secondary_connection,
-                addresses,
+                state: PeerState::Connected { record, .. },
+                secondary_connection: None,
+                ..
             } => {
-                assert!(addresses.is_empty());
-                assert!(dial_record.is_none());
-                assert!(secondary_connection.is_none());
-                assert_eq!(record.address(), &dial_address);
-                assert_eq!(record.connection_id(), &Some(connection_id));
+                assert_eq!(record.connection_id(), &Some(kept));
             }
-            state => panic!("invalid peer state: {state:?}"),
-        }
+            state => panic!("invalid state: {state:?}"),
+        };
+    }
+
+    #[tokio::test]
+    async fn simultaneous_connect_drains_replaced_connection() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::from_iter([SupportedTransport::Tcp]),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let mut rx = register_protocol(&mut manager);
+
+        // local node has the smaller peer ID so the connection it dialed replaces the inbound
+        // connection
+        let peer = ordered_peer(&manager.local_peer_id, false);
+        simultaneous_connect(&mut manager, peer);
+
+        verify_simultaneous_connect(
+            &mut manager,
+            &mut rx,
+            peer,
+            ConnectionId::from(1usize),
+            ConnectionId::from(0usize),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn simultaneous_connect_drains_redundant_connection() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::from_iter([SupportedTransport::Tcp]),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let mut rx = register_protocol(&mut manager);
+
+        // remote node has the smaller peer ID so the inbound connection is kept and the
+        // connection the local node dialed is drained
+        let peer = ordered_peer(&manager.local_peer_id, true);
+        simultaneous_connect(&mut manager, peer);
+
+        verify_simultaneous_connect(
+            &mut manager,
+            &mut rx,
+            peer,
+            ConnectionId::from(0usize),
+            ConnectionId::from(1usize),
+        )
+        .await;
     }
 }
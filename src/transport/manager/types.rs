@@ -19,13 +19,17 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::{
+    error::Error,
     transport::manager::address::{AddressRecord, AddressStore},
     types::ConnectionId,
 };
 
 use multiaddr::Multiaddr;
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
 
 /// Supported protocols.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
@@ -41,6 +45,52 @@ pub enum SupportedTransport {
 
     /// WebSocket
     WebSocket,
+
+    /// In-process memory transport.
+    Memory,
+
+    /// Circuit relay.
+    Relay,
+}
+
+/// Number of known peers in each [`PeerState`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct PeerStateCounts {
+    /// Connected peers.
+    pub connected: usize,
+
+    /// Peers to which a connection is being opened over one or more addresses.
+    pub opening: usize,
+
+    /// Peers which are being dialed.
+    pub dialing: usize,
+
+    /// Disconnected peers.
+    pub disconnected: usize,
+
+    /// Banned peers.
+    pub banned: usize,
+}
+
+/// Snapshot of the connection state of [`crate::transport::manager::TransportManager`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    /// Number of established connections, including secondary connections.
+    pub established_connections: usize,
+
+    /// Number of connections which have been opened but not yet negotiated.
+    pub pending_connections: usize,
+
+    /// Number of pending `/dnsaddr` resolutions.
+    pub pending_dns_resolves: usize,
+
+    /// Number of known peers in each state.
+    pub peers: PeerStateCounts,
+
+    /// Number of established connections for each transport.
+    ///
+    /// Transports without established connections are omitted.
+    pub connections_per_transport: HashMap<SupportedTransport, usize>,
 }
 
 /// Peer state.
@@ -70,6 +120,9 @@ pub enum PeerState {
 
         /// Active transports.
         transports: HashSet<SupportedTransport>,
+
+        /// Dialed addresses of the failed transports and the errors they failed with.
+        errors: Vec<(Multiaddr, Error)>,
     },
 
     /// Peer is being dialed.
@@ -89,6 +142,26 @@ pub enum PeerState {
         /// failure even after the connection has been closed.
         dial_record: Option<AddressRecord>,
     },
+
+    /// Peer is banned and cannot be dialed nor can it open connections to the local node.
+    ///
+    /// If the ban is temporary, the peer is moved back to [`PeerState::Disconnected`] once the
+    /// ban has expired.
+    Banned {
+        /// Instant when the ban expires, if the ban is temporary.
+        until: Option<Instant>,
+    },
+}
+
+impl PeerState {
+    /// Get the ID of the connection of the peer or of the dial which is opening one.
+    pub fn connection_id(&self) -> Option<ConnectionId> {
+        match self {
+            Self::Connected { record, .. } | Self::Dialing { record } => *record.connection_id(),
+            Self::Opening { connection_id, .. } => Some(*connection_id),
+            Self::Disconnected { .. } | Self::Banned { .. } => None,
+        }
+    }
 }
 
 /// Peer context.
@@ -102,4 +175,9 @@ pub struct PeerContext {
 
     /// Known addresses of peer.
     pub addresses: AddressStore,
+
+    /// Ban of the peer, if it was banned while connected or being dialed.
+    ///
+    /// The ban is moved to [`PeerState::Banned`] once the peer has disconnected.
+    pub pending_ban: Option<Option<Instant>>,
 }
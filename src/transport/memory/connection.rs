@@ -0,0 +1,138 @@
+// Copyright 2024 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Memory connection.
+//!
+//! Memory connections are secured with Noise and multiplexed with Yamux exactly like TCP
+//! connections, the only difference being that the bytes are carried over an in-process pipe.
+
+use crate::{
+    config::Role,
+    crypto::{
+        ed25519::Keypair,
+        noise::{self, NoiseSocket},
+    },
+    multistream_select::Version,
+    transport::{
+        common::connection::{self, negotiate_protocol},
+        Endpoint,
+    },
+    types::ConnectionId,
+    BandwidthSink, PeerId,
+};
+
+use multiaddr::Multiaddr;
+use tokio::io::DuplexStream;
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+use std::time::Duration;
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::memory::connection";
+
+/// Stream of an upgraded memory connection.
+type MemoryStream = NoiseSocket<Compat<DuplexStream>>;
+
+/// Connection context for an opened memory connection that hasn't yet started its event loop.
+pub type NegotiatedConnection = connection::NegotiatedConnection<MemoryStream>;
+
+/// Memory connection.
+pub type MemoryConnection = connection::Connection<MemoryStream>;
+
+impl MemoryConnection {
+    /// Negotiate noise + yamux for the connection.
+    ///
+    /// `address` is the address of the remote node, used as the address of the connection
+    /// [`Endpoint`].
+    pub(crate) async fn negotiate_connection(
+        stream: DuplexStream,
+        dialed_peer: Option<PeerId>,
+        connection_id: ConnectionId,
+        keypair: Keypair,
+        bandwidth_sink: BandwidthSink,
+        role: Role,
+        address: Multiaddr,
+        yamux_config: crate::yamux::Config,
+        max_read_ahead_factor: usize,
+        max_write_buffer_size: usize,
+        substream_open_timeout: Duration,
+    ) -> crate::Result<NegotiatedConnection> {
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?role,
+            ?address,
+            "negotiate connection",
+        );
+
+        let stream = TokioAsyncReadCompatExt::compat(stream).into_inner();
+        let stream = TokioAsyncWriteCompatExt::compat_write(stream);
+
+        // negotiate `noise`
+        let (stream, _) = negotiate_protocol(
+            stream,
+            &role,
+            vec!["/noise"],
+            Version::V1,
+            substream_open_timeout,
+        )
+        .await?;
+
+        // perform noise handshake
+        let (stream, peer, _) = noise::handshake(
+            stream.inner(),
+            &keypair,
+            role,
+            dialed_peer,
+            &[],
+            max_read_ahead_factor,
+            max_write_buffer_size,
+        )
+        .await?;
+
+        tracing::trace!(target: LOG_TARGET, "noise handshake done");
+        let stream: NoiseSocket<Compat<DuplexStream>> = stream;
+
+        // negotiate `yamux`
+        let (stream, _) = negotiate_protocol(
+            stream,
+            &role,
+            vec!["/yamux/1.0.0"],
+            Version::V1,
+            substream_open_timeout,
+        )
+        .await?;
+        tracing::trace!(target: LOG_TARGET, "`yamux` negotiated");
+
+        let endpoint = match role {
+            Role::Dialer => Endpoint::dialer(address, connection_id),
+            Role::Listener => Endpoint::listener(address, connection_id),
+        };
+
+        Ok(NegotiatedConnection::new(
+            stream.inner(),
+            peer,
+            role,
+            endpoint,
+            &bandwidth_sink,
+            yamux_config,
+            substream_open_timeout,
+        ))
+    }
+}
@@ -0,0 +1,683 @@
+// Copyright 2024 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! In-process memory transport.
+//!
+//! Nodes listen on `/memory/<port>` addresses which are registered in a process-wide registry.
+//! Dialing an address connects the nodes with an in-memory pipe, making the transport useful
+//! for fast and deterministic tests which don't depend on the availability of network ports.
+
+use crate::{
+    config::Role,
+    error::{AddressError, Error},
+    transport::{
+        common::{
+            listener::AddressType,
+            multiaddr::{parse_multiaddr, ParsedAddress},
+        },
+        manager::{SupportedTransport, TransportHandle},
+        memory::{
+            config::Config,
+            connection::{MemoryConnection, NegotiatedConnection},
+        },
+        Transport, TransportBuilder, TransportEvent, MAX_INBOUND_SUBSTREAMS,
+    },
+    types::ConnectionId,
+    PeerId,
+};
+
+use futures::{
+    future::{AbortHandle, Abortable, BoxFuture},
+    stream::{FuturesUnordered, Stream, StreamExt},
+};
+use multiaddr::{Multiaddr, Protocol};
+use parking_lot::Mutex;
+use tokio::{
+    io::DuplexStream,
+    sync::mpsc::{channel, error::TrySendError, Receiver, Sender},
+};
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    io::ErrorKind,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+pub(crate) mod connection;
+
+pub mod config;
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::memory";
+
+/// How many inbound connections can be queued for a listener before dials are refused.
+const LISTEN_BACKLOG: usize = 128;
+
+/// Inbound connection: the listener's end of the pipe and the address of the dialer.
+type InboundConnection = (DuplexStream, Multiaddr);
+
+/// Listeners of the process, indexed by their port.
+static LISTENERS: Mutex<BTreeMap<u64, Sender<InboundConnection>>> =
+    parking_lot::const_mutex(BTreeMap::new());
+
+/// Memory transport.
+pub(crate) struct MemoryTransport {
+    /// Transport context.
+    context: TransportHandle,
+
+    /// Transport configuration.
+    config: Config,
+
+    /// Ports the transport is listening on.
+    listen_ports: Vec<u64>,
+
+    /// Receiver for inbound connections, if the transport is listening.
+    listener: Option<Receiver<InboundConnection>>,
+
+    /// Pending dials.
+    pending_dials: HashMap<ConnectionId, Multiaddr>,
+
+    /// Abort handles of pending dials.
+    dial_aborts: HashMap<ConnectionId, AbortHandle>,
+
+    /// Pending negotiations.
+    pending_connections:
+        FuturesUnordered<BoxFuture<'static, Result<NegotiatedConnection, (ConnectionId, Error)>>>,
+
+    /// Events produced synchronously by [`Transport::open()`].
+    pending_events: VecDeque<TransportEvent>,
+
+    /// Opened raw connection, waiting for approval/rejection from `TransportManager`.
+    opened_raw: HashMap<ConnectionId, (DuplexStream, Multiaddr)>,
+
+    /// Connections which have been opened and negotiated but are being validated by the
+    /// `TransportManager`.
+    pending_open: HashMap<ConnectionId, NegotiatedConnection>,
+}
+
+impl MemoryTransport {
+    /// Address the remote node sees for connections dialed by this node.
+    fn local_address(&self) -> Multiaddr {
+        Multiaddr::empty().with(Protocol::Memory(
+            self.listen_ports.first().copied().unwrap_or(0),
+        ))
+    }
+
+    /// Extract the memory port and `PeerId`, if found, from `address`.
+    fn memory_address(address: &Multiaddr) -> crate::Result<(u64, Option<PeerId>)> {
+        match parse_multiaddr(address)? {
+            ParsedAddress {
+                transport: SupportedTransport::Memory,
+                socket: AddressType::Memory(port),
+                peer,
+            } => Ok((port, peer)),
+            _ => Err(Error::AddressError(AddressError::InvalidProtocol)),
+        }
+    }
+
+    /// Connect to the listener of `port`, returning the dialer's end of the pipe.
+    fn connect(&self, port: u64) -> crate::Result<DuplexStream> {
+        let sender = LISTENERS
+            .lock()
+            .get(&port)
+            .cloned()
+            .ok_or(Error::IoError(ErrorKind::ConnectionRefused))?;
+        let (local, remote) = tokio::io::duplex(self.config.buffer_size);
+
+        match sender.try_send((remote, self.local_address())) {
+            Ok(()) => Ok(local),
+            Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => {
+                tracing::debug!(target: LOG_TARGET, ?port, "listener refused connection");
+                Err(Error::IoError(ErrorKind::ConnectionRefused))
+            }
+        }
+    }
+
+    /// Handle inbound connection.
+    fn on_inbound_connection(&mut self, stream: DuplexStream, address: Multiaddr) {
+        let connection_id = self.context.next_connection_id();
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?connection_id,
+            ?address,
+            "accept inbound connection",
+        );
+
+        let future =
+            self.negotiate_connection(connection_id, stream, Role::Listener, address, None);
+        self.pending_connections.push(future);
+    }
+
+    /// Negotiate the connection over `stream`.
+    fn negotiate_connection(
+        &self,
+        connection_id: ConnectionId,
+        stream: DuplexStream,
+        role: Role,
+        address: Multiaddr,
+        peer: Option<PeerId>,
+    ) -> BoxFuture<'static, Result<NegotiatedConnection, (ConnectionId, Error)>> {
+        let keypair = self.context.keypair.clone();
+        let bandwidth_sink = self.context.bandwidth_sink.clone();
+        let yamux_config = self.config.yamux_config.clone();
+        let max_read_ahead_factor = self.config.noise_read_ahead_frame_count;
+        let max_write_buffer_size = self.config.noise_write_buffer_size;
+        let connection_open_timeout = self.config.connection_open_timeout;
+        let substream_open_timeout = self.config.substream_open_timeout;
+
+        Box::pin(async move {
+            match tokio::time::timeout(
+                connection_open_timeout,
+                MemoryConnection::negotiate_connection(
+                    stream,
+                    peer,
+                    connection_id,
+                    keypair,
+                    bandwidth_sink,
+                    role,
+                    address,
+                    yamux_config,
+                    max_read_ahead_factor,
+                    max_write_buffer_size,
+                    substream_open_timeout,
+                ),
+            )
+            .await
+            {
+                Err(_) => Err((connection_id, Error::Timeout)),
+                Ok(Err(error)) => Err((connection_id, error)),
+                Ok(Ok(connection)) => Ok(connection),
+            }
+        })
+    }
+}
+
+impl TransportBuilder for MemoryTransport {
+    type Config = Config;
+    type Transport = MemoryTransport;
+
+    /// Create new [`MemoryTransport`].
+    fn new(
+        context: TransportHandle,
+        mut config: Self::Config,
+    ) -> crate::Result<(Self, Vec<Multiaddr>)> {
+        tracing::debug!(
+            target: LOG_TARGET,
+            listen_addresses = ?config.listen_addresses,
+            "start memory transport",
+        );
+
+        let (tx, rx) = channel(LISTEN_BACKLOG);
+        let mut listen_ports = Vec::new();
+        let mut listeners = LISTENERS.lock();
+
+        for address in std::mem::take(&mut config.listen_addresses) {
+            let port = match parse_multiaddr(&address) {
+                Ok(ParsedAddress {
+                    socket: AddressType::Memory(port),
+                    ..
+                }) => port,
+                _ => {
+                    tracing::debug!(target: LOG_TARGET, ?address, "invalid memory address");
+                    continue;
+                }
+            };
+
+            let port = match port {
+                0 => loop {
+                    let port = rand::random::<u64>();
+
+                    if port != 0 && !listeners.contains_key(&port) {
+                        break port;
+                    }
+                },
+                port if listeners.contains_key(&port) => {
+                    tracing::debug!(target: LOG_TARGET, ?port, "memory port already in use");
+                    continue;
+                }
+                port => port,
+            };
+
+            listeners.insert(port, tx.clone());
+            listen_ports.push(port);
+        }
+        drop(listeners);
+
+        let listen_addresses = listen_ports
+            .iter()
+            .map(|port| Multiaddr::empty().with(Protocol::Memory(*port)))
+            .collect();
+
+        Ok((
+            Self {
+                context,
+                config,
+                listener: (!listen_ports.is_empty()).then_some(rx),
+                listen_ports,
+                pending_dials: HashMap::new(),
+                dial_aborts: HashMap::new(),
+                pending_connections: FuturesUnordered::new(),
+                pending_events: VecDeque::new(),
+                opened_raw: HashMap::new(),
+                pending_open: HashMap::new(),
+            },
+            listen_addresses,
+        ))
+    }
+}
+
+impl Drop for MemoryTransport {
+    fn drop(&mut self) {
+        let mut listeners = LISTENERS.lock();
+
+        for port in &self.listen_ports {
+            listeners.remove(port);
+        }
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn dial(&mut self, connection_id: ConnectionId, address: Multiaddr) -> crate::Result<()> {
+        tracing::debug!(target: LOG_TARGET, ?connection_id, ?address, "open connection");
+
+        let (port, peer) = Self::memory_address(&address)?;
+        let future: BoxFuture<'static, _> = match self.connect(port) {
+            Ok(stream) => self.negotiate_connection(
+                connection_id,
+                stream,
+                Role::Dialer,
+                Multiaddr::empty().with(Protocol::Memory(port)),
+                peer,
+            ),
+            Err(error) => Box::pin(futures::future::ready(Err((connection_id, error)))),
+        };
+
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        self.pending_dials.insert(connection_id, address);
+        self.dial_aborts.insert(connection_id, abort_handle);
+
+        self.pending_connections.push(Box::pin(async move {
+            match Abortable::new(future, abort_registration).await {
+                Ok(result) => result,
+                Err(_) => Err((connection_id, Error::DialAborted)),
+            }
+        }));
+
+        Ok(())
+    }
+
+    fn accept(&mut self, connection_id: ConnectionId) -> crate::Result<()> {
+        let context = self
+            .pending_open
+            .remove(&connection_id)
+            .ok_or(Error::ConnectionDoesntExist(connection_id))?;
+        let protocol_set = self.context.protocol_set(connection_id);
+        let bandwidth_sink = self.context.bandwidth_sink.clone();
+        let next_substream_id = self.context.next_substream_id.clone();
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?connection_id,
+            "start connection",
+        );
+
+        self.context.executor.run(Box::pin(async move {
+            if let Err(error) = MemoryConnection::new(
+                context,
+                protocol_set,
+                bandwidth_sink,
+                next_substream_id,
+                MAX_INBOUND_SUBSTREAMS,
+            )
+            .start()
+            .await
+            {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?connection_id,
+                    ?error,
+                    "connection exited with error",
+                );
+            }
+        }));
+
+        Ok(())
+    }
+
+    fn reject(&mut self, connection_id: ConnectionId) -> crate::Result<()> {
+        self.pending_open
+            .remove(&connection_id)
+            .map_or(Err(Error::ConnectionDoesntExist(connection_id)), |_| Ok(()))
+    }
+
+    fn open(
+        &mut self,
+        connection_id: ConnectionId,
+        addresses: Vec<Multiaddr>,
+    ) -> crate::Result<()> {
+        let mut errors = Vec::new();
+
+        for address in addresses {
+            match Self::memory_address(&address).and_then(|(port, _)| self.connect(port)) {
+                Ok(stream) => {
+                    self.opened_raw.insert(connection_id, (stream, address.clone()));
+                    self.pending_events.push_back(TransportEvent::ConnectionOpened {
+                        connection_id,
+                        address,
+                    });
+
+                    return Ok(());
+                }
+                Err(error) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?connection_id,
+                        ?address,
+                        ?error,
+                        "failed to open connection",
+                    );
+                    errors.push((address, error.into_dial_error()));
+                }
+            }
+        }
+
+        self.pending_events.push_back(TransportEvent::OpenFailure {
+            connection_id,
+            errors,
+        });
+        Ok(())
+    }
+
+    fn negotiate(&mut self, connection_id: ConnectionId) -> crate::Result<()> {
+        let (stream, address) = self
+            .opened_raw
+            .remove(&connection_id)
+            .ok_or(Error::ConnectionDoesntExist(connection_id))?;
+
+        let (port, peer) = Self::memory_address(&address)?;
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?peer,
+            ?connection_id,
+            ?address,
+            "negotiate connection",
+        );
+
+        let remote_address = Multiaddr::empty().with(Protocol::Memory(port));
+        let future =
+            self.negotiate_connection(connection_id, stream, Role::Dialer, remote_address, peer);
+
+        self.pending_dials.insert(connection_id, address);
+        self.pending_connections.push(future);
+
+        Ok(())
+    }
+
+    fn cancel(&mut self, connection_id: ConnectionId) {
+        if let Some(abort_handle) = self.dial_aborts.remove(&connection_id) {
+            abort_handle.abort();
+            return;
+        }
+
+        // the connection is opened synchronously so canceling it only requires dropping the pipe
+        // and the events of the connection that haven't been reported yet
+        self.opened_raw.remove(&connection_id);
+        self.pending_events.retain(|event| match event {
+            TransportEvent::ConnectionOpened {
+                connection_id: id, ..
+            }
+            | TransportEvent::OpenFailure {
+                connection_id: id, ..
+            } => id != &connection_id,
+            _ => true,
+        });
+    }
+}
+
+impl Stream for MemoryTransport {
+    type Item = TransportEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(event) = self.pending_events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+
+        while let Some(listener) = self.listener.as_mut() {
+            match listener.poll_recv(cx) {
+                Poll::Pending => break,
+                Poll::Ready(None) => self.listener = None,
+                Poll::Ready(Some((stream, address))) => self.on_inbound_connection(stream, address),
+            }
+        }
+
+        while let Poll::Ready(Some(connection)) = self.pending_connections.poll_next_unpin(cx) {
+            let connection_id = match &connection {
+                Ok(connection) => connection.connection_id(),
+                Err((connection_id, _)) => *connection_id,
+            };
+            self.dial_aborts.remove(&connection_id);
+
+            match connection {
+                Ok(connection) => {
+                    let peer = connection.peer();
+                    let endpoint = connection.endpoint();
+                    self.pending_dials.remove(&connection_id);
+                    self.pending_open.insert(connection_id, connection);
+
+                    return Poll::Ready(Some(TransportEvent::ConnectionEstablished {
+                        peer,
+                        endpoint,
+                        transport: SupportedTransport::Memory,
+                    }));
+                }
+                Err((connection_id, error)) => {
+                    if let Some(address) = self.pending_dials.remove(&connection_id) {
+                        return Poll::Ready(Some(TransportEvent::DialFailure {
+                            connection_id,
+                            address,
+                            error: error.into_dial_error(),
+                        }));
+                    }
+                }
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::ProtocolCodec,
+        crypto::ed25519::Keypair,
+        error::DialError,
+        executor::DefaultExecutor,
+        transport::{
+            manager::{ProtocolContext, TransportHandle},
+            Endpoint,
+        },
+        types::protocol::ProtocolName,
+        BandwidthSink,
+    };
+    use multihash::Multihash;
+    use std::sync::Arc;
+    use tokio::sync::mpsc::channel;
+    use trust_dns_resolver::TokioAsyncResolver;
+
+    fn make_transport(config: Config) -> (MemoryTransport, Vec<Multiaddr>, PeerId) {
+        let keypair = Keypair::generate();
+        let (tx, _rx) = channel(64);
+        let (event_tx, _event_rx) = channel(64);
+
+        let handle = TransportHandle {
+            executor: Arc::new(DefaultExecutor {}),
+            resolver: Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
+            protocol_names: Vec::new(),
+            next_substream_id: Default::default(),
+            next_connection_id: Default::default(),
+            keypair: keypair.clone(),
+            tx: event_tx,
+            bandwidth_sink: BandwidthSink::new(),
+            shutdown: Default::default(),
+
+            protocols: HashMap::from_iter([(
+                ProtocolName::from("/notif/1"),
+                ProtocolContext {
+                    tx,
+                    codec: ProtocolCodec::Identity(32),
+                    fallback_names: Vec::new(),
+                },
+            )]),
+        };
+        let (transport, listen_addresses) = MemoryTransport::new(handle, config).unwrap();
+
+        (
+            transport,
+            listen_addresses,
+            PeerId::from_public_key(&keypair.public().into()),
+        )
+    }
+
+    #[tokio::test]
+    async fn connect_and_accept_works() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (mut transport1, listen_addresses, peer1) = make_transport(Default::default());
+        let (mut transport2, _, peer2) = make_transport(Default::default());
+
+        let address = listen_addresses[0].clone().with(Protocol::P2p(Multihash::from(peer1)));
+        transport2.dial(ConnectionId::new(), address).unwrap();
+
+        let (res1, res2) = tokio::join!(transport1.next(), transport2.next());
+
+        match (res1, res2) {
+            (
+                Some(TransportEvent::ConnectionEstablished {
+                    peer: remote1,
+                    endpoint: Endpoint::Listener { .. },
+                    transport: SupportedTransport::Memory,
+                }),
+                Some(TransportEvent::ConnectionEstablished {
+                    peer: remote2,
+                    endpoint: Endpoint::Dialer { address, .. },
+                    transport: SupportedTransport::Memory,
+                }),
+            ) => {
+                assert_eq!(remote1, peer2);
+                assert_eq!(remote2, peer1);
+                assert_eq!(address, listen_addresses[0]);
+            }
+            events => panic!("invalid events: {events:?}"),
+        }
+        assert!(transport2.pending_dials.is_empty());
+    }
+
+    #[tokio::test]
+    async fn open_and_negotiate_works() {
+        let (mut transport1, listen_addresses, peer1) = make_transport(Default::default());
+        let (mut transport2, _, peer2) = make_transport(Default::default());
+
+        let address = listen_addresses[0].clone().with(Protocol::P2p(Multihash::from(peer1)));
+        let connection_id = ConnectionId::new();
+        transport2.open(connection_id, vec![address.clone()]).unwrap();
+
+        match transport2.next().await {
+            Some(TransportEvent::ConnectionOpened {
+                connection_id: opened_id,
+                address: opened_address,
+            }) => {
+                assert_eq!(opened_id, connection_id);
+                assert_eq!(opened_address, address);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+        transport2.negotiate(connection_id).unwrap();
+
+        match tokio::join!(transport1.next(), transport2.next()) {
+            (
+                Some(TransportEvent::ConnectionEstablished { peer: remote1, .. }),
+                Some(TransportEvent::ConnectionEstablished { peer: remote2, .. }),
+            ) => {
+                assert_eq!(remote1, peer2);
+                assert_eq!(remote2, peer1);
+            }
+            events => panic!("invalid events: {events:?}"),
+        }
+        assert!(transport2.pending_dials.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dial_unknown_port_fails() {
+        let (mut transport, _, _) = make_transport(Config {
+            listen_addresses: Vec::new(),
+            ..Default::default()
+        });
+        let address = Multiaddr::empty().with(Protocol::Memory(u64::MAX));
+        let connection_id = ConnectionId::new();
+
+        transport.dial(connection_id, address.clone()).unwrap();
+
+        match transport.next().await {
+            Some(TransportEvent::DialFailure {
+                connection_id: failed_id,
+                address: failed_address,
+                error: Error::Dial(DialError::Refused),
+            }) => {
+                assert_eq!(failed_id, connection_id);
+                assert_eq!(failed_address, address);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn listen_port_released_on_drop() {
+        let (transport, listen_addresses, _) = make_transport(Config {
+            listen_addresses: vec!["/memory/1337".parse().unwrap()],
+            ..Default::default()
+        });
+        assert_eq!(listen_addresses, vec!["/memory/1337".parse().unwrap()]);
+
+        // the port is in use until the first transport is dropped
+        let (_transport, listen_addresses, _) = make_transport(Config {
+            listen_addresses: vec!["/memory/1337".parse().unwrap()],
+            ..Default::default()
+        });
+        assert!(listen_addresses.is_empty());
+
+        drop(transport);
+
+        let (_transport, listen_addresses, _) = make_transport(Config {
+            listen_addresses: vec!["/memory/1337".parse().unwrap()],
+            ..Default::default()
+        });
+        assert_eq!(listen_addresses, vec!["/memory/1337".parse().unwrap()]);
+    }
+}
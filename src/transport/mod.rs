@@ -28,7 +28,11 @@ use multiaddr::Multiaddr;
 use std::{fmt::Debug, time::Duration};
 
 pub(crate) mod common;
+pub mod dns;
+pub mod filter;
+pub mod memory;
 pub mod quic;
+pub mod relay;
 pub mod tcp;
 pub mod webrtc;
 pub mod websocket;
@@ -36,6 +40,9 @@ pub mod websocket;
 pub(crate) mod dummy;
 pub(crate) mod manager;
 
+pub use crate::multistream_select::Version as MultistreamSelectVersion;
+pub use manager::{DialBackoffConfig, Metrics, PeerStateCounts, SupportedTransport};
+
 /// Timeout for opening a connection.
 pub(crate) const CONNECTION_OPEN_TIMEOUT: Duration = Duration::from_secs(10);
 
@@ -45,6 +52,18 @@ pub(crate) const SUBSTREAM_OPEN_TIMEOUT: Duration = Duration::from_secs(5);
 /// Maximum number of parallel dial attempts.
 pub(crate) const MAX_PARALLEL_DIALS: usize = 8;
 
+/// Maximum number of inbound connections negotiated concurrently.
+pub(crate) const MAX_PENDING_NEGOTIATIONS: usize = 128;
+
+/// Maximum number of inbound substreams a connection can have open.
+pub(crate) const MAX_INBOUND_SUBSTREAMS: usize = 256;
+
+/// How many distinct peers must observe an address before it's considered external.
+pub(crate) const EXTERNAL_ADDRESS_CONFIRMATIONS: usize = 3;
+
+/// Maximum number of DNS lookups cached by the resolver.
+pub(crate) const DNS_CACHE_SIZE: usize = 32;
+
 /// Connection endpoint.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Endpoint {
@@ -116,6 +135,9 @@ pub(crate) enum TransportEvent {
 
         /// Endpoint.
         endpoint: Endpoint,
+
+        /// Transport used to establish the connection.
+        transport: SupportedTransport,
     },
 
     /// Connection opened to remote but not yet negotiated.
@@ -153,6 +175,9 @@ pub(crate) enum TransportEvent {
     OpenFailure {
         /// Connection ID.
         connection_id: ConnectionId,
+
+        /// Dialed addresses and the errors they failed with.
+        errors: Vec<(Multiaddr, Error)>,
     },
 }
 
@@ -189,6 +214,9 @@ pub(crate) trait Transport: Stream + Unpin + Send {
 
     /// Cancel opening connections.
     ///
+    /// If `connection_id` belongs to a connection started with [`Transport::dial()`], the dial is
+    /// aborted and reported as [`TransportEvent::DialFailure`] with [`Error::DialAborted`].
+    ///
     /// This is a no-op for connections that have already succeeded/canceled.
     fn cancel(&mut self, connection_id: ConnectionId);
 }
@@ -20,11 +20,11 @@
 
 //! QUIC transport configuration.
 
-use crate::transport::{CONNECTION_OPEN_TIMEOUT, SUBSTREAM_OPEN_TIMEOUT};
+use crate::transport::{filter::ConnectionFilter, CONNECTION_OPEN_TIMEOUT, SUBSTREAM_OPEN_TIMEOUT};
 
 use multiaddr::Multiaddr;
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 /// QUIC transport configuration.
 #[derive(Debug)]
@@ -45,6 +45,14 @@ pub struct Config {
     /// How long should litep2p wait for a substream to be opened before considering
     /// the substream rejected.
     pub substream_open_timeout: Duration,
+
+    /// Filter for inbound connections.
+    ///
+    /// Consulted with the remote address of each incoming connection before the handshake
+    /// is completed. Rejected connections are dropped.
+    ///
+    /// Defaults to `None`, i.e., all inbound connections are accepted.
+    pub connection_filter: Option<Arc<dyn ConnectionFilter>>,
 }
 
 impl Default for Config {
@@ -53,6 +61,7 @@ impl Default for Config {
             listen_addresses: vec!["/ip4/127.0.0.1/udp/0/quic-v1".parse().expect("valid address")],
             connection_open_timeout: CONNECTION_OPEN_TIMEOUT,
             substream_open_timeout: SUBSTREAM_OPEN_TIMEOUT,
+            connection_filter: None,
         }
     }
 }
@@ -29,6 +29,7 @@ use crate::{
     protocol::{Direction, Permit, ProtocolCommand, ProtocolSet},
     substream,
     transport::{
+        common::metered::ConnectionMeter,
         quic::substream::{NegotiatingSubstream, Substream},
         Endpoint,
     },
@@ -107,6 +108,9 @@ pub struct QuicConnection {
     /// Bandwidth sink.
     bandwidth_sink: BandwidthSink,
 
+    /// Meter of the connection.
+    meter: ConnectionMeter,
+
     /// Pending substreams.
     pending_substreams:
         FuturesUnordered<BoxFuture<'static, Result<NegotiatedSubstream, ConnectionError>>>,
@@ -127,6 +131,7 @@ impl QuicConnection {
             endpoint,
             connection,
             protocol_set,
+            meter: bandwidth_sink.connection_meter(peer),
             bandwidth_sink,
             substream_open_timeout,
             pending_substreams: FuturesUnordered::new(),
@@ -291,9 +296,18 @@ impl QuicConnection {
                             };
 
                             if let (Some(protocol), Some(substream_id)) = (protocol, substream_id) {
-                                self.protocol_set
+                                if let Err(error) = self.protocol_set
                                     .report_substream_open_failure(protocol, substream_id, error)
-                                    .await?;
+                                    .await
+                                {
+                                    tracing::debug!(
+                                        target: LOG_TARGET,
+                                        peer = ?self.peer,
+                                        ?error,
+                                        "failed to report substream open failure to protocol, closing connection",
+                                    );
+                                    return self.protocol_set.report_connection_closed(self.peer, self.endpoint.connection_id()).await
+                                }
                             }
                         }
                         Ok(substream) => {
@@ -308,14 +322,24 @@ impl QuicConnection {
                                     substream.permit,
                                     substream.sender,
                                     substream.receiver,
-                                    bandwidth_sink
+                                    bandwidth_sink,
+                                    self.meter.clone(),
                                 ),
                                 self.protocol_set.protocol_codec(&protocol)
                             );
 
-                            self.protocol_set
+                            if let Err(error) = self.protocol_set
                                 .report_substream_open(self.peer, protocol, direction, substream)
-                                .await?;
+                                .await
+                            {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    peer = ?self.peer,
+                                    ?error,
+                                    "failed to register opened substream to protocol, closing connection",
+                                );
+                                return self.protocol_set.report_connection_closed(self.peer, self.endpoint.connection_id()).await
+                            }
                         }
                     }
                 }
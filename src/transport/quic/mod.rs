@@ -26,7 +26,7 @@ use crate::{
     crypto::tls::make_client_config,
     error::{AddressError, Error},
     transport::{
-        manager::TransportHandle,
+        manager::{SupportedTransport, TransportHandle},
         quic::{config::Config as QuicConfig, connection::QuicConnection, listener::QuicListener},
         Endpoint as Litep2pEndpoint, Transport, TransportBuilder, TransportEvent,
     },
@@ -34,7 +34,11 @@ use crate::{
     PeerId,
 };
 
-use futures::{future::BoxFuture, stream::FuturesUnordered, Stream, StreamExt};
+use futures::{
+    future::{AbortHandle, Abortable, BoxFuture},
+    stream::FuturesUnordered,
+    FutureExt, Stream, StreamExt,
+};
 use multiaddr::{Multiaddr, Protocol};
 use quinn::{ClientConfig, Connection, Endpoint, IdleTimeout};
 
@@ -80,6 +84,9 @@ pub(crate) struct QuicTransport {
     /// Pending dials.
     pending_dials: HashMap<ConnectionId, Multiaddr>,
 
+    /// Abort handles of pending dials.
+    dial_aborts: HashMap<ConnectionId, AbortHandle>,
+
     /// Pending connections.
     pending_connections:
         FuturesUnordered<BoxFuture<'static, (ConnectionId, Result<NegotiatedConnection, Error>)>>,
@@ -88,8 +95,16 @@ pub(crate) struct QuicTransport {
     pending_open: HashMap<ConnectionId, (NegotiatedConnection, Litep2pEndpoint)>,
 
     /// Pending raw, unnegotiated connections.
+    ///
+    /// If none of the addresses could be dialed, the error of each address is returned.
     pending_raw_connections: FuturesUnordered<
-        BoxFuture<'static, Result<(ConnectionId, Multiaddr, NegotiatedConnection), ConnectionId>>,
+        BoxFuture<
+            'static,
+            Result<
+                (ConnectionId, Multiaddr, NegotiatedConnection),
+                (ConnectionId, Vec<(Multiaddr, Error)>),
+            >,
+        >,
     >,
 
     /// Opened raw connection, waiting for approval/rejection from `TransportManager`.
@@ -140,7 +155,11 @@ impl QuicTransport {
                 );
                 self.pending_open.insert(connection_id, (connection, endpoint.clone()));
 
-                return Some(TransportEvent::ConnectionEstablished { peer, endpoint });
+                return Some(TransportEvent::ConnectionEstablished {
+                    peer,
+                    endpoint,
+                    transport: SupportedTransport::Quic,
+                });
             }
             Err(error) => {
                 tracing::debug!(target: LOG_TARGET, ?connection_id, ?error, "failed to establish connection");
@@ -151,7 +170,7 @@ impl QuicTransport {
                     return Some(TransportEvent::DialFailure {
                         connection_id,
                         address,
-                        error,
+                        error: error.into_dial_error(),
                     });
                 }
             }
@@ -193,6 +212,7 @@ impl TransportBuilder for QuicTransport {
                 opened_raw: HashMap::new(),
                 pending_open: HashMap::new(),
                 pending_dials: HashMap::new(),
+                dial_aborts: HashMap::new(),
                 pending_raw_connections: FuturesUnordered::new(),
                 pending_connections: FuturesUnordered::new(),
             },
@@ -236,11 +256,16 @@ impl Transport for QuicTransport {
             "dial peer",
         );
 
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let future = Abortable::new(connection, abort_registration);
+
         self.pending_dials.insert(connection_id, address);
+        self.dial_aborts.insert(connection_id, abort_handle);
         self.pending_connections.push(Box::pin(async move {
-            let connection = match connection.await {
-                Ok(connection) => connection,
-                Err(error) => return (connection_id, Err(error.into())),
+            let connection = match future.await {
+                Ok(Ok(connection)) => connection,
+                Ok(Err(error)) => return (connection_id, Err(error.into())),
+                Err(_) => return (connection_id, Err(Error::DialAborted)),
             };
 
             let Some(peer) = Self::extract_peer_id(&connection) else {
@@ -301,6 +326,7 @@ impl Transport for QuicTransport {
             .map(|address| {
                 let keypair = self.context.keypair.clone();
                 let connection_open_timeout = self.config.connection_open_timeout;
+                let dialed_address = address.clone();
 
                 async move {
                     let Ok((socket_address, Some(peer))) =
@@ -360,25 +386,37 @@ impl Transport for QuicTransport {
                         Ok((address, NegotiatedConnection { peer, connection })),
                     )
                 }
+                .map(|(connection_id, result)| {
+                    (
+                        connection_id,
+                        result.map_err(|error| (dialed_address, error.into_dial_error())),
+                    )
+                })
             })
             .collect();
 
         self.pending_raw_connections.push(Box::pin(async move {
+            let mut errors = Vec::new();
+
             while let Some(result) = futures.next().await {
                 let (connection_id, result) = result;
 
                 match result {
                     Ok((address, connection)) => return Ok((connection_id, address, connection)),
-                    Err(error) => tracing::debug!(
-                        target: LOG_TARGET,
-                        ?connection_id,
-                        ?error,
-                        "failed to open connection",
-                    ),
+                    Err((address, error)) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?connection_id,
+                            ?address,
+                            ?error,
+                            "failed to open connection",
+                        );
+                        errors.push((address, error));
+                    }
                 }
             }
 
-            Err(connection_id)
+            Err((connection_id, errors))
         }));
 
         Ok(())
@@ -398,7 +436,12 @@ impl Transport for QuicTransport {
 
     /// Cancel opening connections.
     fn cancel(&mut self, connection_id: ConnectionId) {
-        self.canceled.insert(connection_id);
+        match self.dial_aborts.remove(&connection_id) {
+            Some(abort_handle) => abort_handle.abort(),
+            None => {
+                self.canceled.insert(connection_id);
+            }
+        }
     }
 }
 
@@ -407,6 +450,19 @@ impl Stream for QuicTransport {
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         while let Poll::Ready(Some(connection)) = self.listener.poll_next_unpin(cx) {
+            if let Some(filter) = &self.config.connection_filter {
+                let address = connection.remote_address();
+
+                if !filter.accept(&address) {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?address,
+                        "inbound connection rejected by filter",
+                    );
+                    continue;
+                }
+            }
+
             let connection_id = self.context.next_connection_id();
 
             tracing::trace!(
@@ -449,15 +505,19 @@ impl Stream for QuicTransport {
                         }));
                     }
                 }
-                Err(connection_id) =>
+                Err((connection_id, errors)) =>
                     if !self.canceled.remove(&connection_id) {
-                        return Poll::Ready(Some(TransportEvent::OpenFailure { connection_id }));
+                        return Poll::Ready(Some(TransportEvent::OpenFailure {
+                            connection_id,
+                            errors,
+                        }));
                     },
             }
         }
 
         while let Poll::Ready(Some(connection)) = self.pending_connections.poll_next_unpin(cx) {
             let (connection_id, result) = connection;
+            self.dial_aborts.remove(&connection_id);
 
             match self.on_connection_established(connection_id, result) {
                 Some(event) => return Poll::Ready(Some(event)),
@@ -480,8 +540,8 @@ mod tests {
         types::protocol::ProtocolName,
         BandwidthSink,
     };
-    use multihash::Multihash;
     use tokio::sync::mpsc::channel;
+    use trust_dns_resolver::TokioAsyncResolver;
 
     #[tokio::test]
     async fn test_quinn() {
@@ -495,12 +555,17 @@ mod tests {
 
         let handle1 = TransportHandle {
             executor: Arc::new(DefaultExecutor {}),
+            resolver: Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
             protocol_names: Vec::new(),
             next_substream_id: Default::default(),
             next_connection_id: Default::default(),
             keypair: keypair1.clone(),
             tx: event_tx1,
             bandwidth_sink: BandwidthSink::new(),
+            shutdown: Default::default(),
 
             protocols: HashMap::from_iter([(
                 ProtocolName::from("/notif/1"),
@@ -522,12 +587,17 @@ mod tests {
 
         let handle2 = TransportHandle {
             executor: Arc::new(DefaultExecutor {}),
+            resolver: Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
             protocol_names: Vec::new(),
             next_substream_id: Default::default(),
             next_connection_id: Default::default(),
             keypair: keypair2.clone(),
             tx: event_tx2,
             bandwidth_sink: BandwidthSink::new(),
+            shutdown: Default::default(),
 
             protocols: HashMap::from_iter([(
                 ProtocolName::from("/notif/1"),
@@ -542,9 +612,7 @@ mod tests {
         let (mut transport2, _) = QuicTransport::new(handle2, Default::default()).unwrap();
         let peer1: PeerId = PeerId::from_public_key(&keypair1.public().into());
         let _peer2: PeerId = PeerId::from_public_key(&keypair2.public().into());
-        let listen_address = listen_address.with(Protocol::P2p(
-            Multihash::from_bytes(&peer1.to_bytes()).unwrap(),
-        ));
+        let listen_address = listen_address.with(Protocol::P2p(peer1.to_multihash()));
 
         transport2.dial(ConnectionId::new(), listen_address).unwrap();
         let (res1, res2) = tokio::join!(transport1.next(), transport2.next());
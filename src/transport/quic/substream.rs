@@ -20,6 +20,7 @@
 
 use crate::{
     error::{Error, SubstreamError},
+    transport::common::metered::ConnectionMeter,
     BandwidthSink,
 };
 
@@ -42,6 +43,7 @@ use crate::protocol::Permit;
 pub struct Substream {
     _permit: Permit,
     bandwidth_sink: BandwidthSink,
+    meter: ConnectionMeter,
     send_stream: SendStream,
     recv_stream: RecvStream,
 }
@@ -53,12 +55,14 @@ impl Substream {
         send_stream: SendStream,
         recv_stream: RecvStream,
         bandwidth_sink: BandwidthSink,
+        meter: ConnectionMeter,
     ) -> Self {
         Self {
             _permit,
             send_stream,
             recv_stream,
             bandwidth_sink,
+            meter,
         }
     }
 
@@ -74,6 +78,7 @@ impl Substream {
         {
             Ok(()) => {
                 self.bandwidth_sink.increase_outbound(nwritten);
+                self.meter.on_write(nwritten);
                 Ok(())
             }
             Err(error) => Err(error),
@@ -91,6 +96,7 @@ impl TokioAsyncRead for Substream {
             Err(error) => Poll::Ready(Err(error)),
             Ok(res) => {
                 self.bandwidth_sink.increase_inbound(buf.filled().len());
+                self.meter.on_read(buf.filled().len());
                 Poll::Ready(Ok(res))
             }
         }
@@ -107,6 +113,7 @@ impl TokioAsyncWrite for Substream {
             Err(error) => Poll::Ready(Err(error)),
             Ok(nwritten) => {
                 self.bandwidth_sink.increase_outbound(nwritten);
+                self.meter.on_write(nwritten);
                 Poll::Ready(Ok(nwritten))
             }
         }
@@ -0,0 +1,907 @@
+// Copyright 2024 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Circuit relay v2 transport, client side.
+//!
+//! Relayed addresses have the form `<relay address>/p2p/<relay>/p2p-circuit/p2p/<peer>`. The
+//! dialer connects to the relay and requests a circuit to `<peer>` over the `hop` protocol after
+//! which the relay asks `<peer>` to accept the circuit over the `stop` protocol. Once both legs are
+//! established, the substream is bridged to an in-process pipe and upgraded into a Noise + Yamux
+//! connection exactly like a memory connection.
+//!
+//! A node is only reachable through relays it holds a reservation with, see
+//! [`Config::relays`](config::Config::relays).
+
+use crate::{
+    config::Role,
+    error::{AddressError, Error},
+    protocol::{Direction, KeepAlive, TransportEvent as ProtocolEvent, TransportService},
+    substream::Substream,
+    transport::{
+        common::multiaddr::{parse_multiaddr, ParsedAddress},
+        manager::{SupportedTransport, TransportHandle},
+        memory::connection::{MemoryConnection, NegotiatedConnection},
+        relay::config::Config,
+        Endpoint, Transport, TransportEvent, MAX_INBOUND_SUBSTREAMS,
+    },
+    types::{ConnectionId, SubstreamId},
+    PeerId,
+};
+
+use futures::{
+    future::BoxFuture,
+    stream::{FuturesUnordered, Stream, StreamExt},
+};
+use multiaddr::{Multiaddr, Protocol};
+use multihash::Multihash;
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::ErrorKind,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+pub(crate) mod protocol;
+
+pub mod config;
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::relay";
+
+/// Size of the in-process buffer in each direction of the pipe a circuit is bridged to.
+const CIRCUIT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How long before its expiration a reservation is renewed.
+const RESERVATION_RENEWAL_MARGIN: Duration = Duration::from_secs(60);
+
+/// Minimum time between reservation renewals.
+const MIN_RESERVATION_RENEWAL_DELAY: Duration = Duration::from_secs(5);
+
+/// How long a `hop`/`stop` exchange can take before it's abandoned.
+const CIRCUIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum number of pending `hop`/`stop` exchanges before inbound circuits are rejected.
+const MAX_PENDING_CIRCUITS: usize = 64;
+
+/// Request sent to a relay over a `hop` substream.
+#[derive(Debug)]
+enum HopRequest {
+    /// Reserve a slot with the relay.
+    Reserve,
+
+    /// Connect to `peer` through the relay.
+    Connect {
+        /// Connection ID.
+        connection_id: ConnectionId,
+
+        /// Relayed address.
+        address: Multiaddr,
+
+        /// Peer to connect to.
+        peer: PeerId,
+    },
+}
+
+/// Outcome of a `hop`/`stop` exchange.
+enum CircuitEvent {
+    /// Reservation with `relay` has been answered.
+    Reserved {
+        /// Relay.
+        relay: PeerId,
+
+        /// Expiration time of the reservation.
+        result: crate::Result<SystemTime>,
+    },
+
+    /// Outbound circuit has been answered.
+    Connected {
+        /// Connection ID.
+        connection_id: ConnectionId,
+
+        /// Relayed address.
+        address: Multiaddr,
+
+        /// Substream connected to the remote peer.
+        result: crate::Result<Substream>,
+    },
+
+    /// Inbound circuit has been answered.
+    Accepted {
+        /// Relay.
+        relay: PeerId,
+
+        /// Relayed address of the remote peer and the substream connected to it.
+        result: crate::Result<(Multiaddr, Substream)>,
+    },
+}
+
+/// Relay transport.
+pub(crate) struct RelayTransport {
+    /// Transport context.
+    context: TransportHandle,
+
+    /// Transport configuration.
+    config: Config,
+
+    /// Transport service of the `hop` protocol.
+    hop: TransportService,
+
+    /// Transport service of the `stop` protocol.
+    stop: TransportService,
+
+    /// Connected peers and their addresses, used to construct the relayed addresses of inbound
+    /// circuits.
+    peers: HashMap<PeerId, Multiaddr>,
+
+    /// Relays with an active reservation.
+    reservations: HashSet<PeerId>,
+
+    /// Requests waiting for the connection to the relay to be established.
+    pending_relays: HashMap<PeerId, Vec<HopRequest>>,
+
+    /// Requests waiting for the `hop` substream to open.
+    pending_substreams: HashMap<SubstreamId, (PeerId, HopRequest)>,
+
+    /// Pending dials.
+    pending_dials: HashMap<ConnectionId, Multiaddr>,
+
+    /// In-flight dials started by [`Transport::dial()`], as opposed to [`Transport::open()`].
+    dials: HashSet<ConnectionId>,
+
+    /// Number of circuits still being opened for each connection started by
+    /// [`Transport::open()`] and the errors of the addresses that have failed.
+    pending_opens: HashMap<ConnectionId, (usize, Vec<(Multiaddr, Error)>)>,
+
+    /// Pending `hop`/`stop` exchanges.
+    pending_circuits: FuturesUnordered<BoxFuture<'static, CircuitEvent>>,
+
+    /// Pending reservation renewals.
+    pending_renewals: FuturesUnordered<BoxFuture<'static, PeerId>>,
+
+    /// Pending negotiations.
+    pending_connections:
+        FuturesUnordered<BoxFuture<'static, Result<NegotiatedConnection, (ConnectionId, Error)>>>,
+
+    /// Pending events.
+    pending_events: VecDeque<TransportEvent>,
+
+    /// Opened raw connection, waiting for approval/rejection from `TransportManager`.
+    opened_raw: HashMap<ConnectionId, (Substream, Multiaddr)>,
+
+    /// Connections which have been opened and negotiated but are being validated by the
+    /// `TransportManager`.
+    pending_open: HashMap<ConnectionId, NegotiatedConnection>,
+}
+
+impl RelayTransport {
+    /// Create new [`RelayTransport`].
+    ///
+    /// `hop` and `stop` are the transport services of the `hop` and `stop` protocols and the
+    /// returned addresses are the relayed listen addresses of the local node.
+    pub(crate) fn new(
+        context: TransportHandle,
+        mut config: Config,
+        mut hop: TransportService,
+        stop: TransportService,
+    ) -> crate::Result<(Self, Vec<Multiaddr>)> {
+        tracing::debug!(target: LOG_TARGET, relays = ?config.relays, "start relay transport");
+
+        let mut listen_addresses = Vec::new();
+        let mut pending_relays = HashMap::new();
+
+        for address in std::mem::take(&mut config.relays) {
+            let relay = match parse_multiaddr(&address) {
+                Ok(ParsedAddress {
+                    transport,
+                    peer: Some(relay),
+                    ..
+                }) if transport != SupportedTransport::Relay => relay,
+                _ => {
+                    tracing::debug!(target: LOG_TARGET, ?address, "invalid relay address");
+                    return Err(Error::AddressError(AddressError::InvalidProtocol));
+                }
+            };
+
+            hop.dial_address(address.clone())?;
+            pending_relays.insert(relay, vec![HopRequest::Reserve]);
+            listen_addresses.push(address.with(Protocol::P2pCircuit));
+        }
+
+        Ok((
+            Self {
+                context,
+                config,
+                hop,
+                stop,
+                peers: HashMap::new(),
+                reservations: HashSet::new(),
+                pending_relays,
+                pending_substreams: HashMap::new(),
+                pending_dials: HashMap::new(),
+                dials: HashSet::new(),
+                pending_opens: HashMap::new(),
+                pending_circuits: FuturesUnordered::new(),
+                pending_renewals: FuturesUnordered::new(),
+                pending_connections: FuturesUnordered::new(),
+                pending_events: VecDeque::new(),
+                opened_raw: HashMap::new(),
+                pending_open: HashMap::new(),
+            },
+            listen_addresses,
+        ))
+    }
+
+    /// Get how long to wait before renewing a reservation which expires in `remaining`.
+    ///
+    /// The reservation is renewed [`RESERVATION_RENEWAL_MARGIN`] before it expires, but not
+    /// before half of the remaining time has passed so short reservations aren't renewed in a
+    /// tight loop.
+    fn renewal_delay(remaining: Duration) -> Duration {
+        remaining
+            .saturating_sub(RESERVATION_RENEWAL_MARGIN)
+            .max(remaining / 2)
+            .max(MIN_RESERVATION_RENEWAL_DELAY)
+    }
+
+    /// Split relayed `address` into the address of the relay, the relay and the remote peer.
+    fn circuit_address(address: &Multiaddr) -> crate::Result<(Multiaddr, PeerId, PeerId)> {
+        let peer = match parse_multiaddr(address)? {
+            ParsedAddress {
+                transport: SupportedTransport::Relay,
+                peer: Some(peer),
+                ..
+            } => peer,
+            ParsedAddress {
+                transport: SupportedTransport::Relay,
+                peer: None,
+                ..
+            } => return Err(Error::AddressError(AddressError::PeerIdMissing)),
+            _ => return Err(Error::AddressError(AddressError::InvalidProtocol)),
+        };
+
+        let relay_address: Multiaddr = address
+            .iter()
+            .take_while(|protocol| !std::matches!(protocol, Protocol::P2pCircuit))
+            .collect();
+        let relay = PeerId::try_from_multiaddr(&relay_address)
+            .ok_or(Error::AddressError(AddressError::InvalidPeerId))?;
+
+        Ok((relay_address, relay, peer))
+    }
+
+    /// Send `request` to `relay`, dialing `address` first if the relay is not connected.
+    fn request(&mut self, relay: PeerId, address: Multiaddr, request: HopRequest) {
+        if self.peers.contains_key(&relay) {
+            return self.open_hop_substream(relay, request);
+        }
+
+        if let Some(requests) = self.pending_relays.get_mut(&relay) {
+            requests.push(request);
+            return;
+        }
+
+        tracing::trace!(target: LOG_TARGET, ?relay, ?address, "dial relay");
+
+        match self.hop.dial_address(address) {
+            Ok(()) => {
+                self.pending_relays.insert(relay, vec![request]);
+            }
+            Err(error) => self.on_request_failure(relay, request, error),
+        }
+    }
+
+    /// Open `hop` substream to `relay` for `request`.
+    fn open_hop_substream(&mut self, relay: PeerId, request: HopRequest) {
+        match self.hop.open_substream(relay) {
+            Ok(substream_id) => {
+                self.pending_substreams.insert(substream_id, (relay, request));
+            }
+            Err(error) => self.on_request_failure(relay, request, error),
+        }
+    }
+
+    /// Handle failure to send `request` to `relay`.
+    fn on_request_failure(&mut self, relay: PeerId, request: HopRequest, error: Error) {
+        match request {
+            HopRequest::Reserve => {
+                tracing::debug!(target: LOG_TARGET, ?relay, ?error, "failed to reserve slot");
+            }
+            HopRequest::Connect {
+                connection_id,
+                address,
+                ..
+            } => self.on_circuit_failure(connection_id, address, error),
+        }
+    }
+
+    /// Handle failure to open a circuit for `connection_id`.
+    fn on_circuit_failure(
+        &mut self,
+        connection_id: ConnectionId,
+        address: Multiaddr,
+        error: Error,
+    ) {
+        tracing::debug!(
+            target: LOG_TARGET,
+            ?connection_id,
+            ?address,
+            ?error,
+            "failed to open circuit",
+        );
+
+        if self.dials.remove(&connection_id) {
+            self.pending_dials.remove(&connection_id);
+            self.pending_events.push_back(TransportEvent::DialFailure {
+                connection_id,
+                address,
+                error: error.into_dial_error(),
+            });
+            return;
+        }
+
+        if let Some((pending, errors)) = self.pending_opens.get_mut(&connection_id) {
+            *pending -= 1;
+            errors.push((address, error.into_dial_error()));
+
+            if *pending == 0 {
+                let (_, errors) =
+                    self.pending_opens.remove(&connection_id).expect("pending open to exist");
+                self.pending_events.push_back(TransportEvent::OpenFailure {
+                    connection_id,
+                    errors,
+                });
+            }
+        }
+    }
+
+    /// Handle an opened `hop` substream.
+    fn on_hop_substream(&mut self, relay: PeerId, request: HopRequest, mut substream: Substream) {
+        match request {
+            HopRequest::Reserve => self.pending_circuits.push(Box::pin(async move {
+                CircuitEvent::Reserved {
+                    relay,
+                    result: tokio::time::timeout(
+                        CIRCUIT_TIMEOUT,
+                        protocol::reserve(&mut substream),
+                    )
+                    .await
+                    .unwrap_or(Err(Error::Timeout)),
+                }
+            })),
+            HopRequest::Connect {
+                connection_id,
+                address,
+                peer,
+            } => self.pending_circuits.push(Box::pin(async move {
+                let result =
+                    tokio::time::timeout(CIRCUIT_TIMEOUT, protocol::connect(&mut substream, peer))
+                        .await
+                        .unwrap_or(Err(Error::Timeout))
+                        .map(|limit| {
+                            tracing::trace!(target: LOG_TARGET, ?peer, ?limit, "circuit opened");
+                            substream
+                        });
+
+                CircuitEvent::Connected {
+                    connection_id,
+                    address,
+                    result,
+                }
+            })),
+        }
+    }
+
+    /// Handle an inbound `stop` substream.
+    fn on_stop_substream(&mut self, relay: PeerId, mut substream: Substream) {
+        let relay_address = match self.peers.get(&relay) {
+            Some(address) if self.reservations.contains(&relay) => address.clone(),
+            _ => {
+                tracing::debug!(target: LOG_TARGET, ?relay, "no reservation with relay");
+                return;
+            }
+        };
+
+        if self.pending_circuits.len() >= MAX_PENDING_CIRCUITS {
+            tracing::debug!(target: LOG_TARGET, ?relay, "too many pending circuits");
+            return;
+        }
+
+        self.pending_circuits.push(Box::pin(async move {
+            let result = tokio::time::timeout(CIRCUIT_TIMEOUT, protocol::accept(&mut substream))
+                .await
+                .unwrap_or(Err(Error::Timeout))
+                .map(|(peer, limit)| {
+                    tracing::trace!(target: LOG_TARGET, ?relay, ?peer, ?limit, "circuit accepted");
+
+                    let address = relay_address
+                        .with(Protocol::P2pCircuit)
+                        .with(Protocol::P2p(Multihash::from(peer)));
+                    (address, substream)
+                });
+
+            CircuitEvent::Accepted { relay, result }
+        }));
+    }
+
+    /// Handle answered `hop`/`stop` exchange.
+    fn on_circuit_event(&mut self, event: CircuitEvent) {
+        match event {
+            CircuitEvent::Reserved {
+                relay,
+                result: Ok(expires),
+            } => {
+                tracing::debug!(target: LOG_TARGET, ?relay, ?expires, "slot reserved with relay");
+
+                self.reservations.insert(relay);
+                let _ = self.hop.set_keep_alive(relay, KeepAlive::Yes);
+
+                let renew_after = Self::renewal_delay(
+                    expires.duration_since(SystemTime::now()).unwrap_or_default(),
+                );
+
+                self.pending_renewals.push(Box::pin(async move {
+                    tokio::time::sleep(renew_after).await;
+                    relay
+                }));
+            }
+            CircuitEvent::Reserved {
+                relay,
+                result: Err(error),
+            } => {
+                tracing::debug!(target: LOG_TARGET, ?relay, ?error, "failed to reserve slot");
+                self.reservations.remove(&relay);
+            }
+            CircuitEvent::Connected {
+                connection_id,
+                address,
+                result: Ok(substream),
+            } => {
+                if self.dials.contains(&connection_id) {
+                    let peer = PeerId::try_from_multiaddr(&address);
+                    let future = self.negotiate_connection(
+                        connection_id,
+                        substream,
+                        Role::Dialer,
+                        address,
+                        peer,
+                    );
+                    self.pending_connections.push(future);
+                    return;
+                }
+
+                // the first opened circuit is reported and the rest are dropped
+                if self.pending_opens.remove(&connection_id).is_some() {
+                    self.opened_raw.insert(connection_id, (substream, address.clone()));
+                    self.pending_events.push_back(TransportEvent::ConnectionOpened {
+                        connection_id,
+                        address,
+                    });
+                }
+            }
+            CircuitEvent::Connected {
+                connection_id,
+                address,
+                result: Err(error),
+            } => self.on_circuit_failure(connection_id, address, error),
+            CircuitEvent::Accepted {
+                relay,
+                result: Err(error),
+            } => {
+                tracing::debug!(target: LOG_TARGET, ?relay, ?error, "failed to accept circuit");
+            }
+            CircuitEvent::Accepted {
+                result: Ok((address, substream)),
+                ..
+            } => {
+                let connection_id = self.context.next_connection_id();
+
+                tracing::trace!(
+                    target: LOG_TARGET,
+                    ?connection_id,
+                    ?address,
+                    "accept inbound circuit",
+                );
+
+                let future = self.negotiate_connection(
+                    connection_id,
+                    substream,
+                    Role::Listener,
+                    address,
+                    None,
+                );
+                self.pending_connections.push(future);
+            }
+        }
+    }
+
+    /// Handle event received from the `hop` protocol.
+    fn on_hop_event(&mut self, event: ProtocolEvent) {
+        match event {
+            ProtocolEvent::ConnectionEstablished { peer, endpoint } => {
+                let mut address = endpoint.address().clone();
+                if std::matches!(address.iter().last(), Some(Protocol::P2p(_))) {
+                    address.pop();
+                }
+                self.peers.insert(peer, address.with(Protocol::P2p(Multihash::from(peer))));
+
+                for request in self.pending_relays.remove(&peer).unwrap_or_default() {
+                    self.open_hop_substream(peer, request);
+                }
+            }
+            ProtocolEvent::ConnectionClosed { peer } => {
+                self.peers.remove(&peer);
+
+                if self.reservations.remove(&peer) {
+                    tracing::debug!(target: LOG_TARGET, relay = ?peer, "reservation lost");
+                }
+            }
+            ProtocolEvent::DialFailure { peer, address } => {
+                tracing::trace!(target: LOG_TARGET, relay = ?peer, ?address, "failed to dial relay");
+
+                for request in self.pending_relays.remove(&peer).unwrap_or_default() {
+                    let error = Error::IoError(ErrorKind::ConnectionRefused);
+                    self.on_request_failure(peer, request, error);
+                }
+            }
+            ProtocolEvent::SubstreamOpened {
+                peer,
+                direction: Direction::Outbound(substream_id),
+                substream,
+                ..
+            } => match self.pending_substreams.remove(&substream_id) {
+                Some((_, request)) => self.on_hop_substream(peer, request, substream),
+                None => tracing::debug!(target: LOG_TARGET, ?substream_id, "unknown substream"),
+            },
+            ProtocolEvent::SubstreamOpened {
+                peer,
+                direction: Direction::Inbound,
+                ..
+            } => {
+                tracing::debug!(target: LOG_TARGET, ?peer, "relaying is not supported");
+            }
+            ProtocolEvent::SubstreamOpenFailure { substream, error } =>
+                if let Some((relay, request)) = self.pending_substreams.remove(&substream) {
+                    self.on_request_failure(relay, request, error);
+                },
+            ProtocolEvent::SecondaryConnectionEstablished { .. } => {}
+        }
+    }
+
+    /// Negotiate the connection over the circuit established over `substream`.
+    fn negotiate_connection(
+        &self,
+        connection_id: ConnectionId,
+        mut substream: Substream,
+        role: Role,
+        address: Multiaddr,
+        peer: Option<PeerId>,
+    ) -> BoxFuture<'static, Result<NegotiatedConnection, (ConnectionId, Error)>> {
+        let keypair = self.context.keypair.clone();
+        let bandwidth_sink = self.context.bandwidth_sink.clone();
+        let yamux_config = self.config.yamux_config.clone();
+        let max_read_ahead_factor = self.config.noise_read_ahead_frame_count;
+        let max_write_buffer_size = self.config.noise_write_buffer_size;
+        let connection_open_timeout = self.config.connection_open_timeout;
+        let substream_open_timeout = self.config.substream_open_timeout;
+
+        // bridge the substream to a pipe so the circuit can be upgraded like a memory connection
+        let (stream, mut pipe) = tokio::io::duplex(CIRCUIT_BUFFER_SIZE);
+        self.context.executor.run(Box::pin(async move {
+            if let Err(error) = tokio::io::copy_bidirectional(&mut substream, &mut pipe).await {
+                tracing::trace!(target: LOG_TARGET, ?connection_id, ?error, "circuit closed");
+            }
+        }));
+
+        Box::pin(async move {
+            match tokio::time::timeout(
+                connection_open_timeout,
+                MemoryConnection::negotiate_connection(
+                    stream,
+                    peer,
+                    connection_id,
+                    keypair,
+                    bandwidth_sink,
+                    role,
+                    address,
+                    yamux_config,
+                    max_read_ahead_factor,
+                    max_write_buffer_size,
+                    substream_open_timeout,
+                ),
+            )
+            .await
+            {
+                Err(_) => Err((connection_id, Error::Timeout)),
+                Ok(Err(error)) => Err((connection_id, error)),
+                Ok(Ok(connection)) => Ok(connection),
+            }
+        })
+    }
+}
+
+impl Transport for RelayTransport {
+    fn dial(&mut self, connection_id: ConnectionId, address: Multiaddr) -> crate::Result<()> {
+        tracing::debug!(target: LOG_TARGET, ?connection_id, ?address, "open connection");
+
+        let (relay_address, relay, peer) = Self::circuit_address(&address)?;
+
+        self.dials.insert(connection_id);
+        self.pending_dials.insert(connection_id, address.clone());
+        self.request(
+            relay,
+            relay_address,
+            HopRequest::Connect {
+                connection_id,
+                address,
+                peer,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn accept(&mut self, connection_id: ConnectionId) -> crate::Result<()> {
+        let context = self
+            .pending_open
+            .remove(&connection_id)
+            .ok_or(Error::ConnectionDoesntExist(connection_id))?;
+        let protocol_set = self.context.protocol_set(connection_id);
+        let bandwidth_sink = self.context.bandwidth_sink.clone();
+        let next_substream_id = self.context.next_substream_id.clone();
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?connection_id,
+            "start connection",
+        );
+
+        self.context.executor.run(Box::pin(async move {
+            if let Err(error) = MemoryConnection::new(
+                context,
+                protocol_set,
+                bandwidth_sink,
+                next_substream_id,
+                MAX_INBOUND_SUBSTREAMS,
+            )
+            .start()
+            .await
+            {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?connection_id,
+                    ?error,
+                    "connection exited with error",
+                );
+            }
+        }));
+
+        Ok(())
+    }
+
+    fn reject(&mut self, connection_id: ConnectionId) -> crate::Result<()> {
+        self.pending_open
+            .remove(&connection_id)
+            .map_or(Err(Error::ConnectionDoesntExist(connection_id)), |_| Ok(()))
+    }
+
+    fn open(
+        &mut self,
+        connection_id: ConnectionId,
+        addresses: Vec<Multiaddr>,
+    ) -> crate::Result<()> {
+        let mut errors = Vec::new();
+        let circuits = addresses
+            .into_iter()
+            .filter_map(|address| match Self::circuit_address(&address) {
+                Ok((relay_address, relay, peer)) => Some((relay_address, relay, peer, address)),
+                Err(error) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?connection_id,
+                        ?address,
+                        ?error,
+                        "invalid relayed address",
+                    );
+                    errors.push((address, error.into_dial_error()));
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if circuits.is_empty() {
+            self.pending_events.push_back(TransportEvent::OpenFailure {
+                connection_id,
+                errors,
+            });
+            return Ok(());
+        }
+
+        self.pending_opens.insert(connection_id, (circuits.len(), errors));
+
+        for (relay_address, relay, peer, address) in circuits {
+            self.request(
+                relay,
+                relay_address,
+                HopRequest::Connect {
+                    connection_id,
+                    address,
+                    peer,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    fn negotiate(&mut self, connection_id: ConnectionId) -> crate::Result<()> {
+        let (substream, address) = self
+            .opened_raw
+            .remove(&connection_id)
+            .ok_or(Error::ConnectionDoesntExist(connection_id))?;
+        let peer = PeerId::try_from_multiaddr(&address);
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            ?peer,
+            ?connection_id,
+            ?address,
+            "negotiate connection",
+        );
+
+        let future = self.negotiate_connection(
+            connection_id,
+            substream,
+            Role::Dialer,
+            address.clone(),
+            peer,
+        );
+
+        self.pending_dials.insert(connection_id, address);
+        self.pending_connections.push(future);
+
+        Ok(())
+    }
+
+    fn cancel(&mut self, connection_id: ConnectionId) {
+        if self.dials.remove(&connection_id) {
+            if let Some(address) = self.pending_dials.remove(&connection_id) {
+                self.pending_events.push_back(TransportEvent::DialFailure {
+                    connection_id,
+                    address,
+                    error: Error::DialAborted,
+                });
+            }
+            return;
+        }
+
+        // circuits that are still being opened are dropped once they've been answered
+        self.pending_opens.remove(&connection_id);
+        self.pending_dials.remove(&connection_id);
+        self.opened_raw.remove(&connection_id);
+        self.pending_events.retain(|event| match event {
+            TransportEvent::ConnectionOpened {
+                connection_id: id, ..
+            }
+            | TransportEvent::OpenFailure {
+                connection_id: id, ..
+            } => id != &connection_id,
+            _ => true,
+        });
+    }
+}
+
+impl Stream for RelayTransport {
+    type Item = TransportEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        while let Poll::Ready(Some(event)) = self.hop.poll_next_unpin(cx) {
+            self.on_hop_event(event);
+        }
+
+        while let Poll::Ready(Some(event)) = self.stop.poll_next_unpin(cx) {
+            if let ProtocolEvent::SubstreamOpened {
+                peer,
+                direction: Direction::Inbound,
+                substream,
+                ..
+            } = event
+            {
+                self.on_stop_substream(peer, substream);
+            }
+        }
+
+        while let Poll::Ready(Some(event)) = self.pending_circuits.poll_next_unpin(cx) {
+            self.on_circuit_event(event);
+        }
+
+        while let Poll::Ready(Some(relay)) = self.pending_renewals.poll_next_unpin(cx) {
+            if self.reservations.contains(&relay) {
+                tracing::trace!(target: LOG_TARGET, ?relay, "renew reservation");
+                self.open_hop_substream(relay, HopRequest::Reserve);
+            }
+        }
+
+        if let Some(event) = self.pending_events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+
+        while let Poll::Ready(Some(connection)) = self.pending_connections.poll_next_unpin(cx) {
+            match connection {
+                Ok(connection) => {
+                    let connection_id = connection.connection_id();
+                    let endpoint = connection.endpoint();
+
+                    // the dial was canceled while the connection was being negotiated
+                    self.dials.remove(&connection_id);
+                    if std::matches!(endpoint, Endpoint::Dialer { .. })
+                        && self.pending_dials.remove(&connection_id).is_none()
+                    {
+                        continue;
+                    }
+
+                    let peer = connection.peer();
+                    self.pending_open.insert(connection_id, connection);
+
+                    return Poll::Ready(Some(TransportEvent::ConnectionEstablished {
+                        peer,
+                        endpoint,
+                        transport: SupportedTransport::Relay,
+                    }));
+                }
+                Err((connection_id, error)) => {
+                    self.dials.remove(&connection_id);
+
+                    if let Some(address) = self.pending_dials.remove(&connection_id) {
+                        return Poll::Ready(Some(TransportEvent::DialFailure {
+                            connection_id,
+                            address,
+                            error: error.into_dial_error(),
+                        }));
+                    }
+                }
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reservation_renewal_delay() {
+        assert_eq!(
+            RelayTransport::renewal_delay(Duration::from_secs(3600)),
+            Duration::from_secs(3540)
+        );
+        assert_eq!(
+            RelayTransport::renewal_delay(Duration::from_secs(60)),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            RelayTransport::renewal_delay(Duration::ZERO),
+            MIN_RESERVATION_RENEWAL_DELAY
+        );
+    }
+}
@@ -0,0 +1,373 @@
+// Copyright 2024 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Circuit relay v2 `hop` and `stop` messages.
+//!
+//! The messages are exchanged over substreams opened for [`HOP_PROTOCOL`] and [`STOP_PROTOCOL`]
+//! and they're prefixed with their length encoded as an unsigned varint. Once a circuit has been
+//! established, the substream carries the relayed connection so the messages are read one byte at
+//! a time to make sure no bytes of the relayed connection are consumed.
+
+use crate::{
+    error::{Error, RelayError},
+    PeerId,
+};
+
+use prost::Message;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) mod schema {
+    pub(crate) mod relay {
+        include!(concat!(env!("OUT_DIR"), "/relay.rs"));
+    }
+}
+
+use schema::relay::{hop_message, stop_message, HopMessage, Peer, Status, StopMessage};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::relay::protocol";
+
+/// Protocol name of the `hop` protocol, used to talk to the relay.
+pub(crate) const HOP_PROTOCOL: &str = "/libp2p/circuit/relay/0.2.0/hop";
+
+/// Protocol name of the `stop` protocol, used by the relay to talk to the destination.
+pub(crate) const STOP_PROTOCOL: &str = "/libp2p/circuit/relay/0.2.0/stop";
+
+/// Maximum size of a `hop`/`stop` message.
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Circuit limits announced by the relay.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CircuitLimit {
+    /// Maximum duration of the circuit.
+    pub(crate) duration: Option<Duration>,
+
+    /// Maximum number of bytes relayed in each direction.
+    pub(crate) data: Option<u64>,
+}
+
+impl From<schema::relay::Limit> for CircuitLimit {
+    fn from(limit: schema::relay::Limit) -> Self {
+        Self {
+            duration: limit.duration.map(|duration| Duration::from_secs(duration as u64)),
+            data: limit.data,
+        }
+    }
+}
+
+/// Convert `status` of a response into a result.
+pub(crate) fn status_to_result(status: Status) -> crate::Result<()> {
+    match status {
+        Status::Ok => Ok(()),
+        Status::ReservationRefused => Err(Error::RelayError(RelayError::ReservationRefused)),
+        Status::ResourceLimitExceeded => Err(Error::RelayError(RelayError::ResourceLimitExceeded)),
+        Status::PermissionDenied => Err(Error::RelayError(RelayError::PermissionDenied)),
+        Status::ConnectionFailed => Err(Error::RelayError(RelayError::ConnectionFailed)),
+        Status::NoReservation => Err(Error::RelayError(RelayError::NoReservation)),
+        Status::UnexpectedMessage => Err(Error::RelayError(RelayError::UnexpectedMessage)),
+        Status::MalformedMessage | Status::Unused =>
+            Err(Error::RelayError(RelayError::MalformedMessage)),
+    }
+}
+
+/// Read one length-prefixed message from `io`.
+pub(crate) async fn read_message<S: AsyncRead + Unpin>(io: &mut S) -> crate::Result<Vec<u8>> {
+    let mut buffer = unsigned_varint::encode::usize_buffer();
+    let mut nread = 0usize;
+
+    loop {
+        if nread == buffer.len() {
+            return Err(Error::InvalidData);
+        }
+
+        buffer[nread] = io.read_u8().await?;
+        nread += 1;
+
+        if buffer[nread - 1] & 0x80 == 0 {
+            break;
+        }
+    }
+
+    let (size, _) =
+        unsigned_varint::decode::usize(&buffer[..nread]).map_err(|_| Error::InvalidData)?;
+
+    if size > MAX_MESSAGE_SIZE {
+        return Err(Error::FrameTooLarge(size));
+    }
+
+    let mut message = vec![0u8; size];
+    io.read_exact(&mut message).await?;
+
+    Ok(message)
+}
+
+/// Write `message` to `io`, prefixed with its length.
+pub(crate) async fn write_message<S: AsyncWrite + Unpin>(
+    io: &mut S,
+    message: impl Message,
+) -> crate::Result<()> {
+    let mut buffer = unsigned_varint::encode::usize_buffer();
+    let length = unsigned_varint::encode::usize(message.encoded_len(), &mut buffer);
+
+    let mut payload = Vec::with_capacity(length.len() + message.encoded_len());
+    payload.extend_from_slice(length);
+    message.encode(&mut payload).expect("`payload` to have enough capacity");
+
+    io.write_all(&payload).await?;
+    io.flush().await.map_err(From::from)
+}
+
+/// Reserve a slot with the relay over a `hop` substream.
+///
+/// Returns the expiration time of the reservation.
+pub(crate) async fn reserve<S: AsyncRead + AsyncWrite + Unpin>(
+    io: &mut S,
+) -> crate::Result<SystemTime> {
+    let mut request = HopMessage::default();
+    request.set_type(hop_message::Type::Reserve);
+    write_message(io, request).await?;
+
+    let response = HopMessage::decode(read_message(io).await?.as_slice())?;
+
+    if response.r#type() != hop_message::Type::Status {
+        return Err(Error::RelayError(RelayError::UnexpectedMessage));
+    }
+    status_to_result(response.status())?;
+
+    let reservation =
+        response.reservation.ok_or(Error::RelayError(RelayError::MalformedMessage))?;
+
+    tracing::trace!(
+        target: LOG_TARGET,
+        expire = ?reservation.expire,
+        limit = ?response.limit.map(CircuitLimit::from),
+        "reservation accepted",
+    );
+
+    UNIX_EPOCH
+        .checked_add(Duration::from_secs(reservation.expire))
+        .ok_or(Error::RelayError(RelayError::MalformedMessage))
+}
+
+/// Request the relay to connect to `peer` over a `hop` substream.
+///
+/// Once the call returns successfully, `io` is connected to `peer`.
+pub(crate) async fn connect<S: AsyncRead + AsyncWrite + Unpin>(
+    io: &mut S,
+    peer: PeerId,
+) -> crate::Result<CircuitLimit> {
+    let mut request = HopMessage {
+        peer: Some(Peer {
+            id: peer.to_bytes(),
+            addrs: Vec::new(),
+        }),
+        ..Default::default()
+    };
+    request.set_type(hop_message::Type::Connect);
+    write_message(io, request).await?;
+
+    let response = HopMessage::decode(read_message(io).await?.as_slice())?;
+
+    if response.r#type() != hop_message::Type::Status {
+        return Err(Error::RelayError(RelayError::UnexpectedMessage));
+    }
+    status_to_result(response.status())?;
+
+    Ok(response.limit.map(CircuitLimit::from).unwrap_or_default())
+}
+
+/// Accept a circuit requested by the relay over a `stop` substream.
+///
+/// Returns the `PeerId` of the node which initiated the circuit. Once the call returns
+/// successfully, `io` is connected to that node.
+pub(crate) async fn accept<S: AsyncRead + AsyncWrite + Unpin>(
+    io: &mut S,
+) -> crate::Result<(PeerId, CircuitLimit)> {
+    let request = StopMessage::decode(read_message(io).await?.as_slice())?;
+
+    let peer = match (request.r#type(), request.peer) {
+        (stop_message::Type::Connect, Some(peer)) => PeerId::from_bytes(&peer.id),
+        _ => {
+            let mut response = StopMessage::default();
+            response.set_type(stop_message::Type::Status);
+            response.set_status(Status::UnexpectedMessage);
+            write_message(io, response).await?;
+
+            return Err(Error::RelayError(RelayError::UnexpectedMessage));
+        }
+    };
+
+    let mut response = StopMessage::default();
+    response.set_type(stop_message::Type::Status);
+
+    match peer {
+        Ok(peer) => {
+            response.set_status(Status::Ok);
+            write_message(io, response).await?;
+
+            Ok((
+                peer,
+                request.limit.map(CircuitLimit::from).unwrap_or_default(),
+            ))
+        }
+        Err(_) => {
+            response.set_status(Status::MalformedMessage);
+            write_message(io, response).await?;
+
+            Err(Error::RelayError(RelayError::MalformedMessage))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn connect_doesnt_consume_relayed_bytes() {
+        let (mut io1, mut io2) = tokio::io::duplex(1024);
+        let peer = PeerId::random();
+
+        let relay = async move {
+            let request =
+                HopMessage::decode(read_message(&mut io2).await.unwrap().as_slice()).unwrap();
+            assert_eq!(request.r#type(), hop_message::Type::Connect);
+            assert_eq!(PeerId::from_bytes(&request.peer.unwrap().id).unwrap(), peer);
+
+            let mut response = HopMessage {
+                limit: Some(schema::relay::Limit {
+                    duration: Some(120),
+                    data: Some(1024),
+                }),
+                ..Default::default()
+            };
+            response.set_type(hop_message::Type::Status);
+            response.set_status(Status::Ok);
+            write_message(&mut io2, response).await.unwrap();
+
+            // bytes of the relayed connection may follow the response immediately
+            io2.write_all(b"hello").await.unwrap();
+        };
+
+        let (limit, _) = tokio::join!(connect(&mut io1, peer), relay);
+        assert_eq!(
+            limit.unwrap(),
+            CircuitLimit {
+                duration: Some(Duration::from_secs(120)),
+                data: Some(1024),
+            }
+        );
+
+        let mut buffer = [0u8; 5];
+        io1.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"hello");
+    }
+
+    #[tokio::test]
+    async fn accept_circuit() {
+        let (mut io1, mut io2) = tokio::io::duplex(1024);
+        let initiator = PeerId::random();
+
+        let relay = async move {
+            let mut request = StopMessage {
+                peer: Some(Peer {
+                    id: initiator.to_bytes(),
+                    addrs: Vec::new(),
+                }),
+                ..Default::default()
+            };
+            request.set_type(stop_message::Type::Connect);
+            write_message(&mut io2, request).await.unwrap();
+
+            let response =
+                StopMessage::decode(read_message(&mut io2).await.unwrap().as_slice()).unwrap();
+            assert_eq!(response.r#type(), stop_message::Type::Status);
+            assert_eq!(response.status(), Status::Ok);
+        };
+
+        let (result, _) = tokio::join!(accept(&mut io1), relay);
+        let (peer, limit) = result.unwrap();
+
+        assert_eq!(peer, initiator);
+        assert_eq!(limit, CircuitLimit::default());
+    }
+
+    #[tokio::test]
+    async fn status_is_reported() {
+        let (mut io1, mut io2) = tokio::io::duplex(1024);
+
+        let relay = async move {
+            read_message(&mut io2).await.unwrap();
+
+            let mut response = HopMessage::default();
+            response.set_type(hop_message::Type::Status);
+            response.set_status(Status::NoReservation);
+            write_message(&mut io2, response).await.unwrap();
+        };
+
+        let (result, _) = tokio::join!(connect(&mut io1, PeerId::random()), relay);
+        assert!(std::matches!(
+            result,
+            Err(Error::RelayError(RelayError::NoReservation))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reservation_with_invalid_expiration_rejected() {
+        let (mut io1, mut io2) = tokio::io::duplex(1024);
+
+        let relay = async move {
+            read_message(&mut io2).await.unwrap();
+
+            let mut response = HopMessage {
+                reservation: Some(schema::relay::Reservation {
+                    expire: u64::MAX,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            response.set_type(hop_message::Type::Status);
+            response.set_status(Status::Ok);
+            write_message(&mut io2, response).await.unwrap();
+        };
+
+        let (result, _) = tokio::join!(reserve(&mut io1), relay);
+        assert!(std::matches!(
+            result,
+            Err(Error::RelayError(RelayError::MalformedMessage))
+        ));
+    }
+
+    #[tokio::test]
+    async fn oversized_message_rejected() {
+        let (mut io1, mut io2) = tokio::io::duplex(1024);
+        let mut buffer = unsigned_varint::encode::usize_buffer();
+
+        let length = unsigned_varint::encode::usize(MAX_MESSAGE_SIZE + 1, &mut buffer);
+        io2.write_all(length).await.unwrap();
+
+        assert!(std::matches!(
+            read_message(&mut io1).await,
+            Err(Error::FrameTooLarge(size)) if size == MAX_MESSAGE_SIZE + 1
+        ));
+    }
+}
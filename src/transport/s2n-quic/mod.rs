@@ -34,7 +34,6 @@ use crate::{
 
 use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
 use multiaddr::{Multiaddr, Protocol};
-use multihash::Multihash;
 use s2n_quic::{
     client::Connect,
     connection::{Connection, Error as ConnectionError},
@@ -200,9 +199,7 @@ impl QuicTransport {
                             .with(Protocol::from(address.ip()))
                             .with(Protocol::Udp(address.port()))
                             .with(Protocol::QuicV1)
-                            .with(Protocol::P2p(
-                                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-                            ))
+                            .with(Protocol::P2p(peer.to_multihash()))
                     }
                 };
 
@@ -506,9 +503,7 @@ mod tests {
             .with(Protocol::from(std::net::Ipv4Addr::new(255, 254, 253, 252)))
             .with(Protocol::Udp(8888))
             .with(Protocol::QuicV1)
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
         manager.dial_address(address.clone()).await.unwrap();
 
         assert!(transport.pending_dials.is_empty());
@@ -551,9 +546,7 @@ mod tests {
             .with(Protocol::from(std::net::Ipv4Addr::new(255, 254, 253, 252)))
             .with(Protocol::Udp(8888))
             .with(Protocol::QuicV1)
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer.to_multihash()));
 
         assert!(transport.pending_dials.is_empty());
 
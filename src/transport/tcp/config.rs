@@ -21,10 +21,18 @@
 //! TCP transport configuration.
 
 use crate::{
-    crypto::noise::{MAX_READ_AHEAD_FACTOR, MAX_WRITE_BUFFER_SIZE},
-    transport::{CONNECTION_OPEN_TIMEOUT, SUBSTREAM_OPEN_TIMEOUT},
+    crypto::{
+        noise::{MAX_READ_AHEAD_FACTOR, MAX_WRITE_BUFFER_SIZE},
+        SecurityProtocol,
+    },
+    transport::{
+        filter::ConnectionFilter, MultistreamSelectVersion, CONNECTION_OPEN_TIMEOUT,
+        MAX_INBOUND_SUBSTREAMS, MAX_PENDING_NEGOTIATIONS, SUBSTREAM_OPEN_TIMEOUT,
+    },
 };
 
+use std::sync::Arc;
+
 /// TCP transport configuration.
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -43,9 +51,44 @@ pub struct Config {
 
     /// Enable `TCP_NODELAY`.
     ///
-    /// Defaults to `false`.
+    /// Applied to both dialed and accepted sockets.
+    ///
+    /// Defaults to `true`.
     pub nodelay: bool,
 
+    /// Size of the socket send buffer (`SO_SNDBUF`).
+    ///
+    /// Applied to both dialed and accepted sockets. If `None`, the system default is used.
+    ///
+    /// Defaults to `None`.
+    pub send_buffer_size: Option<usize>,
+
+    /// Size of the socket receive buffer (`SO_RCVBUF`).
+    ///
+    /// Applied to both dialed and accepted sockets. If `None`, the system default is used.
+    ///
+    /// Defaults to `None`.
+    pub recv_buffer_size: Option<usize>,
+
+    /// Security protocols offered for connections, in order of preference.
+    ///
+    /// The protocol used for the connection is selected using `multistream-select`.
+    ///
+    /// Defaults to `[SecurityProtocol::Noise, SecurityProtocol::Tls]`.
+    pub security_protocols: Vec<SecurityProtocol>,
+
+    /// `multistream-select` version used when upgrading outbound connections.
+    ///
+    /// With [`MultistreamSelectVersion::V1Lazy`], the dialer settles on the protocol without
+    /// waiting for the confirmation of the remote if it proposes only one protocol, sending the
+    /// negotiation messages together with the first message of the upgrade and saving a round
+    /// trip. Negotiations proposing more than one protocol, such as the selection between the
+    /// default security protocols, fall back to [`MultistreamSelectVersion::V1`]. Inbound
+    /// connections are accepted from dialers using either version.
+    ///
+    /// Defaults to [`MultistreamSelectVersion::V1`].
+    pub multistream_select_version: MultistreamSelectVersion,
+
     /// Yamux configuration.
     pub yamux_config: crate::yamux::Config,
 
@@ -80,6 +123,31 @@ pub struct Config {
     /// How long should litep2p wait for a substream to be opened before considering
     /// the substream rejected.
     pub substream_open_timeout: std::time::Duration,
+
+    /// Maximum number of inbound connections negotiated concurrently.
+    ///
+    /// When the limit is reached, new inbound connections are not accepted from the listener
+    /// until one of the pending negotiations has finished.
+    ///
+    /// Defaults to `128`.
+    pub max_pending_negotiations: usize,
+
+    /// Maximum number of inbound substreams a connection can have open.
+    ///
+    /// Substreams that are still being negotiated count towards the limit. When the limit is
+    /// reached, new inbound substreams are reset until one of the open substreams is closed.
+    /// The limit is enforced separately from the stream limit of the Yamux configuration.
+    ///
+    /// Defaults to `256`.
+    pub max_inbound_substreams: usize,
+
+    /// Filter for inbound connections.
+    ///
+    /// Consulted with the remote address of each accepted connection before any protocol
+    /// negotiation takes place. Rejected connections are closed immediately.
+    ///
+    /// Defaults to `None`, i.e., all inbound connections are accepted.
+    pub connection_filter: Option<Arc<dyn ConnectionFilter>>,
 }
 
 impl Default for Config {
@@ -90,12 +158,19 @@ impl Default for Config {
                 "/ip6/::/tcp/0".parse().expect("valid address"),
             ],
             reuse_port: true,
-            nodelay: false,
+            nodelay: true,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            security_protocols: vec![SecurityProtocol::Noise, SecurityProtocol::Tls],
+            multistream_select_version: MultistreamSelectVersion::V1,
             yamux_config: Default::default(),
             noise_read_ahead_frame_count: MAX_READ_AHEAD_FACTOR,
             noise_write_buffer_size: MAX_WRITE_BUFFER_SIZE,
             connection_open_timeout: CONNECTION_OPEN_TIMEOUT,
             substream_open_timeout: SUBSTREAM_OPEN_TIMEOUT,
+            max_pending_negotiations: MAX_PENDING_NEGOTIATIONS,
+            max_inbound_substreams: MAX_INBOUND_SUBSTREAMS,
+            connection_filter: None,
         }
     }
 }
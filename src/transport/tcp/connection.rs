@@ -20,200 +20,51 @@
 
 use crate::{
     config::Role,
-    crypto::{
-        ed25519::Keypair,
-        noise::{self, NoiseSocket},
-    },
-    error::{Error, NegotiationError},
-    multistream_select::{dialer_select_proto, listener_select_proto, Negotiated, Version},
-    protocol::{Direction, Permit, ProtocolCommand, ProtocolSet},
-    substream,
+    crypto::{ed25519::Keypair, noise, tls, SecureStream, SecurityProtocol},
+    error::{Error, HandshakeError},
+    multistream_select::{Negotiated, Version},
     transport::{
-        common::listener::{AddressType, DnsType},
-        tcp::substream::Substream,
+        common::{
+            connection::{self, negotiate_protocol},
+            listener::{AddressType, DnsType},
+        },
         Endpoint,
     },
-    types::{protocol::ProtocolName, ConnectionId, SubstreamId},
+    types::ConnectionId,
     BandwidthSink, PeerId,
 };
 
-use futures::{
-    future::BoxFuture,
-    stream::{FuturesUnordered, StreamExt},
-    AsyncRead, AsyncWrite,
-};
+use futures::AsyncWriteExt;
 use multiaddr::{Multiaddr, Protocol};
 use tokio::net::TcpStream;
-use tokio_util::compat::{
-    Compat, FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt,
-};
-
-use std::{
-    borrow::Cow,
-    fmt,
-    net::SocketAddr,
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
-    },
-    time::Duration,
-};
-
-/// Logging target for the file.
-const LOG_TARGET: &str = "litep2p::tcp::connection";
-
-#[derive(Debug)]
-pub struct NegotiatedSubstream {
-    /// Substream direction.
-    direction: Direction,
-
-    /// Substream ID.
-    substream_id: SubstreamId,
-
-    /// Protocol name.
-    protocol: ProtocolName,
-
-    /// Yamux substream.
-    io: crate::yamux::Stream,
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
 
-    /// Permit.
-    permit: Permit,
-}
-
-/// TCP connection error.
-#[derive(Debug)]
-enum ConnectionError {
-    /// Timeout
-    Timeout {
-        /// Protocol.
-        protocol: Option<ProtocolName>,
-
-        /// Substream ID.
-        substream_id: Option<SubstreamId>,
-    },
+use std::{borrow::Cow, net::SocketAddr, time::Duration};
 
-    /// Failed to negotiate connection/substream.
-    FailedToNegotiate {
-        /// Protocol.
-        protocol: Option<ProtocolName>,
-
-        /// Substream ID.
-        substream_id: Option<SubstreamId>,
-
-        /// Error.
-        error: Error,
-    },
-}
+/// Stream of an upgraded connection.
+///
+/// The `multistream-select` wrappers are kept around the streams because with
+/// [`Version::V1Lazy`] the negotiation is only completed once the remote's confirmation has
+/// been read from the stream.
+type NegotiatedStream = Negotiated<SecureStream<Negotiated<Compat<TcpStream>>>>;
 
-/// Connection context for an opened connection that hasn't yet started its event loop.
-pub struct NegotiatedConnection {
-    /// Yamux connection.
-    connection: crate::yamux::ControlledConnection<NoiseSocket<Compat<TcpStream>>>,
-
-    /// Yamux control.
-    control: crate::yamux::Control,
-
-    /// Remote peer ID.
-    peer: PeerId,
-
-    /// Endpoint.
-    endpoint: Endpoint,
-
-    /// Substream open timeout.
-    substream_open_timeout: Duration,
-}
-
-impl NegotiatedConnection {
-    /// Get `ConnectionId` of the negotiated connection.
-    pub fn connection_id(&self) -> ConnectionId {
-        self.endpoint.connection_id()
-    }
-
-    /// Get `PeerId` of the negotiated connection.
-    pub fn peer(&self) -> PeerId {
-        self.peer
-    }
-
-    /// Get `Endpoint` of the negotiated connection.
-    pub fn endpoint(&self) -> Endpoint {
-        self.endpoint.clone()
-    }
-}
+/// Connection context for an opened TCP connection that hasn't yet started its event loop.
+pub type NegotiatedConnection = connection::NegotiatedConnection<NegotiatedStream>;
 
 /// TCP connection.
-pub struct TcpConnection {
-    /// Protocol context.
-    protocol_set: ProtocolSet,
-
-    /// Yamux connection.
-    connection: crate::yamux::ControlledConnection<NoiseSocket<Compat<TcpStream>>>,
-
-    /// Yamux control.
-    control: crate::yamux::Control,
-
-    /// Remote peer ID.
-    peer: PeerId,
+pub type TcpConnection = connection::Connection<NegotiatedStream>;
 
-    /// Endpoint.
-    endpoint: Endpoint,
-
-    /// Substream open timeout.
-    substream_open_timeout: Duration,
-
-    /// Next substream ID.
-    next_substream_id: Arc<AtomicUsize>,
-
-    // Bandwidth sink.
-    bandwidth_sink: BandwidthSink,
-
-    /// Pending substreams.
-    pending_substreams:
-        FuturesUnordered<BoxFuture<'static, Result<NegotiatedSubstream, ConnectionError>>>,
-}
-
-impl fmt::Debug for TcpConnection {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TcpConnection")
-            .field("peer", &self.peer)
-            .field("next_substream_id", &self.next_substream_id)
-            .finish()
-    }
-}
+/// Logging target for the file.
+const LOG_TARGET: &str = "litep2p::tcp::connection";
 
 impl TcpConnection {
-    /// Create new [`TcpConnection`] from [`NegotiatedConnection`].
-    pub(super) fn new(
-        context: NegotiatedConnection,
-        protocol_set: ProtocolSet,
-        bandwidth_sink: BandwidthSink,
-        next_substream_id: Arc<AtomicUsize>,
-    ) -> Self {
-        let NegotiatedConnection {
-            connection,
-            control,
-            peer,
-            endpoint,
-            substream_open_timeout,
-        } = context;
-
-        Self {
-            protocol_set,
-            connection,
-            control,
-            peer,
-            endpoint,
-            bandwidth_sink,
-            next_substream_id,
-            pending_substreams: FuturesUnordered::new(),
-            substream_open_timeout,
-        }
-    }
-
     /// Open connection to remote peer at `address`.
     // TODO: this function can be removed
     pub(super) async fn open_connection(
         connection_id: ConnectionId,
         keypair: Keypair,
+        bandwidth_sink: BandwidthSink,
+        security_protocols: Vec<SecurityProtocol>,
         stream: TcpStream,
         address: AddressType,
         peer: Option<PeerId>,
@@ -222,6 +73,7 @@ impl TcpConnection {
         max_write_buffer_size: usize,
         connection_open_timeout: Duration,
         substream_open_timeout: Duration,
+        multistream_select_version: Version,
     ) -> crate::Result<NegotiatedConnection> {
         tracing::debug!(
             target: LOG_TARGET,
@@ -236,12 +88,15 @@ impl TcpConnection {
                 peer,
                 connection_id,
                 keypair,
+                bandwidth_sink,
+                security_protocols,
                 Role::Dialer,
                 address,
                 yamux_config,
                 max_read_ahead_factor,
                 max_write_buffer_size,
                 substream_open_timeout,
+                multistream_select_version,
             )
             .await
         })
@@ -252,56 +107,13 @@ impl TcpConnection {
         }
     }
 
-    /// Open substream for `protocol`.
-    pub(super) async fn open_substream(
-        mut control: crate::yamux::Control,
-        substream_id: SubstreamId,
-        permit: Permit,
-        protocol: ProtocolName,
-        fallback_names: Vec<ProtocolName>,
-        open_timeout: Duration,
-    ) -> crate::Result<NegotiatedSubstream> {
-        tracing::debug!(target: LOG_TARGET, ?protocol, ?substream_id, "open substream");
-
-        let stream = match control.open_stream().await {
-            Ok(stream) => {
-                tracing::trace!(target: LOG_TARGET, ?substream_id, "substream opened");
-                stream
-            }
-            Err(error) => {
-                tracing::debug!(
-                    target: LOG_TARGET,
-                    ?substream_id,
-                    ?error,
-                    "failed to open substream"
-                );
-                return Err(Error::YamuxError(Direction::Outbound(substream_id), error));
-            }
-        };
-
-        // TODO: protocols don't change after they've been initialized so this should be done only
-        // once
-        let protocols = std::iter::once(&*protocol)
-            .chain(fallback_names.iter().map(|protocol| &**protocol))
-            .collect();
-
-        let (io, protocol) =
-            Self::negotiate_protocol(stream, &Role::Dialer, protocols, open_timeout).await?;
-
-        Ok(NegotiatedSubstream {
-            io: io.inner(),
-            substream_id,
-            direction: Direction::Outbound(substream_id),
-            protocol,
-            permit,
-        })
-    }
-
     /// Accept a new connection.
     pub(super) async fn accept_connection(
         stream: TcpStream,
         connection_id: ConnectionId,
         keypair: Keypair,
+        bandwidth_sink: BandwidthSink,
+        security_protocols: Vec<SecurityProtocol>,
         address: SocketAddr,
         yamux_config: crate::yamux::Config,
         max_read_ahead_factor: usize,
@@ -317,12 +129,15 @@ impl TcpConnection {
                 None,
                 connection_id,
                 keypair,
+                bandwidth_sink,
+                security_protocols,
                 Role::Listener,
                 AddressType::Socket(address),
                 yamux_config,
                 max_read_ahead_factor,
                 max_write_buffer_size,
                 substream_open_timeout,
+                Version::V1,
             )
             .await
         })
@@ -333,80 +148,21 @@ impl TcpConnection {
         }
     }
 
-    /// Accept substream.
-    pub(super) async fn accept_substream(
-        stream: crate::yamux::Stream,
-        permit: Permit,
-        substream_id: SubstreamId,
-        protocols: Vec<ProtocolName>,
-        open_timeout: Duration,
-    ) -> crate::Result<NegotiatedSubstream> {
-        tracing::trace!(
-            target: LOG_TARGET,
-            ?substream_id,
-            "accept inbound substream",
-        );
-
-        let protocols = protocols.iter().map(|protocol| &**protocol).collect::<Vec<&str>>();
-        let (io, protocol) =
-            Self::negotiate_protocol(stream, &Role::Listener, protocols, open_timeout).await?;
-
-        tracing::trace!(
-            target: LOG_TARGET,
-            ?substream_id,
-            "substream accepted and negotiated",
-        );
-
-        Ok(NegotiatedSubstream {
-            io: io.inner(),
-            substream_id,
-            direction: Direction::Inbound,
-            protocol,
-            permit,
-        })
-    }
-
-    /// Negotiate protocol.
-    async fn negotiate_protocol<S: AsyncRead + AsyncWrite + Unpin>(
-        stream: S,
-        role: &Role,
-        protocols: Vec<&str>,
-        substream_open_timeout: Duration,
-    ) -> crate::Result<(Negotiated<S>, ProtocolName)> {
-        tracing::trace!(target: LOG_TARGET, ?protocols, "negotiating protocols");
-
-        match tokio::time::timeout(substream_open_timeout, async move {
-            match role {
-                Role::Dialer => dialer_select_proto(stream, protocols, Version::V1).await,
-                Role::Listener => listener_select_proto(stream, protocols).await,
-            }
-        })
-        .await
-        {
-            Err(_) => Err(Error::Timeout),
-            Ok(Err(error)) => Err(Error::NegotiationError(
-                NegotiationError::MultistreamSelectError(error),
-            )),
-            Ok(Ok((protocol, socket))) => {
-                tracing::trace!(target: LOG_TARGET, ?protocol, "protocol negotiated");
-
-                Ok((socket, ProtocolName::from(protocol.to_string())))
-            }
-        }
-    }
-
-    /// Negotiate noise + yamux for the connection.
+    /// Negotiate security protocol + yamux for the connection.
     pub(super) async fn negotiate_connection(
         stream: TcpStream,
         dialed_peer: Option<PeerId>,
         connection_id: ConnectionId,
         keypair: Keypair,
+        bandwidth_sink: BandwidthSink,
+        security_protocols: Vec<SecurityProtocol>,
         role: Role,
         address: AddressType,
         yamux_config: crate::yamux::Config,
         max_read_ahead_factor: usize,
         max_write_buffer_size: usize,
         substream_open_timeout: Duration,
+        multistream_select_version: Version,
     ) -> crate::Result<NegotiatedConnection> {
         tracing::trace!(
             target: LOG_TARGET,
@@ -417,43 +173,73 @@ impl TcpConnection {
         let stream = TokioAsyncReadCompatExt::compat(stream).into_inner();
         let stream = TokioAsyncWriteCompatExt::compat_write(stream);
 
-        // negotiate `noise`
-        let (stream, _) =
-            Self::negotiate_protocol(stream, &role, vec!["/noise"], substream_open_timeout).await?;
+        // negotiate security protocol
+        let protocols =
+            security_protocols.iter().map(|protocol| protocol.protocol_name()).collect();
+        let (stream, protocol) = negotiate_protocol(
+            stream,
+            &role,
+            protocols,
+            multistream_select_version,
+            substream_open_timeout,
+        )
+        .await?;
 
         tracing::trace!(
             target: LOG_TARGET,
-            "`multistream-select` and `noise` negotiated",
+            ?protocol,
+            "`multistream-select` and security protocol negotiated",
         );
 
-        // perform noise handshake
-        let (stream, peer) = noise::handshake(
-            stream.inner(),
-            &keypair,
-            role,
-            max_read_ahead_factor,
-            max_write_buffer_size,
-        )
-        .await?;
+        // perform the handshake of the negotiated security protocol
+        let (stream, peer) = match &*protocol {
+            tls::PROTOCOL_NAME => {
+                let (stream, peer) = tls::handshake(stream, &keypair, role).await?;
+
+                if let Some(dialed_peer) = dialed_peer {
+                    if dialed_peer != peer {
+                        return Err(Error::HandshakeFailed(HandshakeError::PeerIdMismatch(
+                            dialed_peer,
+                            peer,
+                        )));
+                    }
+                }
 
-        if let Some(dialed_peer) = dialed_peer {
-            if dialed_peer != peer {
-                tracing::debug!(target: LOG_TARGET, ?dialed_peer, ?peer, "peer id mismatch");
-                return Err(Error::PeerIdMismatch(dialed_peer, peer));
+                (SecureStream::Tls(Box::new(stream)), peer)
             }
-        }
+            _ => {
+                let (stream, peer, _) = noise::handshake(
+                    stream,
+                    &keypair,
+                    role,
+                    dialed_peer,
+                    &[],
+                    max_read_ahead_factor,
+                    max_write_buffer_size,
+                )
+                .await?;
 
-        tracing::trace!(target: LOG_TARGET, "noise handshake done");
-        let stream: NoiseSocket<Compat<TcpStream>> = stream;
+                (SecureStream::Noise(Box::new(stream)), peer)
+            }
+        };
+
+        tracing::trace!(target: LOG_TARGET, ?protocol, "security handshake done");
+        let stream: SecureStream<Negotiated<Compat<TcpStream>>> = stream;
 
         // negotiate `yamux`
-        let (stream, _) =
-            Self::negotiate_protocol(stream, &role, vec!["/yamux/1.0.0"], substream_open_timeout)
-                .await?;
+        let (mut stream, _) = negotiate_protocol(
+            stream,
+            &role,
+            vec!["/yamux/1.0.0"],
+            multistream_select_version,
+            substream_open_timeout,
+        )
+        .await?;
         tracing::trace!(target: LOG_TARGET, "`yamux` negotiated");
 
-        let connection = crate::yamux::Connection::new(stream.inner(), yamux_config, role.into());
-        let (control, connection) = crate::yamux::Control::new(connection);
+        // with `V1Lazy` the proposal is buffered until the first write and an idle `yamux`
+        // connection doesn't write anything so flush it to let the listener finish negotiation
+        stream.flush().await.map_err(|error| Error::IoError(error.kind()))?;
 
         let address = match address {
             AddressType::Socket(address) => Multiaddr::empty()
@@ -474,205 +260,43 @@ impl TcpConnection {
                     .with(Protocol::Dns6(Cow::Owned(address)))
                     .with(Protocol::Tcp(port)),
             },
+            AddressType::Memory(port) => Multiaddr::empty().with(Protocol::Memory(port)),
         };
         let endpoint = match role {
             Role::Dialer => Endpoint::dialer(address, connection_id),
             Role::Listener => Endpoint::listener(address, connection_id),
         };
 
-        Ok(NegotiatedConnection {
+        Ok(NegotiatedConnection::new(
+            stream,
             peer,
-            control,
-            connection,
+            role,
             endpoint,
+            &bandwidth_sink,
+            yamux_config,
             substream_open_timeout,
-        })
-    }
-
-    /// Start connection event loop.
-    pub(crate) async fn start(mut self) -> crate::Result<()> {
-        self.protocol_set
-            .report_connection_established(self.peer, self.endpoint.clone())
-            .await?;
-
-        loop {
-            tokio::select! {
-                substream = self.connection.next() => match substream {
-                    Some(Ok(stream)) => {
-                        let substream_id = {
-                            let substream_id = self.next_substream_id.fetch_add(1usize, Ordering::Relaxed);
-                            SubstreamId::from(substream_id)
-                        };
-                        let protocols = self.protocol_set.protocols();
-                        let permit = self.protocol_set.try_get_permit().ok_or(Error::ConnectionClosed)?;
-                        let open_timeout = self.substream_open_timeout;
-
-                        self.pending_substreams.push(Box::pin(async move {
-                            match tokio::time::timeout(
-                                open_timeout,
-                                Self::accept_substream(stream, permit, substream_id, protocols, open_timeout),
-                            )
-                            .await
-                            {
-                                Ok(Ok(substream)) => Ok(substream),
-                                Ok(Err(error)) => Err(ConnectionError::FailedToNegotiate {
-                                    protocol: None,
-                                    substream_id: None,
-                                    error,
-                                }),
-                                Err(_) => Err(ConnectionError::Timeout {
-                                    protocol: None,
-                                    substream_id: None
-                                }),
-                            }
-                        }));
-                    },
-                    Some(Err(error)) => {
-                        tracing::debug!(
-                            target: LOG_TARGET,
-                            peer = ?self.peer,
-                            ?error,
-                            "connection closed with error",
-                        );
-                        self.protocol_set.report_connection_closed(self.peer, self.endpoint.connection_id()).await?;
-
-                        return Ok(())
-                    }
-                    None => {
-                        tracing::debug!(target: LOG_TARGET, peer = ?self.peer, "connection closed");
-                        self.protocol_set.report_connection_closed(self.peer, self.endpoint.connection_id()).await?;
-
-                        return Ok(())
-                    }
-                },
-                // TODO: move this to a function
-                substream = self.pending_substreams.select_next_some(), if !self.pending_substreams.is_empty() => {
-                    match substream {
-                        // TODO: return error to protocol
-                        Err(error) => {
-                            tracing::debug!(
-                                target: LOG_TARGET,
-                                ?error,
-                                "failed to accept/open substream",
-                            );
-
-                            let (protocol, substream_id, error) = match error {
-                                ConnectionError::Timeout { protocol, substream_id } => {
-                                    (protocol, substream_id, Error::Timeout)
-                                }
-                                ConnectionError::FailedToNegotiate { protocol, substream_id, error } => {
-                                    (protocol, substream_id, error)
-                                }
-                            };
-
-                            match (protocol, substream_id) {
-                                (Some(protocol), Some(substream_id)) => {
-                                    if let Err(error) = self.protocol_set
-                                        .report_substream_open_failure(protocol, substream_id, error)
-                                        .await
-                                    {
-                                        tracing::error!(
-                                            target: LOG_TARGET,
-                                            ?error,
-                                            "failed to register opened substream to protocol"
-                                        );
-                                    }
-                                }
-                                _ => {}
-                            }
-                        }
-                        Ok(substream) => {
-                            let protocol = substream.protocol.clone();
-                            let direction = substream.direction;
-                            let substream_id = substream.substream_id;
-                            let socket = FuturesAsyncReadCompatExt::compat(substream.io);
-                            let bandwidth_sink = self.bandwidth_sink.clone();
-
-                            let substream = substream::Substream::new_tcp(
-                                self.peer,
-                                substream_id,
-                                Substream::new(socket, bandwidth_sink, substream.permit),
-                                self.protocol_set.protocol_codec(&protocol)
-                            );
-
-                            if let Err(error) = self.protocol_set
-                                .report_substream_open(self.peer, protocol, direction, substream)
-                                .await
-                            {
-                                tracing::error!(
-                                    target: LOG_TARGET,
-                                    ?error,
-                                    "failed to register opened substream to protocol",
-                                );
-                            }
-                        }
-                    }
-                }
-                protocol = self.protocol_set.next() => match protocol {
-                    Some(ProtocolCommand::OpenSubstream { protocol, fallback_names, substream_id, permit }) => {
-                        let control = self.control.clone();
-                        let open_timeout = self.substream_open_timeout;
-
-                        tracing::trace!(
-                            target: LOG_TARGET,
-                            ?protocol,
-                            ?substream_id,
-                            "open substream",
-                        );
-
-                        self.pending_substreams.push(Box::pin(async move {
-                            match tokio::time::timeout(
-                                open_timeout,
-                                Self::open_substream(
-                                    control,
-                                    substream_id,
-                                    permit,
-                                    protocol.clone(),
-                                    fallback_names,
-                                    open_timeout,
-                                ),
-                            )
-                            .await
-                            {
-                                Ok(Ok(substream)) => Ok(substream),
-                                Ok(Err(error)) => Err(ConnectionError::FailedToNegotiate {
-                                    protocol: Some(protocol),
-                                    substream_id: Some(substream_id),
-                                    error,
-                                }),
-                                Err(_) => Err(ConnectionError::Timeout {
-                                    protocol: Some(protocol),
-                                    substream_id: Some(substream_id)
-                                }),
-                            }
-                        }));
-                    }
-                    Some(ProtocolCommand::ForceClose) => {
-                        tracing::debug!(
-                            target: LOG_TARGET,
-                            peer = ?self.peer,
-                            connection_id = ?self.endpoint.connection_id(),
-                            "force closing connection",
-                        );
-
-                        return self.protocol_set.report_connection_closed(self.peer, self.endpoint.connection_id()).await
-                    }
-                    None => {
-                        tracing::debug!(target: LOG_TARGET, "protocols have disconnected, closing connection");
-                        return self.protocol_set.report_connection_closed(self.peer, self.endpoint.connection_id()).await
-                    }
-                }
-            }
-        }
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::transport::tcp::TcpTransport;
+    use crate::{
+        crypto::noise::NoiseSocket,
+        error::NegotiationError,
+        multistream_select::{dialer_select_proto, listener_select_proto},
+        transport::tcp::TcpTransport,
+        types::protocol::ProtocolName,
+    };
 
     use super::*;
+    use futures::{AsyncRead, AsyncWrite};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
     use tokio::{io::AsyncWriteExt, net::TcpListener};
+    use trust_dns_resolver::TokioAsyncResolver;
 
     #[tokio::test]
     async fn multistream_select_not_supported_dialer() {
@@ -695,6 +319,12 @@ mod tests {
             Default::default(),
             Duration::from_secs(10),
             false,
+            None,
+            None,
+            Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
         )
         .await
         .unwrap();
@@ -702,6 +332,8 @@ mod tests {
         match TcpConnection::open_connection(
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             stream,
             AddressType::Socket(address),
             None,
@@ -710,6 +342,7 @@ mod tests {
             2,
             Duration::from_secs(10),
             Duration::from_secs(10),
+            Version::V1,
         )
         .await
         {
@@ -746,6 +379,8 @@ mod tests {
             stream,
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             dialer_address,
             Default::default(),
             5,
@@ -790,6 +425,12 @@ mod tests {
             Default::default(),
             Duration::from_secs(10),
             false,
+            None,
+            None,
+            Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
         )
         .await
         .unwrap();
@@ -797,6 +438,8 @@ mod tests {
         match TcpConnection::open_connection(
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             stream,
             AddressType::Socket(address),
             None,
@@ -805,13 +448,12 @@ mod tests {
             2,
             Duration::from_secs(10),
             Duration::from_secs(10),
+            Version::V1,
         )
         .await
         {
             Ok(_) => panic!("connection was supposed to fail"),
-            Err(Error::NegotiationError(NegotiationError::MultistreamSelectError(
-                crate::multistream_select::NegotiationError::Failed,
-            ))) => {}
+            Err(Error::ProtocolNegotiationFailed { .. }) => {}
             Err(error) => panic!("invalid error: {error:?}"),
         }
     }
@@ -843,6 +485,8 @@ mod tests {
             listener,
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             dialer_address,
             Default::default(),
             5,
@@ -853,9 +497,7 @@ mod tests {
         .await
         {
             Ok(_) => panic!("connection was supposed to fail"),
-            Err(Error::NegotiationError(NegotiationError::MultistreamSelectError(
-                crate::multistream_select::NegotiationError::Failed,
-            ))) => {}
+            Err(Error::ProtocolNegotiationFailed { .. }) => {}
             Err(error) => panic!("invalid error: {error:?}"),
         }
     }
@@ -890,6 +532,8 @@ mod tests {
             listener,
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             dialer_address,
             Default::default(),
             5,
@@ -932,6 +576,12 @@ mod tests {
             Default::default(),
             Duration::from_secs(10),
             false,
+            None,
+            None,
+            Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
         )
         .await
         .unwrap();
@@ -939,6 +589,8 @@ mod tests {
         match TcpConnection::open_connection(
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             stream,
             AddressType::Socket(address),
             None,
@@ -947,6 +599,7 @@ mod tests {
             2,
             Duration::from_secs(10),
             Duration::from_secs(10),
+            Version::V1,
         )
         .await
         {
@@ -978,6 +631,12 @@ mod tests {
             Default::default(),
             Duration::from_secs(10),
             false,
+            None,
+            None,
+            Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
         )
         .await
         .unwrap();
@@ -985,6 +644,8 @@ mod tests {
         match TcpConnection::open_connection(
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             stream,
             AddressType::Socket(address),
             None,
@@ -993,6 +654,7 @@ mod tests {
             2,
             Duration::from_secs(10),
             Duration::from_secs(10),
+            Version::V1,
         )
         .await
         {
@@ -1027,6 +689,8 @@ mod tests {
             listener,
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             dialer_address,
             Default::default(),
             5,
@@ -1068,8 +732,10 @@ mod tests {
             let keypair = Keypair::generate();
 
             // do a noise handshake
-            let (stream, _peer) =
-                noise::handshake(stream.inner(), &keypair, Role::Dialer, 5, 2).await.unwrap();
+            let (stream, _peer, _) =
+                noise::handshake(stream.inner(), &keypair, Role::Dialer, None, &[], 5, 2)
+                    .await
+                    .unwrap();
             let stream: NoiseSocket<Compat<TcpStream>> = stream;
 
             // after the handshake, try to negotiate some random protocol instead of yamux
@@ -1082,6 +748,8 @@ mod tests {
             listener,
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             dialer_address,
             Default::default(),
             5,
@@ -1092,9 +760,7 @@ mod tests {
         .await
         {
             Ok(_) => panic!("connection was supposed to fail"),
-            Err(Error::NegotiationError(NegotiationError::MultistreamSelectError(
-                crate::multistream_select::NegotiationError::Failed,
-            ))) => {}
+            Err(Error::ProtocolNegotiationFailed { .. }) => {}
             Err(error) => panic!("{error:?}"),
         }
     }
@@ -1118,8 +784,10 @@ mod tests {
 
             // do a noise handshake
             let keypair = Keypair::generate();
-            let (stream, _peer) =
-                noise::handshake(stream.inner(), &keypair, Role::Listener, 5, 2).await.unwrap();
+            let (stream, _peer, _) =
+                noise::handshake(stream.inner(), &keypair, Role::Listener, None, &[], 5, 2)
+                    .await
+                    .unwrap();
             let stream: NoiseSocket<Compat<TcpStream>> = stream;
 
             // after the handshake, try to negotiate some random protocol instead of yamux
@@ -1133,6 +801,12 @@ mod tests {
             Default::default(),
             Duration::from_secs(10),
             false,
+            None,
+            None,
+            Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
         )
         .await
         .unwrap();
@@ -1140,6 +814,8 @@ mod tests {
         match TcpConnection::open_connection(
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             stream,
             AddressType::Socket(address),
             None,
@@ -1148,13 +824,12 @@ mod tests {
             2,
             Duration::from_secs(10),
             Duration::from_secs(10),
+            Version::V1,
         )
         .await
         {
             Ok(_) => panic!("connection was supposed to fail"),
-            Err(Error::NegotiationError(NegotiationError::MultistreamSelectError(
-                crate::multistream_select::NegotiationError::Failed,
-            ))) => {}
+            Err(Error::ProtocolNegotiationFailed { .. }) => {}
             Err(error) => panic!("{error:?}"),
         }
     }
@@ -1184,8 +859,10 @@ mod tests {
 
             // do a noise handshake
             let keypair = Keypair::generate();
-            let (stream, _peer) =
-                noise::handshake(stream.inner(), &keypair, Role::Dialer, 5, 2).await.unwrap();
+            let (stream, _peer, _) =
+                noise::handshake(stream.inner(), &keypair, Role::Dialer, None, &[], 5, 2)
+                    .await
+                    .unwrap();
             let _stream: NoiseSocket<Compat<TcpStream>> = stream;
 
             tokio::time::sleep(std::time::Duration::from_secs(60)).await;
@@ -1195,6 +872,8 @@ mod tests {
             listener,
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             dialer_address,
             Default::default(),
             5,
@@ -1229,8 +908,10 @@ mod tests {
 
             // do a noise handshake
             let keypair = Keypair::generate();
-            let (stream, _peer) =
-                noise::handshake(stream.inner(), &keypair, Role::Listener, 5, 2).await.unwrap();
+            let (stream, _peer, _) =
+                noise::handshake(stream.inner(), &keypair, Role::Listener, None, &[], 5, 2)
+                    .await
+                    .unwrap();
             let _stream: NoiseSocket<Compat<TcpStream>> = stream;
 
             tokio::time::sleep(std::time::Duration::from_secs(60)).await;
@@ -1243,6 +924,12 @@ mod tests {
             Default::default(),
             Duration::from_secs(10),
             false,
+            None,
+            None,
+            Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
         )
         .await
         .unwrap();
@@ -1250,6 +937,8 @@ mod tests {
         match TcpConnection::open_connection(
             ConnectionId::from(0usize),
             Keypair::generate(),
+            BandwidthSink::new(),
+            vec![SecurityProtocol::Noise],
             stream,
             AddressType::Socket(address),
             None,
@@ -1258,6 +947,7 @@ mod tests {
             2,
             Duration::from_secs(10),
             Duration::from_secs(10),
+            Version::V1,
         )
         .await
         {
@@ -1266,4 +956,181 @@ mod tests {
             Err(error) => panic!("invalid error: {error:?}"),
         }
     }
+
+    #[tokio::test]
+    async fn unsupported_protocol_negotiation_fails() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (dialer, listener) = tokio::io::duplex(1024);
+
+        tokio::spawn(async move {
+            let listener = TokioAsyncReadCompatExt::compat(listener).into_inner();
+            let listener = TokioAsyncWriteCompatExt::compat_write(listener);
+
+            let _ = listener_select_proto(listener, vec!["/supported/1"]).await;
+        });
+
+        let dialer = TokioAsyncReadCompatExt::compat(dialer).into_inner();
+        let dialer = TokioAsyncWriteCompatExt::compat_write(dialer);
+
+        match negotiate_protocol(
+            dialer,
+            &Role::Dialer,
+            vec!["/unsupported/1", "/unsupported/2"],
+            Version::V1,
+            Duration::from_secs(10),
+        )
+        .await
+        {
+            Ok(_) => panic!("negotiation was supposed to fail"),
+            Err(Error::ProtocolNegotiationFailed { protocols }) => {
+                assert_eq!(
+                    protocols,
+                    vec!["/unsupported/1".to_string(), "/unsupported/2".to_string()]
+                );
+            }
+            Err(error) => panic!("invalid error: {error:?}"),
+        }
+    }
+
+    /// Stream which counts how many times the local node started reading after having written
+    /// data, i.e., how many round trips it made.
+    struct RoundTripCounter<S> {
+        inner: S,
+        written: bool,
+        round_trips: Arc<AtomicUsize>,
+    }
+
+    impl<S: AsyncRead + Unpin> AsyncRead for RoundTripCounter<S> {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            if std::mem::take(&mut self.written) {
+                self.round_trips.fetch_add(1usize, Ordering::Relaxed);
+            }
+
+            std::pin::Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl<S: AsyncWrite + Unpin> AsyncWrite for RoundTripCounter<S> {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.written = true;
+            std::pin::Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::pin::Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_close(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::pin::Pin::new(&mut self.inner).poll_close(cx)
+        }
+    }
+
+    /// Negotiate an echo protocol using `version`, exchange one message and return the number of
+    /// round trips made by the dialer.
+    async fn echo_round_trips(version: Version) -> usize {
+        use futures::{AsyncReadExt as _, AsyncWriteExt as _};
+
+        let (dialer, listener) = tokio::io::duplex(1024);
+
+        tokio::spawn(async move {
+            let listener = TokioAsyncReadCompatExt::compat(listener).into_inner();
+            let listener = TokioAsyncWriteCompatExt::compat_write(listener);
+
+            let (_, mut io) = listener_select_proto(listener, vec!["/echo/1"]).await.unwrap();
+            let mut buffer = [0u8; 5];
+
+            io.read_exact(&mut buffer).await.unwrap();
+            io.write_all(&buffer).await.unwrap();
+            io.flush().await.unwrap();
+        });
+
+        let dialer = TokioAsyncReadCompatExt::compat(dialer).into_inner();
+        let dialer = TokioAsyncWriteCompatExt::compat_write(dialer);
+        let round_trips = Arc::new(AtomicUsize::new(0usize));
+        let dialer = RoundTripCounter {
+            inner: dialer,
+            written: false,
+            round_trips: Arc::clone(&round_trips),
+        };
+
+        let (mut io, protocol) = negotiate_protocol(
+            dialer,
+            &Role::Dialer,
+            vec!["/echo/1"],
+            version,
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+        assert_eq!(protocol, ProtocolName::from("/echo/1"));
+
+        let mut buffer = [0u8; 5];
+        io.write_all(b"hello").await.unwrap();
+        io.flush().await.unwrap();
+        io.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"hello");
+
+        round_trips.load(Ordering::Relaxed)
+    }
+
+    #[tokio::test]
+    async fn lazy_negotiation_saves_round_trip() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        assert_eq!(echo_round_trips(Version::V1).await, 2);
+        assert_eq!(echo_round_trips(Version::V1Lazy).await, 1);
+    }
+
+    #[tokio::test]
+    async fn lazy_negotiation_falls_back_to_v1_for_multiple_protocols() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let (dialer, listener) = tokio::io::duplex(1024);
+
+        tokio::spawn(async move {
+            let listener = TokioAsyncReadCompatExt::compat(listener).into_inner();
+            let listener = TokioAsyncWriteCompatExt::compat_write(listener);
+
+            let _ = listener_select_proto(listener, vec!["/supported/1"]).await;
+        });
+
+        let dialer = TokioAsyncReadCompatExt::compat(dialer).into_inner();
+        let dialer = TokioAsyncWriteCompatExt::compat_write(dialer);
+
+        // with `V1Lazy`, the dialer would settle on `/unsupported/1` without waiting for the
+        // listener to reject it
+        let (io, protocol) = negotiate_protocol(
+            dialer,
+            &Role::Dialer,
+            vec!["/unsupported/1", "/supported/1"],
+            Version::V1Lazy,
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(protocol, ProtocolName::from("/supported/1"));
+        let _io = io.inner();
+    }
 }
@@ -26,7 +26,8 @@ use crate::{
     error::Error,
     transport::{
         common::listener::{DialAddresses, GetSocketAddr, SocketListener, TcpAddress},
-        manager::TransportHandle,
+        dns::DnsResolver,
+        manager::{SupportedTransport, TransportHandle},
         tcp::{
             config::Config,
             connection::{NegotiatedConnection, TcpConnection},
@@ -37,17 +38,18 @@ use crate::{
 };
 
 use futures::{
-    future::BoxFuture,
+    future::{AbortHandle, Abortable, BoxFuture},
     stream::{FuturesUnordered, Stream, StreamExt},
 };
 use multiaddr::Multiaddr;
-use socket2::{Domain, Socket, Type};
+use socket2::{Domain, SockRef, Socket, Type};
 use tokio::net::TcpStream;
 
 use std::{
     collections::{HashMap, HashSet},
     net::SocketAddr,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::Duration,
 };
@@ -76,6 +78,9 @@ pub(crate) struct TcpTransport {
     /// Pending dials.
     pending_dials: HashMap<ConnectionId, Multiaddr>,
 
+    /// Abort handles of pending dials.
+    dial_aborts: HashMap<ConnectionId, AbortHandle>,
+
     /// Dial addresses.
     dial_addresses: DialAddresses,
 
@@ -83,9 +88,17 @@ pub(crate) struct TcpTransport {
     pending_connections:
         FuturesUnordered<BoxFuture<'static, Result<NegotiatedConnection, (ConnectionId, Error)>>>,
 
+    /// Inbound connections which are being negotiated.
+    pending_inbound: HashSet<ConnectionId>,
+
     /// Pending raw, unnegotiated connections.
+    ///
+    /// If none of the addresses could be dialed, the error of each address is returned.
     pending_raw_connections: FuturesUnordered<
-        BoxFuture<'static, Result<(ConnectionId, Multiaddr, TcpStream), ConnectionId>>,
+        BoxFuture<
+            'static,
+            Result<(ConnectionId, Multiaddr, TcpStream), (ConnectionId, Vec<(Multiaddr, Error)>)>,
+        >,
     >,
 
     /// Opened raw connection, waiting for approval/rejection from `TransportManager`.
@@ -99,22 +112,83 @@ pub(crate) struct TcpTransport {
     pending_open: HashMap<ConnectionId, NegotiatedConnection>,
 }
 
+/// Apply configured socket options to a dialed or accepted TCP socket.
+fn apply_socket_options(
+    socket: SockRef<'_>,
+    nodelay: bool,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+) -> std::io::Result<()> {
+    socket.set_nodelay(nodelay)?;
+
+    if let Some(size) = send_buffer_size {
+        socket.set_send_buffer_size(size)?;
+    }
+
+    if let Some(size) = recv_buffer_size {
+        socket.set_recv_buffer_size(size)?;
+    }
+
+    Ok(())
+}
+
 impl TcpTransport {
+    /// Filter inbound TCP connection and apply configured socket options to it.
+    ///
+    /// Returns `false` if the connection was rejected.
+    fn prepare_inbound_connection(&self, connection: &TcpStream, address: SocketAddr) -> bool {
+        if let Some(filter) = &self.config.connection_filter {
+            if !filter.accept(&address) {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?address,
+                    "inbound connection rejected by filter",
+                );
+                return false;
+            }
+        }
+
+        if let Err(error) = apply_socket_options(
+            SockRef::from(connection),
+            self.config.nodelay,
+            self.config.send_buffer_size,
+            self.config.recv_buffer_size,
+        ) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?address,
+                ?error,
+                "failed to set socket options for inbound connection",
+            );
+        }
+
+        true
+    }
+
     /// Handle inbound TCP connection.
     fn on_inbound_connection(&mut self, connection: TcpStream, address: SocketAddr) {
+        if !self.prepare_inbound_connection(&connection, address) {
+            return;
+        }
+
         let connection_id = self.context.next_connection_id();
         let yamux_config = self.config.yamux_config.clone();
+        let security_protocols = self.config.security_protocols.clone();
         let max_read_ahead_factor = self.config.noise_read_ahead_frame_count;
         let max_write_buffer_size = self.config.noise_write_buffer_size;
         let connection_open_timeout = self.config.connection_open_timeout;
         let substream_open_timeout = self.config.substream_open_timeout;
         let keypair = self.context.keypair.clone();
+        let bandwidth_sink = self.context.bandwidth_sink.clone();
 
+        self.pending_inbound.insert(connection_id);
         self.pending_connections.push(Box::pin(async move {
             TcpConnection::accept_connection(
                 connection,
                 connection_id,
                 keypair,
+                bandwidth_sink,
+                security_protocols,
                 address,
                 yamux_config,
                 max_read_ahead_factor,
@@ -133,14 +207,21 @@ impl TcpTransport {
         dial_addresses: DialAddresses,
         connection_open_timeout: Duration,
         nodelay: bool,
+        send_buffer_size: Option<usize>,
+        recv_buffer_size: Option<usize>,
+        resolver: Arc<dyn DnsResolver>,
     ) -> crate::Result<(Multiaddr, TcpStream)> {
         let (socket_address, _) = TcpAddress::multiaddr_to_socket_address(&address)?;
-        let remote_address =
-            match tokio::time::timeout(connection_open_timeout, socket_address.lookup_ip()).await {
-                Err(_) => return Err(Error::Timeout),
-                Ok(Err(error)) => return Err(error),
-                Ok(Ok(address)) => address,
-            };
+        let remote_address = match tokio::time::timeout(
+            connection_open_timeout,
+            socket_address.lookup_ip(resolver.as_ref()),
+        )
+        .await
+        {
+            Err(_) => return Err(Error::Timeout),
+            Ok(Err(error)) => return Err(error),
+            Ok(Ok(address)) => address,
+        };
 
         let domain = match remote_address.is_ipv4() {
             true => Domain::IPV4,
@@ -151,7 +232,12 @@ impl TcpTransport {
             socket.set_only_v6(true)?;
         }
         socket.set_nonblocking(true)?;
-        socket.set_nodelay(nodelay)?;
+        apply_socket_options(
+            SockRef::from(&socket),
+            nodelay,
+            send_buffer_size,
+            recv_buffer_size,
+        )?;
 
         match dial_addresses.local_dial_address(&remote_address.ip()) {
             Ok(Some(dial_address)) => {
@@ -225,9 +311,11 @@ impl TransportBuilder for TcpTransport {
                 context,
                 dial_addresses,
                 canceled: HashSet::new(),
+                pending_inbound: HashSet::new(),
                 opened_raw: HashMap::new(),
                 pending_open: HashMap::new(),
                 pending_dials: HashMap::new(),
+                dial_aborts: HashMap::new(),
                 pending_connections: FuturesUnordered::new(),
                 pending_raw_connections: FuturesUnordered::new(),
             },
@@ -242,24 +330,42 @@ impl Transport for TcpTransport {
 
         let (socket_address, peer) = TcpAddress::multiaddr_to_socket_address(&address)?;
         let yamux_config = self.config.yamux_config.clone();
+        let security_protocols = self.config.security_protocols.clone();
         let max_read_ahead_factor = self.config.noise_read_ahead_frame_count;
         let max_write_buffer_size = self.config.noise_write_buffer_size;
         let connection_open_timeout = self.config.connection_open_timeout;
         let substream_open_timeout = self.config.substream_open_timeout;
+        let multistream_select_version = self.config.multistream_select_version;
         let dial_addresses = self.dial_addresses.clone();
         let keypair = self.context.keypair.clone();
+        let bandwidth_sink = self.context.bandwidth_sink.clone();
         let nodelay = self.config.nodelay;
+        let send_buffer_size = self.config.send_buffer_size;
+        let recv_buffer_size = self.config.recv_buffer_size;
+        let resolver = self.context.resolver.clone();
 
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
         self.pending_dials.insert(connection_id, address.clone());
-        self.pending_connections.push(Box::pin(async move {
-            let (_, stream) =
-                TcpTransport::dial_peer(address, dial_addresses, connection_open_timeout, nodelay)
-                    .await
-                    .map_err(|error| (connection_id, error))?;
+        self.dial_aborts.insert(connection_id, abort_handle);
+
+        let future = async move {
+            let (_, stream) = TcpTransport::dial_peer(
+                address,
+                dial_addresses,
+                connection_open_timeout,
+                nodelay,
+                send_buffer_size,
+                recv_buffer_size,
+                resolver,
+            )
+            .await
+            .map_err(|error| (connection_id, error))?;
 
             TcpConnection::open_connection(
                 connection_id,
                 keypair,
+                bandwidth_sink,
+                security_protocols,
                 stream,
                 socket_address,
                 peer,
@@ -268,9 +374,17 @@ impl Transport for TcpTransport {
                 max_write_buffer_size,
                 connection_open_timeout,
                 substream_open_timeout,
+                multistream_select_version,
             )
             .await
             .map_err(|error| (connection_id, error))
+        };
+
+        self.pending_connections.push(Box::pin(async move {
+            match Abortable::new(future, abort_registration).await {
+                Ok(result) => result,
+                Err(_) => Err((connection_id, Error::DialAborted)),
+            }
         }));
 
         Ok(())
@@ -284,6 +398,7 @@ impl Transport for TcpTransport {
         let protocol_set = self.context.protocol_set(connection_id);
         let bandwidth_sink = self.context.bandwidth_sink.clone();
         let next_substream_id = self.context.next_substream_id.clone();
+        let max_inbound_substreams = self.config.max_inbound_substreams;
 
         tracing::trace!(
             target: LOG_TARGET,
@@ -292,10 +407,15 @@ impl Transport for TcpTransport {
         );
 
         self.context.executor.run(Box::pin(async move {
-            if let Err(error) =
-                TcpConnection::new(context, protocol_set, bandwidth_sink, next_substream_id)
-                    .start()
-                    .await
+            if let Err(error) = TcpConnection::new(
+                context,
+                protocol_set,
+                bandwidth_sink,
+                next_substream_id,
+                max_inbound_substreams,
+            )
+            .start()
+            .await
             {
                 tracing::debug!(
                     target: LOG_TARGET,
@@ -327,33 +447,46 @@ impl Transport for TcpTransport {
                 let dial_addresses = self.dial_addresses.clone();
                 let connection_open_timeout = self.config.connection_open_timeout;
                 let nodelay = self.config.nodelay;
+                let send_buffer_size = self.config.send_buffer_size;
+                let recv_buffer_size = self.config.recv_buffer_size;
+                let resolver = self.context.resolver.clone();
 
                 async move {
                     TcpTransport::dial_peer(
-                        address,
+                        address.clone(),
                         dial_addresses,
                         connection_open_timeout,
                         nodelay,
+                        send_buffer_size,
+                        recv_buffer_size,
+                        resolver,
                     )
                     .await
+                    .map_err(|error| (address, error.into_dial_error()))
                 }
             })
             .collect();
 
         self.pending_raw_connections.push(Box::pin(async move {
+            let mut errors = Vec::new();
+
             while let Some(result) = futures.next().await {
                 match result {
                     Ok((address, stream)) => return Ok((connection_id, address, stream)),
-                    Err(error) => tracing::debug!(
-                        target: LOG_TARGET,
-                        ?connection_id,
-                        ?error,
-                        "failed to open connection",
-                    ),
+                    Err((address, error)) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?connection_id,
+                            ?address,
+                            ?error,
+                            "failed to open connection",
+                        );
+                        errors.push((address, error));
+                    }
                 }
             }
 
-            Err(connection_id)
+            Err((connection_id, errors))
         }));
 
         Ok(())
@@ -367,11 +500,14 @@ impl Transport for TcpTransport {
 
         let (socket_address, peer) = TcpAddress::multiaddr_to_socket_address(&address)?;
         let yamux_config = self.config.yamux_config.clone();
+        let security_protocols = self.config.security_protocols.clone();
         let max_read_ahead_factor = self.config.noise_read_ahead_frame_count;
         let max_write_buffer_size = self.config.noise_write_buffer_size;
         let connection_open_timeout = self.config.connection_open_timeout;
         let substream_open_timeout = self.config.substream_open_timeout;
+        let multistream_select_version = self.config.multistream_select_version;
         let keypair = self.context.keypair.clone();
+        let bandwidth_sink = self.context.bandwidth_sink.clone();
 
         tracing::trace!(
             target: LOG_TARGET,
@@ -389,12 +525,15 @@ impl Transport for TcpTransport {
                     peer,
                     connection_id,
                     keypair,
+                    bandwidth_sink,
+                    security_protocols,
                     Role::Dialer,
                     socket_address,
                     yamux_config,
                     max_read_ahead_factor,
                     max_write_buffer_size,
                     substream_open_timeout,
+                    multistream_select_version,
                 )
                 .await
                 .map_err(|error| (connection_id, error))
@@ -411,7 +550,12 @@ impl Transport for TcpTransport {
     }
 
     fn cancel(&mut self, connection_id: ConnectionId) {
-        self.canceled.insert(connection_id);
+        match self.dial_aborts.remove(&connection_id) {
+            Some(abort_handle) => abort_handle.abort(),
+            None => {
+                self.canceled.insert(connection_id);
+            }
+        }
     }
 }
 
@@ -419,10 +563,12 @@ impl Stream for TcpTransport {
     type Item = TransportEvent;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        while let Poll::Ready(event) = self.listener.poll_next_unpin(cx) {
-            match event {
-                None | Some(Err(_)) => return Poll::Ready(None),
-                Some(Ok((connection, address))) => {
+        // stop accepting inbound connections while too many of them are being negotiated
+        while self.pending_inbound.len() < self.config.max_pending_negotiations {
+            match self.listener.poll_next_unpin(cx) {
+                Poll::Pending => break,
+                Poll::Ready(None | Some(Err(_))) => return Poll::Ready(None),
+                Poll::Ready(Some(Ok((connection, address)))) => {
                     self.on_inbound_connection(connection, address);
                 }
             }
@@ -448,14 +594,29 @@ impl Stream for TcpTransport {
                         }));
                     }
                 }
-                Err(connection_id) =>
+                Err((connection_id, errors)) =>
                     if !self.canceled.remove(&connection_id) {
-                        return Poll::Ready(Some(TransportEvent::OpenFailure { connection_id }));
+                        return Poll::Ready(Some(TransportEvent::OpenFailure {
+                            connection_id,
+                            errors,
+                        }));
                     },
             }
         }
 
         while let Poll::Ready(Some(connection)) = self.pending_connections.poll_next_unpin(cx) {
+            let connection_id = match &connection {
+                Ok(connection) => connection.connection_id(),
+                Err((connection_id, _)) => *connection_id,
+            };
+            self.dial_aborts.remove(&connection_id);
+
+            // if the listener was paused, poll it again now that a negotiation slot is free
+            let at_capacity = self.pending_inbound.len() >= self.config.max_pending_negotiations;
+            if self.pending_inbound.remove(&connection_id) && at_capacity {
+                cx.waker().wake_by_ref();
+            }
+
             match connection {
                 Ok(connection) => {
                     let peer = connection.peer();
@@ -465,14 +626,30 @@ impl Stream for TcpTransport {
                     return Poll::Ready(Some(TransportEvent::ConnectionEstablished {
                         peer,
                         endpoint,
+                        transport: SupportedTransport::Tcp,
                     }));
                 }
                 Err((connection_id, error)) => {
+                    match &error {
+                        Error::HandshakeFailed(error) => tracing::debug!(
+                            target: LOG_TARGET,
+                            ?connection_id,
+                            ?error,
+                            "security handshake failed",
+                        ),
+                        error => tracing::trace!(
+                            target: LOG_TARGET,
+                            ?connection_id,
+                            ?error,
+                            "failed to negotiate connection",
+                        ),
+                    }
+
                     if let Some(address) = self.pending_dials.remove(&connection_id) {
                         return Poll::Ready(Some(TransportEvent::DialFailure {
                             connection_id,
                             address,
-                            error,
+                            error: error.into_dial_error(),
                         }));
                     }
                 }
@@ -488,16 +665,24 @@ mod tests {
     use super::*;
     use crate::{
         codec::ProtocolCodec,
-        crypto::ed25519::Keypair,
+        crypto::{ed25519::Keypair, SecurityProtocol},
         executor::DefaultExecutor,
-        transport::manager::{ProtocolContext, SupportedTransport, TransportManager},
-        types::protocol::ProtocolName,
+        protocol::{Direction, InnerTransportEvent},
+        transport::{
+            filter::IpFilter,
+            manager::{ProtocolContext, SupportedTransport, TransportManager},
+            MultistreamSelectVersion,
+        },
+        types::{protocol::ProtocolName, SubstreamId},
         BandwidthSink, PeerId,
     };
+    use bytes::Bytes;
+    use futures::SinkExt;
     use multiaddr::Protocol;
     use multihash::Multihash;
     use std::{collections::HashSet, sync::Arc};
-    use tokio::sync::mpsc::channel;
+    use tokio::{io::AsyncReadExt, sync::mpsc::channel};
+    use trust_dns_resolver::TokioAsyncResolver;
 
     #[tokio::test]
     async fn connect_and_accept_works() {
@@ -512,12 +697,17 @@ mod tests {
 
         let handle1 = crate::transport::manager::TransportHandle {
             executor: Arc::new(DefaultExecutor {}),
+            resolver: Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
             protocol_names: Vec::new(),
             next_substream_id: Default::default(),
             next_connection_id: Default::default(),
             keypair: keypair1.clone(),
             tx: event_tx1,
             bandwidth_sink: bandwidth_sink.clone(),
+            shutdown: Default::default(),
 
             protocols: HashMap::from_iter([(
                 ProtocolName::from("/notif/1"),
@@ -543,12 +733,17 @@ mod tests {
 
         let handle2 = crate::transport::manager::TransportHandle {
             executor: Arc::new(DefaultExecutor {}),
+            resolver: Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
             protocol_names: Vec::new(),
             next_substream_id: Default::default(),
             next_connection_id: Default::default(),
             keypair: keypair2.clone(),
             tx: event_tx2,
             bandwidth_sink: bandwidth_sink.clone(),
+            shutdown: Default::default(),
 
             protocols: HashMap::from_iter([(
                 ProtocolName::from("/notif/1"),
@@ -579,6 +774,161 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn inbound_substreams_over_limit_rejected() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let keypair1 = Keypair::generate();
+        let (tx1, mut rx1) = channel(64);
+        let (event_tx1, _event_rx1) = channel(64);
+        let bandwidth_sink = BandwidthSink::new();
+
+        let handle1 = crate::transport::manager::TransportHandle {
+            executor: Arc::new(DefaultExecutor {}),
+            resolver: Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
+            protocol_names: Vec::new(),
+            next_substream_id: Default::default(),
+            next_connection_id: Default::default(),
+            keypair: keypair1.clone(),
+            tx: event_tx1,
+            bandwidth_sink: bandwidth_sink.clone(),
+            shutdown: Default::default(),
+
+            protocols: HashMap::from_iter([(
+                ProtocolName::from("/notif/1"),
+                ProtocolContext {
+                    tx: tx1,
+                    codec: ProtocolCodec::Identity(32),
+                    fallback_names: Vec::new(),
+                },
+            )]),
+        };
+        let transport_config1 = Config {
+            listen_addresses: vec!["/ip6/::1/tcp/0".parse().unwrap()],
+            max_inbound_substreams: 2usize,
+            ..Default::default()
+        };
+
+        let (mut transport1, listen_addresses) =
+            TcpTransport::new(handle1, transport_config1).unwrap();
+        let listen_address = listen_addresses[0].clone();
+
+        let keypair2 = Keypair::generate();
+        let (tx2, mut rx2) = channel(64);
+        let (event_tx2, _event_rx2) = channel(64);
+
+        let handle2 = crate::transport::manager::TransportHandle {
+            executor: Arc::new(DefaultExecutor {}),
+            resolver: Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
+            protocol_names: Vec::new(),
+            next_substream_id: Default::default(),
+            next_connection_id: Default::default(),
+            keypair: keypair2.clone(),
+            tx: event_tx2,
+            bandwidth_sink: bandwidth_sink.clone(),
+            shutdown: Default::default(),
+
+            protocols: HashMap::from_iter([(
+                ProtocolName::from("/notif/1"),
+                ProtocolContext {
+                    tx: tx2,
+                    codec: ProtocolCodec::Identity(32),
+                    fallback_names: Vec::new(),
+                },
+            )]),
+        };
+        let transport_config2 = Config {
+            listen_addresses: vec!["/ip6/::1/tcp/0".parse().unwrap()],
+            ..Default::default()
+        };
+
+        let (mut transport2, _) = TcpTransport::new(handle2, transport_config2).unwrap();
+        transport2.dial(ConnectionId::new(), listen_address).unwrap();
+
+        let (res1, res2) = tokio::join!(transport1.next(), transport2.next());
+
+        match (res1, res2) {
+            (
+                Some(TransportEvent::ConnectionEstablished {
+                    endpoint: endpoint1,
+                    ..
+                }),
+                Some(TransportEvent::ConnectionEstablished {
+                    endpoint: endpoint2,
+                    ..
+                }),
+            ) => {
+                transport1.accept(endpoint1.connection_id()).unwrap();
+                transport2.accept(endpoint2.connection_id()).unwrap();
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        let mut connection = match rx2.recv().await.unwrap() {
+            InnerTransportEvent::ConnectionEstablished { sender, .. } => sender,
+            _ => panic!("invalid event"),
+        };
+        // keep the listener's connection open for the duration of the test
+        let _listener_connection = match rx1.recv().await.unwrap() {
+            InnerTransportEvent::ConnectionEstablished { sender, .. } => sender,
+            _ => panic!("invalid event"),
+        };
+
+        for substream_id in 0..3usize {
+            let permit = connection.try_get_permit().unwrap();
+            connection
+                .open_substream(
+                    ProtocolName::from("/notif/1"),
+                    Vec::new(),
+                    SubstreamId::from(substream_id),
+                    permit,
+                )
+                .unwrap();
+        }
+
+        // two substreams are opened and the excess substream is reset by the listener
+        let mut outbound = Vec::new();
+        let mut failures = 0usize;
+
+        for _ in 0..3 {
+            match rx2.recv().await.unwrap() {
+                InnerTransportEvent::SubstreamOpened { substream, .. } => outbound.push(substream),
+                InnerTransportEvent::SubstreamOpenFailure { .. } => failures += 1,
+                _ => panic!("invalid event"),
+            }
+        }
+        assert_eq!(outbound.len(), 2usize);
+        assert_eq!(failures, 1usize);
+
+        let mut inbound = Vec::new();
+        for _ in 0..2 {
+            match rx1.recv().await.unwrap() {
+                InnerTransportEvent::SubstreamOpened {
+                    substream,
+                    direction: Direction::Inbound,
+                    ..
+                } => inbound.push(substream),
+                _ => panic!("invalid event"),
+            }
+        }
+
+        // the accepted substreams stay open
+        for substream in outbound.iter_mut() {
+            substream.send(Bytes::from(vec![1u8; 32])).await.unwrap();
+        }
+        for substream in inbound.iter_mut() {
+            assert_eq!(substream.next().await.unwrap().unwrap(), vec![1u8; 32]);
+        }
+    }
+
     #[tokio::test]
     async fn dial_failure() {
         let _ = tracing_subscriber::fmt()
@@ -592,12 +942,17 @@ mod tests {
 
         let handle1 = crate::transport::manager::TransportHandle {
             executor: Arc::new(DefaultExecutor {}),
+            resolver: Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
             protocol_names: Vec::new(),
             next_substream_id: Default::default(),
             next_connection_id: Default::default(),
             keypair: keypair1.clone(),
             tx: event_tx1,
             bandwidth_sink: bandwidth_sink.clone(),
+            shutdown: Default::default(),
 
             protocols: HashMap::from_iter([(
                 ProtocolName::from("/notif/1"),
@@ -628,12 +983,17 @@ mod tests {
 
         let handle2 = crate::transport::manager::TransportHandle {
             executor: Arc::new(DefaultExecutor {}),
+            resolver: Arc::new(TokioAsyncResolver::tokio(
+                Default::default(),
+                Default::default(),
+            )),
             protocol_names: Vec::new(),
             next_substream_id: Default::default(),
             next_connection_id: Default::default(),
             keypair: keypair2.clone(),
             tx: event_tx2,
             bandwidth_sink: bandwidth_sink.clone(),
+            shutdown: Default::default(),
 
             protocols: HashMap::from_iter([(
                 ProtocolName::from("/notif/1"),
@@ -657,9 +1017,7 @@ mod tests {
                 0, 0, 0, 0, 0, 0, 0, 1,
             )))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer1.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer1.to_multihash()));
 
         transport2.dial(ConnectionId::new(), address).unwrap();
 
@@ -703,9 +1061,7 @@ mod tests {
         let multiaddr = Multiaddr::empty()
             .with(Protocol::Ip4(std::net::Ipv4Addr::new(255, 254, 253, 252)))
             .with(Protocol::Tcp(8888))
-            .with(Protocol::P2p(
-                Multihash::from_bytes(&peer_id.to_bytes()).unwrap(),
-            ));
+            .with(Protocol::P2p(peer_id.to_multihash()));
         manager.dial_address(multiaddr.clone()).await.unwrap();
 
         assert!(transport.pending_dials.is_empty());
@@ -726,4 +1082,316 @@ mod tests {
         ));
         assert!(transport.pending_dials.is_empty());
     }
+
+    #[tokio::test]
+    async fn canceled_dial_is_aborted() {
+        let (manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        let (mut transport, _) = TcpTransport::new(
+            handle,
+            Config {
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // listener which never responds to the connection negotiation
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let multiaddr = Multiaddr::empty()
+            .with(Protocol::Ip4(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+            .with(Protocol::Tcp(listener.local_addr().unwrap().port()))
+            .with(Protocol::P2p(Multihash::from(PeerId::random())));
+
+        let connection_id = ConnectionId::from(0usize);
+        transport.dial(connection_id, multiaddr.clone()).unwrap();
+        transport.cancel(connection_id);
+
+        match tokio::time::timeout(Duration::from_secs(5), transport.next()).await {
+            Ok(Some(TransportEvent::DialFailure {
+                connection_id: failed_connection_id,
+                address,
+                error: Error::DialAborted,
+            })) => {
+                assert_eq!(failed_connection_id, connection_id);
+                assert_eq!(address, multiaddr);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        assert!(transport.pending_dials.is_empty());
+        assert!(transport.dial_aborts.is_empty());
+        assert!(transport.canceled.is_empty());
+    }
+
+    #[tokio::test]
+    async fn socket_options_applied() {
+        let (manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        let config = Config {
+            listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            nodelay: true,
+            send_buffer_size: Some(64 * 1024),
+            recv_buffer_size: Some(64 * 1024),
+            ..Default::default()
+        };
+        let (mut transport, listen_addresses) = TcpTransport::new(handle, config.clone()).unwrap();
+
+        // dial the transport's own listener and accept the connection the way the transport does
+        //
+        // the listen port isn't reused for the dial as it would connect the socket to itself
+        let (dialed, accepted) = tokio::join!(
+            TcpTransport::dial_peer(
+                listen_addresses[0].clone(),
+                DialAddresses::NoReuse,
+                config.connection_open_timeout,
+                config.nodelay,
+                config.send_buffer_size,
+                config.recv_buffer_size,
+                Arc::clone(&transport.context.resolver),
+            ),
+            transport.listener.next(),
+        );
+        let (_, dialed) = dialed.unwrap();
+        let (accepted, address) = accepted.unwrap().unwrap();
+        assert!(transport.prepare_inbound_connection(&accepted, address));
+
+        // the kernel may round the buffer sizes up but never below the requested size
+        for stream in [&dialed, &accepted] {
+            let socket = SockRef::from(stream);
+
+            assert!(socket.nodelay().unwrap());
+            assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+            assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+        }
+    }
+
+    #[tokio::test]
+    async fn inbound_negotiations_limited() {
+        let (manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        let (mut transport, listen_addresses) = TcpTransport::new(
+            handle,
+            Config {
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+                max_pending_negotiations: 2usize,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let Some(Protocol::Tcp(port)) = listen_addresses[0].iter().nth(1) else {
+            panic!("invalid listen address: {:?}", listen_addresses[0]);
+        };
+
+        // open connections which never start the handshake
+        let mut clients = Vec::new();
+        for _ in 0..5 {
+            clients.push(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        }
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), transport.next())
+                .await
+                .is_err()
+        );
+        assert_eq!(transport.pending_inbound.len(), 2usize);
+
+        // closing the connections fails the pending negotiations and the rest of the
+        // connections are accepted, negotiated and failed as well
+        drop(clients);
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), transport.next())
+                .await
+                .is_err()
+        );
+        assert!(transport.pending_inbound.is_empty());
+        assert!(transport.pending_connections.is_empty());
+    }
+
+    #[tokio::test]
+    async fn inbound_connection_rejected_by_filter() {
+        let (manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        let (mut transport, listen_addresses) = TcpTransport::new(
+            handle,
+            Config {
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+                connection_filter: Some(Arc::new(
+                    IpFilter::new().with_denied("127.0.0.0/8".parse().unwrap()),
+                )),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let Some(Protocol::Tcp(port)) = listen_addresses[0].iter().nth(1) else {
+            panic!("invalid listen address: {:?}", listen_addresses[0]);
+        };
+
+        let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), transport.next())
+                .await
+                .is_err()
+        );
+        assert!(transport.pending_inbound.is_empty());
+        assert!(transport.pending_connections.is_empty());
+
+        // the connection was closed without negotiation
+        let mut buffer = [0u8; 32];
+        assert!(std::matches!(
+            client.read(&mut buffer).await,
+            Ok(0) | Err(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn tls_connection_established() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let keypair1 = Keypair::generate();
+        let (manager1, _handle1) = TransportManager::new(
+            keypair1.clone(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let handle1 = manager1.transport_handle(Arc::new(DefaultExecutor {}));
+        let (mut transport1, listen_addresses) = TcpTransport::new(
+            handle1,
+            Config {
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+                security_protocols: vec![SecurityProtocol::Tls],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let keypair2 = Keypair::generate();
+        let (manager2, _handle2) = TransportManager::new(
+            keypair2.clone(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let handle2 = manager2.transport_handle(Arc::new(DefaultExecutor {}));
+        let (mut transport2, _) = TcpTransport::new(
+            handle2,
+            Config {
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+                security_protocols: vec![SecurityProtocol::Tls],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        transport2.dial(ConnectionId::new(), listen_addresses[0].clone()).unwrap();
+
+        let (res1, res2) = tokio::join!(transport1.next(), transport2.next());
+
+        match res1 {
+            Some(TransportEvent::ConnectionEstablished { peer, .. }) => {
+                assert_eq!(peer, PeerId::from_public_key(&keypair2.public().into()));
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+        match res2 {
+            Some(TransportEvent::ConnectionEstablished { peer, .. }) => {
+                assert_eq!(peer, PeerId::from_public_key(&keypair1.public().into()));
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn security_protocols_must_not_be_empty() {
+        let _ = crate::config::ConfigBuilder::new().with_tcp(Config {
+            security_protocols: Vec::new(),
+            ..Default::default()
+        });
+    }
+
+    #[tokio::test]
+    async fn lazy_multistream_select_connection_established() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .try_init();
+
+        let keypair1 = Keypair::generate();
+        let (manager1, _handle1) = TransportManager::new(
+            keypair1.clone(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let handle1 = manager1.transport_handle(Arc::new(DefaultExecutor {}));
+        let (mut transport1, listen_addresses) = TcpTransport::new(
+            handle1,
+            Config {
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let keypair2 = Keypair::generate();
+        let (manager2, _handle2) = TransportManager::new(
+            keypair2.clone(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let handle2 = manager2.transport_handle(Arc::new(DefaultExecutor {}));
+        let (mut transport2, _) = TcpTransport::new(
+            handle2,
+            Config {
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+                security_protocols: vec![SecurityProtocol::Noise],
+                multistream_select_version: MultistreamSelectVersion::V1Lazy,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        transport2.dial(ConnectionId::new(), listen_addresses[0].clone()).unwrap();
+
+        let (res1, res2) = tokio::join!(transport1.next(), transport2.next());
+
+        match res1 {
+            Some(TransportEvent::ConnectionEstablished { peer, .. }) => {
+                assert_eq!(peer, PeerId::from_public_key(&keypair2.public().into()));
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+        match res2 {
+            Some(TransportEvent::ConnectionEstablished { peer, .. }) => {
+                assert_eq!(peer, PeerId::from_public_key(&keypair1.public().into()));
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+    }
 }
@@ -26,6 +26,7 @@ use tokio_util::compat::Compat;
 use std::{
     io,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 
@@ -43,6 +44,10 @@ pub struct Substream {
 
     /// Connection permit.
     _permit: Permit,
+
+    /// Inbound substream slot, if the substream counts towards the inbound substream limit of
+    /// the connection.
+    _slot: Option<Arc<()>>,
 }
 
 impl Substream {
@@ -51,11 +56,13 @@ impl Substream {
         io: Compat<crate::yamux::Stream>,
         bandwidth_sink: BandwidthSink,
         _permit: Permit,
+        _slot: Option<Arc<()>>,
     ) -> Self {
         Self {
             io,
             bandwidth_sink,
             _permit,
+            _slot,
         }
     }
 }
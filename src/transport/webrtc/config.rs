@@ -20,8 +20,12 @@
 
 //! WebRTC transport configuration.
 
+use crate::transport::filter::ConnectionFilter;
+
 use multiaddr::Multiaddr;
 
+use std::sync::Arc;
+
 /// WebRTC transport configuration.
 #[derive(Debug)]
 pub struct Config {
@@ -32,6 +36,14 @@ pub struct Config {
     ///
     /// How many datagrams can the buffer between `WebRtcTransport` and a connection handler hold.
     pub datagram_buffer_size: usize,
+
+    /// Filter for inbound connections.
+    ///
+    /// Consulted with the remote address of each new client before a connection is opened.
+    /// Datagrams from rejected clients are dropped.
+    ///
+    /// Defaults to `None`, i.e., all inbound connections are accepted.
+    pub connection_filter: Option<Arc<dyn ConnectionFilter>>,
 }
 
 impl Default for Config {
@@ -41,6 +53,7 @@ impl Default for Config {
                 .parse()
                 .expect("valid multiaddress")],
             datagram_buffer_size: 2048,
+            connection_filter: None,
         }
     }
 }
@@ -24,6 +24,7 @@ use crate::{
     protocol::{Direction, Permit, ProtocolCommand, ProtocolSet},
     substream::Substream,
     transport::{
+        common::metered::ConnectionMeter,
         webrtc::{
             substream::{Event as SubstreamEvent, Substream as WebRtcSubstream, SubstreamHandle},
             util::WebRtcMessage,
@@ -189,6 +190,9 @@ pub struct WebRtcConnection {
     /// RX channel for receiving datagrams from the transport.
     dgram_rx: Receiver<Vec<u8>>,
 
+    /// Meter of the datagrams sent and received over the connection.
+    meter: ConnectionMeter,
+
     /// Pending outbound channels.
     pending_outbound: HashMap<ChannelId, ChannelContext>,
 
@@ -210,6 +214,7 @@ impl WebRtcConnection {
         protocol_set: ProtocolSet,
         endpoint: Endpoint,
         dgram_rx: Receiver<Vec<u8>>,
+        meter: ConnectionMeter,
     ) -> Self {
         Self {
             rtc,
@@ -220,6 +225,7 @@ impl WebRtcConnection {
             socket,
             endpoint,
             dgram_rx,
+            meter,
             pending_outbound: HashMap::new(),
             channels: HashMap::new(),
             handles: SubstreamHandleSet::new(),
@@ -681,6 +687,7 @@ impl WebRtcConnection {
                     );
 
                     self.socket.try_send_to(&v.contents, v.destination).unwrap();
+                    self.meter.on_write(v.contents.len());
                     continue;
                 }
                 Output::Event(v) => match v {
@@ -753,6 +760,8 @@ impl WebRtcConnection {
                 biased;
                 datagram = self.dgram_rx.recv() => match datagram {
                     Some(datagram) => {
+                        self.meter.on_read(datagram.len());
+
                         let input = Input::Receive(
                             Instant::now(),
                             Receive {
@@ -23,7 +23,8 @@
 use crate::{
     error::{AddressError, Error},
     transport::{
-        manager::TransportHandle,
+        filter::ConnectionFilter,
+        manager::{SupportedTransport, TransportHandle},
         webrtc::{config::Config, connection::WebRtcConnection, opening::OpeningWebRtcConnection},
         Endpoint, Transport, TransportBuilder, TransportEvent,
     },
@@ -134,6 +135,9 @@ pub(crate) struct WebRtcTransport {
     /// Datagram buffer size.
     datagram_buffer_size: usize,
 
+    /// Filter for inbound connections.
+    connection_filter: Option<Arc<dyn ConnectionFilter>>,
+
     /// Connected peers.
     open: HashMap<SocketAddr, ConnectionContext>,
 
@@ -338,6 +342,17 @@ impl WebRtcTransport {
             }
         }
 
+        if let Some(filter) = &self.connection_filter {
+            if !filter.accept(&source) {
+                tracing::trace!(
+                    target: LOG_TARGET,
+                    ?source,
+                    "datagram from client rejected by filter",
+                );
+                return Ok(false);
+            }
+        }
+
         if buffer.is_empty() {
             // str0m crate panics if the buffer doesn't contain at least one byte:
             // https://github.com/algesten/str0m/blob/2c5dc8ee8ddead08699dd6852a27476af6992a5c/src/io/mod.rs#L222
@@ -481,6 +496,7 @@ impl TransportBuilder for WebRtcTransport {
                 timeouts: HashMap::new(),
                 pending_events: VecDeque::new(),
                 datagram_buffer_size: config.datagram_buffer_size,
+                connection_filter: config.connection_filter,
             },
             listen_multi_addresses,
         ))
@@ -541,6 +557,7 @@ impl Transport for WebRtcTransport {
             protocol_set,
             endpoint,
             rx,
+            self.context.bandwidth_sink.connection_meter(peer),
         );
         self.open.insert(
             source,
@@ -645,7 +662,11 @@ impl Stream for WebRtcTransport {
 
                                     // keep polling the connection until it registers a timeout
                                     this.pending_events.push_back(
-                                        TransportEvent::ConnectionEstablished { peer, endpoint },
+                                        TransportEvent::ConnectionEstablished {
+                                            peer,
+                                            endpoint,
+                                            transport: SupportedTransport::WebRtc,
+                                        },
                                     );
                                 }
                                 ConnectionEvent::ConnectionClosed => {
@@ -698,8 +719,11 @@ impl Stream for WebRtcTransport {
                                 .insert(endpoint.connection_id(), (peer, source, endpoint.clone()));
 
                             // keep polling the connection until it registers a timeout
-                            pending_event =
-                                Some(TransportEvent::ConnectionEstablished { peer, endpoint });
+                            pending_event = Some(TransportEvent::ConnectionEstablished {
+                                peer,
+                                endpoint,
+                                transport: SupportedTransport::WebRtc,
+                            });
                         }
                         ConnectionEvent::ConnectionClosed => {
                             this.opening.remove(&source);
@@ -753,3 +777,65 @@ fn is_stun_packet(bytes: &[u8]) -> bool {
     // 20 bytes for the header, then follows attributes.
     bytes.len() >= 20 && bytes[0] < 2
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::ed25519::Keypair,
+        executor::DefaultExecutor,
+        transport::{filter::IpFilter, manager::TransportManager},
+        BandwidthSink,
+    };
+    use std::collections::HashSet;
+
+    fn make_transport(connection_filter: Option<Arc<dyn ConnectionFilter>>) -> WebRtcTransport {
+        let (manager, _handle) = TransportManager::new(
+            Keypair::generate(),
+            HashSet::new(),
+            BandwidthSink::new(),
+            8usize,
+        );
+        let handle = manager.transport_handle(Arc::new(DefaultExecutor {}));
+        let (transport, _) = WebRtcTransport::new(
+            handle,
+            Config {
+                listen_addresses: vec!["/ip4/127.0.0.1/udp/0/webrtc-direct".parse().unwrap()],
+                connection_filter,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        transport
+    }
+
+    /// STUN binding request without attributes.
+    fn binding_request() -> Vec<u8> {
+        let mut datagram = vec![0x00, 0x01, 0x00, 0x00, 0x21, 0x12, 0xa4, 0x42];
+        datagram.extend_from_slice(&[0xaa; 12]);
+        datagram
+    }
+
+    #[tokio::test]
+    async fn inbound_connection_rejected_by_filter() {
+        let source: SocketAddr = "127.0.0.1:1337".parse().unwrap();
+
+        // without a filter, the binding request is processed and rejected as it lacks credentials
+        let mut transport = make_transport(None);
+        assert!(std::matches!(
+            transport.on_socket_input(source, binding_request()),
+            Err(Error::InvalidData)
+        ));
+
+        let mut transport = make_transport(Some(Arc::new(
+            IpFilter::new().with_denied("127.0.0.0/8".parse().unwrap()),
+        )));
+        assert!(std::matches!(
+            transport.on_socket_input(source, binding_request()),
+            Ok(false)
+        ));
+        assert!(transport.opening.is_empty());
+        assert!(transport.open.is_empty());
+    }
+}
@@ -22,9 +22,14 @@
 
 use crate::{
     crypto::noise::{MAX_READ_AHEAD_FACTOR, MAX_WRITE_BUFFER_SIZE},
-    transport::{CONNECTION_OPEN_TIMEOUT, SUBSTREAM_OPEN_TIMEOUT},
+    transport::{
+        filter::ConnectionFilter, MultistreamSelectVersion, CONNECTION_OPEN_TIMEOUT,
+        SUBSTREAM_OPEN_TIMEOUT,
+    },
 };
 
+use std::sync::Arc;
+
 /// WebSocket transport configuration.
 #[derive(Debug)]
 pub struct Config {
@@ -46,6 +51,16 @@ pub struct Config {
     /// Defaults to `false`.
     pub nodelay: bool,
 
+    /// `multistream-select` version used when upgrading outbound connections.
+    ///
+    /// With [`MultistreamSelectVersion::V1Lazy`], the dialer doesn't wait for the remote to
+    /// confirm the Noise and Yamux protocols before sending the first messages of the
+    /// respective upgrade, saving a round trip for each. Inbound connections are accepted from
+    /// dialers using either version.
+    ///
+    /// Defaults to [`MultistreamSelectVersion::V1`].
+    pub multistream_select_version: MultistreamSelectVersion,
+
     /// Yamux configuration.
     pub yamux_config: crate::yamux::Config,
 
@@ -80,6 +95,14 @@ pub struct Config {
     /// How long should litep2p wait for a substream to be opened before considering
     /// the substream rejected.
     pub substream_open_timeout: std::time::Duration,
+
+    /// Filter for inbound connections.
+    ///
+    /// Consulted with the remote address of each accepted connection before any protocol
+    /// negotiation takes place. Rejected connections are closed immediately.
+    ///
+    /// Defaults to `None`, i.e., all inbound connections are accepted.
+    pub connection_filter: Option<Arc<dyn ConnectionFilter>>,
 }
 
 impl Default for Config {
@@ -91,11 +114,13 @@ impl Default for Config {
             ],
             reuse_port: true,
             nodelay: false,
+            multistream_select_version: MultistreamSelectVersion::V1,
             yamux_config: Default::default(),
             noise_read_ahead_frame_count: MAX_READ_AHEAD_FACTOR,
             noise_write_buffer_size: MAX_WRITE_BUFFER_SIZE,
             connection_open_timeout: CONNECTION_OPEN_TIMEOUT,
             substream_open_timeout: SUBSTREAM_OPEN_TIMEOUT,
+            connection_filter: None,
         }
     }
 }
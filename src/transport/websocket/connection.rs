@@ -29,6 +29,7 @@ use crate::{
     protocol::{Direction, Permit, ProtocolCommand, ProtocolSet},
     substream,
     transport::{
+        common::metered::MeteredStream,
         websocket::{stream::BufferedStream, substream::Substream},
         Endpoint,
     },
@@ -36,7 +37,9 @@ use crate::{
     BandwidthSink, PeerId,
 };
 
-use futures::{future::BoxFuture, stream::FuturesUnordered, AsyncRead, AsyncWrite, StreamExt};
+use futures::{
+    future::BoxFuture, stream::FuturesUnordered, AsyncRead, AsyncWrite, AsyncWriteExt, StreamExt,
+};
 use multiaddr::{multihash::Multihash, Multiaddr, Protocol};
 use tokio::net::TcpStream;
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
@@ -51,6 +54,13 @@ mod schema {
     }
 }
 
+/// Upgraded WebSocket stream.
+///
+/// Noise and Yamux run on top of `Negotiated` as a `V1Lazy` negotiation isn't finished until
+/// the first read from the stream.
+type NegotiatedStream =
+    Negotiated<NoiseSocket<Negotiated<BufferedStream<MaybeTlsStream<TcpStream>>>>>;
+
 /// Logging target for the file.
 const LOG_TARGET: &str = "litep2p::websocket::connection";
 
@@ -106,8 +116,7 @@ pub(super) struct NegotiatedConnection {
     endpoint: Endpoint,
 
     /// Yamux connection.
-    connection:
-        crate::yamux::ControlledConnection<NoiseSocket<BufferedStream<MaybeTlsStream<TcpStream>>>>,
+    connection: crate::yamux::ControlledConnection<MeteredStream<NegotiatedStream>>,
 
     /// Yamux control.
     control: crate::yamux::Control,
@@ -136,8 +145,7 @@ pub(crate) struct WebSocketConnection {
     protocol_set: ProtocolSet,
 
     /// Yamux connection.
-    connection:
-        crate::yamux::ControlledConnection<NoiseSocket<BufferedStream<MaybeTlsStream<TcpStream>>>>,
+    connection: crate::yamux::ControlledConnection<MeteredStream<NegotiatedStream>>,
 
     /// Yamux control.
     control: crate::yamux::Control,
@@ -191,15 +199,24 @@ impl WebSocketConnection {
     }
 
     /// Negotiate protocol.
+    ///
+    /// `version` only affects the dialer and is downgraded to [`Version::V1`] if more than one
+    /// protocol is proposed.
     async fn negotiate_protocol<S: AsyncRead + AsyncWrite + Unpin>(
         stream: S,
         role: &Role,
         protocols: Vec<&str>,
+        version: Version,
     ) -> crate::Result<(Negotiated<S>, ProtocolName)> {
-        tracing::trace!(target: LOG_TARGET, ?protocols, "negotiating protocols");
+        tracing::trace!(target: LOG_TARGET, ?protocols, ?version, "negotiating protocols");
+
+        let version = match protocols.len() {
+            1 => version,
+            _ => Version::V1,
+        };
 
         let (protocol, socket) = match role {
-            Role::Dialer => dialer_select_proto(stream, protocols, Version::V1).await?,
+            Role::Dialer => dialer_select_proto(stream, protocols, version).await?,
             Role::Listener => listener_select_proto(stream, protocols).await?,
         };
 
@@ -212,6 +229,7 @@ impl WebSocketConnection {
     pub(super) async fn open_connection(
         connection_id: ConnectionId,
         keypair: Keypair,
+        bandwidth_sink: BandwidthSink,
         stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
         address: Multiaddr,
         dialed_peer: PeerId,
@@ -219,6 +237,7 @@ impl WebSocketConnection {
         yamux_config: crate::yamux::Config,
         max_read_ahead_factor: usize,
         max_write_buffer_size: usize,
+        multistream_select_version: Version,
     ) -> crate::Result<NegotiatedConnection> {
         tracing::trace!(
             target: LOG_TARGET,
@@ -235,9 +254,11 @@ impl WebSocketConnection {
             address,
             connection_id,
             keypair,
+            bandwidth_sink,
             yamux_config,
             max_read_ahead_factor,
             max_write_buffer_size,
+            multistream_select_version,
         )
         .await
     }
@@ -247,6 +268,7 @@ impl WebSocketConnection {
         stream: TcpStream,
         connection_id: ConnectionId,
         keypair: Keypair,
+        bandwidth_sink: BandwidthSink,
         address: Multiaddr,
         yamux_config: crate::yamux::Config,
         max_read_ahead_factor: usize,
@@ -261,9 +283,11 @@ impl WebSocketConnection {
             address,
             connection_id,
             keypair,
+            bandwidth_sink,
             yamux_config,
             max_read_ahead_factor,
             max_write_buffer_size,
+            Version::V1,
         )
         .await
     }
@@ -276,9 +300,11 @@ impl WebSocketConnection {
         address: Multiaddr,
         connection_id: ConnectionId,
         keypair: Keypair,
+        bandwidth_sink: BandwidthSink,
         yamux_config: crate::yamux::Config,
         max_read_ahead_factor: usize,
         max_write_buffer_size: usize,
+        multistream_select_version: Version,
     ) -> crate::Result<NegotiatedConnection> {
         tracing::trace!(
             target: LOG_TARGET,
@@ -291,7 +317,9 @@ impl WebSocketConnection {
         let stream = BufferedStream::new(stream);
 
         // negotiate `noise`
-        let (stream, _) = Self::negotiate_protocol(stream, &role, vec!["/noise"]).await?;
+        let (stream, _) =
+            Self::negotiate_protocol(stream, &role, vec!["/noise"], multistream_select_version)
+                .await?;
 
         tracing::trace!(
             target: LOG_TARGET,
@@ -299,30 +327,40 @@ impl WebSocketConnection {
         );
 
         // perform noise handshake
-        let (stream, peer) = noise::handshake(
-            stream.inner(),
+        let (stream, peer, _) = noise::handshake(
+            stream,
             &keypair,
             role,
+            dialed_peer,
+            &[],
             max_read_ahead_factor,
             max_write_buffer_size,
         )
         .await?;
 
-        if let Some(dialed_peer) = dialed_peer {
-            if peer != dialed_peer {
-                return Err(Error::PeerIdMismatch(dialed_peer, peer));
-            }
-        }
-
-        let stream: NoiseSocket<BufferedStream<_>> = stream;
+        let stream: NoiseSocket<Negotiated<BufferedStream<_>>> = stream;
 
         tracing::trace!(target: LOG_TARGET, "noise handshake done");
 
         // negotiate `yamux`
-        let (stream, _) = Self::negotiate_protocol(stream, &role, vec!["/yamux/1.0.0"]).await?;
+        let (mut stream, _) = Self::negotiate_protocol(
+            stream,
+            &role,
+            vec!["/yamux/1.0.0"],
+            multistream_select_version,
+        )
+        .await?;
         tracing::trace!(target: LOG_TARGET, "`yamux` negotiated");
 
-        let connection = crate::yamux::Connection::new(stream.inner(), yamux_config, role.into());
+        // with `V1Lazy` the proposal is buffered until the first write and an idle `yamux`
+        // connection doesn't write anything so flush it to let the listener finish negotiation
+        stream.flush().await.map_err(|error| Error::IoError(error.kind()))?;
+
+        let connection = crate::yamux::Connection::new(
+            bandwidth_sink.meter(peer, stream),
+            yamux_config,
+            role.into(),
+        );
         let (control, connection) = crate::yamux::Control::new(connection);
 
         let address = match role {
@@ -355,7 +393,8 @@ impl WebSocketConnection {
         );
 
         let protocols = protocols.iter().map(|protocol| &**protocol).collect::<Vec<&str>>();
-        let (io, protocol) = Self::negotiate_protocol(stream, &Role::Listener, protocols).await?;
+        let (io, protocol) =
+            Self::negotiate_protocol(stream, &Role::Listener, protocols, Version::V1).await?;
 
         tracing::trace!(
             target: LOG_TARGET,
@@ -404,7 +443,9 @@ impl WebSocketConnection {
             .chain(fallback_names.iter().map(|protocol| &**protocol))
             .collect();
 
-        let (io, protocol) = Self::negotiate_protocol(stream, &Role::Dialer, protocols).await?;
+        // `Negotiated::inner()` requires a completed negotiation
+        let (io, protocol) =
+            Self::negotiate_protocol(stream, &Role::Dialer, protocols, Version::V1).await?;
 
         Ok(NegotiatedSubstream {
             io: io.inner(),
@@ -489,9 +530,18 @@ impl WebSocketConnection {
                             };
 
                             if let (Some(protocol), Some(substream_id)) = (protocol, substream_id) {
-                                self.protocol_set
+                                if let Err(error) = self.protocol_set
                                     .report_substream_open_failure(protocol, substream_id, error)
-                                    .await?;
+                                    .await
+                                {
+                                    tracing::debug!(
+                                        target: LOG_TARGET,
+                                        peer = ?self.peer,
+                                        ?error,
+                                        "failed to report substream open failure to protocol, closing connection",
+                                    );
+                                    return self.protocol_set.report_connection_closed(self.peer, self.connection_id).await
+                                }
                             }
                         }
                         Ok(substream) => {
@@ -508,9 +558,18 @@ impl WebSocketConnection {
                                 self.protocol_set.protocol_codec(&protocol)
                             );
 
-                            self.protocol_set
+                            if let Err(error) = self.protocol_set
                                 .report_substream_open(self.peer, protocol, direction, substream)
-                                .await?;
+                                .await
+                            {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    peer = ?self.peer,
+                                    ?error,
+                                    "failed to register opened substream to protocol, closing connection",
+                                );
+                                return self.protocol_set.report_connection_closed(self.peer, self.connection_id).await
+                            }
                         }
                     }
                 }
@@ -25,7 +25,8 @@ use crate::{
     error::{AddressError, Error},
     transport::{
         common::listener::{DialAddresses, GetSocketAddr, SocketListener, WebSocketAddress},
-        manager::TransportHandle,
+        dns::DnsResolver,
+        manager::{SupportedTransport, TransportHandle},
         websocket::{
             config::Config,
             connection::{NegotiatedConnection, WebSocketConnection},
@@ -36,7 +37,11 @@ use crate::{
     PeerId,
 };
 
-use futures::{future::BoxFuture, stream::FuturesUnordered, Stream, StreamExt};
+use futures::{
+    future::{AbortHandle, Abortable, BoxFuture},
+    stream::FuturesUnordered,
+    Stream, StreamExt,
+};
 use multiaddr::{Multiaddr, Protocol};
 use socket2::{Domain, Socket, Type};
 use tokio::net::TcpStream;
@@ -47,6 +52,7 @@ use url::Url;
 use std::{
     collections::{HashMap, HashSet},
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::Duration,
 };
@@ -97,11 +103,16 @@ pub(crate) struct WebSocketTransport {
     /// Pending dials.
     pending_dials: HashMap<ConnectionId, Multiaddr>,
 
+    /// Abort handles of pending dials.
+    dial_aborts: HashMap<ConnectionId, AbortHandle>,
+
     /// Pending connections.
     pending_connections:
         FuturesUnordered<BoxFuture<'static, Result<NegotiatedConnection, WebSocketError>>>,
 
     /// Pending raw, unnegotiated connections.
+    ///
+    /// If none of the addresses could be dialed, the error of each address is returned.
     pending_raw_connections: FuturesUnordered<
         BoxFuture<
             'static,
@@ -111,7 +122,7 @@ pub(crate) struct WebSocketTransport {
                     Multiaddr,
                     WebSocketStream<MaybeTlsStream<TcpStream>>,
                 ),
-                ConnectionId,
+                (ConnectionId, Vec<(Multiaddr, Error)>),
             >,
         >,
     >,
@@ -178,16 +189,21 @@ impl WebSocketTransport {
         dial_addresses: DialAddresses,
         connection_open_timeout: Duration,
         nodelay: bool,
+        resolver: Arc<dyn DnsResolver>,
     ) -> crate::Result<(Multiaddr, WebSocketStream<MaybeTlsStream<TcpStream>>)> {
         let (url, _) = Self::multiaddr_into_url(address.clone())?;
 
         let (socket_address, _) = WebSocketAddress::multiaddr_to_socket_address(&address)?;
-        let remote_address =
-            match tokio::time::timeout(connection_open_timeout, socket_address.lookup_ip()).await {
-                Err(_) => return Err(Error::Timeout),
-                Ok(Err(error)) => return Err(error),
-                Ok(Ok(address)) => address,
-            };
+        let remote_address = match tokio::time::timeout(
+            connection_open_timeout,
+            socket_address.lookup_ip(resolver.as_ref()),
+        )
+        .await
+        {
+            Err(_) => return Err(Error::Timeout),
+            Ok(Err(error)) => return Err(error),
+            Ok(Ok(address)) => address,
+        };
 
         let domain = match remote_address.is_ipv4() {
             true => Domain::IPV4,
@@ -282,6 +298,7 @@ impl TransportBuilder for WebSocketTransport {
                 opened_raw: HashMap::new(),
                 pending_open: HashMap::new(),
                 pending_dials: HashMap::new(),
+                dial_aborts: HashMap::new(),
                 pending_connections: FuturesUnordered::new(),
                 pending_raw_connections: FuturesUnordered::new(),
             },
@@ -294,14 +311,19 @@ impl Transport for WebSocketTransport {
     fn dial(&mut self, connection_id: ConnectionId, address: Multiaddr) -> crate::Result<()> {
         let yamux_config = self.config.yamux_config.clone();
         let keypair = self.context.keypair.clone();
+        let bandwidth_sink = self.context.bandwidth_sink.clone();
         let (ws_address, peer) = Self::multiaddr_into_url(address.clone())?;
         let connection_open_timeout = self.config.connection_open_timeout;
         let max_read_ahead_factor = self.config.noise_read_ahead_frame_count;
         let max_write_buffer_size = self.config.noise_write_buffer_size;
+        let multistream_select_version = self.config.multistream_select_version;
         let dial_addresses = self.dial_addresses.clone();
         let nodelay = self.config.nodelay;
+        let resolver = self.context.resolver.clone();
 
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
         self.pending_dials.insert(connection_id, address.clone());
+        self.dial_aborts.insert(connection_id, abort_handle);
 
         tracing::debug!(target: LOG_TARGET, ?connection_id, ?address, "open connection");
 
@@ -311,6 +333,7 @@ impl Transport for WebSocketTransport {
                 dial_addresses,
                 connection_open_timeout,
                 nodelay,
+                resolver,
             )
             .await
             .map_err(|error| WebSocketError::new(error, Some(connection_id)))?;
@@ -318,6 +341,7 @@ impl Transport for WebSocketTransport {
             WebSocketConnection::open_connection(
                 connection_id,
                 keypair,
+                bandwidth_sink,
                 stream,
                 address,
                 peer,
@@ -325,16 +349,23 @@ impl Transport for WebSocketTransport {
                 yamux_config,
                 max_read_ahead_factor,
                 max_write_buffer_size,
+                multistream_select_version,
             )
             .await
             .map_err(|error| WebSocketError::new(error, Some(connection_id)))
         };
 
+        let future = Abortable::new(
+            tokio::time::timeout(connection_open_timeout, future),
+            abort_registration,
+        );
+
         self.pending_connections.push(Box::pin(async move {
-            match tokio::time::timeout(connection_open_timeout, future).await {
-                Err(_) => Err(WebSocketError::new(Error::Timeout, Some(connection_id))),
-                Ok(Err(error)) => Err(error),
-                Ok(Ok(result)) => Ok(result),
+            match future.await {
+                Err(_) => Err(WebSocketError::new(Error::DialAborted, Some(connection_id))),
+                Ok(Err(_)) => Err(WebSocketError::new(Error::Timeout, Some(connection_id))),
+                Ok(Ok(Err(error))) => Err(error),
+                Ok(Ok(Ok(result))) => Ok(result),
             }
         }));
 
@@ -396,33 +427,42 @@ impl Transport for WebSocketTransport {
                 let connection_open_timeout = self.config.connection_open_timeout;
                 let dial_addresses = self.dial_addresses.clone();
                 let nodelay = self.config.nodelay;
+                let resolver = self.context.resolver.clone();
 
                 async move {
                     WebSocketTransport::dial_peer(
-                        address,
+                        address.clone(),
                         dial_addresses,
                         connection_open_timeout,
                         nodelay,
+                        resolver,
                     )
                     .await
+                    .map_err(|error| (address, error.into_dial_error()))
                 }
             })
             .collect();
 
         self.pending_raw_connections.push(Box::pin(async move {
+            let mut errors = Vec::new();
+
             while let Some(result) = futures.next().await {
                 match result {
                     Ok((address, stream)) => return Ok((connection_id, address, stream)),
-                    Err(error) => tracing::debug!(
-                        target: LOG_TARGET,
-                        ?connection_id,
-                        ?error,
-                        "failed to open connection",
-                    ),
+                    Err((address, error)) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?connection_id,
+                            ?address,
+                            ?error,
+                            "failed to open connection",
+                        );
+                        errors.push((address, error));
+                    }
                 }
             }
 
-            Err(connection_id)
+            Err((connection_id, errors))
         }));
 
         Ok(())
@@ -441,8 +481,10 @@ impl Transport for WebSocketTransport {
         let yamux_config = self.config.yamux_config.clone();
         let max_read_ahead_factor = self.config.noise_read_ahead_frame_count;
         let max_write_buffer_size = self.config.noise_write_buffer_size;
+        let multistream_select_version = self.config.multistream_select_version;
         let connection_open_timeout = self.config.connection_open_timeout;
         let keypair = self.context.keypair.clone();
+        let bandwidth_sink = self.context.bandwidth_sink.clone();
 
         tracing::trace!(
             target: LOG_TARGET,
@@ -462,9 +504,11 @@ impl Transport for WebSocketTransport {
                     address,
                     connection_id,
                     keypair,
+                    bandwidth_sink,
                     yamux_config,
                     max_read_ahead_factor,
                     max_write_buffer_size,
+                    multistream_select_version,
                 )
                 .await
                 .map_err(|error| WebSocketError::new(error, Some(connection_id)))
@@ -481,7 +525,12 @@ impl Transport for WebSocketTransport {
     }
 
     fn cancel(&mut self, connection_id: ConnectionId) {
-        self.canceled.insert(connection_id);
+        match self.dial_aborts.remove(&connection_id) {
+            Some(abort_handle) => abort_handle.abort(),
+            None => {
+                self.canceled.insert(connection_id);
+            }
+        }
     }
 }
 
@@ -493,8 +542,20 @@ impl Stream for WebSocketTransport {
             match connection {
                 Err(_) => return Poll::Ready(None),
                 Ok((stream, address)) => {
+                    if let Some(filter) = &self.config.connection_filter {
+                        if !filter.accept(&address) {
+                            tracing::debug!(
+                                target: LOG_TARGET,
+                                ?address,
+                                "inbound connection rejected by filter",
+                            );
+                            continue;
+                        }
+                    }
+
                     let connection_id = self.context.next_connection_id();
                     let keypair = self.context.keypair.clone();
+                    let bandwidth_sink = self.context.bandwidth_sink.clone();
                     let yamux_config = self.config.yamux_config.clone();
                     let connection_open_timeout = self.config.connection_open_timeout;
                     let max_read_ahead_factor = self.config.noise_read_ahead_frame_count;
@@ -510,6 +571,7 @@ impl Stream for WebSocketTransport {
                                 stream,
                                 connection_id,
                                 keypair,
+                                bandwidth_sink,
                                 address,
                                 yamux_config,
                                 max_read_ahead_factor,
@@ -549,14 +611,25 @@ impl Stream for WebSocketTransport {
                         }));
                     }
                 }
-                Err(connection_id) =>
+                Err((connection_id, errors)) =>
                     if !self.canceled.remove(&connection_id) {
-                        return Poll::Ready(Some(TransportEvent::OpenFailure { connection_id }));
+                        return Poll::Ready(Some(TransportEvent::OpenFailure {
+                            connection_id,
+                            errors,
+                        }));
                     },
             }
         }
 
         while let Poll::Ready(Some(connection)) = self.pending_connections.poll_next_unpin(cx) {
+            let connection_id = match &connection {
+                Ok(connection) => Some(connection.connection_id()),
+                Err(error) => error.connection_id,
+            };
+            if let Some(connection_id) = connection_id {
+                self.dial_aborts.remove(&connection_id);
+            }
+
             match connection {
                 Ok(connection) => {
                     let peer = connection.peer();
@@ -566,6 +639,7 @@ impl Stream for WebSocketTransport {
                     return Poll::Ready(Some(TransportEvent::ConnectionEstablished {
                         peer,
                         endpoint,
+                        transport: SupportedTransport::WebSocket,
                     }));
                 }
                 Err(error) => match error.connection_id {
@@ -574,7 +648,7 @@ impl Stream for WebSocketTransport {
                             return Poll::Ready(Some(TransportEvent::DialFailure {
                                 connection_id,
                                 address,
-                                error: error.error,
+                                error: error.error.into_dial_error(),
                             })),
                         None => {
                             tracing::debug!(target: LOG_TARGET, ?error, "failed to establish connection")
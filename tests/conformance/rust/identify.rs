@@ -32,7 +32,7 @@ use litep2p::{
     crypto::ed25519::Keypair,
     protocol::libp2p::{
         identify::{Config as IdentifyConfig, IdentifyEvent},
-        ping::{Config as PingConfig, PingEvent},
+        ping::{Config as PingConfig, PingHandle},
     },
     transport::tcp::config::Config as TcpConfig,
     Litep2p,
@@ -66,7 +66,7 @@ impl From<ping::Event> for MyBehaviourEvent {
 // initialize litep2p with ping support
 fn initialize_litep2p() -> (
     Litep2p,
-    Box<dyn Stream<Item = PingEvent> + Send + Unpin>,
+    PingHandle,
     Box<dyn Stream<Item = IdentifyEvent> + Send + Unpin>,
 ) {
     let keypair = Keypair::generate();
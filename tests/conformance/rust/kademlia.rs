@@ -403,6 +403,7 @@ async fn get_record() {
         match kad_handle.next().await.unwrap() {
             KademliaEvent::GetRecordSuccess { .. } => break,
             KademliaEvent::RoutingTableUpdate { .. } => {}
+            KademliaEvent::KBucketUpdate { .. } => {}
             _ => panic!("invalid event received"),
         }
     }
@@ -19,7 +19,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use futures::{Stream, StreamExt};
+use futures::StreamExt;
 use libp2p::{
     identity, ping,
     swarm::{keep_alive, NetworkBehaviour, SwarmBuilder, SwarmEvent},
@@ -28,7 +28,7 @@ use libp2p::{
 use litep2p::{
     config::ConfigBuilder,
     crypto::ed25519::Keypair,
-    protocol::libp2p::ping::{Config as PingConfig, PingEvent},
+    protocol::libp2p::ping::{Config as PingConfig, PingHandle},
     transport::tcp::config::Config as TcpConfig,
     Litep2p,
 };
@@ -40,7 +40,7 @@ struct Behaviour {
 }
 
 // initialize litep2p with ping support
-fn initialize_litep2p() -> (Litep2p, Box<dyn Stream<Item = PingEvent> + Send + Unpin>) {
+fn initialize_litep2p() -> (Litep2p, PingHandle) {
     let keypair = Keypair::generate();
     let (ping_config, ping_event_stream) = PingConfig::default();
     let litep2p = Litep2p::new(
@@ -19,7 +19,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use futures::{future::Either, Stream, StreamExt};
+use futures::{future::Either, StreamExt};
 use libp2p::{
     core::{muxing::StreamMuxerBox, transport::OrTransport},
     identity, ping, quic,
@@ -29,7 +29,7 @@ use libp2p::{
 use litep2p::{
     config::ConfigBuilder,
     crypto::ed25519::Keypair,
-    protocol::libp2p::ping::{Config as PingConfig, PingEvent},
+    protocol::libp2p::ping::{Config as PingConfig, PingHandle},
     transport::quic::config::Config as QuicConfig,
     Litep2p,
 };
@@ -41,7 +41,7 @@ struct Behaviour {
 }
 
 // initialize litep2p with ping support
-fn initialize_litep2p() -> (Litep2p, Box<dyn Stream<Item = PingEvent> + Send + Unpin>) {
+fn initialize_litep2p() -> (Litep2p, PingHandle) {
     let keypair = Keypair::generate();
     let (ping_config, ping_event_stream) = PingConfig::default();
     let litep2p = Litep2p::new(
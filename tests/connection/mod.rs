@@ -21,23 +21,36 @@
 use litep2p::{
     config::ConfigBuilder,
     crypto::ed25519::Keypair,
-    error::{AddressError, Error},
-    protocol::libp2p::ping::{Config as PingConfig, PingEvent},
+    error::{AddressError, DialError, Error},
+    executor::{Executor, TaskHandle},
+    protocol::libp2p::ping::{Config as PingConfig, PingHandle},
     transport::{
         quic::config::Config as QuicConfig, tcp::config::Config as TcpConfig,
-        websocket::config::Config as WebSocketConfig,
+        websocket::config::Config as WebSocketConfig, SupportedTransport,
     },
     Litep2p, Litep2pEvent, PeerId,
 };
 
-use futures::{Stream, StreamExt};
+use futures::StreamExt;
 use multiaddr::{Multiaddr, Protocol};
 use multihash::Multihash;
 use network_interface::{NetworkInterface, NetworkInterfaceConfig};
 use tokio::net::{TcpListener, UdpSocket};
 
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
 #[cfg(test)]
 mod protocol_dial_invalid_address;
+#[cfg(test)]
+mod relay;
 
 enum Transport {
     Tcp(TcpConfig),
@@ -89,6 +102,12 @@ async fn two_litep2ps_work(transport1: Transport, transport2: Transport) {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .try_init();
 
+    let expected_transport = match transport1 {
+        Transport::Tcp(_) => SupportedTransport::Tcp,
+        Transport::Quic(_) => SupportedTransport::Quic,
+        Transport::WebSocket(_) => SupportedTransport::WebSocket,
+    };
+
     let (ping_config1, _ping_event_stream1) = PingConfig::default();
     let config1 = ConfigBuilder::new()
         .with_keypair(Keypair::generate())
@@ -123,11 +142,98 @@ async fn two_litep2ps_work(transport1: Transport, transport2: Transport) {
 
     assert!(std::matches!(
         res1,
-        Some(Litep2pEvent::ConnectionEstablished { .. })
+        Some(Litep2pEvent::ConnectionEstablished { transport, .. }) if transport == expected_transport
     ));
     assert!(std::matches!(
         res2,
-        Some(Litep2pEvent::ConnectionEstablished { .. })
+        Some(Litep2pEvent::ConnectionEstablished { transport, .. }) if transport == expected_transport
+    ));
+}
+
+#[tokio::test]
+async fn tcp_and_websocket_enabled_on_same_node() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let (ping_config1, _ping_event_stream1) = PingConfig::default();
+    let config1 = ConfigBuilder::new()
+        .with_keypair(Keypair::generate())
+        .with_libp2p_ping(ping_config1)
+        .with_tcp(TcpConfig {
+            listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            ..Default::default()
+        })
+        .with_websocket(WebSocketConfig {
+            listen_addresses: vec!["/ip4/127.0.0.1/tcp/0/ws".parse().unwrap()],
+            ..Default::default()
+        })
+        .build();
+
+    let (ping_config2, _ping_event_stream2) = PingConfig::default();
+    let config2 = ConfigBuilder::new()
+        .with_keypair(Keypair::generate())
+        .with_libp2p_ping(ping_config2)
+        .with_tcp(TcpConfig {
+            listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            ..Default::default()
+        })
+        .build();
+
+    let (ping_config3, _ping_event_stream3) = PingConfig::default();
+    let config3 = ConfigBuilder::new()
+        .with_keypair(Keypair::generate())
+        .with_libp2p_ping(ping_config3)
+        .with_websocket(WebSocketConfig {
+            listen_addresses: vec!["/ip4/127.0.0.1/tcp/0/ws".parse().unwrap()],
+            ..Default::default()
+        })
+        .build();
+
+    let mut litep2p1 = Litep2p::new(config1).unwrap();
+    let mut litep2p2 = Litep2p::new(config2).unwrap();
+    let mut litep2p3 = Litep2p::new(config3).unwrap();
+
+    let (ws_addresses, tcp_addresses): (Vec<_>, Vec<_>) =
+        litep2p1.listen_addresses().cloned().partition(|address| {
+            address.iter().any(|protocol| std::matches!(protocol, Protocol::Ws(_)))
+        });
+
+    litep2p2.dial_address(tcp_addresses[0].clone()).await.unwrap();
+    litep2p3.dial_address(ws_addresses[0].clone()).await.unwrap();
+
+    let node1 = tokio::spawn(async move {
+        let mut transports = Vec::new();
+
+        while transports.len() < 2 {
+            if let Some(Litep2pEvent::ConnectionEstablished { transport, .. }) =
+                litep2p1.next_event().await
+            {
+                transports.push(transport);
+            }
+        }
+
+        (litep2p1, transports)
+    });
+
+    let (res1, res2, res3) = tokio::join!(node1, litep2p2.next_event(), litep2p3.next_event());
+    let (_litep2p1, transports) = res1.unwrap();
+
+    assert!(transports.contains(&SupportedTransport::Tcp));
+    assert!(transports.contains(&SupportedTransport::WebSocket));
+    assert!(std::matches!(
+        res2,
+        Some(Litep2pEvent::ConnectionEstablished {
+            transport: SupportedTransport::Tcp,
+            ..
+        })
+    ));
+    assert!(std::matches!(
+        res3,
+        Some(Litep2pEvent::ConnectionEstablished {
+            transport: SupportedTransport::WebSocket,
+            ..
+        })
     ));
 }
 
@@ -219,9 +325,7 @@ async fn dial_failure(transport1: Transport, transport2: Transport, dial_address
     let mut litep2p1 = Litep2p::new(config1).unwrap();
     let mut litep2p2 = Litep2p::new(config2).unwrap();
 
-    let address = dial_address.with(Protocol::P2p(
-        Multihash::from_bytes(&litep2p2.local_peer_id().to_bytes()).unwrap(),
-    ));
+    let address = dial_address.with(Protocol::P2p(litep2p2.local_peer_id().to_multihash()));
 
     litep2p1.dial_address(address).await.unwrap();
 
@@ -277,9 +381,7 @@ async fn connect_over_dns() {
     let mut new_address = Multiaddr::empty();
     new_address.push(Protocol::Dns("localhost".into()));
     new_address.push(tcp);
-    new_address.push(Protocol::P2p(
-        Multihash::from_bytes(&peer2.to_bytes()).unwrap(),
-    ));
+    new_address.push(Protocol::P2p(peer2.to_multihash()));
 
     litep2p1.dial_address(new_address).await.unwrap();
     let (res1, res2) = tokio::join!(litep2p1.next_event(), litep2p2.next_event());
@@ -302,9 +404,7 @@ async fn connection_timeout_tcp() {
     let address = Multiaddr::empty()
         .with(Protocol::from(address.ip()))
         .with(Protocol::Tcp(address.port()))
-        .with(Protocol::P2p(
-            Multihash::from_bytes(&PeerId::random().to_bytes()).unwrap(),
-        ));
+        .with(Protocol::P2p(PeerId::random().to_multihash()));
 
     connection_timeout(
         Transport::Tcp(TcpConfig {
@@ -325,9 +425,7 @@ async fn connection_timeout_quic() {
         .with(Protocol::from(address.ip()))
         .with(Protocol::Udp(address.port()))
         .with(Protocol::QuicV1)
-        .with(Protocol::P2p(
-            Multihash::from_bytes(&PeerId::random().to_bytes()).unwrap(),
-        ));
+        .with(Protocol::P2p(PeerId::random().to_multihash()));
 
     connection_timeout(Transport::Quic(Default::default()), address).await;
 }
@@ -341,9 +439,7 @@ async fn connection_timeout_websocket() {
         .with(Protocol::from(address.ip()))
         .with(Protocol::Tcp(address.port()))
         .with(Protocol::Ws(std::borrow::Cow::Owned("/".to_string())))
-        .with(Protocol::P2p(
-            Multihash::from_bytes(&PeerId::random().to_bytes()).unwrap(),
-        ));
+        .with(Protocol::P2p(PeerId::random().to_multihash()));
 
     connection_timeout(
         Transport::WebSocket(WebSocketConfig {
@@ -386,7 +482,7 @@ async fn connection_timeout(transport: Transport, address: Multiaddr) {
 
     assert_eq!(dial_address, address);
     println!("{error:?}");
-    assert!(std::matches!(error, Error::Timeout));
+    assert!(std::matches!(error, Error::Dial(DialError::Timeout)));
 }
 
 #[tokio::test]
@@ -485,9 +581,7 @@ async fn attempt_to_dial_using_unsupported_transport() {
     let address = Multiaddr::empty()
         .with(Protocol::from(std::net::Ipv4Addr::new(127, 0, 0, 1)))
         .with(Protocol::Tcp(8888))
-        .with(Protocol::P2p(
-            Multihash::from_bytes(&PeerId::random().to_bytes()).unwrap(),
-        ));
+        .with(Protocol::P2p(PeerId::random().to_multihash()));
 
     assert!(std::matches!(
         litep2p.dial_address(address.clone()).await,
@@ -495,6 +589,65 @@ async fn attempt_to_dial_using_unsupported_transport() {
     ));
 }
 
+#[tokio::test]
+async fn cancel_dial() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let (ping_config, _ping_event_stream) = PingConfig::default();
+    let config = ConfigBuilder::new()
+        .with_keypair(Keypair::generate())
+        .with_tcp(TcpConfig {
+            listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            ..Default::default()
+        })
+        .with_libp2p_ping(ping_config)
+        .build();
+    let mut litep2p = Litep2p::new(config).unwrap();
+
+    // listener which never responds to the connection negotiation
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = Multiaddr::empty()
+        .with(Protocol::from(std::net::Ipv4Addr::new(127, 0, 0, 1)))
+        .with(Protocol::Tcp(listener.local_addr().unwrap().port()))
+        .with(Protocol::P2p(PeerId::random().to_multihash()));
+
+    let connection_id = litep2p.dial_address(address.clone()).await.unwrap();
+    litep2p.cancel_dial(connection_id).unwrap();
+
+    match tokio::time::timeout(Duration::from_secs(5), litep2p.next_event()).await {
+        Ok(Some(Litep2pEvent::DialFailure {
+            address: failed_address,
+            error: Error::DialAborted,
+        })) => assert_eq!(failed_address, address),
+        event => panic!("invalid event: {event:?}"),
+    }
+}
+
+#[tokio::test]
+async fn invalid_quic_listen_address_rejected() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    for address in ["/ip4/127.0.0.1/tcp/0", "/ip4/127.0.0.1/udp/0"] {
+        let config = ConfigBuilder::new()
+            .with_keypair(Keypair::generate())
+            .with_quic(QuicConfig {
+                listen_addresses: vec![address.parse().unwrap()],
+                ..Default::default()
+            })
+            .build();
+
+        match Litep2p::new(config) {
+            Err(Error::AddressError(AddressError::InvalidProtocol)) => {}
+            Err(error) => panic!("invalid error: {error:?}"),
+            Ok(_) => panic!("litep2p created with invalid listen address {address}"),
+        }
+    }
+}
+
 #[tokio::test]
 async fn keep_alive_timeout_tcp() {
     keep_alive_timeout(
@@ -631,7 +784,7 @@ async fn simultaneous_dial_tcp() {
         litep2p1.dial_address(address2),
         litep2p2.dial_address(address1)
     );
-    assert!(std::matches!((res1, res2), (Ok(()), Ok(()))));
+    assert!(std::matches!((res1, res2), (Ok(_), Ok(_))));
 
     let mut ping_received1 = false;
     let mut ping_received2 = false;
@@ -683,7 +836,7 @@ async fn simultaneous_dial_quic() {
         litep2p1.dial_address(address2),
         litep2p2.dial_address(address1)
     );
-    assert!(std::matches!((res1, res2), (Ok(()), Ok(()))));
+    assert!(std::matches!((res1, res2), (Ok(_), Ok(_))));
 
     let mut ping_received1 = false;
     let mut ping_received2 = false;
@@ -735,7 +888,7 @@ async fn simultaneous_dial_ipv6_quic() {
         litep2p1.dial_address(address2),
         litep2p2.dial_address(address1)
     );
-    assert!(std::matches!((res1, res2), (Ok(()), Ok(()))));
+    assert!(std::matches!((res1, res2), (Ok(_), Ok(_))));
 
     let mut ping_received1 = false;
     let mut ping_received2 = false;
@@ -845,9 +998,7 @@ async fn tcp_dns_resolution() {
     let mut new_address = Multiaddr::empty();
     new_address.push(Protocol::Dns("localhost".into()));
     new_address.push(tcp);
-    new_address.push(Protocol::P2p(
-        Multihash::from_bytes(&peer2.to_bytes()).unwrap(),
-    ));
+    new_address.push(Protocol::P2p(peer2.to_multihash()));
     litep2p1.dial_address(new_address).await.unwrap();
 
     let mut ping_received1 = false;
@@ -907,9 +1058,7 @@ async fn websocket_dns_resolution() {
     new_address.push(Protocol::Dns("localhost".into()));
     new_address.push(tcp);
     new_address.push(Protocol::Ws(std::borrow::Cow::Owned("/".to_string())));
-    new_address.push(Protocol::P2p(
-        Multihash::from_bytes(&peer2.to_bytes()).unwrap(),
-    ));
+    new_address.push(Protocol::P2p(peer2.to_multihash()));
     litep2p1.dial_address(new_address).await.unwrap();
 
     let mut ping_received1 = false;
@@ -999,9 +1148,7 @@ async fn multiple_listen_addresses_websocket() {
     .await;
 }
 
-async fn make_dummy_litep2p(
-    transport: Transport,
-) -> (Litep2p, Box<dyn Stream<Item = PingEvent> + Send + Unpin>) {
+async fn make_dummy_litep2p(transport: Transport) -> (Litep2p, PingHandle) {
     let (ping_config, ping_event_stream) = PingConfig::default();
     let litep2p_config = ConfigBuilder::new()
         .with_keypair(Keypair::generate())
@@ -1500,3 +1647,136 @@ async fn simultaneous_dial_then_redial(transport1: Transport, transport2: Transp
         _ => {}
     }
 }
+
+#[tokio::test]
+async fn shutdown_tcp() {
+    shutdown(
+        Transport::Tcp(TcpConfig {
+            listen_addresses: vec!["/ip6/::1/tcp/0".parse().unwrap()],
+            ..Default::default()
+        }),
+        Transport::Tcp(TcpConfig {
+            listen_addresses: vec!["/ip6/::1/tcp/0".parse().unwrap()],
+            ..Default::default()
+        }),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn shutdown_websocket() {
+    shutdown(
+        Transport::WebSocket(WebSocketConfig {
+            listen_addresses: vec!["/ip4/127.0.0.1/tcp/0/ws".parse().unwrap()],
+            ..Default::default()
+        }),
+        Transport::WebSocket(WebSocketConfig {
+            listen_addresses: vec!["/ip4/127.0.0.1/tcp/0/ws".parse().unwrap()],
+            ..Default::default()
+        }),
+    )
+    .await
+}
+
+/// Executor which keeps track of how many of the tasks it has started are still running.
+struct TrackingExecutor {
+    running: Arc<AtomicUsize>,
+}
+
+/// Decrements the number of running tasks when the task completes or is aborted.
+struct RunningGuard(Arc<AtomicUsize>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Executor for TrackingExecutor {
+    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) -> TaskHandle {
+        let running = Arc::clone(&self.running);
+        running.fetch_add(1, Ordering::SeqCst);
+
+        tokio::spawn(async move {
+            let _guard = RunningGuard(running);
+            future.await
+        })
+        .into()
+    }
+
+    fn run_with_name(
+        &self,
+        _: &'static str,
+        future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ) -> TaskHandle {
+        self.run(future)
+    }
+}
+
+async fn shutdown(transport1: Transport, transport2: Transport) {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let running = Arc::new(AtomicUsize::new(0usize));
+    let (ping_config1, _ping_event_stream1) = PingConfig::default();
+    let config1 = ConfigBuilder::new()
+        .with_keypair(Keypair::generate())
+        .with_executor(Arc::new(TrackingExecutor {
+            running: Arc::clone(&running),
+        }))
+        .with_libp2p_ping(ping_config1);
+
+    let config1 = match transport1 {
+        Transport::Tcp(config) => config1.with_tcp(config),
+        Transport::Quic(config) => config1.with_quic(config),
+        Transport::WebSocket(config) => config1.with_websocket(config),
+    }
+    .build();
+
+    let mut litep2p1 = Litep2p::new(config1).unwrap();
+    let (mut litep2p2, _ping_event_stream2) = make_dummy_litep2p(transport2).await;
+
+    let address1 = litep2p1.listen_addresses().next().unwrap().clone();
+    litep2p2.dial_address(address1.clone()).await.unwrap();
+
+    let (res1, res2) = tokio::join!(litep2p1.next_event(), litep2p2.next_event());
+    assert!(std::matches!(
+        res1,
+        Some(Litep2pEvent::ConnectionEstablished { .. })
+    ));
+    assert!(std::matches!(
+        res2,
+        Some(Litep2pEvent::ConnectionEstablished { .. })
+    ));
+    assert!(running.load(Ordering::SeqCst) > 0);
+
+    let (_, closed) = tokio::join!(
+        litep2p1.shutdown(),
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                if let Some(Litep2pEvent::ConnectionClosed { .. }) = litep2p2.next_event().await {
+                    break;
+                }
+            }
+        })
+    );
+    assert!(closed.is_ok(), "connection was not closed");
+
+    // the connection tasks may still be unwinding after reporting the closed connection
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while running.load(Ordering::SeqCst) != 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("all tasks to exit");
+
+    // the listener has been closed
+    litep2p2.dial_address(address1).await.unwrap();
+
+    match tokio::time::timeout(std::time::Duration::from_secs(10), litep2p2.next_event()).await {
+        Ok(Some(Litep2pEvent::DialFailure { .. })) => {}
+        event => panic!("invalid event: {event:?}"),
+    }
+}
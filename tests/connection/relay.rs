@@ -0,0 +1,391 @@
+// Copyright 2024 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use litep2p::{
+    codec::ProtocolCodec,
+    config::ConfigBuilder,
+    protocol::{
+        libp2p::ping::{ConfigBuilder as PingConfigBuilder, PingEvent},
+        Direction, KeepAlive, TransportEvent, TransportService, UserProtocol,
+    },
+    substream::Substream,
+    transport::{
+        memory::config::Config as MemoryConfig, relay::config::Config as RelayConfig,
+        SupportedTransport,
+    },
+    types::protocol::ProtocolName,
+    Litep2p, Litep2pEvent, PeerId,
+};
+
+use futures::StreamExt;
+use multiaddr::Protocol;
+use prost::Message;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::{mpsc, oneshot},
+};
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const HOP_PROTOCOL: &str = "/libp2p/circuit/relay/0.2.0/hop";
+const STOP_PROTOCOL: &str = "/libp2p/circuit/relay/0.2.0/stop";
+
+// message types and status codes of the circuit relay v2 protocol
+const HOP_RESERVE: i32 = 0;
+const HOP_CONNECT: i32 = 1;
+const HOP_STATUS: i32 = 2;
+const STOP_CONNECT: i32 = 0;
+const STATUS_OK: i32 = 100;
+const STATUS_NO_RESERVATION: i32 = 204;
+
+#[derive(Clone, PartialEq, Message)]
+struct Peer {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    id: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Reservation {
+    #[prost(uint64, optional, tag = "1")]
+    expire: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct HopMessage {
+    #[prost(int32, optional, tag = "1")]
+    r#type: Option<i32>,
+    #[prost(message, optional, tag = "2")]
+    peer: Option<Peer>,
+    #[prost(message, optional, tag = "3")]
+    reservation: Option<Reservation>,
+    #[prost(int32, optional, tag = "5")]
+    status: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StopMessage {
+    #[prost(int32, optional, tag = "1")]
+    r#type: Option<i32>,
+    #[prost(message, optional, tag = "2")]
+    peer: Option<Peer>,
+    #[prost(int32, optional, tag = "4")]
+    status: Option<i32>,
+}
+
+async fn read_message<M: Message + Default>(substream: &mut Substream) -> M {
+    let mut size = 0usize;
+
+    for shift in (0..).step_by(7) {
+        let byte = substream.read_u8().await.unwrap();
+        size |= ((byte & 0x7f) as usize) << shift;
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    let mut message = vec![0u8; size];
+    substream.read_exact(&mut message).await.unwrap();
+
+    M::decode(message.as_slice()).unwrap()
+}
+
+async fn write_message(substream: &mut Substream, message: impl Message) {
+    substream.write_all(&message.encode_length_delimited_to_vec()).await.unwrap();
+    substream.flush().await.unwrap();
+}
+
+/// Request to connect the circuit of `source` to `destination`.
+type CircuitRequest = (PeerId, PeerId, oneshot::Sender<Substream>);
+
+/// Relay side of the `hop` protocol.
+///
+/// Every reservation is accepted and circuits are relayed without any limits.
+struct Hop {
+    reserved_tx: mpsc::Sender<PeerId>,
+    circuit_tx: mpsc::Sender<CircuitRequest>,
+}
+
+#[async_trait::async_trait]
+impl UserProtocol for Hop {
+    fn protocol(&self) -> ProtocolName {
+        ProtocolName::from(HOP_PROTOCOL)
+    }
+
+    fn codec(&self) -> ProtocolCodec {
+        ProtocolCodec::Unspecified
+    }
+
+    async fn run(self: Box<Self>, mut service: TransportService) -> litep2p::Result<()> {
+        while let Some(event) = service.next().await {
+            let TransportEvent::SubstreamOpened {
+                peer,
+                direction: Direction::Inbound,
+                mut substream,
+                ..
+            } = event
+            else {
+                continue;
+            };
+
+            let request: HopMessage = read_message(&mut substream).await;
+
+            match request.r#type {
+                Some(HOP_RESERVE) => {
+                    let expire = SystemTime::now() + Duration::from_secs(3600);
+                    let response = HopMessage {
+                        r#type: Some(HOP_STATUS),
+                        reservation: Some(Reservation {
+                            expire: Some(expire.duration_since(UNIX_EPOCH).unwrap().as_secs()),
+                        }),
+                        status: Some(STATUS_OK),
+                        ..Default::default()
+                    };
+                    write_message(&mut substream, response).await;
+
+                    service.set_keep_alive(peer, KeepAlive::Yes).unwrap();
+                    self.reserved_tx.send(peer).await.unwrap();
+                }
+                Some(HOP_CONNECT) => {
+                    let destination =
+                        PeerId::from_bytes(&request.peer.unwrap().id.unwrap()).unwrap();
+                    let (tx, rx) = oneshot::channel();
+                    self.circuit_tx.send((peer, destination, tx)).await.unwrap();
+
+                    tokio::spawn(async move {
+                        let Ok(mut destination) = rx.await else {
+                            let response = HopMessage {
+                                r#type: Some(HOP_STATUS),
+                                status: Some(STATUS_NO_RESERVATION),
+                                ..Default::default()
+                            };
+                            return write_message(&mut substream, response).await;
+                        };
+
+                        let response = HopMessage {
+                            r#type: Some(HOP_STATUS),
+                            status: Some(STATUS_OK),
+                            ..Default::default()
+                        };
+                        write_message(&mut substream, response).await;
+
+                        let _ =
+                            tokio::io::copy_bidirectional(&mut substream, &mut destination).await;
+                    });
+                }
+                message_type => panic!("unexpected `hop` message: {message_type:?}"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Relay side of the `stop` protocol.
+struct Stop {
+    circuit_rx: mpsc::Receiver<CircuitRequest>,
+}
+
+#[async_trait::async_trait]
+impl UserProtocol for Stop {
+    fn protocol(&self) -> ProtocolName {
+        ProtocolName::from(STOP_PROTOCOL)
+    }
+
+    fn codec(&self) -> ProtocolCodec {
+        ProtocolCodec::Unspecified
+    }
+
+    async fn run(mut self: Box<Self>, mut service: TransportService) -> litep2p::Result<()> {
+        let mut pending: HashMap<_, (PeerId, oneshot::Sender<Substream>)> = HashMap::new();
+
+        loop {
+            tokio::select! {
+                event = service.next() => match event {
+                    Some(TransportEvent::SubstreamOpened {
+                        direction: Direction::Outbound(substream_id),
+                        mut substream,
+                        ..
+                    }) => {
+                        let (source, tx) = pending.remove(&substream_id).unwrap();
+
+                        tokio::spawn(async move {
+                            let request = StopMessage {
+                                r#type: Some(STOP_CONNECT),
+                                peer: Some(Peer { id: Some(source.to_bytes()) }),
+                                ..Default::default()
+                            };
+                            write_message(&mut substream, request).await;
+
+                            let response: StopMessage = read_message(&mut substream).await;
+                            assert_eq!(response.status, Some(STATUS_OK));
+
+                            let _ = tx.send(substream);
+                        });
+                    }
+                    Some(TransportEvent::SubstreamOpenFailure { substream, .. }) => {
+                        pending.remove(&substream);
+                    }
+                    Some(_) => {}
+                    None => return Ok(()),
+                },
+                request = self.circuit_rx.recv() => match request {
+                    Some((source, destination, tx)) => {
+                        if let Ok(substream_id) = service.open_substream(destination) {
+                            pending.insert(substream_id, (source, tx));
+                        }
+                    }
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn relayed_connection_established() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let (reserved_tx, mut reserved_rx) = mpsc::channel(8);
+    let (circuit_tx, circuit_rx) = mpsc::channel(8);
+
+    let mut relay = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_user_protocol(Box::new(Hop {
+                reserved_tx,
+                circuit_tx,
+            }))
+            .with_user_protocol(Box::new(Stop { circuit_rx }))
+            .build(),
+    )
+    .unwrap();
+    let relay_address = relay.listen_addresses().next().unwrap().clone();
+
+    tokio::spawn(async move { while relay.next_event().await.is_some() {} });
+
+    // `litep2p2` reserves a slot with the relay, making it reachable through the relay
+    let (ping_config2, mut ping_event_stream2) = PingConfigBuilder::new().build();
+    let mut litep2p2 = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_relay(RelayConfig {
+                relays: vec![relay_address],
+                ..Default::default()
+            })
+            .with_libp2p_ping(ping_config2)
+            .build(),
+    )
+    .unwrap();
+    let peer2 = *litep2p2.local_peer_id();
+    let circuit_address = litep2p2
+        .listen_addresses()
+        .find(|address| {
+            address.iter().any(|protocol| std::matches!(protocol, Protocol::P2pCircuit))
+        })
+        .unwrap()
+        .clone();
+
+    loop {
+        tokio::select! {
+            _ = litep2p2.next_event() => {}
+            peer = reserved_rx.recv() => {
+                assert_eq!(peer, Some(peer2));
+                break;
+            }
+        }
+    }
+
+    let (ping_config1, mut ping_event_stream1) = PingConfigBuilder::new().build();
+    let mut litep2p1 = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_relay(RelayConfig::default())
+            .with_libp2p_ping(ping_config1)
+            .build(),
+    )
+    .unwrap();
+    let peer1 = *litep2p1.local_peer_id();
+
+    litep2p1.dial_address(circuit_address.clone()).await.unwrap();
+
+    let mut litep2p1_connected = false;
+    let mut litep2p2_connected = false;
+    let mut litep2p1_pinged = false;
+    let mut litep2p2_pinged = false;
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !(litep2p1_connected && litep2p2_connected && litep2p1_pinged && litep2p2_pinged) {
+            tokio::select! {
+                event = litep2p1.next_event() => match event.unwrap() {
+                    Litep2pEvent::ConnectionEstablished {
+                        peer,
+                        endpoint,
+                        transport,
+                    } if peer == peer2 => {
+                        assert_eq!(transport, SupportedTransport::Relay);
+                        assert_eq!(endpoint.address(), &circuit_address);
+                        litep2p1_connected = true;
+                    }
+                    Litep2pEvent::DialFailure { address, error } =>
+                        panic!("failed to dial {address}: {error:?}"),
+                    _ => {}
+                },
+                event = litep2p2.next_event() => match event.unwrap() {
+                    Litep2pEvent::ConnectionEstablished {
+                        peer,
+                        endpoint,
+                        transport,
+                    } if peer == peer1 => {
+                        assert_eq!(transport, SupportedTransport::Relay);
+                        assert!(endpoint.is_listener());
+                        assert!(endpoint
+                            .address()
+                            .iter()
+                            .any(|protocol| std::matches!(protocol, Protocol::P2pCircuit)));
+                        litep2p2_connected = true;
+                    }
+                    _ => {}
+                },
+                event = ping_event_stream1.next() => match event {
+                    Some(PingEvent::Ping { peer, .. }) => {
+                        assert_eq!(peer, peer2);
+                        litep2p1_pinged = true;
+                    }
+                    event => panic!("invalid ping event: {event:?}"),
+                },
+                event = ping_event_stream2.next() => match event {
+                    Some(PingEvent::Ping { peer, .. }) => {
+                        assert_eq!(peer, peer1);
+                        litep2p2_pinged = true;
+                    }
+                    event => panic!("invalid ping event: {event:?}"),
+                },
+            }
+        }
+    })
+    .await
+    .expect("relayed connection to be established");
+}
@@ -21,7 +21,7 @@
 use litep2p::{
     config::ConfigBuilder,
     crypto::ed25519::Keypair,
-    executor::Executor,
+    executor::{Executor, TaskHandle},
     protocol::{
         notification::{
             Config as NotificationConfig, Direction, NotificationEvent, ValidationResult,
@@ -74,12 +74,19 @@ struct TaskExecutorHandle {
 }
 
 impl Executor for TaskExecutorHandle {
-    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+    fn run(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) -> TaskHandle {
+        let (future, handle) = TaskHandle::new(future);
         let _ = self.tx.try_send(future);
+
+        handle
     }
 
-    fn run_with_name(&self, _: &'static str, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
-        let _ = self.tx.try_send(future);
+    fn run_with_name(
+        &self,
+        _: &'static str,
+        future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ) -> TaskHandle {
+        self.run(future)
     }
 }
 
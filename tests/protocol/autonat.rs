@@ -0,0 +1,144 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use litep2p::{
+    config::ConfigBuilder,
+    crypto::ed25519::Keypair,
+    protocol::libp2p::autonat::{
+        AutoNatEvent, ConfigBuilder as AutoNatConfigBuilder, Reachability,
+    },
+    transport::{
+        filter::{IpFilter, IpRange},
+        tcp::config::Config as TcpConfig,
+    },
+    Litep2p,
+};
+
+use futures::{Stream, StreamExt};
+use multiaddr::Multiaddr;
+
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+
+/// Create TCP config which listens on the loopback interface.
+fn tcp_config() -> TcpConfig {
+    TcpConfig {
+        listen_addresses: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+        ..Default::default()
+    }
+}
+
+/// Create node which answers dial requests of other peers and return its listen address.
+fn server() -> Multiaddr {
+    let (autonat_config, _event_stream) = AutoNatConfigBuilder::new()
+        .with_throttle_client_interval(Duration::from_secs(1))
+        .with_dial_back_timeout(Duration::from_secs(3))
+        .build();
+
+    // the dial-back must not reuse the listen port, otherwise it would have the same address
+    // and port pair as the connection the client opened from its own listen port
+    let mut litep2p = Litep2p::new(
+        ConfigBuilder::new()
+            .with_keypair(Keypair::generate())
+            .with_tcp(TcpConfig {
+                reuse_port: false,
+                ..tcp_config()
+            })
+            .with_libp2p_autonat(autonat_config)
+            .build(),
+    )
+    .unwrap();
+    let address = litep2p.listen_addresses().next().unwrap().clone();
+
+    tokio::spawn(async move { while litep2p.next_event().await.is_some() {} });
+
+    address
+}
+
+/// Connect `tcp_config` to three servers and return the first reachability status it reports.
+async fn probe(tcp_config: TcpConfig) -> Reachability {
+    let servers = (0..3).map(|_| server()).collect::<Vec<_>>();
+
+    let (autonat_config, mut event_stream) = AutoNatConfigBuilder::new()
+        .with_probe_interval(Duration::from_millis(500))
+        .with_throttle_server_interval(Duration::from_secs(10))
+        .with_confidence(2)
+        .build();
+
+    let mut litep2p = Litep2p::new(
+        ConfigBuilder::new()
+            .with_keypair(Keypair::generate())
+            .with_tcp(tcp_config)
+            .with_libp2p_autonat(autonat_config)
+            .build(),
+    )
+    .unwrap();
+
+    for address in servers {
+        litep2p.dial_address(address).await.unwrap();
+    }
+
+    next_reachability(&mut litep2p, &mut event_stream).await
+}
+
+/// Poll `litep2p` until AutoNAT reports a reachability status.
+async fn next_reachability(
+    litep2p: &mut Litep2p,
+    event_stream: &mut (impl Stream<Item = AutoNatEvent> + Unpin),
+) -> Reachability {
+    tokio::time::timeout(Duration::from_secs(20), async {
+        loop {
+            tokio::select! {
+                _ = litep2p.next_event() => {}
+                event = event_stream.next() => match event.unwrap() {
+                    AutoNatEvent::ReachabilityChanged { reachability } => return reachability,
+                },
+            }
+        }
+    })
+    .await
+    .expect("reachability to be determined")
+}
+
+#[tokio::test]
+async fn dialable_node_is_public() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    assert_eq!(probe(tcp_config()).await, Reachability::Public);
+}
+
+#[tokio::test]
+async fn firewalled_node_is_private() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    // reject all inbound connections, including the dial-backs of the servers
+    let tcp_config = TcpConfig {
+        connection_filter: Some(Arc::new(
+            IpFilter::new()
+                .with_denied(IpRange::new(Ipv4Addr::new(127, 0, 0, 0).into(), 8).unwrap()),
+        )),
+        ..tcp_config()
+    };
+
+    assert_eq!(probe(tcp_config).await, Reachability::Private);
+}
@@ -0,0 +1,133 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use litep2p::{
+    config::ConfigBuilder,
+    protocol::libp2p::{
+        dcutr::{Config as DcutrConfig, DcutrEvent},
+        relay::Config as RelayServerConfig,
+    },
+    transport::{memory::config::Config as MemoryConfig, relay::config::Config as RelayConfig},
+    Litep2p, Litep2pEvent,
+};
+
+use futures::StreamExt;
+use multiaddr::{Multiaddr, Protocol};
+
+use std::time::Duration;
+
+fn is_relayed(address: &Multiaddr) -> bool {
+    address.iter().any(|protocol| std::matches!(protocol, Protocol::P2pCircuit))
+}
+
+#[tokio::test]
+async fn relayed_connection_upgraded_to_direct_connection() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let mut relay = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_libp2p_relay(RelayServerConfig::default())
+            .build(),
+    )
+    .unwrap();
+    let relay_peer = *relay.local_peer_id();
+    let relay_address = relay.listen_addresses().next().unwrap().clone();
+
+    tokio::spawn(async move { while relay.next_event().await.is_some() {} });
+
+    // node which is only reachable through the relay
+    let (dcutr_config2, mut dcutr_event_stream2) = DcutrConfig::default();
+    let mut litep2p2 = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_relay(RelayConfig {
+                relays: vec![relay_address],
+                ..Default::default()
+            })
+            .with_libp2p_dcutr(dcutr_config2)
+            .build(),
+    )
+    .unwrap();
+    let peer2 = *litep2p2.local_peer_id();
+    let circuit_address =
+        litep2p2.listen_addresses().find(|address| is_relayed(address)).unwrap().clone();
+
+    // wait until the node is connected to the relay and give it time to make the reservation
+    loop {
+        if let Some(Litep2pEvent::ConnectionEstablished { peer, .. }) = litep2p2.next_event().await
+        {
+            if peer == relay_peer {
+                break;
+            }
+        }
+    }
+    let _ = tokio::time::timeout(Duration::from_secs(1), async {
+        while litep2p2.next_event().await.is_some() {}
+    })
+    .await;
+
+    let (dcutr_config1, mut dcutr_event_stream1) = DcutrConfig::default();
+    let mut litep2p1 = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_relay(RelayConfig::default())
+            .with_libp2p_dcutr(dcutr_config1)
+            .build(),
+    )
+    .unwrap();
+    let peer1 = *litep2p1.local_peer_id();
+
+    litep2p1.dial_address(circuit_address).await.unwrap();
+
+    // both nodes dial each other after exchanging their addresses over the relayed connection
+    // and report the direct connection once it's been established
+    tokio::time::timeout(Duration::from_secs(20), async {
+        let mut litep2p1_upgraded = false;
+        let mut litep2p2_upgraded = false;
+
+        while !(litep2p1_upgraded && litep2p2_upgraded) {
+            tokio::select! {
+                _ = litep2p1.next_event() => {}
+                _ = litep2p2.next_event() => {}
+                event = dcutr_event_stream1.next() => match event.unwrap() {
+                    DcutrEvent::DirectConnectionEstablished { peer, address } => {
+                        assert_eq!(peer, peer2);
+                        assert!(!is_relayed(&address));
+                        litep2p1_upgraded = true;
+                    }
+                    event => panic!("unexpected event: {event:?}"),
+                },
+                event = dcutr_event_stream2.next() => match event.unwrap() {
+                    DcutrEvent::DirectConnectionEstablished { peer, address } => {
+                        assert_eq!(peer, peer1);
+                        assert!(!is_relayed(&address));
+                        litep2p2_upgraded = true;
+                    }
+                    event => panic!("unexpected event: {event:?}"),
+                },
+            }
+        }
+    })
+    .await
+    .expect("relayed connection to be upgraded");
+}
@@ -18,6 +18,10 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+#[cfg(test)]
+mod autonat;
+#[cfg(test)]
+mod dcutr;
 #[cfg(test)]
 mod identify;
 #[cfg(test)]
@@ -27,4 +31,8 @@ mod notification;
 #[cfg(test)]
 mod ping;
 #[cfg(test)]
+mod pubsub;
+#[cfg(test)]
+mod relay;
+#[cfg(test)]
 mod request_response;
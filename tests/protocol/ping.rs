@@ -23,8 +23,8 @@ use litep2p::{
     config::ConfigBuilder,
     protocol::libp2p::ping::ConfigBuilder as PingConfigBuilder,
     transport::{
-        quic::config::Config as QuicConfig, tcp::config::Config as TcpConfig,
-        websocket::config::Config as WebSocketConfig,
+        memory::config::Config as MemoryConfig, quic::config::Config as QuicConfig,
+        tcp::config::Config as TcpConfig, websocket::config::Config as WebSocketConfig,
     },
     Litep2p,
 };
@@ -33,6 +33,7 @@ enum Transport {
     Tcp(TcpConfig),
     Quic(QuicConfig),
     WebSocket(WebSocketConfig),
+    Memory(MemoryConfig),
 }
 
 #[tokio::test]
@@ -62,6 +63,15 @@ async fn ping_supported_quic() {
     .await;
 }
 
+#[tokio::test]
+async fn ping_supported_memory() {
+    ping_supported(
+        Transport::Memory(Default::default()),
+        Transport::Memory(Default::default()),
+    )
+    .await;
+}
+
 async fn ping_supported(transport1: Transport, transport2: Transport) {
     let _ = tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
@@ -73,6 +83,7 @@ async fn ping_supported(transport1: Transport, transport2: Transport) {
         Transport::Tcp(config) => ConfigBuilder::new().with_tcp(config),
         Transport::Quic(config) => ConfigBuilder::new().with_quic(config),
         Transport::WebSocket(config) => ConfigBuilder::new().with_websocket(config),
+        Transport::Memory(config) => ConfigBuilder::new().with_memory(config),
     }
     .with_libp2p_ping(ping_config1)
     .build();
@@ -82,6 +93,7 @@ async fn ping_supported(transport1: Transport, transport2: Transport) {
         Transport::Tcp(config) => ConfigBuilder::new().with_tcp(config),
         Transport::Quic(config) => ConfigBuilder::new().with_quic(config),
         Transport::WebSocket(config) => ConfigBuilder::new().with_websocket(config),
+        Transport::Memory(config) => ConfigBuilder::new().with_memory(config),
     }
     .with_libp2p_ping(ping_config2)
     .build();
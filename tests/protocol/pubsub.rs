@@ -0,0 +1,120 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use litep2p::{
+    config::ConfigBuilder,
+    protocol::libp2p::pubsub::{
+        Config as PubsubConfig, MessageAuthenticity, PubsubEvent, PubsubHandle,
+    },
+    transport::memory::config::Config as MemoryConfig,
+    Litep2p,
+};
+
+use futures::StreamExt;
+use multiaddr::Multiaddr;
+
+use std::time::Duration;
+
+/// Create node which is subscribed to `topic`.
+async fn subscriber(topic: &str) -> (Litep2p, PubsubHandle) {
+    let (pubsub_config, handle) = PubsubConfig::new(MessageAuthenticity::Signed);
+    let litep2p = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_pubsub(pubsub_config)
+            .build(),
+    )
+    .unwrap();
+
+    handle.subscribe(topic).await;
+
+    (litep2p, handle)
+}
+
+/// Wait until `handle` has seen `count` peers subscribe to `topic`.
+async fn wait_for_subscriptions(handle: &mut PubsubHandle, topic: &str, count: usize) {
+    let mut subscribed = 0usize;
+
+    while subscribed < count {
+        match handle.next().await.unwrap() {
+            PubsubEvent::Subscribed {
+                topic: subscription,
+                ..
+            } => {
+                assert_eq!(subscription, topic);
+                subscribed += 1;
+            }
+            event => panic!("unexpected event: {event:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn published_message_reaches_all_subscribers_once() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let (mut litep2p1, mut handle1) = subscriber("topic").await;
+    let (mut litep2p2, mut handle2) = subscriber("topic").await;
+    let (litep2p3, mut handle3) = subscriber("topic").await;
+
+    let peer1 = *litep2p1.local_peer_id();
+    let address2: Multiaddr = litep2p2.listen_addresses().next().unwrap().clone();
+    let address3: Multiaddr = litep2p3.listen_addresses().next().unwrap().clone();
+
+    // connect the nodes into a triangle so every message has a redundant path
+    litep2p1.dial_address(address2).await.unwrap();
+    litep2p1.dial_address(address3.clone()).await.unwrap();
+    litep2p2.dial_address(address3).await.unwrap();
+
+    for mut litep2p in [litep2p1, litep2p2, litep2p3] {
+        tokio::spawn(async move { while litep2p.next_event().await.is_some() {} });
+    }
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        wait_for_subscriptions(&mut handle1, "topic", 2).await;
+        wait_for_subscriptions(&mut handle2, "topic", 2).await;
+        wait_for_subscriptions(&mut handle3, "topic", 2).await;
+    })
+    .await
+    .expect("subscriptions to be exchanged");
+
+    handle1.publish("topic", vec![1, 3, 3, 7]).await;
+
+    for handle in [&mut handle2, &mut handle3] {
+        match tokio::time::timeout(Duration::from_secs(10), handle.next()).await {
+            Ok(Some(PubsubEvent::Message { topic, from, data })) => {
+                assert_eq!(topic, "topic");
+                assert_eq!(from, Some(peer1));
+                assert_eq!(data, vec![1, 3, 3, 7]);
+            }
+            event => panic!("unexpected event: {event:?}"),
+        }
+    }
+
+    // give the message time to loop back through the triangle and verify that neither
+    // the subscribers nor the publisher receive it again
+    for handle in [&mut handle1, &mut handle2, &mut handle3] {
+        if let Ok(event) = tokio::time::timeout(Duration::from_secs(1), handle.next()).await {
+            panic!("unexpected event: {event:?}");
+        }
+    }
+}
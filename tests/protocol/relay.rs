@@ -0,0 +1,246 @@
+// Copyright 2023 litep2p developers
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use litep2p::{
+    config::ConfigBuilder,
+    protocol::{
+        libp2p::relay::ConfigBuilder as RelayServerConfigBuilder,
+        request_response::{
+            Config as RequestResponseConfig, DialOptions, RequestResponseEvent,
+            RequestResponseHandle,
+        },
+    },
+    transport::{memory::config::Config as MemoryConfig, relay::config::Config as RelayConfig},
+    types::protocol::ProtocolName,
+    Litep2p, Litep2pEvent, PeerId,
+};
+
+use futures::StreamExt;
+use multiaddr::{Multiaddr, Protocol};
+
+use std::time::Duration;
+
+/// Maximum number of bytes the relay forwards over a circuit in each direction.
+const MAX_CIRCUIT_BYTES: u64 = 16 * 1024;
+
+fn request_response_config() -> (RequestResponseConfig, RequestResponseHandle) {
+    RequestResponseConfig::new(
+        ProtocolName::from("/protocol/1"),
+        Vec::new(),
+        64 * 1024,
+        Duration::from_secs(5),
+        None,
+    )
+}
+
+/// Create relay server and a node holding a reservation with it.
+///
+/// Returns the node, its request-response handle and its relayed address.
+async fn reserve() -> (Litep2p, RequestResponseHandle, Multiaddr) {
+    let mut relay = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_libp2p_relay(
+                RelayServerConfigBuilder::new()
+                    .with_max_circuit_bytes(MAX_CIRCUIT_BYTES)
+                    .build(),
+            )
+            .build(),
+    )
+    .unwrap();
+    let relay_peer = *relay.local_peer_id();
+    let relay_address = relay.listen_addresses().next().unwrap().clone();
+
+    tokio::spawn(async move { while relay.next_event().await.is_some() {} });
+
+    let (req_resp_config, handle) = request_response_config();
+    let mut litep2p = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_relay(RelayConfig {
+                relays: vec![relay_address],
+                ..Default::default()
+            })
+            .with_request_response_protocol(req_resp_config)
+            .build(),
+    )
+    .unwrap();
+    let circuit_address = litep2p
+        .listen_addresses()
+        .find(|address| {
+            address.iter().any(|protocol| std::matches!(protocol, Protocol::P2pCircuit))
+        })
+        .unwrap()
+        .clone();
+
+    // wait until the node is connected to the relay and give it time to make the reservation
+    loop {
+        if let Some(Litep2pEvent::ConnectionEstablished { peer, .. }) = litep2p.next_event().await {
+            if peer == relay_peer {
+                break;
+            }
+        }
+    }
+    let _ = tokio::time::timeout(Duration::from_secs(1), async {
+        while litep2p.next_event().await.is_some() {}
+    })
+    .await;
+
+    (litep2p, handle, circuit_address)
+}
+
+/// Send `request` from `handle1` to `peer2` and wait until `handle2` has responded to it.
+async fn send_request(
+    litep2p1: &mut Litep2p,
+    handle1: &mut RequestResponseHandle,
+    litep2p2: &mut Litep2p,
+    handle2: &mut RequestResponseHandle,
+    peer2: PeerId,
+    request: Vec<u8>,
+) -> Vec<u8> {
+    handle1.send_request(peer2, request, DialOptions::Reject).await.unwrap();
+
+    loop {
+        tokio::select! {
+            _ = litep2p1.next_event() => {}
+            _ = litep2p2.next_event() => {}
+            event = handle2.next() => match event.unwrap() {
+                RequestResponseEvent::RequestReceived { request_id, request, .. } => {
+                    handle2.send_response(request_id, request);
+                }
+                event => panic!("unexpected event: {event:?}"),
+            },
+            event = handle1.next() => match event.unwrap() {
+                RequestResponseEvent::ResponseReceived { response, .. } => return response,
+                event => panic!("unexpected event: {event:?}"),
+            },
+        }
+    }
+}
+
+#[tokio::test]
+async fn relayed_circuit_closed_over_data_limit() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let (mut litep2p2, mut handle2, circuit_address) = reserve().await;
+    let peer2 = *litep2p2.local_peer_id();
+
+    let (req_resp_config, mut handle1) = request_response_config();
+    let mut litep2p1 = Litep2p::new(
+        ConfigBuilder::new()
+            .with_memory(MemoryConfig::default())
+            .with_relay(RelayConfig::default())
+            .with_request_response_protocol(req_resp_config)
+            .build(),
+    )
+    .unwrap();
+    let peer1 = *litep2p1.local_peer_id();
+
+    litep2p1.dial_address(circuit_address).await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        let mut litep2p1_connected = false;
+        let mut litep2p2_connected = false;
+
+        while !(litep2p1_connected && litep2p2_connected) {
+            tokio::select! {
+                event = litep2p1.next_event() => match event.unwrap() {
+                    Litep2pEvent::ConnectionEstablished { peer, .. } if peer == peer2 => {
+                        litep2p1_connected = true;
+                    }
+                    Litep2pEvent::DialFailure { address, error } =>
+                        panic!("failed to dial {address}: {error:?}"),
+                    _ => {}
+                },
+                event = litep2p2.next_event() => match event.unwrap() {
+                    Litep2pEvent::ConnectionEstablished { peer, .. } if peer == peer1 => {
+                        litep2p2_connected = true;
+                    }
+                    _ => {}
+                },
+            }
+        }
+    })
+    .await
+    .expect("relayed connection to be established");
+
+    // request within the data limit is relayed
+    let request = vec![1u8; 1024];
+    let response = tokio::time::timeout(
+        Duration::from_secs(10),
+        send_request(
+            &mut litep2p1,
+            &mut handle1,
+            &mut litep2p2,
+            &mut handle2,
+            peer2,
+            request.clone(),
+        ),
+    )
+    .await
+    .expect("response to be received");
+    assert_eq!(response, request);
+
+    // request exceeding the data limit closes the circuit
+    handle1
+        .send_request(
+            peer2,
+            vec![2u8; 2 * MAX_CIRCUIT_BYTES as usize],
+            DialOptions::Reject,
+        )
+        .await
+        .unwrap();
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        let mut litep2p1_closed = false;
+        let mut litep2p2_closed = false;
+
+        while !(litep2p1_closed && litep2p2_closed) {
+            tokio::select! {
+                event = litep2p1.next_event() => match event.unwrap() {
+                    Litep2pEvent::ConnectionClosed { peer, .. } if peer == peer2 => {
+                        litep2p1_closed = true;
+                    }
+                    _ => {}
+                },
+                event = litep2p2.next_event() => match event.unwrap() {
+                    Litep2pEvent::ConnectionClosed { peer, .. } if peer == peer1 => {
+                        litep2p2_closed = true;
+                    }
+                    _ => {}
+                },
+                event = handle1.next() => match event.unwrap() {
+                    RequestResponseEvent::ResponseReceived { .. } =>
+                        panic!("request over the data limit was relayed"),
+                    _ => {}
+                },
+                event = handle2.next() => match event.unwrap() {
+                    RequestResponseEvent::RequestReceived { .. } =>
+                        panic!("request over the data limit was relayed"),
+                    _ => {}
+                },
+            }
+        }
+    })
+    .await
+    .expect("circuit to be closed");
+}
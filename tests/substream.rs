@@ -31,7 +31,7 @@ use litep2p::{
     Error, Litep2p, Litep2pEvent, PeerId,
 };
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures::{Sink, SinkExt, StreamExt};
 use tokio::{
     io::AsyncWrite,
@@ -42,10 +42,11 @@ use tokio::{
 };
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     io::ErrorKind,
     sync::Arc,
-    task::Poll,
+    task::{Context, Poll},
+    time::Duration,
 };
 
 enum Transport {
@@ -213,6 +214,125 @@ impl UserProtocol for CustomProtocol {
     }
 }
 
+/// Protocol which drives its substreams purely through the poll-based API of [`Substream`].
+struct PollProtocol {
+    protocol: ProtocolName,
+
+    /// RX channel for receiving frames which are sent to a peer over a new substream.
+    rx: Receiver<(PeerId, Vec<Bytes>)>,
+
+    /// TX channel for reporting frames received over inbound substreams.
+    ///
+    /// `None` is reported when an inbound substream is closed.
+    tx: Sender<Option<BytesMut>>,
+}
+
+impl PollProtocol {
+    pub fn new() -> (
+        Self,
+        Sender<(PeerId, Vec<Bytes>)>,
+        Receiver<Option<BytesMut>>,
+    ) {
+        let (cmd_tx, cmd_rx) = channel(64);
+        let (event_tx, event_rx) = channel(64);
+
+        (
+            Self {
+                protocol: ProtocolName::from("/poll-protocol/1"),
+                rx: cmd_rx,
+                tx: event_tx,
+            },
+            cmd_tx,
+            event_rx,
+        )
+    }
+}
+
+/// Read frames from `inbound` and write the pending frames to `outbound` until the substreams
+/// are blocked.
+fn poll_substreams(
+    cx: &mut Context<'_>,
+    inbound: &mut Vec<Substream>,
+    outbound: &mut Vec<(Substream, VecDeque<Bytes>)>,
+    tx: &Sender<Option<BytesMut>>,
+) -> Poll<()> {
+    inbound.retain_mut(|substream| loop {
+        match substream.poll_read(cx) {
+            Poll::Pending => return true,
+            Poll::Ready(Some(Ok(frame))) => tx.try_send(Some(frame)).unwrap(),
+            Poll::Ready(Some(Err(_)) | None) => {
+                tx.try_send(None).unwrap();
+                return false;
+            }
+        }
+    });
+
+    outbound.retain_mut(|(substream, frames)| loop {
+        match frames.front() {
+            Some(frame) => match substream.poll_write(cx, frame.clone()) {
+                Poll::Pending => return true,
+                Poll::Ready(Ok(())) => {
+                    frames.pop_front();
+                }
+                Poll::Ready(Err(error)) => panic!("failed to write frame: {error:?}"),
+            },
+            None => match substream.poll_close(cx) {
+                Poll::Pending => return true,
+                Poll::Ready(result) => {
+                    result.unwrap();
+                    return false;
+                }
+            },
+        }
+    });
+
+    Poll::Pending
+}
+
+#[async_trait::async_trait]
+impl UserProtocol for PollProtocol {
+    fn protocol(&self) -> ProtocolName {
+        self.protocol.clone()
+    }
+
+    fn codec(&self) -> ProtocolCodec {
+        ProtocolCodec::UnsignedVarint(None)
+    }
+
+    async fn run(mut self: Box<Self>, mut service: TransportService) -> litep2p::Result<()> {
+        let mut pending_opens = HashMap::<SubstreamId, VecDeque<Bytes>>::new();
+        let mut inbound = Vec::new();
+        let mut outbound = Vec::new();
+
+        loop {
+            tokio::select! {
+                event = service.next() => match event.unwrap() {
+                    TransportEvent::SubstreamOpened {
+                        substream,
+                        direction,
+                        ..
+                    } => match direction {
+                        Direction::Inbound => inbound.push(substream),
+                        Direction::Outbound(substream_id) => {
+                            let frames = pending_opens.remove(&substream_id).unwrap();
+                            outbound.push((substream, frames));
+                        }
+                    },
+                    _ => {}
+                },
+                command = self.rx.recv() => {
+                    let (peer, frames) = command.unwrap();
+                    let substream_id = service.open_substream(peer).unwrap();
+                    pending_opens.insert(substream_id, frames.into());
+                }
+                _ = futures::future::poll_fn(|cx| {
+                    poll_substreams(cx, &mut inbound, &mut outbound, &self.tx)
+                }) => {}
+            }
+        }
+    }
+}
+
 async fn connect_peers(litep2p1: &mut Litep2p, litep2p2: &mut Litep2p) {
     let listen_address = litep2p1.listen_addresses().next().unwrap().clone();
     litep2p2.dial_address(listen_address).await.unwrap();
@@ -575,3 +695,159 @@ async fn correct_payload_size_async_write(transport1: Transport, transport2: Tra
         event => panic!("invalid event received: {event:?}"),
     }
 }
+
+#[tokio::test]
+async fn bandwidth_is_metered_per_peer_tcp() {
+    bandwidth_is_metered_per_peer(
+        Transport::Tcp(Default::default()),
+        Transport::Tcp(Default::default()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn bandwidth_is_metered_per_peer_quic() {
+    bandwidth_is_metered_per_peer(
+        Transport::Quic(Default::default()),
+        Transport::Quic(Default::default()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn bandwidth_is_metered_per_peer_websocket() {
+    bandwidth_is_metered_per_peer(
+        Transport::WebSocket(Default::default()),
+        Transport::WebSocket(Default::default()),
+    )
+    .await;
+}
+
+async fn bandwidth_is_metered_per_peer(transport1: Transport, transport2: Transport) {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let (custom_protocol1, tx1) = CustomProtocol::new(ProtocolCodec::Identity(1024usize));
+    let config1 = match transport1 {
+        Transport::Tcp(config) => ConfigBuilder::new().with_tcp(config),
+        Transport::Quic(config) => ConfigBuilder::new().with_quic(config),
+        Transport::WebSocket(config) => ConfigBuilder::new().with_websocket(config),
+    }
+    .with_user_protocol(Box::new(custom_protocol1))
+    .build();
+
+    let (custom_protocol2, _tx2) = CustomProtocol::new(ProtocolCodec::Identity(1024usize));
+    let config2 = match transport2 {
+        Transport::Tcp(config) => ConfigBuilder::new().with_tcp(config),
+        Transport::Quic(config) => ConfigBuilder::new().with_quic(config),
+        Transport::WebSocket(config) => ConfigBuilder::new().with_websocket(config),
+    }
+    .with_user_protocol(Box::new(custom_protocol2))
+    .build();
+
+    let mut litep2p1 = Litep2p::new(config1).unwrap();
+    let mut litep2p2 = Litep2p::new(config2).unwrap();
+    let peer1 = *litep2p1.local_peer_id();
+    let peer2 = *litep2p2.local_peer_id();
+    let sink1 = litep2p1.bandwidth_sink();
+    let sink2 = litep2p2.bandwidth_sink();
+
+    // connect peers and start event loops for litep2ps
+    connect_peers(&mut litep2p1, &mut litep2p2).await;
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _event = litep2p1.next_event() => {}
+                _event = litep2p2.next_event() => {}
+            }
+        }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+
+    let (tx, rx) = oneshot::channel();
+    tx1.send(Command::OpenSubstream(peer2, tx)).await.unwrap();
+
+    let Ok(()) = rx.await else {
+        panic!("failed to open substream");
+    };
+
+    let (_, outbound_before) = sink1.peer_bandwidth(&peer2);
+    let (inbound_before, _) = sink2.peer_bandwidth(&peer1);
+
+    let (tx, rx) = oneshot::channel();
+    tx1.send(Command::SendPayloadSink(peer2, vec![0u8; 1024], tx)).await.unwrap();
+
+    match rx.await {
+        Ok(Ok(())) => {}
+        event => panic!("invalid event received: {event:?}"),
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    // the counters include the framing of the connection so they're at least the payload size
+    let (_, outbound_after) = sink1.peer_bandwidth(&peer2);
+    let (inbound_after, _) = sink2.peer_bandwidth(&peer1);
+    assert!(outbound_after - outbound_before >= 1024);
+    assert!(inbound_after - inbound_before >= 1024);
+
+    // with only one connected peer, the totals match the per-peer counters
+    assert_eq!(sink1.connection_bandwidth(), sink1.peer_bandwidth(&peer2));
+    assert_eq!(sink2.connection_bandwidth(), sink2.peer_bandwidth(&peer1));
+}
+
+#[tokio::test]
+async fn substream_driven_through_poll_api() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let (poll_protocol1, tx1, _rx1) = PollProtocol::new();
+    let config1 = ConfigBuilder::new()
+        .with_tcp(Default::default())
+        .with_user_protocol(Box::new(poll_protocol1))
+        .build();
+
+    let (poll_protocol2, _tx2, mut rx2) = PollProtocol::new();
+    let config2 = ConfigBuilder::new()
+        .with_tcp(Default::default())
+        .with_user_protocol(Box::new(poll_protocol2))
+        .build();
+
+    let mut litep2p1 = Litep2p::new(config1).unwrap();
+    let mut litep2p2 = Litep2p::new(config2).unwrap();
+    let peer2 = *litep2p2.local_peer_id();
+
+    // connect peers and start event loops for litep2ps
+    connect_peers(&mut litep2p1, &mut litep2p2).await;
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _event = litep2p1.next_event() => {}
+                _event = litep2p2.next_event() => {}
+            }
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+
+    // send two frames and close the substream, all through `Substream::poll_*()`
+    tx1.send((
+        peer2,
+        vec![Bytes::from_static(b"hello"), Bytes::from_static(b"world")],
+    ))
+    .await
+    .unwrap();
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        assert_eq!(
+            rx2.recv().await.unwrap(),
+            Some(BytesMut::from(&b"hello"[..]))
+        );
+        assert_eq!(
+            rx2.recv().await.unwrap(),
+            Some(BytesMut::from(&b"world"[..]))
+        );
+        assert_eq!(rx2.recv().await.unwrap(), None);
+    })
+    .await
+    .expect("frames to be received");
+}
@@ -32,7 +32,7 @@ use futures::StreamExt;
 use multiaddr::Multiaddr;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
-use std::collections::HashSet;
+use std::{collections::HashSet, time::Duration};
 
 struct CustomProtocol {
     protocol: ProtocolName,
@@ -87,6 +87,7 @@ impl UserProtocol for CustomProtocol {
                         error: _,
                     } => {}
                     TransportEvent::DialFailure { .. } => {}
+                    _ => {}
                 },
                 address = self.rx.recv() => {
                     service.dial_address(address.unwrap()).unwrap();
@@ -96,6 +97,55 @@ impl UserProtocol for CustomProtocol {
     }
 }
 
+/// Protocol which closes the connection to every peer that connects to it.
+struct ClosingProtocol {
+    protocol: ProtocolName,
+    codec: ProtocolCodec,
+    tx: Sender<PeerId>,
+}
+
+impl ClosingProtocol {
+    pub fn new() -> (Self, Receiver<PeerId>) {
+        let (tx, rx) = channel(64);
+
+        (
+            Self {
+                tx,
+                protocol: ProtocolName::from("/custom-protocol/1"),
+                codec: ProtocolCodec::UnsignedVarint(None),
+            },
+            rx,
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl UserProtocol for ClosingProtocol {
+    fn protocol(&self) -> ProtocolName {
+        self.protocol.clone()
+    }
+
+    fn codec(&self) -> ProtocolCodec {
+        self.codec
+    }
+
+    async fn run(self: Box<Self>, mut service: TransportService) -> litep2p::Result<()> {
+        while let Some(event) = service.next().await {
+            match event {
+                TransportEvent::ConnectionEstablished { peer, .. } => {
+                    service.force_close(peer).unwrap();
+                }
+                TransportEvent::ConnectionClosed { peer } => {
+                    let _ = self.tx.send(peer).await;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[tokio::test]
 async fn user_protocol_2() {
     let _ = tracing_subscriber::fmt()
@@ -146,3 +196,67 @@ async fn user_protocol_2() {
         }
     }
 }
+
+#[tokio::test]
+async fn protocol_closes_connection() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init();
+
+    let (closing_protocol, mut closed_rx) = ClosingProtocol::new();
+    let config1 = ConfigBuilder::new()
+        .with_keypair(Keypair::generate())
+        .with_tcp(TcpConfig {
+            ..Default::default()
+        })
+        .with_user_protocol(Box::new(closing_protocol))
+        .build();
+
+    let (custom_protocol2, _sender2) = CustomProtocol::new();
+    let config2 = ConfigBuilder::new()
+        .with_keypair(Keypair::generate())
+        .with_tcp(TcpConfig {
+            ..Default::default()
+        })
+        .with_user_protocol(Box::new(custom_protocol2))
+        .build();
+
+    let mut litep2p1 = Litep2p::new(config1).unwrap();
+    let mut litep2p2 = Litep2p::new(config2).unwrap();
+    let peer2 = *litep2p2.local_peer_id();
+    let address = litep2p2.listen_addresses().next().unwrap().clone();
+
+    litep2p1.dial_address(address).await.unwrap();
+
+    let mut litep2p1_closed = false;
+    let mut litep2p2_closed = false;
+    let mut protocol_closed = false;
+
+    let future = async {
+        while !litep2p1_closed || !litep2p2_closed || !protocol_closed {
+            tokio::select! {
+                event = litep2p1.next_event() => match event.unwrap() {
+                    Litep2pEvent::ConnectionClosed { peer, .. } => {
+                        assert_eq!(peer, peer2);
+                        litep2p1_closed = true;
+                    }
+                    _ => {}
+                },
+                event = litep2p2.next_event() => match event.unwrap() {
+                    Litep2pEvent::ConnectionClosed { .. } => {
+                        litep2p2_closed = true;
+                    }
+                    _ => {}
+                },
+                peer = closed_rx.recv() => {
+                    assert_eq!(peer, Some(peer2));
+                    protocol_closed = true;
+                }
+            }
+        }
+    };
+
+    tokio::time::timeout(Duration::from_secs(10), future)
+        .await
+        .expect("connection to be closed");
+}